@@ -0,0 +1,367 @@
+//! Removing patient identity from DICOM files for `dcmv anonymize`
+//!
+//! Attributes that name the patient, staff, institution or device are
+//! emptied or removed, as are private attributes, whose contents are
+//! unknown, and every UID that is not defined by the standard
+//! is replaced through a [`UidMap`]. The map gives the same replacement for
+//! the same UID in every file of a run, so the anonymized files still form
+//! the same studies and series and their references still resolve. Saved
+//! to a file, it carries that over to later runs.
+//!
+//! Text burned into the pixels is not removed: `--redact` blacks it out.
+
+use crate::dicom::{self, DicomObject, SaveOptions, new_uid, save};
+use crate::stack;
+use ::dicom::core::header::Header;
+use ::dicom::core::value::{PrimitiveValue, Value};
+use ::dicom::core::{DataElement, Tag, VR};
+use ::dicom::dictionary_std::tags;
+use ::dicom::object::InMemDicomObject;
+use anyhow::{Context, Result, bail};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Root of the UIDs defined by the DICOM standard (SOP classes, transfer
+/// syntaxes, coding schemes), which identify nobody and are kept
+const DICOM_ROOT: &str = "1.2.840.10008.";
+
+/// Attributes emptied, as modules require them to be present
+const EMPTIED: [Tag; 7] = [
+    tags::PATIENT_NAME,
+    tags::PATIENT_ID,
+    tags::PATIENT_BIRTH_DATE,
+    tags::PATIENT_SEX,
+    tags::REFERRING_PHYSICIAN_NAME,
+    tags::ACCESSION_NUMBER,
+    tags::STUDY_ID,
+];
+
+/// Attributes removed, including the icon, a thumbnail of the pixels that
+/// may show burned-in text
+#[allow(deprecated)] // Other Patient IDs and Medical Record Locator are retired but still found
+const REMOVED: [Tag; 21] = [
+    tags::PATIENT_BIRTH_TIME,
+    tags::OTHER_PATIENT_I_DS,
+    tags::OTHER_PATIENT_I_DS_SEQUENCE,
+    tags::OTHER_PATIENT_NAMES,
+    tags::PATIENT_BIRTH_NAME,
+    tags::PATIENT_MOTHER_BIRTH_NAME,
+    tags::PATIENT_ADDRESS,
+    tags::PATIENT_TELEPHONE_NUMBERS,
+    tags::MEDICAL_RECORD_LOCATOR,
+    tags::ISSUER_OF_PATIENT_ID,
+    tags::INSTITUTION_NAME,
+    tags::INSTITUTION_ADDRESS,
+    tags::INSTITUTIONAL_DEPARTMENT_NAME,
+    tags::STATION_NAME,
+    tags::DEVICE_SERIAL_NUMBER,
+    tags::PERFORMING_PHYSICIAN_NAME,
+    tags::PHYSICIANS_OF_RECORD,
+    tags::NAME_OF_PHYSICIANS_READING_STUDY,
+    tags::OPERATORS_NAME,
+    tags::REQUESTING_PHYSICIAN,
+    tags::ICON_IMAGE_SEQUENCE,
+];
+
+/// Replacements for the UIDs of the original files
+///
+/// Each UID is replaced by a new UID under the 2.25 root the first time it
+/// is seen, and by the same UID after that.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct UidMap {
+    uids: BTreeMap<String, String>,
+}
+
+impl UidMap {
+    /// Map saved by [`UidMap::save`], or an empty map if `path` does not
+    /// exist yet
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read or is not a JSON object
+    /// of UIDs
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let uids = serde_json::from_str(&text)
+            .with_context(|| format!("{} is not a UID map", path.display()))?;
+        Ok(Self { uids })
+    }
+
+    /// Write the map as a JSON object from original to new UIDs
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be written
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let text = serde_json::to_string_pretty(&self.uids)?;
+        std::fs::write(path, text + "\n")
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    /// Replacement of `uid`, made the first time it is asked for
+    pub fn map(&mut self, uid: &str) -> String {
+        self.uids
+            .entry(uid.to_string())
+            .or_insert_with(new_uid)
+            .clone()
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.uids.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.uids.is_empty()
+    }
+}
+
+/// Anonymize the DICOM files among `paths` into `dir`
+///
+/// Directories stand for the files directly in them. Files are named after
+/// their new SOP Instance UID, since the original names may identify the
+/// patient. With `uid_map`, UIDs replaced in earlier runs are replaced the
+/// same way, and the map is saved with the new ones. Files whose Burned
+/// In Annotation is YES are passed to `warn`, as they still show the
+/// patient's identity, as are files skipped because they cannot be read
+/// as DICOM. Returns the files written.
+///
+/// # Errors
+///
+/// Returns an error if there are no DICOM files, or a file or the map
+/// cannot be written. The map is saved even when a file fails, as the files
+/// written before it use its UIDs.
+pub fn anonymize(
+    paths: &[PathBuf],
+    dir: &Path,
+    uid_map: Option<&Path>,
+    mut warn: impl FnMut(&str),
+) -> Result<Vec<PathBuf>> {
+    let mut map = uid_map.map_or_else(|| Ok(UidMap::default()), UidMap::load)?;
+    std::fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+
+    let written = anonymize_files(paths, dir, &mut map, &mut warn);
+    let saved = uid_map.map_or(Ok(()), |path| map.save(path));
+    let written = written?;
+    saved?;
+    Ok(written)
+}
+
+fn anonymize_files(
+    paths: &[PathBuf],
+    dir: &Path,
+    map: &mut UidMap,
+    warn: &mut impl FnMut(&str),
+) -> Result<Vec<PathBuf>> {
+    let mut written = Vec::new();
+    for path in stack::expand_directories(paths)? {
+        let mut obj = match dicom::open_dicom_file(&path) {
+            Ok(obj) => obj,
+            Err(e) => {
+                warn(&format!("Skipped {}: {e:#}", path.display()));
+                continue;
+            }
+        };
+        let burned_in = obj
+            .element(tags::BURNED_IN_ANNOTATION)
+            .ok()
+            .and_then(|element| element.to_str().ok())
+            .is_some_and(|value| value.trim() == "YES");
+        if burned_in {
+            warn(&format!(
+                "{}: Burned In Annotation is YES, the pixels may show identifying text",
+                path.display()
+            ));
+        }
+        anonymize_object(&mut obj, map);
+
+        let uid = obj
+            .element(tags::SOP_INSTANCE_UID)
+            .ok()
+            .and_then(|element| element.to_str().ok())
+            .map_or_else(new_uid, |uid| uid.trim_end_matches('\0').to_string());
+        let output = dir.join(format!("{uid}.dcm"));
+        save(&mut obj, &output, SaveOptions::default())?;
+        written.push(output);
+    }
+    if written.is_empty() {
+        bail!("No DICOM files to anonymize");
+    }
+    Ok(written)
+}
+
+/// Remove the identity of the patient from `obj`, replacing its UIDs
+/// through `map`
+pub fn anonymize_object(obj: &mut DicomObject, map: &mut UidMap) {
+    for tag in EMPTIED {
+        if let Ok(element) = obj.element(tag) {
+            let vr = element.vr();
+            obj.put(DataElement::new(tag, vr, PrimitiveValue::Empty));
+        }
+    }
+    for tag in REMOVED {
+        obj.remove_element(tag);
+    }
+    remove_private(obj);
+    remap_uids(obj, map);
+
+    obj.put(DataElement::new(
+        tags::PATIENT_IDENTITY_REMOVED,
+        VR::CS,
+        "YES",
+    ));
+    obj.put(DataElement::new(
+        tags::DEIDENTIFICATION_METHOD,
+        VR::LO,
+        "dcmv: identifiers removed, UIDs replaced",
+    ));
+}
+
+/// Remove the private attributes of `obj` and of the items of its sequences
+fn remove_private(obj: &mut InMemDicomObject) {
+    obj.retain(|element| element.tag().group() % 2 == 0);
+
+    let sequences: Vec<Tag> = obj
+        .iter()
+        .filter(|element| element.vr() == VR::SQ)
+        .map(|element| element.tag())
+        .collect();
+    for tag in sequences {
+        obj.update_value(tag, |value| {
+            if let Value::Sequence(sequence) = value {
+                sequence.items_mut().iter_mut().for_each(remove_private);
+            }
+        });
+    }
+}
+
+/// Replace the UIDs of `obj` and of the items of its sequences
+fn remap_uids(obj: &mut InMemDicomObject, map: &mut UidMap) {
+    let elements: Vec<(Tag, VR)> = obj
+        .iter()
+        .map(|element| (element.tag(), element.vr()))
+        .collect();
+
+    for (tag, vr) in elements {
+        match vr {
+            VR::UI => {
+                obj.update_value(tag, |value| {
+                    let Some(uids) = value.to_multi_str().ok() else {
+                        return;
+                    };
+                    let uids: Vec<String> = uids
+                        .iter()
+                        .map(|uid| uid.trim_end_matches(['\0', ' ']))
+                        .map(|uid| {
+                            if uid.is_empty() || uid.starts_with(DICOM_ROOT) {
+                                uid.to_string()
+                            } else {
+                                map.map(uid)
+                            }
+                        })
+                        .collect();
+                    *value = PrimitiveValue::Strs(uids.into()).into();
+                });
+            }
+            VR::SQ => {
+                obj.update_value(tag, |value| {
+                    if let Value::Sequence(sequence) = value {
+                        for item in sequence.items_mut() {
+                            remap_uids(item, map);
+                        }
+                    }
+                });
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestDataset;
+    use ::dicom::core::value::DataSetSequence;
+    use dicom_dictionary_std::uids;
+
+    #[test]
+    fn test_uids_are_replaced_alike_across_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let (input, output) = (dir.path().join("in"), dir.path().join("out"));
+        std::fs::create_dir(&input).unwrap();
+
+        for instance in ["1.2.3.1", "1.2.3.2"] {
+            let reference = InMemDicomObject::from_element_iter([DataElement::new(
+                tags::REFERENCED_SOP_INSTANCE_UID,
+                VR::UI,
+                "1.2.3.1",
+            )]);
+            TestDataset::new(instance)
+                .with(tags::SOP_CLASS_UID, VR::UI, uids::CT_IMAGE_STORAGE)
+                .with(tags::STUDY_INSTANCE_UID, VR::UI, "1.2.3")
+                .with(tags::PATIENT_NAME, VR::PN, "Doe^Jane")
+                .with(tags::INSTITUTION_NAME, VR::LO, "General")
+                .with(tags::BURNED_IN_ANNOTATION, VR::CS, "YES")
+                .with(
+                    tags::ICON_IMAGE_SEQUENCE,
+                    VR::SQ,
+                    DataSetSequence::from(vec![InMemDicomObject::new_empty()]),
+                )
+                .with(Tag(0x0029, 0x0010), VR::LO, "VENDOR")
+                .with(Tag(0x0029, 0x1010), VR::LO, "Doe^Jane")
+                .with(
+                    tags::REFERENCED_IMAGE_SEQUENCE,
+                    VR::SQ,
+                    DataSetSequence::from(vec![reference]),
+                )
+                .write(&input.join(format!("{instance}.dcm")));
+        }
+        std::fs::write(input.join("notes.txt"), "not DICOM").unwrap();
+
+        let map_path = dir.path().join("uids.json");
+        let mut warnings = Vec::new();
+        let files = anonymize(&[input], &output, Some(&map_path), |warning| {
+            warnings.push(warning.to_string());
+        })
+        .unwrap();
+        assert_eq!(files.len(), 2);
+        // Two with burned-in annotation, and the text file
+        assert_eq!(warnings.len(), 3);
+
+        let map = UidMap::load(&map_path).unwrap();
+        assert_eq!(map.len(), 3);
+        let mut map_again = map.clone();
+        let first_uid = map_again.map("1.2.3.1");
+
+        let text =
+            |obj: &DicomObject, tag| obj.element(tag).unwrap().to_str().unwrap().into_owned();
+        for file in &files {
+            let obj = dicom::open_dicom_file(file).unwrap();
+            assert_eq!(text(&obj, tags::STUDY_INSTANCE_UID), map_again.map("1.2.3"));
+            assert_eq!(text(&obj, tags::SOP_CLASS_UID), uids::CT_IMAGE_STORAGE);
+            assert_eq!(text(&obj, tags::PATIENT_NAME), "");
+            assert!(obj.element(tags::INSTITUTION_NAME).is_err());
+            assert!(obj.element(Tag(0x0029, 0x1010)).is_err());
+            assert!(obj.element(tags::ICON_IMAGE_SEQUENCE).is_err());
+            let reference = &obj
+                .element(tags::REFERENCED_IMAGE_SEQUENCE)
+                .unwrap()
+                .items()
+                .unwrap()[0];
+            assert_eq!(
+                reference
+                    .element(tags::REFERENCED_SOP_INSTANCE_UID)
+                    .unwrap()
+                    .to_str()
+                    .unwrap(),
+                first_uid
+            );
+        }
+        assert_eq!(map_again, map);
+    }
+}
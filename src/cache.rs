@@ -0,0 +1,204 @@
+//! On-disk cache of rendered images for `--cache`
+//!
+//! Rendered 8-bit images are stored as PNG files in the user cache
+//! directory, keyed by SOP Instance UID, the size and modification time of
+//! the file, and the render options. Showing a cached image needs only the
+//! file header, so large or compressed files that were seen before are
+//! displayed without decoding their pixel data.
+
+use crate::dicom::DicomMetadata;
+use crate::render::RenderOptions;
+use crate::types::FileStamp;
+use anyhow::{Context, Result};
+use image::{DynamicImage, ImageFormat};
+use sha2::{Digest, Sha256};
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+/// Rendered images cached by SOP Instance UID, file stamp and render options
+#[derive(Debug, Clone)]
+pub struct RenderCache {
+    directory: PathBuf,
+}
+
+impl RenderCache {
+    /// Cache in `$XDG_CACHE_HOME/dcmv`, or `~/.cache/dcmv`
+    ///
+    /// Returns `None` if neither variable is set.
+    #[must_use]
+    pub fn open() -> Option<Self> {
+        let base = env::var_os("XDG_CACHE_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))?;
+
+        Some(Self::in_directory(base.join("dcmv").join("renders")))
+    }
+
+    #[must_use]
+    pub fn in_directory(directory: impl Into<PathBuf>) -> Self {
+        Self {
+            directory: directory.into(),
+        }
+    }
+
+    /// The cached image of `metadata` rendered with `options`, if any
+    ///
+    /// Only the tags of `metadata` are used, so it may come from the file
+    /// header alone.
+    #[must_use]
+    pub fn get(&self, metadata: &DicomMetadata, options: &RenderOptions) -> Option<DynamicImage> {
+        let path = self.path(metadata, options)?;
+        image::open(path).ok()
+    }
+
+    /// Whether an image of `metadata` rendered with `options` is cached
+    #[must_use]
+    pub fn contains(&self, metadata: &DicomMetadata, options: &RenderOptions) -> bool {
+        self.path(metadata, options)
+            .is_some_and(|path| path.is_file())
+    }
+
+    /// Store the image of `metadata` rendered with `options`
+    ///
+    /// Objects without a SOP Instance UID, or not read from a file, are not
+    /// cached.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the cache file cannot be written
+    pub fn put(
+        &self,
+        metadata: &DicomMetadata,
+        options: &RenderOptions,
+        image: &DynamicImage,
+    ) -> Result<()> {
+        let Some(path) = self.path(metadata, options) else {
+            return Ok(());
+        };
+
+        fs::create_dir_all(&self.directory).with_context(|| {
+            format!(
+                "Failed to create cache directory {}",
+                self.directory.display()
+            )
+        })?;
+
+        // Written under a temporary name first, so that concurrent readers
+        // never see a partial file
+        let partial = path.with_extension(format!("{}.tmp", std::process::id()));
+        image
+            .save_with_format(&partial, ImageFormat::Png)
+            .and_then(|()| fs::rename(&partial, &path).map_err(Into::into))
+            .inspect_err(|_| {
+                let _ = fs::remove_file(&partial);
+            })
+            .with_context(|| format!("Failed to write cache file {}", path.display()))
+    }
+
+    fn path(&self, metadata: &DicomMetadata, options: &RenderOptions) -> Option<PathBuf> {
+        let key = cache_key(
+            metadata.sop_instance_uid.as_deref()?,
+            metadata.file_stamp?,
+            options,
+        );
+        Some(self.directory.join(format!("{key}.png")))
+    }
+}
+
+/// Key of a rendered image, as hex digits of a SHA-256
+///
+/// It changes with the dcmv version, as rendering itself may change, and
+/// when the file is rewritten under the same instance UID.
+fn cache_key(sop_instance_uid: &str, stamp: FileStamp, options: &RenderOptions) -> String {
+    // Destructured so that a new option cannot be left out of the key
+    let RenderOptions {
+        orientation,
+        annotations,
+        graphic_annotations,
+        caption,
+        window,
+        tone,
+        invert,
+        frame,
+        subtract_frame,
+        colormap,
+        filter,
+        size,
+    } = options;
+
+    let fields = [
+        format!("version={}", env!("CARGO_PKG_VERSION")),
+        format!("instance={sop_instance_uid}"),
+        format!("size={}", stamp.size),
+        format!("modified={}", stamp.modified),
+        format!(
+            "orientation={:?}",
+            orientation.map(|t| [t.transpose, t.flip_h, t.flip_v])
+        ),
+        format!("annotations={annotations}"),
+        format!("graphic_annotations={graphic_annotations}"),
+        format!("caption={caption:?}"),
+        format!(
+            "window={:?}",
+            window.map(|w| (w.center, w.width, w.function))
+        ),
+        format!(
+            "tone={:?}",
+            tone.map(|t| (t.gamma, t.brightness, t.contrast, t.gsdf))
+        ),
+        format!("invert={invert}"),
+        format!("frame={frame}"),
+        format!("subtract_frame={subtract_frame:?}"),
+        format!("colormap={colormap:?}"),
+        format!("filter={filter:?}"),
+        format!("size={size:?}"),
+    ];
+
+    format!("{:x}", Sha256::digest(fields.join("\n")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::GrayImage;
+
+    #[test]
+    fn test_cache_is_keyed_by_instance_and_options() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = RenderCache::in_directory(dir.path().join("renders"));
+
+        let mut metadata = DicomMetadata::test_grayscale16(1, 2, &[0, 100]);
+        let options = RenderOptions::default();
+        let image = DynamicImage::ImageLuma8(GrayImage::from_raw(2, 1, vec![0, 255]).unwrap());
+
+        // Nothing is cached without an instance UID
+        metadata.file_stamp = Some(FileStamp {
+            size: 1000,
+            modified: 1,
+        });
+        cache.put(&metadata, &options, &image).unwrap();
+        assert!(!dir.path().join("renders").exists());
+
+        metadata.sop_instance_uid = Some("1.2.3".to_string());
+        cache.put(&metadata, &options, &image).unwrap();
+        assert_eq!(cache.get(&metadata, &options), Some(image));
+
+        // A file rewritten under the same UID is rendered again
+        let rewritten = DicomMetadata {
+            file_stamp: Some(FileStamp {
+                size: 1000,
+                modified: 2,
+            }),
+            ..metadata.clone()
+        };
+        assert!(cache.get(&rewritten, &options).is_none());
+
+        let annotated = RenderOptions {
+            annotations: true,
+            ..options
+        };
+        assert!(cache.get(&metadata, &annotated).is_none());
+    }
+}
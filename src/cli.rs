@@ -1,10 +1,25 @@
-use clap::Parser;
-use std::path::PathBuf;
+use crate::color::{self, Palette};
+use crate::datetime::DateStyle;
+use crate::dicom::PhotometricInterpretation;
+use crate::display_metadata::{MetadataFormat, MetadataGroup};
+use crate::fusion;
+use crate::image::{CaptionPlacement, Filter, GSDF_MIN_LUMINANCE, ToneCurve, Transform};
+use crate::layout::Protocol;
+use crate::paths;
+use crate::terminal::{Stream, TerminalContext};
+use crate::types::ByteOrder;
+use ::image::imageops::FilterType;
+use clap::{ArgAction, Parser, Subcommand, ValueEnum};
+use std::path::{Path, PathBuf};
 
 /// A terminal-based DICOM image viewer
-#[derive(Parser, Debug, Clone)]
+#[derive(Parser, Debug, Clone, Default)]
 #[command(author, version, about, long_about = None)]
+#[command(args_conflicts_with_subcommands = true)]
 pub struct Args {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
     /// DICOM file path(s) to display
     #[arg(value_name = "FILE")]
     pub files: Vec<PathBuf>,
@@ -13,15 +28,934 @@ pub struct Args {
     #[arg(short = 'W', long)]
     pub width: Option<u32>,
 
-    /// Output height in terminal rows
+    /// Output height in terminal rows; with --width, the image fits within
+    /// both and is centered on --background
     #[arg(short = 'H', long)]
     pub height: Option<u32>,
 
-    /// Show DICOM metadata
-    #[arg(short, long)]
-    pub verbose: bool,
+    /// Stretch the image to fill both --width and --height, ignoring its
+    /// aspect ratio
+    #[arg(long, requires_all = ["width", "height"])]
+    pub stretch: bool,
+
+    /// Color around an image that does not fill both --width and --height,
+    /// as a name (black, white, gray) or #RRGGBB
+    #[arg(long, value_name = "COLOR", default_value = "black", value_parser = parse_color)]
+    pub background: [u8; 3],
+
+    /// How images are displayed; `auto` picks the best backend for the terminal
+    #[arg(long, value_enum, env = "DCMV_RENDERER", default_value_t = RendererKind::Auto)]
+    pub renderer: RendererKind,
+
+    /// Filter images are scaled down with to the pixels the terminal shows
+    #[arg(long, value_enum, default_value_t = ScaleFilter::Triangle)]
+    pub scale_filter: ScaleFilter,
+
+    /// Show DICOM metadata; twice (-vv) also logs as --debug does
+    #[arg(short, long, action = ArgAction::Count)]
+    pub verbose: u8,
+
+    /// Log processing steps, decisions and their timings to stderr (also
+    /// enabled by RUST_LOG, e.g. RUST_LOG=dcmv=trace)
+    #[arg(long)]
+    pub debug: bool,
+
+    /// Format of metadata (--verbose) and error reports; JSON errors are
+    /// written to stderr, one object per line
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    pub format: OutputFormat,
+
+    /// When to color error and warning labels and file names, and show the
+    /// progress of reading stdin; `auto` does on terminals unless NO_COLOR
+    /// is set
+    #[arg(long, value_enum, value_name = "WHEN", default_value_t = ColorWhen::Auto)]
+    pub color: ColorWhen,
+
+    /// Show pixel value statistics in stored and rescaled units
+    #[arg(long)]
+    pub stats: bool,
+
+    /// Show the SHA-256 of the file and of its decoded pixels, which is the
+    /// same for pixel-identical files with different headers
+    #[arg(long)]
+    pub hash: bool,
+
+    /// Show a histogram of rescaled pixel values below the image, with the
+    /// display window marked
+    #[arg(long)]
+    pub histogram: bool,
+
+    /// Show the stored, rescaled and displayed values of the pixel at column
+    /// X and row Y of the stored image (repeatable)
+    #[arg(long, value_name = "X,Y", value_parser = parse_position)]
+    pub probe: Vec<(u32, u32)>,
+
+    /// Show how the pixel data was decoded and converted for display:
+    /// decoder, byte order, photometric conversion, rescale, window,
+    /// normalization, orientation and resizing
+    #[arg(long)]
+    pub explain: bool,
+
+    /// Show mean, SD, minimum and maximum of rescaled values in the
+    /// rectangle of W by H stored pixels from column X and row Y (repeatable)
+    #[arg(long, value_name = "X,Y,W,H", value_parser = parse_rect)]
+    pub roi_rect: Vec<(u32, u32, u32, u32)>,
+
+    /// List every data element instead of showing the image, with private
+    /// attributes named after their creator
+    #[arg(long)]
+    pub dump: bool,
+
+    /// JSON file naming private attributes for --dump, as
+    /// {"CREATOR": {"0029,xx10": "Name"}}
+    #[arg(long, value_name = "FILE", requires = "dump")]
+    pub private_dict: Option<PathBuf>,
+
+    /// Read files without preamble and file meta header as bare Implicit VR
+    /// Little Endian datasets
+    #[arg(long)]
+    pub force: bool,
+
+    /// Read the pixel data as this Photometric Interpretation instead of
+    /// the declared one, for mislabeled files
+    #[arg(long, value_name = "VALUE")]
+    pub photometric: Option<Photometric>,
+
+    /// Read color pixel data with this Planar Configuration instead of the
+    /// declared one: 0 for interleaved samples, 1 for one plane per color
+    #[arg(long, value_name = "0|1", value_parser = clap::value_parser!(u16).range(0..=1))]
+    pub force_planar: Option<u16>,
+
+    /// Read uncompressed samples in this byte order instead of the one of
+    /// the transfer syntax
+    #[arg(long, value_name = "ORDER")]
+    pub force_endian: Option<Endian>,
+
+    /// Cache rendered images in the user cache directory, so files seen
+    /// before are shown without decoding their pixel data
+    #[arg(long)]
+    pub cache: bool,
+
+    /// Show the embedded icon (Icon Image Sequence) instead of the image,
+    /// reading only the file header, or a decimated image without an icon
+    #[arg(long)]
+    pub thumbnail: bool,
 
     /// Show filename before image (useful for single files)
     #[arg(short = 'F', long)]
     pub filename: bool,
+
+    /// Metadata groups shown with --verbose, comma separated; all by default
+    #[arg(
+        long,
+        value_enum,
+        value_delimiter = ',',
+        env = "DCMV_SHOW",
+        value_name = "GROUPS"
+    )]
+    pub show: Vec<MetadataGroup>,
+
+    /// How dates are shown in metadata output: ISO 8601 (1993-08-22), or
+    /// in the order of the locale in LC_TIME or LANG
+    #[arg(long, value_enum, default_value_t = DateFormat::Iso)]
+    pub date_format: DateFormat,
+
+    /// Rotate the image clockwise, instead of orienting it from the DICOM tags
+    #[arg(long, value_enum, value_name = "DEGREES")]
+    pub rotate: Option<Rotation>,
+
+    /// Flip the image (after --rotate), instead of orienting it from the DICOM tags
+    #[arg(long, value_enum)]
+    pub flip: Option<Flip>,
+
+    /// Show ultrasound frames whole, with the scanner's controls and text,
+    /// instead of cropped to the imaging region
+    #[arg(long)]
+    pub no_crop: bool,
+
+    /// Black out the rectangle of W by H stored pixels from column X and row
+    /// Y in shown and written images, and in the pixel data of a --output
+    /// .dcm file, e.g. to hide a burned-in patient banner (repeatable)
+    #[arg(long, alias = "blank", value_name = "X,Y,W,H", value_parser = parse_rect)]
+    pub redact: Vec<(u32, u32, u32, u32)>,
+
+    /// Subtract frame N (1-based) of a multi-frame image from the shown
+    /// frames, as in digital subtraction angiography
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    pub subtract_frame: Option<u32>,
+
+    /// Gamma applied to displayed levels, one value or one per channel as
+    /// R,G,B (above 1 lifts shadows that the terminal shows too dark)
+    #[arg(long, value_name = "GAMMA", value_parser = parse_gamma)]
+    pub gamma: Option<[f32; 3]>,
+
+    /// Offset added to displayed levels, from -1 (black) to 1 (white)
+    #[arg(long, allow_hyphen_values = true, value_parser = parse_brightness)]
+    pub brightness: Option<f32>,
+
+    /// Factor stretching displayed levels around mid-gray
+    #[arg(long, value_parser = parse_contrast)]
+    pub contrast: Option<f32>,
+
+    /// Map displayed levels through the DICOM Grayscale Standard Display
+    /// Function, for a display whose white is NITS cd/m² (default 250)
+    #[arg(long, value_name = "NITS", num_args = 0..=1, default_missing_value = "250", value_parser = parse_max_luminance)]
+    pub gsdf: Option<f32>,
+
+    /// Show the image with inverted polarity (dark as bright), whether it is
+    /// MONOCHROME1 or MONOCHROME2
+    #[arg(long)]
+    pub invert: bool,
+
+    /// Sharpen, smooth or find the edges of the displayed image, which
+    /// terminals show at low resolution
+    #[arg(long, value_enum)]
+    pub filter: Option<FilterKind>,
+
+    /// Overlay orientation markers (L/R, A/P, H/F), a millimeter scale bar
+    /// and the text and graphics of the file's Graphic Annotation Sequence
+    /// (burned into --output files as 8-bit RGB)
+    #[arg(long)]
+    pub annotations: bool,
+
+    /// Draw the text and graphics of a presentation state (e.g. GSPS) over
+    /// the images it references
+    #[arg(long, value_name = "FILE", conflicts_with = "thumbnail")]
+    pub presentation_state: Option<PathBuf>,
+
+    /// Burn a caption with patient, study, series and frame into the image
+    /// (burned into --output files as 8-bit RGB)
+    #[arg(long, value_enum, value_name = "POSITION", num_args = 0..=1, default_missing_value = "above")]
+    pub caption: Option<CaptionPosition>,
+
+    /// Open an interactive viewer with a cursor for measuring distances (mm)
+    /// and ROI mean/SD: arrows or hjkl move, space places points, r switches
+    /// between ruler and ROI box, q quits
+    #[arg(short, long, conflicts_with = "output")]
+    pub interactive: bool,
+
+    /// Play the time points of a dynamic series (e.g. DCE-MRI) at one
+    /// slice position in the interactive viewer, pausing longer where
+    /// acquisitions are further apart; p pauses
+    #[arg(long, conflicts_with_all = ["output", "grid", "layout", "localizer", "fuse", "dump"])]
+    pub play_temporal: bool,
+
+    /// Time points shown per second by --play-temporal, on average
+    #[arg(long, value_name = "FPS", default_value_t = 4.0, value_parser = parse_fps, requires = "play_temporal")]
+    pub fps: f64,
+
+    /// Show only volume N (from 1) of a 4D series, split by echo, b-value,
+    /// cardiac phase or time point (listed by `dcmv ls`)
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    pub volume: Option<u32>,
+
+    /// Check the slices of each series among the files instead of showing
+    /// them: one orientation, even spacing and no missing Instance Numbers.
+    /// Exits with status 1 if any gap or inconsistency is found
+    #[arg(long, conflicts_with_all = ["output", "interactive", "play_temporal", "grid", "layout", "localizer", "fuse", "dump"])]
+    pub check: bool,
+
+    /// Show all files at once, in a grid of COLUMNS images per row
+    #[arg(long, value_name = "COLUMNS", value_parser = clap::value_parser!(u32).range(1..), conflicts_with_all = ["output", "interactive"])]
+    pub grid: Option<u32>,
+
+    /// Hang the views of a study together: chest PA next to lateral, or the
+    /// four mammograms with the chest walls facing (auto for directories)
+    #[arg(long, value_enum, value_name = "LAYOUT", conflicts_with_all = ["output", "interactive", "grid"])]
+    pub layout: Option<LayoutKind>,
+
+    /// Show each image next to this localizer (scout), with a line where
+    /// the image cuts through it
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["output", "interactive", "grid", "layout"])]
+    pub localizer: Option<PathBuf>,
+
+    /// Blend a PET image, or the nearest slice of a PET series directory,
+    /// over each image with a hot colormap, resampled through the image
+    /// positions of both
+    #[arg(long, value_name = "PET", conflicts_with_all = ["interactive", "localizer"])]
+    pub fuse: Option<PathBuf>,
+
+    /// Opacity of the --fuse image, from 0 to 1
+    #[arg(long, value_name = "ALPHA", default_value_t = fusion::DEFAULT_ALPHA, value_parser = parse_alpha, requires = "fuse")]
+    pub fuse_alpha: f64,
+
+    /// Write the first frame to an image file instead of displaying it
+    /// (grayscale data keeps its original bit depth, e.g. 16-bit PNG).
+    /// .npy, .nii (NIfTI) and .raw write the rescaled pixel values as an array,
+    /// .gif, .apng and .mp4 all frames as an animation,
+    /// .dcm the DICOM file with --redact applied to its pixel data.
+    /// Use `-` to stream PNG to stdout
+    #[arg(short, long, value_name = "FILE")]
+    pub output: Option<PathBuf>,
+
+    /// Also put the rendered image on the clipboard as PNG (with OSC 52
+    /// through the terminal over SSH)
+    #[arg(long)]
+    pub copy: bool,
+}
+
+impl Args {
+    /// Expand glob patterns in the files and a leading `~` in all paths,
+    /// for arguments the shell did not expand (see [`crate::paths`])
+    pub fn expand_paths(&mut self) {
+        self.files = self
+            .files
+            .iter()
+            .flat_map(|file| paths::expand_glob(file))
+            .collect();
+
+        let optional_paths = [
+            &mut self.private_dict,
+            &mut self.presentation_state,
+            &mut self.output,
+            &mut self.localizer,
+            &mut self.fuse,
+        ];
+        for path in optional_paths.into_iter().flatten() {
+            *path = paths::expand_tilde(path);
+        }
+
+        match &mut self.command {
+            Some(Command::Ls { dir, index }) => {
+                *dir = paths::expand_tilde(dir);
+                if let Some(Some(index)) = index {
+                    *index = paths::expand_tilde(index);
+                }
+            }
+            Some(Command::Transcode { input, output, .. } | Command::Split { input, output }) => {
+                *input = paths::expand_tilde(input);
+                *output = paths::expand_tilde(output);
+            }
+            Some(Command::Merge { inputs, output }) => {
+                for input in inputs.iter_mut() {
+                    *input = paths::expand_tilde(input);
+                }
+                *output = paths::expand_tilde(output);
+            }
+            Some(Command::Anonymize {
+                inputs,
+                output,
+                uid_map,
+            }) => {
+                for input in inputs.iter_mut() {
+                    *input = paths::expand_tilde(input);
+                }
+                *output = paths::expand_tilde(output);
+                if let Some(uid_map) = uid_map {
+                    *uid_map = paths::expand_tilde(uid_map);
+                }
+            }
+            Some(Command::Dedupe { dir, move_to, .. }) => {
+                *dir = paths::expand_tilde(dir);
+                if let Some(move_to) = move_to {
+                    *move_to = paths::expand_tilde(move_to);
+                }
+            }
+            Some(Command::Organize { inputs, output, .. }) => {
+                for input in inputs.iter_mut() {
+                    *input = paths::expand_tilde(input);
+                }
+                *output = paths::expand_tilde(output);
+            }
+            Some(Command::Gen { dir }) => *dir = paths::expand_tilde(dir),
+            Some(
+                Command::Codecs | Command::Probe { .. } | Command::Uid { .. } | Command::Tag { .. },
+            )
+            | None => {}
+        }
+    }
+
+    /// Orientation requested with --rotate/--flip, if any
+    ///
+    /// When set, it replaces the automatic orientation from the DICOM tags.
+    #[must_use]
+    pub fn orientation_override(&self) -> Option<Transform> {
+        if self.rotate.is_none() && self.flip.is_none() {
+            return None;
+        }
+
+        let rotation = match self.rotate {
+            None | Some(Rotation::R0) => Transform::IDENTITY,
+            Some(Rotation::R90) => Transform::ROTATE_90,
+            Some(Rotation::R180) => Transform::ROTATE_180,
+            Some(Rotation::R270) => Transform::ROTATE_270,
+        };
+
+        let flip = match self.flip {
+            None => Transform::IDENTITY,
+            Some(Flip::Horizontal) => Transform::FLIP_H,
+            Some(Flip::Vertical) => Transform::FLIP_V,
+        };
+
+        Some(rotation.then(flip))
+    }
+
+    /// Adjustment requested with --gamma, --brightness, --contrast and
+    /// --gsdf, if any
+    #[must_use]
+    pub fn tone_curve(&self) -> Option<ToneCurve> {
+        if self.gamma.is_none()
+            && self.brightness.is_none()
+            && self.contrast.is_none()
+            && self.gsdf.is_none()
+        {
+            return None;
+        }
+
+        let default = ToneCurve::default();
+        Some(ToneCurve {
+            gamma: self.gamma.unwrap_or(default.gamma),
+            brightness: self.brightness.unwrap_or(default.brightness),
+            contrast: self.contrast.unwrap_or(default.contrast),
+            gsdf: self.gsdf,
+        })
+    }
+
+    /// Date style requested with --date-format
+    #[must_use]
+    pub fn date_style(&self) -> DateStyle {
+        match self.date_format {
+            DateFormat::Iso => DateStyle::Iso,
+            DateFormat::Locale => DateStyle::Locale,
+        }
+    }
+
+    /// Whether metadata is shown, with --verbose
+    #[must_use]
+    pub fn shows_metadata(&self) -> bool {
+        self.verbose > 0
+    }
+
+    /// Whether the image is written to stdout with `--output -`, which then
+    /// cannot carry any text
+    #[must_use]
+    pub fn writes_image_to_stdout(&self) -> bool {
+        self.output.as_deref() == Some(Path::new("-"))
+    }
+
+    /// The first option given that prints text to stdout for each file
+    #[must_use]
+    pub fn stdout_text_option(&self) -> Option<&'static str> {
+        [
+            (self.filename, "--filename"),
+            (self.shows_metadata(), "--verbose"),
+            (self.hash, "--hash"),
+            (self.stats, "--stats"),
+            (!self.probe.is_empty(), "--probe"),
+            (!self.roi_rect.is_empty(), "--roi-rect"),
+            (self.explain, "--explain"),
+            (self.histogram, "--histogram"),
+        ]
+        .into_iter()
+        .find_map(|(given, option)| given.then_some(option))
+    }
+
+    /// Whether debug logging was requested with --debug or -vv
+    #[must_use]
+    pub fn debug_logging(&self) -> bool {
+        self.debug || self.verbose > 1
+    }
+
+    /// Colors for text written to `stream`, as requested with --color
+    #[must_use]
+    pub fn palette(&self, stream: Stream) -> Palette {
+        Palette::new(match self.color {
+            ColorWhen::Always => true,
+            ColorWhen::Never => false,
+            ColorWhen::Auto => {
+                TerminalContext::get().is_terminal(stream) && color::env_allows_color()
+            }
+        })
+    }
+
+    /// Metadata groups and date style requested with --show and --date-format
+    #[must_use]
+    pub fn metadata_format(&self) -> MetadataFormat {
+        MetadataFormat {
+            groups: self.show.clone(),
+            dates: self.date_style(),
+        }
+    }
+
+    /// Resampling filter requested with --scale-filter
+    #[must_use]
+    pub fn scale_filter_type(&self) -> FilterType {
+        match self.scale_filter {
+            ScaleFilter::Nearest => FilterType::Nearest,
+            ScaleFilter::Triangle => FilterType::Triangle,
+            ScaleFilter::Lanczos3 => FilterType::Lanczos3,
+        }
+    }
+
+    /// Convolution requested with --filter, if any
+    #[must_use]
+    pub fn image_filter(&self) -> Option<Filter> {
+        self.filter.map(|kind| match kind {
+            FilterKind::Sharpen => Filter::Sharpen,
+            FilterKind::Smooth => Filter::Smooth,
+            FilterKind::Edge => Filter::Edge,
+        })
+    }
+
+    /// Photometric Interpretation forced with --photometric, if any
+    #[must_use]
+    pub fn photometric_override(&self) -> Option<PhotometricInterpretation> {
+        self.photometric.map(|photometric| match photometric {
+            Photometric::Monochrome1 => PhotometricInterpretation::Monochrome1,
+            Photometric::Monochrome2 => PhotometricInterpretation::Monochrome2,
+            Photometric::Rgb => PhotometricInterpretation::Rgb,
+            Photometric::YbrFull => PhotometricInterpretation::YbrFull,
+            Photometric::YbrFull422 => PhotometricInterpretation::YbrFull422,
+            Photometric::YbrPartial422 => PhotometricInterpretation::YbrPartial422,
+            Photometric::YbrPartial420 => PhotometricInterpretation::YbrPartial420,
+        })
+    }
+
+    /// Byte order forced with --force-endian, if any
+    #[must_use]
+    pub fn byte_order_override(&self) -> Option<ByteOrder> {
+        self.force_endian.map(|endian| match endian {
+            Endian::Little => ByteOrder::Little,
+            Endian::Big => ByteOrder::Big,
+        })
+    }
+
+    /// Caption placement requested with --caption, if any
+    #[must_use]
+    pub fn caption_placement(&self) -> Option<CaptionPlacement> {
+        self.caption.map(|position| match position {
+            CaptionPosition::Above => CaptionPlacement::Above,
+            CaptionPosition::Overlay => CaptionPlacement::Overlay,
+        })
+    }
+
+    /// Whether to look for a hanging layout: with --layout, or by default
+    /// when a directory is given
+    #[must_use]
+    pub fn wants_layout(&self) -> bool {
+        match self.layout {
+            None => self.files.iter().any(|file| file.is_dir()),
+            Some(kind) => kind != LayoutKind::None,
+        }
+    }
+
+    /// Hanging protocol requested with --layout, if a specific one
+    #[must_use]
+    pub fn hanging_protocol(&self) -> Option<Protocol> {
+        match self.layout? {
+            LayoutKind::Chest => Some(Protocol::Chest),
+            LayoutKind::Mammo => Some(Protocol::Mammography),
+            LayoutKind::Auto | LayoutKind::None => None,
+        }
+    }
+}
+
+/// How metadata and errors are reported
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    /// Human-readable text
+    #[default]
+    Text,
+    /// One JSON object per file
+    Json,
+}
+
+/// When text output is colored
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorWhen {
+    /// On terminals, unless NO_COLOR is set or TERM is dumb
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+/// How dates are written in metadata output
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DateFormat {
+    /// ISO 8601, e.g. 1993-08-22
+    #[default]
+    Iso,
+    /// As the locale writes dates, e.g. 22.08.1993 or 08/22/1993
+    Locale,
+}
+
+/// Display backend for images
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RendererKind {
+    /// Native kitty or iTerm2 graphics when supported, viuer otherwise
+    #[default]
+    Auto,
+    /// Kitty, iTerm2 or Sixel graphics, or colored blocks as a fallback
+    Viuer,
+    /// Native kitty graphics protocol
+    Kitty,
+    /// Native iTerm2 inline images, at full resolution on high-DPI displays
+    Iterm,
+    /// Save each image as a PNG in the temporary directory and print its path
+    File,
+    /// Decode and render without showing anything (for timing and tests)
+    #[value(name = "none")]
+    Null,
+}
+
+/// Resampling filter for scaling images down to the terminal
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ScaleFilter {
+    /// Nearest pixel: fastest, but aliases fine detail
+    Nearest,
+    /// Bilinear: smooth and fast
+    #[default]
+    Triangle,
+    /// Lanczos with a 3-lobe window: sharpest, and slowest
+    Lanczos3,
+}
+
+/// Clockwise rotation in degrees
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rotation {
+    #[value(name = "0")]
+    R0,
+    #[value(name = "90")]
+    R90,
+    #[value(name = "180")]
+    R180,
+    #[value(name = "270")]
+    R270,
+}
+
+/// Mirror axis
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flip {
+    /// Mirror left-right
+    #[value(name = "h", alias = "horizontal")]
+    Horizontal,
+    /// Mirror top-bottom
+    #[value(name = "v", alias = "vertical")]
+    Vertical,
+}
+
+/// Convolution applied with --filter
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterKind {
+    /// Boost fine detail
+    Sharpen,
+    /// Blur away noise
+    Smooth,
+    /// Show only the edges
+    Edge,
+}
+
+/// Hanging layout chosen with --layout
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LayoutKind {
+    /// Chosen from View Position and laterality
+    Auto,
+    /// Frontal and lateral chest radiographs side by side
+    Chest,
+    /// RCC and LCC above RMLO and LMLO, chest walls facing
+    Mammo,
+    /// Each file on its own
+    None,
+}
+
+impl From<Protocol> for LayoutKind {
+    fn from(protocol: Protocol) -> Self {
+        match protocol {
+            Protocol::Chest => Self::Chest,
+            Protocol::Mammography => Self::Mammo,
+        }
+    }
+}
+
+/// Photometric Interpretation forced with --photometric
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Photometric {
+    /// Grayscale, minimum shown as white
+    Monochrome1,
+    /// Grayscale, minimum shown as black
+    Monochrome2,
+    /// Red, green and blue samples
+    Rgb,
+    /// Full-range YCbCr
+    YbrFull,
+    /// Full-range YCbCr, chroma halved horizontally
+    YbrFull422,
+    /// Video-range YCbCr, chroma halved horizontally
+    YbrPartial422,
+    /// Video-range YCbCr, chroma halved both ways
+    YbrPartial420,
+}
+
+/// Byte order forced with --force-endian
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Endian {
+    /// Least significant byte first
+    Little,
+    /// Most significant byte first
+    Big,
+}
+
+/// Where to put the --caption text
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptionPosition {
+    /// In a banner above the image
+    Above,
+    /// Over the top-left corner of the image
+    Overlay,
+}
+
+/// Transfer syntax to re-encode to with `dcmv transcode`
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TargetSyntax {
+    /// Uncompressed, Explicit VR Little Endian
+    ExplicitLe,
+    /// JPEG-LS Lossless (needs the `charls` feature)
+    JpegLs,
+    /// JPEG 2000 Lossless (needs an encoder, which dicom-rs does not have yet)
+    J2kLossless,
+}
+
+/// Auxiliary commands that do not display images
+#[derive(Subcommand, Debug, Clone)]
+pub enum Command {
+    /// List the transfer syntaxes this build can decode
+    Codecs,
+    /// Show the detected terminal graphics support and the renderer it selects
+    Probe {
+        /// Report this renderer as forced instead of detecting one
+        #[arg(long, value_enum, env = "DCMV_RENDERER", default_value_t = RendererKind::Auto)]
+        renderer: RendererKind,
+    },
+    /// Name a UID (SOP class or transfer syntax), or find the UID of a name
+    Uid {
+        /// UID, SOP class keyword or name, or part of a transfer syntax name
+        #[arg(value_name = "UID|NAME", required = true)]
+        query: Vec<String>,
+    },
+    /// Print the tag, keyword, VR and VM of an attribute of the standard
+    /// dictionary
+    Tag {
+        /// Tag (e.g. 0010,0010) or keyword (e.g. PatientName)
+        #[arg(value_name = "TAG|KEYWORD", required = true)]
+        query: Vec<String>,
+    },
+    /// List the studies and series of the DICOM files in a directory
+    Ls {
+        /// Directory to scan recursively
+        #[arg(value_name = "DIR")]
+        dir: PathBuf,
+
+        /// Cache file headers in an index file, so later listings only read
+        /// new or changed files (default: .dcmv-index.json in DIR)
+        #[arg(long, value_name = "FILE", num_args = 0..=1)]
+        index: Option<Option<PathBuf>>,
+    },
+    /// Re-encode a DICOM file with another transfer syntax, keeping all
+    /// other attributes
+    Transcode {
+        /// DICOM file to read
+        #[arg(value_name = "IN")]
+        input: PathBuf,
+
+        /// Transfer syntax of the output file
+        #[arg(long, value_enum)]
+        ts: TargetSyntax,
+
+        /// DICOM file to write
+        #[arg(value_name = "OUT")]
+        output: PathBuf,
+    },
+    /// Write each frame of a multi-frame DICOM file to its own file, with a
+    /// new SOP Instance UID
+    Split {
+        /// Multi-frame DICOM file to read
+        #[arg(value_name = "IN")]
+        input: PathBuf,
+
+        /// Directory to write the frames to, created if missing
+        #[arg(short, long = "output", value_name = "DIR")]
+        output: PathBuf,
+    },
+    /// Merge a series of single-frame CT, MR or PET files into one
+    /// enhanced multi-frame file
+    Merge {
+        /// Series directory, or the files of the series
+        #[arg(value_name = "FILE", required = true)]
+        inputs: Vec<PathBuf>,
+
+        /// Multi-frame DICOM file to write
+        #[arg(short, long = "output", value_name = "FILE")]
+        output: PathBuf,
+    },
+    /// Remove the patient's identity from DICOM files, replacing their UIDs
+    /// alike across all files so studies and series stay together
+    Anonymize {
+        /// DICOM files or directories of them
+        #[arg(value_name = "FILE", required = true)]
+        inputs: Vec<PathBuf>,
+
+        /// Directory to write the anonymized files to, created if missing
+        #[arg(short, long = "output", value_name = "DIR")]
+        output: PathBuf,
+
+        /// JSON file mapping original to new UIDs, read before and saved
+        /// after, so files anonymized in separate runs stay related
+        #[arg(long, value_name = "FILE")]
+        uid_map: Option<PathBuf>,
+    },
+    /// Find duplicate DICOM files below a directory: files with the same SOP
+    /// Instance UID or identical decoded pixels, as left by repeated exports
+    Dedupe {
+        /// Directory to search, including its subdirectories
+        #[arg(value_name = "DIR")]
+        dir: PathBuf,
+
+        /// Replace duplicates that are byte-identical to the kept file by
+        /// hard links to it
+        #[arg(long, conflicts_with = "move_to")]
+        link: bool,
+
+        /// Move duplicates to this directory, keeping their paths below DIR
+        #[arg(long, value_name = "DIR")]
+        move_to: Option<PathBuf>,
+    },
+    /// Sort DICOM files into Patient/Study/Series directories below a
+    /// directory, with paths made from a template of attribute keywords
+    Organize {
+        /// DICOM files or directories of them, searched recursively
+        #[arg(value_name = "FILE", required = true)]
+        inputs: Vec<PathBuf>,
+
+        /// Directory to sort the files into, created if missing
+        #[arg(short, long = "output", value_name = "DIR")]
+        output: PathBuf,
+
+        /// Path of each file below DIR: `{Keyword}` is replaced by the value
+        /// of the attribute, `{Keyword:N}` by a number padded to N digits
+        #[arg(long, value_name = "TEMPLATE", default_value = crate::organize::DEFAULT_TEMPLATE)]
+        template: String,
+
+        /// Move the files instead of copying them
+        #[arg(long = "move")]
+        move_files: bool,
+
+        /// Only print where each file would go
+        #[arg(short = 'n', long)]
+        dry_run: bool,
+    },
+    /// Write small synthetic DICOM files of a test pattern, one for each
+    /// pixel format and transfer syntax, to check decoding and colors
+    Gen {
+        /// Directory to write the files to, created if missing
+        #[arg(value_name = "DIR")]
+        dir: PathBuf,
+    },
+}
+
+fn parse_positive(value: &str) -> Result<f32, String> {
+    match value.trim().parse::<f32>() {
+        Ok(v) if v > 0.0 && v.is_finite() => Ok(v),
+        _ => Err(format!("`{value}` is not a positive number")),
+    }
+}
+
+/// A color name or #RRGGBB
+fn parse_color(value: &str) -> Result<[u8; 3], String> {
+    let hex = |digits: &str| u8::from_str_radix(digits, 16).ok();
+    match value.trim().to_ascii_lowercase().as_str() {
+        "black" => Ok([0, 0, 0]),
+        "white" => Ok([255, 255, 255]),
+        "gray" | "grey" => Ok([128, 128, 128]),
+        color => color
+            .strip_prefix('#')
+            .filter(|digits| digits.len() == 6 && digits.chars().all(|c| c.is_ascii_hexdigit()))
+            .and_then(|digits| {
+                Some([
+                    hex(&digits[0..2])?,
+                    hex(&digits[2..4])?,
+                    hex(&digits[4..6])?,
+                ])
+            })
+            .ok_or_else(|| format!("`{value}` is not a color name or #RRGGBB")),
+    }
+}
+
+/// One gamma for all channels, or three as R,G,B
+fn parse_gamma(value: &str) -> Result<[f32; 3], String> {
+    let gammas = value
+        .split(',')
+        .map(parse_positive)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    match gammas[..] {
+        [gamma] => Ok([gamma; 3]),
+        [r, g, b] => Ok([r, g, b]),
+        _ => Err("expected one gamma or three as R,G,B".to_string()),
+    }
+}
+
+fn parse_brightness(value: &str) -> Result<f32, String> {
+    match value.trim().parse::<f32>() {
+        Ok(v) if (-1.0..=1.0).contains(&v) => Ok(v),
+        _ => Err(format!("`{value}` is not a number from -1 to 1")),
+    }
+}
+
+fn parse_alpha(value: &str) -> Result<f64, String> {
+    match value.trim().parse::<f64>() {
+        Ok(v) if (0.0..=1.0).contains(&v) => Ok(v),
+        _ => Err(format!("`{value}` is not a number from 0 to 1")),
+    }
+}
+
+fn parse_fps(value: &str) -> Result<f64, String> {
+    match value.trim().parse::<f64>() {
+        Ok(v) if v > 0.0 && v <= 60.0 => Ok(v),
+        _ => Err(format!(
+            "`{value}` is not a rate above 0 and up to 60 per second"
+        )),
+    }
+}
+
+fn parse_contrast(value: &str) -> Result<f32, String> {
+    parse_positive(value)
+}
+
+fn parse_max_luminance(value: &str) -> Result<f32, String> {
+    match parse_positive(value) {
+        Ok(v) if v > GSDF_MIN_LUMINANCE => Ok(v),
+        _ => Err(format!(
+            "`{value}` is not a luminance above {} cd/m²",
+            GSDF_MIN_LUMINANCE
+        )),
+    }
+}
+
+fn parse_position(value: &str) -> Result<(u32, u32), String> {
+    let position = value
+        .split_once(',')
+        .and_then(|(x, y)| Some((x.trim().parse().ok()?, y.trim().parse().ok()?)));
+    position.ok_or_else(|| format!("`{value}` is not a pixel position as X,Y"))
+}
+
+fn parse_rect(value: &str) -> Result<(u32, u32, u32, u32), String> {
+    let numbers = value
+        .split(',')
+        .map(|number| number.trim().parse::<u32>())
+        .collect::<Result<Vec<_>, _>>();
+
+    match numbers.as_deref() {
+        Ok(&[x, y, width, height])
+            if width > 0
+                && height > 0
+                && x.checked_add(width).is_some()
+                && y.checked_add(height).is_some() =>
+        {
+            Ok((x, y, width, height))
+        }
+        _ => Err(format!(
+            "`{value}` is not a rectangle as X,Y,W,H with W and H above 0"
+        )),
+    }
 }
@@ -0,0 +1,157 @@
+//! Copying rendered images to the system clipboard for `--copy`
+//!
+//! Locally the image is handed to the platform's clipboard tool as PNG, so
+//! it pastes as an image. Over SSH that would fill the clipboard of the
+//! remote machine, so the image is sent to the terminal with OSC 52
+//! instead, which puts it on the clipboard of the machine the terminal
+//! runs on. Terminals differ in what they accept there: most keep the
+//! decoded bytes as text, and some limit its size.
+
+use crate::display::is_remote_session;
+use crate::terminal::TerminalContext;
+use anyhow::{Context, Result, bail};
+use base64::{Engine, engine::general_purpose::STANDARD};
+use image::{DynamicImage, ImageFormat};
+use std::io::{Cursor, Write};
+use std::process::{Command, Stdio};
+use tracing::debug;
+
+/// Put `image` on the clipboard as PNG
+///
+/// # Errors
+///
+/// Returns an error if the image cannot be encoded, or neither a clipboard
+/// tool nor the terminal can take it
+pub fn copy_image(image: &DynamicImage) -> Result<()> {
+    let mut png = Vec::new();
+    image
+        .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+        .context("Failed to encode image for the clipboard")?;
+
+    if !is_remote_session() {
+        match copy_native(&png) {
+            Ok(tool) => {
+                debug!(tool, bytes = png.len(), "copied to the clipboard");
+                return Ok(());
+            }
+            Err(e) => debug!("no native clipboard: {e:#}"),
+        }
+    }
+
+    if !TerminalContext::get().stdout {
+        bail!("No clipboard tool found, and output is not a terminal for OSC 52");
+    }
+    let mut stdout = std::io::stdout().lock();
+    write_osc52(&mut stdout, &png)?;
+    debug!(bytes = png.len(), "copied to the clipboard with OSC 52");
+    Ok(())
+}
+
+/// Send `data` to the terminal's clipboard
+///
+/// # Errors
+///
+/// Returns an error if writing fails
+pub fn write_osc52(out: &mut impl Write, data: &[u8]) -> Result<()> {
+    write!(out, "\x1b]52;c;{}\x07", STANDARD.encode(data))?;
+    out.flush()?;
+    Ok(())
+}
+
+/// Clipboard tools to try, with their arguments for PNG input on stdin
+fn native_tools() -> Vec<(&'static str, &'static [&'static str])> {
+    let mut tools: Vec<(&'static str, &'static [&'static str])> = Vec::new();
+    if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+        tools.push(("wl-copy", &["--type", "image/png"]));
+    }
+    if std::env::var_os("DISPLAY").is_some() {
+        tools.push((
+            "xclip",
+            &["-selection", "clipboard", "-t", "image/png", "-i"],
+        ));
+    }
+    tools
+}
+
+/// Copy with the first clipboard tool that works, returning its name
+fn copy_native(png: &[u8]) -> Result<&'static str> {
+    if cfg!(target_os = "macos") {
+        copy_with_osascript(png)?;
+        return Ok("osascript");
+    }
+
+    let tools = native_tools();
+    if tools.is_empty() {
+        bail!("No display server (WAYLAND_DISPLAY or DISPLAY) to copy to");
+    }
+
+    let mut errors = Vec::new();
+    for (program, args) in tools {
+        match pipe_to(program, args, png) {
+            Ok(()) => return Ok(program),
+            Err(e) => errors.push(format!("{e:#}")),
+        }
+    }
+    bail!("{}", errors.join("; "))
+}
+
+/// Run `program` with `data` on its stdin
+fn pipe_to(program: &str, args: &[&str], data: &[u8]) -> Result<()> {
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .with_context(|| format!("Failed to run {program} (is it installed?)"))?;
+
+    let mut stdin = child.stdin.take().context("No input pipe")?;
+    stdin
+        .write_all(data)
+        .with_context(|| format!("{program} stopped reading"))?;
+    // Closing the pipe tells the tool the image is complete
+    drop(stdin);
+
+    let status = child.wait()?;
+    if !status.success() {
+        bail!("{program} failed ({status})");
+    }
+    Ok(())
+}
+
+/// Copy on macOS, where AppleScript reads the PNG from a temporary file
+fn copy_with_osascript(png: &[u8]) -> Result<()> {
+    let mut file = tempfile::Builder::new()
+        .prefix("dcmv-")
+        .suffix(".png")
+        .tempfile()
+        .context("Failed to create a temporary file")?;
+    file.write_all(png)?;
+    file.flush()?;
+
+    let script = format!(
+        "set the clipboard to (read (POSIX file \"{}\") as «class PNGf»)",
+        file.path().display()
+    );
+    let status = Command::new("osascript")
+        .args(["-e", &script])
+        .stdout(Stdio::null())
+        .status()
+        .context("Failed to run osascript")?;
+    if !status.success() {
+        bail!("osascript failed ({status})");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_osc52_sequence() {
+        let mut out = Vec::new();
+        write_osc52(&mut out, b"dcmv").unwrap();
+        assert_eq!(out, b"\x1b]52;c;ZGNtdg==\x07");
+    }
+}
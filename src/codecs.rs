@@ -0,0 +1,103 @@
+//! Transfer syntax decoding capabilities of the current build
+
+use dicom::encoding::TransferSyntaxIndex;
+use dicom::transfer_syntax::TransferSyntaxRegistry;
+
+/// Encapsulated transfer syntaxes decoded through GDCM when the `gdcm` feature is enabled
+#[cfg(feature = "gdcm")]
+const GDCM_TRANSFER_SYNTAXES: &[&str] = &[
+    "1.2.840.10008.1.2.4.50", // JPEG Baseline
+    "1.2.840.10008.1.2.4.51", // JPEG Extended
+    "1.2.840.10008.1.2.4.57", // JPEG Lossless
+    "1.2.840.10008.1.2.4.70", // JPEG Lossless SV1
+    "1.2.840.10008.1.2.4.80", // JPEG-LS Lossless
+    "1.2.840.10008.1.2.4.81", // JPEG-LS Near Lossless
+    "1.2.840.10008.1.2.4.90", // JPEG 2000 Lossless
+    "1.2.840.10008.1.2.4.91", // JPEG 2000
+    "1.2.840.10008.1.2.4.92", // JPEG 2000 Part 2 Lossless
+    "1.2.840.10008.1.2.4.93", // JPEG 2000 Part 2
+    "1.2.840.10008.1.2.5",    // RLE Lossless
+];
+
+/// Decoding support for a single transfer syntax
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodecSupport {
+    pub uid: String,
+    pub name: String,
+    /// Name of the decoder used, or `None` if the pixel data cannot be decoded
+    pub decoder: Option<&'static str>,
+}
+
+impl CodecSupport {
+    #[inline]
+    #[must_use]
+    pub fn is_decodable(&self) -> bool {
+        self.decoder.is_some()
+    }
+}
+
+/// List every transfer syntax known to the registry with its decoding support
+#[must_use]
+pub fn codec_support() -> Vec<CodecSupport> {
+    let mut codecs: Vec<CodecSupport> = TransferSyntaxRegistry
+        .iter()
+        .map(|ts| {
+            let decoder = if !ts.is_encapsulated_pixel_data() {
+                ts.can_decode_dataset().then_some("native")
+            } else {
+                encapsulated_decoder(ts.uid(), ts.can_decode_all())
+            };
+
+            CodecSupport {
+                uid: ts.uid().to_string(),
+                name: ts.name().to_string(),
+                decoder,
+            }
+        })
+        .collect();
+
+    // Sort numerically by UID component so 1.2.840.10008.1.2.4.50 precedes .4.100
+    codecs.sort_by_cached_key(|codec| {
+        codec
+            .uid
+            .split('.')
+            .map(|part| part.parse::<u32>().unwrap_or(0))
+            .collect::<Vec<_>>()
+    });
+    codecs.dedup_by(|a, b| a.uid == b.uid);
+    codecs
+}
+
+/// Check whether a transfer syntax UID can be decoded by this build
+#[must_use]
+pub fn can_decode(uid: &str) -> bool {
+    let uid = uid.trim_end_matches('\0');
+    TransferSyntaxRegistry.get(uid).is_some_and(|ts| {
+        if ts.is_encapsulated_pixel_data() {
+            encapsulated_decoder(ts.uid(), ts.can_decode_all()).is_some()
+        } else {
+            ts.can_decode_dataset()
+        }
+    })
+}
+
+// With GDCM enabled, dicom-pixeldata routes all encapsulated data through it
+#[cfg(feature = "gdcm")]
+fn encapsulated_decoder(uid: &str, _registry_decoder: bool) -> Option<&'static str> {
+    GDCM_TRANSFER_SYNTAXES.contains(&uid).then_some("gdcm")
+}
+
+#[cfg(not(feature = "gdcm"))]
+fn encapsulated_decoder(_uid: &str, registry_decoder: bool) -> Option<&'static str> {
+    registry_decoder.then_some("dicom-rs")
+}
+
+/// Print the capability report for `dcmv codecs`
+pub fn print_codecs() {
+    for codec in codec_support() {
+        let support = codec
+            .decoder
+            .map_or_else(|| "no".to_string(), |decoder| format!("yes ({decoder})"));
+        println!("{:<26} {:<14} {}", codec.uid, support, codec.name);
+    }
+}
@@ -0,0 +1,79 @@
+//! ANSI colors in text output
+//!
+//! Colors are used for `Error:` and `Warning:` labels and file names, and
+//! only on terminals, unless `NO_COLOR` is set (<https://no-color.org>) or
+//! `TERM` is `dumb`. `--color` overrides the detection. Images are not
+//! affected.
+
+use std::env;
+use std::fmt::Display;
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const BOLD_RED: &str = "\x1b[1;31m";
+const BOLD_YELLOW: &str = "\x1b[1;33m";
+
+/// Whether the environment allows colors on a terminal
+#[must_use]
+pub fn env_allows_color() -> bool {
+    let no_color = env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+    let dumb = env::var_os("TERM").is_some_and(|term| term == "dumb");
+    !no_color && !dumb
+}
+
+/// Styles for one output stream, plain text when colors are off
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Palette {
+    enabled: bool,
+}
+
+impl Palette {
+    #[must_use]
+    pub const fn new(enabled: bool) -> Self {
+        Self { enabled }
+    }
+
+    #[must_use]
+    pub const fn is_enabled(self) -> bool {
+        self.enabled
+    }
+
+    /// The `Error:` label of error messages
+    #[must_use]
+    pub fn error(self) -> String {
+        self.paint(BOLD_RED, "Error:")
+    }
+
+    /// The `Warning:` label of warnings
+    #[must_use]
+    pub fn warning(self) -> String {
+        self.paint(BOLD_YELLOW, "Warning:")
+    }
+
+    /// A heading, e.g. the name of a file above its output
+    #[must_use]
+    pub fn heading(self, text: impl Display) -> String {
+        self.paint(BOLD, text)
+    }
+
+    fn paint(self, style: &str, text: impl Display) -> String {
+        if self.enabled {
+            format!("{style}{text}{RESET}")
+        } else {
+            text.to_string()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_palette_is_plain_when_disabled() {
+        assert_eq!(Palette::new(false).error(), "Error:");
+        assert_eq!(Palette::new(false).heading("a.dcm"), "a.dcm");
+        assert_eq!(Palette::new(true).error(), "\x1b[1;31mError:\x1b[0m");
+        assert_eq!(Palette::new(true).heading("a.dcm"), "\x1b[1ma.dcm\x1b[0m");
+    }
+}
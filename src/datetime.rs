@@ -0,0 +1,364 @@
+//! Parsing and formatting of DICOM dates and times (DA, TM and DT)
+//!
+//! Values are kept as they are in the metadata and only parsed for display,
+//! so that unparseable values can still be shown as they were written.
+
+use std::env;
+use std::fmt;
+
+/// How dates are written in metadata output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DateStyle {
+    /// ISO 8601, e.g. 1993-08-22
+    #[default]
+    Iso,
+    /// Day, month and year in the order of the locale in `LC_ALL`,
+    /// `LC_TIME` or `LANG`, e.g. 22.08.1993 for `de_DE`
+    Locale,
+}
+
+/// Order of the date fields in a locale, with their separator
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LocaleOrder {
+    MonthDayYear,
+    DayMonthYear(char),
+    YearMonthDay(char),
+}
+
+impl LocaleOrder {
+    /// Date order of the locale the environment selects for times
+    fn from_env() -> Self {
+        let locale = ["LC_ALL", "LC_TIME", "LANG"]
+            .iter()
+            .filter_map(|name| env::var(name).ok())
+            .find(|value| !value.is_empty())
+            .unwrap_or_default();
+        Self::for_locale(&locale)
+    }
+
+    fn for_locale(locale: &str) -> Self {
+        let tag = locale.split(['.', '@']).next().unwrap_or_default();
+        let language = tag.split(['_', '-']).next().unwrap_or_default();
+
+        match (language, tag) {
+            (_, "en_US" | "en-US" | "en_PH") => Self::MonthDayYear,
+            ("ja" | "zh" | "ko" | "hu" | "lt", _) => Self::YearMonthDay('/'),
+            ("sv" | "C" | "POSIX" | "", _) => Self::YearMonthDay('-'),
+            ("de" | "ru" | "pl" | "cs" | "sk" | "fi" | "nb" | "no" | "da" | "tr" | "uk", _) => {
+                Self::DayMonthYear('.')
+            }
+            ("nl", _) => Self::DayMonthYear('-'),
+            _ => Self::DayMonthYear('/'),
+        }
+    }
+}
+
+/// A DICOM date (DA), or the date part of a date-time (DT)
+///
+/// Month and day are optional, as date-times may be truncated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct DicomDate {
+    pub year: u16,
+    pub month: Option<u8>,
+    pub day: Option<u8>,
+}
+
+impl DicomDate {
+    /// Parse "YYYYMMDD", or the pre-1993 "YYYY.MM.DD" form
+    #[must_use]
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim();
+        let digits =
+            if value.len() == 10 && value.as_bytes()[4] == b'.' && value.as_bytes()[7] == b'.' {
+                value.replace('.', "")
+            } else {
+                value.to_string()
+            };
+
+        if digits.len() != 8 {
+            return None;
+        }
+        Self::parse_digits(&digits)
+    }
+
+    /// Parse "YYYY", "YYYYMM" or "YYYYMMDD"
+    fn parse_digits(digits: &str) -> Option<Self> {
+        let year = number(digits.get(..4)?)?;
+        let month = optional_number(digits.get(4..6))?;
+        let day = optional_number(digits.get(6..8))?;
+
+        let date = Self {
+            year,
+            month: month.map(|m| m as u8),
+            day: day.map(|d| d as u8),
+        };
+        let valid = date.month.is_none_or(|m| (1..=12).contains(&m))
+            && date.day.is_none_or(|d| (1..=31).contains(&d));
+        valid.then_some(date)
+    }
+
+    /// The date as ISO 8601, or as the locale writes it
+    #[must_use]
+    pub fn format(&self, style: DateStyle) -> String {
+        let (Some(month), Some(day)) = (self.month, self.day) else {
+            return match self.month {
+                Some(month) => format!("{:04}-{month:02}", self.year),
+                None => format!("{:04}", self.year),
+            };
+        };
+
+        let year = self.year;
+        match style {
+            DateStyle::Iso => format!("{year:04}-{month:02}-{day:02}"),
+            DateStyle::Locale => match LocaleOrder::from_env() {
+                LocaleOrder::MonthDayYear => format!("{month:02}/{day:02}/{year:04}"),
+                LocaleOrder::DayMonthYear(sep) => format!("{day:02}{sep}{month:02}{sep}{year:04}"),
+                LocaleOrder::YearMonthDay(sep) => format!("{year:04}{sep}{month:02}{sep}{day:02}"),
+            },
+        }
+    }
+
+    /// Age in full years on `date`, e.g. of a patient born on this date
+    #[must_use]
+    pub fn years_until(&self, date: &Self) -> Option<u16> {
+        let birthday = (self.month?, self.day?);
+        let on = (date.month?, date.day?);
+
+        let years = date.year.checked_sub(self.year)?;
+        if on < birthday {
+            years.checked_sub(1)
+        } else {
+            Some(years)
+        }
+    }
+}
+
+/// A DICOM time (TM), or the time part of a date-time (DT)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DicomTime {
+    pub hour: u8,
+    pub minute: Option<u8>,
+    pub second: Option<u8>,
+    /// Fraction of a second in microseconds, with the number of digits given
+    pub fraction: Option<(u32, usize)>,
+}
+
+impl DicomTime {
+    /// Parse "HH[MM[SS[.FFFFFF]]]", or the pre-1993 "HH:MM:SS" form
+    #[must_use]
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim().replace(':', "");
+        let (whole, fraction) = match value.split_once('.') {
+            Some((whole, fraction)) => (whole, Some(fraction)),
+            None => (value.as_str(), None),
+        };
+
+        if !matches!(whole.len(), 2 | 4 | 6) {
+            return None;
+        }
+
+        let hour = number(whole.get(..2)?)?;
+        let minute = optional_number(whole.get(2..4))?;
+        let second = optional_number(whole.get(4..6))?;
+        if hour > 23 || minute.is_some_and(|m| m > 59) || second.is_some_and(|s| s > 60) {
+            return None;
+        }
+
+        let fraction = match fraction {
+            Some(digits) if (1..=6).contains(&digits.len()) && second.is_some() => {
+                let micros = format!("{digits:0<6}").parse::<u32>().ok()?;
+                Some((micros, digits.len()))
+            }
+            Some(_) => return None,
+            None => None,
+        };
+
+        Some(Self {
+            hour: hour as u8,
+            minute: minute.map(|m| m as u8),
+            second: second.map(|s| s as u8),
+            fraction,
+        })
+    }
+
+    /// Seconds since midnight
+    #[must_use]
+    pub fn seconds(&self) -> f64 {
+        let whole = u32::from(self.hour) * 3600
+            + u32::from(self.minute.unwrap_or(0)) * 60
+            + u32::from(self.second.unwrap_or(0));
+        let micros = self.fraction.map_or(0, |(micros, _)| micros);
+        f64::from(whole) + f64::from(micros) / 1_000_000.0
+    }
+}
+
+impl fmt::Display for DicomTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:02}:{:02}", self.hour, self.minute.unwrap_or(0))?;
+        if let Some(second) = self.second {
+            write!(f, ":{second:02}")?;
+        }
+        if let Some((micros, digits)) = self.fraction {
+            let micros = format!("{micros:06}");
+            write!(f, ".{}", &micros[..digits])?;
+        }
+        Ok(())
+    }
+}
+
+/// A DICOM date-time (DT), or a date (DA) combined with a time (TM)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DicomDateTime {
+    pub date: DicomDate,
+    pub time: Option<DicomTime>,
+    /// Offset from UTC in minutes
+    pub offset: Option<i16>,
+}
+
+impl DicomDateTime {
+    /// Parse "YYYY[MM[DD[HH[MM[SS[.FFFFFF]]]]]][&ZZXX]"
+    #[must_use]
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim();
+        if !value.is_ascii() {
+            return None;
+        }
+        let (value, offset) = match value.find(['+', '-']) {
+            Some(index) => (&value[..index], Some(parse_offset(&value[index..])?)),
+            None => (value, None),
+        };
+
+        let date_digits = value.len().min(8);
+        if !matches!(date_digits, 4 | 6 | 8) {
+            return None;
+        }
+        let date = DicomDate::parse_digits(&value[..date_digits])?;
+        let time = match &value[date_digits..] {
+            "" => None,
+            time => Some(DicomTime::parse(time)?),
+        };
+
+        Some(Self { date, time, offset })
+    }
+
+    /// Combine a date and a time, as Study Date and Study Time
+    #[must_use]
+    pub fn from_date_and_time(date: &str, time: Option<&str>) -> Option<Self> {
+        Some(Self {
+            date: DicomDate::parse(date)?,
+            time: time.and_then(DicomTime::parse),
+            offset: None,
+        })
+    }
+
+    /// The date-time as ISO 8601 (with a space before the time), or with
+    /// the date as the locale writes it
+    #[must_use]
+    pub fn format(&self, style: DateStyle) -> String {
+        let mut text = self.date.format(style);
+        if let Some(time) = self.time {
+            text.push_str(&format!(" {time}"));
+        }
+        if let Some(offset) = self.offset {
+            let sign = if offset < 0 { '-' } else { '+' };
+            let offset = offset.unsigned_abs();
+            text.push_str(&format!(" {sign}{:02}:{:02}", offset / 60, offset % 60));
+        }
+        text
+    }
+}
+
+/// Parse a UTC offset "&ZZXX", in minutes
+fn parse_offset(value: &str) -> Option<i16> {
+    let sign = match value.as_bytes().first()? {
+        b'+' => 1,
+        b'-' => -1,
+        _ => return None,
+    };
+    let digits = &value[1..];
+    if digits.len() != 4 {
+        return None;
+    }
+
+    let hours = i16::try_from(number(&digits[..2])?).ok()?;
+    let minutes = i16::try_from(number(&digits[2..])?).ok()?;
+    Some(sign * (hours * 60 + minutes))
+}
+
+/// Parse an optional field: `Some(None)` when absent, `None` when invalid
+fn optional_number(digits: Option<&str>) -> Option<Option<u16>> {
+    match digits {
+        Some(digits) => number(digits).map(Some),
+        None => Some(None),
+    }
+}
+
+/// Parse a fixed-width run of ASCII digits
+fn number(digits: &str) -> Option<u16> {
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    digits.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_format_dates_and_times() {
+        let date = DicomDate::parse("19930822").unwrap();
+        assert_eq!(date.format(DateStyle::Iso), "1993-08-22");
+        assert_eq!(DicomDate::parse("1993.08.22"), Some(date));
+        assert_eq!(DicomDate::parse("19931322"), None);
+
+        let time = DicomTime::parse("143005.12").unwrap();
+        assert_eq!(time.to_string(), "14:30:05.12");
+        assert_eq!(
+            DicomTime::parse("14:30:05").unwrap().to_string(),
+            "14:30:05"
+        );
+        assert_eq!(DicomTime::parse("1430").unwrap().to_string(), "14:30");
+        assert_eq!(DicomTime::parse("2500"), None);
+
+        let study = DicomDateTime::from_date_and_time("19930822", Some("1430")).unwrap();
+        assert_eq!(study.format(DateStyle::Iso), "1993-08-22 14:30");
+
+        let dt = DicomDateTime::parse("20240315093000.5-0500").unwrap();
+        assert_eq!(dt.format(DateStyle::Iso), "2024-03-15 09:30:00.5 -05:00");
+        assert_eq!(
+            DicomDateTime::parse("2024").unwrap().format(DateStyle::Iso),
+            "2024"
+        );
+    }
+
+    #[test]
+    fn test_age_and_locale_order() {
+        let birth = DicomDate::parse("19600823").unwrap();
+        assert_eq!(
+            birth.years_until(&DicomDate::parse("19930822").unwrap()),
+            Some(32)
+        );
+        assert_eq!(
+            birth.years_until(&DicomDate::parse("19930823").unwrap()),
+            Some(33)
+        );
+
+        assert_eq!(
+            LocaleOrder::for_locale("en_US.UTF-8"),
+            LocaleOrder::MonthDayYear
+        );
+        assert_eq!(
+            LocaleOrder::for_locale("de_DE.UTF-8"),
+            LocaleOrder::DayMonthYear('.')
+        );
+        assert_eq!(
+            LocaleOrder::for_locale("en_GB.UTF-8"),
+            LocaleOrder::DayMonthYear('/')
+        );
+        assert_eq!(
+            LocaleOrder::for_locale("ja_JP.UTF-8"),
+            LocaleOrder::YearMonthDay('/')
+        );
+    }
+}
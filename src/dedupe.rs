@@ -0,0 +1,273 @@
+//! Finding duplicate DICOM files for `dcmv dedupe`
+//!
+//! Repeated PACS exports of the same study leave copies of an instance
+//! under different names, often with slightly different headers. Files are
+//! duplicates if they have the same SOP Instance UID or the same decoded
+//! pixels (see [`hash::pixel_sha256`]). Of each group of duplicates the
+//! first file in name order is kept.
+
+use crate::dicom;
+use crate::hash;
+use crate::index::{self, InstanceEntry};
+use crate::terminal::Stream;
+use anyhow::{Context, Result, bail};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// What to do with the duplicates found
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    /// Only list them
+    Report,
+    /// Replace byte-identical duplicates by hard links to the kept file
+    Link,
+    /// Move duplicates to this directory, keeping their paths below the
+    /// scanned directory
+    MoveTo(PathBuf),
+}
+
+/// Files that are the same instance
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Duplicates {
+    pub kept: PathBuf,
+    pub duplicates: Vec<PathBuf>,
+    /// Some files of the group share a SOP Instance UID
+    pub same_uid: bool,
+    /// Some files of the group have identical decoded pixels
+    pub same_pixels: bool,
+}
+
+/// Group the DICOM files below `dir` that have the same SOP Instance UID or
+/// the same decoded pixels
+///
+/// Groups are linked: if A has the UID of B and the pixels of C, all three
+/// are one group. Files without pixel data, or whose pixels cannot be
+/// decoded, are matched by UID only. Progress of the scan is shown on
+/// `progress`.
+///
+/// # Errors
+///
+/// Returns an error if a directory cannot be listed
+pub fn find_duplicates(dir: &Path, progress: Option<Stream>) -> Result<Vec<Duplicates>> {
+    let instances = index::scan_directory(dir, None, progress)?.instances;
+
+    let mut groups = Groups::new(instances.len());
+    let mut by_uid: HashMap<&str, usize> = HashMap::new();
+    let mut by_pixels: HashMap<String, usize> = HashMap::new();
+    for (i, instance) in instances.iter().enumerate() {
+        if let Some(uid) = instance.sop_instance_uid.as_deref() {
+            if let Some(&first) = by_uid.get(uid) {
+                groups.join(first, i, Reason::Uid);
+            } else {
+                by_uid.insert(uid, i);
+            }
+        }
+        if let Some(pixels) = pixel_hash(instance) {
+            if let Some(&first) = by_pixels.get(&pixels) {
+                groups.join(first, i, Reason::Pixels);
+            } else {
+                by_pixels.insert(pixels, i);
+            }
+        }
+    }
+
+    Ok(groups.collect(&instances))
+}
+
+/// Find the duplicates below `dir`, print them and apply `action`
+///
+/// # Errors
+///
+/// Returns an error if `dir` cannot be scanned, or a duplicate cannot be
+/// linked or moved
+pub fn dedupe(dir: &Path, action: &Action, progress: Option<Stream>) -> Result<()> {
+    let groups = find_duplicates(dir, progress)?;
+    if groups.is_empty() {
+        println!("No duplicates found");
+        return Ok(());
+    }
+
+    let mut count = 0;
+    for group in &groups {
+        let reason = match (group.same_uid, group.same_pixels) {
+            (true, true) => "same SOP Instance UID and pixels",
+            (true, false) => "same SOP Instance UID",
+            _ => "same pixels",
+        };
+        println!("{} files, {reason}:", group.duplicates.len() + 1);
+        println!("  {} (kept)", group.kept.display());
+
+        for duplicate in &group.duplicates {
+            let outcome = match action {
+                Action::Report => String::new(),
+                Action::Link => link(&group.kept, duplicate)?,
+                Action::MoveTo(target) => move_into(dir, duplicate, target)?,
+            };
+            println!("  {}{outcome}", duplicate.display());
+        }
+        count += group.duplicates.len();
+    }
+    println!();
+    let plural = |n: usize| if n == 1 { "" } else { "s" };
+    println!(
+        "{count} duplicate{} in {} group{}",
+        plural(count),
+        groups.len(),
+        plural(groups.len())
+    );
+    Ok(())
+}
+
+/// Replace `duplicate` by a hard link to `kept` if their bytes are the same
+fn link(kept: &Path, duplicate: &Path) -> Result<String> {
+    if hash::file_sha256(kept)? != hash::file_sha256(duplicate)? {
+        return Ok(" (headers differ, not linked)".to_string());
+    }
+
+    // Link under a temporary name first, so the duplicate is replaced in one
+    // step and never lost
+    let mut temporary = duplicate.as_os_str().to_owned();
+    temporary.push(".dcmv-link");
+    let temporary = PathBuf::from(temporary);
+    fs::hard_link(kept, &temporary).with_context(|| {
+        format!(
+            "Failed to link {} to {}",
+            temporary.display(),
+            kept.display()
+        )
+    })?;
+    fs::rename(&temporary, duplicate).with_context(|| {
+        let _ = fs::remove_file(&temporary);
+        format!("Failed to replace {}", duplicate.display())
+    })?;
+    Ok(" (linked)".to_string())
+}
+
+/// Move `duplicate` from below `dir` to the same path below `target`
+fn move_into(dir: &Path, duplicate: &Path, target: &Path) -> Result<String> {
+    let relative = duplicate.strip_prefix(dir).unwrap_or(duplicate);
+    let destination = target.join(relative);
+    if destination.exists() {
+        bail!("{} already exists", destination.display());
+    }
+    if let Some(parent) = destination.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    fs::rename(duplicate, &destination).with_context(|| {
+        format!(
+            "Failed to move {} to {}",
+            duplicate.display(),
+            destination.display()
+        )
+    })?;
+    Ok(format!(" -> {}", destination.display()))
+}
+
+fn pixel_hash(instance: &InstanceEntry) -> Option<String> {
+    let obj = dicom::open_dicom_file(&instance.path).ok()?;
+    let metadata = dicom::extract_dicom_data(&obj).ok()?;
+    hash::pixel_sha256(&metadata).ok()
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Reason {
+    Uid,
+    Pixels,
+}
+
+/// Disjoint sets of files, each with the reasons its files were joined
+struct Groups {
+    parent: Vec<usize>,
+    same_uid: Vec<bool>,
+    same_pixels: Vec<bool>,
+}
+
+impl Groups {
+    fn new(len: usize) -> Self {
+        Self {
+            parent: (0..len).collect(),
+            same_uid: vec![false; len],
+            same_pixels: vec![false; len],
+        }
+    }
+
+    fn root(&mut self, mut i: usize) -> usize {
+        while self.parent[i] != i {
+            self.parent[i] = self.parent[self.parent[i]];
+            i = self.parent[i];
+        }
+        i
+    }
+
+    /// Join the groups of `a` and `b`; the smaller index stays the root, so
+    /// the root is the first file in name order
+    fn join(&mut self, a: usize, b: usize, reason: Reason) {
+        let (a, b) = (self.root(a), self.root(b));
+        let (root, other) = (a.min(b), a.max(b));
+        self.parent[other] = root;
+        self.same_uid[root] |= self.same_uid[other] || matches!(reason, Reason::Uid);
+        self.same_pixels[root] |= self.same_pixels[other] || matches!(reason, Reason::Pixels);
+    }
+
+    /// Groups of more than one file, in name order of their kept file
+    fn collect(mut self, instances: &[InstanceEntry]) -> Vec<Duplicates> {
+        let mut groups: Vec<Option<Duplicates>> = vec![None; instances.len()];
+        for (i, instance) in instances.iter().enumerate() {
+            let root = self.root(i);
+            if root == i {
+                continue;
+            }
+            groups[root]
+                .get_or_insert_with(|| Duplicates {
+                    kept: instances[root].path.clone(),
+                    duplicates: Vec::new(),
+                    same_uid: self.same_uid[root],
+                    same_pixels: self.same_pixels[root],
+                })
+                .duplicates
+                .push(instance.path.clone());
+        }
+        groups.into_iter().flatten().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestDataset;
+
+    fn write(path: &Path, uid: &str, pixels: &[u16]) {
+        TestDataset::gray16(uid, 2, 2, pixels).write(path);
+    }
+
+    #[test]
+    fn test_duplicates_by_uid_and_pixels() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = |name: &str| dir.path().join(name);
+        write(&path("a.dcm"), "1.2.3.1", &[1, 2, 3, 4]);
+        // Exported again under another name
+        write(&path("b.dcm"), "1.2.3.1", &[1, 2, 3, 4]);
+        // Same image with a new UID
+        write(&path("c.dcm"), "1.2.3.9", &[1, 2, 3, 4]);
+        write(&path("d.dcm"), "1.2.3.2", &[5, 6, 7, 8]);
+
+        let groups = find_duplicates(dir.path(), None).unwrap();
+        assert_eq!(
+            groups,
+            [Duplicates {
+                kept: path("a.dcm"),
+                duplicates: vec![path("b.dcm"), path("c.dcm")],
+                same_uid: true,
+                same_pixels: true,
+            }]
+        );
+
+        assert_eq!(link(&path("a.dcm"), &path("b.dcm")).unwrap(), " (linked)");
+        assert_eq!(
+            link(&path("a.dcm"), &path("c.dcm")).unwrap(),
+            " (headers differ, not linked)"
+        );
+    }
+}
@@ -0,0 +1,295 @@
+//! Text in character sets with ISO 2022 code extensions
+//!
+//! dicom-rs decodes text with the first value of Specific Character Set
+//! (0008,0005) only. Objects that list more than one value switch character
+//! sets within a value through escape sequences (PS3.5 section 6.1.2.5),
+//! which is how Japanese, Korean and Chinese names are usually encoded. Such
+//! values are turned back into their bytes and decoded again here.
+
+use dicom::dictionary_std::tags;
+use dicom::encoding::text::{SpecificCharacterSet, TextCodec};
+use dicom::object::InMemDicomObject;
+
+const ESC: u8 = 0x1B;
+
+/// Escape sequence (ISO 2022 IR 87) that starts JIS X 0208 text
+const JIS_X_0208: &[u8] = b"\x1b$B";
+
+/// Escape sequence (ISO 2022 IR 159) that starts JIS X 0212 text
+const JIS_X_0212: &[u8] = b"\x1b$(D";
+
+/// A character set invoked for bytes below or above 0x80
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CharacterSet {
+    Ascii,
+    /// Decoded with a dicom-rs codec, given by its Specific Character Set code
+    Codec(&'static str),
+    /// 7-bit two-byte Japanese text, introduced by its escape sequence
+    Jis(&'static [u8]),
+}
+
+/// Character sets designated to G0 (bytes below 0x80) and G1 (above)
+type Designations = (CharacterSet, Option<CharacterSet>);
+
+/// Designations made by the escape sequences that dicom-rs can decode,
+/// without the leading ESC
+const ESCAPE_SEQUENCES: [(&[u8], Designations); 16] = [
+    (b"(B", (CharacterSet::Ascii, None)),
+    (b"(J", (CharacterSet::Ascii, None)),
+    (b"$B", (CharacterSet::Jis(JIS_X_0208), None)),
+    (b"$@", (CharacterSet::Jis(JIS_X_0208), None)),
+    (b"$(D", (CharacterSet::Jis(JIS_X_0212), None)),
+    (
+        b")I",
+        (CharacterSet::Ascii, Some(CharacterSet::Codec("ISO_IR 13"))),
+    ),
+    (
+        b"$)C",
+        (CharacterSet::Ascii, Some(CharacterSet::Codec("ISO_IR 149"))),
+    ),
+    (
+        b"$)A",
+        (CharacterSet::Ascii, Some(CharacterSet::Codec("GB2312"))),
+    ),
+    (
+        b"-A",
+        (CharacterSet::Ascii, Some(CharacterSet::Codec("ISO_IR 100"))),
+    ),
+    (
+        b"-B",
+        (CharacterSet::Ascii, Some(CharacterSet::Codec("ISO_IR 101"))),
+    ),
+    (
+        b"-C",
+        (CharacterSet::Ascii, Some(CharacterSet::Codec("ISO_IR 109"))),
+    ),
+    (
+        b"-D",
+        (CharacterSet::Ascii, Some(CharacterSet::Codec("ISO_IR 110"))),
+    ),
+    (
+        b"-F",
+        (CharacterSet::Ascii, Some(CharacterSet::Codec("ISO_IR 126"))),
+    ),
+    (
+        b"-G",
+        (CharacterSet::Ascii, Some(CharacterSet::Codec("ISO_IR 127"))),
+    ),
+    (
+        b"-H",
+        (CharacterSet::Ascii, Some(CharacterSet::Codec("ISO_IR 138"))),
+    ),
+    (
+        b"-L",
+        (CharacterSet::Ascii, Some(CharacterSet::Codec("ISO_IR 144"))),
+    ),
+];
+
+/// Designations of the first Specific Character Set value, active at the
+/// start of each value and after each delimiter
+fn initial_designations(code: &str) -> Designations {
+    let number = code
+        .trim()
+        .trim_start_matches("ISO 2022 IR ")
+        .trim_start_matches("ISO_IR ");
+
+    let g1 = match number {
+        "13" => "ISO_IR 13",
+        "58" => "GB2312",
+        "100" => "ISO_IR 100",
+        "101" => "ISO_IR 101",
+        "109" => "ISO_IR 109",
+        "110" => "ISO_IR 110",
+        "126" => "ISO_IR 126",
+        "127" => "ISO_IR 127",
+        "138" => "ISO_IR 138",
+        "144" => "ISO_IR 144",
+        "149" => "ISO_IR 149",
+        "166" => "ISO_IR 166",
+        _ => return (CharacterSet::Ascii, None),
+    };
+    (CharacterSet::Ascii, Some(CharacterSet::Codec(g1)))
+}
+
+/// Decodes text of an object according to its Specific Character Set
+#[derive(Debug, Clone, Default)]
+pub struct TextDecoder {
+    /// Codec dicom-rs decoded values with, and the initial designations,
+    /// when the object uses code extensions
+    extensions: Option<(SpecificCharacterSet, Designations)>,
+}
+
+impl TextDecoder {
+    #[must_use]
+    pub fn from_object(obj: &InMemDicomObject) -> Self {
+        let codes = obj
+            .get(tags::SPECIFIC_CHARACTER_SET)
+            .and_then(|e| e.to_multi_str().ok())
+            .unwrap_or_default();
+
+        Self::from_codes(&codes)
+    }
+
+    fn from_codes(codes: &[impl AsRef<str>]) -> Self {
+        // A single character set is decoded correctly by dicom-rs
+        let extensions = match codes {
+            [first, _, ..] => {
+                let first = first.as_ref().trim();
+                let decoded_with = SpecificCharacterSet::from_code(first).unwrap_or_default();
+                Some((decoded_with, initial_designations(first)))
+            }
+            _ => None,
+        };
+
+        Self { extensions }
+    }
+
+    /// Text value as it should read
+    #[must_use]
+    pub fn decode(&self, text: &str) -> String {
+        self.decode_with_delimiters(text, b"\\")
+    }
+
+    /// Person name value, whose components and component groups start
+    /// with the initial character sets again
+    #[must_use]
+    pub fn decode_person_name(&self, text: &str) -> String {
+        self.decode_with_delimiters(text, b"\\^=")
+    }
+
+    fn decode_with_delimiters(&self, text: &str, delimiters: &[u8]) -> String {
+        let Some((decoded_with, initial)) = &self.extensions else {
+            return text.to_string();
+        };
+
+        // Plain ASCII needs no second look
+        if text.bytes().all(|b| b.is_ascii() && b != ESC) {
+            return text.to_string();
+        }
+
+        match decoded_with.encode(text) {
+            Ok(bytes) => decode_bytes(&bytes, *initial, delimiters),
+            Err(_) => text.to_string(),
+        }
+    }
+}
+
+/// Decode bytes with ISO 2022 escape sequences
+///
+/// Delimiters (and control characters ending a line) restore the initial
+/// designations, as the standard requires of encoders.
+fn decode_bytes(bytes: &[u8], initial: Designations, delimiters: &[u8]) -> String {
+    let (mut g0, mut g1) = initial;
+    let mut text = String::new();
+    let mut run: Vec<u8> = Vec::new();
+    let mut run_set = CharacterSet::Ascii;
+
+    let mut i = 0;
+    while i < bytes.len() {
+        let byte = bytes[i];
+
+        if byte == ESC
+            && let Some((sequence, (new_g0, new_g1))) = ESCAPE_SEQUENCES
+                .iter()
+                .find(|(sequence, _)| bytes[i + 1..].starts_with(sequence))
+        {
+            // G1-only designations leave G0 as it is
+            if new_g1.is_some() {
+                g1 = *new_g1;
+            } else {
+                g0 = *new_g0;
+            }
+            i += 1 + sequence.len();
+            continue;
+        }
+
+        // Inside two-byte text these bytes are halves of characters
+        if g0 == CharacterSet::Ascii
+            && (delimiters.contains(&byte) || matches!(byte, b'\r' | b'\n' | b'\t' | 0x0C))
+        {
+            flush(&mut text, &mut run, run_set);
+            text.push(char::from(byte));
+            (g0, g1) = initial;
+            i += 1;
+            continue;
+        }
+
+        let set = if byte < 0x80 {
+            g0
+        } else {
+            g1.unwrap_or(CharacterSet::Codec("ISO_IR 100"))
+        };
+        if set != run_set {
+            flush(&mut text, &mut run, run_set);
+            run_set = set;
+        }
+        run.push(byte);
+        i += 1;
+    }
+
+    flush(&mut text, &mut run, run_set);
+    text
+}
+
+/// Decode the pending bytes of one character set and append them
+fn flush(text: &mut String, run: &mut Vec<u8>, set: CharacterSet) {
+    if run.is_empty() {
+        return;
+    }
+
+    let decoded = match set {
+        CharacterSet::Ascii => Ok(String::from_utf8_lossy(run).into_owned()),
+        CharacterSet::Codec(code) => SpecificCharacterSet::from_code(code)
+            .unwrap_or_default()
+            .decode(run),
+        CharacterSet::Jis(escape) => {
+            let bytes = [escape, run.as_slice(), b"\x1b(B"].concat();
+            SpecificCharacterSet::from_code("ISO_IR 87")
+                .unwrap_or_default()
+                .decode(&bytes)
+        }
+    };
+
+    text.push_str(&decoded.unwrap_or_else(|_| "\u{FFFD}".to_string()));
+    run.clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Text as dicom-rs hands it over when the first value is ISO-IR 6:
+    /// each byte as the Latin-1 character of the same code
+    fn as_latin1(bytes: &[u8]) -> String {
+        bytes.iter().map(|&b| char::from(b)).collect()
+    }
+
+    #[test]
+    fn test_decode_japanese_person_name() {
+        // PS3.5 H.3.1: Yamada^Tarou=山田^太郎=やまだ^たろう
+        let bytes =
+            b"Yamada^Tarou=\x1b$B;3ED\x1b(B^\x1b$BB@O:\x1b(B=\x1b$B$d$^$@\x1b(B^\x1b$B$?$m$&\x1b(B";
+        let decoder = TextDecoder::from_codes(&["", "ISO 2022 IR 87"]);
+
+        assert_eq!(
+            decoder.decode_person_name(&as_latin1(bytes)),
+            "Yamada^Tarou=山田^太郎=やまだ^たろう"
+        );
+    }
+
+    #[test]
+    fn test_decode_korean_person_name() {
+        // PS3.5 I.2: Hong^Gildong=洪^吉洞=홍^길동
+        let bytes = b"Hong^Gildong=\x1b$)C\xfb\xf3^\x1b$)C\xd1\xce\xd4\xd7=\x1b$)C\xc8\xab^\x1b$)C\xb1\xe6\xb5\xbf";
+        let decoder = TextDecoder::from_codes(&["", "ISO 2022 IR 149"]);
+
+        assert_eq!(
+            decoder.decode_person_name(&as_latin1(bytes)),
+            "Hong^Gildong=洪^吉洞=홍^길동"
+        );
+
+        // A single character set is left to dicom-rs
+        let decoder = TextDecoder::from_codes(&["ISO_IR 192"]);
+        assert_eq!(decoder.decode_person_name("Müller"), "Müller");
+    }
+}
@@ -1,4 +1,5 @@
 use crate::dicom::DicomMetadata;
+use std::fmt;
 use thiserror::Error;
 
 /// Error type that preserves metadata when available
@@ -9,9 +10,13 @@ pub enum ProcessError {
     #[error("{0}")]
     NotADicomFile(#[from] anyhow::Error),
 
-    /// Valid DICOM file but extraction failed - no metadata available
-    #[error("{0}")]
-    ExtractionFailed(anyhow::Error),
+    /// Valid DICOM file but extraction failed - partial metadata may be available
+    #[error("{error}")]
+    ExtractionFailed {
+        /// Tags that could still be read, without pixel data
+        metadata: Option<Box<DicomMetadata>>,
+        error: anyhow::Error,
+    },
 
     /// Metadata extracted successfully, but image conversion failed
     #[error("Image conversion failed: {error}")]
@@ -20,6 +25,13 @@ pub enum ProcessError {
         error: anyhow::Error,
     },
 
+    /// Frame decoded but writing the output file failed
+    #[error("Export failed: {error}")]
+    ExportFailed {
+        metadata: Box<DicomMetadata>,
+        error: anyhow::Error,
+    },
+
     /// Image ready but display failed
     #[error("Display failed: {error}")]
     DisplayFailed {
@@ -33,9 +45,58 @@ impl ProcessError {
     #[must_use]
     pub fn metadata(&self) -> Option<&DicomMetadata> {
         match self {
+            ProcessError::ExtractionFailed { metadata, .. } => metadata.as_deref(),
             ProcessError::ConversionFailed { metadata, .. }
+            | ProcessError::ExportFailed { metadata, .. }
             | ProcessError::DisplayFailed { metadata, .. } => Some(metadata),
-            _ => None,
+            ProcessError::NotADicomFile(_) => None,
+        }
+    }
+
+    /// Stable identifier of the error variant, for machine-readable output
+    #[must_use]
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ProcessError::NotADicomFile(_) => "not_a_dicom_file",
+            ProcessError::ExtractionFailed { .. } => "extraction_failed",
+            ProcessError::ConversionFailed { .. } => "conversion_failed",
+            ProcessError::ExportFailed { .. } => "export_failed",
+            ProcessError::DisplayFailed { .. } => "display_failed",
+        }
+    }
+
+    /// Processing stage that failed
+    #[must_use]
+    pub fn stage(&self) -> Stage {
+        match self {
+            ProcessError::NotADicomFile(_) => Stage::Read,
+            ProcessError::ExtractionFailed { .. } => Stage::Extraction,
+            ProcessError::ConversionFailed { .. } => Stage::Conversion,
+            ProcessError::ExportFailed { .. } => Stage::Export,
+            ProcessError::DisplayFailed { .. } => Stage::Display,
         }
     }
 }
+
+/// Stages of processing a file, in the order they run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Stage {
+    Read,
+    Extraction,
+    Conversion,
+    Export,
+    Display,
+}
+
+impl fmt::Display for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Stage::Read => "read",
+            Stage::Extraction => "extraction",
+            Stage::Conversion => "conversion",
+            Stage::Export => "export",
+            Stage::Display => "display",
+        };
+        f.write_str(name)
+    }
+}
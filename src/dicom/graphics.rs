@@ -0,0 +1,392 @@
+//! Text and graphic objects of the Graphic Annotation Sequence
+//!
+//! Some secondary captures carry their measurements and labels as graphic
+//! annotations instead of burning them into the pixels, and Grayscale
+//! Softcopy Presentation States hold them for the images they reference.
+//! They are drawn with `--annotations` or `--presentation-state`.
+
+use super::{DicomMetadata, DicomObject, open_dicom_file};
+use crate::types::{GraphicAnnotation, GraphicShape};
+use anyhow::{Result, bail};
+use dicom::dictionary_std::tags;
+use dicom::object::InMemDicomObject;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// A presentation state given with `--presentation-state`, whose graphic
+/// annotations are drawn over the images it references
+#[derive(Debug, Clone)]
+pub struct PresentationState {
+    obj: DicomObject,
+}
+
+impl PresentationState {
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read or has no graphic
+    /// annotations
+    pub fn open(path: &Path) -> Result<Self> {
+        let obj = open_dicom_file(path)?;
+        if obj.get(tags::GRAPHIC_ANNOTATION_SEQUENCE).is_none() {
+            bail!("{} has no Graphic Annotation Sequence", path.display());
+        }
+        Ok(Self { obj })
+    }
+
+    /// `metadata` with the annotations of this presentation state for its
+    /// image added
+    #[must_use]
+    pub fn apply(&self, metadata: &DicomMetadata) -> DicomMetadata {
+        let mut annotated = metadata.clone();
+        annotated
+            .graphic_annotations
+            .extend(extract_graphic_annotations(
+                &self.obj,
+                metadata.sop_instance_uid.as_deref(),
+                (f64::from(metadata.cols()), f64::from(metadata.rows())),
+            ));
+        annotated
+    }
+}
+
+/// The graphic annotations of `obj` that apply to the image `sop_instance_uid`
+///
+/// Annotations that reference other images are left out; those without
+/// references apply to any image. `size` is the (width, height) of the
+/// image in pixels, which DISPLAY coordinates are fractions of.
+#[must_use]
+pub fn extract_graphic_annotations(
+    obj: &InMemDicomObject,
+    sop_instance_uid: Option<&str>,
+    size: (f64, f64),
+) -> Vec<GraphicAnnotation> {
+    let Some(items) = obj
+        .get(tags::GRAPHIC_ANNOTATION_SEQUENCE)
+        .and_then(|e| e.items())
+    else {
+        return Vec::new();
+    };
+    let layer_colors = layer_colors(obj);
+
+    let mut annotations = Vec::new();
+    for item in items {
+        let Some(frames) = referenced_frames(item, sop_instance_uid) else {
+            continue;
+        };
+        let layer_color =
+            string(item, tags::GRAPHIC_LAYER).and_then(|layer| layer_colors.get(&layer).copied());
+
+        for text in sequence(item, tags::TEXT_OBJECT_SEQUENCE) {
+            let Some(value) = string(text, tags::UNFORMATTED_TEXT_VALUE) else {
+                continue;
+            };
+            let position = point(text, tags::BOUNDING_BOX_TOP_LEFT_HAND_CORNER)
+                .map(|p| to_pixels(p, text, tags::BOUNDING_BOX_ANNOTATION_UNITS, size))
+                .or_else(|| {
+                    point(text, tags::ANCHOR_POINT)
+                        .map(|p| to_pixels(p, text, tags::ANCHOR_POINT_ANNOTATION_UNITS, size))
+                });
+            let Some(position) = position else {
+                continue;
+            };
+            annotations.push(GraphicAnnotation {
+                frames: frames.clone(),
+                color: style_color(
+                    text,
+                    tags::TEXT_STYLE_SEQUENCE,
+                    tags::TEXT_COLOR_CIE_LAB_VALUE,
+                )
+                .or(layer_color),
+                shape: GraphicShape::Text {
+                    text: value,
+                    position,
+                },
+            });
+        }
+
+        for graphic in sequence(item, tags::GRAPHIC_OBJECT_SEQUENCE) {
+            let Some(shape) = graphic_shape(graphic, size) else {
+                continue;
+            };
+            annotations.push(GraphicAnnotation {
+                frames: frames.clone(),
+                color: style_color(
+                    graphic,
+                    tags::LINE_STYLE_SEQUENCE,
+                    tags::PATTERN_ON_COLOR_CIE_LAB_VALUE,
+                )
+                .or(layer_color),
+                shape,
+            });
+        }
+    }
+    annotations
+}
+
+/// Frames of the image an annotation item applies to (all when empty), or
+/// `None` if it references only other images
+fn referenced_frames(item: &InMemDicomObject, sop_instance_uid: Option<&str>) -> Option<Vec<u32>> {
+    let references = sequence(item, tags::REFERENCED_IMAGE_SEQUENCE);
+    if references.is_empty() {
+        return Some(Vec::new());
+    }
+    let Some(uid) = sop_instance_uid else {
+        return Some(Vec::new());
+    };
+
+    let reference = references.iter().find(|reference| {
+        string(reference, tags::REFERENCED_SOP_INSTANCE_UID).as_deref() == Some(uid)
+    })?;
+    Some(
+        reference
+            .get(tags::REFERENCED_FRAME_NUMBER)
+            .and_then(|e| e.to_multi_int::<u32>().ok())
+            .unwrap_or_default(),
+    )
+}
+
+fn graphic_shape(graphic: &InMemDicomObject, size: (f64, f64)) -> Option<GraphicShape> {
+    let data = graphic.get(tags::GRAPHIC_DATA)?.to_multi_float64().ok()?;
+    let points: Vec<(f64, f64)> = data
+        .chunks_exact(2)
+        .map(|pair| {
+            to_pixels(
+                (pair[0], pair[1]),
+                graphic,
+                tags::GRAPHIC_ANNOTATION_UNITS,
+                size,
+            )
+        })
+        .collect();
+
+    match string(graphic, tags::GRAPHIC_TYPE)?.as_str() {
+        "POINT" => Some(GraphicShape::Point(*points.first()?)),
+        "POLYLINE" | "INTERPOLATED" if points.len() >= 2 => Some(GraphicShape::Polyline(points)),
+        "CIRCLE" if points.len() >= 2 => {
+            let (center, edge) = (points[0], points[1]);
+            Some(GraphicShape::Circle {
+                center,
+                radius: (edge.0 - center.0).hypot(edge.1 - center.1),
+            })
+        }
+        "ELLIPSE" if points.len() >= 4 => Some(GraphicShape::Ellipse {
+            major: [points[0], points[1]],
+            minor: [points[2], points[3]],
+        }),
+        _ => None,
+    }
+}
+
+/// A point in PIXEL units, from PIXEL or DISPLAY units as `units_tag` says
+///
+/// DISPLAY units are taken as fractions of the whole image.
+fn to_pixels(
+    point: (f64, f64),
+    item: &InMemDicomObject,
+    units_tag: dicom::core::Tag,
+    (width, height): (f64, f64),
+) -> (f64, f64) {
+    if string(item, units_tag).as_deref() == Some("DISPLAY") {
+        (point.0 * width, point.1 * height)
+    } else {
+        point
+    }
+}
+
+/// Recommended colors of the graphic layers, by layer name
+fn layer_colors(obj: &InMemDicomObject) -> HashMap<String, [u8; 3]> {
+    sequence(obj, tags::GRAPHIC_LAYER_SEQUENCE)
+        .iter()
+        .filter_map(|layer| {
+            let name = string(layer, tags::GRAPHIC_LAYER)?;
+            let color = cielab(layer, tags::GRAPHIC_LAYER_RECOMMENDED_DISPLAY_CIE_LAB_VALUE)
+                .or_else(|| {
+                    let gray = layer
+                        .get(tags::GRAPHIC_LAYER_RECOMMENDED_DISPLAY_GRAYSCALE_VALUE)?
+                        .to_int::<u16>()
+                        .ok()?;
+                    let level = (gray >> 8) as u8;
+                    Some([level; 3])
+                })?;
+            Some((name, color))
+        })
+        .collect()
+}
+
+/// Color in the first item of the style sequence `sequence_tag`
+fn style_color(
+    item: &InMemDicomObject,
+    sequence_tag: dicom::core::Tag,
+    color_tag: dicom::core::Tag,
+) -> Option<[u8; 3]> {
+    sequence(item, sequence_tag)
+        .first()
+        .and_then(|style| cielab(style, color_tag))
+}
+
+fn cielab(item: &InMemDicomObject, tag: dicom::core::Tag) -> Option<[u8; 3]> {
+    let values = item.get(tag)?.to_multi_int::<u16>().ok()?;
+    match values.as_slice() {
+        &[l, a, b] => Some(cielab_to_rgb(l, a, b)),
+        _ => None,
+    }
+}
+
+/// sRGB of a DICOM encoded CIELab color: L* from 0 to 100 and a*, b* from
+/// -128 to 127 scaled to 0-65535, with a D65 white point
+fn cielab_to_rgb(l: u16, a: u16, b: u16) -> [u8; 3] {
+    let l = f64::from(l) * 100.0 / 65535.0;
+    let a = f64::from(a) * 255.0 / 65535.0 - 128.0;
+    let b = f64::from(b) * 255.0 / 65535.0 - 128.0;
+
+    let fy = (l + 16.0) / 116.0;
+    let (fx, fz) = (fy + a / 500.0, fy - b / 200.0);
+    let inverse = |t: f64| {
+        if t > 6.0 / 29.0 {
+            t.powi(3)
+        } else {
+            3.0 * (6.0_f64 / 29.0).powi(2) * (t - 4.0 / 29.0)
+        }
+    };
+    let (x, y, z) = (0.950_47 * inverse(fx), inverse(fy), 1.088_83 * inverse(fz));
+
+    let linear = [
+        3.240_6 * x - 1.537_2 * y - 0.498_6 * z,
+        -0.968_9 * x + 1.875_8 * y + 0.041_5 * z,
+        0.055_7 * x - 0.204_0 * y + 1.057_0 * z,
+    ];
+    linear.map(|c| {
+        let c = c.clamp(0.0, 1.0);
+        let encoded = if c <= 0.003_130_8 {
+            12.92 * c
+        } else {
+            1.055 * c.powf(1.0 / 2.4) - 0.055
+        };
+        (encoded * 255.0).round() as u8
+    })
+}
+
+fn sequence(item: &InMemDicomObject, tag: dicom::core::Tag) -> &[InMemDicomObject] {
+    item.get(tag).and_then(|e| e.items()).unwrap_or_default()
+}
+
+fn string(item: &InMemDicomObject, tag: dicom::core::Tag) -> Option<String> {
+    item.get(tag)
+        .and_then(|e| e.to_str().ok())
+        .map(|s| {
+            s.trim_matches(|c: char| c.is_whitespace() || c == '\0')
+                .to_string()
+        })
+        .filter(|s| !s.is_empty())
+}
+
+fn point(item: &InMemDicomObject, tag: dicom::core::Tag) -> Option<(f64, f64)> {
+    match item.get(tag)?.to_multi_float64().ok()?.as_slice() {
+        &[x, y] => Some((x, y)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dicom::core::value::{DataSetSequence, PrimitiveValue};
+    use dicom::core::{DataElement, VR};
+
+    #[test]
+    fn test_graphic_annotations_of_referenced_frames() {
+        let mut circle = InMemDicomObject::new_empty();
+        circle.put(DataElement::new(tags::GRAPHIC_TYPE, VR::CS, "CIRCLE"));
+        circle.put(DataElement::new(
+            tags::GRAPHIC_ANNOTATION_UNITS,
+            VR::CS,
+            "DISPLAY",
+        ));
+        circle.put(DataElement::new(
+            tags::GRAPHIC_DATA,
+            VR::FL,
+            PrimitiveValue::F32(vec![0.5, 0.5, 0.75, 0.5].into()),
+        ));
+        let mut text = InMemDicomObject::new_empty();
+        text.put(DataElement::new(
+            tags::UNFORMATTED_TEXT_VALUE,
+            VR::ST,
+            "Lesion",
+        ));
+        text.put(DataElement::new(
+            tags::ANCHOR_POINT_ANNOTATION_UNITS,
+            VR::CS,
+            "PIXEL",
+        ));
+        text.put(DataElement::new(
+            tags::ANCHOR_POINT,
+            VR::FL,
+            PrimitiveValue::F32([10.0, 20.0].into()),
+        ));
+        let mut reference = InMemDicomObject::new_empty();
+        reference.put(DataElement::new(
+            tags::REFERENCED_SOP_INSTANCE_UID,
+            VR::UI,
+            "1.2.3",
+        ));
+        reference.put(DataElement::new(tags::REFERENCED_FRAME_NUMBER, VR::IS, "2"));
+
+        let mut item = InMemDicomObject::new_empty();
+        item.put(DataElement::new(tags::GRAPHIC_LAYER, VR::CS, "MARKS"));
+        item.put(DataElement::new(
+            tags::REFERENCED_IMAGE_SEQUENCE,
+            VR::SQ,
+            DataSetSequence::from(vec![reference]),
+        ));
+        item.put(DataElement::new(
+            tags::GRAPHIC_OBJECT_SEQUENCE,
+            VR::SQ,
+            DataSetSequence::from(vec![circle]),
+        ));
+        item.put(DataElement::new(
+            tags::TEXT_OBJECT_SEQUENCE,
+            VR::SQ,
+            DataSetSequence::from(vec![text]),
+        ));
+        let mut layer = InMemDicomObject::new_empty();
+        layer.put(DataElement::new(tags::GRAPHIC_LAYER, VR::CS, "MARKS"));
+        // L* 100, a* and b* 0: white
+        layer.put(DataElement::new(
+            tags::GRAPHIC_LAYER_RECOMMENDED_DISPLAY_CIE_LAB_VALUE,
+            VR::US,
+            PrimitiveValue::U16(vec![65535, 0x8080, 0x8080].into()),
+        ));
+        let mut obj = InMemDicomObject::new_empty();
+        obj.put(DataElement::new(
+            tags::GRAPHIC_ANNOTATION_SEQUENCE,
+            VR::SQ,
+            DataSetSequence::from(vec![item]),
+        ));
+        obj.put(DataElement::new(
+            tags::GRAPHIC_LAYER_SEQUENCE,
+            VR::SQ,
+            DataSetSequence::from(vec![layer]),
+        ));
+
+        assert!(extract_graphic_annotations(&obj, Some("9.9"), (200.0, 100.0)).is_empty());
+
+        let annotations = extract_graphic_annotations(&obj, Some("1.2.3"), (200.0, 100.0));
+        assert_eq!(annotations.len(), 2);
+        assert_eq!(annotations[0].frames, [2]);
+        assert_eq!(annotations[0].color, Some([255, 255, 255]));
+        assert_eq!(
+            annotations[0].shape,
+            GraphicShape::Text {
+                text: "Lesion".to_string(),
+                position: (10.0, 20.0)
+            }
+        );
+        assert_eq!(
+            annotations[1].shape,
+            GraphicShape::Circle {
+                center: (100.0, 50.0),
+                radius: 50.0
+            }
+        );
+    }
+}
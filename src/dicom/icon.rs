@@ -0,0 +1,93 @@
+//! Icon Image Sequence thumbnails
+//!
+//! Many instances embed a small icon of the image in the Icon Image
+//! Sequence (0088,0200). It precedes the pixel data, so it can be shown
+//! after reading only the file header.
+
+use super::DicomObject;
+use dicom::core::value::Value;
+use dicom::dictionary_std::{tags, uids};
+use dicom::encoding::TransferSyntaxIndex;
+use dicom::object::InMemDicomObject;
+use dicom::transfer_syntax::TransferSyntaxRegistry;
+
+/// Group of the Image Pixel module and other pixel presentation attributes
+const IMAGE_PIXEL_GROUP: u16 = 0x0028;
+
+/// The embedded icon as an object of its own, if the file has one
+///
+/// The icon replaces the image pixel attributes (group 0028 and the pixel
+/// data) of `obj`; patient, study and series attributes are kept. Returns
+/// `None` without an icon, or when the icon's pixel data is compressed.
+#[must_use]
+pub fn icon_image(obj: &DicomObject) -> Option<DicomObject> {
+    let icon = obj.get(tags::ICON_IMAGE_SEQUENCE)?.items()?.first()?;
+
+    // Compressed icons would need the file's codec, for a few bytes saved
+    if !matches!(icon.get(tags::PIXEL_DATA)?.value(), Value::Primitive(_)) {
+        return None;
+    }
+
+    let kept = obj.iter().filter(|element| {
+        let tag = element.header().tag;
+        tag.group() != IMAGE_PIXEL_GROUP
+            && tag != tags::PIXEL_DATA
+            && tag != tags::ICON_IMAGE_SEQUENCE
+    });
+    let mut dataset = InMemDicomObject::from_element_iter(kept.cloned());
+    for element in icon.iter() {
+        dataset.put(element.clone());
+    }
+
+    // The icon's pixel data is native even in compressed files
+    let mut meta = obj.meta().clone();
+    meta.set_transfer_syntax(TransferSyntaxRegistry.get(uids::EXPLICIT_VR_LITTLE_ENDIAN)?);
+
+    Some(dataset.with_exact_meta(meta))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestDataset;
+    use dicom::core::{DataElement, PrimitiveValue, VR};
+
+    #[test]
+    fn test_icon_image_replaces_pixel_attributes() {
+        let mut icon = InMemDicomObject::new_empty();
+        icon.put(DataElement::new(
+            tags::ROWS,
+            VR::US,
+            PrimitiveValue::from(2_u16),
+        ));
+        icon.put(DataElement::new(
+            tags::PIXEL_DATA,
+            VR::OB,
+            PrimitiveValue::from(vec![1_u8, 2, 3, 4]),
+        ));
+
+        let obj = TestDataset::new("1.2.3.4")
+            .with(tags::PATIENT_NAME, VR::PN, "Doe^Jane")
+            .with(tags::ROWS, VR::US, PrimitiveValue::from(512_u16))
+            .with(tags::RESCALE_SLOPE, VR::DS, "2")
+            .with(
+                tags::ICON_IMAGE_SEQUENCE,
+                VR::SQ,
+                dicom::core::value::DataSetSequence::from(vec![icon]),
+            )
+            .file(uids::JPEG_BASELINE8_BIT);
+
+        let icon = icon_image(&obj).unwrap();
+        assert_eq!(
+            icon.meta().transfer_syntax(),
+            uids::EXPLICIT_VR_LITTLE_ENDIAN
+        );
+        assert_eq!(
+            icon.element(tags::ROWS).unwrap().to_int::<u16>().unwrap(),
+            2
+        );
+        assert!(icon.get(tags::RESCALE_SLOPE).is_none());
+        assert!(icon.get(tags::ICON_IMAGE_SEQUENCE).is_none());
+        assert!(icon.get(tags::PATIENT_NAME).is_some());
+    }
+}
@@ -0,0 +1,374 @@
+//! JPEG 2000 codestream inspection for resolution-progressive decoding
+//!
+//! Large J2K/HTJ2K images (e.g. mammograms) are usually displayed at a small
+//! fraction of their native size. When the codestream is organised so that
+//! each tile-part carries one resolution level (RLCP/RPCL progression with
+//! resolution tile-parts), the higher resolution tile-parts can be dropped
+//! before decoding. The decoder still produces a full-size image, but skips
+//! entropy decoding of detail that would be lost when scaling to the terminal.
+
+use dicom::core::value::{PixelFragmentSequence, Value};
+use dicom::core::{DataElement, VR};
+use dicom::dictionary_std::tags;
+use dicom::transfer_syntax::entries;
+
+use super::DicomObject;
+
+const SOC: u16 = 0xFF4F;
+const SIZ: u16 = 0xFF51;
+const COD: u16 = 0xFF52;
+const SOT: u16 = 0xFF90;
+const EOC: u16 = 0xFFD9;
+
+/// Bytes of the SOT marker segment that starts every tile-part
+const SOT_LENGTH: usize = 12;
+
+/// Progression order declared in the COD marker segment
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProgressionOrder {
+    Lrcp,
+    Rlcp,
+    Rpcl,
+    Pcrl,
+    Cprl,
+}
+
+impl ProgressionOrder {
+    fn from_byte(value: u8) -> Option<Self> {
+        Some(match value {
+            0 => Self::Lrcp,
+            1 => Self::Rlcp,
+            2 => Self::Rpcl,
+            3 => Self::Pcrl,
+            4 => Self::Cprl,
+            _ => return None,
+        })
+    }
+
+    /// Returns true if packets are ordered by resolution first
+    #[inline]
+    #[must_use]
+    pub fn is_resolution_major(self) -> bool {
+        matches!(self, Self::Rlcp | Self::Rpcl)
+    }
+}
+
+/// Location of a tile-part within the codestream
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TilePart {
+    pub offset: usize,
+    pub length: usize,
+    pub tile_index: u16,
+    pub part_index: u8,
+    pub part_count: u8,
+}
+
+/// Main header information of a JPEG 2000 codestream
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodestreamInfo {
+    pub width: u32,
+    pub height: u32,
+    pub decomposition_levels: u8,
+    pub progression_order: ProgressionOrder,
+    pub header_length: usize,
+    pub tile_parts: Vec<TilePart>,
+}
+
+impl CodestreamInfo {
+    /// Returns true if every tile-part holds exactly one resolution level,
+    /// which allows truncating the codestream at tile-part boundaries
+    #[must_use]
+    pub fn has_resolution_tile_parts(&self) -> bool {
+        let resolutions = usize::from(self.decomposition_levels) + 1;
+
+        self.progression_order.is_resolution_major()
+            && !self.tile_parts.is_empty()
+            && self
+                .tile_parts
+                .iter()
+                .all(|tp| usize::from(tp.part_count) == resolutions)
+    }
+}
+
+#[inline]
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    let bytes = data.get(offset..offset + 2)?;
+    Some(u16::from_be_bytes([bytes[0], bytes[1]]))
+}
+
+#[inline]
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+/// Parse the main header and tile-part layout of a raw J2K codestream
+///
+/// Returns `None` if the data is not a raw codestream (e.g. JP2-wrapped) or
+/// if the marker structure is malformed.
+#[must_use]
+pub fn parse_codestream(data: &[u8]) -> Option<CodestreamInfo> {
+    if read_u16(data, 0)? != SOC {
+        return None;
+    }
+
+    let mut offset = 2;
+    let mut size = None;
+    let mut coding = None;
+
+    // Main header: every marker up to the first SOT has a length field
+    loop {
+        let marker = read_u16(data, offset)?;
+        if marker == SOT {
+            break;
+        }
+
+        let length = usize::from(read_u16(data, offset + 2)?);
+        let segment = offset + 4;
+
+        match marker {
+            SIZ => {
+                let x = read_u32(data, segment + 2)?;
+                let y = read_u32(data, segment + 6)?;
+                let x_offset = read_u32(data, segment + 10)?;
+                let y_offset = read_u32(data, segment + 14)?;
+                size = Some((x.saturating_sub(x_offset), y.saturating_sub(y_offset)));
+            }
+            COD => {
+                let order = ProgressionOrder::from_byte(*data.get(segment + 1)?)?;
+                let levels = *data.get(segment + 5)?;
+                coding = Some((order, levels));
+            }
+            _ => {}
+        }
+
+        offset += 2 + length;
+    }
+
+    let header_length = offset;
+    let mut tile_parts = Vec::new();
+
+    while read_u16(data, offset)? == SOT {
+        let tile_index = read_u16(data, offset + 4)?;
+        let psot = read_u32(data, offset + 6)? as usize;
+        let part_index = *data.get(offset + 10)?;
+        let part_count = *data.get(offset + 11)?;
+
+        // Psot == 0 means the tile-part extends to the EOC marker
+        let length = if psot == 0 {
+            data.len().checked_sub(offset + 2)?
+        } else {
+            psot
+        };
+        // Shorter than its own SOT marker segment, so the file is damaged
+        if length < SOT_LENGTH {
+            return None;
+        }
+
+        tile_parts.push(TilePart {
+            offset,
+            length,
+            tile_index,
+            part_index,
+            part_count,
+        });
+
+        offset += length;
+        if offset + 2 > data.len() || read_u16(data, offset)? == EOC {
+            break;
+        }
+    }
+
+    let (width, height) = size?;
+    let (progression_order, decomposition_levels) = coding?;
+
+    Some(CodestreamInfo {
+        width,
+        height,
+        decomposition_levels,
+        progression_order,
+        header_length,
+        tile_parts,
+    })
+}
+
+/// Number of resolution levels that can be discarded while keeping the
+/// image at least `target_width` pixels wide
+#[must_use]
+pub fn reduction_for_width(info: &CodestreamInfo, target_width: u32) -> u8 {
+    let mut reduce = 0;
+    while reduce < info.decomposition_levels && (info.width >> (reduce + 1)) >= target_width {
+        reduce += 1;
+    }
+    reduce
+}
+
+/// Build a codestream containing only the lowest `levels + 1 - reduce`
+/// resolutions by dropping the trailing resolution tile-parts
+///
+/// Returns `None` if the codestream layout does not allow truncation.
+#[must_use]
+pub fn truncate_resolutions(data: &[u8], info: &CodestreamInfo, reduce: u8) -> Option<Vec<u8>> {
+    if reduce == 0 || !info.has_resolution_tile_parts() {
+        return None;
+    }
+
+    let keep = info.decomposition_levels + 1 - reduce;
+
+    let mut output = Vec::with_capacity(data.len());
+    output.extend_from_slice(data.get(..info.header_length)?);
+
+    for tile_part in info.tile_parts.iter().filter(|tp| tp.part_index < keep) {
+        let start = output.len();
+        output.extend_from_slice(data.get(tile_part.offset..tile_part.offset + tile_part.length)?);
+
+        // TNsot = 0: number of tile-parts is not specified in this tile-part
+        output[start + 11] = 0;
+    }
+
+    output.extend_from_slice(&EOC.to_be_bytes());
+    Some(output)
+}
+
+/// Returns true for JPEG 2000 and High-Throughput JPEG 2000 transfer syntaxes
+#[inline]
+#[must_use]
+pub fn is_jpeg2000(uid: &str) -> bool {
+    uid == entries::JPEG_2000_IMAGE_COMPRESSION.uid()
+        || uid == entries::JPEG_2000_IMAGE_COMPRESSION_LOSSLESS_ONLY.uid()
+        || uid == "1.2.840.10008.1.2.4.201" // HTJ2K Lossless
+        || uid == "1.2.840.10008.1.2.4.202" // HTJ2K Lossless RPCL
+        || uid == "1.2.840.10008.1.2.4.203" // HTJ2K
+}
+
+/// Create a copy of a single-frame J2K object whose codestream only holds
+/// the resolutions needed to display it `target_width` pixels wide
+///
+/// Returns `None` whenever the reduction is not applicable, in which case
+/// the original object should be decoded as is.
+#[must_use]
+pub fn reduce_for_display(
+    obj: &DicomObject,
+    transfer_syntax_uid: &str,
+    number_of_frames: u32,
+    target_width: Option<u32>,
+) -> Option<DicomObject> {
+    let target_width = target_width?;
+    if number_of_frames > 1 || !is_jpeg2000(transfer_syntax_uid) {
+        return None;
+    }
+
+    let fragments = obj.element(tags::PIXEL_DATA).ok()?.value().fragments()?;
+    let codestream = fragments.concat();
+
+    let info = parse_codestream(&codestream)?;
+    let reduce = reduction_for_width(&info, target_width);
+    let truncated = truncate_resolutions(&codestream, &info, reduce)?;
+
+    let mut reduced = obj.clone();
+    reduced.put(DataElement::new(
+        tags::PIXEL_DATA,
+        VR::OB,
+        Value::from(PixelFragmentSequence::new_fragments(vec![truncated])),
+    ));
+
+    Some(reduced)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Build a minimal codestream: SIZ + COD + one tile with one tile-part
+    /// per resolution, each tile-part carrying `body` bytes of payload
+    fn synthetic_codestream(levels: u8, order: u8, body: usize) -> Vec<u8> {
+        let mut data = Vec::new();
+        data.extend_from_slice(&SOC.to_be_bytes());
+
+        // SIZ: Lsiz=41 for a single component
+        data.extend_from_slice(&SIZ.to_be_bytes());
+        data.extend_from_slice(&41u16.to_be_bytes());
+        data.extend_from_slice(&0u16.to_be_bytes()); // Rsiz
+        data.extend_from_slice(&4096u32.to_be_bytes()); // Xsiz
+        data.extend_from_slice(&3328u32.to_be_bytes()); // Ysiz
+        data.extend_from_slice(&[0u8; 8]); // XOsiz, YOsiz
+        data.extend_from_slice(&4096u32.to_be_bytes()); // XTsiz
+        data.extend_from_slice(&3328u32.to_be_bytes()); // YTsiz
+        data.extend_from_slice(&[0u8; 8]); // XTOsiz, YTOsiz
+        data.extend_from_slice(&1u16.to_be_bytes()); // Csiz
+        data.extend_from_slice(&[15, 1, 1]); // Ssiz, XRsiz, YRsiz
+
+        // COD: Scod, order, layers, MCT, levels, cblk w/h, style, transform
+        data.extend_from_slice(&COD.to_be_bytes());
+        data.extend_from_slice(&12u16.to_be_bytes());
+        data.extend_from_slice(&[0, order, 0, 1, 0, levels, 4, 4, 0, 1]);
+
+        for part in 0..=levels {
+            data.extend_from_slice(&SOT.to_be_bytes());
+            data.extend_from_slice(&10u16.to_be_bytes());
+            data.extend_from_slice(&0u16.to_be_bytes());
+            data.extend_from_slice(&((14 + body) as u32).to_be_bytes());
+            data.extend_from_slice(&[part, levels + 1]);
+            data.extend_from_slice(&[0xFF, 0x93]); // SOD
+            data.extend(std::iter::repeat_n(part, body));
+        }
+
+        data.extend_from_slice(&EOC.to_be_bytes());
+        data
+    }
+
+    #[test]
+    fn test_parse_codestream_layout() {
+        let data = synthetic_codestream(5, 2, 16);
+        let info = parse_codestream(&data).expect("valid codestream");
+
+        assert_eq!((info.width, info.height), (4096, 3328));
+        assert_eq!(info.decomposition_levels, 5);
+        assert_eq!(info.progression_order, ProgressionOrder::Rpcl);
+        assert_eq!(info.tile_parts.len(), 6);
+        assert!(info.has_resolution_tile_parts());
+    }
+
+    #[test]
+    fn test_short_tile_part_is_rejected() {
+        let mut data = synthetic_codestream(2, 1, 4);
+        let info = parse_codestream(&data).unwrap();
+
+        // Psot of the first tile-part set below the 12 bytes of its SOT segment
+        let psot = info.tile_parts[0].offset + 6;
+        data[psot..psot + 4].copy_from_slice(&4u32.to_be_bytes());
+        assert!(parse_codestream(&data).is_none());
+    }
+
+    #[test]
+    fn test_reduction_for_width() {
+        let info = parse_codestream(&synthetic_codestream(5, 1, 4)).unwrap();
+
+        assert_eq!(reduction_for_width(&info, 5000), 0);
+        assert_eq!(reduction_for_width(&info, 2048), 1);
+        assert_eq!(reduction_for_width(&info, 300), 3);
+        assert_eq!(reduction_for_width(&info, 1), 5);
+    }
+
+    #[test]
+    fn test_truncate_resolutions() {
+        let data = synthetic_codestream(5, 1, 16);
+        let info = parse_codestream(&data).unwrap();
+
+        let truncated = truncate_resolutions(&data, &info, 2).expect("truncatable");
+        let reparsed = parse_codestream(&truncated).expect("still a valid codestream");
+
+        assert_eq!(reparsed.tile_parts.len(), 4);
+        assert!(truncated.ends_with(&EOC.to_be_bytes()));
+        assert!(truncated.len() < data.len());
+    }
+
+    #[test]
+    fn test_layer_progression_is_not_truncated() {
+        let data = synthetic_codestream(5, 0, 16);
+        let info = parse_codestream(&data).unwrap();
+
+        assert!(!info.has_resolution_tile_parts());
+        assert!(truncate_resolutions(&data, &info, 2).is_none());
+    }
+}
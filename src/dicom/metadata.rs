@@ -1,9 +1,16 @@
 use super::photometric::PhotometricInterpretation;
 use super::pixel_data::DecodedPixelData;
+use super::redact::{self, Rect};
+use crate::explain::DecisionLog;
 use crate::types::{
-    BitDepth, Dimensions, PatientInfo, PixelAspectRatio, RescaleParams, SOPClass, SeriesInfo,
-    StudyInfo, TransferSyntax,
+    AcquisitionInfo, BitDepth, Dimensions, FileStamp, GraphicAnnotation, ImageOrientation,
+    LossyCompression, ModalityLut, OverlayPlane, PaletteLut, PatientInfo, PixelAspectRatio,
+    PixelSpacing, PixelValueRange, RescaleParams, RescaleType, SOPClass, SeriesInfo, StudyInfo,
+    TransferSyntax, UltrasoundRegion, ViewInfo, VoiWindow,
 };
+use anyhow::{Result, bail};
+use image::RgbImage;
+use std::path::Path;
 
 #[derive(Debug, Clone)]
 pub struct DicomMetadata {
@@ -14,23 +21,70 @@ pub struct DicomMetadata {
     pub samples_per_pixel: u16,
     pub planar_configuration: Option<u16>,
     pub number_of_frames: u32,
+    /// Time each frame of a multi-frame image is shown, in milliseconds
+    pub frame_time: Option<f64>,
     pub pixel_aspect_ratio: Option<PixelAspectRatio>,
+    pub pixel_spacing: Option<PixelSpacing>,
+    pub pixel_value_range: Option<PixelValueRange>,
+    pub orientation: Option<ImageOrientation>,
+    /// Image Position (Patient): center of the first pixel, in mm
+    pub position: Option<[f64; 3]>,
     pub(crate) pixel_data_format: DecodedPixelData,
 
     // Rescaling parameters
     pub rescale: RescaleParams,
+    /// Replaces `rescale` for grayscale display when present
+    pub modality_lut: Option<ModalityLut>,
+    /// Colors of the stored values of a PALETTE COLOR image
+    pub palette: Option<PaletteLut>,
+    pub rescale_type: Option<RescaleType>,
+    /// Window Center/Width of the file; only a SIGMOID one is applied by
+    /// default
+    pub voi_window: Option<VoiWindow>,
 
     // Grouped metadata
     pub patient: PatientInfo,
     pub study: StudyInfo,
     pub series: SeriesInfo,
+    pub acquisition: AcquisitionInfo,
+    pub view: ViewInfo,
+    pub ultrasound_regions: Vec<UltrasoundRegion>,
+    /// Text and graphics of the Graphic Annotation Sequence, or of a
+    /// presentation state
+    pub graphic_annotations: Vec<GraphicAnnotation>,
+    /// Bitmaps of the overlay groups, from Overlay Data or taken out of the
+    /// pixel data
+    pub overlays: Vec<OverlayPlane>,
 
     // DICOM header
     pub sop_class: Option<SOPClass>,
+    pub sop_instance_uid: Option<String>,
+    pub frame_of_reference_uid: Option<String>,
     pub transfer_syntax: TransferSyntax,
+    pub lossy_compression: Option<LossyCompression>,
+    /// Burned In Annotation: whether the pixels show identifying text such
+    /// as the patient's name, if the file says
+    pub burned_in_annotation: Option<bool>,
+    /// The file the data was read from, as it was then; `None` for stdin
+    pub file_stamp: Option<FileStamp>,
+
+    // Non-fatal problems found while extracting the data
+    pub warnings: Vec<String>,
+
+    // How the pixel data was decoded, for --explain
+    pub decisions: DecisionLog,
 }
 
 impl DicomMetadata {
+    /// The data with the stamp of `file` it was read from, if any
+    #[must_use]
+    pub fn stamped(self, file: Option<&Path>) -> Self {
+        Self {
+            file_stamp: file.and_then(FileStamp::of),
+            ..self
+        }
+    }
+
     #[inline]
     #[must_use]
     pub fn rows(&self) -> u16 {
@@ -55,6 +109,16 @@ impl DicomMetadata {
         self.rescale.intercept
     }
 
+    /// Modality value (e.g. HU) of a stored value, through the Modality LUT
+    /// when there is one
+    #[must_use]
+    pub fn rescaled_value(&self, stored: f64) -> f64 {
+        match &self.modality_lut {
+            Some(lut) if self.bits_allocated() != 32 => lut.apply(stored as i32),
+            _ => stored.mul_add(self.rescale.slope, self.rescale.intercept),
+        }
+    }
+
     /// Returns true if this DICOM file uses big-endian byte order
     #[inline]
     #[must_use]
@@ -74,6 +138,300 @@ impl DicomMetadata {
         }
     }
 
+    /// Copy of the tags with other pixel data, without cloning the current data
+    fn with_pixel_data(&self, data: Box<[u8]>, dimensions: Dimensions, frames: u32) -> Self {
+        let pixel_data_format = match self.pixel_data_format {
+            DecodedPixelData::YcbCr(_) => DecodedPixelData::YcbCr(data),
+            DecodedPixelData::Rgb(_) => DecodedPixelData::Rgb(data),
+            DecodedPixelData::Native(_) => DecodedPixelData::Native(data),
+        };
+
+        Self {
+            dimensions,
+            bit_depth: self.bit_depth,
+            photometric_interpretation: self.photometric_interpretation.clone(),
+            samples_per_pixel: self.samples_per_pixel,
+            planar_configuration: self.planar_configuration,
+            number_of_frames: frames,
+            frame_time: self.frame_time,
+            pixel_aspect_ratio: self.pixel_aspect_ratio,
+            pixel_spacing: self.pixel_spacing,
+            pixel_value_range: self.pixel_value_range,
+            orientation: self.orientation,
+            position: self.position,
+            pixel_data_format,
+            rescale: self.rescale,
+            modality_lut: self.modality_lut.clone(),
+            palette: self.palette.clone(),
+            rescale_type: self.rescale_type.clone(),
+            voi_window: self.voi_window,
+            patient: self.patient.clone(),
+            study: self.study.clone(),
+            series: self.series.clone(),
+            acquisition: self.acquisition,
+            view: self.view.clone(),
+            ultrasound_regions: self.ultrasound_regions.clone(),
+            graphic_annotations: self.graphic_annotations.clone(),
+            overlays: self.overlays.clone(),
+            sop_class: self.sop_class.clone(),
+            sop_instance_uid: self.sop_instance_uid.clone(),
+            frame_of_reference_uid: self.frame_of_reference_uid.clone(),
+            transfer_syntax: self.transfer_syntax.clone(),
+            lossy_compression: self.lossy_compression.clone(),
+            burned_in_annotation: self.burned_in_annotation,
+            file_stamp: self.file_stamp,
+            warnings: self.warnings.clone(),
+            decisions: self.decisions.clone(),
+        }
+    }
+
+    /// The graphic annotations with every point mapped by `f`
+    fn annotations_mapped(&self, f: impl Fn((f64, f64)) -> (f64, f64)) -> Vec<GraphicAnnotation> {
+        self.graphic_annotations
+            .iter()
+            .map(|annotation| GraphicAnnotation {
+                shape: annotation.shape.map_points(&f),
+                ..annotation.clone()
+            })
+            .collect()
+    }
+
+    /// Copy of this image holding only the frame at `index` (0-based)
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `index` is not below the number of frames
+    pub fn single_frame(&self, index: u32) -> Result<Self> {
+        let frames = self.number_of_frames.max(1);
+        if index >= frames {
+            bail!(
+                "Frame {} out of range: the image has {frames} frame(s)",
+                index + 1
+            );
+        }
+
+        let frame_size = self.pixel_data().len() / frames as usize;
+        let start = index as usize * frame_size;
+        let frame = self.pixel_data()[start..start + frame_size].into();
+
+        Ok(self.with_pixel_data(frame, self.dimensions, 1))
+    }
+
+    /// Copy of the tags with `image` as its 8-bit RGB pixel data, e.g. after
+    /// another image was blended into this one
+    #[must_use]
+    pub fn with_rgb_image(&self, image: RgbImage) -> Self {
+        let dimensions = Dimensions::new(
+            u16::try_from(image.height()).unwrap_or(u16::MAX),
+            u16::try_from(image.width()).unwrap_or(u16::MAX),
+        );
+        let data = image.into_raw().into_boxed_slice();
+
+        Self {
+            bit_depth: BitDepth::new(8, 8),
+            photometric_interpretation: PhotometricInterpretation::Rgb,
+            samples_per_pixel: 3,
+            planar_configuration: Some(0),
+            pixel_value_range: None,
+            pixel_data_format: DecodedPixelData::Rgb(data),
+            rescale: RescaleParams::new(1.0, 0.0),
+            modality_lut: None,
+            palette: None,
+            rescale_type: None,
+            voi_window: None,
+            ..self.with_pixel_data(Box::default(), dimensions, 1)
+        }
+    }
+
+    /// Copy of the tags with `samples` as its single 16-bit grayscale frame
+    #[must_use]
+    pub fn with_gray16_samples(&self, samples: &[u16]) -> Self {
+        let data = samples.iter().flat_map(|v| v.to_le_bytes()).collect();
+
+        Self {
+            bit_depth: BitDepth::new(16, 16),
+            samples_per_pixel: 1,
+            pixel_value_range: None,
+            pixel_data_format: DecodedPixelData::Native(data),
+            ..self.with_pixel_data(Box::default(), self.dimensions, 1)
+        }
+    }
+
+    /// First frame decimated to at most `max_size` pixels on each side
+    ///
+    /// Returns `None` when the image already fits or cannot be decimated
+    /// (see [`Self::decimated`]).
+    #[must_use]
+    pub fn thumbnail(&self, max_size: u16) -> Option<Self> {
+        let longest = self.rows().max(self.cols());
+        let step = longest.div_ceil(max_size.max(1));
+        (step > 1).then(|| self.decimated(step)).flatten()
+    }
+
+    /// First frame reduced to every `step`-th pixel in both directions
+    ///
+    /// Much cheaper to convert than the full image, for quick previews.
+    /// Returns `None` for layouts that cannot be subsampled per pixel:
+    /// planar or chroma-subsampled data and sub-byte bit depths.
+    #[must_use]
+    pub fn decimated(&self, step: u16) -> Option<Self> {
+        if step == 0 {
+            return None;
+        }
+
+        let bytes_per_pixel = self.interleaved_bytes_per_pixel()?;
+        let row_size = usize::from(self.cols()) * bytes_per_pixel;
+        let frame = self
+            .pixel_data()
+            .get(..row_size * usize::from(self.rows()))?;
+
+        let step_size = usize::from(step);
+        let data: Box<[u8]> = frame
+            .chunks_exact(row_size)
+            .step_by(step_size)
+            .flat_map(|row| row.chunks_exact(bytes_per_pixel).step_by(step_size))
+            .flatten()
+            .copied()
+            .collect();
+
+        let dimensions = Dimensions::new(self.rows().div_ceil(step), self.cols().div_ceil(step));
+        let mut preview = self.with_pixel_data(data, dimensions, 1);
+        preview.pixel_spacing = self.pixel_spacing.map(|spacing| {
+            PixelSpacing::new(
+                spacing.row * f64::from(step),
+                spacing.column * f64::from(step),
+            )
+        });
+
+        preview.graphic_annotations =
+            self.annotations_mapped(|(x, y)| (x / f64::from(step), y / f64::from(step)));
+        preview.overlays = self
+            .overlays
+            .iter()
+            .map(|overlay| overlay.decimated(step))
+            .collect();
+
+        Some(preview)
+    }
+
+    /// Bytes of each pixel when its samples are stored together, or `None`
+    /// for layouts whose pixels cannot be handled one by one: planar or
+    /// chroma-subsampled data and sub-byte bit depths
+    fn interleaved_bytes_per_pixel(&self) -> Option<usize> {
+        let chroma_subsampled = matches!(
+            self.photometric_interpretation,
+            PhotometricInterpretation::YbrFull422
+                | PhotometricInterpretation::YbrPartial422
+                | PhotometricInterpretation::YbrPartial420
+        );
+        let planar = matches!(self.planar_configuration, Some(p) if p != 0);
+        if chroma_subsampled || planar || !self.bits_allocated().is_multiple_of(8) {
+            return None;
+        }
+        Some(usize::from(self.samples_per_pixel) * usize::from(self.bits_allocated() / 8))
+    }
+
+    /// All frames cropped to the ultrasound imaging region, leaving out the
+    /// scanner's controls and text around it
+    ///
+    /// Returns `None` without a 2D region smaller than the image, or for
+    /// layouts that cannot be cropped per pixel: planar or chroma-subsampled
+    /// data and sub-byte bit depths.
+    #[must_use]
+    pub fn cropped_to_imaging_region(&self) -> Option<Self> {
+        let region = UltrasoundRegion::imaging(&self.ultrasound_regions)?;
+        let (width, height) = region.size();
+        let (cols, rows) = (u32::from(self.cols()), u32::from(self.rows()));
+        if region.max.0 >= cols || region.max.1 >= rows || (width, height) == (cols, rows) {
+            return None;
+        }
+
+        let bytes_per_pixel = if self.is_already_rgb() {
+            3
+        } else {
+            self.interleaved_bytes_per_pixel()?
+        };
+
+        let row_size = cols as usize * bytes_per_pixel;
+        let frames = self.number_of_frames.max(1);
+        let columns =
+            region.min.0 as usize * bytes_per_pixel..(region.max.0 as usize + 1) * bytes_per_pixel;
+        let data: Box<[u8]> = self
+            .pixel_data()
+            .chunks_exact(row_size * rows as usize)
+            .take(frames as usize)
+            .flat_map(|frame| {
+                frame
+                    .chunks_exact(row_size)
+                    .skip(region.min.1 as usize)
+                    .take(height as usize)
+                    .flat_map(|row| &row[columns.clone()])
+            })
+            .copied()
+            .collect();
+
+        let dimensions = Dimensions::new(u16::try_from(height).ok()?, u16::try_from(width).ok()?);
+        let mut cropped = self.with_pixel_data(data, dimensions, frames);
+        cropped.ultrasound_regions = vec![UltrasoundRegion {
+            min: (0, 0),
+            max: (width - 1, height - 1),
+            ..*region
+        }];
+        let (left, top) = (f64::from(region.min.0), f64::from(region.min.1));
+        cropped.graphic_annotations = self.annotations_mapped(|(x, y)| (x - left, y - top));
+        cropped.overlays = self
+            .overlays
+            .iter()
+            .map(|overlay| overlay.shifted(region.min.0 as i32, region.min.1 as i32))
+            .collect();
+        cropped.decisions.record(
+            "Crop",
+            format!(
+                "ultrasound imaging region, {width}x{height} at ({}, {})",
+                region.min.0, region.min.1
+            ),
+        );
+        Some(cropped)
+    }
+
+    /// All frames with each rectangle `(x, y, width, height)` of stored
+    /// pixels filled with black, e.g. to hide text burned into the image
+    ///
+    /// Rectangles are clipped to the image. Returns `None` for layouts that
+    /// cannot be changed per pixel: planar or chroma-subsampled data and
+    /// sub-byte bit depths.
+    #[must_use]
+    pub fn redacted(&self, rects: &[Rect]) -> Option<Self> {
+        let black = if self.is_already_rgb() {
+            vec![0; 3]
+        } else {
+            self.interleaved_bytes_per_pixel()?;
+            // Decoded data is little endian whatever the transfer syntax
+            redact::black_pixel(
+                &self.photometric_interpretation,
+                self.bits_allocated(),
+                self.bits_stored(),
+                self.samples_per_pixel,
+            )?
+        };
+
+        let mut data: Box<[u8]> = self.pixel_data().into();
+        redact::fill_rects(
+            &mut data,
+            (usize::from(self.cols()), usize::from(self.rows())),
+            self.number_of_frames.max(1) as usize,
+            &black,
+            rects,
+        );
+
+        let mut redacted = self.with_pixel_data(data, self.dimensions, self.number_of_frames);
+        redacted.decisions.record(
+            "Redact",
+            format!("{} rectangle(s) filled with black", rects.len()),
+        );
+        Some(redacted)
+    }
+
     #[inline]
     #[must_use]
     pub fn is_already_rgb(&self) -> bool {
@@ -125,6 +483,30 @@ impl DicomMetadata {
         self.study.date.as_deref()
     }
 
+    #[inline]
+    #[must_use]
+    pub fn study_time(&self) -> Option<&str> {
+        self.study.time.as_deref()
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn institution_name(&self) -> Option<&str> {
+        self.series.institution_name.as_deref()
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn station_name(&self) -> Option<&str> {
+        self.series.station_name.as_deref()
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn body_part_examined(&self) -> Option<&str> {
+        self.series.body_part_examined.as_deref()
+    }
+
     #[inline]
     #[must_use]
     pub fn study_description(&self) -> Option<&str> {
@@ -150,3 +532,119 @@ impl DicomMetadata {
         self.series.slice_thickness
     }
 }
+
+#[cfg(test)]
+impl DicomMetadata {
+    /// 16-bit MONOCHROME2 image for unit tests, with one frame per
+    /// `rows * cols` samples
+    pub(crate) fn test_grayscale16(rows: u16, cols: u16, samples: &[u16]) -> Self {
+        let frame_size = usize::from(rows) * usize::from(cols);
+        let data: Vec<u8> = samples.iter().flat_map(|v| v.to_le_bytes()).collect();
+
+        Self {
+            dimensions: Dimensions::new(rows, cols),
+            bit_depth: BitDepth::new(16, 16),
+            photometric_interpretation: PhotometricInterpretation::Monochrome2,
+            samples_per_pixel: 1,
+            planar_configuration: None,
+            number_of_frames: (samples.len() / frame_size) as u32,
+            frame_time: None,
+            pixel_aspect_ratio: None,
+            pixel_spacing: None,
+            pixel_value_range: None,
+            orientation: None,
+            position: None,
+            pixel_data_format: DecodedPixelData::Native(data.into_boxed_slice()),
+            rescale: RescaleParams::new(1.0, 0.0),
+            modality_lut: None,
+            palette: None,
+            rescale_type: None,
+            voi_window: None,
+            patient: PatientInfo::new(),
+            study: StudyInfo::new(),
+            series: SeriesInfo::new(),
+            acquisition: AcquisitionInfo::default(),
+            view: ViewInfo::default(),
+            ultrasound_regions: Vec::new(),
+            graphic_annotations: Vec::new(),
+            overlays: Vec::new(),
+            sop_class: None,
+            sop_instance_uid: None,
+            frame_of_reference_uid: None,
+            transfer_syntax: TransferSyntax::new(
+                "1.2.840.10008.1.2.1".to_string(),
+                "Explicit VR Little Endian".to_string(),
+            ),
+            lossy_compression: None,
+            burned_in_annotation: None,
+            file_stamp: None,
+            warnings: Vec::new(),
+            decisions: DecisionLog::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decimated_keeps_every_step_pixel() {
+        // 3x3 frame followed by a second frame
+        let samples: Vec<u16> = (0..18).collect();
+        let mut metadata = DicomMetadata::test_grayscale16(3, 3, &samples);
+        metadata.pixel_spacing = Some(PixelSpacing::new(0.5, 0.25));
+
+        let preview = metadata.decimated(2).unwrap();
+        assert_eq!(preview.dimensions, Dimensions::new(2, 2));
+        assert_eq!(preview.number_of_frames, 1);
+        assert_eq!(preview.pixel_data(), &[0, 0, 2, 0, 6, 0, 8, 0]);
+        assert_eq!(preview.pixel_spacing, Some(PixelSpacing::new(1.0, 0.5)));
+
+        metadata.planar_configuration = Some(1);
+        assert!(metadata.decimated(2).is_none());
+    }
+
+    #[test]
+    fn test_crop_to_ultrasound_region_in_every_frame() {
+        // Two 3x3 frames; the image is the bottom-right 2x2 of each
+        let samples: Vec<u16> = (0..18).collect();
+        let mut metadata = DicomMetadata::test_grayscale16(3, 3, &samples);
+        assert!(metadata.cropped_to_imaging_region().is_none());
+
+        let text = UltrasoundRegion {
+            min: (0, 0),
+            max: (2, 0),
+            spatial_format: 0,
+            spacing: None,
+        };
+        let image = UltrasoundRegion {
+            min: (1, 1),
+            max: (2, 2),
+            spatial_format: 1,
+            ..text
+        };
+        metadata.ultrasound_regions = vec![text, image];
+
+        let cropped = metadata.cropped_to_imaging_region().unwrap();
+        assert_eq!(cropped.dimensions, Dimensions::new(2, 2));
+        assert_eq!(cropped.number_of_frames, 2);
+        let values: Vec<u8> = cropped.pixel_data().iter().step_by(2).copied().collect();
+        assert_eq!(values, [4, 5, 7, 8, 13, 14, 16, 17]);
+    }
+
+    #[test]
+    fn test_redact_rectangle_in_every_frame() {
+        // Two 3x3 frames of 1s; the rectangle runs past the right edge
+        let mut metadata = DicomMetadata::test_grayscale16(3, 3, &[1; 18]);
+
+        let redacted = metadata.redacted(&[(1, 0, 5, 2)]).unwrap();
+        let values: Vec<u8> = redacted.pixel_data().iter().step_by(2).copied().collect();
+        let frame = [1, 0, 0, 1, 0, 0, 1, 1, 1];
+        assert_eq!(values, [frame, frame].concat());
+
+        metadata.photometric_interpretation = PhotometricInterpretation::Monochrome1;
+        let redacted = metadata.redacted(&[(0, 0, 1, 1)]).unwrap();
+        assert_eq!(&redacted.pixel_data()[..4], &[0xFF, 0xFF, 1, 0]);
+    }
+}
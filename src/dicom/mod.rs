@@ -1,11 +1,21 @@
 //! DICOM file parsing and metadata extraction
 
+mod charset;
 mod error;
+mod graphics;
+mod icon;
+mod j2k;
 mod metadata;
+mod non_image;
+mod overlay;
 mod parser;
 mod photometric;
 mod pixel_data;
+mod recovery;
+mod redact;
+mod relabel;
 mod validation;
+mod write;
 
 /// Type alias for a parsed DICOM object
 ///
@@ -14,14 +24,27 @@ mod validation;
 pub type DicomObject = FileDicomObject<InMemDicomObject<StandardDataDictionary>>;
 
 // Re-export public API
-pub use error::ProcessError;
+pub use charset::TextDecoder;
+pub use error::{ProcessError, Stage};
+pub use graphics::{PresentationState, extract_graphic_annotations};
+pub use icon::icon_image;
 pub use metadata::DicomMetadata;
+pub use non_image::{NonImage, non_image_class};
+pub use parser::extract_transfer_syntax;
 pub use photometric::PhotometricInterpretation;
 pub use pixel_data::DecodedPixelData;
+pub(crate) use pixel_data::group_fragments_by_frame;
+pub use recovery::{fix_pixel_data_syntax, open_mislabeled};
+pub use redact::{Rect, is_dicom_output, redact_file, redact_pixel_data};
+pub use write::{SaveOptions, new_uid, save};
 
+use crate::explain::DecisionLog;
+use crate::terminal::Stream;
 use crate::types::{
-    BitDepth, Dimensions, PatientInfo, PixelAspectRatio, RescaleParams, SOPClass, SeriesInfo,
-    StudyInfo, TransferSyntax,
+    AcquisitionInfo, BitDepth, ByteOrder, Dimensions, GraphicAnnotation, ImageOrientation,
+    LossyCompression, ModalityLut, OverlayPlane, PaletteLut, PatientInfo, PixelAspectRatio,
+    PixelSpacing, PixelValueRange, RescaleParams, RescaleType, SOPClass, SeriesInfo, StudyInfo,
+    TransferSyntax, UltrasoundRegion, ViewInfo, VoiWindow,
 };
 use anyhow::{Context, Result, anyhow};
 use crossterm::terminal::{Clear, ClearType};
@@ -29,12 +52,16 @@ use crossterm::{cursor::MoveToColumn, execute, style::Print};
 use dicom::dictionary_std::tags;
 use dicom::object::file::ReadPreamble;
 use dicom::object::{
-    FileDicomObject, InMemDicomObject, OpenFileOptions, StandardDataDictionary, open_file,
+    FileDicomObject, FileMetaTableBuilder, InMemDicomObject, OpenFileOptions,
+    StandardDataDictionary, open_file,
 };
-use std::io::{self, IsTerminal, Read, Seek, Write, stdout};
+use dicom::transfer_syntax::entries;
+use std::fs::File;
+use std::io::{self, BufReader, Read, Seek, Write};
 use std::path::Path;
 use std::str::FromStr;
 use tempfile::SpooledTempFile;
+use tracing::{debug, debug_span};
 
 /// Open and parse a DICOM file
 ///
@@ -42,10 +69,53 @@ use tempfile::SpooledTempFile;
 ///
 /// Returns an error if the file cannot be read or is not a valid DICOM file
 pub fn open_dicom_file(file_path: &Path) -> Result<DicomObject> {
+    let _span = debug_span!("parse", file = %file_path.display()).entered();
     open_file(file_path)
         .with_context(|| format!("Failed to open DICOM file: {}", file_path.display()))
 }
 
+/// Open a DICOM file and parse its header, stopping before the pixel data
+///
+/// Much faster than [`open_dicom_file`] for large images, for when only
+/// the tags are needed (e.g. when scanning directories).
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be read or is not a valid DICOM file
+pub fn open_dicom_header(file_path: &Path) -> Result<DicomObject> {
+    OpenFileOptions::new()
+        .read_until(tags::PIXEL_DATA)
+        .open_file(file_path)
+        .with_context(|| format!("Failed to open DICOM file: {}", file_path.display()))
+}
+
+/// Read a dataset stored without preamble and file meta group, as
+/// Implicit VR Little Endian from the start of the file (for `--force`,
+/// like dcmtk's `+fo`)
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be read or parsed as such a dataset
+pub fn open_raw_dataset(file_path: &Path) -> Result<DicomObject> {
+    let _span = debug_span!("parse", file = %file_path.display(), raw = true).entered();
+    let file = File::open(file_path)
+        .with_context(|| format!("Failed to open file: {}", file_path.display()))?;
+    read_raw_dataset(BufReader::new(file))
+        .with_context(|| format!("Failed to read {} as a bare dataset", file_path.display()))
+}
+
+/// Parse a bare Implicit VR Little Endian dataset, adding a file meta group
+fn read_raw_dataset(from: impl Read) -> Result<DicomObject> {
+    let ts = entries::IMPLICIT_VR_LITTLE_ENDIAN.erased();
+    let obj = InMemDicomObject::read_dataset_with_ts(from, &ts)?;
+    if obj.iter().next().is_none() {
+        anyhow::bail!("No data elements found");
+    }
+
+    debug!("read a dataset without file meta group as Implicit VR Little Endian");
+    Ok(obj.with_meta(FileMetaTableBuilder::new().transfer_syntax(ts.uid()))?)
+}
+
 /// Format byte count for progress display
 ///
 /// Returns a human-readable string representation of the byte count,
@@ -61,29 +131,32 @@ fn format_size(bytes: usize) -> String {
 
 /// Read and parse a DICOM file from stdin
 ///
-/// This function reads DICOM data from stdin with progress display and early
-/// validation of the DICOM preamble. Data is read into a spooled temp file
-/// that keeps small files in memory and spills large files to disk.
+/// This function reads DICOM data from stdin, with a progress display on
+/// the `progress` stream if given, and early validation of the DICOM
+/// preamble. Data is read into a spooled temp file that keeps small files
+/// in memory and spills large files to disk. With `force`, input without
+/// the "DICM" magic bytes is read as a bare dataset, as by
+/// [`open_raw_dataset`].
 ///
 /// # Errors
 ///
 /// Returns an error if:
 /// - stdin cannot be read
-/// - the input is not a valid DICOM file (missing "DICM" magic bytes)
+/// - the input is not a valid DICOM file (missing "DICM" magic bytes,
+///   unless `force` is given)
 /// - the DICOM file cannot be parsed
-pub fn read_stdin() -> Result<DicomObject> {
+pub fn read_stdin(progress: Option<Stream>, force: bool) -> Result<DicomObject> {
     let stdin = io::stdin();
     let mut handle = stdin.lock();
-    let is_tty = io::stdout().is_terminal();
     const PREAMBLE_SIZE: usize = 128;
     const MAGIC: &[u8] = b"DICM";
     const HEADER_SIZE: usize = PREAMBLE_SIZE + MAGIC.len();
 
     let mut temp_file = SpooledTempFile::new(32 * 1024 * 1024);
+    let mut progress = progress.map(Stream::writer);
 
-    if is_tty {
-        execute!(stdout(), Print("Reading from stdin..."))?;
-        stdout().flush()?;
+    if let Some(out) = &mut progress {
+        execute!(out, Print("Reading from stdin..."))?;
     }
 
     // Read and validate preamble first for early rejection
@@ -92,7 +165,8 @@ pub fn read_stdin() -> Result<DicomObject> {
         "Input is too short to be a valid DICOM file with preamble (expected at least 132 bytes)"
     })?;
 
-    if &header[PREAMBLE_SIZE..] != MAGIC {
+    let bare = &header[PREAMBLE_SIZE..] != MAGIC;
+    if bare && !force {
         return Err(ProcessError::NotADicomFile(anyhow!(
             "Input is not a valid DICOM file (missing DICM magic bytes)"
         ))
@@ -113,27 +187,31 @@ pub fn read_stdin() -> Result<DicomObject> {
         temp_file.write_all(&chunk[..n])?;
         bytes_read += n;
 
-        if is_tty {
+        if let Some(out) = &mut progress {
             let size_str = format_size(bytes_read);
             execute!(
-                stdout(),
+                out,
                 MoveToColumn(0),
                 Clear(ClearType::UntilNewLine),
                 Print("Reading from stdin ["),
                 Print(&size_str),
                 Print("]")
             )?;
-            stdout().flush()?;
         }
     }
 
-    if is_tty {
-        execute!(stdout(), MoveToColumn(0), Clear(ClearType::CurrentLine))?;
-        stdout().flush()?;
+    if let Some(out) = &mut progress {
+        execute!(out, MoveToColumn(0), Clear(ClearType::CurrentLine))?;
     }
 
     temp_file.rewind()?;
+    debug!(bytes = bytes_read, "read stdin");
 
+    let _span = debug_span!("parse", file = "-").entered();
+    if bare {
+        return read_raw_dataset(BufReader::new(temp_file))
+            .context("Failed to read stdin as a bare dataset");
+    }
     let dcm = OpenFileOptions::new()
         .read_preamble(ReadPreamble::Always)
         .from_reader(temp_file)?;
@@ -152,13 +230,31 @@ struct CommonMetadata {
     samples_per_pixel: u16,
     planar_configuration: Option<u16>,
     number_of_frames: u32,
+    frame_time: Option<f64>,
     pixel_aspect_ratio: Option<PixelAspectRatio>,
+    pixel_spacing: Option<PixelSpacing>,
+    pixel_value_range: Option<PixelValueRange>,
+    orientation: Option<ImageOrientation>,
+    position: Option<[f64; 3]>,
     rescale: RescaleParams,
+    modality_lut: Option<ModalityLut>,
+    palette: Option<PaletteLut>,
+    rescale_type: Option<RescaleType>,
+    voi_window: Option<VoiWindow>,
     patient: PatientInfo,
     study: StudyInfo,
     series: SeriesInfo,
+    acquisition: AcquisitionInfo,
+    view: ViewInfo,
+    ultrasound_regions: Vec<UltrasoundRegion>,
+    graphic_annotations: Vec<GraphicAnnotation>,
+    overlays: Vec<OverlayPlane>,
     sop_class: Option<SOPClass>,
+    sop_instance_uid: Option<String>,
+    frame_of_reference_uid: Option<String>,
     transfer_syntax: TransferSyntax,
+    lossy_compression: Option<LossyCompression>,
+    burned_in_annotation: Option<bool>,
 }
 
 /// Extract common metadata from a DICOM object
@@ -172,8 +268,23 @@ fn extract_common_metadata(
     let dimensions = parser::extract_dimensions(obj, &error_context)?;
 
     let rescale = parser::extract_rescale_params(obj);
+    let rescale_type = parser::extract_rescale_type(obj);
     let pixel_aspect_ratio = parser::extract_pixel_aspect_ratio(obj);
+    let ultrasound_regions = parser::extract_ultrasound_regions(obj);
+    // Ultrasound images are calibrated per region instead
+    let pixel_spacing = parser::extract_pixel_spacing(obj).or_else(|| {
+        UltrasoundRegion::imaging(&ultrasound_regions).and_then(|region| region.spacing)
+    });
+    let pixel_value_range = parser::extract_pixel_value_range(obj);
+    let sop_instance_uid = parser::extract_sop_instance_uid(obj);
+    let graphic_annotations = graphics::extract_graphic_annotations(
+        obj,
+        sop_instance_uid.as_deref(),
+        (f64::from(dimensions.cols), f64::from(dimensions.rows)),
+    );
+    let orientation = parser::extract_image_orientation(obj);
     let number_of_frames = parser::extract_number_of_frames(obj);
+    let frame_time = parser::extract_frame_time(obj);
     let samples_per_pixel = parser::extract_samples_per_pixel(obj);
     let bit_depth = parser::extract_bit_depth(obj, &error_context)?;
     let planar_configuration = parser::extract_planar_configuration(obj);
@@ -200,16 +311,52 @@ fn extract_common_metadata(
         samples_per_pixel,
         planar_configuration,
         number_of_frames,
+        frame_time,
         pixel_aspect_ratio,
+        pixel_spacing,
+        pixel_value_range,
+        orientation,
+        position: parser::extract_image_position(obj),
         rescale,
+        modality_lut: parser::extract_modality_lut(obj, bit_depth.signed),
+        palette: parser::extract_palette(obj),
+        rescale_type,
+        voi_window: parser::extract_voi_window(obj),
         patient,
         study,
         series,
+        acquisition: parser::extract_acquisition_info(obj),
+        view: parser::extract_view_info(obj),
+        ultrasound_regions,
+        graphic_annotations,
+        overlays: overlay::extract_overlay_data(obj, dimensions),
         sop_class: error_context.sop_class,
+        sop_instance_uid,
+        frame_of_reference_uid: parser::extract_frame_of_reference_uid(obj),
         transfer_syntax,
+        lossy_compression: parser::extract_lossy_compression(obj),
+        burned_in_annotation: parser::extract_burned_in_annotation(obj),
     })
 }
 
+/// Options controlling pixel data extraction
+#[derive(Debug, Clone, Default)]
+pub struct ExtractOptions {
+    /// Width in pixels the image will be displayed at, if known.
+    /// Lets resolution-progressive J2K codestreams skip detail levels
+    /// that would be lost when scaling down.
+    pub target_width: Option<u32>,
+    /// Photometric Interpretation to read the pixel data as, instead of
+    /// the declared one, for files whose writer mislabeled it
+    pub photometric: Option<PhotometricInterpretation>,
+    /// Planar Configuration to read the pixel data with, instead of the
+    /// declared one
+    pub planar_configuration: Option<u16>,
+    /// Byte order of uncompressed samples, instead of the one of the
+    /// transfer syntax
+    pub byte_order: Option<ByteOrder>,
+}
+
 /// Extract metadata and pixel data from a DICOM object
 ///
 /// # Errors
@@ -219,22 +366,96 @@ fn extract_common_metadata(
 pub fn extract_dicom_data(
     obj: &FileDicomObject<InMemDicomObject<StandardDataDictionary>>,
 ) -> Result<DicomMetadata> {
+    extract_dicom_data_with_options(obj, &ExtractOptions::default())
+}
+
+/// Extract metadata and pixel data from a DICOM object using the given options
+///
+/// # Errors
+///
+/// Returns an error if required DICOM tags are missing or if the pixel data
+/// cannot be decoded
+pub fn extract_dicom_data_with_options(
+    obj: &FileDicomObject<InMemDicomObject<StandardDataDictionary>>,
+    options: &ExtractOptions,
+) -> Result<DicomMetadata> {
+    let relabeled = relabel::relabel(obj, options);
+    let obj = relabeled.as_ref().map_or(obj, |relabeled| &relabeled.obj);
     let common = extract_common_metadata(obj)?;
 
-    let pixel_data = pixel_data::extract_pixel_data(
+    let extract = |source: &DicomObject, log: &mut DecisionLog| {
+        pixel_data::extract_pixel_data(
+            source,
+            common.bit_depth.allocated,
+            &common.photometric_interpretation.to_string(),
+            &common.transfer_syntax.uid,
+            common.planar_configuration,
+            options.byte_order,
+            log,
+        )
+    };
+
+    // Decode a reduced-resolution codestream when possible, falling back to
+    // the full codestream if the decoder rejects the truncated one
+    let reduced = j2k::reduce_for_display(
         obj,
-        common.bit_depth.allocated,
-        &common.photometric_interpretation.to_string(),
         &common.transfer_syntax.uid,
-        common.planar_configuration,
-    )?;
+        common.number_of_frames,
+        options.target_width,
+    );
+
+    let mut decisions = DecisionLog::default();
+    let extracted = match reduced
+        .as_ref()
+        .map(|reduced| extract(reduced, &mut decisions))
+    {
+        Some(Ok(data)) => {
+            decisions.record(
+                "Resolution",
+                "reduced JPEG 2000 codestream, skipping detail levels beyond the display size",
+            );
+            data
+        }
+        _ => {
+            decisions = DecisionLog::default();
+            extract(obj, &mut decisions)?
+        }
+    };
+    for (step, decision) in relabeled.iter().flat_map(|r| &r.decisions) {
+        decisions.record(step, decision.clone());
+    }
 
-    validation::validate_metadata(
+    // Pixel data decoded fine, so validation problems are reported as
+    // warnings instead of preventing display
+    let mut warnings = validation::collect_validation_warnings(
         &common.photometric_interpretation,
         common.samples_per_pixel,
         common.planar_configuration,
         common.bit_depth.allocated,
-    )?;
+    );
+    warnings.extend(relabeled.iter().flat_map(|r| r.warnings.iter().cloned()));
+
+    // Frames that failed to decode are left out of the pixel data
+    warnings.extend(extracted.failed_frames.iter().map(|frame| {
+        format!(
+            "Frame {number} could not be decoded and was skipped: {message}",
+            number = frame.index + 1,
+            message = frame.message
+        )
+    }));
+    warnings.extend(extracted.warnings.iter().cloned());
+
+    let mut pixel_data_format = extracted.data;
+    let mut overlays = common.overlays;
+    overlays.extend(overlay::separate_embedded_overlays(
+        obj,
+        common.dimensions,
+        common.samples_per_pixel,
+        &mut pixel_data_format,
+        &mut decisions,
+        &mut warnings,
+    ));
+    let number_of_frames = common.number_of_frames - extracted.failed_frames.len() as u32;
 
     Ok(DicomMetadata {
         dimensions: common.dimensions,
@@ -242,15 +463,36 @@ pub fn extract_dicom_data(
         photometric_interpretation: common.photometric_interpretation,
         samples_per_pixel: common.samples_per_pixel,
         planar_configuration: common.planar_configuration,
-        number_of_frames: common.number_of_frames,
+        number_of_frames,
+        frame_time: common.frame_time,
         pixel_aspect_ratio: common.pixel_aspect_ratio,
-        pixel_data_format: pixel_data,
+        pixel_spacing: common.pixel_spacing,
+        pixel_value_range: common.pixel_value_range,
+        orientation: common.orientation,
+        position: common.position,
+        pixel_data_format,
         rescale: common.rescale,
+        modality_lut: common.modality_lut,
+        palette: common.palette,
+        rescale_type: common.rescale_type,
+        voi_window: common.voi_window,
         patient: common.patient,
         study: common.study,
         series: common.series,
+        acquisition: common.acquisition,
+        view: common.view,
+        ultrasound_regions: common.ultrasound_regions,
+        graphic_annotations: common.graphic_annotations,
+        overlays,
         sop_class: common.sop_class,
+        sop_instance_uid: common.sop_instance_uid,
+        frame_of_reference_uid: common.frame_of_reference_uid,
         transfer_syntax: common.transfer_syntax,
+        lossy_compression: common.lossy_compression,
+        burned_in_annotation: common.burned_in_annotation,
+        file_stamp: None,
+        warnings,
+        decisions,
     })
 }
 
@@ -279,14 +521,35 @@ pub fn extract_metadata_tags(
         samples_per_pixel: common.samples_per_pixel,
         planar_configuration: common.planar_configuration,
         number_of_frames: common.number_of_frames,
+        frame_time: common.frame_time,
         pixel_aspect_ratio: common.pixel_aspect_ratio,
+        pixel_spacing: common.pixel_spacing,
+        pixel_value_range: common.pixel_value_range,
+        orientation: common.orientation,
+        position: common.position,
         pixel_data_format,
         rescale: common.rescale,
+        modality_lut: common.modality_lut,
+        palette: common.palette,
+        rescale_type: common.rescale_type,
+        voi_window: common.voi_window,
         patient: common.patient,
         study: common.study,
         series: common.series,
+        acquisition: common.acquisition,
+        view: common.view,
+        ultrasound_regions: common.ultrasound_regions,
+        graphic_annotations: common.graphic_annotations,
+        overlays: common.overlays,
         sop_class: common.sop_class,
+        sop_instance_uid: common.sop_instance_uid,
+        frame_of_reference_uid: common.frame_of_reference_uid,
         transfer_syntax: common.transfer_syntax,
+        lossy_compression: common.lossy_compression,
+        burned_in_annotation: common.burned_in_annotation,
+        file_stamp: None,
+        warnings: Vec::new(),
+        decisions: DecisionLog::default(),
     })
 }
 
@@ -301,6 +564,23 @@ mod tests {
     type GrayscalePixelSamples = [((u32, u32), u8); 10];
     type RgbPixelSamples = [((u32, u32), (u8, u8, u8)); 10];
 
+    #[test]
+    fn test_read_raw_dataset() {
+        // (0008,0060) Modality "OT" and (0028,0010) Rows 2, Implicit VR Little Endian
+        let bytes = [
+            0x08, 0x00, 0x60, 0x00, 2, 0, 0, 0, b'O', b'T', //
+            0x28, 0x00, 0x10, 0x00, 2, 0, 0, 0, 2, 0,
+        ];
+        let obj = read_raw_dataset(&bytes[..]).unwrap();
+        assert_eq!(
+            obj.meta().transfer_syntax(),
+            entries::IMPLICIT_VR_LITTLE_ENDIAN.uid()
+        );
+        assert_eq!(obj.element(tags::MODALITY).unwrap().to_str().unwrap(), "OT");
+
+        assert!(read_raw_dataset(&[][..]).is_err());
+    }
+
     #[test]
     fn test_file1_metadata() {
         let file_path = Path::new(".test-files/file1.dcm");
@@ -338,8 +618,8 @@ mod tests {
         assert_eq!(image.height(), u32::from(metadata.rows()));
 
         // Verify pixel values - check grayscale consistency
-        // Grayscale converted to RGB, so R=G=B for all pixels
-        let rgb = image.as_rgb8().expect("Should be RGB image");
+        // Grayscale quantized for display and converted to RGB, so R=G=B
+        let rgb = &crate::image::quantize(image).into_rgb8();
         let width = rgb.width();
         let height = rgb.height();
 
@@ -524,8 +804,8 @@ mod tests {
         assert_eq!(image.height(), u32::from(metadata.rows()));
 
         // Verify pixel values - check grayscale consistency
-        // Grayscale converted to RGB, so R=G=B for all pixels
-        let rgb = image.as_rgb8().expect("Should be RGB image");
+        // Grayscale quantized for display and converted to RGB, so R=G=B
+        let rgb = &crate::image::quantize(image).into_rgb8();
         let width = rgb.width();
         let height = rgb.height();
 
@@ -640,8 +920,8 @@ mod tests {
         assert_eq!(image.height(), u32::from(metadata.rows()));
 
         // Verify pixel values - check grayscale consistency
-        // Grayscale converted to RGB, so R=G=B for all pixels
-        let rgb = image.as_rgb8().expect("Should be RGB image");
+        // Grayscale quantized for display and converted to RGB, so R=G=B
+        let rgb = &crate::image::quantize(image).into_rgb8();
         let width = rgb.width();
         let height = rgb.height();
 
@@ -733,8 +1013,6 @@ mod tests {
     #[test]
     fn test_palette_color_metadata() {
         // Palette color with lookup table
-        // Metadata extraction should work, but image conversion will fail
-        // because we don't yet implement palette color lookup table decoding
         let file_path = Path::new(".test-files/examples_palette.dcm");
         let obj = open_dicom_file(file_path).expect("Failed to open examples_palette.dcm");
         let metadata =
@@ -748,18 +1026,19 @@ mod tests {
             PhotometricInterpretation::Palette
         );
         assert_eq!(metadata.samples_per_pixel, 1);
+        assert!(
+            metadata.palette.is_some(),
+            "Palette Color LUTs should be read"
+        );
 
         // Pixel data should be present (raw bytes, since we use fallback for palette)
         assert!(!metadata.pixel_data().is_empty());
 
-        // Image conversion should fail (palette → RGB not implemented)
-        let result = convert_to_image(&metadata);
-        assert!(result.is_err(), "Palette image conversion should fail");
-        let err = result.unwrap_err();
-        assert!(
-            err.to_string()
-                .contains("Unsupported photometric interpretation"),
-            "Expected 'Unsupported photometric interpretation' error, got: {err}"
+        // Stored values are looked up in the palette
+        let image = convert_to_image(&metadata).expect("Palette image conversion should work");
+        assert_eq!(
+            image.to_rgb8().dimensions(),
+            (u32::from(metadata.cols()), u32::from(metadata.rows()))
         );
     }
 
@@ -815,49 +1094,51 @@ mod tests {
     #[test]
     fn test_32bit_rgb_metadata() {
         // 32-bit RGB with RLE compression
-        // NOTE: This test currently fails because the dicom crate doesn't support 32-bit RLE decoding.
-        // When the dicom crate adds 32-bit RLE support, this test will automatically pass.
+        // The upstream decoder does not support 32-bit RLE, so this exercises
+        // the native RLE fallback decoder
         let file_path = Path::new(".test-files/SC_rgb_rle_32bit.dcm");
         let obj = open_dicom_file(file_path).expect("Failed to open SC_rgb_rle_32bit.dcm");
+        let metadata = extract_dicom_data(&obj)
+            .expect("Failed to extract data from SC_rgb_rle_32bit.dcm via native RLE");
 
-        // Expect failure due to RLE + 32-bit limitation
-        let result = extract_dicom_data(&obj);
-        assert!(
-            result.is_err(),
-            "32-bit RLE should fail until dicom crate adds support"
+        assert_eq!(metadata.bits_allocated(), 32);
+        assert_eq!(
+            metadata.photometric_interpretation,
+            PhotometricInterpretation::Rgb
         );
+        assert_eq!(metadata.samples_per_pixel, 3);
 
-        // Verify the error message is informative
-        let err = result.unwrap_err();
-        let err_msg = format!("{err}");
-        assert!(
-            err_msg.contains("pixel data") || err_msg.contains("PixelSequence"),
-            "Error should mention pixel data issue, got: {err_msg}"
+        // One frame of 32-bit RGB samples
+        assert_eq!(
+            metadata.pixel_data().len(),
+            metadata.dimensions.pixel_count() * 3 * 4
         );
+
+        let image = convert_to_image(&metadata).expect("Failed to convert 32-bit RGB to image");
+        assert_eq!(image.width(), u32::from(metadata.cols()));
+        assert_eq!(image.height(), u32::from(metadata.rows()));
     }
 
     #[test]
     fn test_32bit_multiframe_metadata() {
-        // 32-bit RGB with RLE compression, 2 frames
-        // NOTE: This test currently fails because the dicom crate doesn't support 32-bit RLE decoding.
-        // When the dicom crate adds 32-bit RLE support, this test will automatically pass.
+        // 32-bit RGB with RLE compression, 2 frames, decoded by the native RLE fallback
         let file_path = Path::new(".test-files/SC_rgb_rle_32bit_2frame.dcm");
         let obj = open_dicom_file(file_path).expect("Failed to open SC_rgb_rle_32bit_2frame.dcm");
+        let metadata = extract_dicom_data(&obj)
+            .expect("Failed to extract data from SC_rgb_rle_32bit_2frame.dcm via native RLE");
 
-        // Expect failure due to RLE + 32-bit limitation
-        let result = extract_dicom_data(&obj);
-        assert!(
-            result.is_err(),
-            "32-bit RLE should fail until dicom crate adds support"
-        );
+        assert_eq!(metadata.bits_allocated(), 32);
+        assert_eq!(metadata.number_of_frames, 2);
 
-        // Verify the error message is informative
-        let err = result.unwrap_err();
-        let err_msg = format!("{err}");
-        assert!(
-            err_msg.contains("pixel data") || err_msg.contains("PixelSequence"),
-            "Error should mention pixel data issue, got: {err_msg}"
+        // Both frames of 32-bit RGB samples
+        assert_eq!(
+            metadata.pixel_data().len(),
+            metadata.dimensions.pixel_count() * 3 * 4 * 2
         );
+
+        let image = convert_to_image(&metadata).expect("Failed to convert 32-bit RGB to image");
+        assert_eq!(image.width(), u32::from(metadata.cols()));
+        assert_eq!(image.height(), u32::from(metadata.rows()));
     }
 
     #[test]
@@ -948,9 +1229,7 @@ mod tests {
         assert_eq!(image.height(), u32::from(metadata.rows()));
 
         // Verify RGB image was created
-        let rgb = image
-            .as_rgb8()
-            .expect("Should be RGB image after grayscale conversion");
+        let rgb = &crate::image::quantize(image).into_rgb8();
         let width = rgb.width();
         let height = rgb.height();
 
@@ -1067,9 +1346,7 @@ mod tests {
         assert_eq!(image.height(), u32::from(metadata.rows()));
 
         // Verify RGB image was created
-        let rgb = image
-            .as_rgb8()
-            .expect("Should be RGB image after grayscale conversion");
+        let rgb = &crate::image::quantize(image).into_rgb8();
         let width = rgb.width();
         let height = rgb.height();
 
@@ -1418,9 +1695,7 @@ mod tests {
         assert_eq!(image.height(), u32::from(metadata.rows()));
 
         // Verify RGB image was created
-        let rgb = image
-            .as_rgb8()
-            .expect("Should be RGB image after grayscale conversion");
+        let rgb = &crate::image::quantize(image).into_rgb8();
         let width = rgb.width();
         let height = rgb.height();
 
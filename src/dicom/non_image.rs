@@ -0,0 +1,195 @@
+//! What DICOM files without an image are, for error messages
+//!
+//! Structured reports, RT plans, presentation states and other non-image
+//! objects have no Rows, Columns or Pixel Data. Their SOP class tells what
+//! they are, so the error can say so and point to what dcmv can do with
+//! them instead.
+
+use dicom::dictionary_std::uids;
+
+/// A kind of non-image object
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NonImage {
+    /// What the file is, e.g. "an RT Plan"
+    pub name: &'static str,
+    /// What to do with it instead of showing it
+    pub hint: &'static str,
+}
+
+const DUMP: &str = "use --dump to list its attributes";
+
+/// SOP classes and the families of SOP classes below them, with what they
+/// are; the first match wins
+const NON_IMAGE_CLASSES: &[(&str, NonImage)] = &[
+    (
+        uids::KEY_OBJECT_SELECTION_DOCUMENT_STORAGE,
+        NonImage {
+            name: "a Key Object Selection",
+            hint: "the key images it flags are shown after it when they are in its directory",
+        },
+    ),
+    (
+        // All Structured Report classes
+        "1.2.840.10008.5.1.4.1.1.88",
+        NonImage {
+            name: "a Structured Report",
+            hint: "use --dump to read its content tree (Content Sequence)",
+        },
+    ),
+    (
+        uids::RT_PLAN_STORAGE,
+        NonImage {
+            name: "an RT Plan",
+            hint: "use --dump to list its beams, fraction groups and prescription",
+        },
+    ),
+    (
+        uids::RT_ION_PLAN_STORAGE,
+        NonImage {
+            name: "an RT Ion Plan",
+            hint: "use --dump to list its beams, fraction groups and prescription",
+        },
+    ),
+    (
+        uids::RT_STRUCTURE_SET_STORAGE,
+        NonImage {
+            name: "an RT Structure Set",
+            hint: "use --dump to list its ROIs and contours; show the images they were drawn on instead",
+        },
+    ),
+    (
+        uids::RT_BEAMS_TREATMENT_RECORD_STORAGE,
+        NonImage {
+            name: "an RT Treatment Record",
+            hint: DUMP,
+        },
+    ),
+    (
+        uids::RT_BRACHY_TREATMENT_RECORD_STORAGE,
+        NonImage {
+            name: "an RT Treatment Record",
+            hint: DUMP,
+        },
+    ),
+    (
+        uids::RT_TREATMENT_SUMMARY_RECORD_STORAGE,
+        NonImage {
+            name: "an RT Treatment Record",
+            hint: DUMP,
+        },
+    ),
+    (
+        uids::RT_ION_BEAMS_TREATMENT_RECORD_STORAGE,
+        NonImage {
+            name: "an RT Treatment Record",
+            hint: DUMP,
+        },
+    ),
+    (
+        // Softcopy and volumetric presentation states, and Standalone VOI LUT
+        "1.2.840.10008.5.1.4.1.1.11",
+        NonImage {
+            name: "a Presentation State",
+            hint: "it holds display settings for other images; show the images it references (Referenced Series Sequence in --dump)",
+        },
+    ),
+    (
+        // PDF, CDA and 3D models
+        "1.2.840.10008.5.1.4.1.1.104",
+        NonImage {
+            name: "an Encapsulated Document",
+            hint: "the document is stored in Encapsulated Document (0042,0011); use --dump to see its MIME type",
+        },
+    ),
+    (
+        // All waveform classes, e.g. ECG
+        "1.2.840.10008.5.1.4.1.1.9",
+        NonImage {
+            name: "a Waveform",
+            hint: "use --dump to list its channels (Waveform Sequence)",
+        },
+    ),
+    (
+        uids::RAW_DATA_STORAGE,
+        NonImage {
+            name: "a Raw Data object",
+            hint: DUMP,
+        },
+    ),
+    (
+        uids::SPATIAL_REGISTRATION_STORAGE,
+        NonImage {
+            name: "a Spatial Registration",
+            hint: DUMP,
+        },
+    ),
+    (
+        uids::DEFORMABLE_SPATIAL_REGISTRATION_STORAGE,
+        NonImage {
+            name: "a Spatial Registration",
+            hint: DUMP,
+        },
+    ),
+    (
+        uids::SPATIAL_FIDUCIALS_STORAGE,
+        NonImage {
+            name: "a Spatial Fiducials object",
+            hint: DUMP,
+        },
+    ),
+    (
+        uids::SURFACE_SEGMENTATION_STORAGE,
+        NonImage {
+            name: "a Surface Segmentation",
+            hint: "its surfaces are meshes, not pixels; use --dump to list them",
+        },
+    ),
+];
+
+/// What the file of `sop_class_uid` is, if it is known not to be an image
+#[must_use]
+pub fn non_image_class(sop_class_uid: &str) -> Option<NonImage> {
+    let uid = sop_class_uid.trim_end_matches(['\0', ' ']);
+    NON_IMAGE_CLASSES
+        .iter()
+        .find(|(class, _)| {
+            uid.strip_prefix(class)
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+        })
+        .map(|&(_, kind)| kind)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_non_image_classes() {
+        assert_eq!(
+            non_image_class(uids::RT_PLAN_STORAGE).unwrap().name,
+            "an RT Plan"
+        );
+        assert_eq!(
+            non_image_class(uids::COMPREHENSIVE_SR_STORAGE)
+                .unwrap()
+                .name,
+            "a Structured Report"
+        );
+        assert_eq!(
+            non_image_class(uids::KEY_OBJECT_SELECTION_DOCUMENT_STORAGE)
+                .unwrap()
+                .name,
+            "a Key Object Selection"
+        );
+        assert_eq!(
+            non_image_class(uids::TWELVE_LEAD_ECG_WAVEFORM_STORAGE)
+                .unwrap()
+                .name,
+            "a Waveform"
+        );
+        // Images, and classes that only share the start of their UID
+        assert_eq!(non_image_class(uids::CT_IMAGE_STORAGE), None);
+        assert_eq!(non_image_class(uids::RT_DOSE_STORAGE), None);
+        assert_eq!(non_image_class("1.2.840.10008.5.1.4.1.1.1040"), None);
+    }
+}
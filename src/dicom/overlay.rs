@@ -0,0 +1,320 @@
+//! Overlay planes (Overlay groups 60xx to 601E)
+//!
+//! Overlays are bitmaps shown over the image, such as markers or text
+//! placed by the modality. They are stored in Overlay Data, or in older
+//! files (mostly CR) in bits of the pixel data that hold no sample value,
+//! as told by Overlay Bit Position. Those bits are taken out of the samples
+//! here, so that they become overlays instead of bright specks in the
+//! grayscale values.
+
+use super::DicomObject;
+use super::pixel_data::DecodedPixelData;
+use crate::explain::DecisionLog;
+use crate::types::{Dimensions, OverlayPlane};
+use dicom::core::Tag;
+use dicom::dictionary_std::tags;
+
+/// Overlay groups, 6000 to 601E in steps of 2
+fn overlay_groups() -> impl Iterator<Item = u16> {
+    (0x6000..=0x601E).step_by(2)
+}
+
+/// Placement of an overlay group, as declared
+struct Layout {
+    frame: u32,
+    origin: (i32, i32),
+    width: u32,
+    height: u32,
+}
+
+fn layout(obj: &DicomObject, group: u16, dimensions: Dimensions) -> Layout {
+    let int = |element: u16| {
+        obj.get(Tag(group, element))
+            .and_then(|e| e.to_multi_int::<i32>().ok())
+    };
+    let size = |element: u16, default: u16| {
+        int(element)
+            .and_then(|values| values.first().copied())
+            .and_then(|value| u32::try_from(value).ok())
+            .unwrap_or(u32::from(default))
+    };
+    // Overlay Origin is the 1-based row and column of the first overlay pixel
+    let origin = int(0x0050)
+        .filter(|values| values.len() >= 2)
+        .map_or((0, 0), |values| (values[1] - 1, values[0] - 1));
+    let frame = int(0x0051)
+        .and_then(|values| values.first().copied())
+        .and_then(|value| u32::try_from(value - 1).ok())
+        .unwrap_or(0);
+
+    Layout {
+        frame,
+        origin,
+        width: size(0x0011, dimensions.cols),
+        height: size(0x0010, dimensions.rows),
+    }
+}
+
+/// Overlays stored in Overlay Data
+///
+/// Only the first frame of a multi-frame overlay is read.
+#[must_use]
+pub fn extract_overlay_data(obj: &DicomObject, dimensions: Dimensions) -> Vec<OverlayPlane> {
+    overlay_groups()
+        .filter_map(|group| {
+            let data = obj.get(Tag(group, 0x3000))?.to_bytes().ok()?;
+            let Layout {
+                frame,
+                origin,
+                width,
+                height,
+            } = layout(obj, group, dimensions);
+            let count = width as usize * height as usize;
+            // Bits are packed from the least significant bit of each byte
+            let bits: Vec<bool> = (0..count)
+                .map(|index| {
+                    data.get(index / 8)
+                        .is_some_and(|byte| byte >> (index % 8) & 1 != 0)
+                })
+                .collect();
+            (count > 0).then_some(OverlayPlane {
+                frame,
+                origin,
+                width,
+                height,
+                bits,
+            })
+        })
+        .collect()
+}
+
+/// Take overlays and other bits outside Bits Stored out of grayscale
+/// samples
+///
+/// Applies to unsigned native 8 and 16-bit grayscale data, whose samples
+/// are left holding only the bits from High Bit down, shifted to the
+/// bottom. Overlays declared at an Overlay Bit Position are returned.
+/// Set bits that no overlay explains are noted in `warnings`.
+pub fn separate_embedded_overlays(
+    obj: &DicomObject,
+    dimensions: Dimensions,
+    samples_per_pixel: u16,
+    data: &mut DecodedPixelData,
+    log: &mut DecisionLog,
+    warnings: &mut Vec<String>,
+) -> Vec<OverlayPlane> {
+    let DecodedPixelData::Native(samples) = data else {
+        return Vec::new();
+    };
+    let uint = |tag| obj.get(tag).and_then(|e| e.to_int::<u16>().ok());
+    let (Some(allocated), Some(stored)) = (uint(tags::BITS_ALLOCATED), uint(tags::BITS_STORED))
+    else {
+        return Vec::new();
+    };
+    let high_bit = uint(tags::HIGH_BIT).unwrap_or(stored.saturating_sub(1));
+    let signed = uint(tags::PIXEL_REPRESENTATION) == Some(1);
+    if samples_per_pixel != 1
+        || signed
+        || !matches!(allocated, 8 | 16)
+        || stored == 0
+        || high_bit >= allocated
+        || high_bit + 1 < stored
+    {
+        return Vec::new();
+    }
+    let sample_bits = SampleBits {
+        bytes: usize::from(allocated / 8),
+        low: high_bit + 1 - stored,
+        stored,
+    };
+    if sample_bits.low == 0 && stored == allocated {
+        return Vec::new();
+    }
+
+    // Overlays in bits that hold sample values would be the image itself
+    let embedded: Vec<(u16, Layout)> = overlay_groups()
+        .filter(|&group| obj.get(Tag(group, 0x3000)).is_none())
+        .filter_map(|group| {
+            let bit = obj
+                .get(Tag(group, 0x0102))
+                .and_then(|e| e.to_int::<u16>().ok())?;
+            (bit < allocated && !sample_bits.holds_value(bit))
+                .then(|| (bit, layout(obj, group, dimensions)))
+        })
+        .collect();
+
+    let frame_size = dimensions.pixel_count() * sample_bits.bytes;
+    let overlays: Vec<OverlayPlane> = embedded
+        .iter()
+        .filter_map(|(bit, overlay)| {
+            let start = overlay.frame as usize * frame_size;
+            let frame = samples.get(start..start + frame_size)?;
+            Some(embedded_plane(
+                frame,
+                dimensions,
+                sample_bits.bytes,
+                *bit,
+                overlay,
+            ))
+        })
+        .collect();
+
+    let overlay_mask = embedded
+        .iter()
+        .fold(0_u32, |mask, (bit, _)| mask | 1 << bit);
+    let stray = sample_bits.clear(samples, overlay_mask);
+
+    if !embedded.is_empty() {
+        let bits: Vec<String> = embedded.iter().map(|(bit, _)| bit.to_string()).collect();
+        log.record(
+            "Overlay Bits",
+            format!(
+                "{} overlay plane(s) in bit(s) {} of the pixel data, taken out of the samples",
+                embedded.len(),
+                bits.join(", ")
+            ),
+        );
+    }
+    if sample_bits.low > 0 {
+        log.record(
+            "Samples",
+            format!(
+                "bits {}-{high_bit} (High Bit {high_bit}) shifted down to 0",
+                sample_bits.low
+            ),
+        );
+    }
+    if stray > 0 {
+        warnings.push(format!(
+            "{stray} pixel(s) have bits set outside Bits Stored {stored} and High Bit {high_bit}, which were cleared"
+        ));
+    }
+    overlays
+}
+
+/// Where the value of a sample is in its bytes
+#[derive(Debug, Clone, Copy)]
+struct SampleBits {
+    /// Bytes per sample
+    bytes: usize,
+    /// Lowest bit of the value
+    low: u16,
+    stored: u16,
+}
+
+impl SampleBits {
+    fn holds_value(&self, bit: u16) -> bool {
+        (self.low..self.low + self.stored).contains(&bit)
+    }
+
+    fn value_mask(&self) -> u32 {
+        ((1_u32 << self.stored) - 1) << self.low
+    }
+
+    /// Keep only the value bits of each sample, moved to the bottom
+    ///
+    /// Returns the number of samples with bits set outside both the value
+    /// and `overlay_mask`.
+    fn clear(&self, samples: &mut [u8], overlay_mask: u32) -> usize {
+        let value_mask = self.value_mask();
+        let mut stray = 0;
+        for sample in samples.chunks_exact_mut(self.bytes) {
+            let raw = sample_value(sample);
+            if raw & !value_mask & !overlay_mask != 0 {
+                stray += 1;
+            }
+            let value = (raw & value_mask) >> self.low;
+            match self.bytes {
+                1 => sample[0] = value as u8,
+                _ => sample.copy_from_slice(&(value as u16).to_le_bytes()),
+            }
+        }
+        stray
+    }
+}
+
+/// Value of an 8 or 16-bit little endian sample
+fn sample_value(sample: &[u8]) -> u32 {
+    match sample {
+        [byte] => u32::from(*byte),
+        [low, high, ..] => u32::from(u16::from_le_bytes([*low, *high])),
+        [] => 0,
+    }
+}
+
+/// Overlay read from `bit` of the samples of one frame
+fn embedded_plane(
+    frame: &[u8],
+    dimensions: Dimensions,
+    bytes: usize,
+    bit: u16,
+    overlay: &Layout,
+) -> OverlayPlane {
+    let (cols, rows) = (i64::from(dimensions.cols), i64::from(dimensions.rows));
+    let bits = (0..i64::from(overlay.height))
+        .flat_map(|y| (0..i64::from(overlay.width)).map(move |x| (x, y)))
+        .map(|(x, y)| {
+            let (x, y) = (
+                x + i64::from(overlay.origin.0),
+                y + i64::from(overlay.origin.1),
+            );
+            if x < 0 || y < 0 || x >= cols || y >= rows {
+                return false;
+            }
+            let start = (y * cols + x) as usize * bytes;
+            sample_value(&frame[start..start + bytes]) >> bit & 1 != 0
+        })
+        .collect();
+
+    OverlayPlane {
+        frame: overlay.frame,
+        origin: overlay.origin,
+        width: overlay.width,
+        height: overlay.height,
+        bits,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_embedded_overlay_bits_are_taken_out_of_samples() {
+        // 12 bits stored in bits 0-11, an overlay in bit 15 of the second
+        // pixel, and a stray bit 13 in the third
+        let mut samples: Vec<u8> = [0x0100_u16, 0x8FFF, 0x2001]
+            .iter()
+            .flat_map(|v| v.to_le_bytes())
+            .collect();
+        let dimensions = Dimensions::new(1, 3);
+        let overlay = Layout {
+            frame: 0,
+            origin: (0, 0),
+            width: 3,
+            height: 1,
+        };
+
+        let plane = embedded_plane(&samples, dimensions, 2, 15, &overlay);
+        assert_eq!(plane.bits, vec![false, true, false]);
+        assert!(plane.is_set(1, 0));
+
+        let sample_bits = SampleBits {
+            bytes: 2,
+            low: 0,
+            stored: 12,
+        };
+        assert_eq!(sample_bits.clear(&mut samples, 1 << 15), 1);
+        assert_eq!(samples, [0x00, 0x01, 0xFF, 0x0F, 0x01, 0x00]);
+
+        // Values in the top bits are moved down
+        let mut samples = vec![0x10, 0xFF];
+        let sample_bits = SampleBits {
+            bytes: 2,
+            low: 4,
+            stored: 12,
+        };
+        assert_eq!(sample_bits.clear(&mut samples, 0), 0);
+        assert_eq!(samples, [0xF1, 0x0F]);
+    }
+}
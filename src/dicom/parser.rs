@@ -1,6 +1,10 @@
+use super::charset::TextDecoder;
+use super::non_image::non_image_class;
 use crate::types::{
-    BitDepth, Dimensions, PatientInfo, PixelAspectRatio, RescaleParams, SOPClass, SeriesInfo,
-    StudyInfo, TransferSyntax,
+    AcquisitionInfo, BitDepth, Dimensions, ImageOrientation, LossyCompression, ModalityLut,
+    PaletteLut, PatientInfo, PixelAspectRatio, PixelSpacing, PixelValueRange, RescaleParams,
+    RescaleType, SOPClass, SeriesInfo, StudyInfo, TransferSyntax, UltrasoundRegion, ViewInfo,
+    VoiLutFunction, VoiWindow,
 };
 use anyhow::{Context, Result};
 use dicom::core::dictionary::UidDictionary;
@@ -37,7 +41,19 @@ impl ErrorContext {
             parts.push(format!("SOP Class: {sc}")); // Uses Display: "Name (UID)"
         }
 
-        if parts.is_empty() {
+        let non_image = self
+            .sop_class
+            .as_ref()
+            .and_then(|sc| non_image_class(&sc.uid));
+
+        if let Some(kind) = non_image {
+            format!(
+                "Missing or invalid {tag_name} tag - this is a non-image DICOM file, {} ({}); {}",
+                kind.name,
+                parts.join(", "),
+                kind.hint
+            )
+        } else if parts.is_empty() {
             // Generic error when no context available
             format!("Missing or invalid {tag_name} tag")
         } else {
@@ -104,6 +120,230 @@ pub fn extract_rescale_params(
     RescaleParams::new(slope, intercept)
 }
 
+/// Extract the lookup table of the Modality LUT Sequence
+///
+/// Only the first item is used. A first entry count of 0 means 65536
+/// entries. Tables of 8-bit entries may pack two entries in each 16-bit
+/// word of LUT Data, low byte first; tables with fewer data values than
+/// declared are ignored. The first mapped value is signed for `signed`
+/// samples, even in a US descriptor.
+pub fn extract_modality_lut(
+    obj: &FileDicomObject<InMemDicomObject<StandardDataDictionary>>,
+    signed: bool,
+) -> Option<ModalityLut> {
+    let item = obj.get(tags::MODALITY_LUT_SEQUENCE)?.items()?.first()?;
+
+    let descriptor = item.get(tags::LUT_DESCRIPTOR)?.to_multi_int::<i32>().ok()?;
+    let (&entries, &first_mapped) = (descriptor.first()?, descriptor.get(1)?);
+    // The entry count is unsigned even when the descriptor is SS
+    let entries = match entries & 0xFFFF {
+        0 => 0x1_0000,
+        n => n as usize,
+    };
+    let first_mapped = if signed {
+        i32::from(first_mapped as u16 as i16)
+    } else {
+        first_mapped
+    };
+
+    let mut data = item.get(tags::LUT_DATA)?.to_multi_int::<u16>().ok()?;
+    if descriptor.get(2) == Some(&8) && data.len() < entries {
+        data = data
+            .iter()
+            .flat_map(|word| word.to_le_bytes())
+            .map(u16::from)
+            .collect();
+    }
+    if data.len() < entries {
+        return None;
+    }
+    data.truncate(entries);
+
+    ModalityLut::new(first_mapped, data)
+}
+
+/// Extract the Red, Green and Blue Palette Color Lookup Tables
+///
+/// Segmented tables are not supported. Entries of 16 bits are scaled down
+/// to 8.
+pub fn extract_palette(
+    obj: &FileDicomObject<InMemDicomObject<StandardDataDictionary>>,
+) -> Option<PaletteLut> {
+    let channel = |descriptor_tag, data_tag| {
+        let descriptor = obj.get(descriptor_tag)?.to_multi_int::<i32>().ok()?;
+        let (&entries, &first_mapped) = (descriptor.first()?, descriptor.get(1)?);
+        let entries = match entries & 0xFFFF {
+            0 => 0x1_0000,
+            n => n as usize,
+        };
+        let bits = descriptor.get(2).copied().unwrap_or(16);
+
+        let mut data = obj.get(data_tag)?.to_multi_int::<u16>().ok()?;
+        if bits == 8 && data.len() < entries {
+            data = data
+                .iter()
+                .flat_map(|word| word.to_le_bytes())
+                .map(u16::from)
+                .collect();
+        }
+        if data.len() < entries {
+            return None;
+        }
+        let levels = data[..entries]
+            .iter()
+            .map(|&value| {
+                if bits == 8 {
+                    value.min(255) as u8
+                } else {
+                    (value >> 8) as u8
+                }
+            })
+            .collect::<Vec<_>>();
+        Some((first_mapped, levels))
+    };
+
+    let (first_mapped, red) = channel(
+        tags::RED_PALETTE_COLOR_LOOKUP_TABLE_DESCRIPTOR,
+        tags::RED_PALETTE_COLOR_LOOKUP_TABLE_DATA,
+    )?;
+    let (_, green) = channel(
+        tags::GREEN_PALETTE_COLOR_LOOKUP_TABLE_DESCRIPTOR,
+        tags::GREEN_PALETTE_COLOR_LOOKUP_TABLE_DATA,
+    )?;
+    let (_, blue) = channel(
+        tags::BLUE_PALETTE_COLOR_LOOKUP_TABLE_DESCRIPTOR,
+        tags::BLUE_PALETTE_COLOR_LOOKUP_TABLE_DATA,
+    )?;
+
+    let colors = red
+        .into_iter()
+        .zip(green)
+        .zip(blue)
+        .map(|((r, g), b)| [r, g, b])
+        .collect();
+    PaletteLut::new(first_mapped, colors)
+}
+
+/// Extract the unit of rescaled values
+///
+/// Uses Rescale Type, or the Modality LUT Type of a Modality LUT Sequence.
+/// The CT Image module has no Rescale Type: CT values are Hounsfield units
+/// by definition, so that is assumed when the tag is missing.
+pub fn extract_rescale_type(
+    obj: &FileDicomObject<InMemDicomObject<StandardDataDictionary>>,
+) -> Option<RescaleType> {
+    let lut_type = || {
+        obj.get(tags::MODALITY_LUT_SEQUENCE)?
+            .items()?
+            .first()?
+            .get(tags::MODALITY_LUT_TYPE)
+    };
+    let rescale_type = obj
+        .get(tags::RESCALE_TYPE)
+        .or_else(lut_type)
+        .and_then(|e| e.value().to_str().ok())
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty());
+
+    if let Some(rescale_type) = rescale_type {
+        return Some(RescaleType::parse(&rescale_type));
+    }
+
+    obj.get(tags::MODALITY)
+        .and_then(|e| e.value().to_str().ok())
+        .filter(|modality| modality.trim() == "CT")
+        .map(|_| RescaleType::Hounsfield)
+}
+
+/// Extract the first Window Center/Width pair and its VOI LUT Function
+///
+/// Windows without a positive width are ignored.
+pub fn extract_voi_window(
+    obj: &FileDicomObject<InMemDicomObject<StandardDataDictionary>>,
+) -> Option<VoiWindow> {
+    let first = |tag| {
+        obj.get(tag)
+            .and_then(|e| e.to_multi_float64().ok())
+            .and_then(|values| values.first().copied())
+    };
+    let center = first(tags::WINDOW_CENTER)?;
+    let width = first(tags::WINDOW_WIDTH).filter(|&width| width > 0.0)?;
+    let function = obj
+        .get(tags::VOILUT_FUNCTION)
+        .and_then(|e| e.value().to_str().ok())
+        .map(|s| VoiLutFunction::parse(&s))
+        .unwrap_or_default();
+
+    Some(VoiWindow {
+        center,
+        width,
+        function,
+    })
+}
+
+/// Extract the declared stored value range, if both tags are present and consistent
+///
+/// Negative (signed) values are ignored, as stored samples are read unsigned.
+pub fn extract_pixel_value_range(
+    obj: &FileDicomObject<InMemDicomObject<StandardDataDictionary>>,
+) -> Option<PixelValueRange> {
+    let smallest = obj
+        .get(tags::SMALLEST_IMAGE_PIXEL_VALUE)
+        .and_then(|e| e.to_int::<i32>().ok())?;
+
+    let largest = obj
+        .get(tags::LARGEST_IMAGE_PIXEL_VALUE)
+        .and_then(|e| e.to_int::<i32>().ok())?;
+
+    (smallest <= largest).then(|| PixelValueRange::new(smallest, largest))
+}
+
+/// Extract the patient-space orientation of the image axes
+///
+/// Uses Image Orientation (Patient) for cross-sectional images and falls back
+/// to Patient Orientation, which projection images (CR, DX, ...) carry instead.
+pub fn extract_image_orientation(
+    obj: &FileDicomObject<InMemDicomObject<StandardDataDictionary>>,
+) -> Option<ImageOrientation> {
+    let cosines = obj
+        .get(tags::IMAGE_ORIENTATION_PATIENT)
+        .and_then(|e| e.to_multi_float64().ok())
+        .filter(|values| values.len() == 6);
+
+    if let Some(v) = cosines {
+        return Some(ImageOrientation::new(
+            [v[0], v[1], v[2]],
+            [v[3], v[4], v[5]],
+        ));
+    }
+
+    obj.get(tags::PATIENT_ORIENTATION)
+        .and_then(|e| e.value().to_str().ok())
+        .and_then(|s| ImageOrientation::from_patient_orientation(&s))
+}
+
+/// Extract Image Position (Patient), the center of the first pixel in mm
+pub fn extract_image_position(
+    obj: &FileDicomObject<InMemDicomObject<StandardDataDictionary>>,
+) -> Option<[f64; 3]> {
+    obj.get(tags::IMAGE_POSITION_PATIENT)
+        .and_then(|e| e.to_multi_float64().ok())
+        .and_then(|values| <[f64; 3]>::try_from(values).ok())
+}
+
+/// Extract the pixel spacing in millimeters
+///
+/// Falls back to Imager Pixel Spacing, used by projection radiography.
+pub fn extract_pixel_spacing(
+    obj: &FileDicomObject<InMemDicomObject<StandardDataDictionary>>,
+) -> Option<PixelSpacing> {
+    [tags::PIXEL_SPACING, tags::IMAGER_PIXEL_SPACING]
+        .into_iter()
+        .filter_map(|tag| obj.get(tag).and_then(|e| e.to_multi_float64().ok()))
+        .find(|values| values.len() == 2 && values.iter().all(|&v| v > 0.0))
+        .map(|values| PixelSpacing::new(values[0], values[1]))
+}
+
 pub fn extract_pixel_aspect_ratio(
     obj: &FileDicomObject<InMemDicomObject<StandardDataDictionary>>,
 ) -> Option<PixelAspectRatio> {
@@ -126,6 +366,87 @@ pub fn extract_number_of_frames(
         .unwrap_or(1)
 }
 
+/// Extract the SOP Instance UID, which identifies this object
+pub fn extract_sop_instance_uid(
+    obj: &FileDicomObject<InMemDicomObject<StandardDataDictionary>>,
+) -> Option<String> {
+    obj.get(tags::SOP_INSTANCE_UID)
+        .and_then(|e| e.to_str().ok())
+        .map(|uid| uid.trim_end_matches(['\0', ' ']).to_string())
+        .filter(|uid| !uid.is_empty())
+}
+
+/// Extract the Frame of Reference UID, shared by images whose positions are
+/// in the same patient coordinate system
+pub fn extract_frame_of_reference_uid(
+    obj: &FileDicomObject<InMemDicomObject<StandardDataDictionary>>,
+) -> Option<String> {
+    obj.get(tags::FRAME_OF_REFERENCE_UID)
+        .and_then(|e| e.to_str().ok())
+        .map(|uid| uid.trim_end_matches(['\0', ' ']).to_string())
+        .filter(|uid| !uid.is_empty())
+}
+
+/// Extract the lossy compression history, if Lossy Image Compression is "01"
+pub fn extract_lossy_compression(
+    obj: &FileDicomObject<InMemDicomObject<StandardDataDictionary>>,
+) -> Option<LossyCompression> {
+    obj.get(tags::LOSSY_IMAGE_COMPRESSION)
+        .and_then(|e| e.to_str().ok())
+        .filter(|value| value.trim() == "01")?;
+
+    Some(LossyCompression {
+        ratios: obj
+            .get(tags::LOSSY_IMAGE_COMPRESSION_RATIO)
+            .and_then(|e| e.to_multi_float64().ok())
+            .unwrap_or_default(),
+        methods: obj
+            .get(tags::LOSSY_IMAGE_COMPRESSION_METHOD)
+            .and_then(|e| e.to_multi_str().ok())
+            .map(|methods| {
+                methods
+                    .iter()
+                    .map(|method| method.trim().to_string())
+                    .filter(|method| !method.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default(),
+    })
+}
+
+/// Extract Burned In Annotation: whether identifying text is drawn into the
+/// pixels, `None` if the tag is missing or neither YES nor NO
+pub fn extract_burned_in_annotation(
+    obj: &FileDicomObject<InMemDicomObject<StandardDataDictionary>>,
+) -> Option<bool> {
+    let value = obj.get(tags::BURNED_IN_ANNOTATION)?.to_str().ok()?;
+    match value.trim() {
+        "YES" => Some(true),
+        "NO" => Some(false),
+        _ => None,
+    }
+}
+
+/// Extract how long each frame of a cine loop is shown, in milliseconds
+///
+/// Uses Frame Time, falling back to Cine Rate and then Recommended Display
+/// Frame Rate (both in frames per second).
+pub fn extract_frame_time(
+    obj: &FileDicomObject<InMemDicomObject<StandardDataDictionary>>,
+) -> Option<f64> {
+    let positive = |tag| {
+        obj.get(tag)
+            .and_then(|e| e.to_float64().ok())
+            .filter(|&value| value > 0.0)
+    };
+
+    positive(tags::FRAME_TIME).or_else(|| {
+        positive(tags::CINE_RATE)
+            .or_else(|| positive(tags::RECOMMENDED_DISPLAY_FRAME_RATE))
+            .map(|rate| 1000.0 / rate)
+    })
+}
+
 #[inline]
 pub fn extract_samples_per_pixel(
     obj: &FileDicomObject<InMemDicomObject<StandardDataDictionary>>,
@@ -149,7 +470,15 @@ pub fn extract_bit_depth(
         .and_then(|e| e.to_int::<u16>().ok())
         .ok_or_else(|| anyhow::anyhow!(error_context.format_error("Bits Stored")))?;
 
-    Ok(BitDepth::new(allocated, stored))
+    let signed = obj
+        .get(tags::PIXEL_REPRESENTATION)
+        .and_then(|e| e.to_int::<u16>().ok())
+        == Some(1);
+
+    Ok(BitDepth {
+        signed,
+        ..BitDepth::new(allocated, stored)
+    })
 }
 
 #[inline]
@@ -186,20 +515,22 @@ pub fn extract_sop_class(
 pub fn extract_patient_info(
     obj: &FileDicomObject<InMemDicomObject<StandardDataDictionary>>,
 ) -> PatientInfo {
+    let text = TextDecoder::from_object(obj);
+
     let name = obj
         .get(tags::PATIENT_NAME)
         .and_then(|e| e.value().to_str().ok())
-        .map(|s| s.to_string());
+        .map(|s| text.decode_person_name(&s));
 
     let id = obj
         .get(tags::PATIENT_ID)
         .and_then(|e| e.value().to_str().ok())
-        .map(|s| s.to_string());
+        .map(|s| text.decode(&s));
 
     let birth_date = obj
         .get(tags::PATIENT_BIRTH_DATE)
         .and_then(|e| e.value().to_str().ok())
-        .map(|s| s.to_string());
+        .map(|s| text.decode(&s));
 
     PatientInfo {
         name,
@@ -211,29 +542,37 @@ pub fn extract_patient_info(
 pub fn extract_study_info(
     obj: &FileDicomObject<InMemDicomObject<StandardDataDictionary>>,
 ) -> StudyInfo {
+    let text = TextDecoder::from_object(obj);
+
     let accession_number = obj
         .get(tags::ACCESSION_NUMBER)
         .and_then(|e| e.value().to_str().ok())
-        .map(|s| s.to_string());
+        .map(|s| text.decode(&s));
 
     let date = obj
         .get(tags::STUDY_DATE)
         .and_then(|e| e.value().to_str().ok())
-        .map(|s| s.to_string());
+        .map(|s| text.decode(&s));
+
+    let time = obj
+        .get(tags::STUDY_TIME)
+        .and_then(|e| e.value().to_str().ok())
+        .map(|s| text.decode(&s));
 
     let description = obj
         .get(tags::STUDY_DESCRIPTION)
         .and_then(|e| e.value().to_str().ok())
-        .map(|s| s.to_string());
+        .map(|s| text.decode(&s));
 
     let modality = obj
         .get(tags::MODALITY)
         .and_then(|e| e.value().to_str().ok())
-        .map(|s| s.to_string());
+        .map(|s| text.decode(&s));
 
     StudyInfo {
         accession_number,
         date,
+        time,
         description,
         modality,
     }
@@ -242,17 +581,111 @@ pub fn extract_study_info(
 pub fn extract_series_info(
     obj: &FileDicomObject<InMemDicomObject<StandardDataDictionary>>,
 ) -> SeriesInfo {
-    let description = obj
-        .get(tags::SERIES_DESCRIPTION)
-        .and_then(|e| e.value().to_str().ok())
-        .map(|s| s.to_string());
+    let text = TextDecoder::from_object(obj);
+    let string = |tag| {
+        obj.get(tag)
+            .and_then(|e| e.value().to_str().ok())
+            .map(|s| text.decode(s.trim()))
+            .filter(|s| !s.is_empty())
+    };
 
     let slice_thickness = obj
         .get(tags::SLICE_THICKNESS)
         .and_then(|e| e.to_float64().ok());
 
     SeriesInfo {
-        description,
+        description: obj
+            .get(tags::SERIES_DESCRIPTION)
+            .and_then(|e| e.value().to_str().ok())
+            .map(|s| text.decode(&s)),
         slice_thickness,
+        body_part_examined: string(tags::BODY_PART_EXAMINED),
+        institution_name: string(tags::INSTITUTION_NAME),
+        station_name: string(tags::STATION_NAME),
+    }
+}
+
+/// Physical Units code for centimeters in ultrasound regions
+const ULTRASOUND_UNITS_CM: u16 = 3;
+
+/// Extract the Sequence of Ultrasound Regions
+///
+/// Items without a complete location are left out.
+pub fn extract_ultrasound_regions(
+    obj: &FileDicomObject<InMemDicomObject<StandardDataDictionary>>,
+) -> Vec<UltrasoundRegion> {
+    let Some(items) = obj
+        .get(tags::SEQUENCE_OF_ULTRASOUND_REGIONS)
+        .and_then(|e| e.items())
+    else {
+        return Vec::new();
+    };
+
+    items
+        .iter()
+        .filter_map(|item| {
+            let int = |tag| item.get(tag).and_then(|e| e.to_int::<u32>().ok());
+            let float = |tag| item.get(tag).and_then(|e| e.to_float64().ok());
+            let in_cm = |tag| {
+                item.get(tag).and_then(|e| e.to_int::<u16>().ok()) == Some(ULTRASOUND_UNITS_CM)
+            };
+
+            let spacing = match (float(tags::PHYSICAL_DELTA_X), float(tags::PHYSICAL_DELTA_Y)) {
+                (Some(x), Some(y))
+                    if in_cm(tags::PHYSICAL_UNITS_X_DIRECTION)
+                        && in_cm(tags::PHYSICAL_UNITS_Y_DIRECTION)
+                        && x > 0.0
+                        && y > 0.0 =>
+                {
+                    Some(PixelSpacing::new(y * 10.0, x * 10.0))
+                }
+                _ => None,
+            };
+
+            Some(UltrasoundRegion {
+                min: (
+                    int(tags::REGION_LOCATION_MIN_X0)?,
+                    int(tags::REGION_LOCATION_MIN_Y0)?,
+                ),
+                max: (
+                    int(tags::REGION_LOCATION_MAX_X1)?,
+                    int(tags::REGION_LOCATION_MAX_Y1)?,
+                ),
+                spatial_format: item
+                    .get(tags::REGION_SPATIAL_FORMAT)
+                    .and_then(|e| e.to_int::<u16>().ok())
+                    .unwrap_or(0),
+                spacing,
+            })
+        })
+        .filter(|region| region.min.0 <= region.max.0 && region.min.1 <= region.max.1)
+        .collect()
+}
+
+/// Extract View Position and laterality, which projection images carry
+pub fn extract_view_info(
+    obj: &FileDicomObject<InMemDicomObject<StandardDataDictionary>>,
+) -> ViewInfo {
+    let code = |tag| {
+        obj.get(tag)
+            .and_then(|e| e.value().to_str().ok())
+            .map(|s| s.trim().to_ascii_uppercase())
+            .filter(|s| !s.is_empty())
+    };
+
+    ViewInfo {
+        position: code(tags::VIEW_POSITION),
+        laterality: code(tags::IMAGE_LATERALITY).or_else(|| code(tags::LATERALITY)),
+    }
+}
+
+pub fn extract_acquisition_info(
+    obj: &FileDicomObject<InMemDicomObject<StandardDataDictionary>>,
+) -> AcquisitionInfo {
+    let number = |tag| obj.get(tag).and_then(|e| e.to_float64().ok());
+
+    AcquisitionInfo {
+        kvp: number(tags::KVP),
+        exposure_time: number(tags::EXPOSURE_TIME),
     }
 }
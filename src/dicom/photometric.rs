@@ -10,14 +10,17 @@ pub enum PhotometricInterpretation {
     Rgb,
     YbrFull,
     YbrFull422,
-    // TODO: Implement Palette color support
-    // Failing files: examples_palette.dcm (2 instances)
-    // Requires reading Palette Color Lookup Table (CLUT) data and mapping pixel values through it
+    YbrPartial422,
+    YbrPartial420,
+    // Stored values are indices into the Palette Color Lookup Tables
     Palette,
-    // TODO: Implement YBR_RCT (reversible color transform) support
-    // Failing files: GDCMJ2K_TextGBR.dcm, examples_jpeg2k.dcm
-    // YBR_RCT is used with JPEG2000 compression, needs conversion similar to YBR_FULL
+    // JPEG 2000 irreversible/reversible component transforms; the codec
+    // inverts them, so decoded pixel data is already RGB
+    YbrIct,
     YbrRct,
+    // Retired color spaces still found in legacy secondary capture files
+    Hsv,
+    Cmyk,
     Unknown(String),
 }
 
@@ -31,8 +34,13 @@ impl FromStr for PhotometricInterpretation {
             "RGB" => Self::Rgb,
             "YBR_FULL" => Self::YbrFull,
             "YBR_FULL_422" => Self::YbrFull422,
+            "YBR_PARTIAL_422" => Self::YbrPartial422,
+            "YBR_PARTIAL_420" => Self::YbrPartial420,
             "PALETTE COLOR" => Self::Palette,
+            "YBR_ICT" => Self::YbrIct,
             "YBR_RCT" => Self::YbrRct,
+            "HSV" => Self::Hsv,
+            "CMYK" => Self::Cmyk,
             other => Self::Unknown(other.to_string()),
         })
     }
@@ -54,7 +62,22 @@ impl PhotometricInterpretation {
     #[inline]
     #[must_use]
     pub fn is_ycbcr(&self) -> bool {
-        matches!(self, Self::YbrFull | Self::YbrFull422 | Self::YbrRct)
+        matches!(
+            self,
+            Self::YbrFull
+                | Self::YbrFull422
+                | Self::YbrPartial422
+                | Self::YbrPartial420
+                | Self::YbrIct
+                | Self::YbrRct
+        )
+    }
+
+    /// Video-range YCbCr (BT.601 limited range: Y in 16..=235, Cb/Cr in 16..=240)
+    #[inline]
+    #[must_use]
+    pub fn is_partial_range(&self) -> bool {
+        matches!(self, Self::YbrPartial422 | Self::YbrPartial420)
     }
 
     #[inline]
@@ -72,8 +95,13 @@ impl Display for PhotometricInterpretation {
             Self::Rgb => write!(f, "RGB"),
             Self::YbrFull => write!(f, "YBR_FULL"),
             Self::YbrFull422 => write!(f, "YBR_FULL_422"),
+            Self::YbrPartial422 => write!(f, "YBR_PARTIAL_422"),
+            Self::YbrPartial420 => write!(f, "YBR_PARTIAL_420"),
             Self::Palette => write!(f, "PALETTE COLOR"),
+            Self::YbrIct => write!(f, "YBR_ICT"),
             Self::YbrRct => write!(f, "YBR_RCT"),
+            Self::Hsv => write!(f, "HSV"),
+            Self::Cmyk => write!(f, "CMYK"),
             Self::Unknown(s) => write!(f, "{s}"),
         }
     }
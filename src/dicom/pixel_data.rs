@@ -1,10 +1,14 @@
+use crate::explain::DecisionLog;
+use crate::types::ByteOrder;
 use anyhow::{Context, Result};
+use dicom::core::PrimitiveValue;
 use dicom::core::header::HasLength;
 use dicom::dictionary_std::tags;
 use dicom::object::{FileDicomObject, InMemDicomObject, StandardDataDictionary};
-use dicom::pixeldata::{ConvertOptions, PixelDecoder};
+use dicom::pixeldata::{ConvertOptions, DecodedPixelData as PixelDecoderOutput, PixelDecoder};
 use dicom::transfer_syntax::entries;
 use image::DynamicImage::ImageRgb8;
+use tracing::{debug, debug_span};
 
 #[derive(Debug, Clone)]
 pub enum DecodedPixelData {
@@ -13,13 +17,50 @@ pub enum DecodedPixelData {
     Native(Box<[u8]>),
 }
 
+/// A frame that failed to decode and was left out of the pixel data
+#[derive(Debug, Clone)]
+pub struct FrameError {
+    /// From 0; messages number frames from 1
+    pub index: u32,
+    pub message: String,
+}
+
+/// Pixel data together with any frames that had to be skipped
+#[derive(Debug, Clone)]
+pub struct ExtractedPixelData {
+    pub data: DecodedPixelData,
+    pub failed_frames: Vec<FrameError>,
+    /// Problems worked around while decoding, shown as warnings
+    pub warnings: Vec<String>,
+}
+
+impl From<DecodedPixelData> for ExtractedPixelData {
+    fn from(data: DecodedPixelData) -> Self {
+        Self {
+            data,
+            failed_frames: Vec::new(),
+            warnings: Vec::new(),
+        }
+    }
+}
+
+/// Decode the pixel data of `obj`, noting the decoding path taken in `log`
 pub fn extract_pixel_data(
     obj: &FileDicomObject<InMemDicomObject<StandardDataDictionary>>,
     bits_allocated: u16,
     photometric_interpretation: &str,
     transfer_syntax_uid: &str,
     planar_configuration: Option<u16>,
-) -> Result<DecodedPixelData> {
+    byte_order: Option<ByteOrder>,
+    log: &mut DecisionLog,
+) -> Result<ExtractedPixelData> {
+    let _span = debug_span!(
+        "decode",
+        transfer_syntax = transfer_syntax_uid,
+        photometric = photometric_interpretation
+    )
+    .entered();
+
     // Check for pixel data presence early (without reading data into memory)
     match obj.element(tags::PIXEL_DATA) {
         Ok(element) => {
@@ -37,8 +78,44 @@ pub fn extract_pixel_data(
     let compressed = is_compressed(transfer_syntax_uid);
     let is_ycbcr = photometric_interpretation.contains("YBR");
 
-    if photometric_interpretation == "YBR_FULL" && !compressed {
-        return extract_via_dynamic_image(obj);
+    let mut warnings = Vec::new();
+    // Samples stored in the other byte order than the transfer syntax says
+    // are read as declared, then swapped
+    let swap = byte_order.is_some_and(|forced| (forced == ByteOrder::Big) != is_big_endian);
+    let swap = if swap && compressed {
+        warnings.push(
+            "--force-endian was ignored, compressed pixel data are in the byte order of their codec"
+                .to_string(),
+        );
+        false
+    } else {
+        swap && bits_allocated > 8
+    };
+
+    if compressed {
+        note(log, "Decoder", format!("{} codec", decoder_name()));
+    } else if let Some(forced) = byte_order.filter(|_| swap) {
+        let declared = if is_big_endian {
+            ByteOrder::Big
+        } else {
+            ByteOrder::Little
+        };
+        note(
+            log,
+            "Byte Order",
+            format!("{forced} (--force-endian), declared {declared}, bytes of each sample swapped"),
+        );
+        warnings.push(format!(
+            "Pixel data read as {forced} (--force-endian), the transfer syntax declares {declared}"
+        ));
+    } else if is_big_endian {
+        note(
+            log,
+            "Byte Order",
+            "big endian, converted to little endian when read",
+        );
+    } else {
+        note(log, "Byte Order", "little endian, used as stored");
     }
 
     if photometric_interpretation == "RGB"
@@ -46,37 +123,150 @@ pub fn extract_pixel_data(
         && bits_allocated == 8
         && !compressed
     {
-        return extract_via_dynamic_image(obj);
+        note(
+            log,
+            "Pixel Decoding",
+            "planar 8-bit RGB, interleaved by to_dynamic_image",
+        );
+        return extract_via_dynamic_image(obj).map(Into::into);
     }
 
     if bits_allocated == 16
         && is_big_endian
+        && !swap
         && !is_ycbcr
         && photometric_interpretation == "RGB"
         && !compressed
     {
-        return extract_via_dynamic_image(obj);
+        note(
+            log,
+            "Pixel Decoding",
+            "big endian 16-bit RGB, converted to 8-bit RGB by to_dynamic_image",
+        );
+        return extract_via_dynamic_image(obj).map(Into::into);
     }
 
-    let format =
-        if is_ycbcr || photometric_interpretation == "PALETTE COLOR" || bits_allocated == 32 {
-            DecodedPixelFormat::YcbCr
-        } else {
-            DecodedPixelFormat::Native
-        };
+    let is_retired_color = matches!(photometric_interpretation, "HSV" | "CMYK");
 
-    let data = if !compressed && matches!(format, DecodedPixelFormat::YcbCr) {
-        extract_raw_pixel_data(obj)?
+    let format = if is_ycbcr
+        || is_retired_color
+        || photometric_interpretation == "PALETTE COLOR"
+        || bits_allocated == 32
+    {
+        DecodedPixelFormat::YcbCr
     } else {
-        extract_decoded_pixel_data(obj, bits_allocated)?
+        DecodedPixelFormat::Native
     };
 
-    Ok(match format {
+    let (data, failed_frames) = if !compressed && matches!(format, DecodedPixelFormat::YcbCr) {
+        note(
+            log,
+            "Pixel Decoding",
+            format!("raw {bits_allocated}-bit samples, converted later"),
+        );
+        (extract_raw_pixel_data(obj)?, Vec::new())
+    } else if compressed {
+        note(
+            log,
+            "Pixel Decoding",
+            format!("decoded to {bits_allocated}-bit samples"),
+        );
+        match extract_decoded_frames_isolated(obj, bits_allocated, log) {
+            Ok(result) => result,
+            // The upstream RLE codec does not support every bit depth (e.g. 32-bit)
+            Err(e) if transfer_syntax_uid == entries::RLE_LOSSLESS.uid() => {
+                note(
+                    log,
+                    "Decoder",
+                    format!(
+                        "{} RLE codec failed ({e:#}), used the native RLE decoder",
+                        decoder_name()
+                    ),
+                );
+                let data = decode_rle_native(obj, bits_allocated, planar_configuration)
+                    .map_err(|native| e.context(format!("Native RLE decoding failed: {native}")))?;
+                (data, Vec::new())
+            }
+            Err(e) => return Err(e),
+        }
+    } else {
+        note(
+            log,
+            "Pixel Decoding",
+            format!("native {bits_allocated}-bit samples"),
+        );
+        match extract_decoded_pixel_data(obj, bits_allocated) {
+            Ok(data) => (data, Vec::new()),
+            // Slightly non-conformant files (wrong VR, odd padding) can
+            // still be read as stored
+            Err(e) => {
+                let reason = format!("{e:#}");
+                let data =
+                    decode_raw_native(obj, bits_allocated, is_big_endian).map_err(|raw| {
+                        // The decoder's error says more about what is wrong
+                        debug!("raw pixel data fallback failed: {raw:#}");
+                        e
+                    })?;
+                note(
+                    log,
+                    "Pixel Decoding",
+                    format!("decoder failed ({reason}), read raw samples from PixelData"),
+                );
+                warnings.push(format!(
+                    "Pixel data could not be decoded ({reason}), showing the raw samples as stored"
+                ));
+                (data, Vec::new())
+            }
+        }
+    };
+
+    let data = if swap {
+        swap_bytes(data, usize::from(bits_allocated / 8))
+    } else {
+        data
+    };
+
+    // Exports from some writers leave extra bytes or frames after the pixel data
+    let data = match native_layout(obj, bits_allocated, photometric_interpretation) {
+        Some((frame_size, frames)) if !compressed => {
+            let (data, warning) = trim_excess(data, frame_size, frames);
+            warnings.extend(warning);
+            data
+        }
+        _ => data,
+    };
+    for frame in &failed_frames {
+        debug!(frame = frame.index + 1, "frame skipped: {}", frame.message);
+    }
+
+    let data = match format {
         DecodedPixelFormat::YcbCr => DecodedPixelData::YcbCr(data),
         DecodedPixelFormat::Native => DecodedPixelData::Native(data),
+    };
+
+    Ok(ExtractedPixelData {
+        data,
+        failed_frames,
+        warnings,
     })
 }
 
+/// Record a decision, and log it for --debug
+fn note(log: &mut DecisionLog, step: &'static str, decision: impl Into<String>) {
+    let decision = decision.into();
+    debug!("{step}: {decision}");
+    log.record(step, decision);
+}
+
+/// Library that decodes compressed transfer syntaxes in this build
+const fn decoder_name() -> &'static str {
+    if cfg!(feature = "gdcm") {
+        "GDCM"
+    } else {
+        "dicom-rs"
+    }
+}
+
 #[derive(Clone, Copy, PartialEq, Eq)]
 enum DecodedPixelFormat {
     YcbCr,
@@ -146,6 +336,174 @@ fn extract_decoded_pixel_data(
         .decode_pixel_data()
         .context("Failed to decode pixel data")?;
 
+    decoded_to_bytes(&decoded_pixel_data, bits_allocated)
+}
+
+/// Read uncompressed samples straight from the PixelData element
+///
+/// Used when the pixel data decoder rejects the element. The size follows
+/// from Rows, Columns, Samples per Pixel, Number of Frames and Bits
+/// Allocated; anything stored beyond it, such as padding, is ignored.
+fn decode_raw_native(
+    obj: &FileDicomObject<InMemDicomObject<StandardDataDictionary>>,
+    bits_allocated: u16,
+    is_big_endian: bool,
+) -> Result<Box<[u8]>> {
+    let (frame_size, frames) = native_layout(obj, bits_allocated, "")
+        .with_context(|| format!("Cannot read raw {bits_allocated}-bit pixel data"))?;
+    let expected = frame_size * frames;
+    let bytes_per_sample = usize::from(bits_allocated / 8);
+
+    let value = obj
+        .element(tags::PIXEL_DATA)?
+        .value()
+        .primitive()
+        .context("Pixel data is encapsulated")?;
+    // Words read as OW are already in native byte order, bytes (OB, UN) are as stored
+    let swap = is_big_endian && matches!(value, PrimitiveValue::U8(_));
+
+    raw_samples(&value.to_bytes(), expected, bytes_per_sample, swap)
+}
+
+/// Size in bytes of one uncompressed frame, and the number of frames
+///
+/// `None` for bit-packed samples or missing image attributes.
+fn native_layout(
+    obj: &FileDicomObject<InMemDicomObject<StandardDataDictionary>>,
+    bits_allocated: u16,
+    photometric_interpretation: &str,
+) -> Option<(usize, usize)> {
+    if bits_allocated == 0 || !bits_allocated.is_multiple_of(8) {
+        return None;
+    }
+
+    let dimensions =
+        super::parser::extract_dimensions(obj, &super::parser::ErrorContext::new()).ok()?;
+    let rows = usize::from(dimensions.rows);
+    let cols = usize::from(dimensions.cols);
+    let samples_per_pixel = usize::from(super::parser::extract_samples_per_pixel(obj));
+    let bytes_per_sample = usize::from(bits_allocated / 8);
+
+    // Chroma-subsampled YBR stores fewer than three samples per pixel
+    let samples = match photometric_interpretation {
+        "YBR_FULL_422" | "YBR_PARTIAL_422" => rows * cols * 2,
+        "YBR_PARTIAL_420" => rows * cols + 2 * rows.div_ceil(2) * cols.div_ceil(2),
+        _ => rows * cols * samples_per_pixel,
+    };
+    let frames = super::parser::extract_number_of_frames(obj) as usize;
+
+    Some((samples * bytes_per_sample, frames.max(1)))
+}
+
+/// Drop bytes after the last of `frames` frames of `frame_size` bytes,
+/// with a warning unless it is the pad byte that makes the length even
+fn trim_excess(data: Box<[u8]>, frame_size: usize, frames: usize) -> (Box<[u8]>, Option<String>) {
+    let expected = frame_size * frames;
+    let excess = data.len().saturating_sub(expected);
+    if excess == 0 {
+        return (data, None);
+    }
+
+    let warning = if excess == 1 && expected % 2 == 1 {
+        None
+    } else if excess == 1 {
+        Some("Pixel data has a trailing pad byte, which was ignored".to_string())
+    } else if excess.is_multiple_of(frame_size) {
+        Some(format!(
+            "Pixel data hold {} more frame(s) than the {frames} in Number of Frames, which were ignored",
+            excess / frame_size
+        ))
+    } else {
+        Some(format!(
+            "Pixel data are {excess} bytes longer than expected, the excess was ignored"
+        ))
+    };
+
+    (data[..expected].into(), warning)
+}
+
+/// The first `expected` bytes of `stored`, with the bytes of each sample
+/// reversed if `swap` is set
+fn raw_samples(
+    stored: &[u8],
+    expected: usize,
+    bytes_per_sample: usize,
+    swap: bool,
+) -> Result<Box<[u8]>> {
+    let data: Box<[u8]> = stored
+        .get(..expected)
+        .with_context(|| format!("Pixel data has {} bytes, expected {expected}", stored.len()))?
+        .into();
+
+    Ok(if swap {
+        swap_bytes(data, bytes_per_sample)
+    } else {
+        data
+    })
+}
+
+/// `data` with the bytes of each sample of `bytes_per_sample` reversed
+fn swap_bytes(mut data: Box<[u8]>, bytes_per_sample: usize) -> Box<[u8]> {
+    for sample in data.chunks_exact_mut(bytes_per_sample) {
+        sample.reverse();
+    }
+    data
+}
+
+/// Decode encapsulated pixel data, isolating failures to individual frames
+///
+/// If decoding all frames at once fails for a multi-frame object, each frame
+/// is decoded separately. Frames that still fail are left out and reported,
+/// so the remaining frames can be displayed. Fails only if no frame decodes.
+fn extract_decoded_frames_isolated(
+    obj: &FileDicomObject<InMemDicomObject<StandardDataDictionary>>,
+    bits_allocated: u16,
+    log: &mut DecisionLog,
+) -> Result<(Box<[u8]>, Vec<FrameError>)> {
+    let number_of_frames = super::parser::extract_number_of_frames(obj);
+
+    let error = match extract_decoded_pixel_data(obj, bits_allocated) {
+        Ok(data) => return Ok((data, Vec::new())),
+        Err(e) if number_of_frames <= 1 => return Err(e),
+        Err(e) => e,
+    };
+    note(
+        log,
+        "Pixel Decoding",
+        format!("decoding all frames failed ({error:#}), decoded frame by frame"),
+    );
+
+    let mut data = Vec::new();
+    let mut failed_frames = Vec::new();
+
+    for index in 0..number_of_frames {
+        let frame = obj
+            .decode_pixel_data_frame(index)
+            .context("Failed to decode pixel data")
+            .and_then(|decoded| decoded_to_bytes(&decoded, bits_allocated));
+
+        match frame {
+            Ok(bytes) => data.extend_from_slice(&bytes),
+            Err(e) => failed_frames.push(FrameError {
+                index,
+                message: format!("{e:#}"),
+            }),
+        }
+    }
+
+    if data.is_empty() {
+        return Err(error.context(format!(
+            "Failed to decode pixel data of all {number_of_frames} frames"
+        )));
+    }
+
+    Ok((data.into_boxed_slice(), failed_frames))
+}
+
+fn decoded_to_bytes(
+    decoded_pixel_data: &PixelDecoderOutput<'_>,
+    bits_allocated: u16,
+) -> Result<Box<[u8]>> {
     if bits_allocated == 32 {
         let data = decoded_pixel_data
             .to_vec::<u32>()
@@ -164,3 +522,280 @@ fn extract_decoded_pixel_data(
             .into_boxed_slice())
     }
 }
+
+/// Decode RLE Lossless pixel data without the upstream codec
+///
+/// Handles any bits allocated that is a multiple of 8. Decoded samples are
+/// written little-endian, interleaved or planar according to the planar
+/// configuration, matching the layout produced by the other decoding paths.
+fn decode_rle_native(
+    obj: &FileDicomObject<InMemDicomObject<StandardDataDictionary>>,
+    bits_allocated: u16,
+    planar_configuration: Option<u16>,
+) -> Result<Box<[u8]>> {
+    if bits_allocated == 0 || !bits_allocated.is_multiple_of(8) {
+        anyhow::bail!("Unsupported bits allocated for RLE: {bits_allocated}");
+    }
+
+    let dimensions = super::parser::extract_dimensions(obj, &super::parser::ErrorContext::new())?;
+    let samples_per_pixel = usize::from(super::parser::extract_samples_per_pixel(obj));
+    let number_of_frames = super::parser::extract_number_of_frames(obj) as usize;
+    let bytes_per_sample = usize::from(bits_allocated / 8);
+    let planar = planar_configuration == Some(1);
+
+    let value = obj.element(tags::PIXEL_DATA)?.value();
+    let fragments = value
+        .fragments()
+        .context("RLE pixel data is not encapsulated")?;
+    let offset_table = value.offset_table().unwrap_or(&[]);
+
+    let frames = group_fragments_by_frame(fragments, offset_table, number_of_frames)?;
+    let layout = RleLayout {
+        pixel_count: dimensions.pixel_count(),
+        samples_per_pixel,
+        bytes_per_sample,
+        planar,
+    };
+
+    let mut output = Vec::with_capacity(layout.frame_size() * frames.len());
+    for (index, frame) in frames.iter().enumerate() {
+        decode_rle_frame(frame, &layout, &mut output)
+            .with_context(|| format!("Failed to decode RLE frame {}", index + 1))?;
+    }
+
+    Ok(output.into_boxed_slice())
+}
+
+struct RleLayout {
+    pixel_count: usize,
+    samples_per_pixel: usize,
+    bytes_per_sample: usize,
+    planar: bool,
+}
+
+impl RleLayout {
+    fn frame_size(&self) -> usize {
+        self.pixel_count * self.samples_per_pixel * self.bytes_per_sample
+    }
+}
+
+/// Collect the encapsulated fragments of each frame into one byte stream
+pub(crate) fn group_fragments_by_frame(
+    fragments: &[Vec<u8>],
+    offset_table: &[u32],
+    number_of_frames: usize,
+) -> Result<Vec<Vec<u8>>> {
+    if fragments.len() == number_of_frames {
+        return Ok(fragments.to_vec());
+    }
+
+    if number_of_frames <= 1 {
+        return Ok(vec![fragments.concat()]);
+    }
+
+    if offset_table.len() != number_of_frames {
+        anyhow::bail!(
+            "Cannot map {} fragments to {number_of_frames} frames without a basic offset table",
+            fragments.len()
+        );
+    }
+
+    // Offsets are relative to the first fragment item, including 8-byte item headers
+    let mut positions = Vec::with_capacity(fragments.len());
+    let mut position = 0u64;
+    for fragment in fragments {
+        positions.push(position);
+        position += fragment.len() as u64 + 8;
+    }
+
+    Ok(offset_table
+        .iter()
+        .enumerate()
+        .map(|(index, &start)| {
+            let end = offset_table
+                .get(index + 1)
+                .map_or(u64::MAX, |&end| u64::from(end));
+
+            fragments
+                .iter()
+                .zip(&positions)
+                .filter(|&(_, &pos)| pos >= u64::from(start) && pos < end)
+                .flat_map(|(fragment, _)| fragment.iter().copied())
+                .collect()
+        })
+        .collect())
+}
+
+/// Decode one RLE frame: a 64-byte header followed by one segment per byte
+/// of each sample, most significant byte first
+fn decode_rle_frame(data: &[u8], layout: &RleLayout, output: &mut Vec<u8>) -> Result<()> {
+    const HEADER_SIZE: usize = 64;
+
+    let header = data
+        .get(..HEADER_SIZE)
+        .context("RLE frame is shorter than its header")?;
+    let read_u32 = |index: usize| {
+        let bytes = &header[index * 4..index * 4 + 4];
+        u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize
+    };
+
+    let segment_count = read_u32(0);
+    let expected_segments = layout.samples_per_pixel * layout.bytes_per_sample;
+    if segment_count != expected_segments || segment_count > 15 {
+        anyhow::bail!("RLE frame has {segment_count} segments, expected {expected_segments}");
+    }
+
+    let start = output.len();
+    output.resize(start + layout.frame_size(), 0);
+    let frame = &mut output[start..];
+
+    for segment in 0..segment_count {
+        let begin = read_u32(segment + 1);
+        let end = if segment + 1 < segment_count {
+            read_u32(segment + 2)
+        } else {
+            data.len()
+        };
+
+        let encoded = data
+            .get(begin..end)
+            .with_context(|| format!("RLE segment {segment} is out of bounds"))?;
+        let decoded = decode_packbits(encoded, layout.pixel_count)?;
+
+        let sample = segment / layout.bytes_per_sample;
+        // Segments go from most to least significant byte; output is little-endian
+        let byte = layout.bytes_per_sample - 1 - segment % layout.bytes_per_sample;
+
+        for (pixel, &value) in decoded.iter().enumerate() {
+            let index = if layout.planar {
+                (sample * layout.pixel_count + pixel) * layout.bytes_per_sample + byte
+            } else {
+                (pixel * layout.samples_per_pixel + sample) * layout.bytes_per_sample + byte
+            };
+            frame[index] = value;
+        }
+    }
+
+    Ok(())
+}
+
+/// Decode a single PackBits-encoded RLE segment
+fn decode_packbits(segment: &[u8], expected_len: usize) -> Result<Vec<u8>> {
+    let mut output = Vec::with_capacity(expected_len);
+    let mut i = 0;
+
+    while i < segment.len() && output.len() < expected_len {
+        let header = segment[i] as i8;
+        i += 1;
+
+        match header {
+            0..=127 => {
+                let count = header as usize + 1;
+                let literal = segment
+                    .get(i..i + count)
+                    .context("Truncated RLE literal run")?;
+                output.extend_from_slice(literal);
+                i += count;
+            }
+            -127..=-1 => {
+                let count = usize::from(header.unsigned_abs()) + 1;
+                let value = *segment.get(i).context("Truncated RLE replicate run")?;
+                output.extend(std::iter::repeat_n(value, count));
+                i += 1;
+            }
+            // -128 is a no-op
+            _ => {}
+        }
+    }
+
+    if output.len() < expected_len {
+        anyhow::bail!(
+            "RLE segment decoded to {} bytes, expected {expected_len}",
+            output.len()
+        );
+    }
+
+    output.truncate(expected_len);
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_packbits_runs() {
+        // literal [1, 2, 3], replicate 7 four times, no-op, literal [9]
+        let segment = [2, 1, 2, 3, 0xFD, 7, 0x80, 0, 9];
+        let decoded = decode_packbits(&segment, 8).unwrap();
+        assert_eq!(decoded, vec![1, 2, 3, 7, 7, 7, 7, 9]);
+    }
+
+    #[test]
+    fn test_raw_samples_drop_padding_and_swap() {
+        let stored = [0x01, 0x02, 0x03, 0x04, 0x00];
+        assert_eq!(
+            &*raw_samples(&stored, 4, 2, false).unwrap(),
+            &[0x01, 0x02, 0x03, 0x04]
+        );
+        assert_eq!(
+            &*raw_samples(&stored, 4, 2, true).unwrap(),
+            &[0x02, 0x01, 0x04, 0x03]
+        );
+        assert!(raw_samples(&stored, 6, 2, false).is_err());
+    }
+
+    #[test]
+    fn test_trim_excess() {
+        let (data, warning) = trim_excess(vec![1, 2, 3, 0].into(), 3, 1);
+        assert_eq!(&*data, &[1, 2, 3]);
+        assert_eq!(warning, None);
+
+        let (data, warning) = trim_excess(vec![1, 2, 3, 4, 0].into(), 2, 2);
+        assert_eq!(&*data, &[1, 2, 3, 4]);
+        assert!(warning.unwrap().contains("pad byte"));
+
+        let (data, warning) = trim_excess(vec![1, 2, 3, 4, 5, 6].into(), 2, 2);
+        assert_eq!(&*data, &[1, 2, 3, 4]);
+        assert!(warning.unwrap().contains("1 more frame(s)"));
+
+        let (data, warning) = trim_excess(vec![1, 2].into(), 2, 2);
+        assert_eq!(&*data, &[1, 2]);
+        assert_eq!(warning, None);
+    }
+
+    #[test]
+    fn test_decode_packbits_truncated() {
+        assert!(decode_packbits(&[4, 1, 2], 5).is_err());
+        assert!(decode_packbits(&[0xFE, 5], 4).is_err());
+    }
+
+    #[test]
+    fn test_decode_rle_frame_32bit_interleaved() {
+        // Two pixels, one sample, 32 bits: values 0x01020304 and 0x0A0B0C0D
+        let segments: [[u8; 3]; 4] = [[1, 1, 10], [1, 2, 11], [1, 3, 12], [1, 4, 13]];
+
+        let mut data = vec![0u8; 64];
+        data[..4].copy_from_slice(&4u32.to_le_bytes());
+        for i in 0..segments.len() {
+            let offset = (64 + i * 3) as u32;
+            data[4 + i * 4..8 + i * 4].copy_from_slice(&offset.to_le_bytes());
+        }
+        data.extend(segments.iter().flatten());
+
+        let layout = RleLayout {
+            pixel_count: 2,
+            samples_per_pixel: 1,
+            bytes_per_sample: 4,
+            planar: false,
+        };
+
+        let mut output = Vec::new();
+        decode_rle_frame(&data, &layout, &mut output).unwrap();
+
+        assert_eq!(
+            output,
+            [0x0102_0304u32.to_le_bytes(), 0x0A0B_0C0Du32.to_le_bytes()].concat()
+        );
+    }
+}
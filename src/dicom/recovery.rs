@@ -0,0 +1,223 @@
+//! Recovery of files labeled with the wrong transfer syntax (for `--force`)
+//!
+//! Some writers declare one transfer syntax in the file meta header but
+//! encode the dataset in another, or store compressed pixel data under an
+//! uncompressed transfer syntax. The actual encoding is detected from the
+//! first data element of the dataset and from the start of the pixel data.
+
+use super::DicomObject;
+use anyhow::{Context, Result};
+use dicom::core::value::{PixelFragmentSequence, Value};
+use dicom::core::{DataElement, VR};
+use dicom::dictionary_std::tags;
+use dicom::encoding::TransferSyntaxIndex;
+use dicom::object::{FileMetaTable, InMemDicomObject};
+use dicom::transfer_syntax::{TransferSyntaxRegistry, entries};
+use std::fs;
+use std::path::Path;
+use tracing::debug;
+
+/// Size of the preamble before the `DICM` magic bytes
+const PREAMBLE_SIZE: usize = 128;
+
+/// Open a file whose dataset is not encoded in the declared transfer syntax
+///
+/// Returns the object, relabeled with the detected transfer syntax, and a
+/// description of the mismatch.
+///
+/// # Errors
+///
+/// Returns an error if the file has no file meta header, the encoding
+/// cannot be detected or matches the declared one, or the dataset cannot
+/// be read with the detected transfer syntax
+pub fn open_mislabeled(file_path: &Path) -> Result<(DicomObject, String)> {
+    let bytes = fs::read(file_path)
+        .with_context(|| format!("Failed to read file: {}", file_path.display()))?;
+    read_mislabeled(&bytes)
+}
+
+fn read_mislabeled(bytes: &[u8]) -> Result<(DicomObject, String)> {
+    let mut reader = match bytes.get(PREAMBLE_SIZE..PREAMBLE_SIZE + 4) {
+        Some(b"DICM") => &bytes[PREAMBLE_SIZE..],
+        _ => bytes,
+    };
+    let mut meta = FileMetaTable::from_reader(&mut reader)?;
+
+    let declared = meta.transfer_syntax().to_string();
+    let detected = dataset_encoding(reader).context("Cannot tell how the dataset is encoded")?;
+    if encoding_of(&declared) == detected {
+        anyhow::bail!("The dataset is encoded as declared");
+    }
+
+    let ts = TransferSyntaxRegistry
+        .get(detected)
+        .context("Unknown transfer syntax")?;
+    let obj = InMemDicomObject::read_dataset_with_ts(reader, ts)?;
+    meta.set_transfer_syntax(ts);
+
+    let mismatch = format!(
+        "Transfer syntax is declared as {}, but the dataset is encoded in {}",
+        name_of(&declared),
+        ts.name()
+    );
+    debug!("{mismatch}");
+    Ok((obj.with_exact_meta(meta), mismatch))
+}
+
+/// Relabel `obj` if its pixel data do not match the declared transfer
+/// syntax, returning a description of the mismatch
+///
+/// Encapsulated pixel data under an uncompressed transfer syntax (JPEG,
+/// JPEG-LS, JPEG 2000 or RLE), or a JPEG stream stored as native pixel
+/// data, get the transfer syntax of their codec. Native pixel data under a
+/// compressed transfer syntax get Explicit VR Little Endian.
+pub fn fix_pixel_data_syntax(obj: &mut DicomObject) -> Option<String> {
+    let declared = obj.meta().transfer_syntax().to_string();
+    let encapsulated = TransferSyntaxRegistry
+        .get(&declared)?
+        .is_encapsulated_pixel_data();
+    let value = obj.element(tags::PIXEL_DATA).ok()?.value();
+
+    let (detected, stream) = match (value.fragments(), encapsulated) {
+        (Some(fragments), false) => {
+            let first = fragments.iter().find(|fragment| !fragment.is_empty())?;
+            (fragment_codec(first)?, None)
+        }
+        (None, false) => {
+            let bytes = value.to_bytes().ok()?;
+            (codec_of(&bytes)?, Some(bytes.into_owned()))
+        }
+        (None, true) => (entries::EXPLICIT_VR_LITTLE_ENDIAN.uid(), None),
+        (Some(_), true) => return None,
+    };
+
+    // A bare stream becomes the single fragment of encapsulated pixel data
+    if let Some(stream) = stream {
+        obj.put(DataElement::new(
+            tags::PIXEL_DATA,
+            VR::OB,
+            Value::PixelSequence(PixelFragmentSequence::new(Vec::<u32>::new(), vec![stream])),
+        ));
+    }
+    obj.meta_mut()
+        .set_transfer_syntax(TransferSyntaxRegistry.get(detected)?);
+
+    let mismatch = format!(
+        "Transfer syntax is declared as {}, but the pixel data are {}",
+        name_of(&declared),
+        name_of(detected)
+    );
+    debug!("{mismatch}");
+    Some(mismatch)
+}
+
+/// Name of a transfer syntax, or its UID if unknown
+fn name_of(uid: &str) -> String {
+    TransferSyntaxRegistry
+        .get(uid)
+        .map_or_else(|| uid.to_string(), |ts| ts.name().to_string())
+}
+
+/// UID of the uncompressed transfer syntax with the dataset encoding of `uid`
+fn encoding_of(uid: &str) -> &'static str {
+    if uid == entries::IMPLICIT_VR_LITTLE_ENDIAN.uid() {
+        entries::IMPLICIT_VR_LITTLE_ENDIAN.uid()
+    } else if uid == entries::EXPLICIT_VR_BIG_ENDIAN.uid() {
+        entries::EXPLICIT_VR_BIG_ENDIAN.uid()
+    } else {
+        entries::EXPLICIT_VR_LITTLE_ENDIAN.uid()
+    }
+}
+
+/// Uncompressed transfer syntax the dataset starting with `head` is
+/// encoded in, judged by its first data element
+///
+/// Datasets start with a low group number, so its byte order shows which
+/// half is zero. Explicit VR puts two upper-case letters after the tag.
+fn dataset_encoding(head: &[u8]) -> Option<&'static str> {
+    let group = head.get(..2)?;
+    let big_endian =
+        u16::from_be_bytes([group[0], group[1]]) < u16::from_le_bytes([group[0], group[1]]);
+    let explicit = head.get(4..6)?.iter().all(u8::is_ascii_uppercase);
+
+    match (big_endian, explicit) {
+        (false, false) => Some(entries::IMPLICIT_VR_LITTLE_ENDIAN.uid()),
+        (false, true) => Some(entries::EXPLICIT_VR_LITTLE_ENDIAN.uid()),
+        (true, true) => Some(entries::EXPLICIT_VR_BIG_ENDIAN.uid()),
+        (true, false) => None,
+    }
+}
+
+/// Transfer syntax of the encapsulated fragment `data`, if recognized
+fn fragment_codec(data: &[u8]) -> Option<&'static str> {
+    // An RLE header: the number of segments, then the offset of the first
+    if let Some(header) = data.get(..8) {
+        let segments = u32::from_le_bytes([header[0], header[1], header[2], header[3]]);
+        let first = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+        if (1..=15).contains(&segments) && first == 64 {
+            return Some(entries::RLE_LOSSLESS.uid());
+        }
+    }
+    codec_of(data)
+}
+
+/// Transfer syntax of the JPEG, JPEG-LS or JPEG 2000 stream starting with
+/// `data`, if it is recognized
+fn codec_of(data: &[u8]) -> Option<&'static str> {
+    if data.starts_with(&[0xFF, 0x4F, 0xFF, 0x51]) {
+        return Some(entries::JPEG_2000_IMAGE_COMPRESSION.uid());
+    }
+    if !data.starts_with(&[0xFF, 0xD8]) {
+        return None;
+    }
+
+    // The start of frame marker after SOI tells the JPEG process
+    let mut position = 2;
+    while let [0xFF, marker, high, low, ..] = *data.get(position..)? {
+        match marker {
+            0xC0 => return Some(entries::JPEG_BASELINE.uid()),
+            0xC1 | 0xC2 => return Some(entries::JPEG_EXTENDED.uid()),
+            0xC3 => {
+                return Some(entries::JPEG_LOSSLESS_NON_HIERARCHICAL_FIRST_ORDER_PREDICTION.uid());
+            }
+            0xF7 => return Some(entries::JPEG_LS_LOSSLESS_IMAGE_COMPRESSION.uid()),
+            _ => position += 2 + usize::from(u16::from_be_bytes([high, low])),
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_encodings() {
+        // (0008,0005) as implicit LE, explicit LE and explicit BE
+        let implicit = [0x08, 0x00, 0x05, 0x00, 10, 0, 0, 0];
+        let explicit = [0x08, 0x00, 0x05, 0x00, b'C', b'S', 10, 0];
+        let big = [0x00, 0x08, 0x00, 0x05, b'C', b'S', 0, 10];
+        assert_eq!(
+            dataset_encoding(&implicit),
+            Some(entries::IMPLICIT_VR_LITTLE_ENDIAN.uid())
+        );
+        assert_eq!(
+            dataset_encoding(&explicit),
+            Some(entries::EXPLICIT_VR_LITTLE_ENDIAN.uid())
+        );
+        assert_eq!(
+            dataset_encoding(&big),
+            Some(entries::EXPLICIT_VR_BIG_ENDIAN.uid())
+        );
+
+        // SOI, APP0 of 4 bytes, SOF0
+        let jpeg = [0xFF, 0xD8, 0xFF, 0xE0, 0, 4, 0, 0, 0xFF, 0xC0, 0, 11];
+        assert_eq!(codec_of(&jpeg), Some(entries::JPEG_BASELINE.uid()));
+        assert_eq!(codec_of(&[0xFF, 0xD8, 0xFF]), None);
+        assert_eq!(
+            fragment_codec(&[1, 0, 0, 0, 64, 0, 0, 0]),
+            Some(entries::RLE_LOSSLESS.uid())
+        );
+        assert_eq!(codec_of(&[1, 0, 0, 0, 64, 0, 0, 0]), None);
+    }
+}
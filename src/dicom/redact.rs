@@ -0,0 +1,260 @@
+//! Blacking out rectangles of pixel data for `--redact`
+//!
+//! Used on decoded images before they are shown or exported, and on the
+//! Pixel Data of a DICOM file written with `--output FILE.dcm`, so text
+//! burned into the pixels (e.g. an ultrasound patient banner) is not shared
+//! along with the image.
+
+use super::photometric::PhotometricInterpretation;
+use super::{DicomObject, SaveOptions, open_dicom_file, save};
+use anyhow::{Context, Result, bail};
+use dicom::core::value::{PrimitiveValue, Value};
+use dicom::core::{DataElement, VR};
+use dicom::dictionary_std::tags;
+use dicom::transfer_syntax::entries;
+use dicom_pixeldata::Transcode;
+use std::path::Path;
+use std::str::FromStr;
+
+/// A rectangle of stored pixels: column, row, width and height
+pub type Rect = (u32, u32, u32, u32);
+
+/// Little-endian samples of a black pixel, or `None` for sub-byte bit
+/// depths
+pub(crate) fn black_pixel(
+    photometric: &PhotometricInterpretation,
+    bits_allocated: u16,
+    bits_stored: u16,
+    samples_per_pixel: u16,
+) -> Option<Vec<u8>> {
+    if bits_allocated == 0 || !bits_allocated.is_multiple_of(8) {
+        return None;
+    }
+    let bytes = usize::from(bits_allocated / 8);
+    let sample = |value: u32| value.to_le_bytes()[..bytes.min(4)].to_vec();
+
+    Some(match photometric {
+        PhotometricInterpretation::Monochrome1 => {
+            sample(1_u32.checked_shl(u32::from(bits_stored))?.wrapping_sub(1))
+        }
+        PhotometricInterpretation::YbrFull => [0, 128, 128].into_iter().flat_map(sample).collect(),
+        _ => sample(0).repeat(usize::from(samples_per_pixel)),
+    })
+}
+
+/// Fill `rects` of each of `frames` interleaved frames of `cols` by `rows`
+/// pixels with `black`, clipped to the frame
+pub(crate) fn fill_rects(
+    data: &mut [u8],
+    (cols, rows): (usize, usize),
+    frames: usize,
+    black: &[u8],
+    rects: &[Rect],
+) {
+    let pixel_size = black.len();
+    let row_size = cols * pixel_size;
+    if row_size == 0 || rows == 0 {
+        return;
+    }
+
+    for frame in data.chunks_exact_mut(row_size * rows).take(frames) {
+        for &(x, y, width, height) in rects {
+            let columns = (x as usize).min(cols)..(x as usize + width as usize).min(cols);
+            for row in frame
+                .chunks_exact_mut(row_size)
+                .skip(y as usize)
+                .take(height as usize)
+            {
+                for pixel in row[columns.start * pixel_size..columns.end * pixel_size]
+                    .chunks_exact_mut(pixel_size)
+                {
+                    pixel.copy_from_slice(black);
+                }
+            }
+        }
+    }
+}
+
+/// Whether `--output` names a DICOM file, which gets the redacted pixel data
+/// of the input instead of a rendered image
+#[must_use]
+pub fn is_dicom_output(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("dcm"))
+}
+
+/// Write `input` to `output` with `rects` blacked out in every frame
+///
+/// Compressed pixel data is decoded and written as Explicit VR Little
+/// Endian. The output gets a new SOP Instance UID, as its pixels differ
+/// from the original's, and a Burned In Annotation of YES becomes NO.
+///
+/// # Errors
+///
+/// Returns an error if the input cannot be read or decoded, its pixel
+/// layout is planar, chroma-subsampled or sub-byte, or the output cannot be
+/// written
+pub fn redact_file(input: &Path, rects: &[Rect], output: &Path) -> Result<()> {
+    let mut obj = open_dicom_file(input)?;
+    redact_pixel_data(&mut obj, rects)?;
+    if obj.get(tags::BURNED_IN_ANNOTATION).is_some() {
+        obj.put(DataElement::new(tags::BURNED_IN_ANNOTATION, VR::CS, "NO"));
+    }
+    save(
+        &mut obj,
+        output,
+        SaveOptions {
+            new_instance_uid: true,
+        },
+    )
+}
+
+/// Black out `rects` in the Pixel Data of `obj`, decoding it if compressed
+///
+/// The Icon Image Sequence is removed, as its thumbnail would still show
+/// what the rectangles hide.
+///
+/// # Errors
+///
+/// Returns an error if the pixel data cannot be decoded or its layout
+/// cannot be changed per pixel
+pub fn redact_pixel_data(obj: &mut DicomObject, rects: &[Rect]) -> Result<()> {
+    let encapsulated = matches!(
+        obj.element(tags::PIXEL_DATA)
+            .context("This DICOM file does not contain pixel data")?
+            .value(),
+        Value::PixelSequence(_)
+    );
+    if encapsulated {
+        obj.transcode(&entries::EXPLICIT_VR_LITTLE_ENDIAN.erased())
+            .context("Failed to decode the pixel data")?;
+    }
+
+    let number = |tag| {
+        obj.element(tag)
+            .ok()
+            .and_then(|element| element.to_int::<u16>().ok())
+    };
+    let rows = number(tags::ROWS).context("Missing Rows")?;
+    let cols = number(tags::COLUMNS).context("Missing Columns")?;
+    let bits_allocated = number(tags::BITS_ALLOCATED).context("Missing Bits Allocated")?;
+    let bits_stored = number(tags::BITS_STORED).unwrap_or(bits_allocated);
+    let samples_per_pixel = number(tags::SAMPLES_PER_PIXEL).unwrap_or(1);
+    let planar = number(tags::PLANAR_CONFIGURATION).unwrap_or(0) != 0;
+    let photometric = obj
+        .element(tags::PHOTOMETRIC_INTERPRETATION)
+        .ok()
+        .and_then(|element| element.to_str().ok())
+        .and_then(|value| PhotometricInterpretation::from_str(&value).ok())
+        .context("Missing or unknown Photometric Interpretation")?;
+    let frames = obj
+        .element(tags::NUMBER_OF_FRAMES)
+        .ok()
+        .and_then(|element| element.to_int::<u32>().ok())
+        .unwrap_or(1)
+        .max(1) as usize;
+
+    let chroma_subsampled = matches!(
+        photometric,
+        PhotometricInterpretation::YbrFull422
+            | PhotometricInterpretation::YbrPartial422
+            | PhotometricInterpretation::YbrPartial420
+    );
+    if planar && samples_per_pixel > 1 || chroma_subsampled {
+        bail!("--redact does not support planar or chroma-subsampled pixel data");
+    }
+    // Values are held in host order in memory and written in the file's
+    // byte order, so they are changed as little endian here
+    let black = black_pixel(&photometric, bits_allocated, bits_stored, samples_per_pixel)
+        .context("--redact does not support pixel data of fewer than 8 bits")?;
+
+    let pixel_data = obj.element(tags::PIXEL_DATA)?;
+    let vr = pixel_data.vr();
+    let value = match pixel_data.value() {
+        Value::Primitive(PrimitiveValue::U8(bytes)) => {
+            let mut bytes = bytes.to_vec();
+            fill_rects(
+                &mut bytes,
+                (cols.into(), rows.into()),
+                frames,
+                &black,
+                rects,
+            );
+            PrimitiveValue::U8(bytes.into())
+        }
+        Value::Primitive(PrimitiveValue::U16(words)) => {
+            let mut bytes: Vec<u8> = words.iter().flat_map(|word| word.to_le_bytes()).collect();
+            fill_rects(
+                &mut bytes,
+                (cols.into(), rows.into()),
+                frames,
+                &black,
+                rects,
+            );
+            PrimitiveValue::U16(
+                bytes
+                    .chunks_exact(2)
+                    .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+                    .collect(),
+            )
+        }
+        _ => bail!("Unsupported pixel data value"),
+    };
+    obj.put(DataElement::new(tags::PIXEL_DATA, vr, value));
+    obj.remove_element(tags::ICON_IMAGE_SEQUENCE);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestDataset;
+    use dicom::core::value::DataSetSequence;
+    use dicom::object::InMemDicomObject;
+
+    #[test]
+    fn test_redact_file_blacks_out_pixel_data() {
+        let dir = tempfile::tempdir().unwrap();
+        let (input, output) = (dir.path().join("in.dcm"), dir.path().join("out.dcm"));
+
+        let icon = InMemDicomObject::from_element_iter([DataElement::new(
+            tags::PIXEL_DATA,
+            VR::OB,
+            PrimitiveValue::from(vec![9_u8; 4]),
+        )]);
+        TestDataset::gray16("1.2.3.4", 2, 3, &[1, 2, 3, 4, 5, 6])
+            .with(tags::BITS_STORED, VR::US, PrimitiveValue::from(12_u16))
+            .with(tags::HIGH_BIT, VR::US, PrimitiveValue::from(11_u16))
+            .with(tags::PHOTOMETRIC_INTERPRETATION, VR::CS, "MONOCHROME1")
+            .with(tags::BURNED_IN_ANNOTATION, VR::CS, "YES")
+            .with(
+                tags::ICON_IMAGE_SEQUENCE,
+                VR::SQ,
+                DataSetSequence::from(vec![icon]),
+            )
+            .write(&input);
+
+        redact_file(&input, &[(1, 0, 9, 1)], &output).unwrap();
+
+        let redacted = open_dicom_file(&output).unwrap();
+        assert_eq!(
+            redacted
+                .element(tags::PIXEL_DATA)
+                .unwrap()
+                .to_multi_int::<u16>()
+                .unwrap(),
+            [1, 0xFFF, 0xFFF, 4, 5, 6]
+        );
+        // The thumbnail would still show the banner
+        assert!(redacted.get(tags::ICON_IMAGE_SEQUENCE).is_none());
+        assert_eq!(
+            redacted
+                .element(tags::BURNED_IN_ANNOTATION)
+                .unwrap()
+                .to_str()
+                .unwrap(),
+            "NO"
+        );
+        assert!(is_dicom_output(Path::new("banner.DCM")));
+    }
+}
@@ -0,0 +1,126 @@
+//! Image attributes overridden on the command line (`--photometric`,
+//! `--force-planar`)
+//!
+//! Some writers declare the wrong Photometric Interpretation or Planar
+//! Configuration for their pixel data. The overrides replace the declared
+//! values in a copy of the object, so that every decoder reads the pixel
+//! data the same way.
+
+use super::{DicomObject, ExtractOptions};
+use dicom::core::{DataElement, PrimitiveValue, Tag, VR};
+use dicom::dictionary_std::tags;
+
+/// Copy of an object with overridden attributes
+pub(super) struct Relabeled {
+    pub obj: DicomObject,
+    /// Replaced attributes, as (step, description) for the decision log
+    pub decisions: Vec<(&'static str, String)>,
+    pub warnings: Vec<String>,
+}
+
+/// Copy of `obj` with the attributes overridden in `options`
+///
+/// Returns `None` if nothing is overridden or the file already declares
+/// the given values.
+pub(super) fn relabel(obj: &DicomObject, options: &ExtractOptions) -> Option<Relabeled> {
+    if options.photometric.is_none() && options.planar_configuration.is_none() {
+        return None;
+    }
+
+    let mut relabeled = Relabeled {
+        obj: obj.clone(),
+        decisions: Vec::new(),
+        warnings: Vec::new(),
+    };
+
+    if let Some(photometric) = &options.photometric
+        && let Some(declared) = replace(
+            &mut relabeled.obj,
+            tags::PHOTOMETRIC_INTERPRETATION,
+            VR::CS,
+            PrimitiveValue::from(photometric.to_string()),
+        )
+    {
+        relabeled.decisions.push((
+            "Photometric",
+            format!("{photometric} (--photometric), declared as {declared}"),
+        ));
+    }
+
+    if let Some(planar) = options.planar_configuration
+        && let Some(declared) = replace(
+            &mut relabeled.obj,
+            tags::PLANAR_CONFIGURATION,
+            VR::US,
+            PrimitiveValue::from(planar),
+        )
+    {
+        relabeled.decisions.push((
+            "Planar Configuration",
+            format!("{planar} (--force-planar), declared as {declared}"),
+        ));
+        relabeled.warnings.push(format!(
+            "Pixel data read with Planar Configuration {planar} (--force-planar), the file declares {declared}"
+        ));
+    }
+
+    (!relabeled.decisions.is_empty()).then_some(relabeled)
+}
+
+/// Put `value` in `tag`, returning the declared value it replaced, or
+/// `None` if it was already `value`
+fn replace(obj: &mut DicomObject, tag: Tag, vr: VR, value: PrimitiveValue) -> Option<String> {
+    let declared = obj
+        .get(tag)
+        .and_then(|e| e.to_str().ok())
+        .map_or_else(|| "none".to_string(), |s| s.trim().to_string());
+    if declared == value.to_str().trim() {
+        return None;
+    }
+
+    obj.put(DataElement::new(tag, vr, value));
+    Some(declared)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dicom::PhotometricInterpretation;
+    use crate::test_support::TestDataset;
+    use dicom_dictionary_std::uids;
+
+    #[test]
+    fn test_relabel_replaces_declared_attributes() {
+        let obj = TestDataset::new("1.2.3.4")
+            .with(tags::PHOTOMETRIC_INTERPRETATION, VR::CS, "YBR_FULL")
+            .with(
+                tags::PLANAR_CONFIGURATION,
+                VR::US,
+                PrimitiveValue::from(0_u16),
+            )
+            .file(uids::EXPLICIT_VR_LITTLE_ENDIAN);
+
+        let options = ExtractOptions {
+            photometric: Some(PhotometricInterpretation::Rgb),
+            planar_configuration: Some(1),
+            ..ExtractOptions::default()
+        };
+        let relabeled = relabel(&obj, &options).unwrap();
+        let value = |tag| relabeled.obj.element(tag).unwrap().to_str().unwrap();
+        assert_eq!(value(tags::PHOTOMETRIC_INTERPRETATION), "RGB");
+        assert_eq!(value(tags::PLANAR_CONFIGURATION), "1");
+        assert_eq!(
+            relabeled.decisions[0].1,
+            "RGB (--photometric), declared as YBR_FULL"
+        );
+        assert_eq!(relabeled.warnings.len(), 1);
+
+        // Overrides matching the declared values change nothing
+        let options = ExtractOptions {
+            photometric: Some(PhotometricInterpretation::YbrFull),
+            planar_configuration: Some(0),
+            ..ExtractOptions::default()
+        };
+        assert!(relabel(&obj, &options).is_none());
+    }
+}
@@ -9,6 +9,7 @@ pub fn validate_photometric_samples(
     let is_valid = match (photometric_interpretation, samples_per_pixel) {
         (pi, 1) if pi.is_grayscale() || matches!(pi, PhotometricInterpretation::Palette) => true,
         (pi, 3) if pi.is_rgb() || pi.is_ycbcr() => true,
+        (PhotometricInterpretation::Hsv, 3) | (PhotometricInterpretation::Cmyk, 4) => true,
         _ => false,
     };
 
@@ -29,8 +30,12 @@ pub fn validate_planar_configuration(
     if planar_configuration.is_some()
         && !photometric_interpretation.is_rgb()
         && !photometric_interpretation.is_ycbcr()
+        && !matches!(
+            photometric_interpretation,
+            PhotometricInterpretation::Hsv | PhotometricInterpretation::Cmyk
+        )
     {
-        bail!("Planar configuration should only be present for RGB or YCbCr images");
+        bail!("Planar configuration should only be present for color images");
     }
 
     Ok(())
@@ -45,14 +50,24 @@ pub fn validate_bits_allocated(bits_allocated: u16) -> Result<()> {
     Ok(())
 }
 
-pub fn validate_metadata(
+/// Run all metadata validations, collecting failures as warning messages
+///
+/// Used once pixel data has been decoded successfully, so marginally
+/// non-conformant files can still be displayed.
+#[must_use]
+pub fn collect_validation_warnings(
     photometric_interpretation: &PhotometricInterpretation,
     samples_per_pixel: u16,
     planar_configuration: Option<u16>,
     bits_allocated: u16,
-) -> Result<()> {
-    validate_photometric_samples(photometric_interpretation, samples_per_pixel)?;
-    validate_planar_configuration(planar_configuration, photometric_interpretation)?;
-    validate_bits_allocated(bits_allocated)?;
-    Ok(())
+) -> Vec<String> {
+    [
+        validate_photometric_samples(photometric_interpretation, samples_per_pixel),
+        validate_planar_configuration(planar_configuration, photometric_interpretation),
+        validate_bits_allocated(bits_allocated),
+    ]
+    .into_iter()
+    .filter_map(Result::err)
+    .map(|e| e.to_string())
+    .collect()
 }
@@ -0,0 +1,133 @@
+//! Native writer for iTerm2 inline images
+//!
+//! The image size is given to the terminal in cells, and the image itself
+//! is sent at up to twice the cells' reported pixel size. macOS reports
+//! cell sizes in points, so this keeps full detail on high-DPI (retina)
+//! displays instead of downscaling to one image pixel per point.
+
+use super::{Renderer, Sizing, cell_aspect, cell_pixel_width, downscale, is_remote_session};
+use crate::types::PixelAspectRatio;
+use anyhow::{Context, Result};
+use base64::{Engine, engine::general_purpose::STANDARD};
+use image::{DynamicImage, GenericImageView, ImageFormat, imageops::FilterType};
+use std::io::{Cursor, Write};
+use viuer::is_iterm_supported;
+
+/// Device pixels per reported pixel on high-DPI displays
+pub const RETINA_SCALE: u32 = 2;
+
+/// Whether the terminal understands iTerm2 inline images
+///
+/// TERM_PROGRAM is not passed on over SSH, but iTerm2 also sets
+/// LC_TERMINAL, which SSH forwards by default along with the locale.
+#[must_use]
+pub fn is_supported() -> bool {
+    is_iterm_supported()
+        || (is_remote_session() && std::env::var("LC_TERMINAL").is_ok_and(|t| t == "iTerm2"))
+}
+
+/// Write `image` inline, stretched to exactly `columns` x `rows` cells
+///
+/// # Errors
+///
+/// Returns an error if the image cannot be encoded or written
+pub fn write_inline(
+    out: &mut impl Write,
+    image: &DynamicImage,
+    columns: u32,
+    rows: u32,
+) -> Result<()> {
+    let mut png = Vec::new();
+    image
+        .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+        .context("Failed to encode image for the terminal")?;
+
+    // The cell box already has the displayed aspect ratio, so iTerm2 must
+    // not letterbox the image within it
+    write!(
+        out,
+        "\x1b]1337;File=inline=1;size={};width={columns};height={rows};preserveAspectRatio=0:",
+        png.len()
+    )?;
+    out.write_all(STANDARD.encode(png).as_bytes())?;
+    out.write_all(b"\x07")?;
+    out.flush()?;
+    Ok(())
+}
+
+/// Shows images with iTerm2 inline images at high-DPI resolution
+#[derive(Debug, Clone, Copy)]
+pub struct ItermRenderer {
+    sizing: Sizing,
+    scale_filter: FilterType,
+}
+
+impl Default for ItermRenderer {
+    fn default() -> Self {
+        Self::new(Sizing::default())
+    }
+}
+
+impl ItermRenderer {
+    #[must_use]
+    pub fn new(sizing: Sizing) -> Self {
+        Self {
+            sizing,
+            scale_filter: FilterType::Triangle,
+        }
+    }
+
+    /// Scale images down with `filter` instead of the default triangle
+    /// (bilinear) filter
+    #[must_use]
+    pub fn with_scale_filter(mut self, filter: FilterType) -> Self {
+        self.scale_filter = filter;
+        self
+    }
+}
+
+impl Renderer for ItermRenderer {
+    fn show(
+        &mut self,
+        image: &DynamicImage,
+        pixel_aspect_ratio: Option<PixelAspectRatio>,
+    ) -> Result<()> {
+        let fit = self
+            .sizing
+            .fit(image.dimensions(), pixel_aspect_ratio, cell_aspect());
+
+        // Only send as many pixels as the display can show
+        let max_width = fit.image.0 * cell_pixel_width() * RETINA_SCALE;
+        let scaled = downscale(image, max_width, self.scale_filter);
+        let image = fit.letterbox(&scaled, self.sizing.background);
+
+        let (columns, rows) = fit.frame;
+        let mut stdout = std::io::stdout().lock();
+        stdout.flush()?;
+        write_inline(&mut stdout, &image, columns, rows)?;
+        writeln!(stdout)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::RgbImage;
+
+    #[test]
+    fn test_write_inline_sizes_in_cells() {
+        let image = DynamicImage::ImageRgb8(RgbImage::new(4, 2));
+
+        let mut out = Vec::new();
+        write_inline(&mut out, &image, 12, 3).unwrap();
+        let out = String::from_utf8(out).unwrap();
+
+        let (header, payload) = out.split_once(':').unwrap();
+        assert!(header.starts_with("\x1b]1337;File=inline=1;size="));
+        assert!(header.ends_with(";width=12;height=3;preserveAspectRatio=0"));
+
+        let png = STANDARD.decode(payload.trim_end_matches('\x07')).unwrap();
+        assert_eq!(image::load_from_memory(&png).unwrap().dimensions(), (4, 2));
+    }
+}
@@ -0,0 +1,243 @@
+//! Native writer for the kitty graphics protocol
+//!
+//! Unlike viuer, this keeps control of image IDs and placements, so an image
+//! already on screen can be replaced in place (without clearing and
+//! reprinting, which flickers) or deleted.
+//!
+//! Images are sent as PNG through escape codes, which works for local and
+//! remote (e.g. SSH) sessions alike.
+
+use super::{Fit, Renderer, Sizing, cell_aspect, reserve_rows};
+use crate::types::PixelAspectRatio;
+use anyhow::{Context, Result};
+use base64::{Engine, engine::general_purpose::STANDARD};
+use crossterm::{
+    cursor::{MoveTo, position},
+    queue,
+};
+use image::{DynamicImage, GenericImageView, ImageFormat};
+use std::io::{Cursor, Write};
+use std::sync::atomic::{AtomicU32, Ordering};
+use viuer::{KittySupport, get_kitty_support};
+
+/// Maximum payload of a single escape code, in base64 bytes
+const CHUNK_SIZE: usize = 4096;
+
+/// Last image ID handed out by [`next_image_id`]
+static LAST_IMAGE_ID: AtomicU32 = AtomicU32::new(0);
+
+/// Whether the terminal understands the kitty graphics protocol
+#[must_use]
+pub fn is_supported() -> bool {
+    get_kitty_support() != KittySupport::None
+}
+
+/// An image ID not used before by this process
+///
+/// IDs start high, to stay clear of images placed by other programs.
+#[must_use]
+pub fn next_image_id() -> u32 {
+    0x00dc_0000 + LAST_IMAGE_ID.fetch_add(1, Ordering::Relaxed) + 1
+}
+
+/// Where and how large to show an image, in terminal cells
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Placement {
+    pub columns: u32,
+    pub rows: u32,
+    /// Top-left cell as (column, row), 0-based. When set, the cursor is
+    /// left where it was; otherwise the image is drawn at the cursor, which
+    /// moves past it.
+    pub position: Option<(u16, u16)>,
+}
+
+/// Send `image` under `id` and show it, scaled to the placement's cells
+///
+/// An image shown earlier with the same ID is replaced in place, which
+/// avoids the flicker of clearing the screen and printing it again.
+///
+/// # Errors
+///
+/// Returns an error if the image cannot be encoded or written
+pub fn place(
+    out: &mut impl Write,
+    image: &DynamicImage,
+    id: u32,
+    placement: Placement,
+) -> Result<()> {
+    let mut png = Vec::new();
+    image
+        .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+        .context("Failed to encode image for the terminal")?;
+    let encoded = STANDARD.encode(png);
+
+    if let Some((column, row)) = placement.position {
+        queue!(out, MoveTo(column, row))?;
+    }
+
+    let cursor_movement = if placement.position.is_some() {
+        ",C=1"
+    } else {
+        ""
+    };
+
+    // Only the first chunk carries the control keys; q=2 silences replies
+    let chunks: Vec<&[u8]> = encoded.as_bytes().chunks(CHUNK_SIZE).collect();
+    for (i, chunk) in chunks.iter().enumerate() {
+        let more = u8::from(i + 1 < chunks.len());
+
+        if i == 0 {
+            write!(
+                out,
+                "\x1b_Ga=T,f=100,t=d,i={id},p=1,c={},r={}{cursor_movement},q=2,m={more};",
+                placement.columns, placement.rows
+            )?;
+        } else {
+            write!(out, "\x1b_Gm={more};")?;
+        }
+
+        out.write_all(chunk)?;
+        out.write_all(b"\x1b\\")?;
+    }
+
+    out.flush()?;
+    Ok(())
+}
+
+/// Remove the image with `id` from the screen and free its data
+///
+/// # Errors
+///
+/// Returns an error if writing fails
+pub fn delete(out: &mut impl Write, id: u32) -> Result<()> {
+    write!(out, "\x1b_Ga=d,d=I,i={id},q=2;\x1b\\")?;
+    out.flush()?;
+    Ok(())
+}
+
+/// Shows images with the kitty graphics protocol, each under its own ID
+///
+/// Previews are placed at a fixed position, where the full image then
+/// replaces them.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct KittyRenderer {
+    sizing: Sizing,
+    /// ID and placement of a preview waiting to be replaced
+    preview: Option<(u32, Placement)>,
+}
+
+impl KittyRenderer {
+    #[must_use]
+    pub fn new(sizing: Sizing) -> Self {
+        Self {
+            sizing,
+            preview: None,
+        }
+    }
+
+    fn fit(&self, image: &DynamicImage, pixel_aspect_ratio: Option<PixelAspectRatio>) -> Fit {
+        self.sizing
+            .fit(image.dimensions(), pixel_aspect_ratio, cell_aspect())
+    }
+}
+
+impl Renderer for KittyRenderer {
+    fn show(
+        &mut self,
+        image: &DynamicImage,
+        pixel_aspect_ratio: Option<PixelAspectRatio>,
+    ) -> Result<()> {
+        let fit = self.fit(image, pixel_aspect_ratio);
+        let image = fit.letterbox(image, self.sizing.background);
+
+        let mut stdout = std::io::stdout().lock();
+        stdout.flush()?;
+
+        if let Some((id, placement)) = self.preview.take() {
+            place(&mut stdout, &image, id, placement)?;
+
+            // Continue on the line below the image
+            if let Some((_, row)) = placement.position {
+                let below = u32::from(row) + placement.rows;
+                queue!(stdout, MoveTo(0, u16::try_from(below).unwrap_or(u16::MAX)))?;
+                stdout.flush()?;
+            }
+            return Ok(());
+        }
+
+        let (columns, rows) = fit.frame;
+        let placement = Placement {
+            columns,
+            rows,
+            position: None,
+        };
+        place(&mut stdout, &image, next_image_id(), placement)?;
+
+        // The cursor is left after the image's last cell on its bottom row
+        writeln!(stdout)?;
+        Ok(())
+    }
+
+    fn supports_preview(&self) -> bool {
+        true
+    }
+
+    fn show_preview(
+        &mut self,
+        image: &DynamicImage,
+        pixel_aspect_ratio: Option<PixelAspectRatio>,
+    ) -> Result<()> {
+        let fit = self.fit(image, pixel_aspect_ratio);
+        let image = fit.letterbox(image, self.sizing.background);
+        let (columns, rows) = fit.frame;
+        let mut stdout = std::io::stdout().lock();
+
+        // Scroll to make room first, as an image placed without moving the
+        // cursor is cut off at the bottom of the screen
+        reserve_rows(&mut stdout, rows)?;
+
+        let placement = Placement {
+            columns,
+            rows,
+            position: Some(position()?),
+        };
+        let id = next_image_id();
+        place(&mut stdout, &image, id, placement)?;
+
+        self.preview = Some((id, placement));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::RgbImage;
+
+    #[test]
+    fn test_place_chunks_payload_and_keeps_cursor_when_positioned() {
+        // Noise does not compress, so the PNG needs several chunks
+        let noise = RgbImage::from_fn(64, 64, |x, y| {
+            let v = (x * 7919 + y * 104_729) as u8;
+            image::Rgb([v, v.wrapping_mul(31), v.wrapping_mul(17)])
+        });
+        let placement = Placement {
+            columns: 20,
+            rows: 10,
+            position: Some((0, 0)),
+        };
+
+        let mut out = Vec::new();
+        place(&mut out, &DynamicImage::ImageRgb8(noise), 7, placement).unwrap();
+        let out = String::from_utf8(out).unwrap();
+
+        assert!(out.starts_with("\x1b[1;1H\x1b_Ga=T,f=100,t=d,i=7,p=1,c=20,r=10,C=1,q=2,m=1;"));
+        assert!(out.matches("\x1b_Gm=1;").count() >= 1);
+        assert_eq!(out.matches("\x1b_Gm=0;").count(), 1);
+        assert!(out.ends_with("\x1b\\"));
+
+        let mut out = Vec::new();
+        delete(&mut out, 7).unwrap();
+        assert_eq!(out, b"\x1b_Ga=d,d=I,i=7,q=2;\x1b\\");
+    }
+}
@@ -0,0 +1,697 @@
+use crate::cli::{Args, RendererKind};
+use crate::terminal::TerminalContext;
+use crate::types::PixelAspectRatio;
+use anyhow::{Context, Result, anyhow};
+use crossterm::{
+    cursor::{MoveDown, MoveToColumn, MoveUp},
+    execute, queue,
+    terminal::{self, Clear, ClearType, window_size},
+};
+use image::{DynamicImage, GenericImageView, Rgb, RgbImage, imageops, imageops::FilterType};
+use iterm::ItermRenderer;
+use kitty::KittyRenderer;
+use std::borrow::Cow;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicU32, Ordering};
+use tracing::debug;
+use viuer::{
+    Config as ViuerConfig, get_kitty_support, is_iterm_supported, is_sixel_supported, print,
+};
+
+pub mod iterm;
+pub mod kitty;
+pub mod probe;
+mod query;
+
+/// Output width in terminal columns when neither width nor height is given
+const DEFAULT_WIDTH: u32 = 24;
+
+/// Assumed ratio of cell height to width when the terminal does not report it
+const FALLBACK_CELL_ASPECT: f64 = 2.0;
+
+/// Assumed cell width in pixels when the terminal does not report it
+const FALLBACK_CELL_WIDTH: u32 = 10;
+
+/// Pixels per row viuer draws Sixel images with, whatever the cell height
+const SIXEL_ROW_PIXELS: u32 = 12;
+
+/// Cell size the terminal answered at startup, when the window size has
+/// no pixels
+static QUERIED_CELL_SIZE: OnceLock<Option<(f64, f64)>> = OnceLock::new();
+
+/// Initialize terminal graphics protocol detection at startup.
+///
+/// Forces viuer's terminal capability queries to happen once at startup
+/// rather than during file processing, preventing escape sequences from
+/// appearing randomly. Results are cached internally by viuer's `LazyLock`.
+/// The cell size is queried too if the window size does not include it.
+pub fn init_terminal_display() {
+    // Only query protocols in TTY - skip if piped/redirected
+    let terminal = TerminalContext::get();
+    if terminal.graphics() {
+        let _kitty = get_kitty_support();
+        let _iterm = is_iterm_supported();
+
+        // The answer comes on stdin
+        if terminal.stdin && window_cell_size().is_none() {
+            let queried = QUERIED_CELL_SIZE.get_or_init(query::cell_size);
+            debug!(?queried, "queried the cell size");
+        }
+
+        // Clear line to hide escape sequences, then move cursor to start
+        let mut stdout = std::io::stdout();
+        let _ = execute!(stdout, Clear(ClearType::CurrentLine), MoveToColumn(0));
+        let _ = stdout.flush();
+    }
+}
+
+/// A way of showing rendered images to the user
+///
+/// Backends receive the final 8-bit image, already oriented and with any
+/// overlays drawn, together with the pixel aspect ratio to show it at.
+pub trait Renderer {
+    /// Show one image
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the image cannot be shown
+    fn show(
+        &mut self,
+        image: &DynamicImage,
+        pixel_aspect_ratio: Option<PixelAspectRatio>,
+    ) -> Result<()>;
+
+    /// Whether [`Renderer::show_preview`] is supported
+    fn supports_preview(&self) -> bool {
+        false
+    }
+
+    /// Show a quick low-resolution stand-in, replaced in place by the next
+    /// [`Renderer::show`]
+    ///
+    /// Only called when [`Renderer::supports_preview`] returns `true`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the preview cannot be shown
+    fn show_preview(
+        &mut self,
+        _image: &DynamicImage,
+        _pixel_aspect_ratio: Option<PixelAspectRatio>,
+    ) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl<R: Renderer + ?Sized> Renderer for Box<R> {
+    fn show(
+        &mut self,
+        image: &DynamicImage,
+        pixel_aspect_ratio: Option<PixelAspectRatio>,
+    ) -> Result<()> {
+        (**self).show(image, pixel_aspect_ratio)
+    }
+
+    fn supports_preview(&self) -> bool {
+        (**self).supports_preview()
+    }
+
+    fn show_preview(
+        &mut self,
+        image: &DynamicImage,
+        pixel_aspect_ratio: Option<PixelAspectRatio>,
+    ) -> Result<()> {
+        (**self).show_preview(image, pixel_aspect_ratio)
+    }
+}
+
+/// Whether dcmv runs over SSH, where terminal detection is less reliable
+#[must_use]
+pub fn is_remote_session() -> bool {
+    ["SSH_CONNECTION", "SSH_CLIENT", "SSH_TTY"]
+        .iter()
+        .any(|name| std::env::var_os(name).is_some())
+}
+
+/// Whether dcmv runs in Windows Terminal, which sets `WT_SESSION` for its
+/// shells, also in WSL
+#[must_use]
+pub fn is_windows_terminal() -> bool {
+    std::env::var_os("WT_SESSION").is_some()
+}
+
+/// The backend `auto` stands for in this terminal; other kinds are kept
+#[must_use]
+pub fn detect_renderer(kind: RendererKind) -> RendererKind {
+    match kind {
+        // Querying the terminal would write the queries into a pipe
+        RendererKind::Auto if !TerminalContext::get().graphics() => RendererKind::Viuer,
+        RendererKind::Auto if kitty::is_supported() => RendererKind::Kitty,
+        RendererKind::Auto if iterm::is_supported() => RendererKind::Iterm,
+        // viuer detects the other graphics protocols itself
+        RendererKind::Auto => RendererKind::Viuer,
+        other => other,
+    }
+}
+
+/// Choose the backend selected with --renderer
+#[must_use]
+pub fn renderer_from_args(args: &Args) -> Box<dyn Renderer> {
+    let kind = detect_renderer(args.renderer);
+    debug!(requested = ?args.renderer, "using the {kind:?} renderer");
+    let sizing = Sizing::from_args(args);
+    let filter = args.scale_filter_type();
+    match kind {
+        RendererKind::Auto | RendererKind::Viuer => {
+            Box::new(ViuerRenderer::new(sizing).with_scale_filter(filter))
+        }
+        RendererKind::Kitty => Box::new(KittyRenderer::new(sizing)),
+        RendererKind::Iterm => Box::new(ItermRenderer::new(sizing).with_scale_filter(filter)),
+        RendererKind::File => Box::new(FileRenderer::new(std::env::temp_dir())),
+        RendererKind::Null => Box::new(NullRenderer::default()),
+    }
+}
+
+/// Prints images with Kitty, iTerm2 or Sixel graphics, or colored blocks
+///
+/// Images are scaled down to the pixels the terminal shows before they are
+/// handed to viuer, with a filter of our choosing.
+#[derive(Debug, Clone, Copy)]
+pub struct ViuerRenderer {
+    sizing: Sizing,
+    scale_filter: FilterType,
+}
+
+impl Default for ViuerRenderer {
+    fn default() -> Self {
+        Self::new(Sizing::default())
+    }
+}
+
+impl ViuerRenderer {
+    #[must_use]
+    pub fn new(sizing: Sizing) -> Self {
+        Self {
+            sizing,
+            scale_filter: FilterType::Triangle,
+        }
+    }
+
+    /// Scale images down with `filter` instead of the default triangle
+    /// (bilinear) filter
+    #[must_use]
+    pub fn with_scale_filter(mut self, filter: FilterType) -> Self {
+        self.scale_filter = filter;
+        self
+    }
+}
+
+impl Renderer for ViuerRenderer {
+    fn show(
+        &mut self,
+        image: &DynamicImage,
+        pixel_aspect_ratio: Option<PixelAspectRatio>,
+    ) -> Result<()> {
+        let graphics = TerminalContext::get().graphics();
+
+        let fit = self
+            .sizing
+            .fit(image.dimensions(), pixel_aspect_ratio, cell_aspect());
+        let scaled = downscale(
+            image,
+            fit.image
+                .0
+                .saturating_mul(pixels_per_column(RendererKind::Viuer)),
+            self.scale_filter,
+        );
+        let image = fit.letterbox(&scaled, self.sizing.background);
+
+        // With both set, viuer fills exactly these cells instead of
+        // assuming cells twice as tall as wide
+        let config = ViuerConfig {
+            width: Some(fit.frame.0),
+            height: Some(fit.frame.1),
+            absolute_offset: false,
+            use_kitty: graphics,
+            use_iterm: graphics,
+            use_sixel: graphics,
+            ..Default::default()
+        };
+
+        let mut stdout = std::io::stdout();
+        stdout
+            .flush()
+            .map_err(|e| anyhow!("Failed to flush stdout: {e}"))?;
+
+        // Terminals leave the cursor in different places after an image,
+        // Sixel ones often on its last row, where the next text would
+        // overwrite it. So room is made first, the cursor put back at the
+        // image's top left after it and moved below it from there.
+        let rows = if graphics && is_sixel_supported() {
+            sixel_rows(fit.frame.1)
+        } else {
+            fit.frame.1
+        };
+        let fits_screen =
+            terminal::size().is_ok_and(|(_, screen_rows)| rows < u32::from(screen_rows));
+        if !graphics || !fits_screen {
+            print(&image, &config).map_err(|e| anyhow!("Failed to display image: {e}"))?;
+            return Ok(());
+        }
+
+        reserve_rows(&mut stdout, rows)?;
+        let config = ViuerConfig {
+            restore_cursor: true,
+            ..config
+        };
+        print(&image, &config).map_err(|e| anyhow!("Failed to display image: {e}"))?;
+        let rows = u16::try_from(rows).unwrap_or(u16::MAX);
+        execute!(stdout, MoveDown(rows), MoveToColumn(0))?;
+
+        Ok(())
+    }
+}
+
+/// Scroll the screen so that `rows` rows below the cursor are free, and put
+/// the cursor back at the start of the first of them
+///
+/// An image drawn there then does not scroll the screen, which would move
+/// it away from a position saved before it.
+///
+/// # Errors
+///
+/// Returns an error if writing to `out` fails
+pub fn reserve_rows(out: &mut impl Write, rows: u32) -> Result<()> {
+    let rows = u16::try_from(rows).unwrap_or(u16::MAX);
+    write!(out, "{}", "\n".repeat(usize::from(rows)))?;
+    queue!(out, MoveUp(rows), MoveToColumn(0))?;
+    out.flush()?;
+    Ok(())
+}
+
+/// Rows a Sixel image printed by viuer into `rows` rows covers, which is
+/// more when cells are less than 12 pixels tall
+fn sixel_rows(rows: u32) -> u32 {
+    let cell_height = cell_pixel_size().map_or(f64::from(SIXEL_ROW_PIXELS), |(_, height)| height);
+    let covered = (f64::from(rows * SIXEL_ROW_PIXELS) / cell_height).ceil() as u32;
+    covered.max(rows)
+}
+
+/// How images are fitted to the terminal, from -W, -H, --stretch and
+/// --background
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sizing {
+    /// Width in terminal columns
+    pub width: Option<u32>,
+    /// Height in terminal rows
+    pub height: Option<u32>,
+    /// Fill both the width and the height, distorting the image
+    pub stretch: bool,
+    /// Color of the bars beside or above and below an image that does not
+    /// fill both the width and the height
+    pub background: Rgb<u8>,
+}
+
+impl Default for Sizing {
+    fn default() -> Self {
+        Self {
+            width: None,
+            height: None,
+            stretch: false,
+            background: Rgb([0, 0, 0]),
+        }
+    }
+}
+
+impl Sizing {
+    #[must_use]
+    pub fn from_args(args: &Args) -> Self {
+        Self {
+            width: args.width,
+            height: args.height,
+            stretch: args.stretch,
+            background: Rgb(args.background),
+        }
+    }
+
+    /// Cells to show an image of `size` pixels in
+    ///
+    /// The image keeps its aspect ratio. With both a width and a height it
+    /// fits within them and is centered in a box of exactly that size, or
+    /// fills the box when stretched.
+    #[must_use]
+    pub fn fit(
+        &self,
+        size: (u32, u32),
+        pixel_aspect_ratio: Option<PixelAspectRatio>,
+        cell_aspect: f64,
+    ) -> Fit {
+        if self.stretch
+            && let (Some(columns), Some(rows)) = (self.width, self.height)
+        {
+            return Fit {
+                image: (columns, rows),
+                frame: (columns, rows),
+            };
+        }
+
+        let image = fit_cells(
+            size,
+            pixel_aspect_ratio,
+            cell_aspect,
+            (self.width, self.height),
+        );
+        let frame = match (self.width, self.height) {
+            (Some(columns), Some(rows)) => (columns, rows),
+            _ => image,
+        };
+        Fit { image, frame }
+    }
+}
+
+/// Terminal cells an image is shown in, as (columns, rows)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fit {
+    /// Cells the image itself covers
+    pub image: (u32, u32),
+    /// Cells of the box the image is centered in, at least as large
+    pub frame: (u32, u32),
+}
+
+impl Fit {
+    /// `image` with bars of `background` added to fill the frame, so that
+    /// it can be stretched to the frame's cells without distortion
+    ///
+    /// Images that already fill it are returned unchanged.
+    #[must_use]
+    pub fn letterbox<'a>(
+        &self,
+        image: &'a DynamicImage,
+        background: Rgb<u8>,
+    ) -> Cow<'a, DynamicImage> {
+        if self.frame == self.image {
+            return Cow::Borrowed(image);
+        }
+
+        // Pixels per cell differ across and down for non-square pixels
+        let padded = |pixels: u32, cells: u32, frame: u32| {
+            (f64::from(pixels) * f64::from(frame) / f64::from(cells.max(1))).round() as u32
+        };
+        let width = padded(image.width(), self.image.0, self.frame.0).max(image.width());
+        let height = padded(image.height(), self.image.1, self.frame.1).max(image.height());
+
+        let mut canvas = RgbImage::from_pixel(width, height, background);
+        imageops::overlay(
+            &mut canvas,
+            &image.to_rgb8(),
+            i64::from((width - image.width()) / 2),
+            i64::from((height - image.height()) / 2),
+        );
+        Cow::Owned(DynamicImage::ImageRgb8(canvas))
+    }
+}
+
+/// Number of the last image saved by a [`FileRenderer`] in this process
+///
+/// Shared, as a renderer is created for every file shown.
+static SAVED_IMAGES: AtomicU32 = AtomicU32::new(0);
+
+/// Saves each image as a numbered PNG in a directory and prints its path
+///
+/// For terminals without graphics support, where the file can be opened
+/// in an image viewer instead.
+#[derive(Debug, Clone)]
+pub struct FileRenderer {
+    directory: PathBuf,
+}
+
+impl FileRenderer {
+    #[must_use]
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self {
+            directory: directory.into(),
+        }
+    }
+}
+
+impl Renderer for FileRenderer {
+    fn show(
+        &mut self,
+        image: &DynamicImage,
+        _pixel_aspect_ratio: Option<PixelAspectRatio>,
+    ) -> Result<()> {
+        let number = SAVED_IMAGES.fetch_add(1, Ordering::Relaxed) + 1;
+        let path = self
+            .directory
+            .join(format!("dcmv-{}-{number}.png", std::process::id()));
+
+        image
+            .save(&path)
+            .with_context(|| format!("Failed to save image: {}", path.display()))?;
+        println!("{}", path.display());
+
+        Ok(())
+    }
+}
+
+/// Discards images, keeping only their sizes
+///
+/// Useful for timing the decoding pipeline and for tests.
+#[derive(Debug, Clone, Default)]
+pub struct NullRenderer {
+    /// (width, height) of every image shown, in order
+    pub shown: Vec<(u32, u32)>,
+}
+
+impl Renderer for NullRenderer {
+    fn show(
+        &mut self,
+        image: &DynamicImage,
+        _pixel_aspect_ratio: Option<PixelAspectRatio>,
+    ) -> Result<()> {
+        self.shown.push((image.width(), image.height()));
+        Ok(())
+    }
+}
+
+/// Cell (width, height) in pixels from the window size, which not all
+/// terminals fill in
+fn window_cell_size() -> Option<(f64, f64)> {
+    window_size()
+        .ok()
+        .filter(|size| size.columns > 0 && size.rows > 0 && size.width > 0 && size.height > 0)
+        .map(|size| {
+            (
+                f64::from(size.width) / f64::from(size.columns),
+                f64::from(size.height) / f64::from(size.rows),
+            )
+        })
+}
+
+/// Cell (width, height) in pixels as reported by the terminal, in the
+/// window size or in answer to the query at startup
+#[must_use]
+pub fn cell_pixel_size() -> Option<(f64, f64)> {
+    window_cell_size().or_else(|| QUERIED_CELL_SIZE.get().copied().flatten())
+}
+
+/// Whether [`cell_pixel_size`] comes from the query rather than the
+/// window size
+#[must_use]
+pub fn cell_size_was_queried() -> bool {
+    window_cell_size().is_none() && cell_pixel_size().is_some()
+}
+
+/// Width of a terminal cell in pixels, as reported by the terminal
+#[must_use]
+pub fn cell_pixel_width() -> u32 {
+    cell_pixel_size().map_or(FALLBACK_CELL_WIDTH, |(width, _)| (width as u32).max(1))
+}
+
+/// Ratio of a terminal cell's height to its width
+///
+/// Fonts differ, so this is measured rather than assumed, to keep circles
+/// round; 2 is assumed only when the terminal does not tell.
+#[must_use]
+pub fn cell_aspect() -> f64 {
+    cell_pixel_size().map_or(FALLBACK_CELL_ASPECT, |(width, height)| height / width)
+}
+
+/// Terminal cells (columns, rows) that show an image at its aspect ratio
+///
+/// `size` is in pixels, stretched by the pixel aspect ratio. The image fills
+/// the given number of columns or rows; with both, it fits within them.
+/// Without either, it is `DEFAULT_WIDTH` columns wide.
+#[must_use]
+pub fn fit_cells(
+    size: (u32, u32),
+    pixel_aspect_ratio: Option<PixelAspectRatio>,
+    cell_aspect: f64,
+    bounds: (Option<u32>, Option<u32>),
+) -> (u32, u32) {
+    // PAR = (vertical, horizontal): (2,1)=2x tall pixels
+    let par_ratio = pixel_aspect_ratio.map_or(1.0, |par| par.ratio());
+    let height_in_pixels = f64::from(size.1) * par_ratio;
+    let rows_per_column = height_in_pixels / f64::from(size.0.max(1)) / cell_aspect;
+
+    let rows_for = |columns: u32| ((f64::from(columns) * rows_per_column).round() as u32).max(1);
+    let columns_for = |rows: u32| ((f64::from(rows) / rows_per_column).round() as u32).max(1);
+
+    match bounds {
+        (Some(columns), Some(rows)) if rows_for(columns) > rows => (columns_for(rows), rows),
+        (Some(columns), _) => (columns, rows_for(columns)),
+        (None, Some(rows)) => (columns_for(rows), rows),
+        (None, None) => (DEFAULT_WIDTH, rows_for(DEFAULT_WIDTH)),
+    }
+}
+
+/// Estimate how many pixels wide the image will be when displayed
+///
+/// Graphics protocols draw at the cell's pixel resolution, while block
+/// output uses a single pixel per column. Returns `None` when the width is
+/// not constrained (only a height was requested).
+#[must_use]
+pub fn target_pixel_width(args: &Args) -> Option<u32> {
+    let columns = match (args.width, args.height) {
+        (Some(w), ..) => w,
+        (None, Some(_)) => return None,
+        (None, None) => DEFAULT_WIDTH,
+    };
+
+    Some(columns.saturating_mul(pixels_per_column(args.renderer)))
+}
+
+/// Image pixels shown across one terminal column by a renderer
+///
+/// Graphics protocols draw at the cell's pixel resolution, while block
+/// output uses a single pixel per column.
+#[must_use]
+pub fn pixels_per_column(renderer: RendererKind) -> u32 {
+    if !TerminalContext::get().graphics() {
+        1
+    } else if detect_renderer(renderer) == RendererKind::Iterm {
+        cell_pixel_width() * iterm::RETINA_SCALE
+    } else {
+        cell_pixel_width()
+    }
+}
+
+/// `image` scaled down with `filter` to at most `max_width` pixels wide,
+/// keeping its aspect ratio
+///
+/// Terminals show large images at a fraction of their pixels; scaling them
+/// here rather than in viuer or the terminal lets the filter be chosen, so
+/// fine detail does not alias.
+#[must_use]
+pub fn downscale(
+    image: &DynamicImage,
+    max_width: u32,
+    filter: FilterType,
+) -> Cow<'_, DynamicImage> {
+    if image.width() <= max_width.max(1) {
+        return Cow::Borrowed(image);
+    }
+    debug!(max_width, ?filter, "scaling down for the terminal");
+    Cow::Owned(image.resize(max_width.max(1), image.height(), filter))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sizing_letterboxes_within_both_bounds() {
+        let sizing = Sizing {
+            width: Some(40),
+            height: Some(20),
+            background: Rgb([0, 0, 255]),
+            ..Sizing::default()
+        };
+        // 200x100 image in cells twice as tall as wide fills 40x10 cells
+        let fit = sizing.fit((200, 100), None, 2.0);
+        assert_eq!(fit.image, (40, 10));
+        assert_eq!(fit.frame, (40, 20));
+
+        let image = DynamicImage::ImageRgb8(RgbImage::from_pixel(200, 100, Rgb([255, 255, 255])));
+        let boxed = fit
+            .letterbox(&image, sizing.background)
+            .into_owned()
+            .into_rgb8();
+        assert_eq!(boxed.dimensions(), (200, 200));
+        assert_eq!(boxed.get_pixel(0, 49), &Rgb([0, 0, 255]));
+        assert_eq!(boxed.get_pixel(0, 50), &Rgb([255, 255, 255]));
+        assert_eq!(boxed.get_pixel(0, 150), &Rgb([0, 0, 255]));
+
+        // Without a height, the image sets the rows
+        let fit = Sizing::default().fit((200, 100), None, 2.0);
+        assert_eq!(fit.frame, fit.image);
+    }
+
+    #[test]
+    fn test_sizing_with_pixel_aspect_ratio() {
+        // 200x100 pixels twice as tall as wide look square: 40x20 cells
+        // when cells are twice as tall as wide
+        let par = Some(PixelAspectRatio::new(2.0, 1.0));
+        let sizing = Sizing {
+            width: Some(40),
+            height: Some(15),
+            ..Sizing::default()
+        };
+
+        // Rows are the tighter bound, so the columns shrink to keep the shape
+        let fit = sizing.fit((200, 100), par, 2.0);
+        assert_eq!(fit.image, (30, 15));
+        assert_eq!(fit.frame, (40, 15));
+
+        // Only the width: rows follow from the pixel aspect ratio
+        let fit = Sizing {
+            height: None,
+            ..sizing
+        }
+        .fit((200, 100), par, 2.0);
+        assert_eq!(fit.image, (40, 20));
+        assert_eq!(fit.frame, (40, 20));
+
+        // Stretched, the box is filled whatever the aspect ratio
+        let stretched = Sizing {
+            stretch: true,
+            ..sizing
+        };
+        assert_eq!(stretched.fit((200, 100), par, 2.0).image, (40, 15));
+        assert_eq!(stretched.fit((200, 100), None, 2.0).image, (40, 15));
+    }
+
+    #[test]
+    fn test_downscale_only_shrinks() {
+        let image = DynamicImage::new_luma8(400, 100);
+
+        let scaled = downscale(&image, 40, FilterType::Lanczos3);
+        assert_eq!(scaled.dimensions(), (40, 10));
+        assert!(matches!(
+            downscale(&image, 800, FilterType::Nearest),
+            Cow::Borrowed(_)
+        ));
+    }
+
+    #[test]
+    fn test_fit_cells_keeps_aspect_ratio() {
+        // 200x100 image in cells twice as tall as wide
+        assert_eq!(fit_cells((200, 100), None, 2.0, (Some(40), None)), (40, 10));
+        assert_eq!(fit_cells((200, 100), None, 2.0, (None, Some(10))), (40, 10));
+
+        // Rows are the tighter bound
+        assert_eq!(
+            fit_cells((200, 100), None, 2.0, (Some(80), Some(10))),
+            (40, 10)
+        );
+
+        // Pixels twice as tall as wide double the rows
+        let par = PixelAspectRatio::new(2.0, 1.0);
+        assert_eq!(
+            fit_cells((200, 100), Some(par), 2.0, (Some(40), None)),
+            (40, 20)
+        );
+    }
+}
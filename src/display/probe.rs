@@ -0,0 +1,143 @@
+//! Terminal capability report for `dcmv probe`
+
+use super::{
+    FALLBACK_CELL_ASPECT, FALLBACK_CELL_WIDTH, cell_pixel_size, cell_size_was_queried,
+    detect_renderer, is_remote_session, is_windows_terminal,
+};
+use crate::cli::RendererKind;
+use crate::terminal::{TerminalContext, supports_escape_codes};
+use clap::ValueEnum;
+use crossterm::terminal;
+use std::env;
+use viuer::{KittySupport, get_kitty_support, is_sixel_supported};
+
+/// Print what was detected about the terminal and which renderer is used
+///
+/// `renderer` is the requested backend; anything but `auto` is reported as
+/// forced.
+pub fn print_probe(renderer: RendererKind) {
+    super::init_terminal_display();
+
+    let is_tty = TerminalContext::get().stdout;
+    print_field(
+        "Terminal",
+        if is_tty {
+            "yes"
+        } else {
+            "no (output is redirected)"
+        },
+    );
+    print_field("TERM", &env_or_unset("TERM"));
+    print_field("TERM_PROGRAM", &env_or_unset("TERM_PROGRAM"));
+    print_field("LC_TERMINAL", &env_or_unset("LC_TERMINAL"));
+    print_field(
+        "Remote Session",
+        if is_remote_session() {
+            "yes (SSH)"
+        } else {
+            "no"
+        },
+    );
+
+    if cfg!(windows) || is_windows_terminal() {
+        print_field("Windows Terminal", yes_no(is_windows_terminal()));
+    }
+    if cfg!(windows) {
+        print_field(
+            "Escape Codes",
+            if supports_escape_codes() {
+                "yes (virtual terminal processing)"
+            } else {
+                "no (legacy console, colored blocks only)"
+            },
+        );
+    }
+
+    // The window size has no pixels on Windows, only the cells
+    match terminal::size() {
+        Ok((columns, rows)) => {
+            print_field("Terminal Size", &format!("{columns}x{rows} cells"));
+
+            let cell = match cell_pixel_size() {
+                Some((width, height)) => format!(
+                    "{width:.0}x{height:.0} pixels{}",
+                    if cell_size_was_queried() {
+                        " (answered to a query)"
+                    } else {
+                        ""
+                    }
+                ),
+                None => format!(
+                    "not reported (assuming {FALLBACK_CELL_WIDTH}x{} pixels)",
+                    f64::from(FALLBACK_CELL_WIDTH) * FALLBACK_CELL_ASPECT
+                ),
+            };
+            print_field("Cell Size", &cell);
+        }
+        Err(e) => print_field("Terminal Size", &format!("unknown ({e})")),
+    }
+
+    print_field("Color Depth", color_depth());
+
+    // The queries would end up in the redirected output, unanswered, or be
+    // printed by a console without escape codes
+    if TerminalContext::get().graphics() {
+        let kitty = match get_kitty_support() {
+            KittySupport::None => "no",
+            KittySupport::Local => "yes (local, files can be shared)",
+            KittySupport::Remote => "yes (escape codes only)",
+        };
+        print_field("Kitty Graphics", kitty);
+        print_field("iTerm2 Images", yes_no(super::iterm::is_supported()));
+        print_field("Sixel", yes_no(is_sixel_supported()));
+    } else if is_tty {
+        print_field("Graphics Protocols", "not queried (no escape codes)");
+    } else {
+        print_field("Graphics Protocols", "not queried (not a terminal)");
+    }
+
+    let detected = if is_tty || renderer != RendererKind::Auto {
+        detect_renderer(renderer)
+    } else {
+        RendererKind::Viuer
+    };
+    let how = if renderer == RendererKind::Auto {
+        "detected"
+    } else {
+        "forced with --renderer or DCMV_RENDERER"
+    };
+    print_field("Renderer", &format!("{} ({how})", renderer_name(detected)));
+}
+
+/// Colors the terminal advertises through COLORTERM and TERM
+fn color_depth() -> &'static str {
+    let colorterm = env::var("COLORTERM").unwrap_or_default();
+    let term = env::var("TERM").unwrap_or_default();
+
+    if colorterm == "truecolor" || colorterm == "24bit" {
+        "24-bit (truecolor)"
+    } else if term.contains("256color") {
+        "8-bit (256 colors)"
+    } else if term == "dumb" {
+        "none"
+    } else {
+        "4-bit (16 colors)"
+    }
+}
+
+fn renderer_name(kind: RendererKind) -> String {
+    kind.to_possible_value()
+        .map_or_else(|| format!("{kind:?}"), |value| value.get_name().to_string())
+}
+
+fn env_or_unset(name: &str) -> String {
+    env::var(name).unwrap_or_else(|_| "(not set)".to_string())
+}
+
+fn yes_no(value: bool) -> &'static str {
+    if value { "yes" } else { "no" }
+}
+
+fn print_field(name: &str, value: &str) {
+    println!("{name:20}: {value}");
+}
@@ -0,0 +1,108 @@
+//! Cell size query for terminals that leave it out of the window size
+//!
+//! Some terminals, and most multiplexers, report zero pixels in
+//! `TIOCGWINSZ` but still answer the XTWINOPS query for the cell size
+//! (`CSI 16 t`) with `CSI 6 ; height ; width t`.
+//!
+//! The Windows console only passes the reply on as input with virtual
+//! terminal input enabled. Consoles that cannot enable it do not answer
+//! either, so they are not queried rather than waited on forever.
+
+use crossterm::terminal;
+use std::io::{self, Read, Write};
+
+/// Longest reply read before giving up on the terminal
+const MAX_REPLY: usize = 256;
+
+/// Cell (width, height) in pixels, as answered by the terminal
+///
+/// The query is followed by a Primary Device Attributes request, which
+/// every terminal answers, so that reading stops even when the cell size
+/// query is ignored.
+#[must_use]
+pub fn cell_size() -> Option<(f64, f64)> {
+    terminal::enable_raw_mode().ok()?;
+    let reply = (|| {
+        #[cfg(windows)]
+        let _input = windows::VirtualTerminalInput::enable()?;
+
+        let mut stdout = io::stdout();
+        stdout.write_all(b"\x1b[16t\x1b[c")?;
+        stdout.flush()?;
+        read_reply(&mut io::stdin().lock())
+    })();
+    let _ = terminal::disable_raw_mode();
+
+    parse_cell_size(&reply.ok()?)
+}
+
+/// Bytes up to the end of the Device Attributes reply, `CSI ? … c`
+fn read_reply(input: &mut impl Read) -> io::Result<Vec<u8>> {
+    let mut reply = Vec::new();
+    let mut byte = [0];
+    while reply.len() < MAX_REPLY {
+        input.read_exact(&mut byte)?;
+        reply.push(byte[0]);
+        if byte[0] == b'c' && reply.windows(3).any(|w| w == b"\x1b[?") {
+            break;
+        }
+    }
+    Ok(reply)
+}
+
+fn parse_cell_size(reply: &[u8]) -> Option<(f64, f64)> {
+    let reply = String::from_utf8_lossy(reply);
+    let (_, rest) = reply.split_once("\x1b[6;")?;
+    let (size, _) = rest.split_once('t')?;
+    let (height, width) = size.split_once(';')?;
+    let (width, height) = (width.parse::<u32>().ok()?, height.parse::<u32>().ok()?);
+
+    (width > 0 && height > 0).then(|| (f64::from(width), f64::from(height)))
+}
+
+#[cfg(windows)]
+mod windows {
+    use crossterm_winapi::{ConsoleMode, Handle};
+    use std::io;
+
+    /// `ENABLE_VIRTUAL_TERMINAL_INPUT` console input mode
+    const VIRTUAL_TERMINAL_INPUT: u32 = 0x0200;
+
+    /// Console input mode with virtual terminal input, restored when
+    /// dropped
+    pub struct VirtualTerminalInput {
+        console: ConsoleMode,
+        previous: u32,
+    }
+
+    impl VirtualTerminalInput {
+        pub fn enable() -> io::Result<Self> {
+            let console = ConsoleMode::from(Handle::current_in_handle()?);
+            let previous = console.mode()?;
+            console.set_mode(previous | VIRTUAL_TERMINAL_INPUT)?;
+            Ok(Self { console, previous })
+        }
+    }
+
+    impl Drop for VirtualTerminalInput {
+        fn drop(&mut self) {
+            let _ = self.console.set_mode(self.previous);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cell_size_reply() {
+        let mut input: &[u8] = b"\x1b[6;20;9t\x1b[?62;22c";
+        let reply = read_reply(&mut input).unwrap();
+        assert_eq!(parse_cell_size(&reply), Some((9.0, 20.0)));
+
+        // Only the device attributes: the query is not supported
+        assert_eq!(parse_cell_size(b"\x1b[?1;2c"), None);
+        assert_eq!(parse_cell_size(b"\x1b[6;0;0t"), None);
+    }
+}
@@ -1,25 +1,104 @@
-use crate::dicom::DicomMetadata;
+use crate::datetime::{DateStyle, DicomDate, DicomDateTime};
+use crate::dicom::{DicomMetadata, ProcessError, Stage};
+use crate::image::{
+    DisplayWindow, Histogram, PixelStatistics, RoiStatistics, decode_frame, window_presets,
+};
+use crate::render::RenderOptions;
+use crate::types::RescaleParams;
+use clap::ValueEnum;
 
 /// String displayed for missing DICOM tags in verbose mode
 const UNKNOWN_TAG_VALUE: &str = "unknown";
 
-pub fn print_metadata(metadata: &DicomMetadata) {
-    print_field("Patient Name", metadata.patient_name());
-    print_field("Patient ID", metadata.patient_id());
-    print_field("Birth Date", metadata.patient_birth_date());
+/// Histogram width in columns when no output width is given
+const DEFAULT_HISTOGRAM_COLUMNS: usize = 64;
 
-    print_field("Accession Number", metadata.accession_number());
-    print_field("Study Date", metadata.study_date());
-    print_field("Study Description", metadata.study_description());
-    print_field("Modality", metadata.modality());
+/// Histogram height in terminal rows
+const HISTOGRAM_ROWS: usize = 8;
 
-    print_field("Series Description", metadata.series_description());
+/// Partial block characters, giving each row eight levels of bar height
+const BAR_BLOCKS: [char; 9] = [' ', '▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
 
-    print_dimensions(metadata);
+/// Group of fields in metadata output, in the order they are printed
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetadataGroup {
+    /// Patient name, ID and birth date
+    Patient,
+    /// Accession number, study date, description and modality
+    Study,
+    /// Series description and body part examined
+    Series,
+    /// Institution and station name
+    Equipment,
+    /// KVP and exposure time
+    Acquisition,
+    /// Dimensions, rescaling, statistics, aspect ratio and spacing
+    Pixel,
+    /// SOP class, transfer syntax and lossy compression
+    File,
+}
+
+/// Which metadata is printed, and how
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MetadataFormat {
+    /// Groups to print; all of them when empty
+    pub groups: Vec<MetadataGroup>,
+    pub dates: DateStyle,
+}
+
+impl MetadataFormat {
+    #[must_use]
+    pub fn shows(&self, group: MetadataGroup) -> bool {
+        self.groups.is_empty() || self.groups.contains(&group)
+    }
+}
 
-    print_pixel_aspect_ratio(metadata);
-    print_sop_class_info(metadata);
-    print_transfer_syntax_info(metadata);
+/// Print the metadata groups selected in `format`
+pub fn print_metadata(metadata: &DicomMetadata, format: &MetadataFormat) {
+    let dates = format.dates;
+
+    if format.shows(MetadataGroup::Patient) {
+        print_field("Patient Name", metadata.patient_name());
+        print_field("Patient ID", metadata.patient_id());
+        print_birth_date(metadata, dates);
+    }
+
+    if format.shows(MetadataGroup::Study) {
+        print_field("Accession Number", metadata.accession_number());
+        print_study_date(metadata, dates);
+        print_field("Study Description", metadata.study_description());
+        print_field("Modality", metadata.modality());
+    }
+
+    if format.shows(MetadataGroup::Series) {
+        print_field("Series Description", metadata.series_description());
+        print_field("Body Part Examined", metadata.body_part_examined());
+    }
+
+    if format.shows(MetadataGroup::Equipment) {
+        print_field("Institution Name", metadata.institution_name());
+        print_field("Station Name", metadata.station_name());
+    }
+
+    if format.shows(MetadataGroup::Acquisition) {
+        print_acquisition(metadata);
+    }
+
+    if format.shows(MetadataGroup::Pixel) {
+        print_dimensions(metadata);
+        print_rescale_type(metadata);
+        print_pixel_statistics(metadata);
+
+        print_pixel_aspect_ratio(metadata);
+        print_pixel_spacing(metadata);
+    }
+
+    if format.shows(MetadataGroup::File) {
+        print_sop_class_info(metadata);
+        print_transfer_syntax_info(metadata);
+        print_lossy_compression(metadata);
+        print_burned_in_annotation(metadata);
+    }
 
     println!();
 }
@@ -29,6 +108,50 @@ fn print_field(name: &str, value: Option<&str>) {
     println!("{name:20}: {display_value}");
 }
 
+/// Birth date with the patient's age on the study date
+fn print_birth_date(metadata: &DicomMetadata, dates: DateStyle) {
+    let Some(birth_date) = metadata.patient_birth_date() else {
+        print_field("Birth Date", None);
+        return;
+    };
+    let Some(birth) = DicomDate::parse(birth_date) else {
+        print_field("Birth Date", Some(birth_date));
+        return;
+    };
+
+    let age = metadata
+        .study_date()
+        .and_then(DicomDate::parse)
+        .and_then(|study| birth.years_until(&study));
+    let value = match age {
+        Some(age) => format!("{} (age {age} at study)", birth.format(dates)),
+        None => birth.format(dates),
+    };
+    print_field("Birth Date", Some(&value));
+}
+
+/// Study Date combined with Study Time
+fn print_study_date(metadata: &DicomMetadata, dates: DateStyle) {
+    let value = metadata.study_date().map(|date| {
+        DicomDateTime::from_date_and_time(date, metadata.study_time())
+            .map_or_else(|| date.to_string(), |datetime| datetime.format(dates))
+    });
+    print_field("Study Date", value.as_deref());
+}
+
+fn print_acquisition(metadata: &DicomMetadata) {
+    let acquisition = &metadata.acquisition;
+    let kvp = acquisition
+        .kvp
+        .map(|kvp| format!("{} kV", format_value(kvp)));
+    print_field("KVP", kvp.as_deref());
+
+    let exposure_time = acquisition
+        .exposure_time
+        .map(|time| format!("{} ms", format_value(time)));
+    print_field("Exposure Time", exposure_time.as_deref());
+}
+
 fn print_dimensions(metadata: &DicomMetadata) {
     let dims = format!("{}x{}", metadata.cols(), metadata.rows());
     println!(
@@ -37,6 +160,290 @@ fn print_dimensions(metadata: &DicomMetadata) {
     );
 }
 
+/// Print what is known about a file that failed (for `--verbose`)
+///
+/// Metadata attached to the error is printed when the failure happened
+/// before the regular metadata output, i.e. while reading or extracting;
+/// later stages have already shown it. The failed stage is always named.
+pub fn print_error_details(error: &ProcessError, format: &MetadataFormat) {
+    if error.stage() <= Stage::Extraction
+        && let Some(metadata) = error.metadata()
+    {
+        print_metadata(metadata, format);
+    }
+
+    println!("{:20}: {}", "Failed Stage", error.stage());
+}
+
+/// Print pixel value statistics on their own (for `--stats`)
+pub fn print_statistics(metadata: &DicomMetadata) {
+    print_rescale_type(metadata);
+    print_pixel_statistics(metadata);
+
+    println!();
+}
+
+/// Print how the pixel data was decoded and converted for display, and
+/// where the image goes (for `--explain`)
+pub fn print_explanation(metadata: &DicomMetadata, options: &RenderOptions, output: &str) {
+    match options.explain(metadata) {
+        Ok(log) => {
+            for (step, decision) in log.entries() {
+                println!("{step:20}: {decision}");
+            }
+        }
+        Err(e) => println!("{:20}: unavailable ({e})", "Pipeline"),
+    }
+    println!("{:20}: {output}", "Output");
+    println!();
+}
+
+/// Print the stored, rescaled and displayed values of pixels at stored
+/// (column, row) coordinates (for `--probe`)
+pub fn print_probes(metadata: &DicomMetadata, options: &RenderOptions, positions: &[(u32, u32)]) {
+    let probes = match options.probe(metadata, positions) {
+        Ok(probes) => probes,
+        Err(e) => {
+            println!("{:20}: unavailable ({e})", "Probe");
+            println!();
+            return;
+        }
+    };
+
+    let unit = metadata
+        .rescale_type
+        .as_ref()
+        .and_then(|rescale_type| rescale_type.unit())
+        .map_or_else(String::new, |unit| format!(" {unit}"));
+
+    for (&(x, y), probe) in positions.iter().zip(probes) {
+        let name = format!("Probe ({x}, {y})");
+        let Some(probe) = probe else {
+            println!(
+                "{name:20}: outside the {}x{} image",
+                metadata.cols(),
+                metadata.rows()
+            );
+            continue;
+        };
+
+        let stored = probe
+            .stored
+            .iter()
+            .map(|&value| format_value(value))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let rescaled = probe
+            .rescaled
+            .map(|value| format!(", rescaled {}{unit}", format_value(value)))
+            .unwrap_or_default();
+        let [r, g, b] = probe.displayed.0;
+        println!("{name:20}: stored {stored}{rescaled}, displayed RGB({r}, {g}, {b})");
+    }
+    println!();
+}
+
+/// Print statistics of rescaled values inside rectangles given as
+/// (x, y, width, height) in stored pixels (for `--roi-rect`)
+pub fn print_roi_statistics(metadata: &DicomMetadata, rects: &[(u32, u32, u32, u32)]) {
+    let frame = decode_frame(metadata);
+    let unit = metadata
+        .rescale_type
+        .as_ref()
+        .and_then(|rescale_type| rescale_type.unit())
+        .map_or_else(String::new, |unit| format!(" {unit}"));
+
+    for &(x, y, width, height) in rects {
+        let name = format!("ROI {x},{y} {width}x{height}");
+        let corner = (x + width - 1, y + height - 1);
+        let stats = frame
+            .as_ref()
+            .ok()
+            .and_then(|frame| RoiStatistics::from_frame(frame, (x, y), corner, &metadata.rescale));
+
+        match stats {
+            Some(stats) => println!(
+                "{name:20}: mean {}{unit}, SD {:.1}, min {}, max {}, n={}",
+                format_value(stats.mean),
+                stats.std_dev,
+                format_value(stats.min),
+                format_value(stats.max),
+                stats.pixel_count
+            ),
+            None if !metadata.photometric_interpretation.is_grayscale() => {
+                println!("{name:20}: only available for grayscale images");
+            }
+            None => println!(
+                "{name:20}: outside the {}x{} image",
+                metadata.cols(),
+                metadata.rows()
+            ),
+        }
+    }
+    println!();
+}
+
+/// Print a bar chart of rescaled pixel values (for `--histogram`)
+///
+/// Bar heights use a log scale so that small peaks remain visible next to
+/// large background areas. The row below the bars marks the display window
+/// with `^` at its bounds. `columns` defaults to 64.
+pub fn print_histogram(metadata: &DicomMetadata, columns: Option<u32>) {
+    let bins = columns.map_or(DEFAULT_HISTOGRAM_COLUMNS, |columns| columns as usize);
+
+    let Ok(frame) = decode_frame(metadata) else {
+        return;
+    };
+    let (Some(histogram), Some(stats)) = (
+        Histogram::from_frame(&frame, &metadata.rescale, bins),
+        PixelStatistics::from_frame(&frame),
+    ) else {
+        println!("{:20}: only available for grayscale images", "Histogram");
+        return;
+    };
+
+    let unit = metadata
+        .rescale_type
+        .as_ref()
+        .and_then(|rescale_type| rescale_type.unit())
+        .map_or_else(String::new, |unit| format!(" {unit}"));
+    let window = DisplayWindow::for_image(metadata, &stats);
+
+    println!(
+        "{:20}: {bins} bins of {}{unit}, log scale, window [{}..{}]{unit}",
+        "Histogram",
+        format_value(histogram.bin_width()),
+        format_value(window.lower()),
+        format_value(window.upper())
+    );
+
+    let max_height = (HISTOGRAM_ROWS * (BAR_BLOCKS.len() - 1)) as f64;
+    let peak = histogram.counts.iter().copied().max().unwrap_or(0);
+    let heights: Vec<usize> = histogram
+        .counts
+        .iter()
+        .map(|&count| {
+            if peak == 0 {
+                return 0;
+            }
+            let scaled = (count as f64).ln_1p() / (peak as f64).ln_1p() * max_height;
+            // Keep non-empty bins visible
+            (scaled.round() as usize).max(usize::from(count > 0))
+        })
+        .collect();
+
+    let levels = BAR_BLOCKS.len() - 1;
+    for row in (0..HISTOGRAM_ROWS).rev() {
+        let line: String = heights
+            .iter()
+            .map(|&height| BAR_BLOCKS[height.saturating_sub(row * levels).min(levels)])
+            .collect();
+        println!("{line}");
+    }
+
+    let (lower, upper) = (
+        histogram.bin_of(window.lower()),
+        histogram.bin_of(window.upper()),
+    );
+    let markers: String = (0..bins)
+        .map(|bin| match bin {
+            _ if bin == lower || bin == upper => '^',
+            _ if bin > lower && bin < upper => '-',
+            _ => ' ',
+        })
+        .collect();
+    println!("{}", markers.trim_end());
+
+    let left = format_value(histogram.min);
+    let right = format!("{}{unit}", format_value(histogram.max));
+    let padding = bins.saturating_sub(left.chars().count() + right.chars().count());
+    println!("{left}{:padding$}{right}", "");
+    println!();
+}
+
+/// Format a value without decimals when it is whole
+fn format_value(value: f64) -> String {
+    if value.fract() == 0.0 {
+        format!("{value}")
+    } else {
+        format!("{value:.1}")
+    }
+}
+
+fn print_rescale_type(metadata: &DicomMetadata) {
+    let display_value = metadata
+        .rescale_type
+        .as_ref()
+        .map_or_else(|| UNKNOWN_TAG_VALUE.to_string(), ToString::to_string);
+    println!("{:20}: {}", "Rescale Type", display_value);
+
+    if let Some(lut) = &metadata.modality_lut {
+        println!("{:20}: {lut}", "Modality LUT");
+    }
+    if let Some(window) = &metadata.voi_window {
+        println!("{:20}: {window}", "Window");
+    }
+}
+
+fn print_pixel_statistics(metadata: &DicomMetadata) {
+    // Metadata extracted without pixel data (e.g. after a decode failure)
+    if metadata.pixel_data().is_empty() {
+        return;
+    }
+
+    let Some(stats) = decode_frame(metadata)
+        .ok()
+        .and_then(|frame| PixelStatistics::from_frame(&frame))
+    else {
+        return;
+    };
+
+    println!("{:20}: {}", "Stored Values", stats);
+
+    if let Some(range) = metadata.pixel_value_range {
+        let note = if range.contains(stats.min, stats.max) {
+            ""
+        } else {
+            " (stored values exceed it)"
+        };
+        println!("{:20}: {range}{note}", "Declared Range");
+    }
+
+    if !metadata.photometric_interpretation.is_grayscale() {
+        return;
+    }
+
+    let unit = metadata
+        .rescale_type
+        .as_ref()
+        .and_then(|rescale_type| rescale_type.unit())
+        .map_or_else(String::new, |unit| format!(" {unit}"));
+
+    // Statistics of values mapped through a Modality LUT are not computed
+    if metadata.modality_lut.is_none()
+        && (metadata.rescale != RescaleParams::default() || !unit.is_empty())
+    {
+        println!(
+            "{:20}: {}{unit}",
+            "Rescaled Values",
+            stats.rescaled(&metadata.rescale)
+        );
+    }
+
+    let window = DisplayWindow::for_image(metadata, &stats);
+    println!("{:20}: {window}{unit}", "Display Window");
+
+    let presets = window_presets(metadata.rescale_type.as_ref());
+    if !presets.is_empty() {
+        let names = presets
+            .iter()
+            .map(|(name, window)| format!("{name} ({}/{})", window.center, window.width))
+            .collect::<Vec<_>>()
+            .join(", ");
+        println!("{:20}: {}", "Window Presets", names);
+    }
+}
+
 fn print_pixel_aspect_ratio(metadata: &DicomMetadata) {
     if let Some(par) = &metadata.pixel_aspect_ratio {
         println!("{:20}: {}", "Pixel Aspect Ratio", par);
@@ -45,6 +452,14 @@ fn print_pixel_aspect_ratio(metadata: &DicomMetadata) {
     }
 }
 
+fn print_pixel_spacing(metadata: &DicomMetadata) {
+    let display_value = metadata.pixel_spacing.map_or_else(
+        || UNKNOWN_TAG_VALUE.to_string(),
+        |spacing| spacing.to_string(),
+    );
+    println!("{:20}: {}", "Pixel Spacing", display_value);
+}
+
 fn print_sop_class_info(metadata: &DicomMetadata) {
     let display_value = metadata
         .sop_class
@@ -56,3 +471,44 @@ fn print_sop_class_info(metadata: &DicomMetadata) {
 fn print_transfer_syntax_info(metadata: &DicomMetadata) {
     println!("{:20}: {}", "Transfer Syntax", metadata.transfer_syntax);
 }
+
+/// Warn that the pixels are not the originals, so they are not misread as such
+fn print_lossy_compression(metadata: &DicomMetadata) {
+    if let Some(lossy) = &metadata.lossy_compression {
+        println!(
+            "{:20}: WARNING: {lossy}, pixel values differ from the original",
+            "Lossy Compression"
+        );
+    }
+}
+
+/// Point out identifying text in the pixels, which removing tags leaves
+fn print_burned_in_annotation(metadata: &DicomMetadata) {
+    match metadata.burned_in_annotation {
+        Some(true) => println!(
+            "{:20}: YES, WARNING: the pixels may show identifying text",
+            "Burned In Annotation"
+        ),
+        Some(false) => println!("{:20}: NO", "Burned In Annotation"),
+        None => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::Args;
+    use clap::Parser;
+
+    #[test]
+    fn test_show_selects_metadata_groups() {
+        let args = Args::try_parse_from(["dcmv", "--show", "patient,pixel", "a.dcm"]).unwrap();
+        let format = args.metadata_format();
+        assert!(format.shows(MetadataGroup::Patient));
+        assert!(format.shows(MetadataGroup::Pixel));
+        assert!(!format.shows(MetadataGroup::Equipment));
+
+        // Everything is shown by default
+        assert!(MetadataFormat::default().shows(MetadataGroup::Acquisition));
+    }
+}
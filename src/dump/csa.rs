@@ -0,0 +1,148 @@
+//! Siemens CSA headers
+//!
+//! Siemens MR and CT scanners store most acquisition parameters (e.g. the
+//! sequence name, b-values, slice timing) in private CSA headers, the CSA
+//! Image and Series Header Info (0029,xx10 and 0029,xx20). Each is a list
+//! of named elements with a VR and string values, in the "SV10" (CSA2)
+//! format or the older CSA1 format without a signature.
+
+use anyhow::{Result, bail, ensure};
+
+/// Signature of CSA2 headers, followed by 4 unused bytes
+const CSA2_SIGNATURE: &[u8] = b"SV10";
+
+/// More elements or values than this mean the header is not what it seems
+const MAX_COUNT: u32 = 1024;
+
+/// An element of a CSA header
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CsaElement {
+    pub name: String,
+    pub vr: String,
+    pub values: Vec<String>,
+}
+
+/// Parse a CSA1 or CSA2 header
+///
+/// # Errors
+///
+/// Returns an error if the header is truncated or malformed
+pub fn parse(bytes: &[u8]) -> Result<Vec<CsaElement>> {
+    let csa2 = bytes.starts_with(CSA2_SIGNATURE);
+    let mut reader = Reader {
+        bytes,
+        position: if csa2 { 8 } else { 0 },
+    };
+
+    let count = reader.u32()?;
+    ensure!(count <= MAX_COUNT, "implausible CSA element count {count}");
+    reader.u32()?;
+
+    (0..count)
+        .map(|_| {
+            let name = reader.string(64)?;
+            let _multiplicity = reader.u32()?;
+            let vr = reader.string(4)?;
+            let _syngo_type = reader.u32()?;
+            let items = reader.u32()?;
+            ensure!(items <= MAX_COUNT, "implausible CSA item count {items}");
+            reader.u32()?;
+
+            let mut values = Vec::new();
+            for _ in 0..items {
+                let lengths = [reader.u32()?, reader.u32()?, reader.u32()?, reader.u32()?];
+                // CSA1 stores the length offset by the element count
+                let length = if csa2 {
+                    lengths[1]
+                } else {
+                    lengths[0].wrapping_sub(count)
+                } as usize;
+
+                let value = reader.string(length)?;
+                reader.skip(length.next_multiple_of(4) - length)?;
+                if !value.is_empty() {
+                    values.push(value);
+                }
+            }
+
+            Ok(CsaElement { name, vr, values })
+        })
+        .collect()
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl Reader<'_> {
+    fn take(&mut self, length: usize) -> Result<&[u8]> {
+        let Some(bytes) = self
+            .position
+            .checked_add(length)
+            .and_then(|end| self.bytes.get(self.position..end))
+        else {
+            bail!("CSA header truncated at byte {}", self.position);
+        };
+        self.position += length;
+        Ok(bytes)
+    }
+
+    fn skip(&mut self, length: usize) -> Result<()> {
+        self.take(length).map(|_| ())
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    /// A fixed-size field holding text up to its first NUL
+    fn string(&mut self, length: usize) -> Result<String> {
+        let bytes = self.take(length)?;
+        let text = bytes.split(|&b| b == 0).next().unwrap_or_default();
+        Ok(String::from_utf8_lossy(text).trim().to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixed(text: &str, length: usize) -> Vec<u8> {
+        let mut bytes = text.as_bytes().to_vec();
+        bytes.resize(length, 0);
+        bytes
+    }
+
+    #[test]
+    fn test_parse_csa2_header() {
+        let mut bytes = b"SV10\x04\x03\x02\x01".to_vec();
+        bytes.extend(1_u32.to_le_bytes());
+        bytes.extend(77_u32.to_le_bytes());
+
+        bytes.extend(fixed("EchoTime", 64));
+        bytes.extend(1_u32.to_le_bytes());
+        bytes.extend(fixed("DS", 4));
+        bytes.extend(3_u32.to_le_bytes());
+        bytes.extend(2_u32.to_le_bytes());
+        bytes.extend(77_u32.to_le_bytes());
+        for value in ["2.46\0", ""] {
+            let length = value.len() as u32;
+            for field in [length, length, 77, length] {
+                bytes.extend(field.to_le_bytes());
+            }
+            bytes.extend(fixed(value, value.len().next_multiple_of(4)));
+        }
+
+        assert_eq!(
+            parse(&bytes).unwrap(),
+            vec![CsaElement {
+                name: "EchoTime".to_string(),
+                vr: "DS".to_string(),
+                values: vec!["2.46".to_string()],
+            }]
+        );
+        assert!(parse(&bytes[..bytes.len() - 4]).is_err());
+    }
+}
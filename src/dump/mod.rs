@@ -0,0 +1,186 @@
+//! Listing of all data elements for `--dump`
+//!
+//! Elements are printed one per line with their tag, VR, name and value,
+//! items of sequences indented below them. Private attributes are named
+//! after their Private Creator and a [`PrivateDictionary`], and Siemens
+//! CSA headers are decoded into their elements.
+
+mod csa;
+mod private;
+
+pub use private::PrivateDictionary;
+
+use crate::dicom::DicomObject;
+use dicom::core::dictionary::{DataDictionary, DataDictionaryEntry};
+use dicom::core::header::{HasLength, Header};
+use dicom::core::value::{PrimitiveValue, Value};
+use dicom::core::{Tag, VR};
+use dicom::dictionary_std::StandardDataDictionary;
+use dicom::object::InMemDicomObject;
+
+/// Width of the name column
+const NAME_WIDTH: usize = 36;
+
+/// Values longer than this are cut, e.g. long lists of numbers
+const MAX_VALUE_CHARS: usize = 80;
+
+/// Print the file meta information and every element of `obj`
+pub fn print_dump(obj: &DicomObject, dictionary: &PrivateDictionary) {
+    for element in obj.meta().to_element_iter() {
+        let value = match element.value() {
+            Value::Primitive(value) => format_primitive(element.vr(), value),
+            _ => String::new(),
+        };
+        print_line(
+            0,
+            element.tag(),
+            element.vr(),
+            &public_name(element.tag()),
+            &value,
+        );
+    }
+
+    print_dataset(obj, dictionary, 0);
+    println!();
+}
+
+fn print_dataset(dataset: &InMemDicomObject, dictionary: &PrivateDictionary, depth: usize) {
+    for element in dataset.iter() {
+        let tag = element.tag();
+        let name = element_name(dataset, tag, dictionary);
+
+        match element.value() {
+            Value::Primitive(value) => {
+                print_line(
+                    depth,
+                    tag,
+                    element.vr(),
+                    &name,
+                    &format_primitive(element.vr(), value),
+                );
+                if is_csa_header(dataset, tag) {
+                    print_csa_header(&value.to_bytes(), depth + 1);
+                }
+            }
+            Value::Sequence(sequence) => {
+                let items = sequence.items();
+                print_line(depth, tag, element.vr(), &name, &count(items.len(), "item"));
+                for (index, item) in items.iter().enumerate() {
+                    println!("{:indent$}Item {}", "", index + 1, indent = (depth + 1) * 2);
+                    print_dataset(item, dictionary, depth + 2);
+                }
+            }
+            Value::PixelSequence(sequence) => {
+                let bytes: usize = sequence.fragments().iter().map(Vec::len).sum();
+                let value = format!(
+                    "{}, {bytes} bytes (encapsulated)",
+                    count(sequence.fragments().len(), "fragment")
+                );
+                print_line(depth, tag, element.vr(), &name, &value);
+            }
+        }
+    }
+}
+
+fn print_line(depth: usize, tag: Tag, vr: VR, name: &str, value: &str) {
+    let indent = depth * 2;
+    println!(
+        "{:indent$}({:04X},{:04X}) {} {name:NAME_WIDTH$} {value}",
+        "",
+        tag.group(),
+        tag.element(),
+        vr.to_string()
+    );
+}
+
+/// Elements of a CSA header with values, indented below it
+fn print_csa_header(bytes: &[u8], depth: usize) {
+    let indent = depth * 2;
+    match csa::parse(bytes) {
+        Ok(elements) => {
+            for element in elements.iter().filter(|element| !element.values.is_empty()) {
+                println!(
+                    "{:indent$}{:2} {:NAME_WIDTH$} {}",
+                    "",
+                    element.vr,
+                    element.name,
+                    truncate(&element.values.join("\\"))
+                );
+            }
+        }
+        Err(e) => println!("{:indent$}(unreadable CSA header: {e})", ""),
+    }
+}
+
+/// Whether `tag` is the CSA Image or Series Header Info
+fn is_csa_header(dataset: &InMemDicomObject, tag: Tag) -> bool {
+    tag.group() == 0x0029
+        && matches!(tag.element() & 0xFF, 0x10 | 0x20)
+        && private_creator(dataset, tag).is_some_and(|creator| creator == "SIEMENS CSA HEADER")
+}
+
+/// Creator of a private attribute, from the Private Creator of its block
+fn private_creator(dataset: &InMemDicomObject, tag: Tag) -> Option<String> {
+    let creator = dataset.get(private::creator_tag(tag)?)?.to_str().ok()?;
+    Some(creator.trim().to_string())
+}
+
+/// Name of an element, with private attributes named after their creator
+fn element_name(dataset: &InMemDicomObject, tag: Tag, dictionary: &PrivateDictionary) -> String {
+    if private::is_creator(tag) {
+        return "PrivateCreator".to_string();
+    }
+
+    if private::creator_tag(tag).is_none() {
+        return public_name(tag);
+    }
+    let Some(creator) = private_creator(dataset, tag) else {
+        return "(private, no creator)".to_string();
+    };
+
+    match dictionary.name(&creator, tag) {
+        Some(name) => format!("[{creator}] {name}"),
+        None => format!("[{creator}]"),
+    }
+}
+
+fn public_name(tag: Tag) -> String {
+    StandardDataDictionary.by_tag(tag).map_or_else(
+        || "(unknown)".to_string(),
+        |entry| entry.alias().to_string(),
+    )
+}
+
+/// A value as text, or its length for binary values
+fn format_primitive(vr: VR, value: &PrimitiveValue) -> String {
+    if value.is_empty() {
+        return "(empty)".to_string();
+    }
+
+    if matches!(
+        vr,
+        VR::OB | VR::OW | VR::OF | VR::OD | VR::OL | VR::OV | VR::UN
+    ) {
+        return format!("({} bytes)", value.calculate_byte_len());
+    }
+
+    truncate(value.to_str().trim_end_matches(['\0', ' ']))
+}
+
+/// Text cut to [`MAX_VALUE_CHARS`]
+fn truncate(text: &str) -> String {
+    if text.chars().count() > MAX_VALUE_CHARS {
+        let cut: String = text.chars().take(MAX_VALUE_CHARS).collect();
+        format!("{cut}...")
+    } else {
+        text.to_string()
+    }
+}
+
+fn count(n: usize, noun: &str) -> String {
+    if n == 1 {
+        format!("1 {noun}")
+    } else {
+        format!("{n} {noun}s")
+    }
+}
@@ -0,0 +1,185 @@
+//! Names of private attributes
+//!
+//! Private attributes are numbered by their creator: a Private Creator
+//! element (gggg,00xx) reserves the block (gggg,xx00-xxFF) for its vendor
+//! string, so the same attribute has different tags from file to file. Names
+//! are looked up by creator, group and the element's offset in the block.
+
+use anyhow::{Context, Result, bail};
+use dicom::core::Tag;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// Private attributes known without a dictionary file, as listed by the
+/// vendors' conformance statements
+const BUILTIN: &[(&str, u16, u8, &str)] = &[
+    ("SIEMENS CSA HEADER", 0x0029, 0x08, "CSAImageHeaderType"),
+    ("SIEMENS CSA HEADER", 0x0029, 0x09, "CSAImageHeaderVersion"),
+    ("SIEMENS CSA HEADER", 0x0029, 0x10, "CSAImageHeaderInfo"),
+    ("SIEMENS CSA HEADER", 0x0029, 0x18, "CSASeriesHeaderType"),
+    ("SIEMENS CSA HEADER", 0x0029, 0x19, "CSASeriesHeaderVersion"),
+    ("SIEMENS CSA HEADER", 0x0029, 0x20, "CSASeriesHeaderInfo"),
+    ("SIEMENS MR HEADER", 0x0019, 0x08, "CSAImageHeaderType"),
+    ("SIEMENS MR HEADER", 0x0019, 0x09, "CSAImageHeaderVersion"),
+    (
+        "SIEMENS MR HEADER",
+        0x0019,
+        0x0B,
+        "SliceMeasurementDuration",
+    ),
+    ("SIEMENS MR HEADER", 0x0019, 0x0C, "BValue"),
+    ("SIEMENS MR HEADER", 0x0019, 0x0D, "DiffusionDirectionality"),
+    (
+        "SIEMENS MR HEADER",
+        0x0019,
+        0x0E,
+        "DiffusionGradientDirection",
+    ),
+    ("SIEMENS MR HEADER", 0x0019, 0x18, "RealDwellTime"),
+    (
+        "SIEMENS MR HEADER",
+        0x0019,
+        0x28,
+        "BandwidthPerPixelPhaseEncode",
+    ),
+    ("SIEMENS MR HEADER", 0x0019, 0x29, "MosaicRefAcqTimes"),
+    ("SIEMENS MR HEADER", 0x0051, 0x0A, "TimeOfAcquisition"),
+    ("SIEMENS MR HEADER", 0x0051, 0x0B, "AcquisitionMatrixText"),
+    ("SIEMENS MR HEADER", 0x0051, 0x0C, "FieldOfView"),
+    ("SIEMENS MR HEADER", 0x0051, 0x0D, "SlicePositionText"),
+    ("SIEMENS MR HEADER", 0x0051, 0x0F, "CoilString"),
+    ("SIEMENS MR HEADER", 0x0051, 0x11, "PATModeText"),
+    ("GEMS_ACQU_01", 0x0019, 0x9C, "PulseSequenceName"),
+    ("GEMS_PARM_01", 0x0043, 0x39, "SliceInfo"),
+    ("Philips Imaging DD 001", 0x2001, 0x03, "DiffusionBFactor"),
+    ("Philips Imaging DD 001", 0x2001, 0x08, "PhaseNumber"),
+];
+
+/// Names of private attributes by creator, group and block offset
+#[derive(Debug, Clone, Default)]
+pub struct PrivateDictionary {
+    names: HashMap<(String, u16, u8), String>,
+}
+
+impl PrivateDictionary {
+    /// The attributes known to dcmv
+    #[must_use]
+    pub fn builtin() -> Self {
+        let names = BUILTIN
+            .iter()
+            .map(|&(creator, group, offset, name)| {
+                ((creator.to_string(), group, offset), name.to_string())
+            })
+            .collect();
+        Self { names }
+    }
+
+    /// Add the attributes of a JSON dictionary file, which replace known ones
+    ///
+    /// The file maps creators to their attributes, keyed by group and block
+    /// offset as in `{"SIEMENS CSA HEADER": {"0029,xx10": "CSAImageHeaderInfo"}}`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read or is not such a dictionary
+    pub fn load(&mut self, path: &Path) -> Result<()> {
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read private dictionary {}", path.display()))?;
+        let creators: HashMap<String, HashMap<String, String>> = serde_json::from_str(&text)
+            .with_context(|| format!("Invalid private dictionary {}", path.display()))?;
+
+        for (creator, attributes) in creators {
+            for (key, name) in attributes {
+                let (group, offset) = parse_key(&key)
+                    .with_context(|| format!("Invalid private dictionary {}", path.display()))?;
+                self.names
+                    .insert((creator.trim().to_string(), group, offset), name);
+            }
+        }
+        Ok(())
+    }
+
+    /// Name of a private attribute reserved by `creator`
+    #[must_use]
+    pub fn name(&self, creator: &str, tag: Tag) -> Option<&str> {
+        let offset = (tag.element() & 0xFF) as u8;
+        self.names
+            .get(&(creator.trim().to_string(), tag.group(), offset))
+            .map(String::as_str)
+    }
+}
+
+/// Group and block offset of a key such as "0029,xx10"
+fn parse_key(key: &str) -> Result<(u16, u8)> {
+    let parsed = key.trim().split_once(',').and_then(|(group, element)| {
+        let offset = element
+            .strip_prefix("xx")
+            .or_else(|| element.strip_prefix("XX"))
+            .filter(|offset| offset.len() == 2)?;
+        let group = u16::from_str_radix(group, 16)
+            .ok()
+            .filter(|group| group % 2 == 1)?;
+        Some((group, u8::from_str_radix(offset, 16).ok()?))
+    });
+
+    match parsed {
+        Some(parsed) => Ok(parsed),
+        None => bail!("expected a private key such as \"0029,xx10\", found \"{key}\""),
+    }
+}
+
+/// Tag of the Private Creator element that reserves the block of `tag`,
+/// if `tag` is a private attribute in a block
+#[must_use]
+pub fn creator_tag(tag: Tag) -> Option<Tag> {
+    let block = tag.element() >> 8;
+    (is_private(tag) && block >= 0x10).then(|| Tag(tag.group(), block))
+}
+
+/// Whether `tag` is a Private Creator element
+#[must_use]
+pub fn is_creator(tag: Tag) -> bool {
+    is_private(tag) && (0x10..=0xFF).contains(&tag.element())
+}
+
+fn is_private(tag: Tag) -> bool {
+    // Groups 0001, 0003, 0005 and 0007 are reserved, not private
+    tag.group() % 2 == 1 && tag.group() > 0x0008
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_private_names_by_creator_and_offset() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("private.json");
+        fs::write(
+            &path,
+            r#"{"ACME 1.0": {"0041,xx02": "Widget"}, "SIEMENS CSA HEADER": {"0029,XX10": "Renamed"}}"#,
+        )
+        .unwrap();
+
+        let mut dictionary = PrivateDictionary::builtin();
+        assert_eq!(
+            dictionary.name("SIEMENS CSA HEADER", Tag(0x0029, 0x1110)),
+            Some("CSAImageHeaderInfo")
+        );
+
+        dictionary.load(&path).unwrap();
+        assert_eq!(
+            dictionary.name("ACME 1.0 ", Tag(0x0041, 0x1002)),
+            Some("Widget")
+        );
+        assert_eq!(
+            dictionary.name("SIEMENS CSA HEADER", Tag(0x0029, 0x1010)),
+            Some("Renamed")
+        );
+        assert_eq!(dictionary.name("ACME 1.0", Tag(0x0043, 0x1002)), None);
+
+        assert_eq!(creator_tag(Tag(0x0029, 0x1110)), Some(Tag(0x0029, 0x0011)));
+        assert!(parse_key("0028,xx10").is_err());
+    }
+}
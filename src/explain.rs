@@ -0,0 +1,29 @@
+//! Record of the decisions taken while processing a file, for `--explain`
+//!
+//! Decoding and conversion note which path they took (decoder, byte order,
+//! photometric conversion, normalization, resizing) as they run, so the
+//! report shows what actually happened rather than what should have.
+
+/// Processing decisions in the order they were taken
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DecisionLog {
+    entries: Vec<(&'static str, String)>,
+}
+
+impl DecisionLog {
+    /// Note a decision taken in processing `step`
+    pub fn record(&mut self, step: &'static str, decision: impl Into<String>) {
+        self.entries.push((step, decision.into()));
+    }
+
+    /// Steps and decisions, in the order they were recorded
+    #[must_use]
+    pub fn entries(&self) -> &[(&'static str, String)] {
+        &self.entries
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
@@ -0,0 +1,659 @@
+use crate::image::{DecodedFrame, Samples};
+use crate::types::{PixelSpacing, RescaleParams};
+use anyhow::{Context, Result, bail};
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::codecs::png::PngEncoder;
+use image::{Delay, DynamicImage, Frame, ImageBuffer, Luma, Rgb, RgbImage};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+/// Magic bytes at the start of a `.raw` export
+const RAW_MAGIC: &[u8; 4] = b"DCMV";
+
+/// Version of the `.raw` header layout
+const RAW_VERSION: u8 = 1;
+
+/// Frame time of animations when the file gives none, in milliseconds
+pub const DEFAULT_FRAME_TIME_MS: f64 = 100.0;
+
+/// Write a decoded frame to a file without quantizing it for display
+///
+/// The output format is chosen from the file extension. PNG keeps 8- and
+/// 16-bit samples as they are stored in the DICOM file.
+///
+/// # Errors
+///
+/// Returns an error if the format is unsupported, the sample layout cannot
+/// be represented in the format, or the file cannot be written
+pub fn export_frame(frame: &DecodedFrame, path: &Path) -> Result<()> {
+    match extension(path).as_deref() {
+        Some("png") => frame_to_image(frame)?
+            .save(path)
+            .with_context(|| format!("Failed to write {}", path.display())),
+        Some("npy" | "nii" | "raw") => export_array(frame, &RescaleParams::default(), None, path),
+        _ => bail!(
+            "Unsupported output format: {} (expected .png, .npy, .nii or .raw)",
+            path.display()
+        ),
+    }
+}
+
+/// Whether `path` names a pixel array format (`.npy`, `.nii` or `.raw`)
+/// rather than an image
+#[must_use]
+pub fn is_array_format(path: &Path) -> bool {
+    matches!(extension(path).as_deref(), Some("npy" | "nii" | "raw"))
+}
+
+/// Write the frame's pixel values, rescaled to modality units, as an array
+///
+/// `.npy` files load with `numpy.load`, `.nii` files are NIfTI-1 images
+/// (see [`write_nifti_to`]) and `.raw` files have a 16-byte header (see
+/// [`write_raw_to`]). Stored values are kept as they are when the rescale
+/// is the identity; integer rescales of 8- and 16-bit data give int32
+/// values and anything else float64. Color frames are not rescaled.
+///
+/// # Errors
+///
+/// Returns an error if the format is unsupported or the file cannot be
+/// written
+pub fn export_array(
+    frame: &DecodedFrame,
+    rescale: &RescaleParams,
+    spacing: Option<PixelSpacing>,
+    path: &Path,
+) -> Result<()> {
+    let format = extension(path);
+    if !is_array_format(path) {
+        bail!(
+            "Unsupported array format: {} (expected .npy, .nii or .raw)",
+            path.display()
+        );
+    }
+
+    let file =
+        File::create(path).with_context(|| format!("Failed to create {}", path.display()))?;
+    let mut writer = BufWriter::new(file);
+    match format.as_deref() {
+        Some("npy") => write_npy_to(frame, rescale, &mut writer),
+        Some("nii") => write_nifti_to(frame, rescale, spacing, &mut writer),
+        _ => write_raw_to(frame, rescale, &mut writer),
+    }
+    .and_then(|()| writer.flush().map_err(Into::into))
+    .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Write the rescaled frame as a NumPy `.npy` (version 1.0) array
+///
+/// The shape is (rows, columns), or (rows, columns, samples) for color.
+///
+/// # Errors
+///
+/// Returns an error if writing fails
+pub fn write_npy_to(
+    frame: &DecodedFrame,
+    rescale: &RescaleParams,
+    writer: &mut impl Write,
+) -> Result<()> {
+    let values = ArrayValues::new(frame, rescale);
+
+    let shape = if frame.samples_per_pixel == 1 {
+        format!("({}, {})", frame.height, frame.width)
+    } else {
+        format!(
+            "({}, {}, {})",
+            frame.height, frame.width, frame.samples_per_pixel
+        )
+    };
+    let mut header = format!(
+        "{{'descr': '{}', 'fortran_order': False, 'shape': {shape}, }}",
+        values.descr()
+    );
+
+    // The data starts 64-byte aligned, after the magic, version and length
+    let unpadded = 10 + header.len() + 1;
+    header.push_str(&" ".repeat(unpadded.next_multiple_of(64) - unpadded));
+    header.push('\n');
+
+    let header_len = u16::try_from(header.len()).context("NumPy header is too long")?;
+    writer.write_all(b"\x93NUMPY\x01\x00")?;
+    writer.write_all(&header_len.to_le_bytes())?;
+    writer.write_all(header.as_bytes())?;
+    values.write_le(writer)
+}
+
+/// Write the rescaled frame as little-endian values after a small header
+///
+/// The 16-byte header is the magic `DCMV`, the header version (1), the
+/// value kind (`u`nsigned, `i`nteger or `f`loat), bytes per value and
+/// samples per pixel as single bytes, then the width and height as
+/// little-endian u32. Values follow in row-major order, samples
+/// interleaved.
+///
+/// # Errors
+///
+/// Returns an error if writing fails
+pub fn write_raw_to(
+    frame: &DecodedFrame,
+    rescale: &RescaleParams,
+    writer: &mut impl Write,
+) -> Result<()> {
+    let values = ArrayValues::new(frame, rescale);
+    let (kind, size) = values.kind_and_size();
+    let samples_per_pixel =
+        u8::try_from(frame.samples_per_pixel).context("Too many samples per pixel")?;
+
+    writer.write_all(RAW_MAGIC)?;
+    writer.write_all(&[RAW_VERSION, kind, size, samples_per_pixel])?;
+    writer.write_all(&frame.width.to_le_bytes())?;
+    writer.write_all(&frame.height.to_le_bytes())?;
+    values.write_le(writer)
+}
+
+/// Write the rescaled frame as an uncompressed NIfTI-1 (`.nii`) image
+///
+/// The image is a single slice of `width` x `height` voxels, sized by
+/// `spacing` in mm when known. Color frames are written as RGB24. NIfTI
+/// counts rows from the bottom, so the header maps the first stored row to
+/// the top of the slice, and viewers show it as dcmv does.
+///
+/// # Errors
+///
+/// Returns an error if the samples cannot be written as NIfTI or writing
+/// fails
+pub fn write_nifti_to(
+    frame: &DecodedFrame,
+    rescale: &RescaleParams,
+    spacing: Option<PixelSpacing>,
+    writer: &mut impl Write,
+) -> Result<()> {
+    let values = ArrayValues::new(frame, rescale);
+    let (datatype, bitpix) = match (&values, frame.samples_per_pixel) {
+        (ArrayValues::U8(_), 3) => (128_i16, 24_i16),
+        (_, 1) => values.nifti_datatype(),
+        (_, samples) => bail!("NIfTI cannot hold {samples} samples per pixel"),
+    };
+    let width = i16::try_from(frame.width).context("Image is too wide for NIfTI")?;
+    let height = i16::try_from(frame.height).context("Image is too tall for NIfTI")?;
+    let (column, row) = spacing.map_or((1.0, 1.0), |s| (s.column as f32, s.row as f32));
+
+    let mut header = [0_u8; 348];
+    let mut put = |offset: usize, bytes: &[u8]| {
+        header[offset..offset + bytes.len()].copy_from_slice(bytes);
+    };
+    put(0, &348_i32.to_le_bytes());
+    put(38, b"r");
+    for (i, dim) in [2, width, height, 1, 1, 1, 1, 1].into_iter().enumerate() {
+        put(40 + 2 * i, &dim.to_le_bytes());
+    }
+    put(70, &datatype.to_le_bytes());
+    put(72, &bitpix.to_le_bytes());
+    for (i, size) in [1.0_f32, column, row, 1.0].into_iter().enumerate() {
+        put(76 + 4 * i, &size.to_le_bytes());
+    }
+    put(108, &352.0_f32.to_le_bytes());
+    // Millimetres, when the spacing is known
+    put(123, &[if spacing.is_some() { 2 } else { 0 }]);
+    put(148, b"dcmv");
+
+    // Aligned to an anatomical space, with y running down the rows
+    put(254, &2_i16.to_le_bytes());
+    let top = f32::from(height - 1) * row;
+    let srows = [
+        [column, 0.0, 0.0, 0.0],
+        [0.0, -row, 0.0, top],
+        [0.0, 0.0, 1.0, 0.0],
+    ];
+    for (i, value) in srows.into_iter().flatten().enumerate() {
+        put(280 + 4 * i, &value.to_le_bytes());
+    }
+    put(344, b"n+1\0");
+
+    writer.write_all(&header)?;
+    // No extensions
+    writer.write_all(&[0; 4])?;
+    values.write_le(writer)
+}
+
+/// Whether `path` names an animation or video format (`.gif`, `.apng` or
+/// `.mp4`)
+#[must_use]
+pub fn is_animation_format(path: &Path) -> bool {
+    matches!(extension(path).as_deref(), Some("gif" | "apng" | "mp4"))
+}
+
+/// Write rendered frames as a looping animation, or as a video
+///
+/// Frames are encoded as they arrive, so a long clip is never held in
+/// memory as a whole. MP4 (H.264) is encoded by an `ffmpeg` executable and
+/// needs the `ffmpeg` feature. Each frame is shown for `frame_time_ms`, or
+/// [`DEFAULT_FRAME_TIME_MS`] when unknown. All frames must have the size of
+/// the first.
+///
+/// # Errors
+///
+/// Returns an error if the format is unsupported, a frame fails to render,
+/// frame sizes differ or the file cannot be written
+pub fn export_animation(
+    frames: impl ExactSizeIterator<Item = Result<RgbImage>>,
+    frame_time_ms: Option<f64>,
+    path: &Path,
+) -> Result<()> {
+    let frame_time_ms = frame_time_ms.unwrap_or(DEFAULT_FRAME_TIME_MS);
+
+    let write = match extension(path).as_deref() {
+        Some("gif") => write_gif,
+        Some("apng") => write_apng,
+        #[cfg(feature = "ffmpeg")]
+        Some("mp4") => return ffmpeg::write_mp4(Box::new(frames), frame_time_ms, path),
+        #[cfg(not(feature = "ffmpeg"))]
+        Some("mp4") => bail!("MP4 export needs a build with the `ffmpeg` feature"),
+        _ => bail!(
+            "Unsupported animation format: {} (expected .gif, .apng or .mp4)",
+            path.display()
+        ),
+    };
+
+    let file =
+        File::create(path).with_context(|| format!("Failed to create {}", path.display()))?;
+    let mut writer = BufWriter::new(file);
+    write(Box::new(frames), frame_time_ms, &mut writer)
+        .and_then(|()| writer.flush().map_err(Into::into))
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+type FrameIter<'a> = Box<dyn ExactSizeIterator<Item = Result<RgbImage>> + 'a>;
+
+fn write_gif(frames: FrameIter, frame_time_ms: f64, writer: &mut BufWriter<File>) -> Result<()> {
+    // GIF stores delays in hundredths of a second
+    let delay = Delay::from_numer_denom_ms((frame_time_ms * 1000.0).round() as u32, 1000);
+
+    let mut encoder = GifEncoder::new_with_speed(writer, 10);
+    encoder.set_repeat(Repeat::Infinite)?;
+
+    let mut size = None;
+    for frame in frames {
+        let frame = frame?;
+        check_frame_size(&mut size, &frame)?;
+
+        let rgba = DynamicImage::ImageRgb8(frame).into_rgba8();
+        encoder.encode_frame(Frame::from_parts(rgba, 0, 0, delay))?;
+    }
+    Ok(())
+}
+
+fn write_apng(
+    mut frames: FrameIter,
+    frame_time_ms: f64,
+    writer: &mut BufWriter<File>,
+) -> Result<()> {
+    let count = u32::try_from(frames.len()).context("Too many frames")?;
+    let Some(first) = frames.next().transpose()? else {
+        bail!("No frames to write");
+    };
+
+    let mut encoder = png::Encoder::new(writer, first.width(), first.height());
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_animated(count, 0)?;
+    // In tenths of a millisecond, which covers frames up to 6.5 seconds
+    encoder.set_frame_delay(
+        (frame_time_ms * 10.0).round().clamp(1.0, 65535.0) as u16,
+        10_000,
+    )?;
+
+    let mut png = encoder.write_header()?;
+    let mut size = Some(first.dimensions());
+    png.write_image_data(first.as_raw())?;
+
+    for frame in frames {
+        let frame = frame?;
+        check_frame_size(&mut size, &frame)?;
+        png.write_image_data(frame.as_raw())?;
+    }
+
+    png.finish()?;
+    Ok(())
+}
+
+fn check_frame_size(size: &mut Option<(u32, u32)>, frame: &RgbImage) -> Result<()> {
+    match *size {
+        Some(expected) if expected != frame.dimensions() => bail!(
+            "Frame size {}x{} differs from the first frame ({}x{})",
+            frame.width(),
+            frame.height(),
+            expected.0,
+            expected.1
+        ),
+        Some(_) => Ok(()),
+        None => {
+            *size = Some(frame.dimensions());
+            Ok(())
+        }
+    }
+}
+
+fn extension(path: &Path) -> Option<String> {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(str::to_ascii_lowercase)
+}
+
+/// Encode a decoded frame as PNG into any writer (e.g. stdout)
+///
+/// # Errors
+///
+/// Returns an error if the sample layout cannot be represented in PNG or
+/// writing fails
+pub fn write_png_to(frame: &DecodedFrame, writer: impl Write) -> Result<()> {
+    frame_to_image(frame)?
+        .write_with_encoder(PngEncoder::new(writer))
+        .context("Failed to write PNG stream")
+}
+
+/// Wrap the frame samples in an image buffer, keeping their bit depth
+fn frame_to_image(frame: &DecodedFrame) -> Result<DynamicImage> {
+    let (width, height) = (frame.width, frame.height);
+
+    let image = match (&frame.samples, frame.samples_per_pixel) {
+        (Samples::U8(values), 1) => {
+            ImageBuffer::<Luma<u8>, _>::from_raw(width, height, values.clone())
+                .map(DynamicImage::ImageLuma8)
+        }
+        (Samples::U8(values), 3) => {
+            ImageBuffer::<Rgb<u8>, _>::from_raw(width, height, values.clone())
+                .map(DynamicImage::ImageRgb8)
+        }
+        (Samples::U16(values), 1) => {
+            ImageBuffer::<Luma<u16>, _>::from_raw(width, height, values.clone())
+                .map(DynamicImage::ImageLuma16)
+        }
+        (Samples::U16(values), 3) => {
+            ImageBuffer::<Rgb<u16>, _>::from_raw(width, height, values.clone())
+                .map(DynamicImage::ImageRgb16)
+        }
+        // PNG has no signed samples, so they are offset by 32768
+        (Samples::I16(_), 1) => {
+            ImageBuffer::<Luma<u16>, _>::from_raw(width, height, frame.to_u16())
+                .map(DynamicImage::ImageLuma16)
+        }
+        (samples, spp) => bail!(
+            "Cannot write {bits}-bit data with {spp} samples per pixel to PNG",
+            bits = samples.bits()
+        ),
+    };
+
+    image.context("Frame buffer does not match its dimensions")
+}
+
+/// Pixel values for array export, in the type they are written as
+#[derive(Debug, PartialEq)]
+enum ArrayValues {
+    U8(Vec<u8>),
+    U16(Vec<u16>),
+    U32(Vec<u32>),
+    I16(Vec<i16>),
+    I32(Vec<i32>),
+    F64(Vec<f64>),
+}
+
+impl ArrayValues {
+    fn new(frame: &DecodedFrame, rescale: &RescaleParams) -> Self {
+        let identity = rescale.slope == 1.0 && rescale.intercept == 0.0;
+
+        if frame.samples_per_pixel != 1 || identity {
+            return match &frame.samples {
+                Samples::U8(values) => Self::U8(values.clone()),
+                Samples::U16(values) => Self::U16(values.clone()),
+                Samples::U32(values) => Self::U32(values.clone()),
+                Samples::I16(values) => Self::I16(values.clone()),
+                Samples::I32(values) => Self::I32(values.clone()),
+            };
+        }
+
+        let rescaled = frame
+            .samples
+            .values()
+            .into_iter()
+            .map(|v| v.mul_add(rescale.slope, rescale.intercept));
+
+        // Integer slopes and intercepts (e.g. CT in HU) stay integers, which
+        // fit in i32 for 8- and 16-bit data with any sensible rescale
+        let integral = rescale.slope.fract() == 0.0
+            && rescale.intercept.fract() == 0.0
+            && rescale.slope.abs() <= 32768.0
+            && rescale.intercept.abs() <= 1_073_741_824.0;
+
+        if integral && frame.samples.bits() <= 16 {
+            Self::I32(rescaled.map(|v| v as i32).collect())
+        } else {
+            Self::F64(rescaled.collect())
+        }
+    }
+
+    /// NumPy type descriptor
+    fn descr(&self) -> &'static str {
+        match self {
+            Self::U8(_) => "|u1",
+            Self::U16(_) => "<u2",
+            Self::U32(_) => "<u4",
+            Self::I16(_) => "<i2",
+            Self::I32(_) => "<i4",
+            Self::F64(_) => "<f8",
+        }
+    }
+
+    /// Value kind and size in bytes, as in the `.raw` header
+    fn kind_and_size(&self) -> (u8, u8) {
+        match self {
+            Self::U8(_) => (b'u', 1),
+            Self::U16(_) => (b'u', 2),
+            Self::U32(_) => (b'u', 4),
+            Self::I16(_) => (b'i', 2),
+            Self::I32(_) => (b'i', 4),
+            Self::F64(_) => (b'f', 8),
+        }
+    }
+
+    /// NIfTI-1 datatype code and bits per voxel
+    fn nifti_datatype(&self) -> (i16, i16) {
+        match self {
+            Self::U8(_) => (2, 8),
+            Self::I16(_) => (4, 16),
+            Self::I32(_) => (8, 32),
+            Self::F64(_) => (64, 64),
+            Self::U16(_) => (512, 16),
+            Self::U32(_) => (768, 32),
+        }
+    }
+
+    fn write_le(&self, writer: &mut impl Write) -> Result<()> {
+        let bytes: Vec<u8> = match self {
+            Self::U8(values) => values.clone(),
+            Self::U16(values) => values.iter().flat_map(|v| v.to_le_bytes()).collect(),
+            Self::U32(values) => values.iter().flat_map(|v| v.to_le_bytes()).collect(),
+            Self::I16(values) => values.iter().flat_map(|v| v.to_le_bytes()).collect(),
+            Self::I32(values) => values.iter().flat_map(|v| v.to_le_bytes()).collect(),
+            Self::F64(values) => values.iter().flat_map(|v| v.to_le_bytes()).collect(),
+        };
+        writer.write_all(&bytes)?;
+        Ok(())
+    }
+}
+
+/// MP4 encoding through an external `ffmpeg`
+///
+/// H.264 copes with clips of hundreds of frames, where GIF files become
+/// impractically large.
+#[cfg(feature = "ffmpeg")]
+mod ffmpeg {
+    use super::{FrameIter, check_frame_size};
+    use anyhow::{Context, Result, bail};
+    use std::io::Write;
+    use std::path::Path;
+    use std::process::{Command, Stdio};
+
+    /// Executable to run, overridden with `DCMV_FFMPEG`
+    fn program() -> String {
+        std::env::var("DCMV_FFMPEG").unwrap_or_else(|_| "ffmpeg".to_string())
+    }
+
+    /// Pipe the frames to ffmpeg as raw RGB and let it write `path`
+    pub(super) fn write_mp4(mut frames: FrameIter, frame_time_ms: f64, path: &Path) -> Result<()> {
+        let Some(first) = frames.next().transpose()? else {
+            bail!("No frames to write");
+        };
+        let (width, height) = first.dimensions();
+
+        let mut child = Command::new(program())
+            .args(["-hide_banner", "-loglevel", "error", "-y"])
+            .args(["-f", "rawvideo", "-pix_fmt", "rgb24"])
+            .args(["-s", &format!("{width}x{height}")])
+            .args(["-framerate", &format!("{:.6}", 1000.0 / frame_time_ms)])
+            .args(["-i", "-"])
+            // yuv420p, which players expect, needs even dimensions
+            .args(["-vf", "pad=ceil(iw/2)*2:ceil(ih/2)*2"])
+            .args([
+                "-c:v",
+                "libx264",
+                "-pix_fmt",
+                "yuv420p",
+                "-movflags",
+                "+faststart",
+            ])
+            .arg(path)
+            .stdin(Stdio::piped())
+            .spawn()
+            .with_context(|| format!("Failed to run {} (is it installed?)", program()))?;
+
+        let mut stdin = child.stdin.take().context("ffmpeg has no input pipe")?;
+        let mut size = Some((width, height));
+        let written = std::iter::once(Ok(first))
+            .chain(frames)
+            .try_for_each(|frame| {
+                let frame = frame?;
+                check_frame_size(&mut size, &frame)?;
+                stdin
+                    .write_all(frame.as_raw())
+                    .context("ffmpeg stopped reading frames")
+            });
+
+        // Closing the pipe tells ffmpeg the clip is complete
+        drop(stdin);
+        let status = child.wait().context("Failed to wait for ffmpeg")?;
+        written?;
+
+        if !status.success() {
+            bail!("ffmpeg failed ({status})");
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_npy_header_is_aligned_and_rescaled_to_integers() {
+        let frame = DecodedFrame {
+            width: 3,
+            height: 2,
+            samples_per_pixel: 1,
+            samples: Samples::U16(vec![0, 1000, 1024, 2000, 3000, 4095]),
+        };
+        let rescale = RescaleParams::new(1.0, -1024.0);
+
+        let mut out = Vec::new();
+        write_npy_to(&frame, &rescale, &mut out).unwrap();
+
+        assert!(out.starts_with(b"\x93NUMPY\x01\x00"));
+        let header_len = usize::from(u16::from_le_bytes([out[8], out[9]]));
+        let header = std::str::from_utf8(&out[10..10 + header_len]).unwrap();
+        assert_eq!((10 + header_len) % 64, 0);
+        assert!(header.starts_with("{'descr': '<i4', 'fortran_order': False, 'shape': (2, 3), }"));
+        assert!(header.ends_with('\n'));
+
+        let values: Vec<i32> = out[10 + header_len..]
+            .chunks_exact(4)
+            .map(|chunk| i32::from_le_bytes(chunk.try_into().unwrap()))
+            .collect();
+        assert_eq!(values, vec![-1024, -24, 0, 976, 1976, 3071]);
+    }
+
+    #[test]
+    fn test_nifti_header_describes_the_slice() {
+        let frame = DecodedFrame {
+            width: 3,
+            height: 2,
+            samples_per_pixel: 1,
+            samples: Samples::U16(vec![0, 1000, 1024, 2000, 3000, 4095]),
+        };
+        let rescale = RescaleParams::new(1.0, -1024.0);
+
+        let mut out = Vec::new();
+        write_nifti_to(
+            &frame,
+            &rescale,
+            Some(PixelSpacing::new(0.5, 0.25)),
+            &mut out,
+        )
+        .unwrap();
+
+        let i16_at = |offset: usize| i16::from_le_bytes([out[offset], out[offset + 1]]);
+        let f32_at =
+            |offset: usize| f32::from_le_bytes(out[offset..offset + 4].try_into().unwrap());
+        assert_eq!(out.len(), 352 + 6 * 4);
+        assert_eq!(&out[344..348], b"n+1\0");
+        assert_eq!([i16_at(40), i16_at(42), i16_at(44)], [2, 3, 2]);
+        // int32
+        assert_eq!([i16_at(70), i16_at(72)], [8, 32]);
+        assert_eq!([f32_at(80), f32_at(84)], [0.25, 0.5]);
+        assert_eq!(f32_at(108), 352.0);
+        // The second row lies below the first
+        assert_eq!([f32_at(300), f32_at(308)], [-0.5, 0.5]);
+
+        let values: Vec<i32> = out[352..]
+            .chunks_exact(4)
+            .map(|chunk| i32::from_le_bytes(chunk.try_into().unwrap()))
+            .collect();
+        assert_eq!(values, vec![-1024, -24, 0, 976, 1976, 3071]);
+    }
+
+    #[test]
+    fn test_npy_keeps_signed_samples_signed() {
+        let frame = DecodedFrame {
+            width: 2,
+            height: 1,
+            samples_per_pixel: 1,
+            samples: Samples::I16(vec![-1000, 40]),
+        };
+
+        let mut out = Vec::new();
+        write_npy_to(&frame, &RescaleParams::default(), &mut out).unwrap();
+
+        let header_len = usize::from(u16::from_le_bytes([out[8], out[9]]));
+        let header = std::str::from_utf8(&out[10..10 + header_len]).unwrap();
+        assert!(header.starts_with("{'descr': '<i2',"));
+        assert_eq!(&out[10 + header_len..], &[0x18, 0xfc, 40, 0]);
+    }
+
+    #[test]
+    fn test_raw_header_describes_values() {
+        let frame = DecodedFrame {
+            width: 2,
+            height: 1,
+            samples_per_pixel: 1,
+            samples: Samples::U8(vec![10, 20]),
+        };
+
+        let mut out = Vec::new();
+        write_raw_to(&frame, &RescaleParams::new(0.5, 0.0), &mut out).unwrap();
+
+        assert_eq!(&out[..8], b"DCMV\x01f\x08\x01");
+        assert_eq!(&out[8..16], &[2, 0, 0, 0, 1, 0, 0, 0]);
+        assert_eq!(out[16..].len(), 2 * 8);
+        assert_eq!(f64::from_le_bytes(out[24..32].try_into().unwrap()), 10.0);
+    }
+}
@@ -0,0 +1,214 @@
+//! Fusion of a functional image, such as PET, over an anatomical one
+//!
+//! The PET slice nearest to the anatomical image is resampled onto its
+//! pixel grid through the patient coordinates of both images, mapped
+//! through a colormap and blended over the rendered anatomical image.
+
+use crate::dicom::{self, DicomMetadata, ExtractOptions};
+use crate::geometry::ImagePlane;
+use crate::image::{self, Colormap, DecodedFrame, Transform};
+use crate::render::{RenderOptions, RenderSource};
+use crate::stack;
+use ::image::Rgb;
+use anyhow::{Context, Result, bail};
+use std::path::{Path, PathBuf};
+use tracing::debug;
+
+/// Farthest a PET slice may be from the center of the image, in mm. PET
+/// slices are usually 2 to 4 mm apart.
+const SLICE_TOLERANCE: f64 = 5.0;
+
+/// Default opacity of the fused image
+pub const DEFAULT_ALPHA: f64 = 0.5;
+
+/// PET slices to fuse over anatomical images
+///
+/// Only the headers are read up front; the slice nearest to each image is
+/// decoded when it is fused.
+pub struct Fusion {
+    slices: Vec<(ImagePlane, PathBuf)>,
+    /// Opacity of the fused image, from 0 (invisible) to 1 (opaque)
+    alpha: f64,
+    colormap: Colormap,
+}
+
+impl Fusion {
+    /// PET slices from a file, or from the files of a directory
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the directory cannot be listed or no file has
+    /// the geometry needed for resampling
+    pub fn open(path: &Path, alpha: f64) -> Result<Self> {
+        let mut slices = Vec::new();
+
+        for path in stack::expand_directories(&[path.to_path_buf()])? {
+            let plane = dicom::open_dicom_header(&path)
+                .and_then(|header| dicom::extract_metadata_tags(&header))
+                .map(|metadata| ImagePlane::of(&metadata));
+
+            match plane {
+                Ok(Some(plane)) => slices.push((plane, path)),
+                Ok(None) => debug!(path = %path.display(), "no geometry, not fused"),
+                Err(e) => debug!(path = %path.display(), "not fused: {e:#}"),
+            }
+        }
+
+        if slices.is_empty() {
+            bail!(
+                "No image with Image Position, Image Orientation and Pixel Spacing to fuse in {}",
+                path.display()
+            );
+        }
+
+        Ok(Self {
+            slices,
+            alpha: alpha.clamp(0.0, 1.0),
+            colormap: Colormap::Hot,
+        })
+    }
+
+    /// `metadata` rendered with `options` and the nearest PET slice blended
+    /// over it, as an RGB image with the tags of `metadata`
+    ///
+    /// The result is not yet oriented and has no overlays, so that it is
+    /// rendered like any other image.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the image has no geometry, no PET slice is near
+    /// it, or either image cannot be decoded
+    pub fn fuse(&self, metadata: &DicomMetadata, options: &RenderOptions) -> Result<DicomMetadata> {
+        let plane = ImagePlane::of(metadata).context(
+            "Fusion needs Image Position, Image Orientation and Pixel Spacing in the image",
+        )?;
+        let (pet_plane, path) = self.nearest(&plane)?;
+        debug!(path = %path.display(), "fusing");
+
+        let pet = path.load(&ExtractOptions::default())?;
+        let frame = image::decode_frame(&pet.single_frame(0)?)?;
+        let values = resample(&frame, &pet, pet_plane, &plane);
+
+        // Colors span from zero to the highest value in the slice
+        let peak = values.iter().flatten().copied().fold(0.0, f64::max);
+
+        let unoriented = RenderOptions {
+            orientation: Some(Transform::IDENTITY),
+            annotations: false,
+            graphic_annotations: false,
+            caption: None,
+            tone: None,
+            colormap: None,
+            size: None,
+            ..*options
+        };
+        let (base, _) = unoriented.render(metadata)?;
+        let mut fused = image::quantize(base).into_rgb8();
+
+        for (pixel, value) in fused.pixels_mut().zip(&values) {
+            if let Some(value) = value {
+                let level = if peak > 0.0 {
+                    (value / peak).clamp(0.0, 1.0) * 255.0
+                } else {
+                    0.0
+                };
+                *pixel = blend(*pixel, self.colormap.color(level as u8), self.alpha);
+            }
+        }
+
+        Ok(metadata.with_rgb_image(fused))
+    }
+
+    /// The PET slice nearest to the center of `plane`
+    fn nearest(&self, plane: &ImagePlane) -> Result<&(ImagePlane, PathBuf)> {
+        let center = plane.point((
+            f64::from(plane.dimensions.cols) / 2.0,
+            f64::from(plane.dimensions.rows) / 2.0,
+        ));
+
+        let (distance, nearest) = self
+            .slices
+            .iter()
+            .filter(|(pet, _)| pet.shares_frame_of_reference(plane))
+            .map(|slice| (slice.0.distance(center).abs(), slice))
+            .min_by(|a, b| a.0.total_cmp(&b.0))
+            .context("No PET image is in the frame of reference of the image")?;
+
+        if distance > SLICE_TOLERANCE {
+            bail!("The nearest PET slice is {distance:.1} mm away from the image");
+        }
+        Ok(nearest)
+    }
+}
+
+/// Rescaled values of `frame` at each pixel of `target`, row by row, with
+/// `None` outside the PET image
+///
+/// Values are interpolated bilinearly between the four nearest pixels.
+fn resample(
+    frame: &DecodedFrame,
+    metadata: &DicomMetadata,
+    source: &ImagePlane,
+    target: &ImagePlane,
+) -> Vec<Option<f64>> {
+    let (cols, rows) = (target.dimensions.cols, target.dimensions.rows);
+    let value = |x: u32, y: u32| {
+        frame
+            .sample(x, y)
+            .map(|stored| metadata.rescaled_value(stored))
+    };
+
+    let mut values = Vec::with_capacity(usize::from(cols) * usize::from(rows));
+    for y in 0..rows {
+        for x in 0..cols {
+            let point = target.point((f64::from(x), f64::from(y)));
+            let (u, v) = source.pixel(point);
+
+            let (x0, y0) = (u.floor(), v.floor());
+            if x0 < 0.0 || y0 < 0.0 {
+                values.push(None);
+                continue;
+            }
+            let (fx, fy) = (u - x0, v - y0);
+            let (x0, y0) = (x0 as u32, y0 as u32);
+
+            let corners = [
+                value(x0, y0),
+                value(x0 + 1, y0).or(value(x0, y0)),
+                value(x0, y0 + 1).or(value(x0, y0)),
+                value(x0 + 1, y0 + 1).or(value(x0, y0)),
+            ];
+            values.push(match corners {
+                [Some(a), Some(b), Some(c), Some(d)] => {
+                    let top = (b - a).mul_add(fx, a);
+                    let bottom = (d - c).mul_add(fx, c);
+                    Some((bottom - top).mul_add(fy, top))
+                }
+                _ => None,
+            });
+        }
+    }
+    values
+}
+
+/// `overlay` over `base` with the given opacity
+fn blend(base: Rgb<u8>, overlay: Rgb<u8>, alpha: f64) -> Rgb<u8> {
+    Rgb([0, 1, 2].map(|c| {
+        (f64::from(overlay[c]) - f64::from(base[c]))
+            .mul_add(alpha, f64::from(base[c]))
+            .round() as u8
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blend() {
+        let (black, red) = (Rgb([0, 0, 0]), Rgb([255, 0, 0]));
+        assert_eq!(blend(black, red, 0.0), black);
+        assert_eq!(blend(black, red, 1.0), red);
+        assert_eq!(blend(Rgb([100, 100, 100]), red, 0.5), Rgb([178, 50, 50]));
+    }
+}
@@ -0,0 +1,169 @@
+//! Image planes in patient coordinates
+//!
+//! Image Position and Image Orientation (Patient) place each pixel of a
+//! cross-sectional image in the patient coordinate system, in mm. Images
+//! with the same Frame of Reference UID share that coordinate system, so
+//! the position of one image can be found on another, e.g. the line an
+//! axial slice cuts through a localizer.
+
+use crate::dicom::DicomMetadata;
+use crate::types::{Dimensions, ImageOrientation, PixelSpacing};
+use anyhow::{Result, bail};
+
+/// Planes closer to parallel than this (cosine of the angle between their
+/// normals) are treated as parallel
+const PARALLEL_COSINE: f64 = 0.9999;
+
+/// Position and extent of an image in patient coordinates
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImagePlane {
+    /// Center of the first pixel
+    pub origin: [f64; 3],
+    pub orientation: ImageOrientation,
+    pub spacing: PixelSpacing,
+    pub dimensions: Dimensions,
+    pub frame_of_reference_uid: Option<String>,
+}
+
+impl ImagePlane {
+    /// Plane of an image, if it has Image Position, Image Orientation and
+    /// Pixel Spacing
+    #[must_use]
+    pub fn of(metadata: &DicomMetadata) -> Option<Self> {
+        Some(Self {
+            origin: metadata.position?,
+            orientation: metadata.orientation?,
+            spacing: metadata.pixel_spacing?,
+            dimensions: metadata.dimensions,
+            frame_of_reference_uid: metadata.frame_of_reference_uid.clone(),
+        })
+    }
+
+    /// Patient position of the pixel at column `x` and row `y`
+    #[must_use]
+    pub fn point(&self, (x, y): (f64, f64)) -> [f64; 3] {
+        let (dx, dy) = (x * self.spacing.column, y * self.spacing.row);
+        let ImageOrientation { row, column } = self.orientation;
+        [0, 1, 2].map(|i| column[i].mul_add(dy, row[i].mul_add(dx, self.origin[i])))
+    }
+
+    /// Column and row of the pixel nearest to `point`, projected onto the
+    /// plane, in fractional pixels
+    #[must_use]
+    pub fn pixel(&self, point: [f64; 3]) -> (f64, f64) {
+        let offset = [0, 1, 2].map(|i| point[i] - self.origin[i]);
+        (
+            dot(offset, self.orientation.row) / self.spacing.column,
+            dot(offset, self.orientation.column) / self.spacing.row,
+        )
+    }
+
+    /// Signed distance of `point` from the plane, along its normal
+    #[must_use]
+    pub fn distance(&self, point: [f64; 3]) -> f64 {
+        let offset = [0, 1, 2].map(|i| point[i] - self.origin[i]);
+        dot(offset, self.orientation.normal())
+    }
+
+    /// Whether both planes are known to be in the same coordinate system
+    ///
+    /// Images without a Frame of Reference UID are assumed to share it.
+    #[must_use]
+    pub fn shares_frame_of_reference(&self, other: &Self) -> bool {
+        match (&self.frame_of_reference_uid, &other.frame_of_reference_uid) {
+            (Some(a), Some(b)) => a == b,
+            _ => true,
+        }
+    }
+
+    /// Where `slice` cuts through this image, as the pixel positions where
+    /// the line enters and leaves it
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the images are in different frames of reference,
+    /// are parallel, or `slice` does not cross this image
+    pub fn intersection(&self, slice: &Self) -> Result<((f64, f64), (f64, f64))> {
+        if !self.shares_frame_of_reference(slice) {
+            bail!("The images are in different frames of reference");
+        }
+        let cosine = dot(self.orientation.normal(), slice.orientation.normal());
+        if cosine.abs() > PARALLEL_COSINE {
+            bail!("The slice is parallel to the localizer");
+        }
+
+        let right = f64::from(self.dimensions.cols.max(1) - 1);
+        let bottom = f64::from(self.dimensions.rows.max(1) - 1);
+        let corners = [(0.0, 0.0), (right, 0.0), (right, bottom), (0.0, bottom)];
+        let distances = corners.map(|corner| slice.distance(self.point(corner)));
+
+        // The slice plane crosses the edges whose ends lie on opposite sides
+        let mut crossings: Vec<(f64, f64)> = Vec::with_capacity(4);
+        for i in 0..corners.len() {
+            let j = (i + 1) % corners.len();
+            let (a, b) = (corners[i], corners[j]);
+            let (da, db) = (distances[i], distances[j]);
+
+            let crossing = if da == 0.0 {
+                Some(a)
+            } else if da.signum() != db.signum() && db != 0.0 {
+                let t = da / (da - db);
+                Some((t.mul_add(b.0 - a.0, a.0), t.mul_add(b.1 - a.1, a.1)))
+            } else {
+                None
+            };
+
+            if let Some(crossing) = crossing
+                && !crossings.contains(&crossing)
+            {
+                crossings.push(crossing);
+            }
+        }
+
+        match crossings[..] {
+            [from, to, ..] => Ok((from, to)),
+            _ => bail!("The slice does not cross the localizer"),
+        }
+    }
+}
+
+fn dot(a: [f64; 3], b: [f64; 3]) -> f64 {
+    a[0].mul_add(b[0], a[1].mul_add(b[1], a[2] * b[2]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plane(origin: [f64; 3], row: [f64; 3], column: [f64; 3]) -> ImagePlane {
+        ImagePlane {
+            origin,
+            orientation: ImageOrientation::new(row, column),
+            spacing: PixelSpacing::new(1.0, 1.0),
+            dimensions: Dimensions::new(101, 101),
+            frame_of_reference_uid: Some("1.2.3".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_axial_slice_on_coronal_localizer() {
+        // Coronal localizer from (-50, 0, 50), rows going down to the feet
+        let localizer = plane([-50.0, 0.0, 50.0], [1.0, 0.0, 0.0], [0.0, 0.0, -1.0]);
+        // Axial slice 20 mm below the top of the localizer
+        let slice = plane([-50.0, -50.0, 30.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]);
+
+        let (from, to) = localizer.intersection(&slice).unwrap();
+        let rounded = |(x, y): (f64, f64)| (x.round(), y.round());
+        assert_eq!(rounded(from), (100.0, 20.0));
+        assert_eq!(rounded(to), (0.0, 20.0));
+
+        let parallel = plane([-50.0, 10.0, 50.0], [1.0, 0.0, 0.0], [0.0, 0.0, -1.0]);
+        assert!(localizer.intersection(&parallel).is_err());
+
+        let other = ImagePlane {
+            frame_of_reference_uid: Some("4.5.6".to_string()),
+            ..slice
+        };
+        assert!(localizer.intersection(&other).is_err());
+    }
+}
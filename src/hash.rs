@@ -0,0 +1,127 @@
+//! SHA-256 hashes of files and of their decoded pixels for `--hash`
+//!
+//! The file hash checks that a file arrived intact. The pixel hash is taken
+//! over the decoded samples of every frame, so it is the same for files
+//! whose pixels are identical whatever their headers, transfer syntax or
+//! byte order: a lossless transcode, or the same image anonymized.
+
+use crate::dicom::DicomMetadata;
+use crate::image::{DecodedFrame, Samples, decode_frame};
+use anyhow::{Context, Result};
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+/// SHA-256 of the bytes of the file at `path`, in hex
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be read
+pub fn file_sha256(path: &Path) -> Result<String> {
+    let mut file =
+        File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// SHA-256 of the decoded pixels of every frame, in hex
+///
+/// The hash covers the width, height, samples per pixel, sample size and
+/// number of frames, then the samples of each frame as little-endian
+/// values. Grayscale samples are the stored values; color is hashed as the
+/// 8-bit RGB it is shown as.
+///
+/// # Errors
+///
+/// Returns an error if a frame cannot be decoded
+pub fn pixel_sha256(metadata: &DicomMetadata) -> Result<String> {
+    let frames = metadata.number_of_frames.max(1);
+    let mut hasher = Sha256::new();
+
+    for index in 0..frames {
+        let frame = if frames == 1 {
+            decode_frame(metadata)?
+        } else {
+            decode_frame(&metadata.single_frame(index)?)?
+        };
+        if index == 0 {
+            hash_shape(&mut hasher, &frame, frames);
+        }
+        match &frame.samples {
+            Samples::U8(values) => hasher.update(values),
+            Samples::U16(values) => values
+                .iter()
+                .for_each(|value| hasher.update(value.to_le_bytes())),
+            Samples::U32(values) => values
+                .iter()
+                .for_each(|value| hasher.update(value.to_le_bytes())),
+            Samples::I16(values) => values
+                .iter()
+                .for_each(|value| hasher.update(value.to_le_bytes())),
+            Samples::I32(values) => values
+                .iter()
+                .for_each(|value| hasher.update(value.to_le_bytes())),
+        }
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Print the file and pixel hashes for `--hash`; `file` is `None` for stdin,
+/// which has no file to hash
+pub fn print_hashes(file: Option<&Path>, metadata: &DicomMetadata) {
+    let hash_or_error =
+        |hash: Result<String>| hash.unwrap_or_else(|e| format!("unavailable ({e:#})"));
+    if let Some(file) = file {
+        println!(
+            "{:20}: {}",
+            "File SHA-256",
+            hash_or_error(file_sha256(file))
+        );
+    }
+    println!(
+        "{:20}: {}",
+        "Pixel SHA-256",
+        hash_or_error(pixel_sha256(metadata))
+    );
+    println!();
+}
+
+/// The layout, so that the same samples in another shape hash differently
+fn hash_shape(hasher: &mut Sha256, frame: &DecodedFrame, frames: u32) {
+    let sample_bytes = u32::from(frame.samples.bits() / 8);
+    for value in [
+        frame.width,
+        frame.height,
+        u32::from(frame.samples_per_pixel),
+        sample_bytes,
+        frames,
+    ] {
+        hasher.update(value.to_le_bytes());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pixel_hash_ignores_byte_order_but_not_shape() {
+        let samples: Vec<u16> = (0..12).collect();
+        let metadata = DicomMetadata::test_grayscale16(2, 3, &samples);
+        let hash = pixel_sha256(&metadata).unwrap();
+        assert_eq!(hash.len(), 64);
+        assert_eq!(pixel_sha256(&metadata).unwrap(), hash);
+
+        // Same samples as 3x2 frames
+        let transposed = DicomMetadata::test_grayscale16(3, 2, &samples);
+        assert_ne!(pixel_sha256(&transposed).unwrap(), hash);
+
+        let mut changed = samples;
+        changed[11] = 99;
+        let changed = DicomMetadata::test_grayscale16(2, 3, &changed);
+        assert_ne!(pixel_sha256(&changed).unwrap(), hash);
+    }
+}
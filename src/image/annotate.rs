@@ -0,0 +1,368 @@
+use super::font::{GLYPH_ADVANCE, GLYPH_HEIGHT, GLYPH_WIDTH, glyph};
+use super::orientation::Transform;
+use crate::dicom::DicomMetadata;
+use crate::types::{GraphicShape, OverlayPlane};
+use image::{DynamicImage, Rgb, RgbImage};
+use std::f64::consts::TAU;
+
+pub const ANNOTATION_COLOR: Rgb<u8> = Rgb([255, 255, 0]);
+const SHADOW_COLOR: Rgb<u8> = Rgb([0, 0, 0]);
+
+/// Segments circles and ellipses are drawn with
+const OUTLINE_SEGMENTS: usize = 72;
+
+/// Scale bar lengths to choose from, in millimeters
+const SCALE_BAR_LENGTHS_MM: [f64; 10] =
+    [1.0, 2.0, 5.0, 10.0, 20.0, 50.0, 100.0, 200.0, 500.0, 1000.0];
+
+/// Overlay orientation markers and a millimeter scale bar
+///
+/// `transform` is the orientation transform already applied to `image`, so
+/// the markers describe what is on screen. Markers need the image
+/// orientation and the scale bar needs pixel spacing; each is skipped when
+/// the file does not provide it.
+#[must_use]
+pub fn annotate(
+    image: DynamicImage,
+    metadata: &DicomMetadata,
+    transform: Transform,
+) -> DynamicImage {
+    let mut canvas = super::quantize(image).into_rgb8();
+    let scale = text_scale(&canvas);
+
+    if let Some(orientation) = &metadata.orientation {
+        let (right, down) = transform.patient_axes(orientation);
+        draw_orientation_markers(&mut canvas, right, down, scale);
+    }
+
+    if let Some(spacing) = metadata.pixel_spacing {
+        let horizontal_spacing = if transform.swaps_axes() {
+            spacing.row
+        } else {
+            spacing.column
+        };
+        draw_scale_bar(&mut canvas, horizontal_spacing, scale);
+    }
+
+    DynamicImage::ImageRgb8(canvas)
+}
+
+/// Draw the graphic annotations of `metadata` that are shown on `frame`
+/// (0-based)
+///
+/// `transform` is the orientation transform already applied to `image`.
+/// Objects without a color of their own, or of their graphic layer, are
+/// drawn in [`ANNOTATION_COLOR`].
+#[must_use]
+pub fn draw_graphic_annotations(
+    image: DynamicImage,
+    metadata: &DicomMetadata,
+    transform: Transform,
+    frame: u32,
+) -> DynamicImage {
+    let mut canvas = super::quantize(image).into_rgb8();
+    let scale = text_scale(&canvas);
+    let size = (f64::from(metadata.cols()), f64::from(metadata.rows()));
+    let map = |point| {
+        let (x, y) = transform.target_point(point, size);
+        (x.round() as i64, y.round() as i64)
+    };
+
+    let shown = metadata.graphic_annotations.iter().filter(|annotation| {
+        annotation.frames.is_empty() || annotation.frames.contains(&(frame + 1))
+    });
+    for annotation in shown {
+        let color = annotation.color.map_or(ANNOTATION_COLOR, Rgb);
+        let outline: Vec<(i64, i64)> = match &annotation.shape {
+            GraphicShape::Text { text, position } => {
+                let (x, y) = map(*position);
+                let line_height = i64::from(text_height(scale) + scale);
+                for (index, line) in text.lines().enumerate() {
+                    draw_text(
+                        &mut canvas,
+                        x,
+                        y + index as i64 * line_height,
+                        line,
+                        scale,
+                        color,
+                    );
+                }
+                continue;
+            }
+            GraphicShape::Point(point) => {
+                let (x, y) = map(*point);
+                let arm = i64::from(scale * 3);
+                draw_line(&mut canvas, (x - arm, y), (x + arm, y), scale, color);
+                draw_line(&mut canvas, (x, y - arm), (x, y + arm), scale, color);
+                continue;
+            }
+            GraphicShape::Polyline(points) => points.iter().copied().map(map).collect(),
+            GraphicShape::Circle { center, radius } => {
+                ellipse_points(*center, (*radius, 0.0), (0.0, *radius))
+                    .into_iter()
+                    .map(map)
+                    .collect()
+            }
+            GraphicShape::Ellipse { major, minor } => {
+                let center = (
+                    (major[0].0 + major[1].0) / 2.0,
+                    (major[0].1 + major[1].1) / 2.0,
+                );
+                let half = |[a, b]: [(f64, f64); 2]| ((b.0 - a.0) / 2.0, (b.1 - a.1) / 2.0);
+                ellipse_points(center, half(*major), half(*minor))
+                    .into_iter()
+                    .map(map)
+                    .collect()
+            }
+        };
+        for pair in outline.windows(2) {
+            draw_line(&mut canvas, pair[0], pair[1], scale, color);
+        }
+    }
+
+    DynamicImage::ImageRgb8(canvas)
+}
+
+/// Draw the overlay planes of `metadata` that are shown on `frame`
+/// (0-based) in [`ANNOTATION_COLOR`]
+///
+/// `transform` is the orientation transform already applied to `image`.
+#[must_use]
+pub fn draw_overlay_planes(
+    image: DynamicImage,
+    metadata: &DicomMetadata,
+    transform: Transform,
+    frame: u32,
+) -> DynamicImage {
+    let mut canvas = super::quantize(image).into_rgb8();
+    let size = (u32::from(metadata.cols()), u32::from(metadata.rows()));
+
+    let shown = metadata
+        .overlays
+        .iter()
+        .filter(|overlay| overlay.frame == frame);
+    for (x, y) in shown.flat_map(OverlayPlane::set_pixels) {
+        let (Ok(x), Ok(y)) = (u32::try_from(x), u32::try_from(y)) else {
+            continue;
+        };
+        if x < size.0 && y < size.1 {
+            let (x, y) = transform.target_position((x, y), size);
+            if x < canvas.width() && y < canvas.height() {
+                canvas.put_pixel(x, y, ANNOTATION_COLOR);
+            }
+        }
+    }
+
+    DynamicImage::ImageRgb8(canvas)
+}
+
+/// Closed outline of the ellipse with semi-axes `u` and `v` around `center`
+fn ellipse_points(center: (f64, f64), u: (f64, f64), v: (f64, f64)) -> Vec<(f64, f64)> {
+    (0..=OUTLINE_SEGMENTS)
+        .map(|step| {
+            let angle = TAU * step as f64 / OUTLINE_SEGMENTS as f64;
+            let (cos, sin) = (angle.cos(), angle.sin());
+            (
+                center.0 + u.0 * cos + v.0 * sin,
+                center.1 + u.1 * cos + v.1 * sin,
+            )
+        })
+        .collect()
+}
+
+/// Glyph scale that keeps text legible once the image is shrunk to terminal size
+#[must_use]
+pub fn text_scale(image: &RgbImage) -> u32 {
+    (image.width().min(image.height()) / 160).max(1)
+}
+
+#[must_use]
+pub fn text_width(text: &str, scale: u32) -> u32 {
+    (text.chars().count() as u32 * GLYPH_ADVANCE).saturating_sub(1) * scale
+}
+
+#[must_use]
+pub fn text_height(scale: u32) -> u32 {
+    GLYPH_HEIGHT * scale
+}
+
+/// Draw text with a drop shadow, so it reads on both dark and bright areas
+pub fn draw_text(image: &mut RgbImage, x: i64, y: i64, text: &str, scale: u32, color: Rgb<u8>) {
+    let offset = i64::from(scale);
+    draw_glyphs(image, x + offset, y + offset, text, scale, SHADOW_COLOR);
+    draw_glyphs(image, x, y, text, scale, color);
+}
+
+fn draw_glyphs(image: &mut RgbImage, x: i64, y: i64, text: &str, scale: u32, color: Rgb<u8>) {
+    let scale_i = i64::from(scale);
+
+    for (index, c) in text.chars().enumerate() {
+        let origin_x = x + index as i64 * i64::from(GLYPH_ADVANCE) * scale_i;
+
+        for (row, bits) in glyph(c).into_iter().enumerate() {
+            for col in 0..GLYPH_WIDTH {
+                if bits & (0x10 >> col) != 0 {
+                    fill_rect(
+                        image,
+                        origin_x + i64::from(col) * scale_i,
+                        y + row as i64 * scale_i,
+                        scale,
+                        scale,
+                        color,
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Fill a rectangle, clipped to the image bounds
+pub fn fill_rect(image: &mut RgbImage, x: i64, y: i64, width: u32, height: u32, color: Rgb<u8>) {
+    let x0 = x.max(0);
+    let y0 = y.max(0);
+    let x1 = (x + i64::from(width)).min(i64::from(image.width()));
+    let y1 = (y + i64::from(height)).min(i64::from(image.height()));
+
+    for py in y0..y1 {
+        for px in x0..x1 {
+            image.put_pixel(px as u32, py as u32, color);
+        }
+    }
+}
+
+/// Draw a straight line of square dots, clipped to the image bounds
+pub fn draw_line(
+    image: &mut RgbImage,
+    from: (i64, i64),
+    to: (i64, i64),
+    thickness: u32,
+    color: Rgb<u8>,
+) {
+    let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+    let steps = dx.abs().max(dy.abs()).max(1);
+    let offset = i64::from(thickness / 2);
+
+    for step in 0..=steps {
+        let x = from.0 + dx * step / steps;
+        let y = from.1 + dy * step / steps;
+        fill_rect(image, x - offset, y - offset, thickness, thickness, color);
+    }
+}
+
+/// Patient direction letters for a vector, strongest component first (e.g. "LA")
+#[must_use]
+pub fn direction_label(direction: [f64; 3]) -> String {
+    const LETTERS: [(char, char); 3] = [('L', 'R'), ('P', 'A'), ('H', 'F')];
+
+    let mut components: Vec<(f64, char)> = direction
+        .iter()
+        .zip(LETTERS)
+        .map(|(&v, (positive, negative))| (v.abs(), if v >= 0.0 { positive } else { negative }))
+        // Ignore minor components of slightly oblique slices
+        .filter(|&(magnitude, _)| magnitude > 0.25)
+        .collect();
+
+    components.sort_by(|a, b| b.0.total_cmp(&a.0));
+    components.into_iter().map(|(_, letter)| letter).collect()
+}
+
+fn draw_orientation_markers(image: &mut RgbImage, right: [f64; 3], down: [f64; 3], scale: u32) {
+    let (width, height) = (i64::from(image.width()), i64::from(image.height()));
+    let margin = i64::from(2 * scale);
+    let text_h = i64::from(text_height(scale));
+    let opposite = |v: [f64; 3]| v.map(|c| -c);
+
+    let markers = [
+        (direction_label(right), Edge::Right),
+        (direction_label(opposite(right)), Edge::Left),
+        (direction_label(down), Edge::Bottom),
+        (direction_label(opposite(down)), Edge::Top),
+    ];
+
+    for (label, edge) in markers {
+        let text_w = i64::from(text_width(&label, scale));
+
+        let (x, y) = match edge {
+            Edge::Left => (margin, (height - text_h) / 2),
+            Edge::Right => (width - margin - text_w, (height - text_h) / 2),
+            Edge::Top => ((width - text_w) / 2, margin),
+            Edge::Bottom => ((width - text_w) / 2, height - margin - text_h),
+        };
+
+        draw_text(image, x, y, &label, scale, ANNOTATION_COLOR);
+    }
+}
+
+enum Edge {
+    Left,
+    Right,
+    Top,
+    Bottom,
+}
+
+/// Draw a bar of a round length (at most a quarter of the image width) in the bottom left
+fn draw_scale_bar(image: &mut RgbImage, mm_per_pixel: f64, scale: u32) {
+    let width_mm = f64::from(image.width()) * mm_per_pixel;
+
+    let Some(length_mm) = SCALE_BAR_LENGTHS_MM
+        .iter()
+        .rev()
+        .copied()
+        .find(|&length| length <= width_mm / 4.0)
+    else {
+        return;
+    };
+
+    let length = (length_mm / mm_per_pixel).round() as u32;
+    let thickness = scale.max(1) * 2;
+    let tick = thickness * 3;
+    let margin = i64::from(2 * scale);
+
+    let x = margin;
+    let y = i64::from(image.height()) - margin - i64::from(thickness);
+    let shadow = i64::from(scale);
+
+    for (offset, color) in [(shadow, SHADOW_COLOR), (0, ANNOTATION_COLOR)] {
+        let (x, y) = (x + offset, y + offset);
+        fill_rect(image, x, y, length, thickness, color);
+
+        // End ticks rising above the bar
+        let tick_y = y + i64::from(thickness) - i64::from(tick);
+        fill_rect(image, x, tick_y, thickness, tick, color);
+        fill_rect(
+            image,
+            x + i64::from(length) - i64::from(thickness),
+            tick_y,
+            thickness,
+            tick,
+            color,
+        );
+    }
+
+    let label = format!("{length_mm} mm");
+    let label_y = y + i64::from(thickness)
+        - i64::from(tick)
+        - i64::from(scale * 2)
+        - i64::from(text_height(scale));
+    draw_text(image, x, label_y, &label, scale, ANNOTATION_COLOR);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_direction_label() {
+        assert_eq!(direction_label([1.0, 0.0, 0.0]), "L");
+        assert_eq!(direction_label([0.0, -1.0, 0.0]), "A");
+        assert_eq!(direction_label([0.0, 0.0, -1.0]), "F");
+        assert_eq!(direction_label([-0.8, 0.6, 0.1]), "RP");
+    }
+
+    #[test]
+    fn test_text_is_clipped() {
+        let mut image = RgbImage::new(8, 8);
+        draw_text(&mut image, -3, 4, "LR", 2, ANNOTATION_COLOR);
+        assert_eq!(text_width("LR", 2), 22);
+    }
+}
@@ -0,0 +1,183 @@
+use super::annotate::{draw_text, fill_rect, text_height, text_scale};
+use super::font::GLYPH_ADVANCE;
+use crate::datetime::{DateStyle, DicomDate};
+use crate::dicom::DicomMetadata;
+use image::{DynamicImage, Rgb, RgbImage, imageops};
+
+const CAPTION_COLOR: Rgb<u8> = Rgb([230, 230, 230]);
+const BANNER_COLOR: Rgb<u8> = Rgb([0, 0, 0]);
+
+/// Where the caption is drawn
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptionPlacement {
+    /// In a banner added above the image
+    Above,
+    /// Over the top-left corner of the image
+    Overlay,
+}
+
+/// Caption lines for a frame: patient, study/series, frame position, and a
+/// warning for lossy compressed images
+///
+/// Missing fields are left out; a file without any of them gets no caption.
+#[must_use]
+pub fn caption_lines(metadata: &DicomMetadata, frame_index: u32) -> Vec<String> {
+    let mut lines = Vec::new();
+
+    let name = metadata.patient_name().map(format_person_name);
+    let patient = match (name, metadata.patient_id()) {
+        (Some(name), Some(id)) => Some(format!("{name} ({id})")),
+        (Some(name), None) => Some(name),
+        (None, Some(id)) => Some(id.to_string()),
+        (None, None) => None,
+    };
+    lines.extend(patient);
+
+    let study = [
+        metadata.study_date().map(format_date),
+        metadata.series_description().map(str::to_string),
+    ]
+    .into_iter()
+    .flatten()
+    .filter(|s| !s.is_empty())
+    .collect::<Vec<_>>();
+    if !study.is_empty() {
+        lines.push(study.join("  "));
+    }
+
+    if metadata.number_of_frames > 1 {
+        lines.push(format!(
+            "Frame {}/{}",
+            frame_index + 1,
+            metadata.number_of_frames
+        ));
+    }
+
+    if let Some(lossy) = &metadata.lossy_compression {
+        let details = lossy.details();
+        lines.push(if details.is_empty() {
+            "LOSSY".to_string()
+        } else {
+            format!("LOSSY {details}")
+        });
+    }
+
+    lines
+}
+
+/// Burn caption lines into the image
+#[must_use]
+pub fn add_caption(
+    image: DynamicImage,
+    lines: &[String],
+    placement: CaptionPlacement,
+) -> DynamicImage {
+    if lines.is_empty() {
+        return image;
+    }
+
+    let image = super::quantize(image).into_rgb8();
+    let scale = text_scale(&image);
+    let padding = 2 * scale;
+    let line_height = text_height(scale) + 2 * scale;
+    let banner_height = lines.len() as u32 * line_height + 2 * padding - 2 * scale;
+
+    // Cut lines that would run off the right edge
+    let max_chars = (image.width().saturating_sub(2 * padding) + scale) / (GLYPH_ADVANCE * scale);
+    let fitted = lines
+        .iter()
+        .map(|line| line.chars().take(max_chars as usize).collect::<String>());
+
+    let mut canvas = match placement {
+        CaptionPlacement::Above => {
+            let mut canvas = RgbImage::new(image.width(), image.height() + banner_height);
+            fill_rect(
+                &mut canvas,
+                0,
+                0,
+                image.width(),
+                banner_height,
+                BANNER_COLOR,
+            );
+            imageops::replace(&mut canvas, &image, 0, i64::from(banner_height));
+            canvas
+        }
+        CaptionPlacement::Overlay => image,
+    };
+
+    for (index, line) in fitted.enumerate() {
+        let y = i64::from(padding + index as u32 * line_height);
+        draw_text(
+            &mut canvas,
+            i64::from(padding),
+            y,
+            &line,
+            scale,
+            CAPTION_COLOR,
+        );
+    }
+
+    DynamicImage::ImageRgb8(canvas)
+}
+
+/// Person name as "Family, Given Middle" from DICOM PN components
+fn format_person_name(name: &str) -> String {
+    let mut components = name.split('^').map(str::trim);
+    let family = components.next().unwrap_or_default();
+    let given = components
+        .take(2)
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    match (family.is_empty(), given.is_empty()) {
+        (false, false) => format!("{family}, {given}"),
+        (false, true) => family.to_string(),
+        _ => given,
+    }
+}
+
+/// DICOM DA (YYYYMMDD) as YYYY-MM-DD, other values unchanged
+fn format_date(date: &str) -> String {
+    DicomDate::parse(date).map_or_else(|| date.trim().to_string(), |d| d.format(DateStyle::Iso))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::LossyCompression;
+
+    #[test]
+    fn test_format_person_name() {
+        assert_eq!(format_person_name("Doe^John"), "Doe, John");
+        assert_eq!(format_person_name("Doe^John^Q^Dr"), "Doe, John Q");
+        assert_eq!(format_person_name("Anonymous"), "Anonymous");
+    }
+
+    #[test]
+    fn test_format_date() {
+        assert_eq!(format_date("20240315"), "2024-03-15");
+        assert_eq!(format_date("2024"), "2024");
+    }
+
+    #[test]
+    fn test_caption_warns_of_lossy_compression() {
+        let mut metadata = DicomMetadata::test_grayscale16(1, 1, &[0]);
+        assert!(caption_lines(&metadata, 0).is_empty());
+
+        metadata.lossy_compression = Some(LossyCompression {
+            ratios: vec![12.5],
+            methods: vec!["ISO_10918_1".to_string()],
+        });
+        assert_eq!(caption_lines(&metadata, 0), ["LOSSY 12.5:1, JPEG"]);
+    }
+
+    #[test]
+    fn test_caption_above_adds_banner() {
+        let image = DynamicImage::ImageRgb8(RgbImage::new(100, 50));
+        let captioned = add_caption(image, &["Doe, John".to_string()], CaptionPlacement::Above);
+
+        assert_eq!(captioned.width(), 100);
+        assert!(captioned.height() > 50);
+    }
+}
@@ -0,0 +1,37 @@
+use super::frame::first_frame_interleaved_8bit;
+use crate::dicom::DicomMetadata;
+use anyhow::{Context, Result};
+use image::{DynamicImage, ImageBuffer, RgbImage};
+
+/// Convert CMYK DICOM pixel data to a `DynamicImage`
+///
+/// Uses the uncalibrated multiplicative conversion; the data carries no
+/// color profile to do better.
+///
+/// # Errors
+///
+/// Returns an error if the pixel data is not 8-bit or has an invalid size
+pub fn convert_cmyk(metadata: &DicomMetadata) -> Result<DynamicImage> {
+    let pixel_data = first_frame_interleaved_8bit(metadata, 4)?;
+
+    let rgb_pixels: Vec<u8> = pixel_data
+        .chunks_exact(4)
+        .flat_map(|cmyk| cmyk_to_rgb(cmyk[0], cmyk[1], cmyk[2], cmyk[3]))
+        .collect();
+
+    let rgb_image: RgbImage = ImageBuffer::from_raw(
+        u32::from(metadata.cols()),
+        u32::from(metadata.rows()),
+        rgb_pixels,
+    )
+    .context("Failed to create RGB image buffer from CMYK")?;
+
+    Ok(DynamicImage::ImageRgb8(rgb_image))
+}
+
+fn cmyk_to_rgb(c: u8, m: u8, y: u8, k: u8) -> [u8; 3] {
+    let white = 255 - u16::from(k);
+    let channel = |ink: u8| ((255 - u16::from(ink)) * white / 255) as u8;
+
+    [channel(c), channel(m), channel(y)]
+}
@@ -0,0 +1,79 @@
+use image::{DynamicImage, Rgb, RgbImage};
+
+/// Color lookup table for grayscale images
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Colormap {
+    /// Black through red and yellow to white
+    Hot,
+    /// Grayscale with a blue tint, common for bone scans
+    Bone,
+    /// Dark blue through cyan and yellow to dark red
+    Jet,
+    /// Perceptually uniform purple through green to yellow
+    Viridis,
+}
+
+impl Colormap {
+    /// Evenly spaced colors between which levels are interpolated
+    fn stops(self) -> &'static [[u8; 3]] {
+        match self {
+            Self::Hot => &[[0, 0, 0], [255, 0, 0], [255, 255, 0], [255, 255, 255]],
+            Self::Bone => &[[0, 0, 0], [84, 84, 116], [167, 199, 199], [255, 255, 255]],
+            Self::Jet => &[
+                [0, 0, 128],
+                [0, 0, 255],
+                [0, 255, 255],
+                [255, 255, 0],
+                [255, 0, 0],
+                [128, 0, 0],
+            ],
+            Self::Viridis => &[
+                [68, 1, 84],
+                [59, 82, 139],
+                [33, 145, 140],
+                [94, 201, 98],
+                [253, 231, 37],
+            ],
+        }
+    }
+
+    /// Color of a gray level
+    #[must_use]
+    pub fn color(self, level: u8) -> Rgb<u8> {
+        let stops = self.stops();
+        let position = f32::from(level) / 255.0 * (stops.len() - 1) as f32;
+        let index = (position as usize).min(stops.len() - 2);
+        let t = position - index as f32;
+
+        let (from, to) = (stops[index], stops[index + 1]);
+        Rgb([0, 1, 2]
+            .map(|c| (f32::from(to[c]) - f32::from(from[c])).mul_add(t, f32::from(from[c])) as u8))
+    }
+
+    /// Map the luminance of every pixel through the colormap
+    #[must_use]
+    pub fn apply(self, image: &DynamicImage) -> DynamicImage {
+        let gray = super::quantize(image.clone()).into_luma8();
+        let lut: Vec<Rgb<u8>> = (0..=255).map(|level| self.color(level)).collect();
+
+        let colored = RgbImage::from_fn(gray.width(), gray.height(), |x, y| {
+            lut[usize::from(gray.get_pixel(x, y)[0])]
+        });
+        DynamicImage::ImageRgb8(colored)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_colormap_endpoints_and_interpolation() {
+        assert_eq!(Colormap::Hot.color(0), Rgb([0, 0, 0]));
+        assert_eq!(Colormap::Hot.color(255), Rgb([255, 255, 255]));
+        assert_eq!(Colormap::Viridis.color(255), Rgb([253, 231, 37]));
+
+        // A third of the way is the first stop after black
+        assert_eq!(Colormap::Hot.color(85), Rgb([255, 0, 0]));
+    }
+}
@@ -0,0 +1,91 @@
+use image::{DynamicImage, ImageBuffer, Pixel};
+
+/// Convolution filter applied to displayed levels
+///
+/// Terminals show images at a few pixels per cell, which softens fine
+/// detail; sharpening or edge detection brings some of it back.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Filter {
+    /// Boost the difference between each pixel and its neighbors
+    Sharpen,
+    /// Gaussian blur, which hides noise
+    Smooth,
+    /// Laplacian edge detection: edges bright, flat areas black
+    Edge,
+}
+
+impl Filter {
+    /// 3x3 weights, row by row, and the divisor of their weighted sum
+    fn kernel(self) -> ([i32; 9], i32) {
+        match self {
+            Self::Sharpen => ([0, -1, 0, -1, 5, -1, 0, -1, 0], 1),
+            Self::Smooth => ([1, 2, 1, 2, 4, 2, 1, 2, 1], 16),
+            Self::Edge => ([-1, -1, -1, -1, 8, -1, -1, -1, -1], 1),
+        }
+    }
+
+    /// Convolve every channel of the image, reduced to 8 bits
+    ///
+    /// Pixels beyond the edges repeat the nearest edge pixel.
+    #[must_use]
+    pub fn apply(self, image: DynamicImage) -> DynamicImage {
+        match super::quantize(image) {
+            DynamicImage::ImageLuma8(gray) => DynamicImage::ImageLuma8(self.convolve(&gray)),
+            image => DynamicImage::ImageRgb8(self.convolve(&image.into_rgb8())),
+        }
+    }
+
+    fn convolve<P>(self, image: &ImageBuffer<P, Vec<u8>>) -> ImageBuffer<P, Vec<u8>>
+    where
+        P: Pixel<Subpixel = u8>,
+    {
+        let (weights, divisor) = self.kernel();
+        let (width, height) = image.dimensions();
+        let channels = usize::from(P::CHANNEL_COUNT);
+
+        ImageBuffer::from_fn(width, height, |x, y| {
+            let mut sums = [0_i32; 4];
+            for (i, weight) in (0_u32..).zip(weights) {
+                let nx = (x + i % 3).saturating_sub(1).min(width - 1);
+                let ny = (y + i / 3).saturating_sub(1).min(height - 1);
+                for (sum, &level) in sums.iter_mut().zip(image.get_pixel(nx, ny).channels()) {
+                    *sum += weight * i32::from(level);
+                }
+            }
+
+            let levels = sums.map(|sum| {
+                (f64::from(sum) / f64::from(divisor))
+                    .round()
+                    .clamp(0.0, 255.0) as u8
+            });
+            *P::from_slice(&levels[..channels])
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::GrayImage;
+
+    #[test]
+    fn test_filters_on_a_bright_dot() {
+        let mut gray = GrayImage::from_pixel(3, 3, image::Luma([100]));
+        gray.put_pixel(1, 1, image::Luma([140]));
+        let levels = |filter: Filter| {
+            filter
+                .apply(DynamicImage::ImageLuma8(gray.clone()))
+                .into_luma8()
+                .into_raw()
+        };
+
+        // The dot gains four times its excess, clipped, and its side
+        // neighbors lose it
+        assert_eq!(
+            levels(Filter::Sharpen),
+            vec![100, 60, 100, 60, 255, 60, 100, 60, 100]
+        );
+        assert_eq!(levels(Filter::Smooth)[4], 110);
+        assert_eq!(levels(Filter::Edge), vec![0, 0, 0, 0, 255, 0, 0, 0, 0]);
+    }
+}
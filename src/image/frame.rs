@@ -0,0 +1,305 @@
+use crate::dicom::DicomMetadata;
+use anyhow::{Result, bail};
+use image::{DynamicImage, RgbImage};
+
+/// Sample values of a decoded frame at their original bit depth
+///
+/// Signed samples (Pixel Representation 1) are sign-extended from Bits
+/// Stored; signed 8-bit samples are widened to `I16`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Samples {
+    U8(Vec<u8>),
+    U16(Vec<u16>),
+    U32(Vec<u32>),
+    I16(Vec<i16>),
+    I32(Vec<i32>),
+}
+
+impl Samples {
+    #[inline]
+    #[must_use]
+    pub fn len(&self) -> usize {
+        match self {
+            Self::U8(values) => values.len(),
+            Self::U16(values) => values.len(),
+            Self::U32(values) => values.len(),
+            Self::I16(values) => values.len(),
+            Self::I32(values) => values.len(),
+        }
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Bits per sample of the underlying storage
+    #[inline]
+    #[must_use]
+    pub fn bits(&self) -> u16 {
+        match self {
+            Self::U8(_) => 8,
+            Self::U16(_) | Self::I16(_) => 16,
+            Self::U32(_) | Self::I32(_) => 32,
+        }
+    }
+
+    /// Every sample as a number, signed samples with their sign
+    #[must_use]
+    pub fn values(&self) -> Vec<f64> {
+        match self {
+            Self::U8(values) => values.iter().map(|&v| f64::from(v)).collect(),
+            Self::U16(values) => values.iter().map(|&v| f64::from(v)).collect(),
+            Self::U32(values) => values.iter().map(|&v| f64::from(v)).collect(),
+            Self::I16(values) => values.iter().map(|&v| f64::from(v)).collect(),
+            Self::I32(values) => values.iter().map(|&v| f64::from(v)).collect(),
+        }
+    }
+}
+
+/// A single decoded frame, before any display-oriented conversion
+///
+/// This is the common input of both the display rendering stage (which
+/// renders 16-bit grayscale or 8-bit RGB) and the data export stage (which keeps the
+/// original bit depth).
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodedFrame {
+    pub width: u32,
+    pub height: u32,
+    pub samples_per_pixel: u16,
+    pub samples: Samples,
+}
+
+impl DecodedFrame {
+    #[inline]
+    #[must_use]
+    pub fn pixel_count(&self) -> usize {
+        self.width as usize * self.height as usize
+    }
+
+    /// First sample of the pixel at (x, y), or `None` outside the frame
+    #[must_use]
+    pub fn sample(&self, x: u32, y: u32) -> Option<f64> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+
+        let index =
+            (y as usize * self.width as usize + x as usize) * usize::from(self.samples_per_pixel);
+
+        match &self.samples {
+            Samples::U8(values) => values.get(index).map(|&v| f64::from(v)),
+            Samples::U16(values) => values.get(index).map(|&v| f64::from(v)),
+            Samples::U32(values) => values.get(index).map(|&v| f64::from(v)),
+            Samples::I16(values) => values.get(index).map(|&v| f64::from(v)),
+            Samples::I32(values) => values.get(index).map(|&v| f64::from(v)),
+        }
+    }
+
+    /// All samples of the pixel at (x, y), or `None` outside the frame
+    #[must_use]
+    pub fn pixel(&self, x: u32, y: u32) -> Option<Vec<f64>> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+
+        let samples = usize::from(self.samples_per_pixel);
+        let start = (y as usize * self.width as usize + x as usize) * samples;
+        let range = start..start + samples;
+
+        match &self.samples {
+            Samples::U8(values) => Some(values.get(range)?.iter().map(|&v| v.into()).collect()),
+            Samples::U16(values) => Some(values.get(range)?.iter().map(|&v| v.into()).collect()),
+            Samples::U32(values) => Some(values.get(range)?.iter().map(|&v| v.into()).collect()),
+            Samples::I16(values) => Some(values.get(range)?.iter().map(|&v| v.into()).collect()),
+            Samples::I32(values) => Some(values.get(range)?.iter().map(|&v| v.into()).collect()),
+        }
+    }
+
+    /// Sample values widened to u16 for the grayscale rendering path
+    ///
+    /// Signed 16-bit samples are shifted up by [`Self::u16_offset`]. 32-bit
+    /// samples are normalized into the u16 range, as the display only needs
+    /// relative values.
+    #[must_use]
+    pub fn to_u16(&self) -> Vec<u16> {
+        match &self.samples {
+            Samples::U8(values) => values.iter().map(|&v| u16::from(v)).collect(),
+            Samples::U16(values) => values.clone(),
+            Samples::I16(values) => values.iter().map(|&v| (v as u16) ^ 0x8000).collect(),
+            Samples::U32(values) => normalize_to_u16(values.iter().map(|&v| v as f32)),
+            Samples::I32(values) => normalize_to_u16(values.iter().map(|&v| v as f32)),
+        }
+    }
+
+    /// Stored value of the samples [`Self::to_u16`] gives as 0, for 8- and
+    /// 16-bit samples
+    #[must_use]
+    pub fn u16_offset(&self) -> i32 {
+        match self.samples {
+            Samples::I16(_) => -32768,
+            _ => 0,
+        }
+    }
+}
+
+fn normalize_to_u16(values: impl Iterator<Item = f32> + Clone) -> Vec<u16> {
+    let (min, max) = values
+        .clone()
+        .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), v| {
+            (min.min(v), max.max(v))
+        });
+    let range = if max > min { max - min } else { 1.0_f32 };
+
+    values
+        .map(|v| ((v - min) / range * 65535.0_f32) as u16)
+        .collect()
+}
+
+impl From<RgbImage> for DecodedFrame {
+    fn from(image: RgbImage) -> Self {
+        Self {
+            width: image.width(),
+            height: image.height(),
+            samples_per_pixel: 3,
+            samples: Samples::U8(image.into_raw()),
+        }
+    }
+}
+
+/// Keeps 8 and 16-bit grayscale and 16-bit color at their depth; anything
+/// else is converted to 8-bit RGB. Alpha is dropped.
+impl From<DynamicImage> for DecodedFrame {
+    fn from(image: DynamicImage) -> Self {
+        let (width, height) = (image.width(), image.height());
+        let (samples_per_pixel, samples) = match image {
+            DynamicImage::ImageLuma8(gray) => (1, Samples::U8(gray.into_raw())),
+            DynamicImage::ImageLuma16(gray) => (1, Samples::U16(gray.into_raw())),
+            DynamicImage::ImageRgb16(_) | DynamicImage::ImageRgba16(_) => {
+                (3, Samples::U16(image.into_rgb16().into_raw()))
+            }
+            image => return image.into_rgb8().into(),
+        };
+
+        Self {
+            width,
+            height,
+            samples_per_pixel,
+            samples,
+        }
+    }
+}
+
+/// Decode the first frame of single-sample (grayscale) pixel data into typed samples
+///
+/// # Errors
+///
+/// Returns an error if the bit depth is unsupported or the pixel data is too
+/// short for a full frame
+pub fn decode_grayscale_frame(metadata: &DicomMetadata) -> Result<DecodedFrame> {
+    let pixel_data = metadata.pixel_data();
+    let pixel_count = metadata.dimensions.pixel_count();
+    let bit_depth = metadata.bit_depth;
+
+    let samples = match bit_depth.allocated {
+        8 if bit_depth.signed => Samples::I16(
+            first_frame(pixel_data, pixel_count, 1)?
+                .iter()
+                .map(|&v| sign_extend(v.into(), bit_depth.stored.min(8)) as i16)
+                .collect(),
+        ),
+        8 => Samples::U8(first_frame(pixel_data, pixel_count, 1)?.to_vec()),
+        16 => {
+            if !pixel_data.len().is_multiple_of(2) {
+                bail!("Invalid 16-bit pixel data length");
+            }
+
+            let values = first_frame(pixel_data, pixel_count, 2)?
+                .chunks_exact(2)
+                .map(|chunk| u16::from_le_bytes([chunk[0], chunk[1]]));
+            if bit_depth.signed {
+                let bits = bit_depth.stored.min(16);
+                Samples::I16(values.map(|v| sign_extend(v.into(), bits) as i16).collect())
+            } else {
+                Samples::U16(values.collect())
+            }
+        }
+        32 => {
+            if !pixel_data.len().is_multiple_of(4) {
+                bail!("Invalid 32-bit pixel data length");
+            }
+
+            let values = first_frame(pixel_data, pixel_count, 4)?
+                .chunks_exact(4)
+                .map(|chunk| u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]));
+            if bit_depth.signed {
+                let bits = bit_depth.stored.min(32);
+                Samples::I32(values.map(|v| sign_extend(v, bits)).collect())
+            } else {
+                Samples::U32(values.collect())
+            }
+        }
+        other => bail!("Unsupported bits allocated for grayscale: {other}"),
+    };
+
+    Ok(DecodedFrame {
+        width: u32::from(metadata.cols()),
+        height: u32::from(metadata.rows()),
+        samples_per_pixel: 1,
+        samples,
+    })
+}
+
+/// Two's complement value of the low `bits` bits of `value`
+fn sign_extend(value: u32, bits: u16) -> i32 {
+    let shift = 32 - u32::from(bits.clamp(1, 32));
+    ((value << shift) as i32) >> shift
+}
+
+fn first_frame(pixel_data: &[u8], pixel_count: usize, bytes_per_sample: usize) -> Result<&[u8]> {
+    let expected_size = pixel_count * bytes_per_sample;
+
+    pixel_data.get(..expected_size).ok_or_else(|| {
+        anyhow::anyhow!(
+            "Invalid grayscale pixel data size: expected {expected_size} bytes for first frame, got {}",
+            pixel_data.len()
+        )
+    })
+}
+
+/// First frame of 8-bit multi-sample pixel data, with samples interleaved
+///
+/// # Errors
+///
+/// Returns an error if the data is not 8-bit or too short for a full frame
+pub(super) fn first_frame_interleaved_8bit(
+    metadata: &DicomMetadata,
+    samples_per_pixel: usize,
+) -> Result<Vec<u8>> {
+    if metadata.bits_allocated() != 8 {
+        bail!(
+            "Unsupported bits allocated for {}: {} (expected 8)",
+            metadata.photometric_interpretation,
+            metadata.bits_allocated()
+        );
+    }
+
+    let pixel_count = metadata.dimensions.pixel_count();
+    let expected_size = pixel_count * samples_per_pixel;
+    let data = metadata.pixel_data().get(..expected_size).ok_or_else(|| {
+        anyhow::anyhow!(
+            "Invalid {} pixel data size: expected {expected_size} bytes for first frame, got {}",
+            metadata.photometric_interpretation,
+            metadata.pixel_data().len()
+        )
+    })?;
+
+    match metadata.planar_configuration {
+        None | Some(0) => Ok(data.to_vec()),
+        Some(1) => Ok((0..expected_size)
+            .map(|i| data[(i % samples_per_pixel) * pixel_count + i / samples_per_pixel])
+            .collect()),
+        Some(other) => bail!("Unsupported planar configuration: {other}"),
+    }
+}
@@ -1,7 +1,11 @@
-use super::normalization::find_min_max;
+use super::frame::{DecodedFrame, decode_grayscale_frame};
+use super::mammography::{is_mammogram, tissue_range};
+use super::statistics::DisplayWindow;
 use crate::dicom::DicomMetadata;
+use crate::explain::DecisionLog;
+use crate::types::VoiLutFunction;
 use anyhow::{Context, Result};
-use image::{DynamicImage, ImageBuffer, RgbImage};
+use image::{DynamicImage, ImageBuffer, Luma};
 
 // f32: better SIMD (8 floats/AVX2 reg vs 4)
 /// Convert grayscale DICOM pixel data to a `DynamicImage`
@@ -10,17 +14,155 @@ use image::{DynamicImage, ImageBuffer, RgbImage};
 ///
 /// Returns an error if pixel data extraction or conversion fails
 pub fn convert_grayscale(metadata: &DicomMetadata) -> Result<DynamicImage> {
-    let pixel_data = extract_grayscale_pixels(metadata)?;
+    let frame = decode_grayscale_frame(metadata)?;
+    render_grayscale(&frame, metadata)
+}
+
+/// Render a decoded grayscale frame to 16-bit grayscale for display
+///
+/// The displayed range is spread over all 16-bit levels; [`super::quantize`]
+/// reduces the result to 8 bits at the final display step.
+///
+/// # Errors
+///
+/// Returns an error if the image buffer cannot be created
+pub fn render_grayscale(frame: &DecodedFrame, metadata: &DicomMetadata) -> Result<DynamicImage> {
+    render_grayscale_windowed(frame, metadata, None)
+}
+
+/// Render a decoded grayscale frame with an explicit display window
+///
+/// `window` is in rescaled units; values outside it are clipped. Without a
+/// window, a SIGMOID window declared in the file is applied, or else the
+/// declared or actual range of values is shown, or the range of the breast
+/// tissue in a mammogram. Windows do not apply to 32-bit samples, which are
+/// renormalized for display.
+///
+/// # Errors
+///
+/// Returns an error if the image buffer cannot be created
+pub fn render_grayscale_windowed(
+    frame: &DecodedFrame,
+    metadata: &DicomMetadata,
+    window: Option<DisplayWindow>,
+) -> Result<DynamicImage> {
+    render_grayscale_explained(frame, metadata, window, &mut DecisionLog::default())
+}
+
+/// [`render_grayscale_windowed`], noting rescaling, normalization and
+/// inversion in `log`
+///
+/// # Errors
+///
+/// Returns an error if the image buffer cannot be created
+pub fn render_grayscale_explained(
+    frame: &DecodedFrame,
+    metadata: &DicomMetadata,
+    window: Option<DisplayWindow>,
+    log: &mut DecisionLog,
+) -> Result<DynamicImage> {
+    let is_32_bit = frame.samples.bits() == 32;
+    if is_32_bit {
+        log.record("Samples", "32-bit, renormalized to 16 bits");
+    }
+    let pixel_data = frame.to_u16();
 
     let slope = metadata.rescale_slope() as f32;
     let intercept = metadata.rescale_intercept() as f32;
 
-    let (min_val, max_val) = pixel_data
-        .iter()
-        .map(|&pixel| f32::from(pixel).mul_add(slope, intercept))
-        .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), val| {
-            (min.min(val), max.max(val))
-        });
+    // A Modality LUT replaces slope and intercept. It cannot apply to 32-bit
+    // samples either, as to_u16() no longer gives their stored values.
+    let modality_lut = metadata.modality_lut.as_ref().filter(|_| !is_32_bit);
+    let rescale = |stored: i32| match modality_lut {
+        Some(lut) => lut.apply(stored) as f32,
+        None => (stored as f32).mul_add(slope, intercept),
+    };
+    // to_u16() moves signed samples up to start at 0
+    let offset = frame.u16_offset();
+    let rescale_pixel = |pixel: u16| rescale(i32::from(pixel) + offset);
+    log.record(
+        "Rescale",
+        match modality_lut {
+            Some(_) => "Modality LUT".to_string(),
+            None if is_32_bit => "none, as stored values are renormalized".to_string(),
+            None => format!("slope {slope}, intercept {intercept}"),
+        },
+    );
+
+    if window.is_some() && is_32_bit {
+        log.record("Window", "not applied to 32-bit samples");
+    }
+    let window = window.filter(|_| !is_32_bit);
+    // Without a window of its own, a sigmoid image is shown as the file says
+    let declared_sigmoid = window
+        .is_none()
+        .then(|| DisplayWindow::declared_sigmoid(metadata))
+        .flatten();
+    let window = window.or(declared_sigmoid);
+
+    // A declared stored value range saves a pass over the pixels. 32-bit
+    // samples are renormalized by to_u16(), so the declared range does not apply.
+    let declared_range = metadata.pixel_value_range.filter(|_| !is_32_bit);
+
+    // The air around the breast would take most of the range of a mammogram
+    let tissue = if window.is_none() && is_mammogram(metadata) {
+        let values: Vec<f32> = pixel_data
+            .iter()
+            .map(|&pixel| rescale_pixel(pixel))
+            .collect();
+        tissue_range(
+            &values,
+            !metadata.photometric_interpretation.should_invert(),
+        )
+    } else {
+        None
+    };
+
+    let (min_val, max_val) = match (window, declared_range) {
+        (Some(window), _) => (window.lower() as f32, window.upper() as f32),
+        (None, _) if let Some(range) = tissue => range,
+        (None, Some(range)) => match modality_lut {
+            Some(lut) => {
+                let (min, max) = lut.output_range(range.smallest, range.largest);
+                (min as f32, max as f32)
+            }
+            None => {
+                let (a, b) = (rescale(range.smallest), rescale(range.largest));
+                (a.min(b), a.max(b))
+            }
+        },
+        (None, None) => pixel_data
+            .iter()
+            .map(|&pixel| rescale_pixel(pixel))
+            .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), val| {
+                (min.min(val), max.max(val))
+            }),
+    };
+
+    log.record(
+        "Normalization",
+        match (window, declared_range) {
+            (Some(window), _) if window.function == VoiLutFunction::Sigmoid => format!(
+                "sigmoid window C {} W {}{}",
+                window.center,
+                window.width,
+                if declared_sigmoid.is_some() {
+                    " (VOI LUT Function)"
+                } else {
+                    ""
+                }
+            ),
+            (Some(window), _) => format!(
+                "window C {} W {}: {min_val} to {max_val}, clipped outside",
+                window.center, window.width
+            ),
+            (None, _) if tissue.is_some() => {
+                format!("breast tissue {min_val} to {max_val}, air and markers clipped (mammogram)")
+            }
+            (None, Some(_)) => format!("declared value range {min_val} to {max_val}"),
+            (None, None) => format!("actual value range {min_val} to {max_val}"),
+        },
+    );
 
     let range = if max_val > min_val {
         max_val - min_val
@@ -29,75 +171,71 @@ pub fn convert_grayscale(metadata: &DicomMetadata) -> Result<DynamicImage> {
     };
 
     let should_invert = metadata.photometric_interpretation.should_invert();
+    if should_invert {
+        log.record("Inversion", "MONOCHROME1, low values shown white");
+    }
 
-    let rgb_pixels: Vec<u8> = pixel_data
+    let sigmoid = window.filter(|window| window.function == VoiLutFunction::Sigmoid);
+    let levels: Vec<u16> = pixel_data
         .iter()
-        .flat_map(|&pixel| {
-            let rescaled = f32::from(pixel).mul_add(slope, intercept);
-
-            let normalized = (rescaled - min_val) / range;
-            let gray = (normalized * 255.0_f32) as u8;
+        .map(|&pixel| {
+            let rescaled = rescale_pixel(pixel);
 
-            let gray = if should_invert {
-                255u8.saturating_sub(gray)
-            } else {
-                gray
+            let normalized = match sigmoid {
+                Some(window) => window.normalize(f64::from(rescaled)) as f32,
+                None => ((rescaled - min_val) / range).clamp(0.0, 1.0),
             };
+            let level = to_level(normalized * 255.0_f32);
 
-            [gray, gray, gray]
+            if should_invert {
+                invert_level(level)
+            } else {
+                level
+            }
         })
         .collect();
 
-    let rgb_image: RgbImage = ImageBuffer::from_raw(
-        u32::from(metadata.cols()),
-        u32::from(metadata.rows()),
-        rgb_pixels,
-    )
-    .context("Failed to create RGB image buffer")?;
+    let gray_image: ImageBuffer<Luma<u16>, _> =
+        ImageBuffer::from_raw(frame.width, frame.height, levels)
+            .context("Failed to create grayscale image buffer")?;
 
-    Ok(DynamicImage::ImageRgb8(rgb_image))
+    Ok(DynamicImage::ImageLuma16(gray_image))
 }
 
-fn extract_grayscale_pixels(metadata: &DicomMetadata) -> Result<Vec<u16>> {
-    let pixel_data = metadata.pixel_data();
+/// 16-bit level for a value on the 0-255 display scale
+///
+/// Computed in f64 so that [`super::quantize`] gives back exactly the
+/// integer part of `value`, the level shown before 16-bit output.
+#[inline]
+fn to_level(value: f32) -> u16 {
+    (f64::from(value) * 257.0) as u16
+}
 
-    match metadata.bits_allocated() {
-        8 => Ok(pixel_data.iter().map(|&b| u16::from(b)).collect()),
-        16 => {
-            if !pixel_data.len().is_multiple_of(2) {
-                anyhow::bail!("Invalid 16-bit pixel data length");
+/// The image with dark and bright swapped
+///
+/// 16-bit grayscale levels are inverted like MONOCHROME1 data, so that the
+/// 8-bit levels shown are exactly 255 minus the ones before.
+#[must_use]
+pub fn invert_polarity(image: DynamicImage) -> DynamicImage {
+    match image {
+        DynamicImage::ImageLuma16(mut gray) => {
+            for pixel in gray.pixels_mut() {
+                pixel.0[0] = invert_level(pixel.0[0]);
             }
-
-            Ok(pixel_data
-                .chunks_exact(2)
-                .map(|chunk| u16::from_le_bytes([chunk[0], chunk[1]]))
-                .collect())
+            DynamicImage::ImageLuma16(gray)
         }
-        32 => {
-            if !pixel_data.len().is_multiple_of(4) {
-                anyhow::bail!("Invalid 32-bit pixel data length");
-            }
-
-            let values: Vec<u32> = pixel_data
-                .chunks_exact(4)
-                .map(|chunk| u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
-                .collect();
-
-            let (min, max) = find_min_max(&values);
-            let range = if max > min { max - min } else { 1.0_f32 };
-
-            Ok(values
-                .iter()
-                .map(|&v| {
-                    let v_f32 = v as f32;
-                    let normalized = (v_f32 - min) / range;
-                    (normalized * 65535.0_f32) as u16
-                })
-                .collect())
+        mut image => {
+            image.invert();
+            image
         }
-        _ => anyhow::bail!(
-            "Unsupported bits allocated for grayscale: {}",
-            metadata.bits_allocated()
-        ),
     }
 }
+
+/// Inverted 16-bit level that quantizes to 255 minus the 8-bit level
+///
+/// The top 8-bit level holds a single 16-bit level (65535), so the levels
+/// quantizing to 0 all invert to it, as they would in 8 bits.
+#[inline]
+fn invert_level(level: u16) -> u16 {
+    u16::try_from((u32::from(u16::MAX) + 256).saturating_sub(u32::from(level))).unwrap_or(u16::MAX)
+}
@@ -0,0 +1,94 @@
+//! Grayscale Standard Display Function (DICOM PS3.14)
+//!
+//! The GSDF spaces gray levels by just-noticeable differences (JNDs) in
+//! luminance, so that equal steps in value look equally different. Levels
+//! are mapped to the luminances a calibrated display would show, then to the
+//! drive levels an uncalibrated sRGB display needs to show them.
+
+/// Luminance of a terminal's black, in cd/m²
+pub const GSDF_MIN_LUMINANCE: f32 = 0.5;
+
+/// Gamma of the uncalibrated display the levels are sent to
+const DISPLAY_GAMMA: f64 = 2.2;
+
+/// Luminance in cd/m² at JND index `j` (1 to 1023)
+#[must_use]
+pub fn luminance(j: f64) -> f64 {
+    const A: f64 = -1.301_187_7;
+    const B: f64 = -2.584_019_1e-2;
+    const C: f64 = 8.024_263_6e-2;
+    const D: f64 = -1.032_022_9e-1;
+    const E: f64 = 1.364_669_9e-1;
+    const F: f64 = 2.874_562e-2;
+    const G: f64 = -2.546_840_4e-2;
+    const H: f64 = -3.197_897_7e-3;
+    const K: f64 = 1.299_263_4e-4;
+    const M: f64 = 1.363_533_4e-3;
+
+    let x = j.ln();
+    let numerator = M.mul_add(x, G).mul_add(x, E).mul_add(x, C).mul_add(x, A);
+    let denominator = K
+        .mul_add(x, H)
+        .mul_add(x, F)
+        .mul_add(x, D)
+        .mul_add(x, B)
+        .mul_add(x, 1.0);
+    10_f64.powf(numerator / denominator)
+}
+
+/// JND index of a luminance in cd/m², the inverse of [`luminance`]
+#[must_use]
+pub fn jnd_index(luminance: f64) -> f64 {
+    const COEFFICIENTS: [f64; 9] = [
+        71.498_068,
+        94.593_053,
+        41.912_053,
+        9.824_700_4,
+        0.281_754_07,
+        -1.187_845_5,
+        -0.180_143_49,
+        0.147_108_99,
+        -0.017_046_845,
+    ];
+
+    let x = luminance.log10();
+    COEFFICIENTS
+        .iter()
+        .rev()
+        .fold(0.0, |sum, &coefficient| sum.mul_add(x, coefficient))
+}
+
+/// Drive level (0-1) that shows a normalized value (0-1) as the GSDF would
+/// on a display from [`GSDF_MIN_LUMINANCE`] to `max_luminance`
+#[must_use]
+pub fn display_level(value: f32, max_luminance: f32) -> f32 {
+    let (j_min, j_max) = (
+        jnd_index(f64::from(GSDF_MIN_LUMINANCE)),
+        jnd_index(f64::from(max_luminance)),
+    );
+    // The end points on the curve itself, as the fits are not exact inverses
+    let (min, max) = (luminance(j_min), luminance(j_max));
+
+    let target = luminance((j_max - j_min).mul_add(f64::from(value), j_min));
+    let relative = ((target - min) / (max - min)).clamp(0.0, 1.0);
+    relative.powf(1.0 / DISPLAY_GAMMA) as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gsdf_luminance_range_and_inverse() {
+        // The standard's end points: 0.05 and 3993 cd/m²
+        assert!((luminance(1.0) - 0.05).abs() < 0.001);
+        assert!((luminance(1023.0) - 3993.4).abs() < 1.0);
+        assert!((jnd_index(luminance(500.0)) - 500.0).abs() < 0.5);
+
+        assert!(display_level(0.0, 250.0).abs() < 1e-3);
+        assert!((display_level(1.0, 250.0) - 1.0).abs() < 1e-3);
+        // Mid-gray is shown darker than with the plain sRGB ramp
+        let mid = display_level(0.5, 250.0);
+        assert!(mid > 0.35 && mid < 0.4, "{mid}");
+    }
+}
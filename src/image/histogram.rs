@@ -0,0 +1,98 @@
+use super::frame::DecodedFrame;
+use crate::types::RescaleParams;
+
+/// Counts of rescaled sample values in equal-width bins
+#[derive(Debug, Clone, PartialEq)]
+pub struct Histogram {
+    /// Lowest rescaled value, the start of the first bin
+    pub min: f64,
+    /// Highest rescaled value, the end of the last bin
+    pub max: f64,
+    pub counts: Vec<usize>,
+}
+
+impl Histogram {
+    /// Histogram of a single-sample (grayscale) frame in rescaled units
+    ///
+    /// Returns `None` for color or empty frames, or when `bins` is zero.
+    #[must_use]
+    pub fn from_frame(frame: &DecodedFrame, rescale: &RescaleParams, bins: usize) -> Option<Self> {
+        if frame.samples_per_pixel != 1 || bins == 0 {
+            return None;
+        }
+
+        let rescaled: Vec<f64> = frame
+            .samples
+            .values()
+            .into_iter()
+            .map(|v| v.mul_add(rescale.slope, rescale.intercept))
+            .collect();
+
+        if rescaled.is_empty() {
+            return None;
+        }
+
+        let (min, max) = rescaled
+            .iter()
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), &v| {
+                (min.min(v), max.max(v))
+            });
+
+        let mut histogram = Self {
+            min,
+            max,
+            counts: vec![0; bins],
+        };
+
+        for value in rescaled {
+            let bin = histogram.bin_of(value);
+            histogram.counts[bin] += 1;
+        }
+
+        Some(histogram)
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn bin_width(&self) -> f64 {
+        (self.max - self.min) / self.counts.len() as f64
+    }
+
+    /// Bin containing `value`, clamped to the first and last bin
+    #[must_use]
+    pub fn bin_of(&self, value: f64) -> usize {
+        let last = self.counts.len().saturating_sub(1);
+
+        if self.max <= self.min {
+            return 0;
+        }
+
+        let position = (value - self.min) / (self.max - self.min) * self.counts.len() as f64;
+        (position.floor().max(0.0) as usize).min(last)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::frame::Samples;
+
+    #[test]
+    fn test_histogram_bins_rescaled_values() {
+        let frame = DecodedFrame {
+            width: 4,
+            height: 2,
+            samples_per_pixel: 1,
+            samples: Samples::U16(vec![0, 0, 0, 10, 20, 30, 39, 40]),
+        };
+        let rescale = RescaleParams::new(1.0, -1000.0);
+
+        let histogram = Histogram::from_frame(&frame, &rescale, 4).unwrap();
+        assert_eq!((histogram.min, histogram.max), (-1000.0, -960.0));
+        assert_eq!(histogram.counts, vec![3, 1, 1, 3]);
+
+        // Values outside the range land in the edge bins
+        assert_eq!(histogram.bin_of(-2000.0), 0);
+        assert_eq!(histogram.bin_of(500.0), 3);
+    }
+}
@@ -0,0 +1,71 @@
+use super::frame::first_frame_interleaved_8bit;
+use crate::dicom::DicomMetadata;
+use anyhow::{Context, Result};
+use image::{DynamicImage, ImageBuffer, RgbImage};
+
+/// Convert HSV DICOM pixel data to a `DynamicImage`
+///
+/// Hue, saturation and value are each stored as 8-bit samples scaled to
+/// the full 0-255 range.
+///
+/// # Errors
+///
+/// Returns an error if the pixel data is not 8-bit or has an invalid size
+pub fn convert_hsv(metadata: &DicomMetadata) -> Result<DynamicImage> {
+    let pixel_data = first_frame_interleaved_8bit(metadata, 3)?;
+
+    let rgb_pixels: Vec<u8> = pixel_data
+        .chunks_exact(3)
+        .flat_map(|hsv| hsv_to_rgb(hsv[0], hsv[1], hsv[2]))
+        .collect();
+
+    let rgb_image: RgbImage = ImageBuffer::from_raw(
+        u32::from(metadata.cols()),
+        u32::from(metadata.rows()),
+        rgb_pixels,
+    )
+    .context("Failed to create RGB image buffer from HSV")?;
+
+    Ok(DynamicImage::ImageRgb8(rgb_image))
+}
+
+fn hsv_to_rgb(h: u8, s: u8, v: u8) -> [u8; 3] {
+    let hue = f32::from(h) / 255.0_f32 * 6.0_f32;
+    let saturation = f32::from(s) / 255.0_f32;
+    let value = f32::from(v);
+
+    let sector = hue.floor();
+    let fraction = hue - sector;
+
+    let p = value * (1.0 - saturation);
+    let q = value * fraction.mul_add(-saturation, 1.0);
+    let t = value * (1.0 - fraction).mul_add(-saturation, 1.0);
+
+    let (r, g, b) = match sector as u8 {
+        0 | 6 => (value, t, p),
+        1 => (q, value, p),
+        2 => (p, value, t),
+        3 => (p, q, value),
+        4 => (t, p, value),
+        _ => (value, p, q),
+    };
+
+    [
+        r.round().clamp(0.0, 255.0) as u8,
+        g.round().clamp(0.0, 255.0) as u8,
+        b.round().clamp(0.0, 255.0) as u8,
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hsv_primaries() {
+        assert_eq!(hsv_to_rgb(0, 255, 255), [255, 0, 0]);
+        assert_eq!(hsv_to_rgb(85, 255, 255), [0, 255, 0]);
+        assert_eq!(hsv_to_rgb(170, 255, 255), [0, 0, 255]);
+        assert_eq!(hsv_to_rgb(123, 0, 200), [200, 200, 200]);
+    }
+}
@@ -0,0 +1,205 @@
+//! Display conventions for mammograms
+//!
+//! Most of a mammogram is the air around the breast. Spreading the full
+//! range of values over the gray levels leaves the tissue in a narrow band
+//! of grays, so the air is left out of the range. Mammograms are also
+//! mirrored the way they are read: the chest wall on the right edge of a
+//! right breast and on the left edge of a left one, with the head up in
+//! oblique and lateral views and the lateral side up in CC views.
+
+use super::orientation::Transform;
+use crate::dicom::DicomMetadata;
+
+/// Fraction of the tissue values left out at each end of the range, so
+/// that lead markers and noise do not stretch it
+const TISSUE_TAIL: f64 = 0.005;
+
+/// Bins of the histogram that separates air from tissue
+const BINS: usize = 1024;
+
+/// Whether `metadata` is a mammogram, by Modality or SOP Class
+#[must_use]
+pub fn is_mammogram(metadata: &DicomMetadata) -> bool {
+    metadata.modality() == Some("MG")
+        || metadata
+            .sop_class
+            .as_ref()
+            .is_some_and(|class| class.name.contains("Mammography"))
+}
+
+/// Orientation that shows a mammogram the way it is read, `None` for other
+/// images
+///
+/// Needs laterality and Patient Orientation. The screen right points
+/// posterior for a right breast and anterior for a left one. The screen
+/// down points to the feet in MLO, ML and LM views, and medially in CC
+/// views. Other views keep their stored vertical direction.
+#[must_use]
+pub fn mammogram_transform(metadata: &DicomMetadata) -> Option<Transform> {
+    if !is_mammogram(metadata) {
+        return None;
+    }
+    let orientation = metadata.orientation.as_ref()?;
+    let posterior = match metadata.view.laterality.as_deref()? {
+        "R" => 1.0,
+        "L" => -1.0,
+        _ => return None,
+    };
+    let want_down = match metadata.view.position.as_deref() {
+        Some("MLO" | "ML" | "LM") => [0.0, 0.0, -1.0],
+        // Medial is toward the other breast
+        Some("CC") => [posterior, 0.0, 0.0],
+        _ => [0.0; 3],
+    };
+
+    let score = |transform: &Transform| {
+        let (right, down) = transform.patient_axes(orientation);
+        right[1].mul_add(
+            posterior,
+            down[0].mul_add(want_down[0], down[2] * want_down[2]),
+        )
+    };
+    // Mammograms are not stored rotated, so only flips are considered, and
+    // the stored layout wins ties
+    let candidates = [
+        Transform::IDENTITY,
+        Transform::FLIP_H,
+        Transform::FLIP_V,
+        Transform::ROTATE_180,
+    ];
+    candidates
+        .into_iter()
+        .fold(None, |best: Option<(Transform, f64)>, candidate| {
+            let candidate_score = score(&candidate);
+            match best {
+                Some((_, best_score)) if candidate_score <= best_score + 1e-9 => best,
+                _ => Some((candidate, candidate_score)),
+            }
+        })
+        .map(|(transform, _)| transform)
+}
+
+/// Range of the breast tissue among rescaled `values`, leaving out the air
+/// around it
+///
+/// Air and tissue are told apart by Otsu's threshold. Air is at the low end
+/// when `air_is_low` (MONOCHROME2) and the high end otherwise. Returns
+/// `None` when the values do not split into air and tissue.
+#[must_use]
+pub fn tissue_range(values: &[f32], air_is_low: bool) -> Option<(f32, f32)> {
+    let (min, max) = values
+        .iter()
+        .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), &v| {
+            (min.min(v), max.max(v))
+        });
+    if max <= min {
+        return None;
+    }
+
+    let scale = BINS as f32 / (max - min);
+    let mut counts = [0_usize; BINS];
+    for &value in values {
+        counts[(((value - min) * scale) as usize).min(BINS - 1)] += 1;
+    }
+
+    let threshold = otsu_threshold(&counts);
+    let tissue = if air_is_low {
+        threshold + 1..BINS
+    } else {
+        0..threshold + 1
+    };
+    let tissue_count: usize = counts[tissue.clone()].iter().sum();
+    if tissue_count == 0 || tissue_count == values.len() {
+        return None;
+    }
+
+    // Bins holding the given fraction of the tissue values
+    let tail = (tissue_count as f64 * TISSUE_TAIL) as usize;
+    let mut seen = 0;
+    let mut lower = None;
+    let mut upper = tissue.end - 1;
+    for bin in tissue {
+        seen += counts[bin];
+        if lower.is_none() && seen > tail {
+            lower = Some(bin);
+        }
+        if seen >= tissue_count - tail {
+            upper = bin;
+            break;
+        }
+    }
+
+    let value = |bin: usize| min + bin as f32 / scale;
+    Some((value(lower?), value(upper + 1).min(max)))
+}
+
+/// Bin that best splits the histogram into two classes (Otsu's method):
+/// the last bin of the lower class
+fn otsu_threshold(counts: &[usize]) -> usize {
+    let total: usize = counts.iter().sum();
+    let weighted: f64 = (0..)
+        .zip(counts)
+        .map(|(i, &c)| f64::from(i) * c as f64)
+        .sum();
+
+    let (mut below, mut below_weighted) = (0_usize, 0.0_f64);
+    let (mut best, mut best_variance) = (0, f64::NEG_INFINITY);
+    for (bin, &count) in (0..).zip(counts) {
+        below += count;
+        below_weighted += f64::from(bin) * count as f64;
+        let above = total - below;
+        if below == 0 || above == 0 {
+            continue;
+        }
+
+        let mean_below = below_weighted / below as f64;
+        let mean_above = (weighted - below_weighted) / above as f64;
+        let variance = below as f64 * above as f64 * (mean_below - mean_above).powi(2);
+        if variance > best_variance {
+            best = bin as usize;
+            best_variance = variance;
+        }
+    }
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{ImageOrientation, ViewInfo};
+
+    #[test]
+    fn test_tissue_range_leaves_out_air() {
+        // Air, tissue from 1000 to 1999, and a bright lead marker
+        let mut values = vec![0.0_f32; 3000];
+        values.extend((0..2000).map(|v| 1000.0 + (v / 2) as f32));
+        values.push(4000.0);
+
+        let (lower, upper) = tissue_range(&values, true).unwrap();
+        assert!((990.0..1030.0).contains(&lower), "{lower}");
+        assert!((1970.0..2010.0).contains(&upper), "{upper}");
+        assert_eq!(tissue_range(&[5.0; 10], true), None);
+    }
+
+    #[test]
+    fn test_chest_wall_faces_the_reader() {
+        // Chest wall on the left, anterior to the right and feet down
+        let mut metadata = DicomMetadata::test_grayscale16(2, 2, &[0; 4]);
+        metadata.study.modality = Some("MG".to_string());
+        metadata.orientation = ImageOrientation::from_patient_orientation("A\\F");
+        let view = |position: &str, laterality: &str| ViewInfo {
+            position: Some(position.to_string()),
+            laterality: Some(laterality.to_string()),
+        };
+
+        metadata.view = view("MLO", "R");
+        assert_eq!(mammogram_transform(&metadata), Some(Transform::FLIP_H));
+        metadata.view = view("MLO", "L");
+        assert_eq!(mammogram_transform(&metadata), Some(Transform::IDENTITY));
+
+        // A right CC view stored with the lateral side (R) down
+        metadata.orientation = ImageOrientation::from_patient_orientation("P\\R");
+        metadata.view = view("CC", "R");
+        assert_eq!(mammogram_transform(&metadata), Some(Transform::FLIP_V));
+    }
+}
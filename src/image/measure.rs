@@ -0,0 +1,160 @@
+use super::frame::DecodedFrame;
+use crate::types::{PixelSpacing, RescaleParams};
+use std::fmt;
+
+/// Distance between two pixels, physical when the pixel spacing is known
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Distance {
+    Millimeters(f64),
+    Pixels(f64),
+}
+
+impl Distance {
+    /// Distance between pixel centers given in stored (column, row) coordinates
+    #[must_use]
+    pub fn between(a: (u32, u32), b: (u32, u32), spacing: Option<PixelSpacing>) -> Self {
+        let dx = f64::from(a.0.abs_diff(b.0));
+        let dy = f64::from(a.1.abs_diff(b.1));
+
+        match spacing {
+            Some(spacing) => Self::Millimeters((dx * spacing.column).hypot(dy * spacing.row)),
+            None => Self::Pixels(dx.hypot(dy)),
+        }
+    }
+}
+
+impl fmt::Display for Distance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Millimeters(mm) => write!(f, "{mm:.1} mm"),
+            Self::Pixels(px) => write!(f, "{px:.1} px"),
+        }
+    }
+}
+
+/// Mean, standard deviation and range of rescaled values inside a rectangle
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RoiStatistics {
+    pub mean: f64,
+    pub std_dev: f64,
+    pub min: f64,
+    pub max: f64,
+    pub pixel_count: usize,
+}
+
+impl RoiStatistics {
+    /// Statistics of the rectangle with opposite corners `a` and `b` (inclusive)
+    ///
+    /// Corners are stored (column, row) coordinates and are clipped to the
+    /// frame. Returns `None` for multi-sample (color) frames and for
+    /// rectangles entirely outside the frame.
+    #[must_use]
+    pub fn from_frame(
+        frame: &DecodedFrame,
+        a: (u32, u32),
+        b: (u32, u32),
+        rescale: &RescaleParams,
+    ) -> Option<Self> {
+        if frame.samples_per_pixel != 1 {
+            return None;
+        }
+
+        let (x0, x1) = (a.0.min(b.0), a.0.max(b.0).min(frame.width.checked_sub(1)?));
+        let (y0, y1) = (a.1.min(b.1), a.1.max(b.1).min(frame.height.checked_sub(1)?));
+
+        let (sum, sum_squares, min, max, count) = (y0..=y1)
+            .flat_map(|y| (x0..=x1).map(move |x| (x, y)))
+            .filter_map(|(x, y)| frame.sample(x, y))
+            .map(|stored| stored.mul_add(rescale.slope, rescale.intercept))
+            .fold(
+                (0.0_f64, 0.0_f64, f64::INFINITY, f64::NEG_INFINITY, 0_usize),
+                |(sum, sum_squares, min, max, count), v| {
+                    (
+                        sum + v,
+                        v.mul_add(v, sum_squares),
+                        min.min(v),
+                        max.max(v),
+                        count + 1,
+                    )
+                },
+            );
+
+        if count == 0 {
+            return None;
+        }
+
+        let mean = sum / count as f64;
+        let variance = (sum_squares / count as f64 - mean * mean).max(0.0);
+
+        Some(Self {
+            mean,
+            std_dev: variance.sqrt(),
+            min,
+            max,
+            pixel_count: count,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dicom::DicomMetadata;
+    use crate::image::{Samples, decode_frame};
+    use crate::types::BitDepth;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn test_distance_uses_row_and_column_spacing() {
+        // 3 columns at 0.5 mm and 4 rows at 1.0 mm: 1.5 mm by 4 mm
+        let spacing = PixelSpacing::new(1.0, 0.5);
+        let Distance::Millimeters(mm) = Distance::between((1, 1), (4, 5), Some(spacing)) else {
+            panic!("expected a physical distance");
+        };
+        assert_relative_eq!(mm, 1.5_f64.hypot(4.0));
+
+        assert_eq!(
+            Distance::between((0, 0), (3, 4), None),
+            Distance::Pixels(5.0)
+        );
+    }
+
+    #[test]
+    fn test_roi_statistics_are_rescaled_and_clipped() {
+        // 3x2 frame:  0 10 20
+        //            30 40 50
+        let frame = DecodedFrame {
+            width: 3,
+            height: 2,
+            samples_per_pixel: 1,
+            samples: Samples::U16(vec![0, 10, 20, 30, 40, 50]),
+        };
+        let rescale = RescaleParams::new(1.0, -1000.0);
+
+        // Right two columns, with the corner far outside the frame
+        let stats = RoiStatistics::from_frame(&frame, (1, 0), (100, 100), &rescale).unwrap();
+        assert_eq!(stats.pixel_count, 4);
+        assert_relative_eq!(stats.mean, -970.0);
+        assert_relative_eq!(stats.std_dev, 250.0_f64.sqrt());
+        assert_relative_eq!(stats.min, -990.0);
+        assert_relative_eq!(stats.max, -950.0);
+
+        assert!(RoiStatistics::from_frame(&frame, (5, 5), (9, 9), &rescale).is_none());
+    }
+
+    #[test]
+    fn test_roi_statistics_of_signed_samples() {
+        // -1000, -990 and 40 in signed 12-bit samples
+        let mut metadata = DicomMetadata::test_grayscale16(1, 3, &[0xfc18, 0xfc22, 40]);
+        metadata.bit_depth = BitDepth {
+            signed: true,
+            ..BitDepth::new(16, 12)
+        };
+        let frame = decode_frame(&metadata).unwrap();
+
+        let stats = RoiStatistics::from_frame(&frame, (0, 0), (1, 0), &metadata.rescale).unwrap();
+        assert_relative_eq!(stats.mean, -995.0);
+        assert_relative_eq!(stats.min, -1000.0);
+        assert_relative_eq!(stats.max, -990.0);
+    }
+}
@@ -1,15 +1,56 @@
+mod annotate;
+mod caption;
+mod cmyk;
+mod colormap;
+mod filters;
+mod font;
+mod frame;
 mod grayscale;
+mod gsdf;
+mod histogram;
+mod hsv;
+mod mammography;
+mod measure;
+mod montage;
 mod normalization;
+mod orientation;
+mod palette;
 mod rgb;
+mod statistics;
+mod subtraction;
+mod tone;
 mod ycbcr;
 
-pub use grayscale::convert_grayscale;
+pub use annotate::{
+    ANNOTATION_COLOR, annotate, direction_label, draw_graphic_annotations, draw_line,
+    draw_overlay_planes, text_scale,
+};
+pub use caption::{CaptionPlacement, add_caption, caption_lines};
+pub use cmyk::convert_cmyk;
+pub use colormap::Colormap;
+pub use filters::Filter;
+pub use frame::{DecodedFrame, Samples, decode_grayscale_frame};
+pub use grayscale::{
+    convert_grayscale, invert_polarity, render_grayscale, render_grayscale_windowed,
+};
+pub use gsdf::GSDF_MIN_LUMINANCE;
+pub use histogram::Histogram;
+pub use hsv::convert_hsv;
+pub use mammography::{is_mammogram, mammogram_transform};
+pub use measure::{Distance, RoiStatistics};
+pub use montage::montage;
+pub use orientation::Transform;
+pub use palette::convert_palette;
 pub use rgb::convert_rgb;
+pub use statistics::{DisplayWindow, HU_WINDOW_PRESETS, PixelStatistics, window_presets};
+pub use subtraction::subtract_mask;
+pub use tone::ToneCurve;
 pub use ycbcr::convert_ycbcr;
 
 use crate::dicom::{DicomMetadata, PhotometricInterpretation};
+use crate::explain::DecisionLog;
 use anyhow::Result;
-use image::DynamicImage;
+use image::{DynamicImage, ImageBuffer, Luma, Rgb};
 
 /// Convert DICOM metadata and pixel data to a `DynamicImage`
 ///
@@ -18,24 +59,127 @@ use image::DynamicImage;
 /// Returns an error if the photometric interpretation is unsupported or
 /// if the conversion fails
 pub fn convert_to_image(metadata: &DicomMetadata) -> Result<DynamicImage> {
+    convert_to_image_explained(metadata, None, &mut DecisionLog::default())
+}
+
+/// Convert to a `DynamicImage`, showing grayscale values through `window`
+///
+/// The window is in rescaled units and is ignored for color images. Without
+/// one, this is the same as [`convert_to_image`].
+///
+/// # Errors
+///
+/// Returns an error if the photometric interpretation is unsupported or
+/// if the conversion fails
+pub fn convert_to_image_windowed(
+    metadata: &DicomMetadata,
+    window: Option<DisplayWindow>,
+) -> Result<DynamicImage> {
+    convert_to_image_explained(metadata, window, &mut DecisionLog::default())
+}
+
+/// [`convert_to_image_windowed`], noting the conversion steps in `log`
+///
+/// # Errors
+///
+/// Returns an error if the photometric interpretation is unsupported or
+/// if the conversion fails
+pub fn convert_to_image_explained(
+    metadata: &DicomMetadata,
+    window: Option<DisplayWindow>,
+    log: &mut DecisionLog,
+) -> Result<DynamicImage> {
+    let photometric = &metadata.photometric_interpretation;
+    if !metadata.is_already_rgb() && photometric.is_grayscale() {
+        log.record(
+            "Photometric",
+            format!("{photometric}, rendered as grayscale"),
+        );
+        let frame = decode_grayscale_frame(metadata)?;
+        return grayscale::render_grayscale_explained(&frame, metadata, window, log);
+    }
+
+    if window.is_some() {
+        log.record("Window", "not applied to color images");
+    }
+
     if metadata.is_already_rgb() {
+        log.record(
+            "Photometric",
+            format!("{photometric}, already RGB after decoding"),
+        );
         return convert_rgb(metadata);
     }
 
-    match metadata.photometric_interpretation {
-        PhotometricInterpretation::Monochrome1 | PhotometricInterpretation::Monochrome2 => {
-            convert_grayscale(metadata)
-        }
-        PhotometricInterpretation::Rgb => convert_rgb(metadata),
-        PhotometricInterpretation::YbrFull | PhotometricInterpretation::YbrFull422 => {
-            convert_ycbcr(metadata)
-        }
+    let (conversion, image) = match photometric {
+        PhotometricInterpretation::Rgb => ("RGB", convert_rgb(metadata)),
+        PhotometricInterpretation::Hsv => ("HSV to RGB", convert_hsv(metadata)),
+        PhotometricInterpretation::Cmyk => ("CMYK to RGB", convert_cmyk(metadata)),
+        PhotometricInterpretation::Palette => ("Palette Color LUT", convert_palette(metadata)),
+        // The JPEG 2000 decoder has already inverted the component transform
+        PhotometricInterpretation::YbrIct | PhotometricInterpretation::YbrRct => (
+            "RGB, the JPEG 2000 decoder inverted the component transform",
+            convert_rgb(metadata),
+        ),
+        PhotometricInterpretation::YbrFull
+        | PhotometricInterpretation::YbrFull422
+        | PhotometricInterpretation::YbrPartial422
+        | PhotometricInterpretation::YbrPartial420 => ("YCbCr to RGB", convert_ycbcr(metadata)),
         _ => {
             anyhow::bail!(
                 "Unsupported photometric interpretation: {:?}",
                 metadata.photometric_interpretation
             )
         }
+    };
+    log.record(
+        "Photometric",
+        format!("{photometric}, converted as {conversion}"),
+    );
+    image
+}
+
+/// Decode the first frame into typed samples at their original bit depth
+///
+/// Grayscale data keeps its stored values; color data is converted to RGB,
+/// which is already 8 bits per sample for all supported color formats.
+///
+/// # Errors
+///
+/// Returns an error if the pixel data cannot be decoded
+pub fn decode_frame(metadata: &DicomMetadata) -> Result<DecodedFrame> {
+    if !metadata.is_already_rgb() && metadata.photometric_interpretation.is_grayscale() {
+        return decode_grayscale_frame(metadata);
+    }
+
+    Ok(convert_to_image(metadata)?.into_rgb8().into())
+}
+
+/// Reduce a rendered image to 8 bits per sample for display
+///
+/// Rendering keeps 16-bit grayscale so that exports can write full-depth
+/// files; terminals only show 8 bits. Each 8-bit level covers 257 16-bit
+/// levels, so this truncates rather than rounds, unlike the `image` crate's
+/// own conversions. Images already at 8 bits are returned unchanged.
+#[must_use]
+pub fn quantize(image: DynamicImage) -> DynamicImage {
+    let level = |v: u16| (v / 257) as u8;
+
+    match image {
+        DynamicImage::ImageLuma16(gray) => {
+            let (width, height) = gray.dimensions();
+            let levels = gray.into_raw().into_iter().map(level).collect();
+            ImageBuffer::<Luma<u8>, _>::from_raw(width, height, levels)
+                .map_or_else(|| DynamicImage::new_luma8(width, height), Into::into)
+        }
+        DynamicImage::ImageRgb16(_) | DynamicImage::ImageRgba16(_) => {
+            let rgb = image.into_rgb16();
+            let (width, height) = rgb.dimensions();
+            let levels = rgb.into_raw().into_iter().map(level).collect();
+            ImageBuffer::<Rgb<u8>, _>::from_raw(width, height, levels)
+                .map_or_else(|| DynamicImage::new_rgb8(width, height), Into::into)
+        }
+        image => image,
     }
 }
 
@@ -43,6 +187,14 @@ pub fn convert_to_image(metadata: &DicomMetadata) -> Result<DynamicImage> {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_quantize_truncates_16_bit_levels() {
+        let gray = ImageBuffer::<Luma<u16>, _>::from_raw(3, 1, vec![0, 256, 65535]).unwrap();
+
+        let quantized = quantize(DynamicImage::ImageLuma16(gray));
+        assert_eq!(quantized.as_luma8().unwrap().as_raw(), &vec![0, 0, 255]);
+    }
+
     #[test]
     fn test_convert_grayscale_dispatch() {
         // Test that grayscale photometric interpretations dispatch correctly
@@ -57,17 +209,38 @@ mod tests {
             samples_per_pixel: 1,
             planar_configuration: None,
             number_of_frames: 1,
+            frame_time: None,
             pixel_aspect_ratio: None,
+            pixel_spacing: None,
+            pixel_value_range: None,
+            orientation: None,
+            position: None,
             pixel_data_format: DecodedPixelData::Native(vec![0u8; 64 * 64 * 2].into_boxed_slice()),
             rescale: crate::types::RescaleParams::new(1.0, 0.0),
+            modality_lut: None,
+            palette: None,
+            rescale_type: None,
+            voi_window: None,
             patient: PatientInfo::new(),
             study: StudyInfo::new(),
             series: SeriesInfo::new(),
+            acquisition: crate::types::AcquisitionInfo::default(),
+            view: crate::types::ViewInfo::default(),
+            ultrasound_regions: Vec::new(),
+            graphic_annotations: Vec::new(),
+            overlays: Vec::new(),
             sop_class: None,
+            sop_instance_uid: None,
+            frame_of_reference_uid: None,
             transfer_syntax: crate::types::TransferSyntax::new(
                 "1.2.840.10008.1.2".to_string(),
                 "Implicit VR Little Endian".to_string(),
             ),
+            lossy_compression: None,
+            burned_in_annotation: None,
+            file_stamp: None,
+            warnings: Vec::new(),
+            decisions: DecisionLog::default(),
         };
 
         // This should not compile if the dispatch is broken
@@ -0,0 +1,73 @@
+use image::imageops::{self, FilterType};
+use image::{Rgb, RgbImage};
+
+/// Pixels between the cells of a montage
+const GAP: u32 = 4;
+
+const BACKGROUND: Rgb<u8> = Rgb([0, 0, 0]);
+
+/// Images side by side in rows of `columns` cells
+///
+/// Every cell is as large as the largest image; smaller images are scaled
+/// up to fill it, keeping their aspect ratio, and centered. Returns `None`
+/// without images.
+#[must_use]
+pub fn montage(images: &[RgbImage], columns: u32) -> Option<RgbImage> {
+    let cell = (
+        images.iter().map(RgbImage::width).max()?,
+        images.iter().map(RgbImage::height).max()?,
+    );
+    let columns = columns.clamp(1, images.len() as u32);
+    let rows = (images.len() as u32).div_ceil(columns);
+
+    let mut canvas = RgbImage::from_pixel(
+        columns * cell.0 + (columns - 1) * GAP,
+        rows * cell.1 + (rows - 1) * GAP,
+        BACKGROUND,
+    );
+
+    for (index, image) in (0..).zip(images) {
+        let scale = f64::min(
+            f64::from(cell.0) / f64::from(image.width().max(1)),
+            f64::from(cell.1) / f64::from(image.height().max(1)),
+        );
+        let size = (
+            ((f64::from(image.width()) * scale).round() as u32).clamp(1, cell.0),
+            ((f64::from(image.height()) * scale).round() as u32).clamp(1, cell.1),
+        );
+        let fitted = if size == image.dimensions() {
+            image.clone()
+        } else {
+            imageops::resize(image, size.0, size.1, FilterType::Triangle)
+        };
+
+        let x = (index % columns) * (cell.0 + GAP) + (cell.0 - size.0) / 2;
+        let y = (index / columns) * (cell.1 + GAP) + (cell.1 - size.1) / 2;
+        imageops::replace(&mut canvas, &fitted, i64::from(x), i64::from(y));
+    }
+
+    Some(canvas)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_montage_places_images_in_equal_cells() {
+        let white = RgbImage::from_pixel(4, 2, Rgb([255, 255, 255]));
+        let small = RgbImage::from_pixel(2, 1, Rgb([100, 100, 100]));
+        let tall = RgbImage::from_pixel(1, 2, Rgb([50, 50, 50]));
+
+        let grid = montage(&[white, small, tall], 2).unwrap();
+        assert_eq!(grid.dimensions(), (4 * 2 + GAP, 2 * 2 + GAP));
+
+        // The small image is scaled up to fill the second cell
+        assert_eq!(grid.get_pixel(4 + GAP, 0), &Rgb([100, 100, 100]));
+        // The tall one is centered in the third, on the next row
+        assert_eq!(grid.get_pixel(0, 2 + GAP), &BACKGROUND);
+        assert_eq!(grid.get_pixel(1, 2 + GAP), &Rgb([50, 50, 50]));
+
+        assert!(montage(&[], 2).is_none());
+    }
+}
@@ -0,0 +1,346 @@
+use super::frame::{DecodedFrame, Samples};
+use crate::types::ImageOrientation;
+use image::DynamicImage;
+
+/// One of the eight rotations/flips of an image
+///
+/// Applied as: transpose (swap rows and columns), then horizontal flip,
+/// then vertical flip.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Transform {
+    pub transpose: bool,
+    pub flip_h: bool,
+    pub flip_v: bool,
+}
+
+/// A screen axis expressed in the stored image axes: (x, y) with entries -1, 0 or 1
+type Axis = [i8; 2];
+
+impl Transform {
+    pub const IDENTITY: Self = Self::new(false, false, false);
+    pub const FLIP_H: Self = Self::new(false, true, false);
+    pub const FLIP_V: Self = Self::new(false, false, true);
+    pub const ROTATE_90: Self = Self::new(true, true, false);
+    pub const ROTATE_180: Self = Self::new(false, true, true);
+    pub const ROTATE_270: Self = Self::new(true, false, true);
+
+    #[must_use]
+    pub const fn new(transpose: bool, flip_h: bool, flip_v: bool) -> Self {
+        Self {
+            transpose,
+            flip_h,
+            flip_v,
+        }
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn is_identity(&self) -> bool {
+        *self == Self::IDENTITY
+    }
+
+    /// Transform that shows the image in the conventional radiological view
+    ///
+    /// Axial slices are shown with patient left on the right of the screen
+    /// and posterior at the bottom; coronal and sagittal slices with the
+    /// head at the top and, respectively, patient left or posterior on the
+    /// right. Without orientation information the image is left as stored.
+    #[must_use]
+    pub fn from_orientation(orientation: Option<&ImageOrientation>) -> Self {
+        let Some(orientation) = orientation else {
+            return Self::IDENTITY;
+        };
+
+        let (want_right, want_down) = conventional_axes(orientation.normal());
+
+        let mut best = Self::IDENTITY;
+        let mut best_score = f64::NEG_INFINITY;
+
+        for candidate in Self::all() {
+            let (right, down) = candidate.patient_axes(orientation);
+            let score = dot(right, want_right) + dot(down, want_down);
+
+            // Strictly greater: prefer the stored layout on ties
+            if score > best_score + 1e-9 {
+                best = candidate;
+                best_score = score;
+            }
+        }
+
+        best
+    }
+
+    /// Apply `next` after this transform
+    #[must_use]
+    pub fn then(self, next: Self) -> Self {
+        let (right1, down1) = self.axes();
+        let (right2, down2) = next.axes();
+
+        // The axes of `next` are relative to the output of `self`
+        let map = |v: Axis| {
+            [
+                v[0] * right1[0] + v[1] * down1[0],
+                v[0] * right1[1] + v[1] * down1[1],
+            ]
+        };
+
+        Self::from_axes(map(right2), map(down2))
+    }
+
+    /// Patient-space directions of screen right and down after this transform
+    #[must_use]
+    pub fn patient_axes(&self, orientation: &ImageOrientation) -> ([f64; 3], [f64; 3]) {
+        let (right, down) = self.axes();
+        (
+            patient_direction(orientation, right),
+            patient_direction(orientation, down),
+        )
+    }
+
+    /// Whether rows and columns swap, so pixel aspect ratios must swap too
+    #[inline]
+    #[must_use]
+    pub fn swaps_axes(&self) -> bool {
+        self.transpose
+    }
+
+    /// Apply the transform to a display image
+    #[must_use]
+    pub fn apply_to_image(&self, image: DynamicImage) -> DynamicImage {
+        let image = if self.transpose {
+            image.rotate90().fliph()
+        } else {
+            image
+        };
+        let image = if self.flip_h { image.fliph() } else { image };
+        if self.flip_v { image.flipv() } else { image }
+    }
+
+    /// Apply the transform to a decoded frame, keeping its sample type
+    #[must_use]
+    pub fn apply_to_frame(&self, frame: DecodedFrame) -> DecodedFrame {
+        if self.is_identity() {
+            return frame;
+        }
+
+        let (width, height) = (frame.width as usize, frame.height as usize);
+        let spp = usize::from(frame.samples_per_pixel);
+
+        let samples = match &frame.samples {
+            Samples::U8(values) => Samples::U8(self.remap(values, width, height, spp)),
+            Samples::U16(values) => Samples::U16(self.remap(values, width, height, spp)),
+            Samples::U32(values) => Samples::U32(self.remap(values, width, height, spp)),
+            Samples::I16(values) => Samples::I16(self.remap(values, width, height, spp)),
+            Samples::I32(values) => Samples::I32(self.remap(values, width, height, spp)),
+        };
+
+        let (width, height) = if self.transpose {
+            (frame.height, frame.width)
+        } else {
+            (frame.width, frame.height)
+        };
+
+        DecodedFrame {
+            width,
+            height,
+            samples_per_pixel: frame.samples_per_pixel,
+            samples,
+        }
+    }
+
+    /// Position in the untransformed image of pixel (x, y) of the transformed one
+    ///
+    /// `size` is the (width, height) of the transformed image.
+    #[must_use]
+    pub fn source_position(&self, (x, y): (u32, u32), size: (u32, u32)) -> (u32, u32) {
+        let (width, height) = size;
+        let fx = if self.flip_h { width - 1 - x } else { x };
+        let fy = if self.flip_v { height - 1 - y } else { y };
+
+        if self.transpose { (fy, fx) } else { (fx, fy) }
+    }
+
+    /// Position in the transformed image of pixel (x, y) of the untransformed one
+    ///
+    /// `size` is the (width, height) of the untransformed image.
+    #[must_use]
+    pub fn target_position(&self, (x, y): (u32, u32), size: (u32, u32)) -> (u32, u32) {
+        let ((x, y), (width, height)) = if self.transpose {
+            ((y, x), (size.1, size.0))
+        } else {
+            ((x, y), size)
+        };
+
+        (
+            if self.flip_h { width - 1 - x } else { x },
+            if self.flip_v { height - 1 - y } else { y },
+        )
+    }
+
+    /// Position in the transformed image of point (x, y) of the
+    /// untransformed one, in continuous coordinates where (0, 0) is the top
+    /// left corner of the first pixel
+    ///
+    /// `size` is the (width, height) of the untransformed image.
+    #[must_use]
+    pub fn target_point(&self, (x, y): (f64, f64), size: (f64, f64)) -> (f64, f64) {
+        let ((x, y), (width, height)) = if self.transpose {
+            ((y, x), (size.1, size.0))
+        } else {
+            ((x, y), size)
+        };
+
+        (
+            if self.flip_h { width - x } else { x },
+            if self.flip_v { height - y } else { y },
+        )
+    }
+
+    fn remap<T: Copy>(&self, values: &[T], width: usize, height: usize, spp: usize) -> Vec<T> {
+        let (out_width, out_height) = if self.transpose {
+            (height, width)
+        } else {
+            (width, height)
+        };
+
+        let mut output = Vec::with_capacity(values.len());
+
+        for y in 0..out_height {
+            let fy = if self.flip_v { out_height - 1 - y } else { y };
+
+            for x in 0..out_width {
+                let fx = if self.flip_h { out_width - 1 - x } else { x };
+                let (sx, sy) = if self.transpose { (fy, fx) } else { (fx, fy) };
+
+                let source = (sy * width + sx) * spp;
+                output.extend_from_slice(&values[source..source + spp]);
+            }
+        }
+
+        output
+    }
+
+    fn all() -> impl Iterator<Item = Self> {
+        (0..8u8).map(|bits| Self::new(bits & 4 != 0, bits & 2 != 0, bits & 1 != 0))
+    }
+
+    /// Screen right and down directions in stored image axes
+    fn axes(self) -> (Axis, Axis) {
+        let (right, down) = if self.transpose {
+            ([0, 1], [1, 0])
+        } else {
+            ([1, 0], [0, 1])
+        };
+
+        let negate = |v: Axis, flip: bool| if flip { [-v[0], -v[1]] } else { v };
+        (negate(right, self.flip_h), negate(down, self.flip_v))
+    }
+
+    fn from_axes(right: Axis, down: Axis) -> Self {
+        let transpose = right[0] == 0;
+        let flip_h = right[0] + right[1] < 0;
+        let flip_v = down[0] + down[1] < 0;
+        Self::new(transpose, flip_h, flip_v)
+    }
+}
+
+/// Desired screen (right, down) directions in patient space for the slice plane
+fn conventional_axes(normal: [f64; 3]) -> ([f64; 3], [f64; 3]) {
+    let [x, y, z] = normal.map(f64::abs);
+
+    if z >= x && z >= y {
+        // Axial: viewed from the feet
+        ([1.0, 0.0, 0.0], [0.0, 1.0, 0.0])
+    } else if y >= x {
+        // Coronal: viewed from the front
+        ([1.0, 0.0, 0.0], [0.0, 0.0, -1.0])
+    } else {
+        // Sagittal: anterior on the left
+        ([0.0, 1.0, 0.0], [0.0, 0.0, -1.0])
+    }
+}
+
+fn patient_direction(orientation: &ImageOrientation, axis: Axis) -> [f64; 3] {
+    let (a, b) = (f64::from(axis[0]), f64::from(axis[1]));
+    [0, 1, 2].map(|i| a.mul_add(orientation.row[i], b * orientation.column[i]))
+}
+
+fn dot(a: [f64; 3], b: [f64; 3]) -> f64 {
+    a[0].mul_add(b[0], a[1].mul_add(b[1], a[2] * b[2]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_target_position_inverts_source_position() {
+        let size = (4, 3);
+        for transform in Transform::all() {
+            let transformed = if transform.swaps_axes() { (3, 4) } else { size };
+            let target = transform.target_position((3, 1), size);
+            assert_eq!(transform.source_position(target, transformed), (3, 1));
+        }
+    }
+
+    #[test]
+    fn test_standard_axial_is_identity() {
+        let orientation = ImageOrientation::new([1.0, 0.0, 0.0], [0.0, 1.0, 0.0]);
+        assert_eq!(
+            Transform::from_orientation(Some(&orientation)),
+            Transform::IDENTITY
+        );
+    }
+
+    #[test]
+    fn test_sagittal_with_swapped_axes_is_transposed() {
+        // Rows run head-to-feet, columns run anterior-to-posterior
+        let orientation = ImageOrientation::new([0.0, 0.0, -1.0], [0.0, 1.0, 0.0]);
+        let transform = Transform::from_orientation(Some(&orientation));
+        assert!(transform.transpose);
+        assert!(!transform.flip_h && !transform.flip_v);
+    }
+
+    #[test]
+    fn test_coronal_from_patient_orientation() {
+        // Rows towards patient right, columns towards feet: mirrored coronal
+        let orientation = ImageOrientation::from_patient_orientation("R\\F").unwrap();
+        assert_eq!(
+            Transform::from_orientation(Some(&orientation)),
+            Transform::FLIP_H
+        );
+    }
+
+    #[test]
+    fn test_rotations_compose() {
+        assert_eq!(
+            Transform::ROTATE_90.then(Transform::ROTATE_90),
+            Transform::ROTATE_180
+        );
+        assert_eq!(
+            Transform::ROTATE_90.then(Transform::ROTATE_270),
+            Transform::IDENTITY
+        );
+        assert_eq!(
+            Transform::FLIP_H.then(Transform::FLIP_V),
+            Transform::ROTATE_180
+        );
+    }
+
+    #[test]
+    fn test_apply_to_frame_rotate_90() {
+        // 3x2 image:  1 2 3     rotated clockwise:  4 1
+        //             4 5 6                         5 2
+        //                                           6 3
+        let frame = DecodedFrame {
+            width: 3,
+            height: 2,
+            samples_per_pixel: 1,
+            samples: Samples::U8(vec![1, 2, 3, 4, 5, 6]),
+        };
+
+        let rotated = Transform::ROTATE_90.apply_to_frame(frame);
+        assert_eq!((rotated.width, rotated.height), (2, 3));
+        assert_eq!(rotated.samples, Samples::U8(vec![4, 1, 5, 2, 6, 3]));
+    }
+}
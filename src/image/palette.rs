@@ -0,0 +1,54 @@
+use crate::dicom::DicomMetadata;
+use anyhow::{Context, Result, bail};
+use image::{DynamicImage, ImageBuffer, RgbImage};
+
+/// Convert PALETTE COLOR DICOM pixel data to a `DynamicImage`
+///
+/// Each stored value of the first frame is looked up in the Red, Green and
+/// Blue Palette Color Lookup Tables.
+///
+/// # Errors
+///
+/// Returns an error if the file has no usable lookup tables, the pixel data
+/// is not 8 or 16-bit, or it is too short for a full frame
+pub fn convert_palette(metadata: &DicomMetadata) -> Result<DynamicImage> {
+    let palette = metadata
+        .palette
+        .as_ref()
+        .context("PALETTE COLOR image without usable Palette Color Lookup Tables")?;
+
+    let pixel_count = metadata.dimensions.pixel_count();
+    let bytes_per_pixel = match metadata.bits_allocated() {
+        8 => 1,
+        16 => 2,
+        other => bail!("Unsupported bits allocated for PALETTE COLOR: {other} (expected 8 or 16)"),
+    };
+    let expected_size = pixel_count * bytes_per_pixel;
+    let data = metadata.pixel_data().get(..expected_size).ok_or_else(|| {
+        anyhow::anyhow!(
+            "Invalid PALETTE COLOR pixel data size: expected {expected_size} bytes for first frame, got {}",
+            metadata.pixel_data().len()
+        )
+    })?;
+
+    let stored: Vec<u16> = if bytes_per_pixel == 2 {
+        data.chunks_exact(2)
+            .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]))
+            .collect()
+    } else {
+        data.iter().map(|&value| u16::from(value)).collect()
+    };
+    let rgb_pixels: Vec<u8> = stored
+        .into_iter()
+        .flat_map(|value| palette.color(i32::from(value)))
+        .collect();
+
+    let rgb_image: RgbImage = ImageBuffer::from_raw(
+        u32::from(metadata.cols()),
+        u32::from(metadata.rows()),
+        rgb_pixels,
+    )
+    .context("Failed to create RGB image buffer from PALETTE COLOR")?;
+
+    Ok(DynamicImage::ImageRgb8(rgb_image))
+}
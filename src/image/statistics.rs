@@ -0,0 +1,272 @@
+use super::frame::DecodedFrame;
+use crate::dicom::DicomMetadata;
+use crate::types::{RescaleParams, RescaleType, VoiLutFunction, VoiWindow};
+use std::fmt;
+
+/// Summary of the stored sample values of a frame
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PixelStatistics {
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64,
+}
+
+impl PixelStatistics {
+    /// Compute min/max/mean over all samples of a frame
+    ///
+    /// Returns `None` for an empty frame.
+    #[must_use]
+    pub fn from_frame(frame: &DecodedFrame) -> Option<Self> {
+        Self::from_values(&frame.samples.values())
+    }
+
+    fn from_values(values: &[f64]) -> Option<Self> {
+        let (min, max, sum, count) = values.iter().fold(
+            (f64::INFINITY, f64::NEG_INFINITY, 0.0_f64, 0_usize),
+            |(min, max, sum, count), &v| (min.min(v), max.max(v), sum + v, count + 1),
+        );
+
+        (count > 0).then(|| Self {
+            min,
+            max,
+            mean: sum / count as f64,
+        })
+    }
+
+    /// Statistics converted to rescaled (modality) units
+    #[must_use]
+    pub fn rescaled(&self, rescale: &RescaleParams) -> Self {
+        let a = self.min.mul_add(rescale.slope, rescale.intercept);
+        let b = self.max.mul_add(rescale.slope, rescale.intercept);
+
+        Self {
+            min: a.min(b),
+            max: a.max(b),
+            mean: self.mean.mul_add(rescale.slope, rescale.intercept),
+        }
+    }
+
+    /// Display window covering the full rescaled value range
+    ///
+    /// This is the window applied when no explicit window is requested.
+    #[must_use]
+    pub fn display_window(&self, rescale: &RescaleParams) -> DisplayWindow {
+        DisplayWindow::from_stored_range(self.min, self.max, rescale)
+    }
+}
+
+impl fmt::Display for PixelStatistics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "min={min}, max={max}, mean={mean:.1}",
+            min = self.min,
+            max = self.max,
+            mean = self.mean
+        )
+    }
+}
+
+/// Display window in rescaled (modality) units
+///
+/// Linear windows map `lower()..upper()` exactly onto the displayed range,
+/// so `LINEAR` and `LINEAR_EXACT` look the same; their half-value
+/// difference does not show in 8-bit levels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DisplayWindow {
+    pub center: f64,
+    pub width: f64,
+    pub function: VoiLutFunction,
+}
+
+impl DisplayWindow {
+    #[must_use]
+    pub const fn new(center: f64, width: f64) -> Self {
+        Self {
+            center,
+            width,
+            function: VoiLutFunction::Linear,
+        }
+    }
+
+    #[must_use]
+    pub fn from_range(lower: f64, upper: f64) -> Self {
+        Self::new((lower + upper) / 2.0, upper - lower)
+    }
+
+    /// The window declared in the file
+    #[must_use]
+    pub fn from_voi(voi: &VoiWindow) -> Self {
+        Self {
+            center: voi.center,
+            width: voi.width,
+            function: voi.function,
+        }
+    }
+
+    /// Position of `value` in the displayed range, from 0 to 1
+    #[inline]
+    #[must_use]
+    pub fn normalize(&self, value: f64) -> f64 {
+        match self.function {
+            VoiLutFunction::Sigmoid => {
+                1.0 / (1.0 + (-4.0 * (value - self.center) / self.width).exp())
+            }
+            VoiLutFunction::Linear | VoiLutFunction::LinearExact => {
+                ((value - self.lower()) / self.width).clamp(0.0, 1.0)
+            }
+        }
+    }
+
+    /// Window covering a range of stored values, in rescaled units
+    #[must_use]
+    pub fn from_stored_range(min: f64, max: f64, rescale: &RescaleParams) -> Self {
+        let a = min.mul_add(rescale.slope, rescale.intercept);
+        let b = max.mul_add(rescale.slope, rescale.intercept);
+        Self::from_range(a.min(b), a.max(b))
+    }
+
+    /// Window the renderer applies to the image by default
+    ///
+    /// A SIGMOID window declared in the file, which is how the image is meant
+    /// to be seen. Otherwise the declared Smallest/Largest Image Pixel Value
+    /// when present (ignored for 32-bit samples, which are renormalized for
+    /// display), or else the full range of stored values in `stats`.
+    #[must_use]
+    pub fn for_image(metadata: &DicomMetadata, stats: &PixelStatistics) -> Self {
+        if let Some(window) = Self::declared_sigmoid(metadata) {
+            return window;
+        }
+
+        let declared_range = metadata
+            .pixel_value_range
+            .filter(|_| metadata.bits_allocated() != 32);
+
+        if let Some(lut) = metadata
+            .modality_lut
+            .as_ref()
+            .filter(|_| metadata.bits_allocated() != 32)
+        {
+            let (min, max) = declared_range.map_or((stats.min as i32, stats.max as i32), |range| {
+                (range.smallest, range.largest)
+            });
+            let (min, max) = lut.output_range(min, max);
+            return Self::from_range(min, max);
+        }
+
+        match declared_range {
+            Some(range) => Self::from_stored_range(
+                f64::from(range.smallest),
+                f64::from(range.largest),
+                &metadata.rescale,
+            ),
+            None => stats.display_window(&metadata.rescale),
+        }
+    }
+
+    /// The file's window, if it is SIGMOID and applies to the samples
+    ///
+    /// A sigmoid cannot be spread over the value range like a linear window,
+    /// so such images look flat without it. 32-bit samples are renormalized
+    /// for display, which the window does not follow.
+    #[must_use]
+    pub fn declared_sigmoid(metadata: &DicomMetadata) -> Option<Self> {
+        metadata
+            .voi_window
+            .filter(|voi| voi.function == VoiLutFunction::Sigmoid)
+            .filter(|_| metadata.bits_allocated() != 32)
+            .map(|voi| Self::from_voi(&voi))
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn lower(&self) -> f64 {
+        self.center - self.width / 2.0
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn upper(&self) -> f64 {
+        self.center + self.width / 2.0
+    }
+}
+
+impl fmt::Display for DisplayWindow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "center={center}, width={width} [{lower}..{upper}]",
+            center = self.center,
+            width = self.width,
+            lower = self.lower(),
+            upper = self.upper()
+        )?;
+        if self.function == VoiLutFunction::Sigmoid {
+            write!(f, " sigmoid")?;
+        }
+        Ok(())
+    }
+}
+
+/// Common CT window presets, in Hounsfield units
+pub const HU_WINDOW_PRESETS: &[(&str, DisplayWindow)] = &[
+    ("brain", DisplayWindow::new(40.0, 80.0)),
+    ("subdural", DisplayWindow::new(75.0, 215.0)),
+    ("stroke", DisplayWindow::new(40.0, 40.0)),
+    ("lung", DisplayWindow::new(-600.0, 1500.0)),
+    ("mediastinum", DisplayWindow::new(50.0, 350.0)),
+    ("abdomen", DisplayWindow::new(60.0, 400.0)),
+    ("liver", DisplayWindow::new(30.0, 150.0)),
+    ("bone", DisplayWindow::new(400.0, 1800.0)),
+];
+
+/// Window presets that make sense for values in the given unit
+///
+/// HU presets only apply to Hounsfield units; other units have no
+/// meaningful fixed windows.
+#[must_use]
+pub fn window_presets(
+    rescale_type: Option<&RescaleType>,
+) -> &'static [(&'static str, DisplayWindow)] {
+    match rescale_type {
+        Some(rescale_type) if rescale_type.is_hounsfield() => HU_WINDOW_PRESETS,
+        _ => &[],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::decode_frame;
+    use crate::types::BitDepth;
+
+    /// 1x2 image of signed 12-bit samples, -1000 and 40
+    fn signed_ct() -> DicomMetadata {
+        let mut metadata = DicomMetadata::test_grayscale16(1, 2, &[0xfc18, 40]);
+        metadata.bit_depth = BitDepth {
+            signed: true,
+            ..BitDepth::new(16, 12)
+        };
+        metadata
+    }
+
+    #[test]
+    fn test_stored_statistics_of_signed_samples() {
+        let frame = decode_frame(&signed_ct()).unwrap();
+        let stats = PixelStatistics::from_frame(&frame).unwrap();
+        assert_eq!((stats.min, stats.max, stats.mean), (-1000.0, 40.0, -480.0));
+    }
+
+    #[test]
+    fn test_rescaled_statistics_of_signed_samples_are_negative() {
+        let mut metadata = signed_ct();
+        metadata.rescale = RescaleParams::new(1.0, -24.0);
+
+        let frame = decode_frame(&metadata).unwrap();
+        let rescaled = PixelStatistics::from_frame(&frame)
+            .unwrap()
+            .rescaled(&metadata.rescale);
+        assert_eq!((rescaled.min, rescaled.max), (-1024.0, 16.0));
+        assert_eq!(rescaled.to_string(), "min=-1024, max=16, mean=-504.0");
+    }
+}
@@ -0,0 +1,71 @@
+//! Mask subtraction, as in digital subtraction angiography (DSA)
+
+use super::frame::{Samples, decode_grayscale_frame};
+use crate::dicom::DicomMetadata;
+use crate::types::RescaleParams;
+use anyhow::{Result, bail};
+
+/// The frame at `index` with the frame at `mask` subtracted, as a single
+/// frame rescaled to the difference in modality values
+///
+/// Stored differences are offset to keep them positive, and the rescale
+/// intercept takes the offset back off, so that unchanged pixels are 0.
+///
+/// # Errors
+///
+/// Returns an error if the image is not grayscale or either frame does not
+/// exist
+pub fn subtract_mask(metadata: &DicomMetadata, index: u32, mask: u32) -> Result<DicomMetadata> {
+    if !metadata.photometric_interpretation.is_grayscale() || metadata.is_already_rgb() {
+        bail!("Frames can only be subtracted in grayscale images");
+    }
+
+    let frame = stored_values(metadata, index)?;
+    let mask = stored_values(metadata, mask)?;
+
+    // Differences of up to 15-bit values fit in 16 bits with the offset
+    let offset = 1_i64 << metadata.bits_stored().clamp(1, 15);
+    let differences: Vec<u16> = frame
+        .iter()
+        .zip(&mask)
+        .map(|(value, mask)| (value - mask + offset).clamp(0, i64::from(u16::MAX)) as u16)
+        .collect();
+
+    let slope = metadata.rescale_slope();
+    Ok(DicomMetadata {
+        rescale: RescaleParams::new(slope, -(offset as f64) * slope),
+        modality_lut: None,
+        // Differences are not in the units of the file's window
+        voi_window: None,
+        ..metadata.with_gray16_samples(&differences)
+    })
+}
+
+fn stored_values(metadata: &DicomMetadata, index: u32) -> Result<Vec<i64>> {
+    let frame = decode_grayscale_frame(&metadata.single_frame(index)?)?;
+    Ok(match frame.samples {
+        Samples::U8(values) => values.into_iter().map(i64::from).collect(),
+        Samples::U16(values) => values.into_iter().map(i64::from).collect(),
+        Samples::U32(values) => values.into_iter().map(i64::from).collect(),
+        Samples::I16(values) => values.into_iter().map(i64::from).collect(),
+        Samples::I32(values) => values.into_iter().map(i64::from).collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_subtract_mask() {
+        // Two 1x2 frames: the mask, then one with contrast in the first pixel
+        let mut metadata = DicomMetadata::test_grayscale16(1, 2, &[100, 50, 40, 50]);
+        metadata.bit_depth = crate::types::BitDepth::new(16, 12);
+
+        let subtracted = subtract_mask(&metadata, 1, 0).unwrap();
+        let frame = decode_grayscale_frame(&subtracted).unwrap();
+        let value = |x| subtracted.rescaled_value(frame.sample(x, 0).unwrap());
+        assert_eq!(subtracted.number_of_frames, 1);
+        assert_eq!((value(0), value(1)), (-60.0, 0.0));
+    }
+}
@@ -0,0 +1,130 @@
+use super::gsdf;
+use image::DynamicImage;
+
+/// Brightness, contrast, GSDF and gamma applied to normalized display levels
+///
+/// All of them are folded into one lookup table per channel. Contrast and
+/// brightness come first, then the GSDF mapping, then gamma, so that gamma
+/// can lift shadows that a terminal renders too dark.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ToneCurve {
+    /// Gamma per red, green and blue channel; above 1 brightens mid-tones
+    pub gamma: [f32; 3],
+    /// Offset added to levels, as a fraction of the full range (-1 to 1)
+    pub brightness: f32,
+    /// Factor scaling levels around mid-gray
+    pub contrast: f32,
+    /// Maximum luminance in cd/m² of the display the Grayscale Standard
+    /// Display Function is applied for, if it is applied
+    pub gsdf: Option<f32>,
+}
+
+impl Default for ToneCurve {
+    fn default() -> Self {
+        Self {
+            gamma: [1.0; 3],
+            brightness: 0.0,
+            contrast: 1.0,
+            gsdf: None,
+        }
+    }
+}
+
+impl ToneCurve {
+    /// Adjusted level of a channel, both on a 0-1 scale
+    #[must_use]
+    pub fn map(self, channel: usize, level: f32) -> f32 {
+        let level = ((level - 0.5).mul_add(self.contrast, 0.5) + self.brightness).clamp(0.0, 1.0);
+        let level = match self.gsdf {
+            Some(max_luminance) => gsdf::display_level(level, max_luminance),
+            None => level,
+        };
+        level.powf(1.0 / self.gamma[channel])
+    }
+
+    /// Whether all channels get the same curve, so gray stays gray
+    fn is_gray(self) -> bool {
+        self.gamma.iter().all(|&gamma| gamma == self.gamma[0])
+    }
+
+    /// Lookup table of a channel for levels `0..=max`
+    fn table(self, channel: usize, max: u16) -> Vec<u16> {
+        let scale = f32::from(max);
+        (0..=max)
+            .map(|level| (self.map(channel, f32::from(level) / scale) * scale).round() as u16)
+            .collect()
+    }
+
+    /// Adjust every pixel, keeping the bit depth
+    ///
+    /// Grayscale images become RGB when the channels have different gammas.
+    #[must_use]
+    pub fn apply(self, image: DynamicImage) -> DynamicImage {
+        match image {
+            DynamicImage::ImageLuma8(mut gray) if self.is_gray() => {
+                let table = self.table(0, u8::MAX.into());
+                for pixel in gray.pixels_mut() {
+                    pixel[0] = table[usize::from(pixel[0])] as u8;
+                }
+                DynamicImage::ImageLuma8(gray)
+            }
+            DynamicImage::ImageLuma16(mut gray) if self.is_gray() => {
+                let table = self.table(0, u16::MAX);
+                for pixel in gray.pixels_mut() {
+                    pixel[0] = table[usize::from(pixel[0])];
+                }
+                DynamicImage::ImageLuma16(gray)
+            }
+            DynamicImage::ImageLuma16(_) | DynamicImage::ImageRgb16(_) => {
+                let mut rgb = image.into_rgb16();
+                let tables = [0, 1, 2].map(|channel| self.table(channel, u16::MAX));
+                for pixel in rgb.pixels_mut() {
+                    for (v, table) in pixel.0.iter_mut().zip(&tables) {
+                        *v = table[usize::from(*v)];
+                    }
+                }
+                DynamicImage::ImageRgb16(rgb)
+            }
+            _ => {
+                let mut rgb = image.into_rgb8();
+                let tables = [0, 1, 2].map(|channel| self.table(channel, u8::MAX.into()));
+                for pixel in rgb.pixels_mut() {
+                    for (v, table) in pixel.0.iter_mut().zip(&tables) {
+                        *v = table[usize::from(*v)] as u8;
+                    }
+                }
+                DynamicImage::ImageRgb8(rgb)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tone_curve_levels() {
+        let identity = ToneCurve::default();
+        assert!((identity.map(0, 0.25) - 0.25).abs() < 1e-6);
+
+        let contrast = ToneCurve {
+            contrast: 2.0,
+            ..ToneCurve::default()
+        };
+        assert!((contrast.map(0, 0.75) - 1.0).abs() < 1e-6);
+        assert!((contrast.map(0, 0.5) - 0.5).abs() < 1e-6);
+
+        let gamma = ToneCurve {
+            gamma: [2.0, 1.0, 1.0],
+            brightness: 0.05,
+            ..ToneCurve::default()
+        };
+        assert!((gamma.map(0, 0.2) - 0.5).abs() < 1e-6);
+        assert!((gamma.map(1, 0.2) - 0.25).abs() < 1e-6);
+
+        // Different gammas per channel turn gray into color
+        let gray = DynamicImage::ImageLuma8(image::GrayImage::new(1, 1));
+        assert!(matches!(gamma.apply(gray), DynamicImage::ImageRgb8(_)));
+    }
+}
@@ -9,29 +9,16 @@ use image::{DynamicImage, ImageBuffer, RgbImage};
 /// Returns an error if pixel data extraction or YCbCr conversion fails
 pub fn convert_ycbcr(metadata: &DicomMetadata) -> Result<DynamicImage> {
     let pixel_data = extract_ycbcr_pixels(metadata)?;
+    let partial_range = metadata.photometric_interpretation.is_partial_range();
 
     let rgb_pixels: Vec<u8> = pixel_data
         .chunks_exact(3)
         .flat_map(|ycbcr| {
-            let y = f32::from(ycbcr[0]);
-            let cb = f32::from(ycbcr[1]);
-            let cr = f32::from(ycbcr[2]);
-
-            let r = y.mul_add(1.0_f32, (cr - 128.0_f32).mul_add(1.402_f32, 0.0_f32));
-            let g = y.mul_add(
-                1.0_f32,
-                (cb - 128.0_f32).mul_add(
-                    -0.344_136_f32,
-                    (cr - 128.0_f32).mul_add(-0.714_136_f32, 0.0_f32),
-                ),
-            );
-            let b = y.mul_add(1.0_f32, (cb - 128.0_f32).mul_add(1.772_f32, 0.0_f32));
-
-            [
-                r.clamp(0.0, 255.0) as u8,
-                g.clamp(0.0, 255.0) as u8,
-                b.clamp(0.0, 255.0) as u8,
-            ]
+            if partial_range {
+                ycbcr_partial_to_rgb(ycbcr[0], ycbcr[1], ycbcr[2])
+            } else {
+                ycbcr_full_to_rgb(ycbcr[0], ycbcr[1], ycbcr[2])
+            }
         })
         .collect();
 
@@ -45,6 +32,82 @@ pub fn convert_ycbcr(metadata: &DicomMetadata) -> Result<DynamicImage> {
     Ok(DynamicImage::ImageRgb8(rgb_image))
 }
 
+/// Full-range YCbCr (JPEG/JFIF coefficients) to RGB
+fn ycbcr_full_to_rgb(y: u8, cb: u8, cr: u8) -> [u8; 3] {
+    let y = f32::from(y);
+    let cb = f32::from(cb);
+    let cr = f32::from(cr);
+
+    let r = y.mul_add(1.0_f32, (cr - 128.0_f32).mul_add(1.402_f32, 0.0_f32));
+    let g = y.mul_add(
+        1.0_f32,
+        (cb - 128.0_f32).mul_add(
+            -0.344_136_f32,
+            (cr - 128.0_f32).mul_add(-0.714_136_f32, 0.0_f32),
+        ),
+    );
+    let b = y.mul_add(1.0_f32, (cb - 128.0_f32).mul_add(1.772_f32, 0.0_f32));
+
+    [
+        r.clamp(0.0, 255.0) as u8,
+        g.clamp(0.0, 255.0) as u8,
+        b.clamp(0.0, 255.0) as u8,
+    ]
+}
+
+/// Limited-range YCbCr (BT.601 video levels) to RGB
+fn ycbcr_partial_to_rgb(y: u8, cb: u8, cr: u8) -> [u8; 3] {
+    let y = (f32::from(y) - 16.0_f32) * 1.164_383_f32;
+    let cb = f32::from(cb) - 128.0_f32;
+    let cr = f32::from(cr) - 128.0_f32;
+
+    let r = cr.mul_add(1.596_027_f32, y);
+    let g = cb.mul_add(-0.391_762_f32, cr.mul_add(-0.812_968_f32, y));
+    let b = cb.mul_add(2.017_232_f32, y);
+
+    [
+        r.round().clamp(0.0, 255.0) as u8,
+        g.round().clamp(0.0, 255.0) as u8,
+        b.round().clamp(0.0, 255.0) as u8,
+    ]
+}
+
+/// How the chroma samples of a frame are subsampled relative to luma
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ChromaSubsampling {
+    /// 4:4:4, one Cb/Cr pair per pixel
+    Full,
+    /// 4:2:2, one Cb/Cr pair per two horizontal pixels
+    Horizontal,
+    /// 4:2:0, one Cb/Cr pair per 2x2 block of pixels
+    HorizontalVertical,
+}
+
+impl ChromaSubsampling {
+    fn frame_size(self, rows: usize, cols: usize) -> usize {
+        let pixel_count = rows * cols;
+
+        match self {
+            Self::Full => pixel_count * 3,
+            Self::Horizontal => pixel_count * 2,
+            Self::HorizontalVertical => pixel_count + 2 * rows.div_ceil(2) * cols.div_ceil(2),
+        }
+    }
+
+    /// Detect the subsampling from the size of the decoded data
+    ///
+    /// Decoders may already have upsampled the chroma, so the photometric
+    /// interpretation alone does not tell the layout.
+    fn detect(data_len: usize, frames: usize, rows: usize, cols: usize) -> Self {
+        let frames = frames.max(1);
+
+        [Self::Full, Self::Horizontal, Self::HorizontalVertical]
+            .into_iter()
+            .find(|s| s.frame_size(rows, cols) * frames == data_len)
+            .unwrap_or(Self::Full)
+    }
+}
+
 fn extract_ycbcr_pixels(metadata: &DicomMetadata) -> Result<Vec<u8>> {
     if metadata.bits_allocated() != 8 {
         anyhow::bail!(
@@ -58,62 +121,72 @@ fn extract_ycbcr_pixels(metadata: &DicomMetadata) -> Result<Vec<u8>> {
     let pixel_count = rows * cols;
 
     let data = metadata.pixel_data();
+    let subsampling =
+        ChromaSubsampling::detect(data.len(), metadata.number_of_frames as usize, rows, cols);
 
-    let pixel_data = if metadata.number_of_frames > 1 {
-        let expected_full_size = pixel_count * 3;
-        let expected_422_size = pixel_count * 2;
-
-        let total_frames = data.len() / expected_full_size;
-        let is_422 = data.len() == expected_422_size * total_frames;
-
-        let single_frame_size = if is_422 {
-            expected_422_size
-        } else {
-            expected_full_size
-        };
-
-        if data.len() > single_frame_size {
-            &data[..single_frame_size]
-        } else {
-            data
-        }
+    let frame_size = subsampling.frame_size(rows, cols);
+    let pixel_data = if metadata.number_of_frames > 1 && data.len() > frame_size {
+        &data[..frame_size]
     } else {
         data
     };
 
-    let has_422_subsampling = pixel_data.len() == pixel_count * 2;
-
-    match metadata.planar_configuration {
-        None | Some(0) => {
-            if has_422_subsampling {
-                Ok(upsample_ycbcr_422_interleaved(pixel_data, rows, cols))
-            } else {
-                if pixel_data.len() != pixel_count * 3 {
-                    anyhow::bail!(
-                        "Invalid YCbCr pixel data size: expected {} bytes, got {}",
-                        pixel_count * 3,
-                        pixel_data.len()
-                    );
-                }
-                Ok(pixel_data.to_vec())
-            }
+    match (subsampling, metadata.planar_configuration) {
+        (ChromaSubsampling::HorizontalVertical, _) => {
+            // 4:2:0 data is only produced by video decoders, which output
+            // separate Y, Cb and Cr planes regardless of planar configuration
+            Ok(upsample_ycbcr_420_planar(pixel_data, rows, cols))
         }
-        Some(1) => {
-            if has_422_subsampling {
-                Ok(upsample_ycbcr_422_planar(
-                    pixel_data,
-                    rows,
-                    cols,
-                    pixel_count,
-                ))
-            } else {
-                interleave_ycbcr_planar(pixel_data, pixel_count)
+        (ChromaSubsampling::Horizontal, None | Some(0)) => {
+            Ok(upsample_ycbcr_422_interleaved(pixel_data, rows, cols))
+        }
+        (ChromaSubsampling::Horizontal, Some(1)) => Ok(upsample_ycbcr_422_planar(
+            pixel_data,
+            rows,
+            cols,
+            pixel_count,
+        )),
+        (ChromaSubsampling::Full, None | Some(0)) => {
+            if pixel_data.len() != pixel_count * 3 {
+                anyhow::bail!(
+                    "Invalid YCbCr pixel data size: expected {} bytes, got {}",
+                    pixel_count * 3,
+                    pixel_data.len()
+                );
             }
+            Ok(pixel_data.to_vec())
         }
-        Some(other) => anyhow::bail!("Unsupported planar configuration for YCbCr: {other}"),
+        (ChromaSubsampling::Full, Some(1)) => interleave_ycbcr_planar(pixel_data, pixel_count),
+        (_, Some(other)) => anyhow::bail!("Unsupported planar configuration for YCbCr: {other}"),
     }
 }
 
+fn upsample_ycbcr_420_planar(pixel_data: &[u8], rows: usize, cols: usize) -> Vec<u8> {
+    let pixel_count = rows * cols;
+    let chroma_cols = cols.div_ceil(2);
+    let chroma_size = rows.div_ceil(2) * chroma_cols;
+
+    let y_plane = &pixel_data[..pixel_count];
+    let cb_plane = &pixel_data[pixel_count..pixel_count + chroma_size];
+    let cr_plane = &pixel_data[pixel_count + chroma_size..pixel_count + chroma_size * 2];
+
+    let mut output = vec![0u8; pixel_count * 3];
+
+    for y in 0..rows {
+        // Each chroma row covers two luma rows
+        let chroma_row = (y / 2) * chroma_cols;
+
+        for x in 0..cols {
+            let out_idx = (y * cols + x) * 3;
+            output[out_idx] = y_plane[y * cols + x];
+            output[out_idx + 1] = cb_plane[chroma_row + x / 2];
+            output[out_idx + 2] = cr_plane[chroma_row + x / 2];
+        }
+    }
+
+    output
+}
+
 fn upsample_ycbcr_422_interleaved(pixel_data: &[u8], rows: usize, cols: usize) -> Vec<u8> {
     let pixel_count = rows * cols;
     let mut output = vec![0u8; pixel_count * 3];
@@ -185,3 +258,45 @@ fn interleave_ycbcr_planar(pixel_data: &[u8], pixel_count: usize) -> Result<Vec<
 
     Ok(interleaved)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_partial_range_levels() {
+        assert_eq!(ycbcr_partial_to_rgb(16, 128, 128), [0, 0, 0]);
+        assert_eq!(ycbcr_partial_to_rgb(235, 128, 128), [255, 255, 255]);
+        assert_eq!(ycbcr_full_to_rgb(255, 128, 128), [255, 255, 255]);
+    }
+
+    #[test]
+    fn test_detect_subsampling() {
+        assert_eq!(
+            ChromaSubsampling::detect(4 * 4 * 3, 1, 4, 4),
+            ChromaSubsampling::Full
+        );
+        assert_eq!(
+            ChromaSubsampling::detect(4 * 4 * 2 * 3, 3, 4, 4),
+            ChromaSubsampling::Horizontal
+        );
+        assert_eq!(
+            ChromaSubsampling::detect(16 + 8, 1, 4, 4),
+            ChromaSubsampling::HorizontalVertical
+        );
+    }
+
+    #[test]
+    fn test_upsample_420() {
+        // 2x4 image: one chroma row shared by both luma rows
+        let data = [
+            1, 2, 3, 4, 5, 6, 7, 8, // Y
+            10, 11, // Cb
+            20, 21, // Cr
+        ];
+        let output = upsample_ycbcr_420_planar(&data, 2, 4);
+
+        assert_eq!(&output[..6], &[1, 10, 20, 2, 10, 20]);
+        assert_eq!(&output[15..], &[6, 10, 20, 7, 11, 21, 8, 11, 21]);
+    }
+}
@@ -0,0 +1,277 @@
+use super::{InstanceEntry, VolumeAttributes};
+use crate::types::{ImageOrientation, TransferSyntax};
+use anyhow::{Context, Result};
+use serde_json::{Map, Value, json};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// Index file name used when none is given, created in the scanned directory
+pub const DEFAULT_INDEX_FILE: &str = ".dcmv-index.json";
+
+/// Bumped when the stored fields change, so older indexes are rebuilt
+const INDEX_VERSION: u64 = 4;
+
+/// Modification time and size, to detect files changed since indexing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Stamp {
+    secs: u64,
+    nanos: u32,
+    size: u64,
+}
+
+impl Stamp {
+    fn of(path: &Path) -> Option<Self> {
+        let metadata = fs::metadata(path).ok()?;
+        let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+
+        Some(Self {
+            secs: modified.as_secs(),
+            nanos: modified.subsec_nanos(),
+            size: metadata.len(),
+        })
+    }
+}
+
+/// Cached header of one file; `None` when the file is not DICOM
+type CachedFile = (Stamp, Option<InstanceEntry>);
+
+/// On-disk cache of the headers read while scanning a directory
+///
+/// Entries are keyed by path relative to the scanned directory and reused
+/// while the file's modification time and size are unchanged. Files that
+/// are not DICOM are cached too, so they are not parsed again. Only files
+/// seen in the latest scan are saved.
+#[derive(Debug)]
+pub struct IndexCache {
+    root: PathBuf,
+    file: PathBuf,
+    previous: HashMap<String, CachedFile>,
+    current: HashMap<String, CachedFile>,
+    hits: usize,
+}
+
+impl IndexCache {
+    /// Load the index of `root` from `file`
+    ///
+    /// A missing, unreadable or outdated index starts out empty.
+    #[must_use]
+    pub fn load(root: &Path, file: &Path) -> Self {
+        let previous = fs::read(file)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice::<Value>(&bytes).ok())
+            .map(|index| parse_index(root, &index))
+            .unwrap_or_default();
+
+        Self {
+            root: root.to_path_buf(),
+            file: file.to_path_buf(),
+            previous,
+            current: HashMap::new(),
+            hits: 0,
+        }
+    }
+
+    /// Path of the index file
+    #[must_use]
+    pub fn file(&self) -> &Path {
+        &self.file
+    }
+
+    /// Number of files taken from the index instead of being read
+    #[must_use]
+    pub fn hits(&self) -> usize {
+        self.hits
+    }
+
+    /// Header of `path` from the index, if the file is unchanged since it
+    /// was indexed
+    ///
+    /// Returns `Some(None)` for a file indexed as not DICOM, and `None` if
+    /// the file has to be read, and then passed to [`IndexCache::insert`].
+    pub(super) fn cached(&mut self, path: &Path) -> Option<Option<InstanceEntry>> {
+        let key = self.key(path)?;
+        let stamp = Stamp::of(path)?;
+        let (cached, instance) = self.previous.get(&key)?;
+        if *cached != stamp {
+            return None;
+        }
+
+        self.hits += 1;
+        let instance = instance.clone();
+        self.current.insert(key, (stamp, instance.clone()));
+        Some(instance)
+    }
+
+    /// Remember the header read from `path`, `None` if it is not DICOM
+    pub(super) fn insert(&mut self, path: &Path, instance: Option<InstanceEntry>) {
+        if let (Some(key), Some(stamp)) = (self.key(path), Stamp::of(path)) {
+            self.current.insert(key, (stamp, instance));
+        }
+    }
+
+    fn key(&self, path: &Path) -> Option<String> {
+        path.strip_prefix(&self.root)
+            .ok()
+            .and_then(Path::to_str)
+            .map(str::to_string)
+    }
+
+    /// Write the entries of the latest scan to the index file
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the index file cannot be written
+    pub fn save(&self) -> Result<()> {
+        let files: Map<String, Value> = self
+            .current
+            .iter()
+            .map(|(key, (stamp, instance))| {
+                let value = json!({
+                    "modified": [stamp.secs, stamp.nanos],
+                    "size": stamp.size,
+                    "instance": instance.as_ref().map(instance_to_json),
+                });
+                (key.clone(), value)
+            })
+            .collect();
+
+        let index = json!({ "version": INDEX_VERSION, "files": files });
+
+        // Write next to the index and rename, so readers never see a partial file
+        let partial = self.file.with_extension("tmp");
+        fs::write(&partial, index.to_string())
+            .and_then(|()| fs::rename(&partial, &self.file))
+            .with_context(|| format!("Failed to write index: {}", self.file.display()))
+    }
+}
+
+fn parse_index(root: &Path, index: &Value) -> HashMap<String, CachedFile> {
+    if index.get("version").and_then(Value::as_u64) != Some(INDEX_VERSION) {
+        return HashMap::new();
+    }
+
+    let Some(files) = index.get("files").and_then(Value::as_object) else {
+        return HashMap::new();
+    };
+
+    files
+        .iter()
+        .filter_map(|(key, value)| {
+            let modified = value.get("modified")?.as_array()?;
+            let stamp = Stamp {
+                secs: modified.first()?.as_u64()?,
+                nanos: u32::try_from(modified.get(1)?.as_u64()?).ok()?,
+                size: value.get("size")?.as_u64()?,
+            };
+
+            let instance = match value.get("instance")? {
+                Value::Null => None,
+                instance => Some(instance_from_json(root.join(key), instance)?),
+            };
+
+            Some((key.clone(), (stamp, instance)))
+        })
+        .collect()
+}
+
+fn instance_to_json(instance: &InstanceEntry) -> Value {
+    json!({
+        "study_uid": instance.study_uid,
+        "series_uid": instance.series_uid,
+        "sop_instance_uid": instance.sop_instance_uid,
+        "study_date": instance.study_date,
+        "study_description": instance.study_description,
+        "modality": instance.modality,
+        "series_number": instance.series_number,
+        "series_description": instance.series_description,
+        "rows": instance.rows,
+        "columns": instance.columns,
+        "transfer_syntax": {
+            "uid": instance.transfer_syntax.uid,
+            "name": instance.transfer_syntax.name,
+        },
+        "instance_number": instance.instance_number,
+        "position": instance.position,
+        "orientation": instance
+            .orientation
+            .map(|o| [o.row, o.column].concat()),
+        "echo_number": instance.volume.echo_number,
+        "echo_time": instance.volume.echo_time,
+        "b_value": instance.volume.b_value,
+        "trigger_time": instance.volume.trigger_time,
+        "temporal_position": instance.volume.temporal_position,
+    })
+}
+
+fn instance_from_json(path: PathBuf, value: &Value) -> Option<InstanceEntry> {
+    let string = |key: &str| value.get(key).and_then(Value::as_str).map(String::from);
+    let number = |key: &str| value.get(key).and_then(Value::as_i64);
+    let int = |key: &str| number(key).and_then(|n| i32::try_from(n).ok());
+    let float = |key: &str| value.get(key).and_then(Value::as_f64);
+    let floats = |key: &str| -> Option<Vec<f64>> {
+        value
+            .get(key)?
+            .as_array()?
+            .iter()
+            .map(Value::as_f64)
+            .collect()
+    };
+    let position = floats("position").and_then(|p| <[f64; 3]>::try_from(p).ok());
+    let orientation = floats("orientation")
+        .filter(|v| v.len() == 6)
+        .map(|v| ImageOrientation::new([v[0], v[1], v[2]], [v[3], v[4], v[5]]));
+    let transfer_syntax = value.get("transfer_syntax")?;
+
+    Some(InstanceEntry {
+        path,
+        study_uid: string("study_uid"),
+        series_uid: string("series_uid"),
+        sop_instance_uid: string("sop_instance_uid"),
+        study_date: string("study_date"),
+        study_description: string("study_description"),
+        modality: string("modality"),
+        series_number: int("series_number"),
+        series_description: string("series_description"),
+        rows: number("rows").and_then(|n| u16::try_from(n).ok()),
+        columns: number("columns").and_then(|n| u16::try_from(n).ok()),
+        transfer_syntax: TransferSyntax::new(
+            transfer_syntax.get("uid")?.as_str()?.to_string(),
+            transfer_syntax.get("name")?.as_str()?.to_string(),
+        ),
+        instance_number: int("instance_number"),
+        position,
+        orientation,
+        volume: VolumeAttributes {
+            echo_number: int("echo_number"),
+            echo_time: float("echo_time"),
+            b_value: float("b_value"),
+            trigger_time: float("trigger_time"),
+            temporal_position: int("temporal_position"),
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::scan_directory;
+
+    #[test]
+    fn test_unchanged_files_come_from_the_index() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("notes.txt"), "not a DICOM file").unwrap();
+        let index_file = dir.path().join(DEFAULT_INDEX_FILE);
+
+        let mut cache = IndexCache::load(dir.path(), &index_file);
+        let scan = scan_directory(dir.path(), Some(&mut cache), None).unwrap();
+        assert_eq!((scan.skipped.len(), cache.hits()), (1, 0));
+        cache.save().unwrap();
+
+        // The index file itself is not scanned
+        let mut cache = IndexCache::load(dir.path(), &index_file);
+        let scan = scan_directory(dir.path(), Some(&mut cache), None).unwrap();
+        assert_eq!((scan.skipped.len(), cache.hits()), (1, 1));
+    }
+}
@@ -0,0 +1,295 @@
+//! Slice geometry of a series: spacing, orientation and completeness (for
+//! --check, and when browsing a series)
+//!
+//! Stepping through a series and reconstructing other planes from it both
+//! assume parallel, evenly spaced slices with none missing. Each volume of
+//! the series is checked on its own, from the file headers alone.
+
+use super::{InstanceEntry, Series, Volume};
+use crate::types::ImageOrientation;
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Largest difference in direction cosines between slices of one
+/// orientation
+const ORIENTATION_TOLERANCE: f64 = 1e-3;
+
+/// Largest deviation from the usual slice spacing, relative to it, that
+/// is not reported
+const SPACING_TOLERANCE: f64 = 0.05;
+
+/// What was found checking the slices of a series
+#[derive(Debug, Clone, PartialEq)]
+pub struct GeometryReport {
+    pub slices: usize,
+    pub volumes: usize,
+    /// Distance between most neighboring slices along the slice normal, in
+    /// mm, if the slices have positions
+    pub spacing: Option<f64>,
+    /// Each inconsistency found, e.g. "Gap of 10 mm between slices at 40
+    /// and 50 mm, 1 slice missing"
+    pub problems: Vec<String>,
+}
+
+impl fmt::Display for GeometryReport {
+    /// e.g. "48 slices in 2 volumes, 2.5 mm apart, consistent"
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} slices", self.slices)?;
+        if self.volumes > 1 {
+            write!(f, " in {} volumes", self.volumes)?;
+        }
+        if let Some(spacing) = self.spacing {
+            write!(f, ", {} mm apart", mm(spacing))?;
+        }
+        if self.problems.is_empty() {
+            write!(f, ", consistent")?;
+        }
+        Ok(())
+    }
+}
+
+/// Check the slices of each volume of `series` for one orientation, even
+/// spacing and consecutive instance numbers
+#[must_use]
+pub fn check_series(series: &Series) -> GeometryReport {
+    let volumes = series.volumes();
+    let mut report = GeometryReport {
+        slices: series.instances.len(),
+        volumes: volumes.len(),
+        spacing: None,
+        problems: Vec::new(),
+    };
+
+    for (index, volume) in volumes.iter().enumerate() {
+        let start = report.problems.len();
+        check_orientation(volume, &mut report.problems);
+        let spacing = check_spacing(volume, &mut report.problems);
+        check_instance_numbers(volume, &mut report.problems);
+        report.spacing = report.spacing.or(spacing);
+
+        if volumes.len() > 1 {
+            for problem in &mut report.problems[start..] {
+                *problem = format!("Volume {}: {problem}", index + 1);
+            }
+        }
+    }
+
+    report
+}
+
+fn check_orientation(volume: &Volume, problems: &mut Vec<String>) {
+    let slices = &volume.instances;
+    let missing = slices.iter().filter(|s| s.orientation.is_none()).count();
+    if missing > 0 && missing < slices.len() {
+        problems.push(format!(
+            "{missing} of {} slices have no Image Orientation (Patient)",
+            slices.len()
+        ));
+    }
+
+    let Some((first, reference)) = oriented(slices).next() else {
+        return;
+    };
+    let differing: Vec<&InstanceEntry> = oriented(slices)
+        .filter(|(_, orientation)| !same_orientation(orientation, &reference))
+        .map(|(slice, _)| slice)
+        .collect();
+    if let Some(other) = differing.first() {
+        problems.push(format!(
+            "{} of {} slices are oriented differently from {}, e.g. {}",
+            differing.len(),
+            slices.len(),
+            first.path.display(),
+            other.path.display()
+        ));
+    }
+}
+
+fn oriented<'a>(
+    slices: &[&'a InstanceEntry],
+) -> impl Iterator<Item = (&'a InstanceEntry, ImageOrientation)> {
+    slices
+        .iter()
+        .filter_map(|&slice| Some((slice, slice.orientation?)))
+}
+
+fn same_orientation(a: &ImageOrientation, b: &ImageOrientation) -> bool {
+    a.row
+        .iter()
+        .chain(&a.column)
+        .zip(b.row.iter().chain(&b.column))
+        .all(|(a, b)| (a - b).abs() <= ORIENTATION_TOLERANCE)
+}
+
+/// Report uneven gaps between the slices, returning the usual spacing
+fn check_spacing(volume: &Volume, problems: &mut Vec<String>) -> Option<f64> {
+    let slices = &volume.instances;
+    let missing = slices.iter().filter(|s| s.position.is_none()).count();
+    if missing > 0 && missing < slices.len() {
+        problems.push(format!(
+            "{missing} of {} slices have no Image Position (Patient)",
+            slices.len()
+        ));
+    }
+
+    let normal = slices.iter().find_map(|s| s.orientation)?.normal();
+    let mut distances: Vec<f64> = slices
+        .iter()
+        .filter_map(|s| s.position)
+        .map(|p| p[0] * normal[0] + p[1] * normal[1] + p[2] * normal[2])
+        .collect();
+    distances.sort_by(f64::total_cmp);
+
+    let gaps: Vec<f64> = distances.windows(2).map(|d| d[1] - d[0]).collect();
+    if gaps.is_empty() {
+        return None;
+    }
+    let spacing = median(&gaps);
+
+    let tolerance = (spacing * SPACING_TOLERANCE).max(0.01);
+    for (pair, &gap) in distances.windows(2).zip(&gaps) {
+        if (gap - spacing).abs() <= tolerance {
+            continue;
+        }
+        let (from, to) = (mm(pair[0]), mm(pair[1]));
+        let problem = if gap > spacing && spacing > 0.0 {
+            // Rounded to slices, but at least one
+            let missing = ((gap / spacing).round() as usize).max(2) - 1;
+            let plural = if missing == 1 { "" } else { "s" };
+            format!(
+                "Gap of {} mm between slices at {from} and {to} mm, {missing} slice{plural} missing",
+                mm(gap)
+            )
+        } else if gap == 0.0 {
+            format!("Two slices at {from} mm")
+        } else {
+            format!("Slices at {from} and {to} mm only {} mm apart", mm(gap))
+        };
+        problems.push(problem);
+    }
+
+    Some(spacing)
+}
+
+/// Report instance numbers skipped or used twice
+///
+/// The numbers are expected to go up by the same step throughout, which is
+/// more than one for a volume interleaved with others.
+fn check_instance_numbers(volume: &Volume, problems: &mut Vec<String>) {
+    let mut counts: BTreeMap<i32, usize> = BTreeMap::new();
+    for number in volume.instances.iter().filter_map(|s| s.instance_number) {
+        *counts.entry(number).or_default() += 1;
+    }
+
+    for (number, count) in &counts {
+        if *count > 1 {
+            problems.push(format!("Instance Number {number} used by {count} slices"));
+        }
+    }
+
+    let numbers: Vec<i32> = counts.into_keys().collect();
+    let steps: Vec<i32> = numbers.windows(2).map(|n| n[1] - n[0]).collect();
+    let Some(step) = most_common(&steps) else {
+        return;
+    };
+
+    for pair in numbers.windows(2) {
+        let skipped = (pair[1] - pair[0]) / step - 1;
+        if skipped <= 0 {
+            continue;
+        }
+        let (first, last) = (pair[0] + step, pair[1] - step);
+        problems.push(if skipped == 1 {
+            format!("Instance Number {first} missing")
+        } else if step == 1 {
+            format!("Instance Numbers {first}-{last} missing")
+        } else {
+            format!(
+                "{skipped} Instance Numbers missing between {} and {}",
+                pair[0], pair[1]
+            )
+        });
+    }
+}
+
+fn median(values: &[f64]) -> f64 {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    sorted[sorted.len() / 2]
+}
+
+fn most_common(values: &[i32]) -> Option<i32> {
+    let mut counts: BTreeMap<i32, usize> = BTreeMap::new();
+    for &value in values {
+        *counts.entry(value).or_default() += 1;
+    }
+    counts
+        .into_iter()
+        .max_by_key(|&(_, count)| count)
+        .map(|(value, _)| value)
+}
+
+/// A distance in mm, to 0.01 mm without trailing zeros
+fn mm(value: f64) -> String {
+    ((value * 100.0).round() / 100.0).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::VolumeAttributes;
+    use crate::types::TransferSyntax;
+    use std::path::PathBuf;
+
+    fn slice(number: i32, z: f64) -> InstanceEntry {
+        InstanceEntry {
+            path: PathBuf::from(format!("{number}.dcm")),
+            study_uid: None,
+            series_uid: None,
+            sop_instance_uid: None,
+            study_date: None,
+            study_description: None,
+            modality: Some("CT".to_string()),
+            series_number: Some(1),
+            series_description: None,
+            rows: Some(512),
+            columns: Some(512),
+            transfer_syntax: TransferSyntax::new(
+                "1.2.840.10008.1.2.1".to_string(),
+                "Explicit VR Little Endian".to_string(),
+            ),
+            instance_number: Some(number),
+            position: Some([0.0, 0.0, z]),
+            orientation: Some(ImageOrientation::new([1.0, 0.0, 0.0], [0.0, 1.0, 0.0])),
+            volume: VolumeAttributes::default(),
+        }
+    }
+
+    fn check(instances: &[InstanceEntry]) -> GeometryReport {
+        check_series(&Series {
+            study_uid: None,
+            series_uid: None,
+            instances: instances.iter().collect(),
+        })
+    }
+
+    #[test]
+    fn test_check_series_reports_gaps() {
+        let even: Vec<InstanceEntry> = (1..=5).map(|n| slice(n, f64::from(n) * 2.5)).collect();
+        let report = check(&even);
+        assert_eq!(report.spacing, Some(2.5));
+        assert!(report.problems.is_empty(), "{:?}", report.problems);
+
+        // Slices 3 and 4 missing, slice 5 tilted
+        let mut gaps = vec![slice(1, 0.0), slice(2, 2.5), slice(5, 10.0), slice(6, 12.5)];
+        gaps[2].orientation = Some(ImageOrientation::new([1.0, 0.0, 0.0], [0.0, 0.8, 0.6]));
+        assert_eq!(
+            check(&gaps).problems,
+            [
+                "1 of 4 slices are oriented differently from 1.dcm, e.g. 5.dcm",
+                "Gap of 7.5 mm between slices at 2.5 and 10 mm, 2 slices missing",
+                "Instance Numbers 3-4 missing",
+            ]
+        );
+    }
+}
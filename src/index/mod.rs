@@ -0,0 +1,564 @@
+//! Directory scanning and grouping of DICOM files into studies and series
+
+mod cache;
+mod check;
+mod volume;
+
+pub use cache::{DEFAULT_INDEX_FILE, IndexCache};
+pub use check::{GeometryReport, check_series};
+pub use volume::{Volume, VolumeAttributes, split_volumes, volume_files};
+
+use crate::dicom::{self, DicomObject};
+use crate::interrupt::{self, Cancellable, Interrupted};
+use crate::stack;
+use crate::terminal::Stream;
+use crate::types::{ImageOrientation, TransferSyntax};
+use ::dicom::core::Tag;
+use ::dicom::dictionary_std::tags;
+use anyhow::{Context, Result};
+use crossterm::cursor::MoveToColumn;
+use crossterm::execute;
+use crossterm::style::Print;
+use crossterm::terminal::{Clear, ClearType};
+use rayon::prelude::*;
+use std::collections::BTreeMap;
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::Duration;
+
+/// How often the number of files scanned is redrawn
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Header summary of one DICOM file, read without its pixel data
+#[derive(Debug, Clone, PartialEq)]
+pub struct InstanceEntry {
+    pub path: PathBuf,
+    pub study_uid: Option<String>,
+    pub series_uid: Option<String>,
+    pub sop_instance_uid: Option<String>,
+    pub study_date: Option<String>,
+    pub study_description: Option<String>,
+    pub modality: Option<String>,
+    pub series_number: Option<i32>,
+    pub series_description: Option<String>,
+    pub rows: Option<u16>,
+    pub columns: Option<u16>,
+    pub transfer_syntax: TransferSyntax,
+    pub instance_number: Option<i32>,
+    /// Image Position (Patient), to find repeated slice positions
+    pub position: Option<[f64; 3]>,
+    /// Image Orientation (Patient)
+    pub orientation: Option<ImageOrientation>,
+    pub volume: VolumeAttributes,
+}
+
+impl InstanceEntry {
+    /// Read the header of a DICOM file
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file is not a readable DICOM file
+    pub fn read(path: &Path) -> Result<Self> {
+        let obj = dicom::open_dicom_header(path)?;
+        Ok(Self::from_object(path, &obj))
+    }
+
+    fn from_object(path: &Path, obj: &DicomObject) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            study_uid: string_tag(obj, tags::STUDY_INSTANCE_UID),
+            series_uid: string_tag(obj, tags::SERIES_INSTANCE_UID),
+            sop_instance_uid: string_tag(obj, tags::SOP_INSTANCE_UID)
+                .map(|uid| uid.trim_end_matches('\0').to_string()),
+            study_date: string_tag(obj, tags::STUDY_DATE),
+            study_description: string_tag(obj, tags::STUDY_DESCRIPTION),
+            modality: string_tag(obj, tags::MODALITY),
+            series_number: obj
+                .get(tags::SERIES_NUMBER)
+                .and_then(|e| e.to_int::<i32>().ok()),
+            series_description: string_tag(obj, tags::SERIES_DESCRIPTION),
+            rows: obj.get(tags::ROWS).and_then(|e| e.to_int::<u16>().ok()),
+            columns: obj.get(tags::COLUMNS).and_then(|e| e.to_int::<u16>().ok()),
+            transfer_syntax: dicom::extract_transfer_syntax(obj),
+            instance_number: obj
+                .get(tags::INSTANCE_NUMBER)
+                .and_then(|e| e.to_int::<i32>().ok()),
+            position: obj
+                .get(tags::IMAGE_POSITION_PATIENT)
+                .and_then(|e| e.to_multi_float64().ok())
+                .and_then(|values| <[f64; 3]>::try_from(values).ok()),
+            orientation: obj
+                .get(tags::IMAGE_ORIENTATION_PATIENT)
+                .and_then(|e| e.to_multi_float64().ok())
+                .filter(|values| values.len() == 6)
+                .map(|v| ImageOrientation::new([v[0], v[1], v[2]], [v[3], v[4], v[5]])),
+            volume: VolumeAttributes::from_object(obj),
+        }
+    }
+
+    /// "COLSxROWS", if both are present
+    #[must_use]
+    pub fn dimensions(&self) -> Option<String> {
+        Some(format!("{}x{}", self.columns?, self.rows?))
+    }
+}
+
+/// Headers of the DICOM files among `paths`, with directories standing for
+/// the files directly in them
+///
+/// Files that are not DICOM are left out.
+///
+/// # Errors
+///
+/// Returns an error if a directory cannot be listed, or reading is
+/// interrupted with Ctrl-C
+pub fn read_instances(paths: &[PathBuf]) -> Result<Vec<InstanceEntry>> {
+    Ok(read_files(paths)?.instances)
+}
+
+/// Headers of the files among `paths`, read in parallel, with directories
+/// standing for the files directly in them
+///
+/// # Errors
+///
+/// Returns an error if a directory cannot be listed, or reading is
+/// interrupted with Ctrl-C
+pub fn read_files(paths: &[PathBuf]) -> Result<Scan> {
+    let _cancellable = Cancellable::new();
+    let files = stack::expand_directories(paths)?;
+    let all: Vec<usize> = (0..files.len()).collect();
+    let read = read_headers(&files, &all, None)?;
+
+    let mut scan = Scan::default();
+    for (path, instance) in files.into_iter().zip(read) {
+        match instance {
+            Some(instance) => scan.instances.push(instance),
+            None => scan.skipped.push(path),
+        }
+    }
+    Ok(scan)
+}
+
+fn string_tag(obj: &DicomObject, tag: Tag) -> Option<String> {
+    obj.get(tag)
+        .and_then(|e| e.value().to_str().ok())
+        .map(|s| dicom::TextDecoder::from_object(obj).decode(&s))
+        .filter(|s| !s.is_empty())
+}
+
+/// Result of scanning a directory tree
+#[derive(Debug, Clone, Default)]
+pub struct Scan {
+    pub instances: Vec<InstanceEntry>,
+    /// Files that could not be read as DICOM
+    pub skipped: Vec<PathBuf>,
+}
+
+/// Read the headers of all DICOM files below `dir`
+///
+/// Directories are visited recursively in name order; symbolic links to
+/// directories are not followed. Headers are read in parallel, with the
+/// number of files read so far shown on `progress`. With a `cache`,
+/// unchanged files are taken from the index instead of being read.
+///
+/// # Errors
+///
+/// Returns an error if a directory cannot be listed, or the scan is
+/// interrupted with Ctrl-C
+pub fn scan_directory(
+    dir: &Path,
+    mut cache: Option<&mut IndexCache>,
+    progress: Option<Stream>,
+) -> Result<Scan> {
+    let _cancellable = Cancellable::new();
+    let mut files = Vec::new();
+    let index_file = cache.as_deref().and_then(|cache| cache.file().file_name());
+    list_files(dir, index_file, &mut files)?;
+
+    let mut instances: Vec<Option<Option<InstanceEntry>>> = files
+        .iter()
+        .map(|path| cache.as_deref_mut().and_then(|cache| cache.cached(path)))
+        .collect();
+    let pending: Vec<usize> = (0..files.len())
+        .filter(|&i| instances[i].is_none())
+        .collect();
+
+    let read = read_headers(&files, &pending, progress)?;
+    for (i, instance) in pending.into_iter().zip(read) {
+        if let Some(cache) = cache.as_deref_mut() {
+            cache.insert(&files[i], instance.clone());
+        }
+        instances[i] = Some(instance);
+    }
+
+    let mut scan = Scan::default();
+    for (path, instance) in files.into_iter().zip(instances) {
+        match instance.flatten() {
+            Some(instance) => scan.instances.push(instance),
+            None => scan.skipped.push(path),
+        }
+    }
+    Ok(scan)
+}
+
+/// Append the files below `dir` to `files`, in name order, leaving out the
+/// index file
+fn list_files(dir: &Path, index_file: Option<&OsStr>, files: &mut Vec<PathBuf>) -> Result<()> {
+    let mut entries = fs::read_dir(dir)
+        .with_context(|| format!("Failed to read directory: {}", dir.display()))?
+        .collect::<Result<Vec<_>, _>>()
+        .with_context(|| format!("Failed to read directory: {}", dir.display()))?;
+    entries.sort_by_key(fs::DirEntry::file_name);
+
+    for entry in entries {
+        if interrupt::requested() {
+            return Err(Interrupted.into());
+        }
+        if entry.file_type().is_ok_and(|file_type| file_type.is_dir()) {
+            list_files(&entry.path(), index_file, files)?;
+        } else if index_file != Some(entry.file_name().as_os_str()) {
+            files.push(entry.path());
+        }
+    }
+
+    Ok(())
+}
+
+/// Headers of `files[i]` for each of `pending`, `None` for files that are
+/// not DICOM
+fn read_headers(
+    files: &[PathBuf],
+    pending: &[usize],
+    progress: Option<Stream>,
+) -> Result<Vec<Option<InstanceEntry>>> {
+    let done = AtomicUsize::new(0);
+    let read = thread::scope(|scope| {
+        let reader = scope.spawn(|| {
+            pending
+                .par_iter()
+                .map(|&i| {
+                    // Files left after Ctrl-C are skipped; the result is dropped
+                    if interrupt::requested() {
+                        return None;
+                    }
+                    let instance = InstanceEntry::read(&files[i]).ok();
+                    done.fetch_add(1, Ordering::Relaxed);
+                    instance
+                })
+                .collect::<Vec<_>>()
+        });
+
+        if let Some(mut out) = progress.map(Stream::writer) {
+            while !reader.is_finished() {
+                let count = done.load(Ordering::Relaxed);
+                let _ = execute!(
+                    out,
+                    MoveToColumn(0),
+                    Clear(ClearType::UntilNewLine),
+                    Print(format!("Scanning [{count}/{} files]", pending.len()))
+                );
+                thread::sleep(PROGRESS_INTERVAL);
+            }
+            let _ = execute!(out, MoveToColumn(0), Clear(ClearType::CurrentLine));
+        }
+        reader
+            .join()
+            .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+    });
+
+    if interrupt::requested() {
+        return Err(Interrupted.into());
+    }
+    Ok(read)
+}
+
+/// Instances sharing a Study and Series Instance UID
+#[derive(Debug, Clone)]
+pub struct Series<'a> {
+    pub study_uid: Option<&'a str>,
+    pub series_uid: Option<&'a str>,
+    pub instances: Vec<&'a InstanceEntry>,
+}
+
+impl<'a> Series<'a> {
+    /// Instance whose tags describe the series
+    #[must_use]
+    pub fn first(&self) -> &'a InstanceEntry {
+        self.instances[0]
+    }
+
+    /// "Series N (DESCRIPTION)", as far as the tags tell
+    #[must_use]
+    pub fn name(&self) -> String {
+        let first = self.first();
+        let number = first
+            .series_number
+            .map_or_else(|| "-".to_string(), |n| n.to_string());
+        match &first.series_description {
+            Some(description) => format!("Series {number} ({description})"),
+            None => format!("Series {number}"),
+        }
+    }
+
+    /// Common value of a per-instance property, or "mixed"
+    fn common(&self, value: impl Fn(&InstanceEntry) -> Option<String>) -> Option<String> {
+        let first = value(self.first());
+        if self
+            .instances
+            .iter()
+            .all(|instance| value(instance) == first)
+        {
+            first
+        } else {
+            Some("mixed".to_string())
+        }
+    }
+
+    fn sort_key(
+        &self,
+    ) -> (
+        Option<&'a str>,
+        Option<&'a str>,
+        Option<i32>,
+        Option<&'a str>,
+    ) {
+        let first = self.first();
+        (
+            first.study_date.as_deref(),
+            self.study_uid,
+            first.series_number,
+            self.series_uid,
+        )
+    }
+
+    #[must_use]
+    pub fn dimensions(&self) -> Option<String> {
+        self.common(InstanceEntry::dimensions)
+    }
+
+    #[must_use]
+    pub fn transfer_syntax(&self) -> Option<String> {
+        self.common(|instance| Some(instance.transfer_syntax.name.clone()))
+    }
+
+    /// The volumes of a 4D series, or the whole series as one volume
+    #[must_use]
+    pub fn volumes(&self) -> Vec<Volume<'a>> {
+        split_volumes(&self.instances)
+    }
+}
+
+/// Group instances into series, ordered by study (date, then UID) and
+/// series number within each study
+///
+/// Files without UIDs are grouped together under a missing UID.
+#[must_use]
+pub fn group_series(instances: &[InstanceEntry]) -> Vec<Series<'_>> {
+    let mut groups: BTreeMap<(Option<&str>, Option<&str>), Vec<&InstanceEntry>> = BTreeMap::new();
+
+    for instance in instances {
+        groups
+            .entry((
+                instance.study_uid.as_deref(),
+                instance.series_uid.as_deref(),
+            ))
+            .or_default()
+            .push(instance);
+    }
+
+    let mut series: Vec<Series<'_>> = groups
+        .into_iter()
+        .map(|((study_uid, series_uid), instances)| Series {
+            study_uid,
+            series_uid,
+            instances,
+        })
+        .collect();
+
+    series.sort_by_key(Series::sort_key);
+    series
+}
+
+/// Print a table of the series found below `dir` (for `dcmv ls`)
+///
+/// With an `index` file, headers are cached there for the next listing.
+/// Progress of the scan is shown on `progress`.
+///
+/// # Errors
+///
+/// Returns an error if the directory cannot be scanned
+pub fn print_series_table(
+    dir: &Path,
+    index: Option<&Path>,
+    progress: Option<Stream>,
+) -> Result<()> {
+    let mut cache = index.map(|file| IndexCache::load(dir, file));
+    let scan = scan_directory(dir, cache.as_mut(), progress)?;
+    let series = group_series(&scan.instances);
+
+    let header = [
+        "#",
+        "Modality",
+        "Description",
+        "Images",
+        "Dimensions",
+        "Transfer Syntax",
+    ]
+    .map(String::from);
+
+    // Outer `None` until the first study header, as the UID itself may be missing
+    let mut current_study: Option<Option<&str>> = None;
+    let mut rows: Vec<(Option<String>, [String; 6], Vec<String>)> = Vec::new();
+
+    for s in &series {
+        let first = s.first();
+        let study = (current_study != Some(s.study_uid)).then(|| {
+            current_study = Some(s.study_uid);
+            format!(
+                "Study {} {}{}",
+                first.study_date.as_deref().unwrap_or("-"),
+                first
+                    .study_description
+                    .as_deref()
+                    .unwrap_or("(no description)"),
+                s.study_uid
+                    .map_or_else(String::new, |uid| format!(" [{uid}]"))
+            )
+        });
+
+        // A 4D series lists its volumes below it
+        let volumes = s.volumes();
+        let volumes = if volumes.len() > 1 {
+            volumes
+                .iter()
+                .enumerate()
+                .map(|(i, volume)| {
+                    let label = if volume.label.is_empty() {
+                        String::new()
+                    } else {
+                        format!(" {},", volume.label)
+                    };
+                    format!("volume {}:{label} {} images", i + 1, volume.instances.len())
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        let or_dash = |value: Option<String>| value.unwrap_or_else(|| "-".to_string());
+        rows.push((
+            study,
+            [
+                or_dash(first.series_number.map(|n| n.to_string())),
+                or_dash(first.modality.clone()),
+                or_dash(first.series_description.clone()),
+                s.instances.len().to_string(),
+                or_dash(s.dimensions()),
+                or_dash(s.transfer_syntax()),
+            ],
+            volumes,
+        ));
+    }
+
+    let widths: Vec<usize> = (0..header.len())
+        .map(|column| {
+            rows.iter()
+                .map(|(_, cells, _)| &cells[column])
+                .chain([&header[column]])
+                .map(|cell| cell.chars().count())
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+
+    let format_row = |cells: &[String; 6]| {
+        cells
+            .iter()
+            .zip(&widths)
+            .map(|(cell, &width)| format!("{cell:width$}"))
+            .collect::<Vec<_>>()
+            .join("  ")
+            .trim_end()
+            .to_string()
+    };
+
+    for (study, cells, volumes) in &rows {
+        if let Some(study) = study {
+            println!("{study}");
+            println!("  {}", format_row(&header));
+        }
+        println!("  {}", format_row(cells));
+        for volume in volumes {
+            println!("      {volume}");
+        }
+    }
+
+    println!();
+    println!("{} series, {} files", series.len(), scan.instances.len());
+    if !scan.skipped.is_empty() {
+        println!("{} files skipped (not DICOM)", scan.skipped.len());
+    }
+
+    if let Some(cache) = &cache {
+        println!(
+            "{} files from index {}",
+            cache.hits(),
+            cache.file().display()
+        );
+
+        // A stale index only costs speed, so don't fail the listing
+        if let Err(e) = cache.save() {
+            eprintln!("Warning: {e:#}");
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn instance(study: &str, series: &str, number: i32, rows: u16) -> InstanceEntry {
+        InstanceEntry {
+            path: PathBuf::from(format!("{series}-{number}.dcm")),
+            study_uid: Some(study.to_string()),
+            series_uid: Some(series.to_string()),
+            sop_instance_uid: Some(format!("{series}.{number}")),
+            study_date: Some("20240101".to_string()),
+            study_description: None,
+            modality: Some("CT".to_string()),
+            series_number: Some(number),
+            series_description: None,
+            rows: Some(rows),
+            columns: Some(512),
+            transfer_syntax: TransferSyntax::new(
+                "1.2.840.10008.1.2.1".to_string(),
+                "Explicit VR Little Endian".to_string(),
+            ),
+            instance_number: Some(1),
+            position: None,
+            orientation: None,
+            volume: VolumeAttributes::default(),
+        }
+    }
+
+    #[test]
+    fn test_group_series_orders_by_series_number() {
+        let instances = vec![
+            instance("1.1", "1.1.3", 3, 512),
+            instance("1.1", "1.1.2", 2, 512),
+            instance("1.1", "1.1.3", 3, 256),
+        ];
+
+        let series = group_series(&instances);
+        assert_eq!(series.len(), 2);
+        assert_eq!(series[0].series_uid, Some("1.1.2"));
+        assert_eq!(series[1].instances.len(), 2);
+        assert_eq!(series[1].dimensions().as_deref(), Some("mixed"));
+        assert_eq!(series[0].dimensions().as_deref(), Some("512x512"));
+    }
+}
@@ -0,0 +1,261 @@
+//! Volumes of a 4D series: echoes, diffusion b-values, cardiac phases
+//!
+//! A series that images each slice position more than once holds several
+//! volumes. The files at each position are put in order of echo, b-value,
+//! trigger time, temporal position and instance number, and the nth file at
+//! every position goes to the nth volume. This undoes any interleaving of
+//! the volumes by the writer, and does not depend on how the instances are
+//! numbered across them.
+
+use super::{InstanceEntry, group_series, read_instances};
+use crate::dicom::DicomObject;
+use ::dicom::core::Tag;
+use ::dicom::dictionary_std::tags;
+use anyhow::{Context, Result, bail};
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+/// Slice positions are compared in units of this many mm
+const POSITION_RESOLUTION: f64 = 0.01;
+
+/// Attributes that tell the volumes of a 4D series apart
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct VolumeAttributes {
+    pub echo_number: Option<i32>,
+    /// Echo Time in ms
+    pub echo_time: Option<f64>,
+    /// Diffusion b-value in s/mm²
+    pub b_value: Option<f64>,
+    /// Trigger Time after the R wave in ms, for cardiac phases
+    pub trigger_time: Option<f64>,
+    pub temporal_position: Option<i32>,
+}
+
+impl VolumeAttributes {
+    pub(super) fn from_object(obj: &DicomObject) -> Self {
+        let int = |tag: Tag| obj.get(tag).and_then(|e| e.to_int::<i32>().ok());
+        let float = |tag: Tag| obj.get(tag).and_then(|e| e.to_float64().ok());
+
+        Self {
+            echo_number: int(tags::ECHO_NUMBERS),
+            echo_time: float(tags::ECHO_TIME),
+            b_value: float(tags::DIFFUSION_B_VALUE),
+            trigger_time: float(tags::TRIGGER_TIME),
+            temporal_position: int(tags::TEMPORAL_POSITION_IDENTIFIER),
+        }
+    }
+
+    /// Each attribute as a number, in the order volumes are sorted by
+    fn values(&self) -> [Option<f64>; 5] {
+        [
+            self.echo_number.map(f64::from),
+            self.echo_time,
+            self.b_value,
+            self.trigger_time,
+            self.temporal_position.map(f64::from),
+        ]
+    }
+}
+
+/// How each attribute is shown in a volume label
+const LABELS: [fn(&str) -> String; 5] = [
+    |value| format!("echo {value}"),
+    |value| format!("TE {value} ms"),
+    |value| format!("b={value}"),
+    |value| format!("trigger {value} ms"),
+    |value| format!("time point {value}"),
+];
+
+/// The files of a series taken at one echo, b-value, phase etc.
+#[derive(Debug, Clone)]
+pub struct Volume<'a> {
+    /// The attributes setting the volume apart, e.g. "echo 2, TE 20 ms",
+    /// empty if the headers do not tell
+    pub label: String,
+    /// In order of Instance Number
+    pub instances: Vec<&'a InstanceEntry>,
+}
+
+/// Split the instances of one series into volumes
+///
+/// A series with no repeated slice position, or with files lacking an
+/// Image Position (Patient), is a single volume.
+#[must_use]
+pub fn split_volumes<'a>(instances: &[&'a InstanceEntry]) -> Vec<Volume<'a>> {
+    let single = || {
+        vec![Volume {
+            label: String::new(),
+            instances: sorted(instances.to_vec()),
+        }]
+    };
+
+    let mut positions: BTreeMap<[i64; 3], Vec<&InstanceEntry>> = BTreeMap::new();
+    for &instance in instances {
+        let Some(position) = instance.position else {
+            return single();
+        };
+        let key = position.map(|v| (v / POSITION_RESOLUTION).round() as i64);
+        positions.entry(key).or_default().push(instance);
+    }
+
+    let count = positions.values().map(Vec::len).max().unwrap_or(0);
+    if count < 2 {
+        return single();
+    }
+
+    let mut volumes = vec![Vec::new(); count];
+    for files in positions.values_mut() {
+        files.sort_by(|a, b| compare(a, b));
+        for (volume, &file) in volumes.iter_mut().zip(files.iter()) {
+            volume.push(file);
+        }
+    }
+
+    // Only the attributes that change within the series tell volumes apart
+    let values: Vec<[Option<f64>; 5]> = instances.iter().map(|i| i.volume.values()).collect();
+    let varying: Vec<usize> = (0..LABELS.len())
+        .filter(|&a| values.iter().any(|v| v[a] != values[0][a]))
+        .collect();
+
+    volumes
+        .into_iter()
+        .map(|files| Volume {
+            label: label(&files, &varying),
+            instances: sorted(files),
+        })
+        .collect()
+}
+
+fn compare(a: &InstanceEntry, b: &InstanceEntry) -> Ordering {
+    a.volume
+        .values()
+        .iter()
+        .zip(b.volume.values())
+        .map(|(a, b)| match (a, b) {
+            (Some(a), Some(b)) => a.total_cmp(&b),
+            (a, b) => a.is_some().cmp(&b.is_some()),
+        })
+        .find(|ordering| ordering.is_ne())
+        .unwrap_or_else(|| a.instance_number.cmp(&b.instance_number))
+}
+
+fn sorted(mut instances: Vec<&InstanceEntry>) -> Vec<&InstanceEntry> {
+    instances.sort_by_key(|instance| instance.instance_number);
+    instances
+}
+
+/// The `varying` attributes of a volume, as a range where they differ
+/// between its files (e.g. trigger times of the slices of one phase)
+fn label(files: &[&InstanceEntry], varying: &[usize]) -> String {
+    varying
+        .iter()
+        .filter_map(|&a| {
+            let values: Vec<f64> = files.iter().filter_map(|f| f.volume.values()[a]).collect();
+            let min = values.iter().copied().reduce(f64::min)?;
+            let max = values.iter().copied().reduce(f64::max)?;
+            let value = if min == max {
+                min.to_string()
+            } else {
+                format!("{min}-{max}")
+            };
+            Some(LABELS[a](&value))
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Files of volume `number` (from 1) of the series among `paths`, with
+/// directories standing for the files directly in them (for --volume)
+///
+/// Files that are not DICOM are left out.
+///
+/// # Errors
+///
+/// Returns an error if a directory cannot be listed, the files are not of
+/// one series, or the series has no such volume
+pub fn volume_files(paths: &[PathBuf], number: u32) -> Result<Vec<PathBuf>> {
+    let instances = read_instances(paths)?;
+
+    let volumes = match &group_series(&instances)[..] {
+        [] => bail!("No DICOM files to choose a volume from"),
+        [series] => series.volumes(),
+        several => bail!(
+            "--volume needs the files of one series, these are of {} series",
+            several.len()
+        ),
+    };
+    let volume = (number as usize)
+        .checked_sub(1)
+        .and_then(|index| volumes.get(index))
+        .with_context(|| match volumes.len() {
+            1 => format!("No volume {number}, the series is a single volume"),
+            n => format!("No volume {number}, the series has {n} volumes"),
+        })?;
+
+    Ok(volume
+        .instances
+        .iter()
+        .map(|instance| instance.path.clone())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::TransferSyntax;
+
+    fn slice(number: i32, z: f64, b_value: f64) -> InstanceEntry {
+        InstanceEntry {
+            path: PathBuf::from(format!("{number}.dcm")),
+            study_uid: None,
+            series_uid: None,
+            sop_instance_uid: None,
+            study_date: None,
+            study_description: None,
+            modality: Some("MR".to_string()),
+            series_number: Some(1),
+            series_description: None,
+            rows: Some(64),
+            columns: Some(64),
+            transfer_syntax: TransferSyntax::new(
+                "1.2.840.10008.1.2.1".to_string(),
+                "Explicit VR Little Endian".to_string(),
+            ),
+            instance_number: Some(number),
+            position: Some([0.0, 0.0, z]),
+            orientation: None,
+            volume: VolumeAttributes {
+                b_value: Some(b_value),
+                ..VolumeAttributes::default()
+            },
+        }
+    }
+
+    #[test]
+    fn test_split_volumes_by_b_value() {
+        // Interleaved slice by slice, b=1000 before b=0 at the second slice
+        let instances = [
+            slice(1, 0.0, 0.0),
+            slice(2, 0.0, 1000.0),
+            slice(3, 5.0, 1000.0),
+            slice(4, 5.0, 0.0),
+        ];
+        let refs: Vec<&InstanceEntry> = instances.iter().collect();
+        let volumes = split_volumes(&refs);
+
+        let numbers = |volume: &Volume| -> Vec<Option<i32>> {
+            volume.instances.iter().map(|i| i.instance_number).collect()
+        };
+        assert_eq!(volumes.len(), 2);
+        assert_eq!(volumes[0].label, "b=0");
+        assert_eq!(numbers(&volumes[0]), [Some(1), Some(4)]);
+        assert_eq!(volumes[1].label, "b=1000");
+        assert_eq!(numbers(&volumes[1]), [Some(2), Some(3)]);
+
+        // Without repeated positions the series is one volume
+        let volumes = split_volumes(&refs[..1]);
+        assert_eq!(volumes.len(), 1);
+        assert!(volumes[0].label.is_empty());
+    }
+}
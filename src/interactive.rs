@@ -0,0 +1,725 @@
+use crate::cli::Args;
+use crate::dicom::DicomMetadata;
+use crate::display::{self, kitty, kitty::Placement};
+use crate::image::{
+    self, ANNOTATION_COLOR, CaptionPlacement, DecodedFrame, Distance, RoiStatistics, Transform,
+    draw_line,
+};
+use crate::interrupt;
+use crate::render::RenderOptions;
+use crate::stack::SliceStack;
+use crate::temporal::TemporalSeries;
+use crate::terminal::TerminalContext;
+use crate::types::RescaleType;
+use ::image::{DynamicImage, Rgb, RgbImage};
+use anyhow::{Result, anyhow, bail};
+use crossterm::{
+    cursor::{Hide, MoveTo, Show},
+    event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    execute, queue,
+    style::Print,
+    terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use std::io::Write;
+use std::rc::Rc;
+use std::time::Duration;
+use viuer::Config as ViuerConfig;
+
+const CURSOR_COLOR: Rgb<u8> = Rgb([0, 255, 255]);
+
+/// Terminal rows kept free below the image for the status lines
+const STATUS_ROWS: u16 = 3;
+
+/// Length of each crosshair arm, in terminal cells
+const CROSSHAIR_CELLS: u32 = 2;
+
+const HELP: &str = "arrows/hjkl: move (shift: by pixel)  space: set point  r: ruler/ROI  c: clear  i: invert  q: quit";
+
+/// Keys for stepping through frames or slices, while up/down step
+const SCROLL_HELP: &str = "up/down/jk, pgup/pgdn: previous/next  s: move cursor instead";
+
+/// Keys for stepping through frames or slices, while up/down move the cursor
+const MOVE_HELP: &str = "pgup/pgdn: previous/next  s: up/down/jk step instead";
+
+/// Keys for the playback of time points
+const PLAY_HELP: &str = "p: pause/play";
+
+/// What the two placed points measure
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Tool {
+    /// Distance between the points
+    Ruler,
+    /// Statistics of the box with the points as opposite corners
+    Roi,
+}
+
+enum Action {
+    Redraw,
+    /// Show the frame or slice this many steps away, or render the current
+    /// one again with 0
+    Step(i64),
+    /// Show the next time point of the playback, after the last the first
+    Advance,
+    Ignore,
+    Quit,
+    /// Ctrl-C, read as a key in raw mode
+    Interrupt,
+}
+
+/// Images the viewer steps through
+enum Slices<'a> {
+    /// Frames of a single image
+    Frames(&'a DicomMetadata),
+    /// Single-frame files of a series, with the one shown
+    Stack {
+        stack: SliceStack,
+        shown: Rc<DicomMetadata>,
+    },
+}
+
+impl Slices<'_> {
+    fn len(&self) -> usize {
+        match self {
+            Self::Frames(metadata) => metadata.number_of_frames.max(1) as usize,
+            Self::Stack { stack, .. } => stack.len(),
+        }
+    }
+
+    fn metadata(&self) -> &DicomMetadata {
+        match self {
+            Self::Frames(metadata) => metadata,
+            Self::Stack { shown, .. } => shown,
+        }
+    }
+
+    fn noun(&self) -> &'static str {
+        match self {
+            Self::Frames(_) => "frame",
+            Self::Stack { .. } => "slice",
+        }
+    }
+}
+
+/// Time points of a dynamic series played one after the other
+struct Playback {
+    /// How long each time point is shown for
+    delays: Vec<Duration>,
+    /// Acquisition time of each time point after the first, in seconds
+    times: Option<Vec<f64>>,
+    /// The slice position played, from 0, and the number of positions
+    position: (usize, usize),
+    paused: bool,
+}
+
+/// Interactive view of a rendered image with a keyboard-driven cursor
+///
+/// The cursor and points are kept in the coordinates of the rendered
+/// (oriented) image and mapped back to stored pixel coordinates for
+/// measurements. The cursor moves by one terminal cell, or by one pixel
+/// with shift. Multi-frame images and series of single-frame files are
+/// stepped through frame by frame or slice by slice, and the time points
+/// of a dynamic series are played one after the other.
+pub struct Viewer<'a> {
+    slices: Slices<'a>,
+    /// Frame or slice shown
+    index: usize,
+    args: &'a Args,
+    options: RenderOptions,
+    image: RgbImage,
+    transform: Transform,
+    /// Stored grayscale samples, for pixel values and ROI statistics
+    frame: Option<DecodedFrame>,
+    cursor: (u32, u32),
+    points: Vec<(u32, u32)>,
+    tool: Tool,
+    /// Image pixels covered by one terminal cell in the last draw
+    cell: (u32, u32),
+    /// ID of the image when drawn with the kitty graphics protocol, which
+    /// is replaced in place on redraws instead of clearing the screen
+    kitty_image: Option<u32>,
+    /// Whether up/down and j/k step through the frames or slices instead
+    /// of moving the cursor
+    scrolling: bool,
+    /// Why the frame or slice at `index` cannot be shown
+    error: Option<String>,
+    /// Automatic stepping through the slices, which are time points
+    playback: Option<Playback>,
+}
+
+impl<'a> Viewer<'a> {
+    /// Viewer of an image, starting at the frame in `options`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the frame cannot be rendered
+    pub fn new(
+        args: &'a Args,
+        options: RenderOptions,
+        metadata: &'a DicomMetadata,
+    ) -> Result<Self> {
+        let index = options.frame as usize;
+        Self::open(args, options, Slices::Frames(metadata), index)
+    }
+
+    /// Viewer of a series of single-frame files, starting at the first
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the stack is empty or its first slice cannot be
+    /// read or rendered
+    pub fn with_stack(
+        args: &'a Args,
+        options: RenderOptions,
+        mut stack: SliceStack,
+    ) -> Result<Self> {
+        if stack.is_empty() {
+            bail!("No DICOM files to show");
+        }
+        let shown = stack.get(0)?;
+        Self::open(args, options, Slices::Stack { stack, shown }, 0)
+    }
+
+    /// Viewer playing the time points of `series`, loaded in `stack`, at
+    /// `fps` time points per second
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the stack is empty or its first time point
+    /// cannot be read or rendered
+    pub fn with_time_points(
+        args: &'a Args,
+        options: RenderOptions,
+        stack: SliceStack,
+        series: &TemporalSeries,
+        fps: f64,
+    ) -> Result<Self> {
+        let mut viewer = Self::with_stack(args, options, stack)?;
+        viewer.playback = Some(Playback {
+            delays: series.delays(fps),
+            times: series.times.clone(),
+            position: series.position,
+            paused: false,
+        });
+        Ok(viewer)
+    }
+
+    fn open(
+        args: &'a Args,
+        options: RenderOptions,
+        slices: Slices<'a>,
+        index: usize,
+    ) -> Result<Self> {
+        // A banner above the image would shift it away from the cursor
+        // coordinates, so the caption is drawn over the image instead
+        let options = RenderOptions {
+            caption: options.caption.map(|_| CaptionPlacement::Overlay),
+            ..options
+        };
+        let scrolling = slices.len() > 1;
+
+        let mut viewer = Self {
+            slices,
+            index,
+            args,
+            options,
+            image: RgbImage::new(0, 0),
+            transform: Transform::default(),
+            frame: None,
+            cursor: (0, 0),
+            points: Vec::with_capacity(2),
+            tool: Tool::Ruler,
+            cell: (1, 1),
+            kitty_image: kitty::is_supported().then(kitty::next_image_id),
+            scrolling,
+            error: None,
+            playback: None,
+        };
+        viewer.load(index)?;
+        Ok(viewer)
+    }
+
+    /// Render the frame or slice at `index` in place of the current one
+    ///
+    /// The cursor and points are kept unless the image size changes.
+    fn load(&mut self, index: usize) -> Result<()> {
+        let frame = match &mut self.slices {
+            Slices::Frames(_) => u32::try_from(index)?,
+            Slices::Stack { stack, shown } => {
+                *shown = stack.get(index)?;
+                self.options.frame
+            }
+        };
+        let metadata = self.slices.metadata();
+
+        let options = RenderOptions {
+            frame,
+            ..self.options
+        };
+        let (rendered, transform) = options.render(metadata)?;
+        let rendered = image::quantize(rendered).into_rgb8();
+
+        let samples = metadata
+            .single_frame(frame)
+            .and_then(|frame| image::decode_frame(&frame))
+            .ok()
+            .filter(|frame| frame.samples_per_pixel == 1);
+
+        if rendered.dimensions() != self.image.dimensions() {
+            self.cursor = (rendered.width() / 2, rendered.height() / 2);
+            self.points.clear();
+        }
+        self.image = rendered;
+        self.transform = transform;
+        self.frame = samples;
+        Ok(())
+    }
+
+    /// Move `delta` frames or slices, stopping at the first and last
+    fn step(&mut self, delta: i64) {
+        let last = self.slices.len().saturating_sub(1);
+        let index = usize::try_from(i64::try_from(self.index).unwrap_or(0) + delta)
+            .unwrap_or(0)
+            .min(last);
+
+        self.index = index;
+        self.error = self
+            .load(index)
+            .err()
+            .map(|e| format!("Cannot show {} {}: {e:#}", self.noun(), index + 1));
+    }
+
+    /// Show the next time point, or the first after the last
+    fn advance(&mut self) {
+        let next = (self.index + 1) % self.slices.len();
+        self.step(next as i64 - self.index as i64);
+    }
+
+    /// How long the shown time point is left for the next, while playing
+    fn playback_delay(&self) -> Option<Duration> {
+        let playback = self.playback.as_ref().filter(|playback| !playback.paused)?;
+        playback.delays.get(self.index).copied()
+    }
+
+    fn noun(&self) -> &'static str {
+        match self.playback {
+            Some(_) => "time point",
+            None => self.slices.noun(),
+        }
+    }
+
+    /// Decode the slices next to the shown one ahead of time
+    fn prefetch(&mut self) {
+        if let Slices::Stack { stack, .. } = &mut self.slices {
+            stack.prefetch_neighbors(self.index);
+        }
+    }
+
+    fn metadata(&self) -> &DicomMetadata {
+        self.slices.metadata()
+    }
+
+    /// Show the image and handle key presses until the user quits
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if stdout is not a terminal or terminal I/O fails
+    pub fn run(&mut self) -> Result<()> {
+        if !TerminalContext::get().stdout {
+            bail!("Interactive mode needs a terminal");
+        }
+
+        let raw_mode = RawMode::enable()?;
+        execute!(std::io::stdout(), Clear(ClearType::All))?;
+        self.draw()?;
+        self.prefetch();
+
+        loop {
+            let action = match self.playback_delay() {
+                Some(delay) if !event::poll(delay)? => Action::Advance,
+                _ => match event::read()? {
+                    Event::Key(key) if key.kind == KeyEventKind::Press => self.handle_key(key),
+                    Event::Resize(..) => Action::Redraw,
+                    _ => Action::Ignore,
+                },
+            };
+
+            match action {
+                Action::Redraw => self.draw()?,
+                Action::Step(delta) => {
+                    self.step(delta);
+                    self.draw()?;
+                    self.prefetch();
+                }
+                Action::Advance => {
+                    self.advance();
+                    self.draw()?;
+                    self.prefetch();
+                }
+                Action::Ignore => {}
+                Action::Quit => return Ok(()),
+                Action::Interrupt => {
+                    drop(raw_mode);
+                    interrupt::exit();
+                }
+            }
+        }
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) -> Action {
+        let (step_x, step_y) = if key.modifiers.contains(KeyModifiers::SHIFT) {
+            (1, 1)
+        } else {
+            (i64::from(self.cell.0), i64::from(self.cell.1))
+        };
+
+        match key.code {
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                Action::Interrupt
+            }
+            KeyCode::Char('q') | KeyCode::Esc => Action::Quit,
+            KeyCode::PageUp => Action::Step(-1),
+            KeyCode::PageDown => Action::Step(1),
+            KeyCode::Home => Action::Step(i64::MIN / 2),
+            KeyCode::End => Action::Step(i64::MAX / 2),
+            KeyCode::Up | KeyCode::Char('k') if self.scrolling => Action::Step(-1),
+            KeyCode::Down | KeyCode::Char('j') if self.scrolling => Action::Step(1),
+            KeyCode::Char('p') if let Some(playback) = &mut self.playback => {
+                playback.paused = !playback.paused;
+                Action::Redraw
+            }
+            KeyCode::Char('s') if self.slices.len() > 1 => {
+                self.scrolling = !self.scrolling;
+                Action::Redraw
+            }
+            KeyCode::Left | KeyCode::Char('h' | 'H') => self.move_cursor(-step_x, 0),
+            KeyCode::Right | KeyCode::Char('l' | 'L') => self.move_cursor(step_x, 0),
+            KeyCode::Up | KeyCode::Char('k' | 'K') => self.move_cursor(0, -step_y),
+            KeyCode::Down | KeyCode::Char('j' | 'J') => self.move_cursor(0, step_y),
+            KeyCode::Char(' ') | KeyCode::Enter => {
+                if self.points.len() == 2 {
+                    self.points.clear();
+                }
+                self.points.push(self.cursor);
+                Action::Redraw
+            }
+            KeyCode::Char('r') => {
+                self.tool = match self.tool {
+                    Tool::Ruler => Tool::Roi,
+                    Tool::Roi => Tool::Ruler,
+                };
+                Action::Redraw
+            }
+            KeyCode::Char('c') | KeyCode::Backspace => {
+                self.points.clear();
+                Action::Redraw
+            }
+            KeyCode::Char('i') => {
+                self.options.invert = !self.options.invert;
+                Action::Step(0)
+            }
+            _ => Action::Ignore,
+        }
+    }
+
+    fn move_cursor(&mut self, dx: i64, dy: i64) -> Action {
+        let clamp = |position: u32, delta: i64, size: u32| {
+            (i64::from(position) + delta).clamp(0, i64::from(size) - 1) as u32
+        };
+
+        self.cursor = (
+            clamp(self.cursor.0, dx, self.image.width()),
+            clamp(self.cursor.1, dy, self.image.height()),
+        );
+        Action::Redraw
+    }
+
+    /// Measured points: both placed points, or the first one and the cursor
+    fn endpoints(&self) -> Option<((u32, u32), (u32, u32))> {
+        match self.points[..] {
+            [a] => Some((a, self.cursor)),
+            [a, b] => Some((a, b)),
+            _ => None,
+        }
+    }
+
+    /// Stored pixel coordinates of a position in the rendered image
+    fn stored(&self, position: (u32, u32)) -> (u32, u32) {
+        self.transform
+            .source_position(position, self.image.dimensions())
+    }
+
+    fn draw(&mut self) -> Result<()> {
+        let (columns, rows) = terminal::size()?;
+        let config = ViuerConfig {
+            width: Some(self.args.width.unwrap_or(u32::from(columns))),
+            height: Some(
+                self.args
+                    .height
+                    .unwrap_or(u32::from(rows.saturating_sub(STATUS_ROWS).max(1))),
+            ),
+            absolute_offset: true,
+            x: 0,
+            y: 0,
+            ..Default::default()
+        };
+
+        let mut stdout = std::io::stdout();
+
+        let printed_rows = loop {
+            let canvas = DynamicImage::ImageRgb8(self.overlay());
+
+            let (width, height) = match self.kitty_image {
+                Some(id) => {
+                    let (columns, rows) = display::fit_cells(
+                        self.image.dimensions(),
+                        None,
+                        display::cell_aspect(),
+                        (config.width, config.height),
+                    );
+                    let placement = Placement {
+                        columns,
+                        rows,
+                        position: Some((0, 0)),
+                    };
+                    kitty::place(&mut stdout, &canvas, id, placement)?;
+                    (columns, rows)
+                }
+                None => {
+                    let (columns, _) = display::fit_cells(
+                        self.image.dimensions(),
+                        None,
+                        display::cell_aspect(),
+                        (config.width, config.height),
+                    );
+                    let canvas = display::downscale(
+                        &canvas,
+                        columns.saturating_mul(display::pixels_per_column(self.args.renderer)),
+                        self.args.scale_filter_type(),
+                    );
+                    execute!(stdout, Clear(ClearType::All), MoveTo(0, 0))?;
+                    viuer::print(&canvas, &config)
+                        .map_err(|e| anyhow!("Failed to display image: {e}"))?
+                }
+            };
+
+            let cell = (
+                self.image.width().div_ceil(width.max(1)).max(1),
+                self.image.height().div_ceil(height.max(1)).max(1),
+            );
+
+            if cell == self.cell {
+                break height;
+            }
+
+            // Line widths and cursor steps follow the cell size, so draw
+            // again once the image's size on screen is known
+            self.cell = cell;
+        };
+
+        let status = [
+            self.cursor_status(),
+            self.error
+                .clone()
+                .unwrap_or_else(|| self.measurement_status()),
+            self.help(),
+        ];
+        let top = u16::try_from(printed_rows).unwrap_or(0);
+
+        for (row, line) in (top..).zip(status) {
+            queue!(
+                stdout,
+                MoveTo(0, row),
+                Clear(ClearType::CurrentLine),
+                Print(line)
+            )?;
+        }
+
+        stdout.flush()?;
+        Ok(())
+    }
+
+    /// The image with the measurement and the cursor drawn over it
+    fn overlay(&self) -> RgbImage {
+        let mut canvas = self.image.clone();
+        let thickness = (self.cell.0.min(self.cell.1) / 2).max(1);
+        let point = |(x, y): (u32, u32)| (i64::from(x), i64::from(y));
+
+        if let Some((a, b)) = self.endpoints() {
+            let (a, b) = (point(a), point(b));
+
+            match self.tool {
+                Tool::Ruler => draw_line(&mut canvas, a, b, thickness, ANNOTATION_COLOR),
+                Tool::Roi => {
+                    let corners = [a, (b.0, a.1), b, (a.0, b.1)];
+                    for (i, &from) in corners.iter().enumerate() {
+                        let to = corners[(i + 1) % corners.len()];
+                        draw_line(&mut canvas, from, to, thickness, ANNOTATION_COLOR);
+                    }
+                }
+            }
+        }
+
+        let (x, y) = point(self.cursor);
+        let arm_x = i64::from(self.cell.0 * CROSSHAIR_CELLS);
+        let arm_y = i64::from(self.cell.1 * CROSSHAIR_CELLS);
+        draw_line(
+            &mut canvas,
+            (x - arm_x, y),
+            (x + arm_x, y),
+            thickness,
+            CURSOR_COLOR,
+        );
+        draw_line(
+            &mut canvas,
+            (x, y - arm_y),
+            (x, y + arm_y),
+            thickness,
+            CURSOR_COLOR,
+        );
+
+        canvas
+    }
+
+    fn cursor_status(&self) -> String {
+        let tool = match self.tool {
+            Tool::Ruler => "Ruler",
+            Tool::Roi => "ROI",
+        };
+        let (x, y) = self.stored(self.cursor);
+
+        let value = self
+            .frame
+            .as_ref()
+            .and_then(|frame| frame.sample(x, y))
+            .map(|stored| {
+                format!(
+                    "  stored {stored}  value {}",
+                    self.with_unit(self.metadata().rescaled_value(stored))
+                )
+            })
+            .unwrap_or_default();
+        let [r, g, b] = self.image.get_pixel(self.cursor.0, self.cursor.1).0;
+
+        let position = match self.slices.len() {
+            1 => String::new(),
+            count => format!(
+                "  {} {}/{count}{}",
+                self.noun(),
+                self.index + 1,
+                self.playback_status()
+            ),
+        };
+
+        format!("[{tool}]{position}  pixel ({x}, {y}){value}  RGB({r}, {g}, {b})")
+    }
+
+    /// Time and slice position of the shown time point, while playing
+    fn playback_status(&self) -> String {
+        let Some(playback) = &self.playback else {
+            return String::new();
+        };
+
+        let mut status = String::new();
+        if let Some(time) = playback
+            .times
+            .as_ref()
+            .and_then(|times| times.get(self.index))
+        {
+            status.push_str(&format!(" +{time:.1} s"));
+        }
+        let (position, positions) = playback.position;
+        if positions > 1 {
+            status.push_str(&format!(" at slice position {}/{positions}", position + 1));
+        }
+        if playback.paused {
+            status.push_str(" (paused)");
+        }
+        status
+    }
+
+    fn help(&self) -> String {
+        let help = match (self.slices.len(), self.scrolling) {
+            (1, _) => HELP.to_string(),
+            (_, true) => format!("{SCROLL_HELP}  {HELP}"),
+            (_, false) => format!("{MOVE_HELP}  {HELP}"),
+        };
+        match self.playback {
+            Some(_) => format!("{PLAY_HELP}  {help}"),
+            None => help,
+        }
+    }
+
+    fn measurement_status(&self) -> String {
+        let Some((a, b)) = self.endpoints() else {
+            return "Press space to place the first point".to_string();
+        };
+        let (a, b) = (self.stored(a), self.stored(b));
+
+        match self.tool {
+            Tool::Ruler => format!(
+                "Distance {}",
+                Distance::between(a, b, self.metadata().pixel_spacing)
+            ),
+            Tool::Roi => {
+                let size = format!("{}x{} px", a.0.abs_diff(b.0) + 1, a.1.abs_diff(b.1) + 1);
+                let stats = self.frame.as_ref().and_then(|frame| {
+                    RoiStatistics::from_frame(frame, a, b, &self.metadata().rescale)
+                });
+
+                match stats {
+                    Some(stats) => format!(
+                        "ROI {size}: mean {}, SD {:.1}, min {}, max {}, n={}",
+                        self.with_unit(stats.mean),
+                        stats.std_dev,
+                        self.with_unit(stats.min),
+                        self.with_unit(stats.max),
+                        stats.pixel_count
+                    ),
+                    None => format!("ROI {size}: statistics need grayscale pixel data"),
+                }
+            }
+        }
+    }
+
+    /// Rescaled value with the unit from Rescale Type, if any
+    fn with_unit(&self, value: f64) -> String {
+        let value = if value.fract() == 0.0 {
+            format!("{value}")
+        } else {
+            format!("{value:.1}")
+        };
+
+        match self
+            .metadata()
+            .rescale_type
+            .as_ref()
+            .and_then(RescaleType::unit)
+        {
+            Some(unit) => format!("{value} {unit}"),
+            None => value,
+        }
+    }
+}
+
+/// Raw terminal mode in the alternate screen with a hidden cursor,
+/// restored when dropped
+///
+/// The alternate screen keeps the viewer from scrolling away the shell
+/// history, which is shown again as it was when the viewer closes.
+struct RawMode;
+
+impl RawMode {
+    fn enable() -> Result<Self> {
+        terminal::enable_raw_mode()?;
+        let guard = Self;
+        execute!(std::io::stdout(), EnterAlternateScreen, Hide)?;
+        Ok(guard)
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        let _ = execute!(std::io::stdout(), Show, LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
@@ -0,0 +1,120 @@
+//! Ctrl-C handling
+//!
+//! Long operations that can stop cleanly, such as scanning a directory,
+//! run inside a [`Cancellable`] and poll [`requested`]; Ctrl-C then makes
+//! them return [`Interrupted`]. Outside of them, or on a second Ctrl-C, the
+//! terminal is restored and the process exits with 130, the status of a
+//! process stopped by SIGINT.
+
+use crate::terminal::{Stream, TerminalContext, supports_escape_codes};
+use crossterm::cursor::{MoveToColumn, Show};
+use crossterm::queue;
+use crossterm::style::{Print, ResetColor};
+use crossterm::terminal::{self, Clear, ClearType};
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use thiserror::Error;
+use tracing::debug;
+
+/// Exit status of a process stopped by SIGINT (128 + 2)
+pub const EXIT_STATUS: i32 = 130;
+
+/// String Terminator, which ends the Sixel, Kitty and iTerm2 escape
+/// sequences images are sent in
+const STRING_TERMINATOR: &str = "\x1b\\";
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+static CANCELLABLE: AtomicUsize = AtomicUsize::new(0);
+
+/// Error returned by a [`Cancellable`] operation stopped with Ctrl-C
+#[derive(Error, Debug)]
+#[error("Interrupted")]
+pub struct Interrupted;
+
+/// Install the Ctrl-C handler; called once at startup
+pub fn install() {
+    let result = ctrlc::set_handler(|| {
+        if CANCELLABLE.load(Ordering::SeqCst) == 0 || INTERRUPTED.swap(true, Ordering::SeqCst) {
+            exit();
+        }
+    });
+    // Without the handler, Ctrl-C still stops the process, just not cleanly
+    if let Err(e) = result {
+        debug!("failed to install the Ctrl-C handler: {e}");
+    }
+}
+
+/// Whether Ctrl-C was pressed during a [`Cancellable`] operation
+#[must_use]
+pub fn requested() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+/// Restore the terminal and exit as if stopped by SIGINT
+///
+/// Also used for Ctrl-C in raw mode, where it is read as a key press
+/// instead of raising the signal.
+pub fn exit() -> ! {
+    restore_terminal();
+    std::process::exit(EXIT_STATUS);
+}
+
+/// Exit with [`exit`] if `error` is [`Interrupted`]
+pub fn exit_if_interrupted(error: &anyhow::Error) {
+    if error.is::<Interrupted>() {
+        exit();
+    }
+}
+
+/// Leave the terminal usable after output stopped at any point
+///
+/// An image cut off mid-way would leave the terminal waiting for the end
+/// of its escape sequence, swallowing what is typed next. Colors of block
+/// output, a hidden cursor, raw mode and a progress line are reset too.
+fn restore_terminal() {
+    let _ = terminal::disable_raw_mode();
+
+    let context = TerminalContext::get();
+    for stream in [Stream::Stdout, Stream::Stderr] {
+        if !context.is_terminal(stream) {
+            continue;
+        }
+        let mut out = stream.writer();
+        // The legacy Windows console would print it
+        if supports_escape_codes() {
+            let _ = queue!(out, Print(STRING_TERMINATOR));
+        }
+        let _ = queue!(
+            out,
+            ResetColor,
+            Show,
+            MoveToColumn(0),
+            Clear(ClearType::UntilNewLine)
+        );
+        let _ = out.flush();
+    }
+}
+
+/// Marks an operation that stops on Ctrl-C, while it is alive
+#[derive(Debug)]
+pub struct Cancellable(());
+
+impl Cancellable {
+    #[must_use]
+    pub fn new() -> Self {
+        CANCELLABLE.fetch_add(1, Ordering::SeqCst);
+        Self(())
+    }
+}
+
+impl Default for Cancellable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for Cancellable {
+    fn drop(&mut self) {
+        CANCELLABLE.fetch_sub(1, Ordering::SeqCst);
+    }
+}
@@ -0,0 +1,86 @@
+use crate::dicom::{DicomMetadata, ProcessError};
+use serde_json::{Value, json};
+use std::path::Path;
+
+/// Metadata as a JSON object, with `null` for missing tags
+#[must_use]
+pub fn metadata_to_json(metadata: &DicomMetadata) -> Value {
+    json!({
+        "patient_name": metadata.patient_name(),
+        "patient_id": metadata.patient_id(),
+        "birth_date": metadata.patient_birth_date(),
+        "accession_number": metadata.accession_number(),
+        "study_date": metadata.study_date(),
+        "study_time": metadata.study_time(),
+        "study_description": metadata.study_description(),
+        "modality": metadata.modality(),
+        "series_description": metadata.series_description(),
+        "body_part_examined": metadata.body_part_examined(),
+        "institution_name": metadata.institution_name(),
+        "station_name": metadata.station_name(),
+        "kvp": metadata.acquisition.kvp,
+        "exposure_time_ms": metadata.acquisition.exposure_time,
+        "rows": metadata.rows(),
+        "columns": metadata.cols(),
+        "samples_per_pixel": metadata.samples_per_pixel,
+        "photometric_interpretation": metadata.photometric_interpretation.to_string(),
+        "bits_allocated": metadata.bits_allocated(),
+        "bits_stored": metadata.bits_stored(),
+        "number_of_frames": metadata.number_of_frames,
+        "rescale_slope": metadata.rescale_slope(),
+        "rescale_intercept": metadata.rescale_intercept(),
+        "rescale_type": metadata.rescale_type.as_ref().map(ToString::to_string),
+        "window": metadata.voi_window.map(|window| json!({
+            "center": window.center,
+            "width": window.width,
+            "function": window.function.to_string(),
+        })),
+        "pixel_spacing": metadata.pixel_spacing.map(|spacing| [spacing.row, spacing.column]),
+        "sop_class": metadata.sop_class.as_ref().map(|sop_class| json!({
+            "uid": sop_class.uid,
+            "name": sop_class.name,
+        })),
+        "transfer_syntax": {
+            "uid": metadata.transfer_syntax.uid,
+            "name": metadata.transfer_syntax.name,
+        },
+        "lossy_compression": metadata.lossy_compression.as_ref().map(|lossy| json!({
+            "ratios": lossy.ratios,
+            "methods": lossy.methods,
+        })),
+        "burned_in_annotation": metadata.burned_in_annotation,
+        "warnings": metadata.warnings,
+    })
+}
+
+/// Error report as a JSON object
+///
+/// `file` is `None` for stdin. `metadata` holds whatever was extracted
+/// before the failure, or `null`.
+#[must_use]
+pub fn error_to_json(error: &ProcessError, file: Option<&Path>) -> Value {
+    json!({
+        "file": file.map_or_else(|| "-".to_string(), |path| path.display().to_string()),
+        "kind": error.kind(),
+        "stage": error.stage().to_string(),
+        "message": error.to_string(),
+        "metadata": error.metadata().map(metadata_to_json),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_without_metadata() {
+        let error = ProcessError::NotADicomFile(anyhow::anyhow!("missing DICM magic bytes"));
+        let value = error_to_json(&error, Some(Path::new("scan.dcm")));
+
+        assert_eq!(value["file"], "scan.dcm");
+        assert_eq!(value["kind"], "not_a_dicom_file");
+        assert_eq!(value["stage"], "read");
+        assert_eq!(value["message"], "missing DICM magic bytes");
+        assert!(value["metadata"].is_null());
+    }
+}
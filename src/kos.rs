@@ -0,0 +1,254 @@
+//! Key Object Selection documents
+//!
+//! A KOS flags images of a study as significant, e.g. "Of Interest" or "For
+//! Teaching", by their SOP Instance UIDs. It has no pixel data of its own,
+//! so the references are listed and the images found in the same directory
+//! are shown.
+
+use crate::dicom::{self, TextDecoder};
+use crate::stack;
+use ::dicom::core::Tag;
+use ::dicom::core::dictionary::UidDictionary;
+use ::dicom::dictionary_std::{sop_class, tags, uids};
+use ::dicom::object::InMemDicomObject;
+use anyhow::Result;
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use tracing::debug;
+
+/// Key images flagged by a Key Object Selection document
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyObjectSelection {
+    /// Meaning of the document title, e.g. "Of Interest"
+    pub title: Option<String>,
+    /// Key Object Description, free text from the author
+    pub description: Option<String>,
+    pub images: Vec<KeyImage>,
+}
+
+/// An image referenced by a Key Object Selection
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyImage {
+    pub sop_class_uid: Option<String>,
+    pub sop_instance_uid: String,
+    /// Flagged frames (1-based) of a multi-frame image; all when empty
+    pub frames: Vec<i32>,
+}
+
+impl KeyObjectSelection {
+    /// The key images of `obj`, if it is a Key Object Selection document
+    ///
+    /// Images are taken from the IMAGE items of the content, or from the
+    /// evidence sequence when the content has none.
+    #[must_use]
+    pub fn from_object(obj: &InMemDicomObject) -> Option<Self> {
+        if uid(obj, tags::SOP_CLASS_UID)? != uids::KEY_OBJECT_SELECTION_DOCUMENT_STORAGE {
+            return None;
+        }
+        let text = TextDecoder::from_object(obj);
+
+        let title = items(obj, tags::CONCEPT_NAME_CODE_SEQUENCE)
+            .first()
+            .and_then(|code| string(code, tags::CODE_MEANING));
+
+        let content = items(obj, tags::CONTENT_SEQUENCE);
+        let description = content
+            .iter()
+            .find(|item| string(item, tags::VALUE_TYPE).as_deref() == Some("TEXT"))
+            .and_then(|item| string(item, tags::TEXT_VALUE))
+            .map(|description| text.decode(&description));
+
+        let mut images: Vec<KeyImage> = content
+            .iter()
+            .filter(|item| string(item, tags::VALUE_TYPE).as_deref() == Some("IMAGE"))
+            .flat_map(|item| items(item, tags::REFERENCED_SOP_SEQUENCE))
+            .filter_map(KeyImage::from_reference)
+            .collect();
+
+        if images.is_empty() {
+            images = items(obj, tags::CURRENT_REQUESTED_PROCEDURE_EVIDENCE_SEQUENCE)
+                .into_iter()
+                .flat_map(|study| items(study, tags::REFERENCED_SERIES_SEQUENCE))
+                .flat_map(|series| items(series, tags::REFERENCED_SOP_SEQUENCE))
+                .filter_map(KeyImage::from_reference)
+                .collect();
+        }
+
+        Some(Self {
+            title,
+            description,
+            images,
+        })
+    }
+
+    /// Files of the key images among the files in `directory`, by SOP
+    /// Instance UID
+    ///
+    /// Only the headers are read. Images not found are left out.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the directory cannot be listed
+    pub fn locate(&self, directory: &Path) -> Result<HashMap<String, PathBuf>> {
+        let wanted: Vec<&str> = self
+            .images
+            .iter()
+            .map(|image| image.sop_instance_uid.as_str())
+            .collect();
+        let mut found = HashMap::new();
+
+        for path in stack::expand_directories(&[directory.to_path_buf()])? {
+            let Ok(header) = dicom::open_dicom_header(&path) else {
+                continue;
+            };
+            if let Some(uid) = uid(&header, tags::SOP_INSTANCE_UID)
+                && wanted.contains(&uid.as_str())
+            {
+                debug!(path = %path.display(), "key image");
+                found.entry(uid).or_insert(path);
+            }
+            if found.len() == wanted.len() {
+                break;
+            }
+        }
+        Ok(found)
+    }
+
+    /// The title, description and references, one line each, with the file
+    /// of each image in `found` or "not found"
+    #[must_use]
+    pub fn describe(&self, found: &HashMap<String, PathBuf>) -> String {
+        let mut text = format!(
+            "Key Object Selection: {}\n",
+            self.title.as_deref().unwrap_or("(no title)")
+        );
+        if let Some(description) = &self.description {
+            let _ = writeln!(text, "  {description}");
+        }
+
+        for image in &self.images {
+            let class = image
+                .sop_class_uid
+                .as_deref()
+                .and_then(|uid| sop_class::StandardSopClassDictionary.by_uid(uid))
+                .map_or_else(String::new, |entry| format!(" ({})", entry.name));
+            let frames = if image.frames.is_empty() {
+                String::new()
+            } else {
+                let frames: Vec<String> = image.frames.iter().map(ToString::to_string).collect();
+                format!(", frames {}", frames.join(", "))
+            };
+            let file = found.get(&image.sop_instance_uid).map_or_else(
+                || "not found".to_string(),
+                |path| path.display().to_string(),
+            );
+            let _ = writeln!(text, "  {}{class}{frames}: {file}", image.sop_instance_uid);
+        }
+        text
+    }
+}
+
+impl KeyImage {
+    /// The image of a Referenced SOP Sequence item
+    fn from_reference(item: &InMemDicomObject) -> Option<Self> {
+        Some(Self {
+            sop_class_uid: uid(item, tags::REFERENCED_SOP_CLASS_UID),
+            sop_instance_uid: uid(item, tags::REFERENCED_SOP_INSTANCE_UID)?,
+            frames: item
+                .get(tags::REFERENCED_FRAME_NUMBER)
+                .and_then(|e| e.to_multi_int::<i32>().ok())
+                .unwrap_or_default(),
+        })
+    }
+}
+
+fn items(obj: &InMemDicomObject, tag: Tag) -> Vec<&InMemDicomObject> {
+    obj.get(tag)
+        .and_then(|e| e.items())
+        .map(|items| items.iter().collect())
+        .unwrap_or_default()
+}
+
+fn string(obj: &InMemDicomObject, tag: Tag) -> Option<String> {
+    obj.get(tag)
+        .and_then(|e| e.to_str().ok())
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+}
+
+fn uid(obj: &InMemDicomObject, tag: Tag) -> Option<String> {
+    string(obj, tag).map(|uid| uid.trim_end_matches('\0').to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::dicom::core::value::DataSetSequence;
+    use ::dicom::core::{DataElement, PrimitiveValue, VR};
+
+    fn sequence(tag: Tag, items: Vec<InMemDicomObject>) -> DataElement<InMemDicomObject> {
+        DataElement::new(tag, VR::SQ, DataSetSequence::from(items))
+    }
+
+    #[test]
+    fn test_key_images_from_content() {
+        let mut code = InMemDicomObject::new_empty();
+        code.put(DataElement::new(tags::CODE_MEANING, VR::LO, "Of Interest"));
+
+        let mut description = InMemDicomObject::new_empty();
+        description.put(DataElement::new(tags::VALUE_TYPE, VR::CS, "TEXT"));
+        description.put(DataElement::new(tags::TEXT_VALUE, VR::UT, "Nodule"));
+
+        let mut reference = InMemDicomObject::new_empty();
+        reference.put(DataElement::new(
+            tags::REFERENCED_SOP_CLASS_UID,
+            VR::UI,
+            uids::CT_IMAGE_STORAGE,
+        ));
+        reference.put(DataElement::new(
+            tags::REFERENCED_SOP_INSTANCE_UID,
+            VR::UI,
+            "1.2.3.4\0",
+        ));
+        reference.put(DataElement::new(
+            tags::REFERENCED_FRAME_NUMBER,
+            VR::IS,
+            PrimitiveValue::I32([2, 5].into_iter().collect()),
+        ));
+        let mut image = InMemDicomObject::new_empty();
+        image.put(DataElement::new(tags::VALUE_TYPE, VR::CS, "IMAGE"));
+        image.put(sequence(tags::REFERENCED_SOP_SEQUENCE, vec![reference]));
+
+        let mut obj = InMemDicomObject::new_empty();
+        obj.put(DataElement::new(
+            tags::SOP_CLASS_UID,
+            VR::UI,
+            uids::KEY_OBJECT_SELECTION_DOCUMENT_STORAGE,
+        ));
+        obj.put(sequence(tags::CONCEPT_NAME_CODE_SEQUENCE, vec![code]));
+        obj.put(sequence(tags::CONTENT_SEQUENCE, vec![description, image]));
+
+        let kos = KeyObjectSelection::from_object(&obj).unwrap();
+        assert_eq!(kos.title.as_deref(), Some("Of Interest"));
+        assert_eq!(kos.description.as_deref(), Some("Nodule"));
+        assert_eq!(
+            kos.images,
+            vec![KeyImage {
+                sop_class_uid: Some(uids::CT_IMAGE_STORAGE.to_string()),
+                sop_instance_uid: "1.2.3.4".to_string(),
+                frames: vec![2, 5],
+            }]
+        );
+
+        let described = kos.describe(&HashMap::new());
+        assert!(described.contains("1.2.3.4 (CT Image Storage), frames 2, 5: not found"));
+
+        obj.put(DataElement::new(
+            tags::SOP_CLASS_UID,
+            VR::UI,
+            uids::CT_IMAGE_STORAGE,
+        ));
+        assert!(KeyObjectSelection::from_object(&obj).is_none());
+    }
+}
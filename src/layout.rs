@@ -0,0 +1,178 @@
+//! Hanging protocols: the views of a study shown together, as they are read
+//!
+//! The views are recognized by View Position and laterality. A frontal
+//! chest radiograph is hung next to the lateral, and the four screening
+//! mammograms are hung with the right breast on the left, CC views above
+//! MLO views. Rendering mirrors each mammogram so that the chest walls
+//! face each other.
+
+use crate::dicom;
+use crate::stack;
+use crate::types::ViewInfo;
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+use tracing::debug;
+
+/// Arrangement of the views of one kind of study
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Protocol {
+    /// Frontal (PA or AP) and lateral chest radiographs, side by side
+    Chest,
+    /// RCC, LCC above RMLO, LMLO
+    Mammography,
+}
+
+/// The files hung by a protocol, in the order of its panes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hanging {
+    pub protocol: Protocol,
+    pub files: Vec<PathBuf>,
+}
+
+impl Protocol {
+    /// Panes in each row
+    #[must_use]
+    pub const fn columns(self) -> u32 {
+        2
+    }
+
+    /// Number of panes, row by row
+    #[must_use]
+    pub const fn panes(self) -> usize {
+        match self {
+            Self::Chest => 2,
+            Self::Mammography => 4,
+        }
+    }
+
+    /// Name of the layout, in words
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Chest => "chest PA and lateral",
+            Self::Mammography => "four-view mammography",
+        }
+    }
+
+    /// Pane of the image with `view`, if the protocol shows it
+    #[must_use]
+    pub fn pane(self, view: &ViewInfo) -> Option<usize> {
+        let position = view.position.as_deref()?;
+        match self {
+            Self::Chest => match position {
+                "PA" | "AP" => Some(0),
+                "LL" | "RL" | "LAT" | "LATERAL" => Some(1),
+                _ => None,
+            },
+            Self::Mammography => {
+                let row = match position {
+                    "CC" => 0,
+                    "MLO" | "ML" | "LM" => 1,
+                    _ => return None,
+                };
+                let column = match view.laterality.as_deref()? {
+                    "R" => 0,
+                    "L" => 1,
+                    _ => return None,
+                };
+                Some(row * 2 + column)
+            }
+        }
+    }
+
+    /// The protocol that shows the most of `views`: all four mammograms or
+    /// any two of them, or both chest views
+    fn detect(views: &[ViewInfo]) -> Option<Self> {
+        let filled = |protocol: Self| {
+            let mut panes: Vec<usize> = views.iter().filter_map(|v| protocol.pane(v)).collect();
+            panes.sort_unstable();
+            panes.dedup();
+            panes.len()
+        };
+
+        if filled(Self::Mammography) >= 2 {
+            Some(Self::Mammography)
+        } else if filled(Self::Chest) == Self::Chest.panes() {
+            Some(Self::Chest)
+        } else {
+            None
+        }
+    }
+}
+
+/// The files among `paths` to hang, with directories standing for the files
+/// directly in them
+///
+/// Only the headers are read. The protocol is detected from the views unless
+/// given, and the first file of each view is hung. Returns `None` when no
+/// protocol fits.
+///
+/// # Errors
+///
+/// Returns an error if a directory cannot be listed
+pub fn plan(paths: &[PathBuf], protocol: Option<Protocol>) -> Result<Option<Hanging>> {
+    let views: Vec<(PathBuf, ViewInfo)> = stack::expand_directories(paths)?
+        .into_iter()
+        .filter_map(|path| {
+            let view = read_view(&path)?;
+            Some((path, view))
+        })
+        .collect();
+
+    let Some(protocol) = protocol.or_else(|| {
+        Protocol::detect(
+            &views
+                .iter()
+                .map(|(_, view)| view.clone())
+                .collect::<Vec<_>>(),
+        )
+    }) else {
+        return Ok(None);
+    };
+
+    let mut panes: Vec<Option<PathBuf>> = vec![None; protocol.panes()];
+    for (path, view) in views {
+        if let Some(pane) = protocol.pane(&view)
+            && panes[pane].is_none()
+        {
+            panes[pane] = Some(path);
+        }
+    }
+
+    let files: Vec<PathBuf> = panes.into_iter().flatten().collect();
+    debug!(?protocol, files = files.len(), "hanging");
+    Ok((!files.is_empty()).then_some(Hanging { protocol, files }))
+}
+
+fn read_view(path: &Path) -> Option<ViewInfo> {
+    let header = dicom::open_dicom_header(path).ok()?;
+    let metadata = dicom::extract_metadata_tags(&header).ok()?;
+    Some(metadata.view)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn view(position: &str, laterality: &str) -> ViewInfo {
+        ViewInfo {
+            position: Some(position.to_string()),
+            laterality: Some(laterality.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_views_choose_the_protocol() {
+        let views = [view("MLO", "L"), view("CC", "R"), view("CC", "L")];
+        assert_eq!(Protocol::detect(&views), Some(Protocol::Mammography));
+        assert_eq!(
+            views.map(|v| Protocol::Mammography.pane(&v)),
+            [Some(3), Some(0), Some(1)]
+        );
+        assert_eq!(
+            Protocol::detect(&[view("PA", ""), view("LL", "")]),
+            Some(Protocol::Chest)
+        );
+        assert_eq!(Protocol::detect(&[view("PA", "")]), None);
+    }
+}
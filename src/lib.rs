@@ -1,9 +1,48 @@
+pub mod anonymize;
+pub mod cache;
 pub mod cli;
+pub mod clipboard;
+pub mod codecs;
+pub mod color;
+pub mod datetime;
+pub mod dedupe;
 pub mod dicom;
 pub mod display;
 pub mod display_metadata;
+pub mod dump;
+pub mod explain;
+pub mod export;
+pub mod fusion;
+pub mod geometry;
+pub mod hash;
 pub mod image;
+pub mod index;
+pub mod interactive;
+pub mod interrupt;
+pub mod json;
+pub mod kos;
+pub mod layout;
+pub mod logging;
+pub mod merge;
+pub mod organize;
+pub mod paths;
+pub mod render;
+pub mod sink;
+pub mod split;
+pub mod stack;
+pub mod synthetic;
+pub mod tag;
+pub mod temporal;
+pub mod terminal;
+#[cfg(test)]
+mod test_support;
+pub mod transcode;
 pub mod types;
+pub mod uid;
 
 pub use display::init_terminal_display;
-pub use display_metadata::print_metadata;
+pub use display_metadata::{
+    MetadataFormat, MetadataGroup, print_error_details, print_explanation, print_histogram,
+    print_metadata, print_probes, print_roi_statistics, print_statistics,
+};
+pub use render::{RenderOptions, render};
@@ -0,0 +1,30 @@
+//! Debug logging of `tracing` events and span timings to stderr
+//!
+//! Enabled with `--debug` (or `-vv`), which logs dcmv's own events at debug
+//! level, or with `RUST_LOG`, e.g. `RUST_LOG=dcmv=trace,dicom_object=debug`.
+//! Spans (parse, decode, convert, display) log how long they took when
+//! they close.
+
+use std::env;
+use tracing_subscriber::EnvFilter;
+use tracing_subscriber::fmt::format::FmtSpan;
+use tracing_subscriber::fmt::time::Uptime;
+
+/// Install the logger, if `--debug` or `RUST_LOG` asks for it
+///
+/// `RUST_LOG` takes precedence over `--debug`.
+pub fn init(debug: bool) {
+    let filter = match env::var("RUST_LOG") {
+        Ok(directives) if !directives.trim().is_empty() => EnvFilter::new(directives),
+        _ if debug => EnvFilter::new("dcmv=debug"),
+        _ => return,
+    };
+
+    // Only fails if a subscriber was already set, which then stays in use
+    let _ = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr)
+        .with_timer(Uptime::default())
+        .with_span_events(FmtSpan::CLOSE)
+        .try_init();
+}
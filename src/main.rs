@@ -1,107 +1,821 @@
+use anyhow::anyhow;
 use clap::{CommandFactory, Parser};
-use dcmv::cli::Args;
-use dcmv::dicom::{self, DicomObject, ProcessError, read_stdin};
+use dcmv::cli::{Args, Command, LayoutKind, OutputFormat};
+use dcmv::color::Palette;
+use dcmv::dicom::{self, DicomObject, ExtractOptions, PresentationState, ProcessError, read_stdin};
 use dcmv::display;
-use dcmv::image;
-use std::io::{self, IsTerminal};
+use dcmv::dump::PrivateDictionary;
+use dcmv::fusion::Fusion;
+use dcmv::index::{self, InstanceEntry};
+use dcmv::interactive::Viewer;
+use dcmv::kos::KeyObjectSelection;
+use dcmv::layout;
+use dcmv::render::{RenderOptions, RenderSource};
+use dcmv::sink::{
+    self, ClipboardSink, FusionSink, LocalizerSink, OutputSink, PresentationStateSink,
+};
+use dcmv::stack::SliceStack;
+use dcmv::temporal::TemporalSeries;
+use dcmv::terminal::{Stream, TerminalContext};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// Longest side of --thumbnail images decimated from the full image
+const THUMBNAIL_SIZE: u16 = 128;
 
 fn main() {
-    let args = Args::parse();
+    let mut args = Args::parse();
+    args.expand_paths();
+    dcmv::logging::init(args.debug_logging());
+    let terminal = TerminalContext::get();
+    let stdout = args.palette(Stream::Stdout);
+    let stderr = args.palette(Stream::Stderr);
+    dcmv::interrupt::install();
+
+    // Progress is redrawn with escape sequences, so it is shown only where
+    // colors are
+    let progress = terminal
+        .progress()
+        .filter(|&stream| args.palette(stream).is_enabled());
+
+    if let Some(command) = &args.command {
+        if let Err(e) = run_command(command, &stderr, progress) {
+            fail(&stderr, &e);
+        }
+        return;
+    }
 
     // Show help if no files provided in TTY mode
-    if args.files.is_empty() && io::stdin().is_terminal() {
+    if args.files.is_empty() && terminal.stdin {
         let _ = Args::command().print_help();
         println!();
         return;
     }
 
-    let use_stdin = args.files.is_empty() && !io::stdin().is_terminal();
+    // Only the files of one volume of a 4D series are shown
+    if let Some(volume) = args.volume {
+        match index::volume_files(&args.files, volume) {
+            Ok(files) => args.files = files,
+            Err(e) => fail(&stderr, &e),
+        }
+    }
 
-    if use_stdin {
-        match read_stdin() {
-            Ok(dcm) => {
-                // Initialize terminal AFTER stdin reading, BEFORE processing
-                // This ensures terminal is in a clean state after progress display
-                dcmv::init_terminal_display();
+    if args.check {
+        match check_geometry(&args) {
+            Ok(true) => {}
+            Ok(false) => std::process::exit(1),
+            Err(e) => fail(&stderr, &e),
+        }
+        return;
+    }
 
-                if let Err(e) = process_dicom(&dcm, &args) {
-                    eprintln!("Error: {e}");
-                    std::process::exit(1);
-                }
+    if args.output.is_some() && args.files.len() > 1 {
+        fail(
+            &stderr,
+            &anyhow!("--output can only be used with a single input file"),
+        );
+    }
+
+    // Text on stdout would end up inside the PNG stream
+    if args.writes_image_to_stdout()
+        && let Some(option) = args.stdout_text_option()
+    {
+        fail(
+            &stderr,
+            &anyhow!("--output - writes the PNG to stdout, so {option} cannot print there too"),
+        );
+    }
+
+    // A DICOM file is written from the input's data set, not the rendering
+    if let Some(output) = &args.output
+        && dicom::is_dicom_output(output)
+    {
+        let result = match args.files.first() {
+            Some(input) if input.as_os_str() != "-" => {
+                dicom::redact_file(input, &args.redact, output)
+            }
+            _ => Err(anyhow!("--output .dcm needs a DICOM file, not stdin")),
+        };
+        if let Err(e) = result {
+            fail(&stderr, &e);
+        }
+        return;
+    }
+
+    let mut inputs = Input::from_files(&args.files);
+    if inputs
+        .iter()
+        .filter(|input| **input == Input::Stdin)
+        .count()
+        > 1
+    {
+        fail(
+            &stderr,
+            &anyhow!("`-` can only be given once, as stdin can be read only once"),
+        );
+    }
+
+    // A piped image given with other files is compared side by side with
+    // them, e.g. a pipeline's output with its input
+    if inputs.len() > 1
+        && inputs.contains(&Input::Stdin)
+        && args.grid.is_none()
+        && !args.interactive
+        && !args.dump
+    {
+        args.grid = u32::try_from(inputs.len()).ok();
+    }
+
+    // A study directory is hung by its views, e.g. a chest PA next to the
+    // lateral, and only the hung files are shown
+    if args.wants_layout()
+        && !inputs.contains(&Input::Stdin)
+        && args.grid.is_none()
+        && args.localizer.is_none()
+        && args.output.is_none()
+        && !args.interactive
+        && !args.play_temporal
+        && !args.dump
+    {
+        match layout::plan(&args.files, args.hanging_protocol()) {
+            Ok(Some(hanging)) => {
+                args.layout = Some(hanging.protocol.into());
+                inputs = hanging.files.into_iter().map(Input::File).collect();
             }
-            Err(e) => {
-                eprintln!("Error: {e}");
-                std::process::exit(1);
+            Ok(None) => {
+                if args.layout.is_some() {
+                    warn(&args, "No views for a hanging layout found");
+                }
+                args.layout = Some(LayoutKind::None);
             }
+            Err(e) => fail(&stderr, &e),
+        }
+    }
+    let args = args;
+
+    // Loaded once, so that a broken dictionary is reported before any file
+    let dictionary = args.dump.then(|| {
+        let mut dictionary = PrivateDictionary::builtin();
+        if let Some(path) = &args.private_dict
+            && let Err(e) = dictionary.load(path)
+        {
+            fail(&stderr, &e);
+        }
+        dictionary
+    });
+
+    // Also loaded before any file, and once for all of them
+    let localizer = args.localizer.as_deref().map(|path| {
+        path.load(&ExtractOptions::default())
+            .unwrap_or_else(|e| fail(&stderr, &e.context("--localizer")))
+    });
+
+    let fusion = args.fuse.as_deref().map(|path| {
+        Fusion::open(path, args.fuse_alpha).unwrap_or_else(|e| fail(&stderr, &e.context("--fuse")))
+    });
+
+    let presentation_state = args.presentation_state.as_deref().map(|path| {
+        PresentationState::open(path)
+            .unwrap_or_else(|e| fail(&stderr, &e.context("--presentation-state")))
+    });
+
+    // Stdin is read before the terminal is set up, so that the terminal is
+    // in a clean state after the progress display
+    let mut piped = inputs.contains(&Input::Stdin).then(|| {
+        if terminal.stdin {
+            Err(anyhow!("Nothing is piped to stdin for `-`"))
+        } else {
+            read_stdin(progress, args.force)
+        }
+    });
+
+    // Initialize terminal once before file processing loop
+    // (viuer caches protocol results, so no need to call per-file)
+    dcmv::init_terminal_display();
+
+    // The time points of a dynamic series are played in the viewer
+    if args.play_temporal {
+        let result = if inputs.contains(&Input::Stdin) {
+            Err(anyhow!(
+                "--play-temporal needs files, as stdin holds only one"
+            ))
+        } else {
+            play_temporal(&args)
+        };
+        if let Err(e) = result {
+            fail(&stderr, &e);
+        }
+        return;
+    }
+
+    // Several files or a directory are stepped through as one series
+    if args.interactive
+        && dictionary.is_none()
+        && !inputs.contains(&Input::Stdin)
+        && (inputs.len() > 1 || args.files.iter().any(|file| file.is_dir()))
+    {
+        if let Err(e) = browse_series(&args) {
+            fail(&stderr, &e);
         }
+        return;
+    }
+
+    // A grid shows its images together, after the text of all files
+    let multiple_inputs = inputs.len() > 1;
+    let show_names = if args.grid.is_some() || args.hanging_protocol().is_some() {
+        args.filename || args.shows_metadata()
     } else {
-        // Initialize terminal once before file processing loop
-        // (viuer caches protocol results, so no need to call per-file)
-        dcmv::init_terminal_display();
+        multiple_inputs || args.filename
+    };
+    let mut any_failed = false;
+    let mut sink = match localizer {
+        Some(localizer) => Box::new(LocalizerSink::new(
+            display::renderer_from_args(&args),
+            RenderOptions::from_args(&args),
+            localizer,
+        )),
+        None => sink::from_args(&args),
+    };
+    // Inside the fusion, so the fused image is copied
+    if args.copy {
+        sink = Box::new(ClipboardSink::new(sink, RenderOptions::from_args(&args)));
+    }
+    if let Some(fusion) = fusion {
+        sink = Box::new(FusionSink::new(
+            sink,
+            fusion,
+            RenderOptions::from_args(&args),
+        ));
+    }
+    // Outermost, so the annotations reach every other sink
+    if let Some(presentation_state) = presentation_state {
+        sink = Box::new(PresentationStateSink::new(sink, presentation_state));
+    }
 
-        let multiple_files = args.files.len() > 1;
-        let mut any_failed = false;
+    for (idx, input) in inputs.iter().enumerate() {
+        if show_names {
+            println!("{}", stdout.heading(input));
+        }
 
-        for (idx, file_path) in args.files.iter().enumerate() {
-            if multiple_files || args.filename {
-                println!("{}", file_path.display());
+        let result = match (input, &dictionary) {
+            (Input::Stdin, dictionary) => {
+                process_piped(piped.take(), &args, dictionary.as_ref(), sink.as_mut())
             }
+            (Input::File(path), Some(dictionary)) => dump_file(path, &args, dictionary),
+            (Input::File(path), None) => process_file(path, &args, sink.as_mut()),
+        };
+        if let Err(e) = result {
+            report_error(&e, input.path(), &args);
+            any_failed = true;
+        }
 
-            if let Err(e) = process_file(file_path, &args) {
-                println!("Error: {e}");
-                any_failed = true;
-            }
+        if show_names && multiple_inputs && idx < inputs.len() - 1 {
+            println!();
+        }
+    }
+
+    if !finish(sink.as_mut(), &args) || any_failed {
+        std::process::exit(1);
+    }
+}
 
-            if multiple_files && idx < args.files.len() - 1 {
-                println!();
+/// Run a subcommand, printing the files it wrote
+fn run_command(
+    command: &Command,
+    stderr: &Palette,
+    progress: Option<Stream>,
+) -> anyhow::Result<()> {
+    let print_files = |files: Vec<PathBuf>| {
+        for file in files {
+            println!("{}", file.display());
+        }
+    };
+
+    match command {
+        Command::Codecs => dcmv::codecs::print_codecs(),
+        Command::Probe { renderer } => display::probe::print_probe(*renderer),
+        Command::Uid { query } => dcmv::uid::print_lookup(&query.join(" "))?,
+        Command::Tag { query } => dcmv::tag::print_lookup(&query.join(" "))?,
+        Command::Ls { dir, index } => {
+            let index = index.as_ref().map(|file| {
+                file.clone()
+                    .unwrap_or_else(|| dir.join(dcmv::index::DEFAULT_INDEX_FILE))
+            });
+            dcmv::index::print_series_table(dir, index.as_deref(), progress)?;
+        }
+        Command::Transcode { input, ts, output } => {
+            dcmv::transcode::transcode(input, *ts, output)?;
+        }
+        Command::Split { input, output } => print_files(dcmv::split::split(input, output)?),
+        Command::Merge { inputs, output } => {
+            dcmv::merge::merge(inputs, output)?;
+        }
+        Command::Anonymize {
+            inputs,
+            output,
+            uid_map,
+        } => print_files(dcmv::anonymize::anonymize(
+            inputs,
+            output,
+            uid_map.as_deref(),
+            |warning| eprintln!("{} {warning}", stderr.warning()),
+        )?),
+        Command::Dedupe { dir, link, move_to } => {
+            let action = match (move_to, link) {
+                (Some(target), _) => dcmv::dedupe::Action::MoveTo(target.clone()),
+                (None, true) => dcmv::dedupe::Action::Link,
+                (None, false) => dcmv::dedupe::Action::Report,
+            };
+            dcmv::dedupe::dedupe(dir, &action, progress)?;
+        }
+        Command::Organize {
+            inputs,
+            output,
+            template,
+            move_files,
+            dry_run,
+        } => {
+            let template = dcmv::organize::Template::parse(template)?;
+            let files = dcmv::organize::organize(
+                inputs,
+                output,
+                &template,
+                *move_files,
+                *dry_run,
+                progress,
+            )?;
+            for (file, destination) in files {
+                println!("{} -> {}", file.display(), destination.display());
             }
         }
+        Command::Gen { dir } => print_files(dcmv::synthetic::generate(dir)?),
+    }
+    Ok(())
+}
+
+/// Report an error that ends dcmv, and exit with status 1
+///
+/// An interrupted run exits as on Ctrl-C instead.
+fn fail(stderr: &Palette, error: &anyhow::Error) -> ! {
+    dcmv::interrupt::exit_if_interrupted(error);
+    eprintln!("{} {error:#}", stderr.error());
+    std::process::exit(1);
+}
+
+/// A DICOM file to show, or stdin
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Input {
+    Stdin,
+    File(PathBuf),
+}
+
+impl Input {
+    /// Inputs in the order given, with `-` standing for stdin; stdin alone
+    /// without any files
+    fn from_files(files: &[PathBuf]) -> Vec<Self> {
+        if files.is_empty() {
+            return vec![Self::Stdin];
+        }
+
+        files
+            .iter()
+            .map(|file| {
+                if file.as_os_str() == "-" {
+                    Self::Stdin
+                } else {
+                    Self::File(file.clone())
+                }
+            })
+            .collect()
+    }
+
+    fn path(&self) -> Option<&Path> {
+        match self {
+            Self::Stdin => None,
+            Self::File(path) => Some(path),
+        }
+    }
+}
 
-        if any_failed {
-            std::process::exit(1);
+impl fmt::Display for Input {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Stdin => f.write_str("-"),
+            Self::File(path) => write!(f, "{}", path.display()),
         }
     }
 }
 
-/// Process a parsed DICOM object (common logic for files and stdin)
-fn process_dicom(obj: &DicomObject, args: &Args) -> Result<(), ProcessError> {
-    let metadata = match dicom::extract_dicom_data(obj) {
-        Ok(m) => m,
+/// Show what the sink held back, reporting whether that succeeded
+fn finish(sink: &mut dyn OutputSink, args: &Args) -> bool {
+    match sink.finish() {
+        Ok(()) => true,
         Err(e) => {
-            // Try to get partial metadata for verbose display before failing
-            let partial_metadata = dicom::extract_metadata_tags(obj);
+            eprintln!("{} {e:#}", args.palette(Stream::Stderr).error());
+            false
+        }
+    }
+}
 
-            if args.verbose
-                && let Ok(meta) = partial_metadata
-            {
-                dcmv::print_metadata(&meta);
-            }
+/// Process a parsed DICOM object (common logic for files and stdin)
+fn process_dicom(
+    obj: &DicomObject,
+    file: Option<&Path>,
+    args: &Args,
+    sink: &mut dyn OutputSink,
+) -> Result<(), ProcessError> {
+    let icon = if args.thumbnail {
+        dicom::icon_image(obj)
+    } else {
+        None
+    };
+    let obj = icon.as_ref().unwrap_or(obj);
+
+    let options = extract_options(args);
 
-            return Err(ProcessError::ExtractionFailed(e));
+    let metadata = dicom::extract_dicom_data_with_options(obj, &options).map_err(|error| {
+        // Keep whatever tags can still be read for the verbose error report
+        ProcessError::ExtractionFailed {
+            metadata: dicom::extract_metadata_tags(obj).ok().map(Box::new),
+            error,
         }
+    })?;
+    let metadata = metadata.stamped(file);
+
+    // Of the pixels as decoded, before --thumbnail and --redact change them
+    if args.hash {
+        dcmv::hash::print_hashes(file, &metadata);
+    }
+
+    let metadata = if args.thumbnail {
+        metadata.thumbnail(THUMBNAIL_SIZE).unwrap_or(metadata)
+    } else {
+        metadata
+    };
+
+    // Before anything is shown, measured or written
+    let metadata = if args.redact.is_empty() {
+        metadata
+    } else {
+        let Some(redacted) = metadata.redacted(&args.redact) else {
+            return Err(ProcessError::ConversionFailed {
+                metadata: Box::new(metadata),
+                error: anyhow!(
+                    "--redact does not support planar, chroma-subsampled or sub-byte pixel data"
+                ),
+            });
+        };
+        redacted
+    };
+
+    // Probes and ROIs are given in stored coordinates, so only what is shown
+    // is cropped, and not at all for a presentation state, which is applied
+    // to the shown image
+    let shown = if args.no_crop || args.presentation_state.is_some() {
+        None
+    } else {
+        metadata.cropped_to_imaging_region()
     };
+    let shown = shown.as_ref().unwrap_or(&metadata);
 
-    if args.verbose {
-        dcmv::print_metadata(&metadata);
+    // JSON reports carry the warnings in their metadata instead
+    if args.format == OutputFormat::Text {
+        for warning in &metadata.warnings {
+            eprintln!("{} {warning}", args.palette(Stream::Stderr).warning());
+        }
     }
 
-    let image = image::convert_to_image(&metadata).map_err(|e| ProcessError::ConversionFailed {
-        metadata: Box::new(metadata.clone()),
-        error: e,
-    })?;
+    // Removing tags does not remove the patient's name from the pixels
+    if metadata.burned_in_annotation == Some(true)
+        && args.redact.is_empty()
+        && (args.output.is_some() || args.copy)
+    {
+        warn(
+            args,
+            "Burned In Annotation is YES: the pixels may show identifying text, \
+             which is exported as it is; --redact X,Y,W,H hides a region",
+        );
+    }
 
-    display::print_image(&image, &metadata, args).map_err(|e| ProcessError::DisplayFailed {
-        metadata: Box::new(metadata),
-        error: e,
-    })?;
+    if args.shows_metadata() && args.format == OutputFormat::Json {
+        println!("{}", dcmv::json::metadata_to_json(&metadata));
+    } else if args.shows_metadata() {
+        dcmv::print_metadata(&metadata, &args.metadata_format());
+    } else if args.stats {
+        dcmv::print_statistics(&metadata);
+    }
+
+    if !args.probe.is_empty() {
+        dcmv::print_probes(&metadata, &RenderOptions::from_args(args), &args.probe);
+    }
+
+    if args.explain {
+        dcmv::print_explanation(
+            shown,
+            &RenderOptions::from_args(args),
+            &sink::describe(args),
+        );
+    }
+
+    if !args.roi_rect.is_empty() {
+        dcmv::print_roi_statistics(&metadata, &args.roi_rect);
+    }
+
+    sink.write(shown)?;
+
+    if args.histogram {
+        dcmv::print_histogram(&metadata, args.width);
+    }
+
+    Ok(())
+}
+
+/// Report a failed file (`None` for stdin)
+///
+/// With `--format json` the report is a single JSON line on stderr.
+/// Otherwise verbose details precede the message, which goes to stdout for
+/// files, in line with their other output, and to stderr for stdin or when
+/// stdout carries the image (`--output -`).
+fn report_error(error: &ProcessError, file: Option<&Path>, args: &Args) {
+    if args.format == OutputFormat::Json {
+        eprintln!("{}", dcmv::json::error_to_json(error, file));
+        return;
+    }
+
+    if args.shows_metadata() {
+        dcmv::print_error_details(error, &args.metadata_format());
+    }
+
+    if file.is_some() && !args.writes_image_to_stdout() {
+        println!("{} {error}", args.palette(Stream::Stdout).error());
+    } else {
+        eprintln!("{} {error}", args.palette(Stream::Stderr).error());
+    }
+}
+
+/// Process the object read from stdin
+///
+/// `piped` is `None` once it was processed, as stdin can be read only once.
+fn process_piped(
+    piped: Option<anyhow::Result<DicomObject>>,
+    args: &Args,
+    dictionary: Option<&PrivateDictionary>,
+    sink: &mut dyn OutputSink,
+) -> Result<(), ProcessError> {
+    let mut obj = piped
+        .ok_or_else(|| anyhow!("Stdin can only be read once"))?
+        .map_err(|e| {
+            e.downcast::<ProcessError>()
+                .unwrap_or_else(ProcessError::NotADicomFile)
+        })?;
+
+    if args.force
+        && let Some(mismatch) = dicom::fix_pixel_data_syntax(&mut obj)
+    {
+        warn(args, &mismatch);
+    }
+
+    match dictionary {
+        Some(dictionary) => {
+            dcmv::dump::print_dump(&obj, dictionary);
+            Ok(())
+        }
+        // Without a directory, the key images cannot be looked for
+        None if let Some(kos) = KeyObjectSelection::from_object(&obj) => {
+            show_key_images(&kos, None, args, sink);
+            Ok(())
+        }
+        None => process_dicom(&obj, None, args, sink),
+    }
+}
 
+/// List the elements of a single DICOM file (for `--dump`)
+fn dump_file(
+    file_path: &Path,
+    args: &Args,
+    dictionary: &PrivateDictionary,
+) -> Result<(), ProcessError> {
+    let obj = open_file(file_path, args)?;
+    dcmv::dump::print_dump(&obj, dictionary);
     Ok(())
 }
 
 /// Process a single DICOM file
-fn process_file(file_path: &std::path::Path, args: &Args) -> Result<(), ProcessError> {
-    let obj = dicom::open_dicom_file(file_path)?;
-    process_dicom(&obj, args)
+fn process_file(
+    file_path: &std::path::Path,
+    args: &Args,
+    sink: &mut dyn OutputSink,
+) -> Result<(), ProcessError> {
+    // The icon precedes the pixel data, which then need not be read at all
+    if args.thumbnail
+        && let Some(header) = open_header(file_path, args)?
+        && let Some(icon) = dicom::icon_image(&header)
+    {
+        return process_dicom(&icon, Some(file_path), args, sink);
+    }
+
+    // Cached renders need only the header, as long as nothing else needs
+    // the pixel data
+    if args.cache
+        && !args.shows_metadata()
+        && !args.stats
+        && !args.hash
+        && !args.histogram
+        && args.probe.is_empty()
+        && args.roi_rect.is_empty()
+        && args.redact.is_empty()
+        && !args.explain
+        && let Some(header) = open_header(file_path, args)?
+        && let Ok(metadata) =
+            dicom::extract_metadata_tags(&header).map(|metadata| metadata.stamped(Some(file_path)))
+        && sink::is_cached(args, &metadata)
+    {
+        return sink.write(&metadata);
+    }
+
+    let obj = open_file(file_path, args)?;
+    if let Some(kos) = KeyObjectSelection::from_object(&obj) {
+        let directory = file_path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+            .unwrap_or(Path::new("."));
+        show_key_images(&kos, Some(directory), args, sink);
+        return Ok(());
+    }
+    process_dicom(&obj, Some(file_path), args, sink)
+}
+
+/// List the images flagged by a Key Object Selection and show those found
+/// in `directory`
+fn show_key_images(
+    kos: &KeyObjectSelection,
+    directory: Option<&Path>,
+    args: &Args,
+    sink: &mut dyn OutputSink,
+) {
+    let found = directory.map_or_else(HashMap::new, |directory| {
+        kos.locate(directory).unwrap_or_else(|e| {
+            warn(args, &format!("{e:#}"));
+            HashMap::new()
+        })
+    });
+    print!("{}", kos.describe(&found));
+
+    for image in &kos.images {
+        if let Some(path) = found.get(&image.sop_instance_uid)
+            && let Err(e) = process_file(path, args, sink)
+        {
+            report_error(&e, Some(path), args);
+        }
+    }
+}
+
+/// Show the files as the slices of one series (for --interactive with
+/// several files or a directory)
+fn browse_series(args: &Args) -> anyhow::Result<()> {
+    let (stack, scan) = SliceStack::open(&args.files, extract_options(args))?;
+
+    let result = Viewer::with_stack(args, RenderOptions::from_args(args), stack)
+        .and_then(|mut viewer| viewer.run());
+
+    // After the viewer, so they are not hidden behind its screen
+    warn_skipped(args, &scan.skipped);
+    warn_geometry(args, &scan.instances);
+    result
+}
+
+/// Play the time points of a dynamic series (for --play-temporal)
+fn play_temporal(args: &Args) -> anyhow::Result<()> {
+    let (series, skipped) = TemporalSeries::open(&args.files)?;
+    let stack = SliceStack::from_paths(series.paths.clone(), extract_options(args));
+
+    let result = Viewer::with_time_points(
+        args,
+        RenderOptions::from_args(args),
+        stack,
+        &series,
+        args.fps,
+    )
+    .and_then(|mut viewer| viewer.run());
+
+    warn_skipped(args, &skipped);
+    result
+}
+
+/// Print the slice geometry of each series among the files (for --check),
+/// returning whether all are consistent
+fn check_geometry(args: &Args) -> anyhow::Result<bool> {
+    let instances = index::read_instances(&args.files)?;
+    if instances.is_empty() {
+        return Err(anyhow!("No DICOM files to check"));
+    }
+
+    let mut consistent = true;
+    for series in index::group_series(&instances) {
+        let report = index::check_series(&series);
+        println!("{}: {report}", series.name());
+        for problem in &report.problems {
+            println!("  {problem}");
+        }
+        consistent &= report.problems.is_empty();
+    }
+    Ok(consistent)
+}
+
+/// Warn of gaps and inconsistencies in the slices of a browsed series
+fn warn_geometry(args: &Args, instances: &[InstanceEntry]) {
+    let series = index::group_series(instances);
+    for s in &series {
+        for problem in index::check_series(s).problems {
+            if series.len() > 1 {
+                warn(args, &format!("{}: {problem}", s.name()));
+            } else {
+                warn(args, &problem);
+            }
+        }
+    }
+}
+
+fn warn_skipped(args: &Args, skipped: &[PathBuf]) {
+    for path in skipped {
+        warn(
+            args,
+            &format!("Skipped, not a DICOM file: {}", path.display()),
+        );
+    }
+}
+
+/// How pixel data are extracted, as given by the arguments
+fn extract_options(args: &Args) -> ExtractOptions {
+    ExtractOptions {
+        target_width: display_width(args),
+        photometric: args.photometric_override(),
+        planar_configuration: args.force_planar,
+        byte_order: args.byte_order_override(),
+    }
+}
+
+/// Pixel width of images shown in the terminal, so that J2K images are
+/// decoded only at the resolution needed
+///
+/// `None` when every pixel is needed: for exports, hashes, measurements,
+/// the statistics in metadata output and the interactive viewer.
+fn display_width(args: &Args) -> Option<u32> {
+    let full_resolution = args.output.is_some()
+        || args.copy
+        || args.interactive
+        || args.play_temporal
+        || args.hash
+        || args.stats
+        || args.shows_metadata()
+        || args.histogram
+        || !args.probe.is_empty()
+        || !args.roi_rect.is_empty();
+
+    if full_resolution {
+        None
+    } else {
+        display::target_pixel_width(args)
+    }
+}
+
+/// Open a DICOM file, with --force also one with the wrong transfer syntax
+/// or a bare dataset
+fn open_file(file_path: &Path, args: &Args) -> anyhow::Result<DicomObject> {
+    let mut obj = match dicom::open_dicom_file(file_path) {
+        Err(e) if args.force => match dicom::open_mislabeled(file_path) {
+            Ok((obj, mismatch)) => {
+                warn(args, &mismatch);
+                obj
+            }
+            Err(_) => dicom::open_raw_dataset(file_path).map_err(|raw| e.context(raw))?,
+        },
+        result => result?,
+    };
+
+    if args.force
+        && let Some(mismatch) = dicom::fix_pixel_data_syntax(&mut obj)
+    {
+        warn(args, &mismatch);
+    }
+    Ok(obj)
+}
+
+/// Open the header of a DICOM file
+///
+/// `None` with --force when it cannot be read, as the file may need
+/// recovering, which reads it in full.
+fn open_header(file_path: &Path, args: &Args) -> anyhow::Result<Option<DicomObject>> {
+    match dicom::open_dicom_header(file_path) {
+        Ok(header) => Ok(Some(header)),
+        Err(_) if args.force => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+fn warn(args: &Args, warning: &str) {
+    eprintln!("{} {warning}", args.palette(Stream::Stderr).warning());
 }
 
 #[cfg(test)]
@@ -109,6 +823,20 @@ mod tests {
     use super::*;
     use std::path::Path;
 
+    #[test]
+    fn test_dash_stands_for_stdin_among_files() {
+        let files = ["a.dcm", "-", "b.dcm"].map(PathBuf::from);
+        assert_eq!(
+            Input::from_files(&files),
+            vec![
+                Input::File(files[0].clone()),
+                Input::Stdin,
+                Input::File(files[2].clone()),
+            ]
+        );
+        assert_eq!(Input::from_files(&[]), vec![Input::Stdin]);
+    }
+
     #[test]
     fn test_rtstruct_returns_notadicomfile_error() {
         let file_path = Path::new(".test-files/rtstruct.dcm");
@@ -116,13 +844,11 @@ mod tests {
 
         let args = Args {
             files: vec![file_path.to_path_buf()],
-            verbose: true,
-            width: None,
-            height: None,
-            filename: false,
+            verbose: 1,
+            ..Default::default()
         };
 
-        let result = process_file(file_path, &args);
+        let result = process_file(file_path, &args, sink::from_args(&args).as_mut());
         assert_matches::assert_matches!(result, Err(ProcessError::NotADicomFile(_)));
     }
 
@@ -133,13 +859,11 @@ mod tests {
 
         let args = Args {
             files: vec![file_path.to_path_buf()],
-            verbose: true,
-            width: None,
-            height: None,
-            filename: false,
+            verbose: 1,
+            ..Default::default()
         };
 
-        let result = process_file(file_path, &args);
-        assert_matches::assert_matches!(result, Err(ProcessError::ExtractionFailed(_)));
+        let result = process_file(file_path, &args, sink::from_args(&args).as_mut());
+        assert_matches::assert_matches!(result, Err(ProcessError::ExtractionFailed { .. }));
     }
 }
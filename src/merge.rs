@@ -0,0 +1,461 @@
+//! Merging a series of single-frame files into one file for `dcmv merge`
+//!
+//! CT, MR and PET series are written as Legacy Converted Enhanced images.
+//! The attributes of the first file are kept, and those that can differ
+//! between slices (position, orientation, pixel spacing, rescale and
+//! window) move into functional groups: shared when every slice has the
+//! same values, per frame otherwise. Each frame also records the slice it
+//! was converted from. Compressed slices are copied without decoding when
+//! all are in the same transfer syntax.
+
+use crate::dicom::{self, DicomObject, SaveOptions, save};
+use crate::stack;
+use ::dicom::core::header::Header;
+use ::dicom::core::value::{DataSetSequence, PixelFragmentSequence, PrimitiveValue};
+use ::dicom::core::{DataElement, Tag, VR};
+use ::dicom::dictionary_std::{tags, uids};
+use ::dicom::object::InMemDicomObject;
+use anyhow::{Context, Result, bail};
+use std::path::{Path, PathBuf};
+use tracing::debug;
+
+/// Functional groups filled from the attributes of each slice
+const GROUPS: [(Tag, &[Tag]); 5] = [
+    (
+        tags::PIXEL_MEASURES_SEQUENCE,
+        &[tags::PIXEL_SPACING, tags::SLICE_THICKNESS],
+    ),
+    (
+        tags::PLANE_ORIENTATION_SEQUENCE,
+        &[tags::IMAGE_ORIENTATION_PATIENT],
+    ),
+    (
+        tags::PLANE_POSITION_SEQUENCE,
+        &[tags::IMAGE_POSITION_PATIENT],
+    ),
+    (
+        tags::PIXEL_VALUE_TRANSFORMATION_SEQUENCE,
+        &[
+            tags::RESCALE_INTERCEPT,
+            tags::RESCALE_SLOPE,
+            tags::RESCALE_TYPE,
+        ],
+    ),
+    (
+        tags::FRAME_VOILUT_SEQUENCE,
+        &[
+            tags::WINDOW_CENTER,
+            tags::WINDOW_WIDTH,
+            tags::WINDOW_CENTER_WIDTH_EXPLANATION,
+        ],
+    ),
+];
+
+/// Attributes every slice must share to be frames of one image
+const SHARED: [Tag; 7] = [
+    tags::SERIES_INSTANCE_UID,
+    tags::ROWS,
+    tags::COLUMNS,
+    tags::SAMPLES_PER_PIXEL,
+    tags::BITS_ALLOCATED,
+    tags::PIXEL_REPRESENTATION,
+    tags::PHOTOMETRIC_INTERPRETATION,
+];
+
+struct Slice {
+    path: PathBuf,
+    obj: DicomObject,
+}
+
+/// Merge the slices among `paths` into one multi-frame file at `output`
+///
+/// Directories stand for the files directly in them. Slices are ordered
+/// along the normal of their planes, or by Instance Number. Returns the
+/// number of frames written.
+///
+/// # Errors
+///
+/// Returns an error if there are no slices, they are not one CT, MR or PET
+/// series of same-sized images, or the file cannot be written
+pub fn merge(paths: &[PathBuf], output: &Path) -> Result<usize> {
+    let slices: Vec<Slice> = stack::expand_directories(paths)?
+        .into_iter()
+        .filter_map(|path| match dicom::open_dicom_file(&path) {
+            Ok(obj) => Some(Slice { path, obj }),
+            Err(e) => {
+                debug!("not a slice: {e:#}");
+                None
+            }
+        })
+        .collect();
+    let Some(first) = slices.first() else {
+        bail!("No DICOM files to merge");
+    };
+
+    let sop_class = match text(&first.obj, tags::MODALITY).as_deref() {
+        Some("CT") => uids::LEGACY_CONVERTED_ENHANCED_CT_IMAGE_STORAGE,
+        Some("MR") => uids::LEGACY_CONVERTED_ENHANCED_MR_IMAGE_STORAGE,
+        Some("PT") => uids::LEGACY_CONVERTED_ENHANCED_PET_IMAGE_STORAGE,
+        other => bail!(
+            "Only CT, MR and PET series can be merged, not {}",
+            other.unwrap_or("images without a Modality")
+        ),
+    };
+    let transfer_syntax = first.obj.meta().transfer_syntax().to_string();
+    for slice in &slices[1..] {
+        if slice.obj.meta().transfer_syntax() != transfer_syntax {
+            bail!(
+                "{} is in another transfer syntax than {}",
+                slice.path.display(),
+                first.path.display()
+            );
+        }
+        if let Some(&tag) = SHARED
+            .iter()
+            .find(|&&tag| text(&slice.obj, tag) != text(&first.obj, tag))
+        {
+            bail!(
+                "{} differs from {} in {tag}",
+                slice.path.display(),
+                first.path.display()
+            );
+        }
+    }
+
+    let mut slices = sort_slices(slices);
+    let frame_count = slices.len();
+    let pixel_data = merge_pixel_data(&mut slices)?;
+
+    // Each group of each slice, taken out of the slice
+    let groups: Vec<Vec<Option<InMemDicomObject>>> = GROUPS
+        .iter()
+        .map(|(_, attributes)| {
+            slices
+                .iter_mut()
+                .map(|slice| {
+                    let elements: Vec<_> = attributes
+                        .iter()
+                        .filter_map(|&tag| slice.obj.take(tag))
+                        .collect();
+                    (!elements.is_empty()).then(|| InMemDicomObject::from_element_iter(elements))
+                })
+                .collect()
+        })
+        .collect();
+
+    let mut shared = InMemDicomObject::new_empty();
+    let mut per_frame: Vec<InMemDicomObject> = slices
+        .iter()
+        .enumerate()
+        .map(|(index, slice)| frame_item(index, &slice.obj))
+        .collect();
+    for ((sequence, _), items) in GROUPS.iter().zip(groups) {
+        if items.iter().all(Option::is_none) {
+            continue;
+        }
+        let first = &items[0];
+        if items.iter().all(|item| same(item, first)) {
+            if let Some(item) = items.into_iter().next().flatten() {
+                shared.put(sequence_element(*sequence, item));
+            }
+        } else {
+            for (frame, item) in per_frame.iter_mut().zip(items) {
+                frame.put(sequence_element(
+                    *sequence,
+                    item.unwrap_or_else(InMemDicomObject::new_empty),
+                ));
+            }
+        }
+    }
+
+    let Slice { mut obj, .. } = slices.swap_remove(0);
+    for tag in [
+        tags::SLICE_LOCATION,
+        tags::PER_FRAME_FUNCTIONAL_GROUPS_SEQUENCE,
+    ] {
+        obj.remove_element(tag);
+    }
+    obj.put(DataElement::new(tags::SOP_CLASS_UID, VR::UI, sop_class));
+    obj.put(DataElement::new(tags::INSTANCE_NUMBER, VR::IS, "1"));
+    obj.put(DataElement::new(
+        tags::NUMBER_OF_FRAMES,
+        VR::IS,
+        frame_count.to_string(),
+    ));
+    obj.put(DataElement::new(
+        tags::SHARED_FUNCTIONAL_GROUPS_SEQUENCE,
+        VR::SQ,
+        DataSetSequence::from(vec![shared]),
+    ));
+    obj.put(DataElement::new(
+        tags::PER_FRAME_FUNCTIONAL_GROUPS_SEQUENCE,
+        VR::SQ,
+        DataSetSequence::from(per_frame),
+    ));
+    obj.put(pixel_data);
+
+    save(
+        &mut obj,
+        output,
+        SaveOptions {
+            new_instance_uid: true,
+        },
+    )?;
+    Ok(frame_count)
+}
+
+/// `slices` ordered along the normal of the first one, when all have a
+/// position and orientation, or else by Instance Number when all have one
+fn sort_slices(slices: Vec<Slice>) -> Vec<Slice> {
+    let numbers = |slice: &Slice, tag| -> Option<Vec<f64>> {
+        slice
+            .obj
+            .element(tag)
+            .ok()
+            .and_then(|element| element.to_multi_float64().ok())
+    };
+    let normal = numbers(&slices[0], tags::IMAGE_ORIENTATION_PATIENT)
+        .filter(|cosines| cosines.len() == 6)
+        .map(|c| {
+            [
+                c[1].mul_add(c[5], -(c[2] * c[4])),
+                c[2].mul_add(c[3], -(c[0] * c[5])),
+                c[0].mul_add(c[4], -(c[1] * c[3])),
+            ]
+        });
+    let distances: Option<Vec<f64>> = slices
+        .iter()
+        .map(|slice| {
+            let normal = normal?;
+            let position = numbers(slice, tags::IMAGE_POSITION_PATIENT)?;
+            (position.len() == 3).then(|| (0..3).map(|i| position[i] * normal[i]).sum())
+        })
+        .collect();
+    let keys = distances.or_else(|| {
+        slices
+            .iter()
+            .map(|slice| {
+                let number = slice.obj.element(tags::INSTANCE_NUMBER).ok()?;
+                number.to_int::<i32>().ok().map(f64::from)
+            })
+            .collect()
+    });
+
+    let Some(keys) = keys else {
+        return slices;
+    };
+    let mut keyed: Vec<(f64, Slice)> = keys.into_iter().zip(slices).collect();
+    keyed.sort_by(|(a, _), (b, _)| a.total_cmp(b));
+    keyed.into_iter().map(|(_, slice)| slice).collect()
+}
+
+/// Pixel Data holding the frame of each slice, in order
+fn merge_pixel_data(slices: &mut [Slice]) -> Result<DataElement<InMemDicomObject>> {
+    let elements: Vec<_> = slices
+        .iter_mut()
+        .map(|slice| {
+            slice
+                .obj
+                .take(tags::PIXEL_DATA)
+                .with_context(|| format!("{} has no pixel data", slice.path.display()))
+        })
+        .collect::<Result<_>>()?;
+    let vr = elements[0].vr();
+
+    if elements[0].value().fragments().is_some() {
+        // One fragment per frame, with an offset table to find them
+        let frames: Vec<Vec<u8>> = elements
+            .iter()
+            .map(|element| element.value().fragments().unwrap_or_default().concat())
+            .collect();
+        let mut offsets = Vec::with_capacity(frames.len());
+        let mut offset = 0_u32;
+        for frame in &frames {
+            offsets.push(offset);
+            offset += u32::try_from(frame.len() + 8).context("Frame is too large")?;
+        }
+        return Ok(DataElement::new(
+            tags::PIXEL_DATA,
+            vr,
+            PixelFragmentSequence::new(offsets, frames),
+        ));
+    }
+
+    let metadata = dicom::extract_metadata_tags(&slices[0].obj)?;
+    let frame_bytes = metadata.dimensions.pixel_count()
+        * usize::from(metadata.samples_per_pixel)
+        * usize::from(metadata.bits_allocated())
+        / 8;
+
+    let value = match elements
+        .iter()
+        .map(|element| element.value().primitive())
+        .collect::<Option<Vec<_>>>()
+    {
+        Some(values) => concat_values(&values, frame_bytes)?,
+        None => bail!("Pixel data of the slices is not all uncompressed"),
+    };
+    Ok(DataElement::new(tags::PIXEL_DATA, vr, value))
+}
+
+/// Uncompressed frames of `frame_bytes` bytes one after the other
+fn concat_values(values: &[&PrimitiveValue], frame_bytes: usize) -> Result<PrimitiveValue> {
+    fn concat<T: Clone>(frames: &[&[T]], frame_len: usize) -> Option<Vec<T>> {
+        frames
+            .iter()
+            .map(|frame| frame.get(..frame_len))
+            .collect::<Option<Vec<_>>>()
+            .map(|frames| frames.concat())
+    }
+
+    let merged = match values[0] {
+        PrimitiveValue::U8(_) => values
+            .iter()
+            .map(|value| match value {
+                PrimitiveValue::U8(bytes) => Some(&bytes[..]),
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>()
+            .and_then(|frames| concat(&frames, frame_bytes))
+            .map(PrimitiveValue::from),
+        PrimitiveValue::U16(_) => values
+            .iter()
+            .map(|value| match value {
+                PrimitiveValue::U16(words) => Some(&words[..]),
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>()
+            .and_then(|frames| concat(&frames, frame_bytes / 2))
+            .map(|words| PrimitiveValue::U16(words.into())),
+        _ => bail!("Unsupported pixel data value"),
+    };
+    merged.with_context(|| format!("A slice has less than {frame_bytes} bytes of pixel data"))
+}
+
+/// Per-frame item naming the frame's place in the stack and its source
+fn frame_item(index: usize, slice: &DicomObject) -> InMemDicomObject {
+    let position = u32::try_from(index + 1).unwrap_or(u32::MAX);
+    let content = InMemDicomObject::from_element_iter([
+        DataElement::new(tags::STACK_ID, VR::SH, "1"),
+        DataElement::new(
+            tags::IN_STACK_POSITION_NUMBER,
+            VR::UL,
+            PrimitiveValue::from(position),
+        ),
+    ]);
+    let source = InMemDicomObject::from_element_iter([
+        DataElement::new(
+            tags::REFERENCED_SOP_CLASS_UID,
+            VR::UI,
+            text(slice, tags::SOP_CLASS_UID).unwrap_or_default(),
+        ),
+        DataElement::new(
+            tags::REFERENCED_SOP_INSTANCE_UID,
+            VR::UI,
+            text(slice, tags::SOP_INSTANCE_UID).unwrap_or_default(),
+        ),
+    ]);
+    InMemDicomObject::from_element_iter([
+        sequence_element(tags::FRAME_CONTENT_SEQUENCE, content),
+        sequence_element(tags::CONVERSION_SOURCE_ATTRIBUTES_SEQUENCE, source),
+    ])
+}
+
+fn sequence_element(tag: Tag, item: InMemDicomObject) -> DataElement<InMemDicomObject> {
+    DataElement::new(tag, VR::SQ, DataSetSequence::from(vec![item]))
+}
+
+fn text(obj: &InMemDicomObject, tag: Tag) -> Option<String> {
+    let value = obj.element(tag).ok()?.to_str().ok()?;
+    Some(value.trim_end_matches(['\0', ' ']).to_string())
+}
+
+/// Whether two groups hold the same values
+fn same(a: &Option<InMemDicomObject>, b: &Option<InMemDicomObject>) -> bool {
+    let values = |item: &Option<InMemDicomObject>| -> Vec<(Tag, Option<String>)> {
+        item.iter()
+            .flat_map(|item| item.iter())
+            .map(|element| {
+                let value = element.to_str().ok().map(|value| value.into_owned());
+                (element.tag(), value)
+            })
+            .collect()
+    };
+    values(a) == values(b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestDataset;
+
+    fn write_slice(path: &Path, z: f64, instance: i32, value: u16) {
+        TestDataset::gray16(&format!("1.2.3.{instance}"), 1, 2, &[value, value])
+            .with(tags::SOP_CLASS_UID, VR::UI, uids::CT_IMAGE_STORAGE)
+            .with(tags::SERIES_INSTANCE_UID, VR::UI, "1.2.3")
+            .with(tags::MODALITY, VR::CS, "CT")
+            .with(tags::INSTANCE_NUMBER, VR::IS, instance.to_string())
+            .with(tags::IMAGE_ORIENTATION_PATIENT, VR::DS, "1\\0\\0\\0\\1\\0")
+            .with(tags::IMAGE_POSITION_PATIENT, VR::DS, format!("0\\0\\{z}"))
+            .with(tags::PIXEL_SPACING, VR::DS, "0.5\\0.5")
+            .write(path);
+    }
+
+    #[test]
+    fn test_merge_orders_slices_by_position() {
+        let dir = tempfile::tempdir().unwrap();
+        let series = dir.path().join("series");
+        std::fs::create_dir(&series).unwrap();
+        // Instance numbers run against the positions
+        write_slice(&series.join("a.dcm"), 10.0, 1, 30);
+        write_slice(&series.join("b.dcm"), -5.0, 2, 10);
+        write_slice(&series.join("c.dcm"), 2.5, 3, 20);
+
+        let output = dir.path().join("enhanced.dcm");
+        assert_eq!(merge(&[series], &output).unwrap(), 3);
+
+        let merged = dicom::open_dicom_file(&output).unwrap();
+        assert_eq!(
+            merged
+                .meta()
+                .media_storage_sop_class_uid()
+                .trim_end_matches('\0'),
+            uids::LEGACY_CONVERTED_ENHANCED_CT_IMAGE_STORAGE
+        );
+        assert_eq!(
+            merged
+                .element(tags::PIXEL_DATA)
+                .unwrap()
+                .to_multi_int::<u16>()
+                .unwrap(),
+            [10, 10, 20, 20, 30, 30]
+        );
+
+        // Spacing is shared, positions are per frame
+        let shared = &merged
+            .element(tags::SHARED_FUNCTIONAL_GROUPS_SEQUENCE)
+            .unwrap()
+            .items()
+            .unwrap()[0];
+        assert!(shared.element(tags::PIXEL_MEASURES_SEQUENCE).is_ok());
+        assert!(shared.element(tags::PLANE_POSITION_SEQUENCE).is_err());
+        let frames = merged
+            .element(tags::PER_FRAME_FUNCTIONAL_GROUPS_SEQUENCE)
+            .unwrap()
+            .items()
+            .unwrap();
+        let position = &frames[0]
+            .element(tags::PLANE_POSITION_SEQUENCE)
+            .unwrap()
+            .items()
+            .unwrap()[0];
+        assert_eq!(
+            position
+                .element(tags::IMAGE_POSITION_PATIENT)
+                .unwrap()
+                .to_multi_float64()
+                .unwrap(),
+            [0.0, 0.0, -5.0]
+        );
+    }
+}
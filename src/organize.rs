@@ -0,0 +1,272 @@
+//! Sorting DICOM files into directory trees for `dcmv organize`
+//!
+//! Each file gets a path made from a template of attribute keywords, by
+//! default one directory for the patient, the study and the series, and a
+//! file named after the instance number. A flat dump of an archive export
+//! becomes a tree that can be browsed (and shown with `--series`).
+
+use crate::dicom::{self, DicomObject, TextDecoder};
+use crate::index;
+use crate::terminal::Stream;
+use ::dicom::core::Tag;
+use ::dicom::core::dictionary::{DataDictionary, DataDictionaryEntry};
+use ::dicom::dictionary_std::StandardDataDictionary;
+use anyhow::{Context, Result, bail};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Template used without `--template`
+pub const DEFAULT_TEMPLATE: &str = "{PatientName}_{PatientID}/{StudyDate}_{StudyDescription}/{SeriesNumber:03}_{SeriesDescription}/{InstanceNumber:04}.dcm";
+
+/// A path template such as `{PatientID}/{StudyDate}/{SeriesNumber:03}.dcm`
+///
+/// `{Keyword}` is replaced by the value of the attribute, and `{Keyword:N}`
+/// by an integer value padded with zeros to N digits. Tags can be given
+/// instead of keywords, e.g. `{0010,0020}`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template {
+    /// Parts of each path component
+    components: Vec<Vec<Part>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Text(String),
+    Attribute { tag: Tag, width: Option<usize> },
+}
+
+impl Template {
+    /// # Errors
+    ///
+    /// Returns an error if a `{` is not closed, or names neither a keyword
+    /// nor a tag
+    pub fn parse(template: &str) -> Result<Self> {
+        let components = template
+            .split(['/', '\\'])
+            .filter(|component| !component.is_empty())
+            .map(parse_component)
+            .collect::<Result<Vec<_>>>()?;
+        if components.is_empty() {
+            bail!("The template is empty");
+        }
+        Ok(Self { components })
+    }
+
+    /// Path of `obj` relative to the output directory
+    ///
+    /// Characters that are not allowed in file names are replaced by `_`,
+    /// and components left empty by missing attributes are named `unknown`.
+    #[must_use]
+    pub fn render(&self, obj: &DicomObject) -> PathBuf {
+        let decoder = TextDecoder::from_object(obj);
+        self.components
+            .iter()
+            .map(|parts| {
+                let text: String = parts
+                    .iter()
+                    .map(|part| match part {
+                        Part::Text(text) => text.clone(),
+                        Part::Attribute { tag, width } => {
+                            attribute_text(obj, &decoder, *tag, *width)
+                        }
+                    })
+                    .collect();
+                sanitize(&text)
+            })
+            .collect()
+    }
+}
+
+fn parse_component(component: &str) -> Result<Vec<Part>> {
+    let mut parts = Vec::new();
+    let mut rest = component;
+    while let Some(start) = rest.find('{') {
+        if start > 0 {
+            parts.push(Part::Text(rest[..start].to_string()));
+        }
+        let end = rest[start..]
+            .find('}')
+            .with_context(|| format!("Unclosed `{{` in template component `{component}`"))?;
+        let field = &rest[start + 1..start + end];
+        let (name, width) = match field.rsplit_once(':') {
+            Some((name, width)) if !width.contains(',') => {
+                let width = width
+                    .parse()
+                    .with_context(|| format!("Invalid width in `{{{field}}}`"))?;
+                (name, Some(width))
+            }
+            _ => (field, None),
+        };
+        let tag = match name.trim().parse::<Tag>() {
+            Ok(tag) => tag,
+            Err(_) => StandardDataDictionary
+                .by_name(name.trim())
+                .map(|entry| entry.tag())
+                .with_context(|| format!("`{name}` in the template is not an attribute keyword"))?,
+        };
+        parts.push(Part::Attribute { tag, width });
+        rest = &rest[start + end + 1..];
+    }
+    if !rest.is_empty() {
+        parts.push(Part::Text(rest.to_string()));
+    }
+    Ok(parts)
+}
+
+fn attribute_text(
+    obj: &DicomObject,
+    decoder: &TextDecoder,
+    tag: Tag,
+    width: Option<usize>,
+) -> String {
+    let Some(text) = obj
+        .get(tag)
+        .and_then(|element| element.value().to_str().ok())
+        .map(|text| decoder.decode(&text))
+    else {
+        return String::new();
+    };
+    let text = text.trim_matches(|c: char| c.is_whitespace() || c == '\0');
+    match (width, text.parse::<i64>()) {
+        (Some(width), Ok(number)) => format!("{number:0width$}"),
+        _ => text.to_string(),
+    }
+}
+
+/// A file or directory name from `text`, valid on Windows as well
+fn sanitize(text: &str) -> String {
+    let name: String = text
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    // Separators left over from missing attributes, and trailing dots and
+    // spaces, which Windows drops
+    let name = name.trim_matches(|c: char| c == '_' || c == '-' || c == ' ');
+    let name = name.trim_end_matches(['.', ' ']);
+    match name {
+        "" | "." | ".." => "unknown".to_string(),
+        name if name.starts_with('.') => format!("unknown{name}"),
+        name => name.to_string(),
+    }
+}
+
+/// Copy (or with `move_files`, move) the DICOM files among `inputs` to
+/// paths below `output` made from `template`
+///
+/// Directories are searched recursively, with progress shown on
+/// `progress`. A file whose path is taken gets a number appended, e.g.
+/// `0001_2.dcm`. With `dry_run` nothing is written. Returns each file with
+/// its new path.
+///
+/// # Errors
+///
+/// Returns an error if there are no DICOM files, or a file cannot be
+/// copied or moved
+pub fn organize(
+    inputs: &[PathBuf],
+    output: &Path,
+    template: &Template,
+    move_files: bool,
+    dry_run: bool,
+    progress: Option<Stream>,
+) -> Result<Vec<(PathBuf, PathBuf)>> {
+    let mut files = Vec::new();
+    for input in inputs {
+        if input.is_dir() {
+            let scan = index::scan_directory(input, None, progress)?;
+            files.extend(scan.instances.into_iter().map(|instance| instance.path));
+        } else {
+            files.push(input.clone());
+        }
+    }
+
+    let mut organized: Vec<(PathBuf, PathBuf)> = Vec::new();
+    for file in files {
+        let Ok(obj) = dicom::open_dicom_header(&file) else {
+            continue;
+        };
+        let destination = available_path(&output.join(template.render(&obj)), |path| {
+            path.exists() || organized.iter().any(|(_, taken)| taken == path)
+        });
+
+        if !dry_run {
+            if let Some(parent) = destination.parent() {
+                fs::create_dir_all(parent)
+                    .with_context(|| format!("Failed to create {}", parent.display()))?;
+            }
+            transfer(&file, &destination, move_files)?;
+        }
+        organized.push((file, destination));
+    }
+    if organized.is_empty() {
+        bail!("No DICOM files to organize");
+    }
+    Ok(organized)
+}
+
+/// `path`, or with `_2`, `_3`… before the extension if it is `taken`
+fn available_path(path: &Path, taken: impl Fn(&Path) -> bool) -> PathBuf {
+    if !taken(path) {
+        return path.to_path_buf();
+    }
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = path
+        .extension()
+        .map(|extension| format!(".{}", extension.to_string_lossy()))
+        .unwrap_or_default();
+    (2..)
+        .map(|n| path.with_file_name(format!("{stem}_{n}{extension}")))
+        .find(|candidate| !taken(candidate))
+        .unwrap_or_else(|| path.to_path_buf())
+}
+
+fn transfer(file: &Path, destination: &Path, move_file: bool) -> Result<()> {
+    let context = || format!("Failed to write {}", destination.display());
+    if move_file {
+        // Renaming fails across file systems, where the file is copied instead
+        if fs::rename(file, destination).is_err() {
+            fs::copy(file, destination).with_context(context)?;
+            fs::remove_file(file)
+                .with_context(|| format!("Failed to remove {}", file.display()))?;
+        }
+    } else {
+        fs::copy(file, destination).with_context(context)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestDataset;
+    use ::dicom::core::VR;
+    use ::dicom::dictionary_std::tags;
+    use dicom_dictionary_std::uids;
+
+    #[test]
+    fn test_template_renders_sanitized_paths() {
+        let obj = TestDataset::new("1.2.3")
+            .with(tags::SOP_CLASS_UID, VR::UI, uids::CT_IMAGE_STORAGE)
+            .with(tags::PATIENT_NAME, VR::PN, "Doe^Jane")
+            .with(tags::PATIENT_ID, VR::LO, "12/34")
+            .with(tags::SERIES_NUMBER, VR::IS, "7 ")
+            .with(tags::INSTANCE_NUMBER, VR::IS, "12")
+            .file(uids::EXPLICIT_VR_LITTLE_ENDIAN);
+
+        let template = Template::parse(DEFAULT_TEMPLATE).unwrap();
+        assert_eq!(
+            template.render(&obj),
+            Path::new("Doe^Jane_12_34/unknown/007/0012.dcm")
+        );
+
+        let template = Template::parse("{0010,0020}/{Modality}-{SeriesNumber}.dcm").unwrap();
+        assert_eq!(template.render(&obj), Path::new("12_34/7.dcm"));
+
+        assert!(Template::parse("{NotAKeyword}").is_err());
+        assert!(Template::parse("{PatientID").is_err());
+    }
+}
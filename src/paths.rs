@@ -0,0 +1,214 @@
+//! Expansion of `~` and glob patterns in paths
+//!
+//! Shells expand both before dcmv sees its arguments, but not in quoted
+//! arguments, on Windows, or in paths from other programs (e.g. a
+//! `storescp --exec-on-reception` command line). Paths that exist as given
+//! are never expanded.
+
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+/// Replace a leading `~` with the home directory
+///
+/// `~user` forms are left as they are.
+#[must_use]
+pub fn expand_tilde(path: &Path) -> PathBuf {
+    let mut components = path.components();
+    let starts_with_tilde = matches!(
+        components.next(),
+        Some(Component::Normal(first)) if first == "~"
+    );
+
+    match home_dir() {
+        Some(home) if starts_with_tilde && !path.exists() => home.join(components.as_path()),
+        _ => path.to_path_buf(),
+    }
+}
+
+fn home_dir() -> Option<PathBuf> {
+    ["HOME", "USERPROFILE"]
+        .iter()
+        .filter_map(env::var_os)
+        .find(|home| !home.is_empty())
+        .map(PathBuf::from)
+}
+
+/// Paths matching a glob pattern with `*`, `?` and `[...]`, sorted
+///
+/// Patterns may have wildcards in any component, but `*` and `?` do not
+/// match a leading `.` of hidden files. Paths without wildcards, and
+/// patterns that match nothing, are returned as they are, so that opening
+/// them reports the error.
+#[must_use]
+pub fn expand_glob(pattern: &Path) -> Vec<PathBuf> {
+    let pattern = expand_tilde(pattern);
+    if pattern.exists() || !has_wildcards(&pattern.to_string_lossy()) {
+        return vec![pattern];
+    }
+
+    let mut matches = vec![PathBuf::new()];
+    for component in pattern.components() {
+        let Component::Normal(part) = component else {
+            for path in &mut matches {
+                path.push(component);
+            }
+            continue;
+        };
+
+        let text = part.to_string_lossy();
+        if !has_wildcards(&text) {
+            for path in &mut matches {
+                path.push(part);
+            }
+            continue;
+        }
+
+        matches = matches
+            .iter()
+            .flat_map(|dir| matching_entries(dir, &text))
+            .collect();
+    }
+
+    if matches.is_empty() {
+        return vec![pattern];
+    }
+    matches.sort();
+    matches
+}
+
+/// Entries of `dir` (the current directory when empty) whose names match
+fn matching_entries(dir: &Path, pattern: &str) -> Vec<PathBuf> {
+    let listed = if dir.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        dir
+    };
+    let Ok(entries) = fs::read_dir(listed) else {
+        return Vec::new();
+    };
+
+    entries
+        .filter_map(Result::ok)
+        .map(|entry| entry.file_name())
+        .filter(|name| matches_name(pattern, name))
+        .map(|name| dir.join(name))
+        .collect()
+}
+
+fn has_wildcards(text: &str) -> bool {
+    text.contains(['*', '?', '['])
+}
+
+/// Whether a file name matches a pattern; names that are not valid UTF-8
+/// are matched with their invalid bytes replaced
+fn matches_name(pattern: &str, name: &OsString) -> bool {
+    let name = name.to_string_lossy();
+    if name.starts_with('.') && !pattern.starts_with('.') {
+        return false;
+    }
+
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    matches(&pattern, &name)
+}
+
+/// Match without backtracking into earlier stars: on a mismatch, the last
+/// `*` takes one more character of the name, so matching takes at most
+/// pattern length times name length steps
+fn matches(pattern: &[char], name: &[char]) -> bool {
+    let (mut p, mut n) = (0, 0);
+    // Pattern position after the last `*`, and the name position it took up to
+    let mut star = None;
+
+    while n < name.len() {
+        if pattern.get(p) == Some(&'*') {
+            p += 1;
+            star = Some((p, n));
+        } else if let Some(len) = match_char(&pattern[p..], name[n]) {
+            p += len;
+            n += 1;
+        } else if let Some((after_star, taken)) = star {
+            p = after_star;
+            n = taken + 1;
+            star = Some((after_star, n));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Length of the token at the start of `pattern` if it matches `c`
+fn match_char(pattern: &[char], c: char) -> Option<usize> {
+    match pattern.first()? {
+        '?' => Some(1),
+        '[' => match character_class(&pattern[1..]) {
+            Some((class, rest)) => class(c).then_some(pattern.len() - rest.len()),
+            // An unclosed bracket is taken literally
+            None => (c == '[').then_some(1),
+        },
+        &literal => (literal == c).then_some(1),
+    }
+}
+
+/// A `[...]` class after its opening bracket, with `!` or `^` negating and
+/// `a-z` ranges, and the pattern after its closing bracket
+fn character_class(pattern: &[char]) -> Option<(impl Fn(char) -> bool + '_, &[char])> {
+    let negated = matches!(pattern.first(), Some('!' | '^'));
+    let start = usize::from(negated);
+    // A `]` right after the bracket is part of the class
+    let end = start + 1 + pattern.get(start + 1..)?.iter().position(|&c| c == ']')?;
+    let members = &pattern[start..end];
+
+    let class = move |c: char| {
+        let mut i = 0;
+        let mut found = false;
+        while i < members.len() {
+            if i + 2 < members.len() && members[i + 1] == '-' {
+                found |= (members[i]..=members[i + 2]).contains(&c);
+                i += 3;
+            } else {
+                found |= members[i] == c;
+                i += 1;
+            }
+        }
+        found != negated
+    };
+    Some((class, &pattern[end + 1..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_patterns() {
+        let matches = |pattern: &str, name: &str| matches_name(pattern, &OsString::from(name));
+
+        assert!(matches("*.dcm", "IM0001.dcm"));
+        assert!(!matches("*.dcm", "IM0001.dcm.bak"));
+        assert!(!matches("*", ".hidden"));
+        assert!(matches("IM000?.dcm", "IM0007.dcm"));
+        assert!(matches("IM[0-3]*", "IM2"));
+        assert!(!matches("IM[!0-3]*", "IM2"));
+        assert!(matches("[]]", "]"));
+        assert!(matches("a[b", "a[b"));
+        assert!(matches("*a*b", "xaxxb"));
+        // Would take minutes with backtracking over every star
+        assert!(!matches(&"*a".repeat(20), &"a".repeat(19)));
+
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["b.dcm", "a.dcm", "notes.txt"] {
+            fs::write(dir.path().join(name), "").unwrap();
+        }
+        assert_eq!(
+            expand_glob(&dir.path().join("*.dcm")),
+            vec![dir.path().join("a.dcm"), dir.path().join("b.dcm")]
+        );
+
+        let nothing = dir.path().join("*.png");
+        assert_eq!(expand_glob(&nothing), vec![nothing]);
+    }
+}
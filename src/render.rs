@@ -0,0 +1,647 @@
+//! Rendering DICOM images to RGB buffers, for embedding dcmv in other programs
+//!
+//! ```no_run
+//! use dcmv::image::DisplayWindow;
+//! use dcmv::render::{RenderOptions, render};
+//! use std::path::Path;
+//!
+//! let options = RenderOptions {
+//!     window: Some(DisplayWindow::new(40.0, 400.0)),
+//!     size: Some((512, 512)),
+//!     ..RenderOptions::default()
+//! };
+//! let image = render(Path::new("ct.dcm"), &options)?;
+//! image.save("ct.png")?;
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use crate::cli::Args;
+use crate::dicom::{self, DicomMetadata, DicomObject, ExtractOptions};
+use crate::explain::DecisionLog;
+use crate::image::{
+    self, CaptionPlacement, Colormap, DecodedFrame, DisplayWindow, Filter, ToneCurve, Transform,
+};
+use ::image::{DynamicImage, Rgb, RgbImage};
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+use tracing::{debug, debug_span};
+
+/// How images are rendered and what is drawn over them
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RenderOptions {
+    /// Replaces the automatic orientation when set
+    pub orientation: Option<Transform>,
+    /// Orientation markers and scale bar
+    pub annotations: bool,
+    /// Text and graphics of the Graphic Annotation Sequence, or of a
+    /// presentation state, and overlay planes
+    pub graphic_annotations: bool,
+    /// Patient/study caption
+    pub caption: Option<CaptionPlacement>,
+    /// Display window in rescaled units, for grayscale images. A SIGMOID
+    /// window of the file, or else the declared or actual range of values,
+    /// is shown when not set.
+    pub window: Option<DisplayWindow>,
+    /// Brightness, contrast and gamma applied after windowing
+    pub tone: Option<ToneCurve>,
+    /// Flip the displayed polarity, after windowing and the tone curve
+    pub invert: bool,
+    /// Frame of a multi-frame image to render (0-based)
+    pub frame: u32,
+    /// Mask frame subtracted from the rendered frame (0-based)
+    pub subtract_frame: Option<u32>,
+    /// Color lookup applied to the gray levels
+    pub colormap: Option<Colormap>,
+    /// Convolution applied to the displayed 8-bit levels
+    pub filter: Option<Filter>,
+    /// Bounds the rendered image is scaled to fit, keeping its aspect ratio
+    pub size: Option<(u32, u32)>,
+}
+
+impl RenderOptions {
+    #[must_use]
+    pub fn from_args(args: &Args) -> Self {
+        Self {
+            orientation: args.orientation_override(),
+            annotations: args.annotations,
+            graphic_annotations: args.annotations || args.presentation_state.is_some(),
+            caption: args.caption_placement(),
+            tone: args.tone_curve(),
+            invert: args.invert,
+            filter: args.image_filter(),
+            subtract_frame: args.subtract_frame.map(|frame| frame - 1),
+            ..Self::default()
+        }
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn has_overlays(&self) -> bool {
+        self.annotations || self.graphic_annotations || self.caption.is_some()
+    }
+
+    /// Whether the displayed pixel values differ from the decoded ones
+    #[inline]
+    #[must_use]
+    fn adjusts_pixels(&self) -> bool {
+        self.window.is_some()
+            || self.subtract_frame.is_some()
+            || self.tone.is_some()
+            || self.invert
+            || self.colormap.is_some()
+            || self.filter.is_some()
+            || self.size.is_some()
+    }
+
+    /// Explicit orientation if given, otherwise the conventional view for the
+    /// slice, or the reading view of a mammogram
+    #[must_use]
+    pub fn transform(&self, metadata: &DicomMetadata) -> Transform {
+        self.orientation
+            .or_else(|| image::mammogram_transform(metadata))
+            .unwrap_or_else(|| Transform::from_orientation(metadata.orientation.as_ref()))
+    }
+
+    /// Render the selected frame for display, oriented and with overlays drawn
+    ///
+    /// The returned transform maps the image back to stored pixel
+    /// coordinates, which only holds when no `size` is set.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the frame does not exist or the pixel data cannot
+    /// be converted
+    pub fn render(&self, metadata: &DicomMetadata) -> Result<(DynamicImage, Transform)> {
+        self.render_explained(metadata, &mut DecisionLog::default())
+    }
+
+    /// [`Self::render`], noting each conversion step in `log`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the frame does not exist or the pixel data cannot
+    /// be converted
+    pub fn render_explained(
+        &self,
+        metadata: &DicomMetadata,
+        log: &mut DecisionLog,
+    ) -> Result<(DynamicImage, Transform)> {
+        let _span = debug_span!("convert", frame = self.frame).entered();
+        debug!(window = ?self.window, size = ?self.size, "rendering");
+        let single_frame;
+        let frame = if metadata.number_of_frames > 1 || self.frame > 0 {
+            log.record(
+                "Frame",
+                format!("{} of {}", self.frame + 1, metadata.number_of_frames),
+            );
+            single_frame = metadata.single_frame(self.frame)?;
+            &single_frame
+        } else {
+            metadata
+        };
+
+        let subtracted;
+        let frame = match self.subtract_frame {
+            Some(mask) => {
+                log.record("Subtraction", format!("mask frame {}", mask + 1));
+                subtracted = image::subtract_mask(metadata, self.frame, mask)?;
+                &subtracted
+            }
+            None => frame,
+        };
+
+        let transform = self.transform(metadata);
+        let image = image::convert_to_image_explained(frame, self.window, log)?;
+        let image = match self.tone {
+            Some(tone) => {
+                log.record(
+                    "Tone Curve",
+                    format!(
+                        "gamma {:?}, brightness {}, contrast {}{}",
+                        tone.gamma,
+                        tone.brightness,
+                        tone.contrast,
+                        tone.gsdf
+                            .map_or_else(String::new, |nits| format!(", GSDF for {nits} cd/m²"))
+                    ),
+                );
+                tone.apply(image)
+            }
+            None => image,
+        };
+        let image = if self.invert {
+            log.record("Invert", "polarity flipped (--invert)");
+            image::invert_polarity(image)
+        } else {
+            image
+        };
+        let image = match self.colormap {
+            Some(colormap) => {
+                log.record("Colormap", format!("{colormap:?}"));
+                colormap.apply(&image)
+            }
+            None => image,
+        };
+        let image = match self.filter {
+            Some(filter) => {
+                log.record(
+                    "Filter",
+                    format!("{filter:?} on 8-bit levels").to_lowercase(),
+                );
+                filter.apply(image)
+            }
+            None => image,
+        };
+        log.record(
+            "Orientation",
+            format!(
+                "{}{}",
+                describe_transform(transform),
+                if self.orientation.is_some() {
+                    " (--rotate/--flip)"
+                } else if image::mammogram_transform(metadata).is_some() {
+                    " (mammogram, chest wall by laterality and view)"
+                } else {
+                    ""
+                }
+            ),
+        );
+        let image = transform.apply_to_image(image);
+
+        // In stored coordinates, so before the markers around the image
+        let image = if self.graphic_annotations && !metadata.overlays.is_empty() {
+            log.record(
+                "Overlays",
+                format!("{} overlay plane(s)", metadata.overlays.len()),
+            );
+            image::draw_overlay_planes(image, metadata, transform, self.frame)
+        } else {
+            image
+        };
+        let image = if self.graphic_annotations && !metadata.graphic_annotations.is_empty() {
+            log.record(
+                "Overlays",
+                format!(
+                    "{} graphic annotation object(s)",
+                    metadata.graphic_annotations.len()
+                ),
+            );
+            image::draw_graphic_annotations(image, metadata, transform, self.frame)
+        } else {
+            image
+        };
+
+        let image = if self.annotations {
+            log.record("Overlays", "orientation markers and scale bar");
+            image::annotate(image, metadata, transform)
+        } else {
+            image
+        };
+
+        let image = match self.caption {
+            Some(placement) => {
+                log.record("Overlays", format!("caption {placement:?}").to_lowercase());
+                image::add_caption(
+                    image,
+                    &image::caption_lines(metadata, self.frame),
+                    placement,
+                )
+            }
+            None => image,
+        };
+
+        let image = match self.size {
+            Some((width, height)) => {
+                let resized = image.resize(width, height, ::image::imageops::Lanczos3);
+                log.record(
+                    "Resize",
+                    format!(
+                        "{}x{} to {}x{} with Lanczos3",
+                        image.width(),
+                        image.height(),
+                        resized.width(),
+                        resized.height()
+                    ),
+                );
+                resized
+            }
+            None => image,
+        };
+
+        Ok((image, transform))
+    }
+
+    /// Frame for export
+    ///
+    /// Keeps the original bit depth and stored layout, unless overlays have
+    /// to be burned in or the pixels are adjusted for display, in which
+    /// case the rendered image is used, oriented as it is shown: 16-bit
+    /// grayscale, or 8-bit RGB with overlays or a colormap.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the pixel data cannot be decoded
+    pub fn export_frame(&self, metadata: &DicomMetadata) -> Result<DecodedFrame> {
+        if self.has_overlays() || self.adjusts_pixels() {
+            let (image, _) = self.render(metadata)?;
+            return Ok(image.into());
+        }
+
+        self.stored_frame(metadata)
+    }
+
+    /// Frame with its stored values, ignoring all display options
+    ///
+    /// Rows and columns stay those of the file, so array indices match the
+    /// DICOM pixel coordinates; only an explicit `orientation` (--rotate,
+    /// --flip) turns the frame, never the automatic one.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the pixel data cannot be decoded
+    pub fn stored_frame(&self, metadata: &DicomMetadata) -> Result<DecodedFrame> {
+        let frame = image::decode_frame(&metadata.single_frame(self.frame)?)?;
+        Ok(match self.orientation {
+            Some(transform) => transform.apply_to_frame(frame),
+            None => frame,
+        })
+    }
+
+    /// How `metadata` was decoded and would be rendered, step by step
+    /// (for `--explain`)
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the frame does not exist or the pixel data cannot
+    /// be converted
+    pub fn explain(&self, metadata: &DicomMetadata) -> Result<DecisionLog> {
+        let mut log = metadata.decisions.clone();
+        self.render_explained(metadata, &mut log)?;
+        Ok(log)
+    }
+
+    /// Values of the pixels at stored (column, row) coordinates in the
+    /// selected frame, or `None` for coordinates outside it
+    ///
+    /// The displayed color includes the window and tone curve, but not
+    /// overlays or scaling.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the frame does not exist or the pixel data cannot
+    /// be converted
+    pub fn probe(
+        &self,
+        metadata: &DicomMetadata,
+        positions: &[(u32, u32)],
+    ) -> Result<Vec<Option<PixelProbe>>> {
+        let stored = image::decode_frame(&metadata.single_frame(self.frame)?)?;
+        let unoriented = Self {
+            orientation: Some(Transform::IDENTITY),
+            annotations: false,
+            graphic_annotations: false,
+            caption: None,
+            size: None,
+            ..*self
+        };
+        let (displayed, _) = unoriented.render(metadata)?;
+        // Quantized as for the terminal, which may differ from rounding
+        let displayed = image::quantize(displayed).into_rgb8();
+
+        let grayscale = metadata.photometric_interpretation.is_grayscale();
+        Ok(positions
+            .iter()
+            .map(|&(x, y)| {
+                let samples = stored.pixel(x, y)?;
+                Some(PixelProbe {
+                    position: (x, y),
+                    rescaled: grayscale.then(|| metadata.rescaled_value(samples[0])),
+                    stored: samples,
+                    displayed: *displayed.get_pixel_checked(x, y)?,
+                })
+            })
+            .collect())
+    }
+}
+
+/// Values of one pixel, from stored to displayed
+#[derive(Debug, Clone, PartialEq)]
+pub struct PixelProbe {
+    /// Stored (column, row) coordinates
+    pub position: (u32, u32),
+    /// Stored samples, one per channel
+    pub stored: Vec<f64>,
+    /// Modality value (e.g. HU), for grayscale images
+    pub rescaled: Option<f64>,
+    /// Color the pixel is displayed with
+    pub displayed: Rgb<u8>,
+}
+
+/// Something a DICOM image can be loaded from for [`render`]
+pub trait RenderSource {
+    /// Extract the metadata and pixel data
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the source cannot be read or decoded
+    fn load(self, options: &ExtractOptions) -> Result<DicomMetadata>;
+}
+
+impl RenderSource for &Path {
+    fn load(self, options: &ExtractOptions) -> Result<DicomMetadata> {
+        dicom::open_dicom_file(self)?.load(options)
+    }
+}
+
+impl RenderSource for &PathBuf {
+    fn load(self, options: &ExtractOptions) -> Result<DicomMetadata> {
+        self.as_path().load(options)
+    }
+}
+
+impl RenderSource for &DicomObject {
+    fn load(self, options: &ExtractOptions) -> Result<DicomMetadata> {
+        dicom::extract_dicom_data_with_options(self, options)
+    }
+}
+
+/// Render a DICOM file or parsed object to an 8-bit RGB image
+///
+/// This runs the same pipeline as the command line viewer, without any
+/// terminal output. Use [`RenderOptions::render`] when the metadata has
+/// already been extracted.
+///
+/// # Errors
+///
+/// Returns an error if the image cannot be read, decoded or converted
+pub fn render(source: impl RenderSource, options: &RenderOptions) -> Result<RgbImage> {
+    // Lets resolution-progressive J2K codestreams skip unneeded detail
+    let extract = ExtractOptions {
+        target_width: options.size.map(|(width, _)| width),
+        ..ExtractOptions::default()
+    };
+
+    let metadata = source.load(&extract)?;
+    let (image, _) = options.render(&metadata)?;
+    Ok(image::quantize(image).into_rgb8())
+}
+
+/// A transform in words, e.g. "rotated 90° clockwise"
+fn describe_transform(transform: Transform) -> &'static str {
+    match (transform.transpose, transform.flip_h, transform.flip_v) {
+        (false, false, false) => "as stored",
+        (false, true, false) => "flipped horizontally",
+        (false, false, true) => "flipped vertically",
+        (false, true, true) => "rotated 180°",
+        (true, true, false) => "rotated 90° clockwise",
+        (true, false, true) => "rotated 90° counterclockwise",
+        (true, false, false) => "transposed",
+        (true, true, true) => "transposed and rotated 180°",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::Samples;
+    use crate::types::{
+        BitDepth, ImageOrientation, ModalityLut, PixelValueRange, RescaleParams, VoiLutFunction,
+        VoiWindow,
+    };
+
+    /// Two frames of 2x1 pixels: [0, 100] and [100, 200]
+    fn two_frames() -> DicomMetadata {
+        DicomMetadata::test_grayscale16(1, 2, &[0, 100, 100, 200])
+    }
+
+    fn gray_levels(image: &DynamicImage) -> Vec<u8> {
+        image::quantize(image.clone())
+            .to_rgb8()
+            .pixels()
+            .map(|pixel| pixel[0])
+            .collect()
+    }
+
+    #[test]
+    fn test_invert_flips_displayed_levels() {
+        let options = RenderOptions {
+            window: Some(DisplayWindow::from_range(50.0, 150.0)),
+            invert: true,
+            ..RenderOptions::default()
+        };
+        let (image, _) = options.render(&two_frames()).unwrap();
+        assert_eq!(gray_levels(&image), vec![255, 128]);
+    }
+
+    #[test]
+    fn test_render_selects_frame_and_window() {
+        let metadata = two_frames();
+
+        let options = RenderOptions {
+            frame: 1,
+            ..RenderOptions::default()
+        };
+        let (image, _) = options.render(&metadata).unwrap();
+        assert_eq!(gray_levels(&image), vec![0, 255]);
+
+        // Values outside the window are clipped
+        let options = RenderOptions {
+            window: Some(DisplayWindow::from_range(50.0, 150.0)),
+            ..RenderOptions::default()
+        };
+        let (image, _) = options.render(&metadata).unwrap();
+        assert_eq!(gray_levels(&image), vec![0, 127]);
+
+        let options = RenderOptions {
+            frame: 2,
+            ..RenderOptions::default()
+        };
+        assert!(options.render(&metadata).is_err());
+    }
+
+    #[test]
+    fn test_modality_lut_replaces_rescale() {
+        let mut metadata = DicomMetadata::test_grayscale16(1, 3, &[10, 11, 12]);
+        metadata.rescale = RescaleParams::new(-1.0, 0.0);
+        // Maps 10 to 0 and 11 (and everything above) to 100
+        metadata.modality_lut = ModalityLut::new(10, vec![0, 100]);
+
+        let options = RenderOptions {
+            window: Some(DisplayWindow::from_range(0.0, 100.0)),
+            ..RenderOptions::default()
+        };
+        let (image, _) = options.render(&metadata).unwrap();
+        assert_eq!(gray_levels(&image), vec![0, 255, 255]);
+    }
+
+    #[test]
+    fn test_modality_lut_range_covers_entries_in_between() {
+        let mut metadata = DicomMetadata::test_grayscale16(1, 3, &[10, 11, 12]);
+        metadata.pixel_value_range = Some(PixelValueRange::new(10, 12));
+        // Not monotonic: the largest output is in the middle of the range
+        metadata.modality_lut = ModalityLut::new(10, vec![50, 200, 100]);
+
+        let (image, _) = RenderOptions::default().render(&metadata).unwrap();
+        assert_eq!(gray_levels(&image), vec![0, 255, 85]);
+    }
+
+    #[test]
+    fn test_declared_sigmoid_window_is_applied() {
+        let mut metadata = DicomMetadata::test_grayscale16(1, 3, &[0, 100, 200]);
+        metadata.voi_window = Some(VoiWindow {
+            center: 100.0,
+            width: 100.0,
+            function: VoiLutFunction::Linear,
+        });
+        // A linear window of the file is not, the value range is shown
+        let (image, _) = RenderOptions::default().render(&metadata).unwrap();
+        assert_eq!(gray_levels(&image), vec![0, 127, 255]);
+
+        metadata.voi_window = metadata.voi_window.map(|window| VoiWindow {
+            function: VoiLutFunction::Sigmoid,
+            ..window
+        });
+        let (image, _) = RenderOptions::default().render(&metadata).unwrap();
+        assert_eq!(gray_levels(&image), vec![4, 127, 250]);
+
+        // An explicit window replaces it
+        let options = RenderOptions {
+            window: Some(DisplayWindow::from_range(50.0, 150.0)),
+            ..RenderOptions::default()
+        };
+        let (image, _) = options.render(&metadata).unwrap();
+        assert_eq!(gray_levels(&image), vec![0, 127, 255]);
+    }
+
+    #[test]
+    fn test_probe_reports_stored_rescaled_and_displayed_values() {
+        let mut metadata = DicomMetadata::test_grayscale16(1, 3, &[0, 100, 200]);
+        metadata.rescale = RescaleParams::new(2.0, -100.0);
+        let options = RenderOptions {
+            window: Some(DisplayWindow::from_range(-100.0, 300.0)),
+            ..RenderOptions::default()
+        };
+
+        let probes = options.probe(&metadata, &[(1, 0), (3, 0)]).unwrap();
+        let probe = probes[0].as_ref().unwrap();
+        assert_eq!(probe.stored, vec![100.0]);
+        assert_eq!(probe.rescaled, Some(100.0));
+        assert_eq!(probe.displayed, Rgb([127, 127, 127]));
+        assert!(probes[1].is_none());
+    }
+
+    #[test]
+    fn test_probe_rescales_signed_samples() {
+        // -1000 and 40 in signed 16-bit samples
+        let mut metadata = DicomMetadata::test_grayscale16(1, 2, &[0xfc18, 40]);
+        metadata.bit_depth = BitDepth {
+            signed: true,
+            ..BitDepth::new(16, 16)
+        };
+
+        let probes = RenderOptions::default()
+            .probe(&metadata, &[(0, 0)])
+            .unwrap();
+        let probe = probes[0].as_ref().unwrap();
+        assert_eq!(probe.stored, vec![-1000.0]);
+        assert_eq!(probe.rescaled, Some(-1000.0));
+        assert_eq!(probe.displayed, Rgb([0, 0, 0]));
+
+        // A Modality LUT starting below zero covers the negative values
+        metadata.modality_lut = ModalityLut::new(-1001, vec![7, 9, 11]);
+        let probes = RenderOptions::default()
+            .probe(&metadata, &[(0, 0)])
+            .unwrap();
+        assert_eq!(probes[0].as_ref().unwrap().rescaled, Some(9.0));
+    }
+
+    #[test]
+    fn test_explain_lists_steps_in_order() {
+        let options = RenderOptions {
+            frame: 1,
+            orientation: Some(Transform::ROTATE_90),
+            size: Some((4, 4)),
+            ..RenderOptions::default()
+        };
+
+        let log = options.explain(&two_frames()).unwrap();
+        let steps: Vec<_> = log.entries().iter().map(|(step, _)| *step).collect();
+        assert_eq!(steps.first(), Some(&"Frame"));
+        assert_eq!(steps.last(), Some(&"Resize"));
+        assert!(log.entries().contains(&(
+            "Orientation",
+            "rotated 90° clockwise (--rotate/--flip)".to_string()
+        )));
+    }
+
+    #[test]
+    fn test_stored_frame_keeps_stored_layout() {
+        // Rows running right to left, which the display flips
+        let mut metadata = DicomMetadata::test_grayscale16(1, 2, &[0, 100]);
+        metadata.orientation = Some(ImageOrientation::new([-1.0, 0.0, 0.0], [0.0, 1.0, 0.0]));
+        let options = RenderOptions::default();
+        assert_ne!(options.transform(&metadata), Transform::IDENTITY);
+
+        let frame = options.stored_frame(&metadata).unwrap();
+        assert_eq!(frame.samples, Samples::U16(vec![0, 100]));
+
+        let flipped = RenderOptions {
+            orientation: Some(Transform::FLIP_H),
+            ..options
+        };
+        let frame = flipped.stored_frame(&metadata).unwrap();
+        assert_eq!(frame.samples, Samples::U16(vec![100, 0]));
+    }
+
+    #[test]
+    fn test_windowed_export_keeps_16_bits() {
+        let options = RenderOptions {
+            window: Some(DisplayWindow::from_range(0.0, 200.0)),
+            ..RenderOptions::default()
+        };
+
+        let frame = options.export_frame(&two_frames()).unwrap();
+        assert_eq!(frame.samples_per_pixel, 1);
+        assert_eq!(frame.samples, Samples::U16(vec![0, 32767]));
+    }
+}
@@ -0,0 +1,734 @@
+use crate::cache::RenderCache;
+use crate::cli::Args;
+use crate::clipboard;
+use crate::dicom::{DicomMetadata, PresentationState, ProcessError};
+use crate::display::{self, Renderer};
+use crate::fusion::Fusion;
+use crate::geometry::ImagePlane;
+use crate::image::{ANNOTATION_COLOR, Transform, draw_line};
+use crate::interactive::Viewer;
+use crate::layout::Protocol;
+use crate::render::RenderOptions;
+use crate::types::PixelAspectRatio;
+use crate::{export, image};
+use ::image::imageops::FilterType;
+use ::image::{DynamicImage, RgbImage};
+use anyhow::{Context, Result};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use tracing::{debug, debug_span};
+
+/// A destination for a processed DICOM image
+///
+/// Each sink decides which representation it needs: the terminal renders
+/// an 8-bit display image, while file and stream sinks keep the decoded
+/// frame at its original bit depth unless overlays are burned in.
+pub trait OutputSink {
+    /// Write the image described by `metadata` to this sink
+    ///
+    /// # Errors
+    ///
+    /// Returns `ConversionFailed` if the pixel data cannot be turned into
+    /// the sink's representation, or a sink-specific error if writing fails
+    fn write(&mut self, metadata: &DicomMetadata) -> Result<(), ProcessError>;
+
+    /// Show or write what was held back until all images were written
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if showing or writing fails
+    fn finish(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+/// Choose the sink selected by the command-line arguments
+///
+/// `--output -` streams to stdout, any other `--output` path writes a file,
+/// `--interactive` opens the measurement viewer, `--grid` shows all images
+/// at once, `--layout` hangs them by their views, and otherwise each image
+/// is shown in the terminal.
+#[must_use]
+pub fn from_args(args: &Args) -> Box<dyn OutputSink + '_> {
+    let options = RenderOptions::from_args(args);
+
+    match args.output.as_deref() {
+        Some(path) if path == Path::new("-") => Box::new(StdoutSink::new(options)),
+        Some(path) => Box::new(FileSink::new(path, options)),
+        None if args.interactive => Box::new(InteractiveSink::new(args, options)),
+        None if let Some(columns) = args.grid => Box::new(GridSink::new(
+            display::renderer_from_args(args),
+            options,
+            columns,
+        )),
+        None if let Some(protocol) = args.hanging_protocol() => Box::new(HangingSink::new(
+            display::renderer_from_args(args),
+            options,
+            protocol,
+        )),
+        None => Box::new(
+            TerminalSink::new(display::renderer_from_args(args), options)
+                .with_cache(render_cache(args)),
+        ),
+    }
+}
+
+/// Where [`from_args`] sends images, in words (for `--explain`)
+#[must_use]
+pub fn describe(args: &Args) -> String {
+    match args.output.as_deref() {
+        Some(path) if path == Path::new("-") => "PNG to stdout".to_string(),
+        Some(path) => format!("file {}", path.display()),
+        None if args.interactive => "interactive viewer".to_string(),
+        None if let Some(columns) = args.grid => format!("grid of {columns} columns"),
+        None if let Some(protocol) = args.hanging_protocol() => {
+            format!("{} layout", protocol.name())
+        }
+        None if let Some(localizer) = &args.localizer => {
+            format!("next to localizer {}", localizer.display())
+        }
+        None => {
+            let width = args
+                .width
+                .map_or_else(|| "terminal width".to_string(), |w| format!("{w} columns"));
+            format!(
+                "{:?} renderer, {width}",
+                display::detect_renderer(args.renderer)
+            )
+        }
+    }
+}
+
+/// Cache of rendered images, when enabled with --cache
+///
+/// Thumbnails are quick to make and are not cached, nor are images read
+/// with --photometric, --force-planar or --force-endian, which the cache
+/// key does not cover, nor whole ultrasound frames (--no-crop). Images
+/// with --redact are always rendered from their pixel data, so that an
+/// unredacted render is never shown instead.
+fn render_cache(args: &Args) -> Option<RenderCache> {
+    let overridden = args.photometric.is_some()
+        || args.force_planar.is_some()
+        || args.force_endian.is_some()
+        || args.no_crop;
+    if args.cache && !args.thumbnail && !overridden && args.redact.is_empty() {
+        RenderCache::open()
+    } else {
+        None
+    }
+}
+
+/// Whether the image for `metadata` would be shown from the render cache
+///
+/// Only the tags of `metadata` are needed, so they can come from the file
+/// header alone.
+#[must_use]
+pub fn is_cached(args: &Args, metadata: &DicomMetadata) -> bool {
+    args.output.is_none()
+        && !args.interactive
+        && args.grid.is_none()
+        && args.hanging_protocol().is_none()
+        && args.localizer.is_none()
+        && args.fuse.is_none()
+        && args.presentation_state.is_none()
+        && !args.copy
+        && render_cache(args)
+            .is_some_and(|cache| cache.contains(metadata, &RenderOptions::from_args(args)))
+}
+
+/// Images with more pixels than this are previewed while being converted
+const PROGRESSIVE_MIN_PIXELS: usize = 8 * 1024 * 1024;
+
+/// Approximate number of pixels in a preview
+const PREVIEW_PIXELS: usize = 512 * 512;
+
+/// Displays images with a display backend, usually in the terminal
+pub struct TerminalSink<R> {
+    renderer: R,
+    options: RenderOptions,
+    cache: Option<RenderCache>,
+}
+
+impl<R: Renderer> TerminalSink<R> {
+    #[must_use]
+    pub fn new(renderer: R, options: RenderOptions) -> Self {
+        Self {
+            renderer,
+            options,
+            cache: None,
+        }
+    }
+
+    /// Show cached renders when available and cache new ones
+    #[must_use]
+    pub fn with_cache(mut self, cache: Option<RenderCache>) -> Self {
+        self.cache = cache;
+        self
+    }
+
+    #[must_use]
+    pub fn renderer(&self) -> &R {
+        &self.renderer
+    }
+}
+
+impl<R: Renderer> TerminalSink<R> {
+    /// Show a decimated version of a large image while the full one is
+    /// converted, if the renderer can replace it afterwards
+    fn show_preview(&mut self, metadata: &DicomMetadata) {
+        let pixel_count = metadata.dimensions.pixel_count();
+        if !self.renderer.supports_preview()
+            || metadata.number_of_frames > 1
+            || pixel_count < PROGRESSIVE_MIN_PIXELS
+        {
+            return;
+        }
+
+        let step = u16::try_from((pixel_count / PREVIEW_PIXELS).isqrt().max(2)).unwrap_or(u16::MAX);
+        let Some(preview) = metadata.decimated(step) else {
+            return;
+        };
+
+        // Failures are left for the full image to report
+        if let Ok((image, transform)) = self.options.render(&preview) {
+            let _ = self.renderer.show_preview(
+                &image::quantize(image),
+                displayed_aspect_ratio(metadata, transform),
+            );
+        }
+    }
+}
+
+impl<R: Renderer> OutputSink for TerminalSink<R> {
+    fn write(&mut self, metadata: &DicomMetadata) -> Result<(), ProcessError> {
+        let cached = self
+            .cache
+            .as_ref()
+            .and_then(|cache| cache.get(metadata, &self.options));
+
+        let cached_image = cached.is_some();
+        let (image, transform) = match cached {
+            Some(image) => (image, self.options.transform(metadata)),
+            None => {
+                self.show_preview(metadata);
+
+                let (image, transform) = self
+                    .options
+                    .render(metadata)
+                    .map_err(|e| conversion_failed(metadata, e))?;
+                let image = image::quantize(image);
+
+                if let Some(cache) = &self.cache
+                    && let Err(e) = cache.put(metadata, &self.options, &image)
+                {
+                    eprintln!("Warning: {e:#}");
+                }
+                (image, transform)
+            }
+        };
+
+        let _span = debug_span!("display", cached = cached_image).entered();
+        self.renderer
+            .show(&image, displayed_aspect_ratio(metadata, transform))
+            .map_err(|e| ProcessError::DisplayFailed {
+                metadata: Box::new(metadata.clone()),
+                error: e,
+            })
+    }
+}
+
+/// Pixel aspect ratio of the image after `transform`
+fn displayed_aspect_ratio(
+    metadata: &DicomMetadata,
+    transform: Transform,
+) -> Option<PixelAspectRatio> {
+    metadata.pixel_aspect_ratio.map(|par| {
+        if transform.swaps_axes() {
+            par.transposed()
+        } else {
+            par
+        }
+    })
+}
+
+/// Longest side of an image in a `--grid` montage
+const GRID_CELL_MAX: u32 = 1024;
+
+/// Shows all images in one montage, once they are all rendered
+pub struct GridSink<R> {
+    renderer: R,
+    options: RenderOptions,
+    columns: u32,
+    images: Vec<RgbImage>,
+}
+
+impl<R: Renderer> GridSink<R> {
+    #[must_use]
+    pub fn new(renderer: R, options: RenderOptions, columns: u32) -> Self {
+        Self {
+            renderer,
+            options,
+            columns,
+            images: Vec::new(),
+        }
+    }
+
+    #[must_use]
+    pub fn renderer(&self) -> &R {
+        &self.renderer
+    }
+}
+
+impl<R: Renderer> OutputSink for GridSink<R> {
+    fn write(&mut self, metadata: &DicomMetadata) -> Result<(), ProcessError> {
+        let (image, transform) = self
+            .options
+            .render(metadata)
+            .map_err(|e| conversion_failed(metadata, e))?;
+
+        self.images
+            .push(grid_cell(metadata, image::quantize(image), transform));
+        Ok(())
+    }
+
+    fn finish(&mut self) -> anyhow::Result<()> {
+        let Some(grid) = image::montage(&self.images, self.columns) else {
+            return Ok(());
+        };
+        self.images.clear();
+
+        let _span = debug_span!("display", columns = self.columns).entered();
+        self.renderer.show(&DynamicImage::ImageRgb8(grid), None)
+    }
+}
+
+/// Shows the views of a hanging protocol together, each in its pane, once
+/// they are all rendered
+pub struct HangingSink<R> {
+    renderer: R,
+    options: RenderOptions,
+    protocol: Protocol,
+    panes: Vec<Option<RgbImage>>,
+}
+
+impl<R: Renderer> HangingSink<R> {
+    #[must_use]
+    pub fn new(renderer: R, options: RenderOptions, protocol: Protocol) -> Self {
+        Self {
+            renderer,
+            options,
+            protocol,
+            panes: vec![None; protocol.panes()],
+        }
+    }
+
+    #[must_use]
+    pub fn renderer(&self) -> &R {
+        &self.renderer
+    }
+}
+
+impl<R: Renderer> OutputSink for HangingSink<R> {
+    fn write(&mut self, metadata: &DicomMetadata) -> Result<(), ProcessError> {
+        let Some(pane) = self.protocol.pane(&metadata.view) else {
+            debug!(view = ?metadata.view, "not in the layout");
+            return Ok(());
+        };
+
+        let (image, transform) = self
+            .options
+            .render(metadata)
+            .map_err(|e| conversion_failed(metadata, e))?;
+
+        self.panes[pane] = Some(grid_cell(metadata, image::quantize(image), transform));
+        Ok(())
+    }
+
+    fn finish(&mut self) -> anyhow::Result<()> {
+        // Rows without any view are left out, empty panes stay black
+        let columns = self.protocol.columns() as usize;
+        let Some(last) = self.panes.iter().rposition(Option::is_some) else {
+            return Ok(());
+        };
+        let panes: Vec<RgbImage> = self.panes[..(last / columns + 1) * columns]
+            .iter_mut()
+            .map(|pane| pane.take().unwrap_or_else(|| RgbImage::new(1, 1)))
+            .collect();
+
+        let Some(hanging) = image::montage(&panes, self.protocol.columns()) else {
+            return Ok(());
+        };
+
+        let _span = debug_span!("display", layout = self.protocol.name()).entered();
+        self.renderer.show(&DynamicImage::ImageRgb8(hanging), None)
+    }
+}
+
+/// A rendered image prepared for a montage: with square pixels, so the
+/// montage can be drawn as one image, and at most [`GRID_CELL_MAX`] pixels
+/// on each side
+fn grid_cell(metadata: &DicomMetadata, image: DynamicImage, transform: Transform) -> RgbImage {
+    let height = match displayed_aspect_ratio(metadata, transform) {
+        Some(par) if !par.is_square() => {
+            (f64::from(image.height()) * par.ratio()).round().max(1.0) as u32
+        }
+        _ => image.height(),
+    };
+    let scale = f64::from(GRID_CELL_MAX) / f64::from(image.width().max(height));
+    let image = if scale < 1.0 || height != image.height() {
+        let scale = scale.min(1.0);
+        image.resize_exact(
+            ((f64::from(image.width()) * scale).round() as u32).max(1),
+            ((f64::from(height) * scale).round() as u32).max(1),
+            FilterType::Triangle,
+        )
+    } else {
+        image
+    };
+
+    image.into_rgb8()
+}
+
+/// Shows each image next to a localizer, with the line where the image
+/// cuts through the localizer drawn on it
+pub struct LocalizerSink<R> {
+    renderer: R,
+    options: RenderOptions,
+    localizer: DicomMetadata,
+}
+
+impl<R: Renderer> LocalizerSink<R> {
+    #[must_use]
+    pub fn new(renderer: R, options: RenderOptions, localizer: DicomMetadata) -> Self {
+        Self {
+            renderer,
+            options,
+            localizer,
+        }
+    }
+
+    /// The localizer with the position of the image in `metadata` drawn on
+    /// it, or without it and the reason it cannot be drawn
+    fn render_localizer(&self, metadata: &DicomMetadata) -> Result<(RgbImage, Option<String>)> {
+        let options = RenderOptions {
+            frame: 0,
+            caption: None,
+            size: None,
+            ..self.options
+        };
+        let (image, transform) = options.render(&self.localizer)?;
+        let mut image = image::quantize(image).into_rgb8();
+
+        let plane = |metadata: &DicomMetadata, name: &str| {
+            ImagePlane::of(metadata).with_context(|| {
+                format!("The {name} has no Image Position, Image Orientation or Pixel Spacing")
+            })
+        };
+        let line = plane(&self.localizer, "localizer")
+            .and_then(|localizer| localizer.intersection(&plane(metadata, "image")?));
+
+        match line {
+            Ok((from, to)) => {
+                let size = (
+                    u32::from(self.localizer.cols()),
+                    u32::from(self.localizer.rows()),
+                );
+                let position = |(x, y): (f64, f64)| {
+                    let (x, y) =
+                        transform.target_position((x.round() as u32, y.round() as u32), size);
+                    (i64::from(x), i64::from(y))
+                };
+                let thickness = image::text_scale(&image);
+                draw_line(
+                    &mut image,
+                    position(from),
+                    position(to),
+                    thickness,
+                    ANNOTATION_COLOR,
+                );
+                Ok((image, None))
+            }
+            Err(e) => Ok((image, Some(format!("No localizer line: {e:#}")))),
+        }
+    }
+}
+
+impl<R: Renderer> OutputSink for LocalizerSink<R> {
+    fn write(&mut self, metadata: &DicomMetadata) -> Result<(), ProcessError> {
+        let (image, transform) = self
+            .options
+            .render(metadata)
+            .map_err(|e| conversion_failed(metadata, e))?;
+        let image = grid_cell(metadata, image::quantize(image), transform);
+
+        let (localizer, missing_line) = self.render_localizer(metadata).map_err(|e| {
+            conversion_failed(metadata, e.context("Failed to render the localizer"))
+        })?;
+        if let Some(reason) = missing_line {
+            eprintln!("Warning: {reason}");
+        }
+        let localizer = grid_cell(
+            &self.localizer,
+            DynamicImage::ImageRgb8(localizer),
+            self.options.transform(&self.localizer),
+        );
+
+        let Some(pair) = image::montage(&[image, localizer], 2) else {
+            return Ok(());
+        };
+
+        let _span = debug_span!("display").entered();
+        self.renderer
+            .show(&DynamicImage::ImageRgb8(pair), None)
+            .map_err(|e| ProcessError::DisplayFailed {
+                metadata: Box::new(metadata.clone()),
+                error: e,
+            })
+    }
+}
+
+/// Blends PET slices over each image before passing it on to another sink
+pub struct FusionSink<'a> {
+    inner: Box<dyn OutputSink + 'a>,
+    fusion: Fusion,
+    options: RenderOptions,
+}
+
+impl<'a> FusionSink<'a> {
+    #[must_use]
+    pub fn new(inner: Box<dyn OutputSink + 'a>, fusion: Fusion, options: RenderOptions) -> Self {
+        Self {
+            inner,
+            fusion,
+            options,
+        }
+    }
+}
+
+impl OutputSink for FusionSink<'_> {
+    fn write(&mut self, metadata: &DicomMetadata) -> Result<(), ProcessError> {
+        let fused = self
+            .fusion
+            .fuse(metadata, &self.options)
+            .map_err(|e| conversion_failed(metadata, e))?;
+        self.inner.write(&fused)
+    }
+
+    fn finish(&mut self) -> anyhow::Result<()> {
+        self.inner.finish()
+    }
+}
+
+/// Adds the graphic annotations of a presentation state to each image before
+/// passing it on to another sink
+pub struct PresentationStateSink<'a> {
+    inner: Box<dyn OutputSink + 'a>,
+    presentation_state: PresentationState,
+}
+
+impl<'a> PresentationStateSink<'a> {
+    #[must_use]
+    pub fn new(inner: Box<dyn OutputSink + 'a>, presentation_state: PresentationState) -> Self {
+        Self {
+            inner,
+            presentation_state,
+        }
+    }
+}
+
+impl OutputSink for PresentationStateSink<'_> {
+    fn write(&mut self, metadata: &DicomMetadata) -> Result<(), ProcessError> {
+        self.inner.write(&self.presentation_state.apply(metadata))
+    }
+
+    fn finish(&mut self) -> anyhow::Result<()> {
+        self.inner.finish()
+    }
+}
+
+/// Copies each image to the clipboard before passing it on to another sink,
+/// so the last one is left there
+pub struct ClipboardSink<'a> {
+    inner: Box<dyn OutputSink + 'a>,
+    options: RenderOptions,
+}
+
+impl<'a> ClipboardSink<'a> {
+    #[must_use]
+    pub fn new(inner: Box<dyn OutputSink + 'a>, options: RenderOptions) -> Self {
+        Self { inner, options }
+    }
+}
+
+impl OutputSink for ClipboardSink<'_> {
+    fn write(&mut self, metadata: &DicomMetadata) -> Result<(), ProcessError> {
+        let (image, _) = self
+            .options
+            .render(metadata)
+            .map_err(|e| conversion_failed(metadata, e))?;
+        clipboard::copy_image(&image::quantize(image)).map_err(|e| export_failed(metadata, e))?;
+        self.inner.write(metadata)
+    }
+
+    fn finish(&mut self) -> anyhow::Result<()> {
+        self.inner.finish()
+    }
+}
+
+/// Shows images one at a time in the interactive measurement viewer
+pub struct InteractiveSink<'a> {
+    args: &'a Args,
+    options: RenderOptions,
+}
+
+impl<'a> InteractiveSink<'a> {
+    #[must_use]
+    pub fn new(args: &'a Args, options: RenderOptions) -> Self {
+        Self { args, options }
+    }
+}
+
+impl OutputSink for InteractiveSink<'_> {
+    fn write(&mut self, metadata: &DicomMetadata) -> Result<(), ProcessError> {
+        Viewer::new(self.args, self.options, metadata)
+            .map_err(|e| conversion_failed(metadata, e))?
+            .run()
+            .map_err(|e| ProcessError::DisplayFailed {
+                metadata: Box::new(metadata.clone()),
+                error: e,
+            })
+    }
+}
+
+/// Writes the first frame to an image file, format chosen by extension
+pub struct FileSink {
+    path: PathBuf,
+    options: RenderOptions,
+}
+
+impl FileSink {
+    #[must_use]
+    pub fn new(path: impl Into<PathBuf>, options: RenderOptions) -> Self {
+        Self {
+            path: path.into(),
+            options,
+        }
+    }
+}
+
+impl OutputSink for FileSink {
+    fn write(&mut self, metadata: &DicomMetadata) -> Result<(), ProcessError> {
+        // Arrays are for analysis, so they hold modality values, not pixels
+        // prepared for display
+        if export::is_array_format(&self.path) {
+            let frame = self
+                .options
+                .stored_frame(metadata)
+                .map_err(|e| conversion_failed(metadata, e))?;
+
+            return export::export_array(
+                &frame,
+                &metadata.rescale,
+                metadata.pixel_spacing,
+                &self.path,
+            )
+            .map_err(|e| export_failed(metadata, e));
+        }
+
+        if export::is_animation_format(&self.path) {
+            let frames = (0..metadata.number_of_frames.max(1)).map(|frame| {
+                let options = RenderOptions {
+                    frame,
+                    ..self.options
+                };
+                let (image, _) = options.render(metadata)?;
+                Ok(image::quantize(image).into_rgb8())
+            });
+
+            return export::export_animation(frames, metadata.frame_time, &self.path)
+                .map_err(|e| export_failed(metadata, e));
+        }
+
+        let frame = self
+            .options
+            .export_frame(metadata)
+            .map_err(|e| conversion_failed(metadata, e))?;
+
+        export::export_frame(&frame, &self.path).map_err(|e| export_failed(metadata, e))
+    }
+}
+
+/// Streams the first frame to stdout as PNG, for piping into other tools
+pub struct StdoutSink {
+    options: RenderOptions,
+}
+
+impl StdoutSink {
+    #[must_use]
+    pub fn new(options: RenderOptions) -> Self {
+        Self { options }
+    }
+}
+
+impl OutputSink for StdoutSink {
+    fn write(&mut self, metadata: &DicomMetadata) -> Result<(), ProcessError> {
+        let frame = self
+            .options
+            .export_frame(metadata)
+            .map_err(|e| conversion_failed(metadata, e))?;
+
+        let mut stdout = std::io::stdout().lock();
+        export::write_png_to(&frame, &mut stdout)
+            .and_then(|()| stdout.flush().map_err(Into::into))
+            .map_err(|e| export_failed(metadata, e))
+    }
+}
+
+fn conversion_failed(metadata: &DicomMetadata, error: anyhow::Error) -> ProcessError {
+    ProcessError::ConversionFailed {
+        metadata: Box::new(metadata.clone()),
+        error,
+    }
+}
+
+fn export_failed(metadata: &DicomMetadata, error: anyhow::Error) -> ProcessError {
+    ProcessError::ExportFailed {
+        metadata: Box::new(metadata.clone()),
+        error,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::display::NullRenderer;
+
+    #[test]
+    fn test_terminal_sink_shows_oriented_image() {
+        let metadata = DicomMetadata::test_grayscale16(2, 3, &[0; 6]);
+        let options = RenderOptions {
+            orientation: Some(Transform::ROTATE_90),
+            ..RenderOptions::default()
+        };
+
+        let mut sink = TerminalSink::new(NullRenderer::default(), options);
+        sink.write(&metadata).unwrap();
+        sink.write(&metadata).unwrap();
+
+        assert_eq!(sink.renderer().shown, vec![(2, 3), (2, 3)]);
+    }
+
+    #[test]
+    fn test_grid_sink_shows_one_montage() {
+        let metadata = DicomMetadata::test_grayscale16(2, 3, &[0; 6]);
+
+        let mut sink = GridSink::new(NullRenderer::default(), RenderOptions::default(), 2);
+        for _ in 0..3 {
+            sink.write(&metadata).unwrap();
+        }
+        assert!(sink.renderer().shown.is_empty());
+
+        sink.finish().unwrap();
+        assert_eq!(sink.renderer().shown.len(), 1);
+    }
+}
@@ -0,0 +1,175 @@
+//! Splitting a multi-frame file into single-frame files for `dcmv split`
+//!
+//! Each frame is written with all attributes of the multi-frame file, one
+//! frame of pixel data, its own item of the Per-frame Functional Groups
+//! Sequence and a new SOP Instance UID. Compressed frames are copied as
+//! they are, without decoding.
+
+use crate::dicom::{self, SaveOptions, save};
+use ::dicom::core::value::{DataSetSequence, PixelFragmentSequence, PrimitiveValue, Value};
+use ::dicom::core::{DataElement, VR};
+use ::dicom::dictionary_std::tags;
+use ::dicom::object::InMemDicomObject;
+use anyhow::{Context, Result, bail};
+use std::path::{Path, PathBuf};
+
+/// Write each frame of `input` to its own file in `dir`
+///
+/// Files are named after the input and the frame number, e.g.
+/// `cine_001.dcm`. Returns the files written.
+///
+/// # Errors
+///
+/// Returns an error if the input cannot be read, has a single frame, its
+/// frames cannot be told apart, or a file cannot be written
+pub fn split(input: &Path, dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut obj = dicom::open_dicom_file(input)?;
+    let metadata = dicom::extract_metadata_tags(&obj)?;
+    let number_of_frames = metadata.number_of_frames as usize;
+    if number_of_frames < 2 {
+        bail!("{} has a single frame", input.display());
+    }
+
+    let pixel_data = obj
+        .take(tags::PIXEL_DATA)
+        .context("This DICOM file does not contain pixel data")?;
+    let frames: Vec<Value<InMemDicomObject>> = match pixel_data.value() {
+        Value::PixelSequence(sequence) => dicom::group_fragments_by_frame(
+            sequence.fragments(),
+            sequence.offset_table(),
+            number_of_frames,
+        )?
+        .into_iter()
+        .map(|fragment| PixelFragmentSequence::new_fragments(vec![fragment]).into())
+        .collect(),
+        Value::Primitive(values) => {
+            let frame_bytes = metadata.dimensions.pixel_count()
+                * usize::from(metadata.samples_per_pixel)
+                * usize::from(metadata.bits_allocated())
+                / 8;
+            if !metadata.bits_allocated().is_multiple_of(8) {
+                bail!(
+                    "Frames of {}-bit samples are not split",
+                    metadata.bits_allocated()
+                );
+            }
+            split_values(values, frame_bytes, number_of_frames)?
+        }
+        Value::Sequence(_) => bail!("Pixel Data is not pixel data"),
+    };
+
+    let per_frame = obj
+        .take(tags::PER_FRAME_FUNCTIONAL_GROUPS_SEQUENCE)
+        .and_then(|element| element.items().map(<[_]>::to_vec));
+
+    std::fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    let stem = input
+        .file_stem()
+        .map_or_else(|| "frame".into(), |stem| stem.to_string_lossy());
+    let digits = number_of_frames.to_string().len();
+
+    let mut written = Vec::with_capacity(number_of_frames);
+    for (index, frame) in frames.into_iter().enumerate() {
+        let mut single = obj.clone();
+        single.put(DataElement::new(tags::PIXEL_DATA, pixel_data.vr(), frame));
+        single.put(DataElement::new(tags::NUMBER_OF_FRAMES, VR::IS, "1"));
+        single.put(DataElement::new(
+            tags::INSTANCE_NUMBER,
+            VR::IS,
+            (index + 1).to_string(),
+        ));
+        if let Some(item) = per_frame.as_ref().and_then(|items| items.get(index)) {
+            single.put(DataElement::new(
+                tags::PER_FRAME_FUNCTIONAL_GROUPS_SEQUENCE,
+                VR::SQ,
+                DataSetSequence::from(vec![item.clone()]),
+            ));
+        }
+
+        let path = dir.join(format!("{stem}_{:0digits$}.dcm", index + 1));
+        save(
+            &mut single,
+            &path,
+            SaveOptions {
+                new_instance_uid: true,
+            },
+        )?;
+        written.push(path);
+    }
+    Ok(written)
+}
+
+/// Native pixel data cut into frames of `frame_bytes` bytes
+fn split_values(
+    values: &PrimitiveValue,
+    frame_bytes: usize,
+    number_of_frames: usize,
+) -> Result<Vec<Value<InMemDicomObject>>> {
+    fn chunks<T: Clone>(values: &[T], frame_len: usize, frames: usize) -> Option<Vec<Vec<T>>> {
+        (frame_len > 0 && values.len() >= frame_len * frames).then(|| {
+            values
+                .chunks(frame_len)
+                .take(frames)
+                .map(<[T]>::to_vec)
+                .collect()
+        })
+    }
+
+    let frames = match values {
+        PrimitiveValue::U8(bytes) => chunks(bytes, frame_bytes, number_of_frames)
+            .map(|frames| frames.into_iter().map(PrimitiveValue::from).collect()),
+        PrimitiveValue::U16(words) => {
+            chunks(words, frame_bytes / 2, number_of_frames).map(|frames| {
+                frames
+                    .into_iter()
+                    .map(|frame| PrimitiveValue::U16(frame.into()))
+                    .collect()
+            })
+        }
+        PrimitiveValue::U32(words) => {
+            chunks(words, frame_bytes / 4, number_of_frames).map(|frames| {
+                frames
+                    .into_iter()
+                    .map(|frame| PrimitiveValue::U32(frame.into()))
+                    .collect()
+            })
+        }
+        _ => bail!("Unsupported pixel data value"),
+    };
+    let frames: Vec<PrimitiveValue> = frames.with_context(|| {
+        format!("Pixel data is shorter than {number_of_frames} frames of {frame_bytes} bytes")
+    })?;
+    Ok(frames.into_iter().map(Value::from).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestDataset;
+
+    #[test]
+    fn test_split_writes_one_file_per_frame() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("cine.dcm");
+
+        TestDataset::gray16("1.2.3.4", 1, 2, &[1, 2, 3, 4, 5, 6])
+            .with(tags::NUMBER_OF_FRAMES, VR::IS, "3")
+            .write(&input);
+
+        let files = split(&input, &dir.path().join("frames")).unwrap();
+        assert_eq!(files.len(), 3);
+        assert!(files[2].ends_with("frames/cine_3.dcm"));
+
+        let last = dicom::open_dicom_file(&files[2]).unwrap();
+        let value = |tag| last.element(tag).unwrap().to_str().unwrap().into_owned();
+        assert_eq!(value(tags::NUMBER_OF_FRAMES), "1");
+        assert_ne!(value(tags::SOP_INSTANCE_UID), "1.2.3.4");
+        assert_eq!(
+            last.element(tags::PIXEL_DATA)
+                .unwrap()
+                .to_multi_int::<u16>()
+                .unwrap(),
+            [5, 6]
+        );
+    }
+}
@@ -0,0 +1,424 @@
+//! A series of single-frame files browsed as one stack of slices
+//!
+//! Only the file headers are read up front, to order the slices by
+//! position along the slice normal or by Instance Number. Slices are
+//! decoded when shown, and the last few are kept in a small cache. While a
+//! slice is shown, a background thread decodes the slices around it, so
+//! stepping through a compressed series does not wait for each one to
+//! decode.
+
+use crate::dicom::{DicomMetadata, ExtractOptions};
+use crate::index::{self, InstanceEntry, Scan};
+use crate::render::RenderSource;
+use anyhow::{Context, Result};
+use std::collections::{HashSet, VecDeque};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use tracing::debug;
+
+/// Slices decoded ahead in each direction
+const PREFETCH_SLICES: usize = 3;
+
+/// Decoded slices kept in memory: the current one and the prefetched ones
+/// on either side
+const CACHED_SLICES: usize = 2 * PREFETCH_SLICES + 1;
+
+/// A slice decoded by the background thread
+type Decoded = (usize, Result<DicomMetadata>);
+
+/// Files of a series in slice order, decoded on demand
+pub struct SliceStack {
+    paths: Vec<PathBuf>,
+    extract: ExtractOptions,
+    cache: SliceCache,
+    prefetcher: Prefetcher,
+}
+
+impl SliceStack {
+    /// Stack of the DICOM files among `paths`, with directories standing
+    /// for the files directly in them
+    ///
+    /// Headers are read in parallel and returned with the files that are not
+    /// DICOM, which are left out.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a directory cannot be listed, or reading is
+    /// interrupted with Ctrl-C
+    pub fn open(paths: &[PathBuf], extract: ExtractOptions) -> Result<(Self, Scan)> {
+        let mut scan = index::read_files(paths)?;
+        scan.instances = in_slice_order(scan.instances);
+
+        let paths = scan
+            .instances
+            .iter()
+            .map(|instance| instance.path.clone())
+            .collect();
+        Ok((Self::from_paths(paths, extract), scan))
+    }
+
+    /// Stack of the files at `paths`, in the order given
+    #[must_use]
+    pub fn from_paths(paths: Vec<PathBuf>, extract: ExtractOptions) -> Self {
+        Self {
+            paths,
+            prefetcher: Prefetcher::spawn(extract.clone()),
+            extract,
+            cache: SliceCache::new(CACHED_SLICES),
+        }
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.paths.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.paths.is_empty()
+    }
+
+    #[must_use]
+    pub fn path(&self, index: usize) -> &Path {
+        &self.paths[index]
+    }
+
+    /// The decoded slice at `index`, from the cache if possible
+    ///
+    /// Waits for the background thread if it is already decoding the slice.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read or decoded
+    pub fn get(&mut self, index: usize) -> Result<Rc<DicomMetadata>> {
+        self.collect_prefetched();
+        if let Some(slice) = self.cache.get(index) {
+            return Ok(slice);
+        }
+
+        let slice = match self.prefetcher.wait_for(index, &mut self.cache) {
+            Some(result) => result?,
+            None => {
+                let path = self.paths.get(index).context("No such slice")?;
+                path.load(&self.extract)?
+            }
+        };
+        let slice = Rc::new(slice);
+        self.cache.insert(index, Rc::clone(&slice));
+        Ok(slice)
+    }
+
+    /// Have the background thread decode the slices around `index`,
+    /// nearest first, without waiting for them
+    pub fn prefetch_neighbors(&mut self, index: usize) {
+        self.collect_prefetched();
+
+        let mut requests = Vec::new();
+        for distance in 1..=PREFETCH_SLICES {
+            for neighbor in [index + distance, index.wrapping_sub(distance)] {
+                if neighbor < self.len() && !self.cache.contains(neighbor) {
+                    requests.push((neighbor, self.paths[neighbor].clone()));
+                }
+            }
+        }
+        // Slices queued for an earlier position are no longer needed first
+        self.prefetcher.replace(requests);
+    }
+
+    /// Move the slices decoded in the background so far into the cache
+    fn collect_prefetched(&mut self) {
+        while let Some((index, result)) = self.prefetcher.try_next() {
+            cache_prefetched(&mut self.cache, index, result);
+        }
+    }
+}
+
+fn cache_prefetched(cache: &mut SliceCache, index: usize, result: Result<DicomMetadata>) {
+    match result {
+        Ok(slice) => cache.insert(index, Rc::new(slice)),
+        Err(e) => debug!(slice = index, "prefetch failed: {e:#}"),
+    }
+}
+
+/// `instances` ordered along the normal of the first one, when all have a
+/// position and it has an orientation, or else by Instance Number when all
+/// have one
+fn in_slice_order(instances: Vec<InstanceEntry>) -> Vec<InstanceEntry> {
+    let normal = instances
+        .first()
+        .and_then(|first| first.orientation)
+        .map(|orientation| orientation.normal());
+    let distances: Option<Vec<f64>> = instances
+        .iter()
+        .map(|instance| {
+            let (normal, position) = (normal?, instance.position?);
+            Some((0..3).map(|i| position[i] * normal[i]).sum())
+        })
+        .collect();
+    let keys = distances.or_else(|| {
+        instances
+            .iter()
+            .map(|instance| instance.instance_number.map(f64::from))
+            .collect()
+    });
+
+    let Some(keys) = keys else {
+        return instances;
+    };
+    let mut keyed: Vec<(f64, InstanceEntry)> = keys.into_iter().zip(instances).collect();
+    keyed.sort_by(|(a, _), (b, _)| a.total_cmp(b));
+    keyed.into_iter().map(|(_, instance)| instance).collect()
+}
+
+/// Background thread decoding the requested slices, nearest first
+///
+/// Requests the thread has not taken up yet are replaced when another slice
+/// is shown, so stepping quickly through a series does not leave it
+/// decoding slices far behind. The thread ends once the stack is dropped.
+struct Prefetcher {
+    queue: Arc<(Mutex<Queue>, Condvar)>,
+    results: Receiver<Decoded>,
+    /// Slices queued or being decoded, not received back yet
+    pending: HashSet<usize>,
+}
+
+/// Requests waiting for the background thread
+#[derive(Default)]
+struct Queue {
+    requests: VecDeque<(usize, PathBuf)>,
+    /// Set when the stack is dropped
+    closed: bool,
+}
+
+impl Prefetcher {
+    fn spawn(extract: ExtractOptions) -> Self {
+        let queue = Arc::new((Mutex::new(Queue::default()), Condvar::new()));
+        let (decoded, results) = mpsc::channel();
+
+        let requests = Arc::clone(&queue);
+        thread::spawn(move || {
+            while let Some((index, path)) = next_request(&requests) {
+                if decoded.send((index, path.load(&extract))).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Self {
+            queue,
+            results,
+            pending: HashSet::new(),
+        }
+    }
+
+    /// Queue `requests` in place of the ones not taken up yet
+    fn replace(&mut self, requests: Vec<(usize, PathBuf)>) {
+        let (lock, wakeup) = &*self.queue;
+        let mut queue = lock.lock().unwrap_or_else(|e| e.into_inner());
+        for (stale, _) in queue.requests.drain(..) {
+            self.pending.remove(&stale);
+        }
+        for (index, path) in requests {
+            // Slices being decoded are not decoded again
+            if self.pending.insert(index) {
+                queue.requests.push_back((index, path));
+            }
+        }
+        wakeup.notify_one();
+    }
+
+    fn try_next(&mut self) -> Option<Decoded> {
+        let decoded = self.results.try_recv().ok()?;
+        self.pending.remove(&decoded.0);
+        Some(decoded)
+    }
+
+    /// The slice at `index` if the thread is decoding it, caching the other
+    /// slices decoded before it
+    ///
+    /// A slice still queued is taken out of the queue and left to the
+    /// caller, rather than waiting for the slices queued before it.
+    fn wait_for(&mut self, index: usize, cache: &mut SliceCache) -> Option<Result<DicomMetadata>> {
+        if !self.pending.contains(&index) {
+            return None;
+        }
+        {
+            let mut queue = self.queue.0.lock().unwrap_or_else(|e| e.into_inner());
+            if let Some(queued) = queue.requests.iter().position(|(i, _)| *i == index) {
+                queue.requests.remove(queued);
+                self.pending.remove(&index);
+                return None;
+            }
+        }
+
+        while let Ok((decoded, result)) = self.results.recv() {
+            self.pending.remove(&decoded);
+            if decoded == index {
+                return Some(result);
+            }
+            cache_prefetched(cache, decoded, result);
+        }
+
+        // The thread is gone, so the slice is decoded here instead
+        self.pending.clear();
+        None
+    }
+}
+
+impl Drop for Prefetcher {
+    fn drop(&mut self) {
+        let (lock, wakeup) = &*self.queue;
+        lock.lock().unwrap_or_else(|e| e.into_inner()).closed = true;
+        wakeup.notify_one();
+    }
+}
+
+/// The next request for the background thread, waiting for one; `None`
+/// once the stack is dropped
+fn next_request(queue: &(Mutex<Queue>, Condvar)) -> Option<(usize, PathBuf)> {
+    let (lock, wakeup) = queue;
+    let mut queue = lock.lock().unwrap_or_else(|e| e.into_inner());
+    loop {
+        if queue.closed {
+            return None;
+        }
+        if let Some(request) = queue.requests.pop_front() {
+            return Some(request);
+        }
+        queue = wakeup.wait(queue).unwrap_or_else(|e| e.into_inner());
+    }
+}
+
+/// `paths` with each directory replaced by the files in it, in name order
+pub(crate) fn expand_directories(paths: &[PathBuf]) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+
+    for path in paths {
+        if !path.is_dir() {
+            files.push(path.clone());
+            continue;
+        }
+
+        let mut entries = fs::read_dir(path)
+            .and_then(|entries| {
+                entries
+                    .map(|entry| entry.map(|entry| entry.path()))
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .with_context(|| format!("Failed to read directory: {}", path.display()))?;
+        entries.sort();
+        files.extend(entries.into_iter().filter(|entry| entry.is_file()));
+    }
+
+    Ok(files)
+}
+
+/// Least recently used cache of decoded slices by index
+struct SliceCache {
+    capacity: usize,
+    /// Most recently used last
+    entries: VecDeque<(usize, Rc<DicomMetadata>)>,
+}
+
+impl SliceCache {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    fn get(&mut self, index: usize) -> Option<Rc<DicomMetadata>> {
+        let position = self
+            .entries
+            .iter()
+            .position(|(cached, _)| *cached == index)?;
+        let entry = self.entries.remove(position)?;
+        let slice = Rc::clone(&entry.1);
+        self.entries.push_back(entry);
+        Some(slice)
+    }
+
+    fn contains(&self, index: usize) -> bool {
+        self.entries.iter().any(|(cached, _)| *cached == index)
+    }
+
+    fn insert(&mut self, index: usize, slice: Rc<DicomMetadata>) {
+        self.entries.retain(|(cached, _)| *cached != index);
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back((index, slice));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestDataset;
+    use ::dicom::core::VR;
+    use ::dicom::dictionary_std::tags;
+
+    #[test]
+    fn test_cache_evicts_least_recently_used() {
+        let slice = Rc::new(DicomMetadata::test_grayscale16(1, 1, &[0]));
+        let mut cache = SliceCache::new(2);
+        cache.insert(0, Rc::clone(&slice));
+        cache.insert(1, Rc::clone(&slice));
+
+        // Using 0 makes 1 the least recently used
+        assert!(cache.get(0).is_some());
+        cache.insert(2, slice);
+
+        assert!(cache.get(1).is_none());
+        assert!(cache.get(0).is_some());
+        assert!(cache.get(2).is_some());
+    }
+
+    #[test]
+    fn test_open_orders_slices_along_the_normal() {
+        let dir = tempfile::tempdir().unwrap();
+        // Instance numbers run against the positions
+        for (name, number, z) in [("a", "1", "20"), ("b", "2", "0"), ("c", "3", "10")] {
+            TestDataset::gray16(name, 1, 1, &[0])
+                .with(tags::INSTANCE_NUMBER, VR::IS, number)
+                .with(tags::IMAGE_POSITION_PATIENT, VR::DS, format!("0\\0\\{z}"))
+                .with(tags::IMAGE_ORIENTATION_PATIENT, VR::DS, "1\\0\\0\\0\\1\\0")
+                .write(&dir.path().join(format!("{name}.dcm")));
+        }
+        fs::write(dir.path().join("notes.txt"), "not DICOM").unwrap();
+
+        let (stack, scan) =
+            SliceStack::open(&[dir.path().to_path_buf()], ExtractOptions::default()).unwrap();
+        let names: Vec<_> = (0..stack.len())
+            .map(|i| stack.path(i).file_stem().unwrap().to_owned())
+            .collect();
+        assert_eq!(names, ["b", "c", "a"]);
+        assert_eq!(scan.skipped, [dir.path().join("notes.txt")]);
+    }
+
+    #[test]
+    fn test_slices_are_decoded_after_moving_on() {
+        let dir = tempfile::tempdir().unwrap();
+        let paths: Vec<PathBuf> = (0..8_u16)
+            .map(|i| {
+                let path = dir.path().join(format!("{i}.dcm"));
+                TestDataset::gray16(&format!("1.2.{i}"), 1, 1, &[i]).write(&path);
+                path
+            })
+            .collect();
+        let mut stack = SliceStack::from_paths(paths, ExtractOptions::default());
+
+        // Requests around 0 are replaced by those around 7 before they finish
+        stack.prefetch_neighbors(0);
+        stack.prefetch_neighbors(7);
+        for index in [7, 3, 5, 0] {
+            let slice = stack.get(index).unwrap();
+            assert_eq!(slice.pixel_data(), (index as u16).to_le_bytes());
+        }
+    }
+}
@@ -0,0 +1,481 @@
+//! Synthetic test images for `dcmv gen`
+//!
+//! Every file shows the same pattern: eight color bars (white, yellow, cyan,
+//! green, magenta, red, blue and black) above a gray ramp from black on the
+//! left to white on the right. Grayscale files show the bars by their
+//! luminance. Files are written for each pixel format dcmv can show, in
+//! each transfer syntax this build can encode, so a wrong conversion or a
+//! terminal that mangles colors shows up as a file that looks different
+//! from the others.
+
+use crate::dicom::{SaveOptions, new_uid, save};
+use anyhow::{Context, Result};
+use dicom::core::{DataElement, PrimitiveValue, VR};
+use dicom::dictionary_std::tags;
+use dicom::encoding::TransferSyntaxIndex;
+use dicom::object::{FileMetaTableBuilder, InMemDicomObject};
+use dicom::transfer_syntax::{TransferSyntaxRegistry, entries};
+use dicom_dictionary_std::uids;
+use dicom_pixeldata::Transcode;
+use std::path::{Path, PathBuf};
+use tracing::debug;
+
+/// Width of the pattern, in pixels
+pub const COLUMNS: u16 = 128;
+/// Height of the pattern, in pixels
+pub const ROWS: u16 = 96;
+/// Rows taken by the color bars; the gray ramp fills the rest
+pub const BAR_ROWS: u16 = 64;
+
+/// The color bars, left to right
+pub const BARS: [[u8; 3]; 8] = [
+    [255, 255, 255],
+    [255, 255, 0],
+    [0, 255, 255],
+    [0, 255, 0],
+    [255, 0, 255],
+    [255, 0, 0],
+    [0, 0, 255],
+    [0, 0, 0],
+];
+
+/// Layout of the stored pixels of a synthetic file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PixelFormat {
+    /// Name in file names
+    pub name: &'static str,
+    pub photometric: &'static str,
+    pub bits_stored: u16,
+    /// Color planes stored one after the other (Planar Configuration 1)
+    pub planar: bool,
+}
+
+impl PixelFormat {
+    const fn gray(name: &'static str, photometric: &'static str, bits: u16) -> Self {
+        Self {
+            name,
+            photometric,
+            bits_stored: bits,
+            planar: false,
+        }
+    }
+
+    const fn color(name: &'static str, photometric: &'static str, planar: bool) -> Self {
+        Self {
+            name,
+            photometric,
+            bits_stored: 8,
+            planar,
+        }
+    }
+
+    #[must_use]
+    pub const fn bits_allocated(&self) -> u16 {
+        if self.bits_stored > 8 { 16 } else { 8 }
+    }
+
+    #[must_use]
+    pub fn samples_per_pixel(&self) -> u16 {
+        match self.photometric {
+            "MONOCHROME1" | "MONOCHROME2" | "PALETTE COLOR" => 1,
+            "CMYK" => 4,
+            _ => 3,
+        }
+    }
+
+    #[must_use]
+    pub fn is_grayscale(&self) -> bool {
+        matches!(self.photometric, "MONOCHROME1" | "MONOCHROME2")
+    }
+}
+
+/// The pixel formats written by [`generate`]
+pub const FORMATS: &[PixelFormat] = &[
+    PixelFormat::gray("mono2-8", "MONOCHROME2", 8),
+    PixelFormat::gray("mono2-12", "MONOCHROME2", 12),
+    PixelFormat::gray("mono2-16", "MONOCHROME2", 16),
+    PixelFormat::gray("mono1-8", "MONOCHROME1", 8),
+    PixelFormat::gray("mono1-16", "MONOCHROME1", 16),
+    PixelFormat::color("rgb", "RGB", false),
+    PixelFormat::color("rgb-planar", "RGB", true),
+    PixelFormat::color("ybr-full", "YBR_FULL", false),
+    PixelFormat::color("ybr-full-planar", "YBR_FULL", true),
+    PixelFormat::color("ybr-full-422", "YBR_FULL_422", false),
+    PixelFormat::color("ybr-partial-422", "YBR_PARTIAL_422", false),
+    PixelFormat::color("ybr-partial-420", "YBR_PARTIAL_420", false),
+    PixelFormat::color("palette", "PALETTE COLOR", false),
+    PixelFormat::color("hsv", "HSV", false),
+    PixelFormat::color("cmyk", "CMYK", false),
+];
+
+/// The transfer syntaxes written by [`generate`], with their names in file
+/// names. Compressed ones are written only when this build can encode the
+/// pixel format with them, and baseline JPEG only for 8-bit formats.
+pub const SYNTAXES: &[(&str, &str)] = &[
+    ("implicit-le", uids::IMPLICIT_VR_LITTLE_ENDIAN),
+    ("explicit-le", uids::EXPLICIT_VR_LITTLE_ENDIAN),
+    ("explicit-be", entries::EXPLICIT_VR_BIG_ENDIAN.uid()),
+    ("rle", uids::RLE_LOSSLESS),
+    ("jpeg-baseline", uids::JPEG_BASELINE8_BIT),
+    ("jpeg-ls", uids::JPEGLS_LOSSLESS),
+];
+
+/// Write the pattern in every pixel format and transfer syntax to `dir`
+///
+/// Files are named after the format and syntax, e.g.
+/// `rgb-planar_explicit-le.dcm`. Returns the files written.
+///
+/// # Errors
+///
+/// Returns an error if `dir` cannot be created or a file cannot be written
+pub fn generate(dir: &Path) -> Result<Vec<PathBuf>> {
+    std::fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+
+    let mut written = Vec::new();
+    for format in FORMATS {
+        for &(syntax, uid) in SYNTAXES {
+            let path = dir.join(format!("{}_{syntax}.dcm", format.name));
+            if write(format, uid, &path)? {
+                written.push(path);
+            }
+        }
+    }
+    Ok(written)
+}
+
+/// Write the pattern in `format` with transfer syntax `ts_uid` to `path`
+///
+/// Returns `false`, writing nothing, when this build cannot encode the
+/// format with that syntax.
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be written
+pub fn write(format: &PixelFormat, ts_uid: &str, path: &Path) -> Result<bool> {
+    let Some(ts) = TransferSyntaxRegistry.get(ts_uid) else {
+        return Ok(false);
+    };
+    let name = path
+        .file_stem()
+        .map_or_else(String::new, |stem| stem.to_string_lossy().into_owned());
+    let sop_instance_uid = new_uid();
+
+    // Compressed syntaxes are reached by encoding the uncompressed file
+    let native = if ts.is_encapsulated_pixel_data() {
+        uids::EXPLICIT_VR_LITTLE_ENDIAN
+    } else {
+        ts_uid
+    };
+    let meta = FileMetaTableBuilder::new()
+        .transfer_syntax(native)
+        .media_storage_sop_class_uid(uids::SECONDARY_CAPTURE_IMAGE_STORAGE)
+        .media_storage_sop_instance_uid(sop_instance_uid.as_str());
+    let mut obj = dataset(format, &name, &sop_instance_uid)
+        .with_meta(meta)
+        .context("Failed to build the file meta group")?;
+
+    if ts.is_encapsulated_pixel_data() {
+        // The dicom-rs encoders take interleaved grayscale or RGB pixels;
+        // others would be encoded as if they were
+        let encodable = format.is_grayscale() || (format.photometric == "RGB" && !format.planar);
+        let lossy_8bit = ts_uid == uids::JPEG_BASELINE8_BIT;
+        if ts.pixel_data_writer().is_none()
+            || !encodable
+            || (lossy_8bit && format.bits_allocated() != 8)
+        {
+            return Ok(false);
+        }
+        if let Err(e) = obj.transcode(ts) {
+            debug!(format = format.name, ts = ts.name(), "not encoded: {e}");
+            return Ok(false);
+        }
+    }
+
+    save(&mut obj, path, SaveOptions::default())?;
+    Ok(true)
+}
+
+/// The data set of a Secondary Capture image of the pattern
+fn dataset(format: &PixelFormat, description: &str, sop_instance_uid: &str) -> InMemDicomObject {
+    let mut obj = InMemDicomObject::new_empty();
+    let text = [
+        (
+            tags::SOP_CLASS_UID,
+            VR::UI,
+            uids::SECONDARY_CAPTURE_IMAGE_STORAGE,
+        ),
+        (tags::SOP_INSTANCE_UID, VR::UI, sop_instance_uid),
+        (tags::MODALITY, VR::CS, "OT"),
+        (tags::PATIENT_NAME, VR::PN, "Pattern^Test"),
+        (tags::SERIES_DESCRIPTION, VR::LO, description),
+        (tags::PHOTOMETRIC_INTERPRETATION, VR::CS, format.photometric),
+    ];
+    for (tag, vr, value) in text {
+        obj.put(DataElement::new(tag, vr, value));
+    }
+
+    let mut numbers = vec![
+        (tags::SAMPLES_PER_PIXEL, format.samples_per_pixel()),
+        (tags::ROWS, ROWS),
+        (tags::COLUMNS, COLUMNS),
+        (tags::BITS_ALLOCATED, format.bits_allocated()),
+        (tags::BITS_STORED, format.bits_stored),
+        (tags::HIGH_BIT, format.bits_stored - 1),
+        (tags::PIXEL_REPRESENTATION, 0),
+    ];
+    if format.samples_per_pixel() > 1 {
+        numbers.push((tags::PLANAR_CONFIGURATION, u16::from(format.planar)));
+    }
+    for (tag, value) in numbers {
+        obj.put(DataElement::new(tag, VR::US, PrimitiveValue::from(value)));
+    }
+
+    if format.photometric == "PALETTE COLOR" {
+        let colors = palette();
+        let descriptor = [colors.len() as u16, 0, 16];
+        let luts = [
+            (
+                tags::RED_PALETTE_COLOR_LOOKUP_TABLE_DESCRIPTOR,
+                tags::RED_PALETTE_COLOR_LOOKUP_TABLE_DATA,
+            ),
+            (
+                tags::GREEN_PALETTE_COLOR_LOOKUP_TABLE_DESCRIPTOR,
+                tags::GREEN_PALETTE_COLOR_LOOKUP_TABLE_DATA,
+            ),
+            (
+                tags::BLUE_PALETTE_COLOR_LOOKUP_TABLE_DESCRIPTOR,
+                tags::BLUE_PALETTE_COLOR_LOOKUP_TABLE_DATA,
+            ),
+        ];
+        for (channel, (descriptor_tag, data_tag)) in luts.into_iter().enumerate() {
+            let data: Vec<u16> = colors
+                .iter()
+                .map(|color| u16::from(color[channel]) * 257)
+                .collect();
+            obj.put(DataElement::new(
+                descriptor_tag,
+                VR::US,
+                PrimitiveValue::U16(descriptor.as_slice().into()),
+            ));
+            obj.put(DataElement::new(
+                data_tag,
+                VR::OW,
+                PrimitiveValue::U16(data.into()),
+            ));
+        }
+    }
+
+    let pixel_data = if format.is_grayscale() {
+        gray_samples(format)
+    } else if format.photometric == "PALETTE COLOR" {
+        let colors = palette();
+        pixels()
+            .map(|rgb| colors.iter().position(|&color| color == rgb).unwrap_or(0) as u16)
+            .collect()
+    } else {
+        color_samples(format)
+    };
+    obj.put(if format.bits_allocated() == 8 {
+        let bytes: Vec<u8> = pixel_data.iter().map(|&s| s as u8).collect();
+        DataElement::new(tags::PIXEL_DATA, VR::OB, PrimitiveValue::from(bytes))
+    } else {
+        DataElement::new(
+            tags::PIXEL_DATA,
+            VR::OW,
+            PrimitiveValue::U16(pixel_data.into()),
+        )
+    });
+    obj
+}
+
+/// Color of the pattern at column `x` and row `y`
+#[must_use]
+pub fn pattern(x: u16, y: u16) -> [u8; 3] {
+    if y < BAR_ROWS {
+        BARS[usize::from(x) * BARS.len() / usize::from(COLUMNS)]
+    } else {
+        let level = u32::from(x) * 255 / u32::from(COLUMNS - 1);
+        [level as u8; 3]
+    }
+}
+
+/// Luminance of `rgb`, from 0 to 1
+#[must_use]
+pub fn luminance([r, g, b]: [u8; 3]) -> f64 {
+    0.114_f64.mul_add(
+        f64::from(b),
+        0.299_f64.mul_add(f64::from(r), 0.587 * f64::from(g)),
+    ) / 255.0
+}
+
+fn pixels() -> impl Iterator<Item = [u8; 3]> {
+    (0..ROWS).flat_map(|y| (0..COLUMNS).map(move |x| pattern(x, y)))
+}
+
+/// The distinct colors of the pattern, the lookup table of PALETTE COLOR
+/// files
+fn palette() -> Vec<[u8; 3]> {
+    let mut colors = Vec::new();
+    for rgb in pixels() {
+        if !colors.contains(&rgb) {
+            colors.push(rgb);
+        }
+    }
+    colors
+}
+
+/// Stored values showing the luminance of the pattern over the full range
+/// of the format, darkest lowest unless MONOCHROME1
+fn gray_samples(format: &PixelFormat) -> Vec<u16> {
+    let levels = (1_u32 << format.bits_stored) - 1;
+    pixels()
+        .map(|rgb| {
+            let mut level = (luminance(rgb) * f64::from(levels)).round() as u32;
+            if format.photometric == "MONOCHROME1" {
+                level = levels - level;
+            }
+            level as u16
+        })
+        .collect()
+}
+
+/// Stored samples of the pattern, pixel by pixel or plane by plane
+fn color_samples(format: &PixelFormat) -> Vec<u16> {
+    let colors: Vec<[u8; 3]> = pixels().collect();
+    let ybr = |rgb| to_ybr(rgb, format.photometric);
+    if format.photometric.ends_with("_422") {
+        // Y of both pixels of a pair, then the Cb and Cr they share
+        return colors
+            .chunks(2)
+            .flat_map(|pair| {
+                let (first, second) = (ybr(pair[0]), ybr(pair[1]));
+                let mean = |i: usize| (first[i] + second[i]).div_ceil(2);
+                [first[0], second[0], mean(1), mean(2)]
+            })
+            .collect();
+    }
+    if format.photometric == "YBR_PARTIAL_420" {
+        // A Y plane, then Cb and Cr planes shared by each 2x2 block
+        let (rows, cols) = (usize::from(ROWS), usize::from(COLUMNS));
+        let pixels: Vec<[u16; 3]> = colors.into_iter().map(ybr).collect();
+        let pixels = &pixels;
+        let chroma = |i: usize| {
+            (0..rows).step_by(2).flat_map(move |y| {
+                (0..cols).step_by(2).map(move |x| {
+                    let block = [(y, x), (y, x + 1), (y + 1, x), (y + 1, x + 1)];
+                    block
+                        .iter()
+                        .map(|&(y, x)| pixels[y * cols + x][i])
+                        .sum::<u16>()
+                        .div_ceil(4)
+                })
+            })
+        };
+        return pixels
+            .iter()
+            .map(|pixel| pixel[0])
+            .chain(chroma(1))
+            .chain(chroma(2))
+            .collect();
+    }
+
+    let pixels: Vec<Vec<u16>> = colors
+        .into_iter()
+        .map(|rgb| match format.photometric {
+            "YBR_FULL" => ybr(rgb).to_vec(),
+            "HSV" => to_hsv(rgb).to_vec(),
+            "CMYK" => rgb.iter().map(|&c| u16::from(255 - c)).chain([0]).collect(),
+            _ => rgb.map(u16::from).to_vec(),
+        })
+        .collect();
+
+    if format.planar {
+        let samples = usize::from(format.samples_per_pixel());
+        (0..samples)
+            .flat_map(|plane| pixels.iter().map(move |pixel| pixel[plane]))
+            .collect()
+    } else {
+        pixels.into_iter().flatten().collect()
+    }
+}
+
+/// Y, Cb and Cr of `rgb` (ITU-R BT.601), full range or, for the
+/// YBR_PARTIAL photometric interpretations, Y from 16 to 235 and Cb and Cr
+/// from 16 to 240
+fn to_ybr([r, g, b]: [u8; 3], photometric: &str) -> [u16; 3] {
+    let (r, g, b) = (f64::from(r), f64::from(g), f64::from(b));
+    let mut y = 0.114_f64.mul_add(b, 0.299_f64.mul_add(r, 0.587 * g));
+    let mut cb = 0.5_f64.mul_add(b, (-0.168_736_f64).mul_add(r, -0.331_264 * g));
+    let mut cr = (-0.081_312_f64).mul_add(b, 0.5_f64.mul_add(r, -0.418_688 * g));
+    if photometric.starts_with("YBR_PARTIAL") {
+        y = y.mul_add(219.0 / 255.0, 16.0);
+        cb *= 224.0 / 255.0;
+        cr *= 224.0 / 255.0;
+    }
+    [y, cb + 128.0, cr + 128.0].map(|v| v.round().clamp(0.0, 255.0) as u16)
+}
+
+/// Hue, saturation and value of `rgb`, each scaled to 0-255
+fn to_hsv(rgb: [u8; 3]) -> [u16; 3] {
+    let [r, g, b] = rgb.map(f64::from);
+    let max = r.max(g).max(b);
+    let chroma = max - r.min(g).min(b);
+    let hue = if chroma == 0.0 {
+        0.0
+    } else if max == r {
+        ((g - b) / chroma).rem_euclid(6.0)
+    } else if max == g {
+        (b - r) / chroma + 2.0
+    } else {
+        (r - g) / chroma + 4.0
+    };
+    let saturation = if max == 0.0 { 0.0 } else { chroma / max };
+    [hue / 6.0 * 255.0, saturation * 255.0, max].map(|v| v.round() as u16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dicom::{extract_dicom_data, open_dicom_file};
+    use crate::image::convert_to_image;
+
+    #[test]
+    fn test_every_generated_file_shows_the_pattern() {
+        let dir = tempfile::tempdir().unwrap();
+        let files = generate(dir.path()).unwrap();
+        assert!(files.len() >= FORMATS.len() * 3, "{files:?}");
+
+        for file in files {
+            let name = file.file_stem().unwrap().to_string_lossy().into_owned();
+            let obj = open_dicom_file(&file).unwrap();
+            let metadata = extract_dicom_data(&obj).unwrap_or_else(|e| panic!("{name}: {e:#}"));
+            let image = convert_to_image(&metadata)
+                .unwrap_or_else(|e| panic!("{name}: {e:#}"))
+                .to_rgb8();
+            assert_eq!(image.dimensions(), (u32::from(COLUMNS), u32::from(ROWS)));
+
+            let is_gray = metadata.photometric_interpretation.is_grayscale();
+            let tolerance = if name.contains("jpeg") { 24 } else { 4 };
+            let bar_width = COLUMNS / BARS.len() as u16;
+            for (x, expected) in (0..).step_by(usize::from(bar_width)).zip(BARS) {
+                let x = x + bar_width / 2;
+                let expected = if is_gray {
+                    [(luminance(expected) * 255.0).round() as u8; 3]
+                } else {
+                    expected
+                };
+                let shown = image.get_pixel(u32::from(x), u32::from(BAR_ROWS / 2)).0;
+                assert!(
+                    shown
+                        .iter()
+                        .zip(expected)
+                        .all(|(&a, b)| a.abs_diff(b) <= tolerance),
+                    "{name}: bar at {x} is {shown:?}, expected {expected:?}"
+                );
+            }
+
+            let ramp = |x: u16| image.get_pixel(u32::from(x), u32::from(ROWS - 4)).0[0];
+            assert!(ramp(2) < 16 && ramp(COLUMNS - 3) > 240, "{name}: ramp");
+        }
+    }
+}
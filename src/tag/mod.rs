@@ -0,0 +1,140 @@
+//! Attribute lookups for `dcmv tag`
+//!
+//! Tags, keywords and VRs come from the standard data dictionary that names
+//! the elements in `--dump`. That dictionary does not carry the Value
+//! Multiplicity or whether an attribute is retired, so these are read from
+//! `attributes.tsv`, which lists the attributes that have a VM other than 1
+//! or are retired.
+
+use anyhow::{Result, bail};
+use dicom::core::Tag;
+use dicom::core::dictionary::{DataDictionary, DataDictionaryEntry, TagRange, VirtualVr};
+use dicom::dictionary_std::StandardDataDictionary;
+
+/// VM and retirement of the attributes that differ from the usual VM of 1
+const ATTRIBUTES: &str = include_str!("attributes.tsv");
+
+/// An attribute of the standard data dictionary
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TagInfo {
+    /// Tag as `(gggg,eeee)`, with `x` for the open digits of repeating
+    /// groups, e.g. `(60xx,3000)`
+    pub tag: String,
+    pub keyword: String,
+    /// VR, or the VRs it can have, e.g. `OB or OW` for Pixel Data
+    pub vr: String,
+    /// Value Multiplicity, e.g. `1`, `2` or `1-n`
+    pub vm: &'static str,
+    pub retired: bool,
+}
+
+/// The attribute named by `query`: a tag such as `0010,0010`, `(0010,0010)`
+/// or `00100010`, or a keyword such as `PatientName`
+#[must_use]
+pub fn lookup(query: &str) -> Option<TagInfo> {
+    let query = query.trim();
+    let entry = match query.parse::<Tag>() {
+        Ok(tag) => StandardDataDictionary.by_tag(tag),
+        Err(_) => {
+            // "Patient Name" as well as "PatientName"
+            let keyword: String = query.chars().filter(|c| !c.is_whitespace()).collect();
+            StandardDataDictionary.by_name(&keyword)
+        }
+    }?;
+
+    let keyword = entry.alias();
+    let (vm, retired) = ATTRIBUTES
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .map(|line| line.split('\t'))
+        .find_map(|mut fields| {
+            (fields.next() == Some(keyword)).then(|| {
+                (
+                    fields.next().unwrap_or("1"),
+                    fields.next() == Some("retired"),
+                )
+            })
+        })
+        .unwrap_or(("1", false));
+
+    Some(TagInfo {
+        tag: format_tag_range(entry.tag_range()),
+        keyword: keyword.to_string(),
+        vr: format_vr(entry.vr()),
+        vm,
+        retired,
+    })
+}
+
+/// Print the attribute named by `query` for `dcmv tag`
+///
+/// # Errors
+///
+/// Returns an error if `query` is neither a tag nor a keyword of the
+/// standard dictionary
+pub fn print_lookup(query: &str) -> Result<()> {
+    let Some(info) = lookup(query) else {
+        let query = query.trim();
+        if query.parse::<Tag>().is_ok() {
+            bail!("{query} is not in the standard data dictionary (private or unknown tag)");
+        }
+        bail!("No attribute has the keyword `{query}`, e.g. PatientName or 0010,0010");
+    };
+    println!(
+        "{} {:<40} {:<8} VM {}{}",
+        info.tag,
+        info.keyword,
+        info.vr,
+        info.vm,
+        if info.retired { " (retired)" } else { "" }
+    );
+    Ok(())
+}
+
+fn format_tag_range(range: TagRange) -> String {
+    match range {
+        TagRange::Single(tag) => format!("({:04X},{:04X})", tag.group(), tag.element()),
+        TagRange::Group100(tag) => format!("({:02X}xx,{:04X})", tag.group() >> 8, tag.element()),
+        TagRange::Element100(tag) => {
+            format!("({:04X},{:02X}xx)", tag.group(), tag.element() >> 8)
+        }
+        TagRange::GroupLength => "(gggg,0000)".to_string(),
+        TagRange::PrivateCreator => "(gggg,00xx)".to_string(),
+    }
+}
+
+fn format_vr(vr: VirtualVr) -> String {
+    match vr {
+        VirtualVr::Exact(vr) => String::from(vr.to_string()),
+        VirtualVr::Xs => "US or SS".to_string(),
+        VirtualVr::Ox | VirtualVr::Px => "OB or OW".to_string(),
+        VirtualVr::Lt => "US or OW".to_string(),
+        _ => "UN".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lookup_by_tag_and_keyword() {
+        let patient_name = lookup("0010,0010").unwrap();
+        assert_eq!(patient_name.keyword, "PatientName");
+        assert_eq!(patient_name.vr, "PN");
+        assert_eq!(patient_name.vm, "1");
+        assert!(!patient_name.retired);
+        assert_eq!(lookup("Patient Name"), Some(patient_name));
+
+        let image_type = lookup("ImageType").unwrap();
+        assert_eq!(image_type.tag, "(0008,0008)");
+        assert_eq!(image_type.vm, "2-n");
+
+        let overlay = lookup("(6000,3000)").unwrap();
+        assert_eq!(overlay.tag, "(60xx,3000)");
+        assert_eq!(overlay.vr, "OB or OW");
+
+        assert!(lookup("(0008,0040)").unwrap().retired);
+        assert_eq!(lookup("NotAKeyword"), None);
+    }
+}
@@ -0,0 +1,271 @@
+//! Time points of a dynamic series, such as DCE-MRI (for --play-temporal)
+//!
+//! A dynamic series images the same slices again and again. The files at
+//! one slice position are put in order of Temporal Position Identifier, or
+//! of Acquisition Time without it. Only the file headers are read.
+
+use crate::datetime::DicomTime;
+use crate::dicom::{self, DicomObject};
+use crate::stack;
+use ::dicom::core::Tag;
+use ::dicom::dictionary_std::tags;
+use anyhow::{Result, bail};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tracing::debug;
+
+/// Slices whose positions are closer than this, in mm, are at the same
+/// position
+const POSITION_TOLERANCE: f64 = 0.01;
+
+/// Shortest and longest time a time point is shown for, relative to the
+/// time for one time point at the chosen rate
+const DELAY_RANGE: (f64, f64) = (0.25, 4.0);
+
+const SECONDS_PER_DAY: f64 = 86_400.0;
+
+/// Attributes of a file that place it in space and time
+#[derive(Debug, Clone)]
+struct Header {
+    path: PathBuf,
+    position: Option<[f64; 3]>,
+    normal: Option<[f64; 3]>,
+    temporal_position: Option<i32>,
+    /// Acquisition Time in seconds since midnight
+    acquisition_time: Option<f64>,
+}
+
+impl Header {
+    fn read(path: &Path, obj: &DicomObject) -> Self {
+        let floats = |tag: Tag| obj.get(tag).and_then(|e| e.to_multi_float64().ok());
+        let acquisition_time = obj
+            .get(tags::ACQUISITION_TIME)
+            .and_then(|e| e.to_str().ok())
+            .and_then(|value| DicomTime::parse(&value))
+            .map(|time| time.seconds());
+
+        Self {
+            path: path.to_path_buf(),
+            position: floats(tags::IMAGE_POSITION_PATIENT)
+                .and_then(|values| <[f64; 3]>::try_from(values).ok()),
+            normal: floats(tags::IMAGE_ORIENTATION_PATIENT)
+                .filter(|values| values.len() == 6)
+                .map(|v| cross([v[0], v[1], v[2]], [v[3], v[4], v[5]])),
+            temporal_position: obj
+                .get(tags::TEMPORAL_POSITION_IDENTIFIER)
+                .and_then(|e| e.to_int::<i32>().ok()),
+            acquisition_time,
+        }
+    }
+
+    fn at_position_of(&self, other: &Self) -> bool {
+        match (self.position, other.position) {
+            (Some(a), Some(b)) => (0..3).all(|i| (a[i] - b[i]).abs() < POSITION_TOLERANCE),
+            (None, None) => true,
+            _ => false,
+        }
+    }
+}
+
+/// The files at one slice position of a dynamic series, in time order
+#[derive(Debug, Clone)]
+pub struct TemporalSeries {
+    pub paths: Vec<PathBuf>,
+    /// Acquisition Time of each time point in seconds after the first, if
+    /// every file has one
+    pub times: Option<Vec<f64>>,
+    /// The slice position played, from 0, and the number of positions in
+    /// the files
+    pub position: (usize, usize),
+}
+
+impl TemporalSeries {
+    /// Time points among `paths`, with directories standing for the files
+    /// directly in them
+    ///
+    /// With files at several slice positions, the middle position is
+    /// played. Files that are not DICOM are left out and returned
+    /// separately.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a directory cannot be listed, or the files have
+    /// no time points to play
+    pub fn open(paths: &[PathBuf]) -> Result<(Self, Vec<PathBuf>)> {
+        let mut headers = Vec::new();
+        let mut skipped = Vec::new();
+
+        for path in stack::expand_directories(paths)? {
+            match dicom::open_dicom_header(&path) {
+                Ok(obj) => headers.push(Header::read(&path, &obj)),
+                Err(e) => {
+                    debug!("not a time point: {e:#}");
+                    skipped.push(path);
+                }
+            }
+        }
+
+        Ok((Self::from_headers(headers)?, skipped))
+    }
+
+    fn from_headers(headers: Vec<Header>) -> Result<Self> {
+        let count = headers.len();
+        if count == 0 {
+            bail!("No DICOM files to play");
+        }
+
+        let mut positions: Vec<Vec<Header>> = Vec::new();
+        for header in headers {
+            match positions
+                .iter_mut()
+                .find(|files| files[0].at_position_of(&header))
+            {
+                Some(files) => files.push(header),
+                None => positions.push(vec![header]),
+            }
+        }
+
+        // Positions in order along the slice normal, so the middle one is
+        // in the middle of the volume
+        let normal = positions
+            .iter()
+            .flatten()
+            .find_map(|header| header.normal)
+            .unwrap_or([0.0, 0.0, 1.0]);
+        let along = |files: &Vec<Header>| files[0].position.map_or(0.0, |p| dot(p, normal));
+        positions.sort_by(|a, b| along(a).total_cmp(&along(b)));
+
+        let position = (positions.len() / 2, positions.len());
+        let mut files = positions.swap_remove(position.0);
+        if files.len() < 2 {
+            match position.1 {
+                1 => bail!("Only one file, so there are no time points to play"),
+                n => bail!("The {count} files are at {n} slice positions, none with time points"),
+            }
+        }
+
+        if files.iter().all(|file| file.temporal_position.is_some()) {
+            files.sort_by_key(|file| file.temporal_position);
+        } else if files.iter().all(|file| file.acquisition_time.is_some()) {
+            let time = |file: &Header| file.acquisition_time.unwrap_or_default();
+            files.sort_by(|a, b| time(a).total_cmp(&time(b)));
+        } else {
+            bail!(
+                "Cannot order the {} files at the slice position in time, as they have no Temporal Position Identifier or Acquisition Time",
+                files.len()
+            );
+        }
+
+        let times: Option<Vec<f64>> = files.iter().map(|file| file.acquisition_time).collect();
+        let times = times.map(|times| {
+            let first = times[0];
+            // Acquisitions that go on past midnight
+            times
+                .iter()
+                .map(|time| (time - first).rem_euclid(SECONDS_PER_DAY))
+                .collect()
+        });
+
+        Ok(Self {
+            paths: files.into_iter().map(|file| file.path).collect(),
+            times,
+            position,
+        })
+    }
+
+    /// How long to show each time point for, `fps` time points per second
+    /// on average
+    ///
+    /// Time points followed by a longer gap before the next acquisition
+    /// are shown for longer, within limits, so that irregular sampling
+    /// (e.g. a fast arterial phase, then slower washout) plays at an even
+    /// pace through time. The last time point is shown for one frame
+    /// before the first comes again.
+    #[must_use]
+    pub fn delays(&self, fps: f64) -> Vec<Duration> {
+        let frame = 1.0 / fps;
+        let gaps: Vec<f64> = self
+            .times
+            .iter()
+            .flat_map(|times| times.windows(2).map(|pair| (pair[1] - pair[0]).max(0.0)))
+            .collect();
+        let mean = gaps.iter().sum::<f64>() / gaps.len().max(1) as f64;
+
+        (0..self.paths.len())
+            .map(|index| {
+                let scale = match gaps.get(index) {
+                    Some(gap) if mean > 0.0 => (gap / mean).clamp(DELAY_RANGE.0, DELAY_RANGE.1),
+                    _ => 1.0,
+                };
+                Duration::from_secs_f64(frame * scale)
+            })
+            .collect()
+    }
+}
+
+fn dot(a: [f64; 3], b: [f64; 3]) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(name: &str, z: f64, temporal_position: Option<i32>, time: f64) -> Header {
+        Header {
+            path: PathBuf::from(name),
+            position: Some([0.0, 0.0, z]),
+            normal: Some([0.0, 0.0, 1.0]),
+            temporal_position,
+            acquisition_time: Some(time),
+        }
+    }
+
+    #[test]
+    fn test_time_points_at_middle_position_in_time_order() {
+        // Three positions, two time points at each, listed out of order
+        let headers = vec![
+            header("z0-t2", 0.0, None, 100.0),
+            header("z5-t2", 5.0, None, 110.0),
+            header("z5-t1", 5.0, None, 10.0),
+            header("z0-t1", 0.0, None, 0.0),
+            header("z9-t1", 9.0, None, 20.0),
+            header("z9-t2", 9.0, None, 120.0),
+        ];
+        let series = TemporalSeries::from_headers(headers).unwrap();
+        assert_eq!(
+            series.paths,
+            [PathBuf::from("z5-t1"), PathBuf::from("z5-t2")]
+        );
+        assert_eq!(series.times, Some(vec![0.0, 100.0]));
+        assert_eq!(series.position, (1, 3));
+
+        // The Temporal Position Identifier comes first
+        let headers = vec![
+            header("a", 0.0, Some(2), 0.0),
+            header("b", 0.0, Some(1), 60.0),
+            header("c", 0.0, Some(3), 70.0),
+        ];
+        let series = TemporalSeries::from_headers(headers).unwrap();
+        assert_eq!(series.paths[0], PathBuf::from("b"));
+
+        // A gap twice the mean is shown twice as long
+        let series = TemporalSeries {
+            paths: vec![PathBuf::new(); 4],
+            times: Some(vec![0.0, 10.0, 20.0, 60.0]),
+            position: (0, 1),
+        };
+        let delays = series.delays(2.0);
+        assert_eq!(delays[0], Duration::from_millis(250));
+        assert_eq!(delays[2], Duration::from_millis(1000));
+        assert_eq!(delays[3], Duration::from_millis(500));
+    }
+}
@@ -0,0 +1,116 @@
+//! Which standard streams are terminals
+//!
+//! Graphics protocols and progress redrawn in place only work on a
+//! terminal. When stdout is redirected, e.g. into a file, images are
+//! written without graphics protocols and progress goes to stderr, if that
+//! is still a terminal.
+
+use std::io::{self, IsTerminal, Write};
+use std::sync::OnceLock;
+
+/// Terminal status of stdin, stdout and stderr
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TerminalContext {
+    pub stdin: bool,
+    pub stdout: bool,
+    pub stderr: bool,
+}
+
+/// An output stream of the process
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stream {
+    Stdout,
+    Stderr,
+}
+
+impl TerminalContext {
+    /// The streams of this process, checked on first use
+    #[must_use]
+    pub fn get() -> Self {
+        static CONTEXT: OnceLock<TerminalContext> = OnceLock::new();
+        *CONTEXT.get_or_init(|| Self {
+            stdin: io::stdin().is_terminal(),
+            stdout: io::stdout().is_terminal(),
+            stderr: io::stderr().is_terminal(),
+        })
+    }
+
+    /// Whether images may be shown with graphics protocols, and the
+    /// terminal be queried for them
+    #[must_use]
+    pub fn graphics(self) -> bool {
+        self.stdout && supports_escape_codes()
+    }
+
+    /// Stream to show progress on: stdout on a terminal, otherwise stderr
+    /// on a terminal, or none
+    #[must_use]
+    pub const fn progress(self) -> Option<Stream> {
+        if self.stdout {
+            Some(Stream::Stdout)
+        } else if self.stderr {
+            Some(Stream::Stderr)
+        } else {
+            None
+        }
+    }
+
+    #[must_use]
+    pub const fn is_terminal(self, stream: Stream) -> bool {
+        match stream {
+            Stream::Stdout => self.stdout,
+            Stream::Stderr => self.stderr,
+        }
+    }
+}
+
+/// Whether escape sequences written to the terminal are interpreted
+///
+/// Always on Unix. The Windows console prints them as text unless virtual
+/// terminal processing is on, which is enabled here on first use; Windows
+/// Terminal and the console of Windows 10 and later support it, the legacy
+/// console does not.
+#[must_use]
+pub fn supports_escape_codes() -> bool {
+    #[cfg(windows)]
+    {
+        crossterm::ansi_support::supports_ansi()
+    }
+    #[cfg(not(windows))]
+    {
+        true
+    }
+}
+
+impl Stream {
+    #[must_use]
+    pub fn writer(self) -> Box<dyn Write> {
+        match self {
+            Self::Stdout => Box::new(io::stdout()),
+            Self::Stderr => Box::new(io::stderr()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_progress_moves_to_stderr_when_stdout_is_redirected() {
+        let redirected = TerminalContext {
+            stdin: true,
+            stdout: false,
+            stderr: true,
+        };
+        assert!(!redirected.graphics());
+        assert_eq!(redirected.progress(), Some(Stream::Stderr));
+
+        let interactive = TerminalContext {
+            stdout: true,
+            ..redirected
+        };
+        assert_eq!(interactive.progress(), Some(Stream::Stdout));
+        assert_eq!(TerminalContext::default().progress(), None);
+    }
+}
@@ -0,0 +1,83 @@
+//! Helpers shared by unit tests
+
+use dicom::core::value::Value;
+use dicom::core::{DataElement, PrimitiveValue, Tag, VR};
+use dicom::dictionary_std::tags;
+use dicom::object::{FileMetaTableBuilder, InMemDicomObject};
+use dicom_dictionary_std::uids;
+use std::path::Path;
+
+/// Small data set for a test, written with Explicit VR Little Endian unless
+/// another transfer syntax is given
+pub struct TestDataset {
+    obj: InMemDicomObject,
+}
+
+impl TestDataset {
+    /// Data set with only a SOP Instance UID
+    pub fn new(sop_instance_uid: &str) -> Self {
+        let mut obj = InMemDicomObject::new_empty();
+        obj.put(DataElement::new(
+            tags::SOP_INSTANCE_UID,
+            VR::UI,
+            sop_instance_uid,
+        ));
+        Self { obj }
+    }
+
+    /// 16-bit MONOCHROME2 image, with one frame per `rows * columns` pixels
+    pub fn gray16(sop_instance_uid: &str, rows: u16, columns: u16, pixels: &[u16]) -> Self {
+        let numbers = [
+            (tags::SAMPLES_PER_PIXEL, 1),
+            (tags::ROWS, rows),
+            (tags::COLUMNS, columns),
+            (tags::BITS_ALLOCATED, 16),
+            (tags::BITS_STORED, 16),
+            (tags::HIGH_BIT, 15),
+            (tags::PIXEL_REPRESENTATION, 0),
+        ];
+        let mut dataset = Self::new(sop_instance_uid)
+            .with(tags::PHOTOMETRIC_INTERPRETATION, VR::CS, "MONOCHROME2")
+            .with(tags::PIXEL_DATA, VR::OW, PrimitiveValue::U16(pixels.into()));
+        for (tag, value) in numbers {
+            dataset = dataset.with(tag, VR::US, PrimitiveValue::from(value));
+        }
+        dataset
+    }
+
+    /// Add or replace an element
+    pub fn with(
+        mut self,
+        tag: Tag,
+        vr: VR,
+        value: impl Into<Value<InMemDicomObject, Vec<u8>>>,
+    ) -> Self {
+        self.obj.put(DataElement::new(tag, vr, value));
+        self
+    }
+
+    /// The data set with a file meta group for `ts_uid`, of its SOP Class
+    /// or else Secondary Capture
+    pub fn file(self, ts_uid: &str) -> crate::dicom::DicomObject {
+        let text = |tag| {
+            self.obj
+                .get(tag)
+                .and_then(|element| element.to_str().ok())
+                .map(|value| value.into_owned())
+        };
+        let sop_class = text(tags::SOP_CLASS_UID)
+            .unwrap_or_else(|| uids::SECONDARY_CAPTURE_IMAGE_STORAGE.to_string());
+        let sop_instance = text(tags::SOP_INSTANCE_UID).unwrap_or_default();
+        let meta = FileMetaTableBuilder::new()
+            .transfer_syntax(ts_uid)
+            .media_storage_sop_class_uid(sop_class)
+            .media_storage_sop_instance_uid(sop_instance);
+        self.obj.with_meta(meta).unwrap()
+    }
+
+    pub fn write(self, path: &Path) {
+        self.file(uids::EXPLICIT_VR_LITTLE_ENDIAN)
+            .write_to_file(path)
+            .unwrap();
+    }
+}
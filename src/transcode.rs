@@ -0,0 +1,103 @@
+//! Re-encoding DICOM files to another transfer syntax for `dcmv transcode`
+//!
+//! Pixel data is decoded with the same decoders used for display and
+//! encoded with the encoders available in dicom-rs; all other attributes
+//! are kept as they are.
+
+use crate::cli::TargetSyntax;
+use crate::dicom::{SaveOptions, open_dicom_file, save};
+use anyhow::{Context, Result, bail};
+use dicom::encoding::TransferSyntaxIndex;
+use dicom::transfer_syntax::TransferSyntaxRegistry;
+use dicom_dictionary_std::uids;
+use dicom_pixeldata::Transcode;
+use std::path::Path;
+
+impl TargetSyntax {
+    /// Transfer syntax UID written to the output file
+    #[must_use]
+    pub fn uid(self) -> &'static str {
+        match self {
+            Self::ExplicitLe => uids::EXPLICIT_VR_LITTLE_ENDIAN,
+            Self::JpegLs => uids::JPEGLS_LOSSLESS,
+            Self::J2kLossless => uids::JPEG2000_LOSSLESS,
+        }
+    }
+}
+
+/// Re-encode `input` with the `target` transfer syntax and write it to `output`
+///
+/// Files already in the target syntax are copied unchanged.
+///
+/// # Errors
+///
+/// Returns an error if the input cannot be read or decoded, this build has
+/// no encoder for the target syntax, or the output cannot be written
+pub fn transcode(input: &Path, target: TargetSyntax, output: &Path) -> Result<()> {
+    let ts = TransferSyntaxRegistry
+        .get(target.uid())
+        .with_context(|| format!("Unknown transfer syntax {}", target.uid()))?;
+
+    if ts.is_encapsulated_pixel_data() && ts.pixel_data_writer().is_none() {
+        bail!(
+            "This build cannot encode {}{}",
+            ts.name(),
+            match target {
+                TargetSyntax::JpegLs => " (rebuild with the `charls` feature)",
+                _ => "",
+            }
+        );
+    }
+
+    let mut obj = open_dicom_file(input)?;
+    obj.transcode(ts)
+        .with_context(|| format!("Failed to transcode to {}", ts.name()))?;
+
+    // The lossless syntaxes keep the image, and so its SOP Instance UID
+    save(&mut obj, output, SaveOptions::default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestDataset;
+    use dicom::core::VR;
+    use dicom::dictionary_std::tags;
+
+    #[test]
+    fn test_transcode_to_explicit_le_keeps_pixels() {
+        let dir = tempfile::tempdir().unwrap();
+        let (input, output) = (dir.path().join("in.dcm"), dir.path().join("out.dcm"));
+        let pixels: Vec<u16> = vec![0, 100, 200, 300];
+
+        TestDataset::gray16("1.2.3.4", 2, 2, &pixels)
+            .with(tags::PATIENT_NAME, VR::PN, "Doe^Jane")
+            .file(uids::IMPLICIT_VR_LITTLE_ENDIAN)
+            .write_to_file(&input)
+            .unwrap();
+
+        transcode(&input, TargetSyntax::ExplicitLe, &output).unwrap();
+
+        let transcoded = open_dicom_file(&output).unwrap();
+        assert_eq!(
+            transcoded.meta().transfer_syntax(),
+            uids::EXPLICIT_VR_LITTLE_ENDIAN
+        );
+        assert_eq!(
+            transcoded
+                .element(tags::PATIENT_NAME)
+                .unwrap()
+                .to_str()
+                .unwrap(),
+            "Doe^Jane"
+        );
+        assert_eq!(
+            transcoded
+                .element(tags::PIXEL_DATA)
+                .unwrap()
+                .to_multi_int::<u16>()
+                .unwrap(),
+            pixels
+        );
+    }
+}
@@ -2,6 +2,29 @@
 
 use dicom::transfer_syntax::entries;
 use std::fmt;
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+/// Size and modification time of a file, which change when it is rewritten
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileStamp {
+    pub size: u64,
+    /// Nanoseconds since the Unix epoch
+    pub modified: u128,
+}
+
+impl FileStamp {
+    /// Stamp of the file at `path`, if its metadata can be read
+    #[must_use]
+    pub fn of(path: &Path) -> Option<Self> {
+        let metadata = std::fs::metadata(path).ok()?;
+        let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+        Some(Self {
+            size: metadata.len(),
+            modified: modified.as_nanos(),
+        })
+    }
+}
 
 /// DICOM transfer syntax (UID, name)
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -29,6 +52,22 @@ impl fmt::Display for TransferSyntax {
     }
 }
 
+/// Byte order of multi-byte samples
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ByteOrder {
+    Little,
+    Big,
+}
+
+impl fmt::Display for ByteOrder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Little => write!(f, "little endian"),
+            Self::Big => write!(f, "big endian"),
+        }
+    }
+}
+
 /// SOP Class (UID, name)
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct SOPClass {
@@ -120,6 +159,314 @@ impl fmt::Display for RescaleParams {
     }
 }
 
+/// Lookup table from the Modality LUT Sequence, used instead of rescale
+/// slope and intercept when present
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModalityLut {
+    /// Stored value mapped by the first entry
+    pub first_mapped: i32,
+    /// Output values, never empty
+    pub data: Vec<u16>,
+}
+
+impl ModalityLut {
+    #[must_use]
+    pub fn new(first_mapped: i32, data: Vec<u16>) -> Option<Self> {
+        (!data.is_empty()).then_some(Self { first_mapped, data })
+    }
+
+    /// Output value for a stored pixel value
+    ///
+    /// Values outside the table map to its first or last entry.
+    #[inline]
+    #[must_use]
+    pub fn apply(&self, stored: i32) -> f64 {
+        f64::from(self.data[self.index(stored)])
+    }
+
+    /// Smallest and largest output for stored values from `min` to `max`
+    ///
+    /// Tables need not be monotonic, so every entry in between counts, not
+    /// just those the two ends map to.
+    #[must_use]
+    pub fn output_range(&self, min: i32, max: i32) -> (f64, f64) {
+        let (from, to) = (self.index(min.min(max)), self.index(min.max(max)));
+        self.data[from..=to]
+            .iter()
+            .map(|&value| f64::from(value))
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), value| {
+                (min.min(value), max.max(value))
+            })
+    }
+
+    fn index(&self, stored: i32) -> usize {
+        let last = self.data.len() - 1;
+        (i64::from(stored) - i64::from(self.first_mapped)).clamp(0, last as i64) as usize
+    }
+}
+
+impl fmt::Display for ModalityLut {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{entries} entries from {first}",
+            entries = self.data.len(),
+            first = self.first_mapped
+        )
+    }
+}
+
+/// Red, green and blue lookup tables of a PALETTE COLOR image
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PaletteLut {
+    /// Stored value mapped by the first entry
+    pub first_mapped: i32,
+    /// Color of each entry, scaled to 8 bits; never empty
+    pub colors: Vec<[u8; 3]>,
+}
+
+impl PaletteLut {
+    #[must_use]
+    pub fn new(first_mapped: i32, colors: Vec<[u8; 3]>) -> Option<Self> {
+        (!colors.is_empty()).then_some(Self {
+            first_mapped,
+            colors,
+        })
+    }
+
+    /// Color of a stored pixel value
+    ///
+    /// Values outside the table map to its first or last entry.
+    #[inline]
+    #[must_use]
+    pub fn color(&self, stored: i32) -> [u8; 3] {
+        let last = self.colors.len() - 1;
+        let index = (i64::from(stored) - i64::from(self.first_mapped)).clamp(0, last as i64);
+        self.colors[index as usize]
+    }
+}
+
+impl fmt::Display for PaletteLut {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{entries} entries from {first}",
+            entries = self.colors.len(),
+            first = self.first_mapped
+        )
+    }
+}
+
+/// Lossy compression the pixel data has undergone (Lossy Image Compression "01")
+///
+/// Ratios and methods have one value per compression step.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct LossyCompression {
+    /// Lossy Image Compression Ratio, as uncompressed to compressed size
+    pub ratios: Vec<f64>,
+    /// Lossy Image Compression Method, e.g. `ISO_10918_1`
+    pub methods: Vec<String>,
+}
+
+impl LossyCompression {
+    /// Readable name of a Lossy Image Compression Method
+    #[must_use]
+    pub fn method_name(method: &str) -> &str {
+        match method {
+            "ISO_10918_1" => "JPEG",
+            "ISO_14495_1" => "JPEG-LS near-lossless",
+            "ISO_15444_1" => "JPEG 2000",
+            "ISO_15444_15" => "HTJ2K",
+            "ISO_13818_2" => "MPEG-2",
+            "ISO_14496_10" => "H.264",
+            "ISO_23008_2" => "HEVC",
+            other => other,
+        }
+    }
+
+    /// Ratios and method names, e.g. "10:1, JPEG"; empty if neither is given
+    #[must_use]
+    pub fn details(&self) -> String {
+        self.ratios
+            .iter()
+            .map(|ratio| format!("{ratio}:1"))
+            .chain(
+                self.methods
+                    .iter()
+                    .map(|method| Self::method_name(method).to_string()),
+            )
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+impl fmt::Display for LossyCompression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let details = self.details();
+        if details.is_empty() {
+            write!(f, "lossy compressed")
+        } else {
+            write!(f, "lossy compressed ({details})")
+        }
+    }
+}
+
+/// Declared range of stored pixel values (Smallest/Largest Image Pixel Value)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PixelValueRange {
+    pub smallest: i32,
+    pub largest: i32,
+}
+
+impl PixelValueRange {
+    #[must_use]
+    pub fn new(smallest: i32, largest: i32) -> Self {
+        Self { smallest, largest }
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn contains(&self, min: f64, max: f64) -> bool {
+        min >= f64::from(self.smallest) && max <= f64::from(self.largest)
+    }
+}
+
+impl fmt::Display for PixelValueRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{smallest}..{largest}",
+            smallest = self.smallest,
+            largest = self.largest
+        )
+    }
+}
+
+/// Curve that maps a window onto the displayed range (VOI LUT Function)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum VoiLutFunction {
+    /// Straight line across the window, clipped outside it
+    #[default]
+    Linear,
+    /// Like `Linear`, without the half-value offsets of the standard's
+    /// formula
+    LinearExact,
+    /// S-shaped curve through the window center, never quite clipping;
+    /// common for digital radiography
+    Sigmoid,
+}
+
+impl VoiLutFunction {
+    /// The function for a VOI LUT Function value; unknown values are
+    /// treated as `LINEAR`, the default
+    #[must_use]
+    pub fn parse(value: &str) -> Self {
+        match value.trim_matches(['\0', ' ']) {
+            "LINEAR_EXACT" => Self::LinearExact,
+            "SIGMOID" => Self::Sigmoid,
+            _ => Self::Linear,
+        }
+    }
+}
+
+impl fmt::Display for VoiLutFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Linear => "LINEAR",
+            Self::LinearExact => "LINEAR_EXACT",
+            Self::Sigmoid => "SIGMOID",
+        })
+    }
+}
+
+/// First Window Center and Width of the file, in rescaled units, with its
+/// VOI LUT Function
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VoiWindow {
+    pub center: f64,
+    pub width: f64,
+    pub function: VoiLutFunction,
+}
+
+impl fmt::Display for VoiWindow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "center={center}, width={width} ({function})",
+            center = self.center,
+            width = self.width,
+            function = self.function
+        )
+    }
+}
+
+/// Units of rescaled pixel values (Rescale Type)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RescaleType {
+    /// Hounsfield units
+    Hounsfield,
+    /// Unspecified
+    Unspecified,
+    /// Optical density
+    OpticalDensity,
+    Other(String),
+}
+
+impl RescaleType {
+    #[must_use]
+    pub fn parse(value: &str) -> Self {
+        match value.trim() {
+            "HU" => Self::Hounsfield,
+            "US" => Self::Unspecified,
+            "OD" => Self::OpticalDensity,
+            other => Self::Other(other.to_string()),
+        }
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn is_hounsfield(&self) -> bool {
+        matches!(self, Self::Hounsfield)
+    }
+
+    /// Unit suffix for rescaled values, `None` when the unit is unspecified
+    #[must_use]
+    pub fn unit(&self) -> Option<&str> {
+        match self {
+            Self::Hounsfield => Some("HU"),
+            Self::Unspecified => None,
+            Self::OpticalDensity => Some("OD"),
+            Self::Other(unit) => Some(unit),
+        }
+    }
+
+    #[must_use]
+    pub fn description(&self) -> Option<&'static str> {
+        match self {
+            Self::Hounsfield => Some("Hounsfield units"),
+            Self::Unspecified => Some("unspecified"),
+            Self::OpticalDensity => Some("optical density"),
+            Self::Other(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for RescaleType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let code = match self {
+            Self::Hounsfield => "HU",
+            Self::Unspecified => "US",
+            Self::OpticalDensity => "OD",
+            Self::Other(code) => code,
+        };
+
+        match self.description() {
+            Some(description) => write!(f, "{code} ({description})"),
+            None => write!(f, "{code}"),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct PixelAspectRatio {
     pub vertical: f64,
@@ -146,6 +493,13 @@ impl PixelAspectRatio {
     pub fn is_square(&self) -> bool {
         (self.vertical - self.horizontal).abs() < f64::EPSILON
     }
+
+    /// Aspect ratio after swapping rows and columns
+    #[inline]
+    #[must_use]
+    pub fn transposed(&self) -> Self {
+        Self::new(self.horizontal, self.vertical)
+    }
 }
 
 impl fmt::Display for PixelAspectRatio {
@@ -159,17 +513,115 @@ impl fmt::Display for PixelAspectRatio {
     }
 }
 
+/// Physical distance between pixel centers, in millimeters
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PixelSpacing {
+    /// Distance between adjacent rows (vertical)
+    pub row: f64,
+    /// Distance between adjacent columns (horizontal)
+    pub column: f64,
+}
+
+impl PixelSpacing {
+    #[must_use]
+    pub fn new(row: f64, column: f64) -> Self {
+        Self { row, column }
+    }
+}
+
+impl fmt::Display for PixelSpacing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{row}\\{column} mm",
+            row = self.row,
+            column = self.column
+        )
+    }
+}
+
+/// Patient-space direction of image rows and columns
+///
+/// Axes follow the DICOM patient coordinate system: +x towards the patient's
+/// left, +y towards posterior, +z towards head.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ImageOrientation {
+    /// Direction of increasing column index (along a row)
+    pub row: [f64; 3],
+    /// Direction of increasing row index (down a column)
+    pub column: [f64; 3],
+}
+
+impl ImageOrientation {
+    #[must_use]
+    pub fn new(row: [f64; 3], column: [f64; 3]) -> Self {
+        Self { row, column }
+    }
+
+    /// Slice normal (row x column)
+    #[must_use]
+    pub fn normal(&self) -> [f64; 3] {
+        let [a1, a2, a3] = self.row;
+        let [b1, b2, b3] = self.column;
+        [
+            a2.mul_add(b3, -(a3 * b2)),
+            a3.mul_add(b1, -(a1 * b3)),
+            a1.mul_add(b2, -(a2 * b1)),
+        ]
+    }
+
+    /// Parse Patient Orientation direction codes (e.g. `A\F`)
+    ///
+    /// Each code is one or more of L/R, A/P, H/F; combined letters such as
+    /// "AL" describe oblique directions.
+    #[must_use]
+    pub fn from_patient_orientation(value: &str) -> Option<Self> {
+        let (row, column) = value.split_once('\\')?;
+        Some(Self::new(
+            direction_from_code(row)?,
+            direction_from_code(column)?,
+        ))
+    }
+}
+
+fn direction_from_code(code: &str) -> Option<[f64; 3]> {
+    let mut direction = [0.0_f64; 3];
+
+    for letter in code.trim().chars() {
+        let (axis, sign) = match letter.to_ascii_uppercase() {
+            'L' => (0, 1.0),
+            'R' => (0, -1.0),
+            'P' => (1, 1.0),
+            'A' => (1, -1.0),
+            'H' => (2, 1.0),
+            'F' => (2, -1.0),
+            _ => return None,
+        };
+        direction[axis] += sign;
+    }
+
+    let length = direction.iter().map(|v| v * v).sum::<f64>().sqrt();
+    (length > 0.0).then(|| direction.map(|v| v / length))
+}
+
 /// Bit depth information for pixel data
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct BitDepth {
     pub allocated: u16,
     pub stored: u16,
+    /// Pixel Representation 1: samples are two's complement
+    pub signed: bool,
 }
 
 impl BitDepth {
+    /// Unsigned samples
     #[must_use]
     pub fn new(allocated: u16, stored: u16) -> Self {
-        Self { allocated, stored }
+        Self {
+            allocated,
+            stored,
+            signed: false,
+        }
     }
 
     #[inline]
@@ -192,7 +644,11 @@ impl fmt::Display for BitDepth {
             "{stored}/{allocated} bits",
             stored = self.stored,
             allocated = self.allocated
-        )
+        )?;
+        if self.signed {
+            write!(f, ", signed")?;
+        }
+        Ok(())
     }
 }
 
@@ -231,6 +687,7 @@ impl Default for PatientInfo {
 pub struct StudyInfo {
     pub accession_number: Option<String>,
     pub date: Option<String>,
+    pub time: Option<String>,
     pub description: Option<String>,
     pub modality: Option<String>,
 }
@@ -241,6 +698,7 @@ impl StudyInfo {
         Self {
             accession_number: None,
             date: None,
+            time: None,
             description: None,
             modality: None,
         }
@@ -261,11 +719,14 @@ impl Default for StudyInfo {
     }
 }
 
-/// Series information metadata
+/// Series information metadata, including the equipment it was made with
 #[derive(Debug, Clone, PartialEq)]
 pub struct SeriesInfo {
     pub description: Option<String>,
     pub slice_thickness: Option<f64>,
+    pub body_part_examined: Option<String>,
+    pub institution_name: Option<String>,
+    pub station_name: Option<String>,
 }
 
 impl SeriesInfo {
@@ -274,12 +735,19 @@ impl SeriesInfo {
         Self {
             description: None,
             slice_thickness: None,
+            body_part_examined: None,
+            institution_name: None,
+            station_name: None,
         }
     }
 
     #[must_use]
     pub fn has_info(&self) -> bool {
-        self.description.is_some() || self.slice_thickness.is_some()
+        self.description.is_some()
+            || self.slice_thickness.is_some()
+            || self.body_part_examined.is_some()
+            || self.institution_name.is_some()
+            || self.station_name.is_some()
     }
 }
 
@@ -288,3 +756,205 @@ impl Default for SeriesInfo {
         Self::new()
     }
 }
+
+/// X-ray exposure of the acquisition
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct AcquisitionInfo {
+    /// Peak kilovoltage of the X-ray generator (KVP)
+    pub kvp: Option<f64>,
+    /// Exposure Time in milliseconds
+    pub exposure_time: Option<f64>,
+}
+
+/// Projection of a radiograph, which tells related views apart
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ViewInfo {
+    /// View Position, e.g. "PA", "LL", "CC" or "MLO"
+    pub position: Option<String>,
+    /// Image Laterality, or the Laterality of the series: "R", "L" or "B"
+    pub laterality: Option<String>,
+}
+
+/// A region of an ultrasound image, from the Sequence of Ultrasound Regions
+///
+/// Ultrasound frames hold the scanner's controls, text and graphs around
+/// the image itself. Each region locates one part and has its own
+/// calibration.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UltrasoundRegion {
+    /// First column and row of the region
+    pub min: (u32, u32),
+    /// Last column and row of the region
+    pub max: (u32, u32),
+    /// Region Spatial Format: 1 for a 2D image, 2 for M-mode, 3 for a
+    /// spectral (Doppler) graph
+    pub spatial_format: u16,
+    /// Physical Delta X and Y in millimeters, when they are in centimeters
+    pub spacing: Option<PixelSpacing>,
+}
+
+impl UltrasoundRegion {
+    /// Width and height in pixels
+    #[must_use]
+    pub fn size(&self) -> (u32, u32) {
+        (
+            self.max.0.saturating_sub(self.min.0) + 1,
+            self.max.1.saturating_sub(self.min.1) + 1,
+        )
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn is_2d(&self) -> bool {
+        self.spatial_format == 1
+    }
+
+    /// The region holding the image itself: the largest 2D one
+    #[must_use]
+    pub fn imaging(regions: &[Self]) -> Option<&Self> {
+        regions
+            .iter()
+            .filter(|region| region.is_2d())
+            .max_by_key(|region| {
+                let (width, height) = region.size();
+                u64::from(width) * u64::from(height)
+            })
+    }
+}
+
+/// A text or graphic object of a Graphic Annotation Sequence, in the image
+/// itself or in a presentation state for it
+#[derive(Debug, Clone, PartialEq)]
+pub struct GraphicAnnotation {
+    /// Frames (1-based) it is shown on; all when empty
+    pub frames: Vec<u32>,
+    /// Recommended color of the object or of its graphic layer
+    pub color: Option<[u8; 3]>,
+    pub shape: GraphicShape,
+}
+
+/// Shape of a [`GraphicAnnotation`], with points as (column, row) in stored
+/// pixels, where (0, 0) is the top left corner of the first pixel
+#[derive(Debug, Clone, PartialEq)]
+pub enum GraphicShape {
+    Text {
+        text: String,
+        /// Top left corner of the text
+        position: (f64, f64),
+    },
+    Point((f64, f64)),
+    /// POLYLINE and INTERPOLATED graphics; closed when the last point is the
+    /// first
+    Polyline(Vec<(f64, f64)>),
+    Circle {
+        center: (f64, f64),
+        radius: f64,
+    },
+    Ellipse {
+        /// End points of the major axis
+        major: [(f64, f64); 2],
+        /// End points of the minor axis
+        minor: [(f64, f64); 2],
+    },
+}
+
+impl GraphicShape {
+    /// Every point mapped by `f`, e.g. to crop or scale the image
+    #[must_use]
+    pub fn map_points(&self, f: impl Fn((f64, f64)) -> (f64, f64)) -> Self {
+        match self {
+            Self::Text { text, position } => Self::Text {
+                text: text.clone(),
+                position: f(*position),
+            },
+            Self::Point(point) => Self::Point(f(*point)),
+            Self::Polyline(points) => Self::Polyline(points.iter().copied().map(f).collect()),
+            Self::Circle { center, radius } => {
+                let (edge, center) = (f((center.0 + radius, center.1)), f(*center));
+                Self::Circle {
+                    center,
+                    radius: (edge.0 - center.0).hypot(edge.1 - center.1),
+                }
+            }
+            Self::Ellipse { major, minor } => Self::Ellipse {
+                major: major.map(&f),
+                minor: minor.map(&f),
+            },
+        }
+    }
+}
+
+/// Bitmap of an overlay plane (Overlay group 60xx), e.g. markers or text
+/// the modality drew over the image
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OverlayPlane {
+    /// Frame it is shown on (0-based)
+    pub frame: u32,
+    /// Column and row of its top left pixel in the image; may be negative
+    pub origin: (i32, i32),
+    pub width: u32,
+    pub height: u32,
+    /// One value per overlay pixel, row by row; set where it is drawn
+    pub bits: Vec<bool>,
+}
+
+impl OverlayPlane {
+    /// Whether the overlay covers image pixel (x, y)
+    #[must_use]
+    pub fn is_set(&self, x: i64, y: i64) -> bool {
+        let (x, y) = (x - i64::from(self.origin.0), y - i64::from(self.origin.1));
+        if x < 0 || y < 0 || x >= i64::from(self.width) || y >= i64::from(self.height) {
+            return false;
+        }
+        self.bits[y as usize * self.width as usize + x as usize]
+    }
+
+    /// Image pixels (x, y) the overlay covers
+    pub fn set_pixels(&self) -> impl Iterator<Item = (i64, i64)> + '_ {
+        let width = self.width.max(1) as usize;
+        self.bits
+            .iter()
+            .enumerate()
+            .filter(|&(_, &set)| set)
+            .map(move |(index, _)| {
+                (
+                    i64::from(self.origin.0) + (index % width) as i64,
+                    i64::from(self.origin.1) + (index / width) as i64,
+                )
+            })
+    }
+
+    /// The overlay of an image with every `step`th pixel kept in each
+    /// direction, starting with the first
+    #[must_use]
+    pub fn decimated(&self, step: u16) -> Self {
+        let step = i64::from(step.max(1));
+        // Kept image pixels the overlay covers, in decimated coordinates
+        let first = |origin: i32| (i64::from(origin) + step - 1).div_euclid(step);
+        let last =
+            |origin: i32, size: u32| (i64::from(origin) + i64::from(size) - 1).div_euclid(step);
+        let (x0, x1) = (first(self.origin.0), last(self.origin.0, self.width));
+        let (y0, y1) = (first(self.origin.1), last(self.origin.1, self.height));
+
+        let bits = (y0..=y1)
+            .flat_map(|y| (x0..=x1).map(move |x| (x, y)))
+            .map(|(x, y)| self.is_set(x * step, y * step))
+            .collect();
+        Self {
+            frame: self.frame,
+            origin: (x0 as i32, y0 as i32),
+            width: u32::try_from(x1 - x0 + 1).unwrap_or(0),
+            height: u32::try_from(y1 - y0 + 1).unwrap_or(0),
+            bits,
+        }
+    }
+
+    /// The overlay of the image cropped to start at (left, top)
+    #[must_use]
+    pub fn shifted(&self, left: i32, top: i32) -> Self {
+        Self {
+            origin: (self.origin.0 - left, self.origin.1 - top),
+            ..self.clone()
+        }
+    }
+}
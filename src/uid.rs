@@ -0,0 +1,143 @@
+//! UID lookups for `dcmv uid`
+//!
+//! SOP classes are found in the standard dictionary that names them in the
+//! metadata, and transfer syntaxes in the registry `dcmv codecs` lists. A
+//! name is matched against the keywords of the SOP classes, ignoring spaces
+//! and punctuation, and against the names of the transfer syntaxes.
+
+use anyhow::{Result, bail};
+use dicom::core::dictionary::{UidDictionary, UidDictionaryEntry};
+use dicom::encoding::TransferSyntaxIndex;
+use dicom::transfer_syntax::TransferSyntaxRegistry;
+use dicom_dictionary_std::sop_class::StandardSopClassDictionary;
+
+/// Root of the UIDs defined by the DICOM standard
+const DICOM_ROOT: &str = "1.2.840.10008.";
+
+/// A UID with its name
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UidInfo {
+    pub uid: String,
+    pub name: String,
+    /// "SOP Class" or "Transfer Syntax"
+    pub kind: &'static str,
+    pub retired: bool,
+}
+
+/// The UIDs matching `query`: a UID, or the name or keyword of one
+#[must_use]
+pub fn lookup(query: &str) -> Vec<UidInfo> {
+    let query = query.trim().trim_end_matches('\0');
+    if is_uid(query) {
+        return by_uid(query).into_iter().collect();
+    }
+
+    let keyword: String = query.chars().filter(char::is_ascii_alphanumeric).collect();
+    let mut found: Vec<UidInfo> = StandardSopClassDictionary
+        .by_keyword(&keyword)
+        .map(sop_class)
+        .into_iter()
+        .collect();
+
+    let needle = query.to_ascii_lowercase();
+    found.extend(
+        TransferSyntaxRegistry
+            .iter()
+            .filter(|ts| ts.name().to_ascii_lowercase().contains(&needle))
+            .map(|ts| transfer_syntax(ts.uid(), ts.name())),
+    );
+    // Numerically by UID component, as `dcmv codecs` lists them
+    found.sort_by_cached_key(|info| {
+        info.uid
+            .split('.')
+            .map(|part| part.parse::<u32>().unwrap_or(0))
+            .collect::<Vec<_>>()
+    });
+    found.dedup_by(|a, b| a.uid == b.uid);
+    found
+}
+
+/// Print the matches of `query` for `dcmv uid`
+///
+/// # Errors
+///
+/// Returns an error if nothing matches
+pub fn print_lookup(query: &str) -> Result<()> {
+    let found = lookup(query);
+    for info in &found {
+        println!(
+            "{:<30} {:<16} {}{}",
+            info.uid,
+            info.kind,
+            info.name,
+            if info.retired { " (retired)" } else { "" }
+        );
+    }
+    if !found.is_empty() {
+        return Ok(());
+    }
+
+    let query = query.trim();
+    match query {
+        uid if uid.starts_with(DICOM_ROOT) => {
+            bail!("{uid} is a DICOM UID that is not in the dictionaries of this build")
+        }
+        uid if uid.starts_with("2.25.") && is_uid(uid) => {
+            println!("{uid:<30} {:<16} derived from a UUID (2.25 root)", "UUID");
+            Ok(())
+        }
+        uid if is_uid(uid) => bail!("{uid} is not a UID defined by DICOM"),
+        name => bail!("No SOP class keyword or transfer syntax name matches `{name}`"),
+    }
+}
+
+fn by_uid(uid: &str) -> Option<UidInfo> {
+    StandardSopClassDictionary
+        .by_uid(uid)
+        .map(sop_class)
+        .or_else(|| {
+            TransferSyntaxRegistry
+                .get(uid)
+                .map(|ts| transfer_syntax(ts.uid(), ts.name()))
+        })
+}
+
+fn sop_class(entry: &impl UidDictionaryEntry) -> UidInfo {
+    UidInfo {
+        uid: entry.uid().to_string(),
+        name: entry.name().to_string(),
+        kind: "SOP Class",
+        retired: entry.is_retired(),
+    }
+}
+
+fn transfer_syntax(uid: &str, name: &str) -> UidInfo {
+    UidInfo {
+        uid: uid.to_string(),
+        name: name.to_string(),
+        kind: "Transfer Syntax",
+        retired: false,
+    }
+}
+
+fn is_uid(text: &str) -> bool {
+    !text.is_empty() && text.chars().all(|c| c.is_ascii_digit() || c == '.')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lookup_by_uid_and_by_name() {
+        let ct = lookup("1.2.840.10008.5.1.4.1.1.2");
+        assert_eq!(ct.len(), 1);
+        assert_eq!(ct[0].name, "CT Image Storage");
+
+        assert_eq!(lookup("CT Image Storage")[0].uid, ct[0].uid);
+        let big_endian = lookup("explicit vr big endian");
+        assert_eq!(big_endian[0].uid, "1.2.840.10008.1.2.2");
+        assert_eq!(big_endian[0].kind, "Transfer Syntax");
+        assert!(lookup("1.2.3.4").is_empty());
+    }
+}